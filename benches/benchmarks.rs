@@ -0,0 +1,210 @@
+/*
+ * Benchmarks for assignment, serialization and merging
+ *
+ * Numbers used to decide on broadcast coalescing and codec changes.
+ * Run with `cargo bench`, from the project root so the hall_request_assigner
+ * executable is found.
+ *
+ * Benchmarks:
+ *  - assignment:     Latency of the hall_request_assigner subprocess and of the in-process assigner.
+ *  - serialization:  ElevatorData encode/decode as JSON (current wire format) and bincode.
+ *  - merge:          Coordinator throughput for packages that must be merged.
+ *
+ * All groups are parameterized over fleets of 3 to 10 elevators.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_channel as cbc;
+use network_rust::udpnet::peers::PeerUpdate;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use project::coordinator::assigner::assign_hall_requests;
+use project::coordinator::coordinator::{execute_hall_request_assigner, Event};
+use project::shared::{Behaviour, ConsensusMessage, ControlCommand, Direction, FsmView, OperatingMode, Snapshot, Terminate};
+use project::{Coordinator, CoordinatorBuilder, ElevatorData, ElevatorState};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const N_FLOORS: u8 = 4;
+const FLEET_SIZES: [usize; 4] = [3, 5, 8, 10];
+
+/***************************************/
+/*              Fixtures               */
+/***************************************/
+// A busy fleet with elevators spread over the floors and a mix of hall and cab requests
+fn fleet(n_elevators: usize) -> ElevatorData {
+    let mut elevator_data = ElevatorData::new(N_FLOORS);
+
+    for floor in 0..N_FLOORS as usize {
+        elevator_data.hall_requests[floor] = vec![floor % 2 == 0, floor % 3 == 0];
+    }
+
+    for index in 0..n_elevators {
+        let mut state = ElevatorState::new(N_FLOORS);
+        state.floor = (index % N_FLOORS as usize) as u8;
+        if index % 2 == 1 {
+            state.behaviour = Behaviour::Moving;
+            state.direction = if state.floor == N_FLOORS - 1 { Direction::Down } else { Direction::Up };
+        }
+        state.cab_requests[(index + 1) % N_FLOORS as usize] = true;
+        elevator_data.states.insert(format!("10.100.23.{}:19735", index), state);
+    }
+
+    elevator_data
+}
+
+struct CoordinatorFixture {
+    coordinator: Coordinator,
+
+    // Kept alive so the coordinator never sends on a disconnected channel
+    _hw_button_light_rx: cbc::Receiver<(u8, u8, bool)>,
+    _fsm_hall_requests_rx: cbc::Receiver<Vec<Vec<bool>>>,
+    _fsm_cab_request_rx: cbc::Receiver<u8>,
+    _fsm_parking_floor_rx: cbc::Receiver<Option<u8>>,
+    _fsm_operating_mode_rx: cbc::Receiver<OperatingMode>,
+    _fsm_door_command_rx: cbc::Receiver<bool>,
+    _fsm_alarm_clear_rx: cbc::Receiver<()>,
+    _fsm_halt_rx: cbc::Receiver<bool>,
+    _fsm_inspect_rx: cbc::Receiver<cbc::Sender<FsmView>>,
+    _net_data_send_rx: cbc::Receiver<ElevatorData>,
+}
+
+fn coordinator(elevator_data: ElevatorData, local_id: String) -> CoordinatorFixture {
+    let (hw_button_light_tx, hw_button_light_rx) = cbc::unbounded::<(u8, u8, bool)>();
+    let (_hw_request_tx, hw_request_rx) = cbc::unbounded::<(u8, u8)>();
+    let (fsm_hall_requests_tx, fsm_hall_requests_rx) = cbc::unbounded::<Vec<Vec<bool>>>();
+    let (fsm_cab_request_tx, fsm_cab_request_rx) = cbc::unbounded::<u8>();
+    let (fsm_parking_floor_tx, fsm_parking_floor_rx) = cbc::unbounded::<Option<u8>>();
+    let (fsm_operating_mode_tx, fsm_operating_mode_rx) = cbc::unbounded::<OperatingMode>();
+    let (fsm_door_command_tx, fsm_door_command_rx) = cbc::unbounded::<bool>();
+    let (fsm_alarm_clear_tx, fsm_alarm_clear_rx) = cbc::unbounded::<()>();
+    let (fsm_halt_tx, fsm_halt_rx) = cbc::unbounded::<bool>();
+    let (fsm_inspect_tx, fsm_inspect_rx) = cbc::unbounded::<cbc::Sender<FsmView>>();
+    let (_fsm_state_tx, fsm_state_rx) = cbc::unbounded::<ElevatorState>();
+    let (_fsm_order_complete_tx, fsm_order_complete_rx) = cbc::unbounded::<(u8, u8)>();
+    let (net_data_send_tx, net_data_send_rx) = cbc::unbounded::<ElevatorData>();
+    let (_net_data_recv_tx, net_data_recv_rx) = cbc::unbounded::<ElevatorData>();
+    let (_net_sync_recv_tx, net_sync_recv_rx) = cbc::unbounded::<ElevatorData>();
+    let (_net_peer_update_tx, net_peer_update_rx) = cbc::unbounded::<PeerUpdate>();
+    let (net_consensus_send_tx, _net_consensus_send_rx) = cbc::unbounded::<(String, ConsensusMessage)>();
+    let (_net_consensus_recv_tx, net_consensus_recv_rx) = cbc::unbounded::<(String, ConsensusMessage)>();
+    let (_coordinator_command_tx, coordinator_command_rx) = cbc::unbounded::<ControlCommand>();
+    let (_coordinator_query_tx, coordinator_query_rx) = cbc::unbounded::<cbc::Sender<Snapshot>>();
+    let (_coordinator_terminate_tx, coordinator_terminate_rx) = cbc::unbounded::<Terminate>();
+
+    CoordinatorFixture {
+        coordinator: CoordinatorBuilder::new(elevator_data, local_id, N_FLOORS)
+            .hw_button_light_tx(hw_button_light_tx)
+            .hw_request_rx(hw_request_rx)
+            .fsm_hall_requests_tx(fsm_hall_requests_tx)
+            .fsm_cab_request_tx(fsm_cab_request_tx)
+            .fsm_parking_floor_tx(fsm_parking_floor_tx)
+            .fsm_operating_mode_tx(fsm_operating_mode_tx)
+            .fsm_door_command_tx(fsm_door_command_tx)
+            .fsm_alarm_clear_tx(fsm_alarm_clear_tx)
+            .fsm_halt_tx(fsm_halt_tx)
+            .fsm_inspect_tx(fsm_inspect_tx)
+            .fsm_state_rx(fsm_state_rx)
+            .fsm_order_complete_rx(fsm_order_complete_rx)
+            .net_data_send_tx(net_data_send_tx)
+            .net_data_recv_rx(net_data_recv_rx)
+            .net_sync_recv_rx(net_sync_recv_rx)
+            .net_peer_update_rx(net_peer_update_rx)
+            .net_consensus_send_tx(net_consensus_send_tx)
+            .net_consensus_recv_rx(net_consensus_recv_rx)
+            .net_peer_version_rx(cbc::never())
+            .net_quality_rx(cbc::never())
+            .coordinator_command_rx(coordinator_command_rx)
+            .coordinator_query_rx(coordinator_query_rx)
+            .coordinator_terminate_rx(coordinator_terminate_rx)
+            .build()
+            .unwrap(),
+        _hw_button_light_rx: hw_button_light_rx,
+        _fsm_hall_requests_rx: fsm_hall_requests_rx,
+        _fsm_cab_request_rx: fsm_cab_request_rx,
+        _fsm_parking_floor_rx: fsm_parking_floor_rx,
+        _fsm_operating_mode_rx: fsm_operating_mode_rx,
+        _fsm_door_command_rx: fsm_door_command_rx,
+        _fsm_alarm_clear_rx: fsm_alarm_clear_rx,
+        _fsm_halt_rx: fsm_halt_rx,
+        _fsm_inspect_rx: fsm_inspect_rx,
+        _net_data_send_rx: net_data_send_rx,
+    }
+}
+
+/***************************************/
+/*             Benchmarks              */
+/***************************************/
+fn bench_assignment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("assignment");
+    group.sample_size(20);
+
+    for n_elevators in FLEET_SIZES {
+        let elevator_data = fleet(n_elevators);
+        group.bench_with_input(BenchmarkId::new("subprocess", n_elevators), &elevator_data, |b, data| {
+            b.iter(|| execute_hall_request_assigner(black_box(data)).expect("hall_request_assigner failed"))
+        });
+        group.bench_with_input(BenchmarkId::new("native", n_elevators), &elevator_data, |b, data| {
+            b.iter(|| assign_hall_requests(black_box(data)).expect("Native assigner failed"))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization");
+
+    for n_elevators in FLEET_SIZES {
+        let elevator_data = fleet(n_elevators);
+        let json = serde_json::to_vec(&elevator_data).unwrap();
+        let binary = bincode::serialize(&elevator_data).unwrap();
+
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        group.bench_with_input(BenchmarkId::new("json_encode", n_elevators), &elevator_data, |b, data| {
+            b.iter(|| serde_json::to_vec(black_box(data)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("json_decode", n_elevators), &json, |b, bytes| {
+            b.iter(|| serde_json::from_slice::<ElevatorData>(black_box(bytes)).unwrap())
+        });
+
+        group.throughput(Throughput::Bytes(binary.len() as u64));
+        group.bench_with_input(BenchmarkId::new("bincode_encode", n_elevators), &elevator_data, |b, data| {
+            b.iter(|| bincode::serialize(black_box(data)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("bincode_decode", n_elevators), &binary, |b, bytes| {
+            b.iter(|| bincode::deserialize::<ElevatorData>(black_box(bytes)).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge");
+    group.throughput(Throughput::Elements(1));
+
+    for n_elevators in FLEET_SIZES {
+        // The package is missing the local elevator, which always yields a merge
+        let local_id = "local".to_string();
+        let package = fleet(n_elevators);
+        let mut elevator_data = fleet(n_elevators);
+        elevator_data.states.insert(local_id.clone(), ElevatorState::new(N_FLOORS));
+        let mut fixture = coordinator(elevator_data, local_id);
+
+        group.bench_with_input(BenchmarkId::new("merge", n_elevators), &package, |b, package| {
+            b.iter(|| fixture.coordinator.handle_event(Event::NewPackage(black_box(package.clone()))))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_assignment, bench_serialization, bench_merge);
+criterion_main!(benches);
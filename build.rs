@@ -0,0 +1,34 @@
+/**
+ * Build script.
+ *
+ * With the `grpc` feature, generates the server of the `ElevatorControl` service of proto/elevator.proto.
+ * The service is described here and the messages in src/grpc/proto.rs, so protoc is not needed.
+ */
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route_name: &str, input_type: &str, output_type: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route_name)
+                .input_type(format!("super::{}", input_type))
+                .output_type(format!("super::{}", output_type))
+                .codec_path("tonic::codec::ProstCodec")
+        };
+
+        let service = Service::builder()
+            .name("ElevatorControl")
+            .package("elevator")
+            .method(method("get_fleet_state", "GetFleetState", "FleetStateRequest", "FleetState").build())
+            .method(method("stream_events", "StreamEvents", "StreamEventsRequest", "Event").server_streaming().build())
+            .method(method("place_call", "PlaceCall", "PlaceCallRequest", "PlaceCallReply").build())
+            .method(method("set_mode", "SetMode", "SetModeRequest", "SetModeReply").build())
+            .build();
+
+        Builder::new().build_client(false).compile(&[service]);
+    }
+}
@@ -0,0 +1,371 @@
+/**
+ * Alerts on faults that need someone on site, posted to a webhook or passed to a command.
+ *
+ * Every `poll_interval` milliseconds the snapshot of the coordinator is compared with the previous one, and the
+ * faults found in between are alerted as JSON:
+ *
+ * - `{"event": "obstructionAlarm", "elevator": "<id>", "floor": 2}` when the door has timed out too often in a row.
+ * - `{"event": "obstructionAlarmCleared", "elevator": "<id>", "floor": 2}` when the alarm is cleared on the console.
+ * - `{"event": "elevatorError", "elevator": "<id>", "floor": 2}` when the elevator enters the error state, as when
+ *   the motor or the door times out, and `elevatorErrorCleared` when it leaves it.
+ * - `{"event": "peerLost", "elevator": "<id>", "peer": "<id>"}` when a peer is lost.
+ *
+ * The modules that stop with an error are alerted too, as told by the supervisor:
+ *
+ * - `{"event": "hardwareLost", "elevator": "<id>", "error": "..."}` when the driver stops, as when the connection
+ *   to the hardware is lost.
+ * - `{"event": "moduleFailed", "elevator": "<id>", "module": "FSM", "error": "..."}` when another module stops.
+ *
+ * Each elevator alerts its own faults, so every fault is alerted once however many elevators see it, except a lost
+ * peer, which every elevator left alerts. The alerts are posted to the webhook, or, with a `command`, the command is
+ * run by `sh -c` with the alert in the environment variable `ALERT`. An alert is sent once the webhook answers 2xx or
+ * the command exits with 0, and is otherwise retried up to `retries` times, `retry_delay` milliseconds apart at first
+ * and twice as long after every retry. At most `max_alerts` alerts are sent within `rate_window` seconds, so a
+ * flapping fault does not flood the phones, and the next alert sent counts those suppressed in between as
+ * `suppressed`.
+ *
+ * The alerts are sent by a thread of their own, so a slow webhook does not hold up the next comparison. Once asked
+ * to terminate, the alerts left are tried once each, so the failure that ends the program is still alerted.
+ *
+ * # Fields
+ * - `hook`:                    The webhook or the command the alerts are sent to.
+ * - `poll_interval`:           Time between comparisons of the snapshot.
+ * - `retries`:                 Retries of an alert that failed to send.
+ * - `retry_delay`:             Time before the first retry, doubled after every retry.
+ * - `rate_limit`:              The alerts sent within the rate window, and those suppressed beyond it.
+ * - `local_id`:                The ID of the local elevator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ * - `alert_failure_rx`:        Receives the modules that stopped with an error, from the supervisor.
+ * - `alert_terminate_rx`:      Receives the request to terminate, acknowledged once the alerts left are tried.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::AlertConfig;
+use crate::shared::{fleet_events, FleetEvent, ModuleError, Snapshot, Terminate};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+const POST_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(3600);
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Alert {
+    hook: Hook,
+    poll_interval: Duration,
+    retries: u32,
+    retry_delay: Duration,
+    rate_limit: RateLimit,
+    local_id: String,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    alert_failure_rx: cbc::Receiver<(&'static str, ModuleError)>,
+    alert_terminate_rx: cbc::Receiver<Terminate>,
+}
+
+impl Alert {
+    pub fn new(
+        alert_config: &AlertConfig,
+        local_id: String,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+        alert_failure_rx: cbc::Receiver<(&'static str, ModuleError)>,
+        alert_terminate_rx: cbc::Receiver<Terminate>,
+    ) -> Result<Alert, String> {
+        let hook = match alert_config.command.is_empty() {
+            true => {
+                let (address, host, path) = parse_webhook(&alert_config.webhook)?;
+                Hook::Webhook { address, host, path }
+            }
+            false => Hook::Command(alert_config.command.clone()),
+        };
+        let rate_window = match alert_config.rate_window {
+            0 => DEFAULT_RATE_WINDOW,
+            seconds => Duration::from_secs(seconds),
+        };
+        Ok(Alert {
+            hook,
+            poll_interval: Duration::from_millis(alert_config.poll_interval),
+            retries: alert_config.retries,
+            retry_delay: match alert_config.retry_delay {
+                0 => DEFAULT_RETRY_DELAY,
+                milliseconds => Duration::from_millis(milliseconds),
+            },
+            rate_limit: RateLimit::new(alert_config.max_alerts, rate_window),
+            local_id,
+            coordinator_query_tx,
+            alert_failure_rx,
+            alert_terminate_rx,
+        })
+    }
+
+    pub fn run(mut self) {
+        info!("Sending alerts to {}", self.hook);
+        let (delivery_tx, delivery_rx) = cbc::unbounded::<Value>();
+        let (stop_tx, stop_rx) = cbc::bounded::<()>(0);
+        let (hook, retries, retry_delay) = (self.hook.clone(), self.retries, self.retry_delay);
+        let delivery_thread = std::thread::Builder::new().name("alert_delivery".into());
+        let delivery_thread = delivery_thread.spawn(move || deliver(hook, retries, retry_delay, delivery_rx, stop_rx)).unwrap();
+
+        let poll_ticker = cbc::tick(self.poll_interval);
+        let mut previous: Option<Snapshot> = None;
+        loop {
+            let alerts = cbc::select! {
+                recv(poll_ticker) -> _ => {
+                    let Some(snapshot) = self.query() else { continue };
+                    let alerts = alerts(&self.local_id, previous.as_ref(), &snapshot);
+                    previous = Some(snapshot);
+                    alerts
+                }
+                recv(self.alert_failure_rx) -> failure => {
+                    match failure {
+                        Ok((module, e)) => vec![failure_alert(&self.local_id, module, &e)],
+                        Err(_) => {
+                            self.alert_failure_rx = cbc::never();
+                            Vec::new()
+                        }
+                    }
+                }
+                recv(self.alert_terminate_rx) -> done => {
+                    // The failure that ends the program may arrive together with the request to terminate
+                    let failures = self.alert_failure_rx.try_iter().collect::<Vec<(&'static str, ModuleError)>>();
+                    for (module, e) in failures {
+                        self.send(failure_alert(&self.local_id, module, &e), &delivery_tx);
+                    }
+                    drop((stop_tx, delivery_tx));
+                    let _ = delivery_thread.join();
+                    if let Ok(done) = done {
+                        let _ = done.send(());
+                    }
+                    return;
+                }
+            };
+            for alert in alerts {
+                self.send(alert, &delivery_tx);
+            }
+        }
+    }
+
+    // Queues the alert for delivery, unless the rate limit suppresses it
+    fn send(&mut self, alert: Value, delivery_tx: &cbc::Sender<Value>) {
+        match self.rate_limit.admit(alert.clone(), Instant::now()) {
+            Some(alert) => {
+                let _ = delivery_tx.send(alert);
+            }
+            None => warn!("Alert {} suppressed by the rate limit", alert),
+        }
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
+
+// Admits up to `max_alerts` alerts within any `window`, and counts those suppressed beyond it. Unlimited with 0.
+pub struct RateLimit {
+    max_alerts: usize,
+    window: Duration,
+    sent: VecDeque<Instant>,
+    suppressed: u32,
+}
+
+impl RateLimit {
+    pub fn new(max_alerts: usize, window: Duration) -> RateLimit {
+        RateLimit { max_alerts, window, sent: VecDeque::new(), suppressed: 0 }
+    }
+
+    // The alert to send, with the count of those suppressed since the last one sent, or `None` if it is suppressed
+    pub fn admit(&mut self, mut alert: Value, now: Instant) -> Option<Value> {
+        if self.max_alerts == 0 {
+            return Some(alert);
+        }
+        while self.sent.front().is_some_and(|sent| now.saturating_duration_since(*sent) >= self.window) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.max_alerts {
+            self.suppressed += 1;
+            return None;
+        }
+
+        self.sent.push_back(now);
+        if self.suppressed > 0 {
+            alert["suppressed"] = json!(self.suppressed);
+            self.suppressed = 0;
+        }
+        Some(alert)
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Splits an `http://host[:port]/path` webhook into the address to connect to, the host and the path
+pub fn parse_webhook(webhook: &str) -> Result<(String, String, String), String> {
+    let Some(rest) = webhook.strip_prefix("http://") else {
+        return Err(format!("The webhook '{}' is not an http:// URL", webhook));
+    };
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(format!("The webhook '{}' has no host", webhook));
+    }
+
+    let address = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    Ok((address, host.to_string(), path.to_string()))
+}
+
+/***************************************/
+/*       Local data structures         */
+/***************************************/
+// Where the alerts are sent
+#[derive(Clone)]
+enum Hook {
+    Webhook { address: String, host: String, path: String },
+    Command(String),
+}
+
+impl Hook {
+    // Sends the alert, failing unless the webhook answers 2xx or the command exits with 0
+    fn fire(&self, alert: &Value) -> io::Result<()> {
+        match self {
+            Hook::Webhook { address, host, path } => post(address, host, path, alert),
+            Hook::Command(command) => {
+                let status = Command::new("sh").arg("-c").arg(command).env("ALERT", alert.to_string()).status()?;
+                match status.success() {
+                    true => Ok(()),
+                    false => Err(io::Error::other(format!("The command exited with {}", status))),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Hook::Webhook { host, path, .. } => write!(f, "http://{}{}", host, path),
+            Hook::Command(command) => write!(f, "the command '{}'", command),
+        }
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The faults of the local elevator and the peers lost between two snapshots
+fn alerts(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot) -> Vec<Value> {
+    fleet_events(local_id, previous, snapshot)
+        .into_iter()
+        .filter_map(|event| match event {
+            FleetEvent::ObstructionAlarm(id, floor, active) if id == local_id => {
+                let event = if active { "obstructionAlarm" } else { "obstructionAlarmCleared" };
+                Some(json!({ "event": event, "elevator": id, "floor": floor }))
+            }
+            FleetEvent::Fault(floor, failed) => {
+                let event = if failed { "elevatorError" } else { "elevatorErrorCleared" };
+                Some(json!({ "event": event, "elevator": local_id, "floor": floor }))
+            }
+            FleetEvent::PeerLost(peer) => Some(json!({ "event": "peerLost", "elevator": local_id, "peer": peer })),
+            _ => None,
+        })
+        .collect()
+}
+
+// The alert of a module that stopped with an error. The driver only stops on its own when the hardware is lost.
+fn failure_alert(local_id: &str, module: &str, e: &ModuleError) -> Value {
+    match module {
+        "driver" => json!({ "event": "hardwareLost", "elevator": local_id, "error": e.to_string() }),
+        _ => json!({ "event": "moduleFailed", "elevator": local_id, "module": module, "error": e.to_string() }),
+    }
+}
+
+// Sends the alerts, retrying each that fails. Once `stop_rx` is disconnected, the alerts left are tried once each.
+fn deliver(hook: Hook, retries: u32, retry_delay: Duration, alert_rx: cbc::Receiver<Value>, stop_rx: cbc::Receiver<()>) {
+    for alert in alert_rx {
+        let mut delay = retry_delay;
+        for attempt in 0..=retries {
+            match hook.fire(&alert) {
+                Ok(()) => {
+                    info!("Alert sent: {}", alert);
+                    break;
+                }
+                Err(e) if attempt == retries => error!("Failed to send alert {}, giving up: {}", alert, e),
+                Err(e) => warn!("Failed to send alert {}, retrying in {:?}: {}", alert, delay, e),
+            }
+            if attempt == retries || stop_rx.recv_timeout(delay) != Err(cbc::RecvTimeoutError::Timeout) {
+                break;
+            }
+            delay *= 2;
+        }
+    }
+}
+
+fn post(address: &str, host: &str, path: &str, alert: &Value) -> io::Result<()> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The webhook host was not found"))?;
+    let mut stream = TcpStream::connect_timeout(&address, POST_TIMEOUT)?;
+    stream.set_read_timeout(Some(POST_TIMEOUT))?;
+    stream.set_write_timeout(Some(POST_TIMEOUT))?;
+
+    let body = alert.to_string();
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    )?;
+
+    // Only the status line of the response matters
+    let mut response = [0u8; 64];
+    let length = stream.read(&mut response)?;
+    let status_line = String::from_utf8_lossy(&response[..length]);
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        status => Err(io::Error::other(format!("The webhook answered {:?}", status))),
+    }
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::Alert;
+    use crate::shared::Snapshot;
+    use serde_json::Value;
+    use std::io;
+
+    impl Alert {
+        pub fn test_post(&self, alert: &Value) -> io::Result<()> {
+            self.hook.fire(alert)
+        }
+    }
+
+    pub fn test_alerts(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot) -> Vec<Value> {
+        super::alerts(local_id, previous, snapshot)
+    }
+}
@@ -0,0 +1,192 @@
+/*
+ * Unit tests for the alert webhook
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * The webhook is a listener on a local port, and the command appends the alerts to a file.
+ *
+ * Tests:
+ *  - test_alert_parse_webhook
+ *  - test_alert_obstruction_alarm
+ *  - test_alert_post
+ *  - test_alert_faults
+ *  - test_alert_rate_limit
+ *  - test_alert_command_retried
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod alert_tests {
+    use crossbeam_channel::unbounded;
+    use serde_json::json;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread::spawn;
+    use crate::alert::alert::{parse_webhook, RateLimit};
+    use crate::alert::alert::testing::test_alerts;
+    use crate::alert::Alert;
+    use crate::config::AlertConfig;
+    use crate::shared::{Behaviour, ModuleError, NetworkStatistics, Snapshot, Uptime};
+    use std::time::{Duration, Instant};
+    use crate::{ElevatorData, ElevatorState};
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string(), "b".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    #[test]
+    fn test_alert_parse_webhook() {
+        // Act / Assert
+        assert_eq!(
+            parse_webhook("http://alerts.local:9000/hooks/elevator"),
+            Ok(("alerts.local:9000".to_string(), "alerts.local:9000".to_string(), "/hooks/elevator".to_string()))
+        );
+        assert_eq!(parse_webhook("http://alerts.local"), Ok(("alerts.local:80".to_string(), "alerts.local".to_string(), "/".to_string())));
+        assert!(parse_webhook("https://alerts.local/hooks").is_err());
+        assert!(parse_webhook("http:///hooks").is_err());
+    }
+
+    #[test]
+    fn test_alert_obstruction_alarm() {
+        // Arrange
+        let previous = snapshot();
+        let mut raised = snapshot();
+        raised.elevator_data.states.get_mut("a").unwrap().obstruction_alarm = true;
+        raised.elevator_data.states.get_mut("a").unwrap().floor = 2;
+        raised.elevator_data.states.get_mut("b").unwrap().obstruction_alarm = true;
+
+        // Act
+        let first = test_alerts("a", None, &raised);
+        let on_raise = test_alerts("a", Some(&previous), &raised);
+        let on_clear = test_alerts("a", Some(&raised), &previous);
+
+        // Assert
+        // The alarm of the peer is left for the peer to post
+        assert!(first.is_empty());
+        assert_eq!(on_raise, vec![json!({ "event": "obstructionAlarm", "elevator": "a", "floor": 2 })]);
+        assert_eq!(on_clear, vec![json!({ "event": "obstructionAlarmCleared", "elevator": "a", "floor": 0 })]);
+    }
+
+    #[test]
+    fn test_alert_post() {
+        // Arrange
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let webhook = spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0u8; 4096];
+            let length = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+            String::from_utf8_lossy(&request[..length]).to_string()
+        });
+        let (query_tx, _query_rx) = unbounded();
+        let alert_config = AlertConfig {
+            enabled: true,
+            webhook: format!("http://127.0.0.1:{}/hooks", port),
+            poll_interval: 500,
+            ..Default::default()
+        };
+        let alert = Alert::new(&alert_config, "a".to_string(), query_tx, unbounded().1, unbounded().1).unwrap();
+
+        // Act
+        let result = alert.test_post(&json!({ "event": "obstructionAlarm", "elevator": "a", "floor": 2 }));
+        let request = webhook.join().unwrap();
+
+        // Assert
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(request.starts_with("POST /hooks HTTP/1.1\r\n"), "{}", request);
+        assert!(request.ends_with(r#"{"elevator":"a","event":"obstructionAlarm","floor":2}"#), "{}", request);
+    }
+
+    #[test]
+    fn test_alert_faults() {
+        // Arrange
+        let previous = snapshot();
+        let mut failed = snapshot();
+        failed.elevator_data.states.get_mut("a").unwrap().behaviour = Behaviour::Error;
+        failed.elevator_data.states.get_mut("a").unwrap().floor = 3;
+        failed.peers = vec!["a".to_string()];
+
+        // Act
+        let on_failure = test_alerts("a", Some(&previous), &failed);
+        let on_recovery = test_alerts("a", Some(&failed), &previous);
+
+        // Assert
+        // The error of the local elevator and the lost peer are alerted, and the recovery once the error is left
+        assert_eq!(
+            on_failure,
+            vec![
+                json!({ "event": "elevatorError", "elevator": "a", "floor": 3 }),
+                json!({ "event": "peerLost", "elevator": "a", "peer": "b" }),
+            ]
+        );
+        assert_eq!(on_recovery, vec![json!({ "event": "elevatorErrorCleared", "elevator": "a", "floor": 0 })]);
+    }
+
+    #[test]
+    fn test_alert_rate_limit() {
+        // Arrange
+        let mut rate_limit = RateLimit::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        let alert = json!({ "event": "peerLost", "elevator": "a", "peer": "b" });
+
+        // Act
+        let within_window = (0..4).map(|_| rate_limit.admit(alert.clone(), start)).collect::<Vec<_>>();
+        let after_window = rate_limit.admit(alert.clone(), start + Duration::from_secs(60));
+
+        // Assert
+        // The alerts beyond the limit are suppressed, and counted in the next alert sent
+        assert_eq!(within_window.iter().filter(|alert| alert.is_some()).count(), 2);
+        assert_eq!(after_window.unwrap()["suppressed"], json!(2));
+    }
+
+    #[test]
+    fn test_alert_command_retried() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("alert_command_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let alert_config = AlertConfig {
+            enabled: true,
+            command: format!(r#"echo "$ALERT" >> {0}; test $(wc -l < {0}) -ge 2"#, path.display()),
+            poll_interval: 60_000,
+            retries: 2,
+            retry_delay: 10,
+            ..Default::default()
+        };
+        let (query_tx, _query_rx) = unbounded();
+        let (failure_tx, failure_rx) = unbounded();
+        let (terminate_tx, terminate_rx) = unbounded();
+        let alert = Alert::new(&alert_config, "a".to_string(), query_tx, failure_rx, terminate_rx).unwrap();
+        let alert_thread = spawn(move || alert.run());
+
+        // Act
+        failure_tx.send(("driver", ModuleError::Disconnected("input_rx".to_string()))).unwrap();
+        std::thread::sleep(Duration::from_millis(500));
+        let (done_tx, done_rx) = unbounded();
+        terminate_tx.send(done_tx).unwrap();
+        alert_thread.join().unwrap();
+        let sent = std::fs::read_to_string(&path).unwrap();
+        let sent = sent.lines().map(|line| serde_json::from_str(line).unwrap()).collect::<Vec<serde_json::Value>>();
+        let _ = std::fs::remove_file(&path);
+
+        // Assert
+        // The command failing the first time is run again, and the termination is acknowledged
+        let expected = json!({ "event": "hardwareLost", "elevator": "a", "error": "The channel input_rx was disconnected" });
+        assert_eq!(sent, vec![expected.clone(), expected]);
+        assert!(done_rx.try_recv().is_ok());
+    }
+}
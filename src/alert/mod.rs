@@ -0,0 +1,4 @@
+pub mod alert;
+pub mod alert_tests;
+
+pub use alert::Alert;
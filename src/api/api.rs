@@ -0,0 +1,373 @@
+/**
+ * HTTP API for calls and fleet state.
+ *
+ * A small HTTP/1.1 server giving test scripts, dashboards and examiners a standard integration point,
+ * without speaking the UDP protocol of the elevators. Requests are translated into commands and queries
+ * to the coordinator. Connections are served one at a time, one request each.
+ *
+ * - `GET /state`:          The snapshot of the coordinator as JSON, with the `floorLabels` of its floors.
+ * - `POST /hall_call`:     Places a hall call from `{"floor": 2, "call": "up"}`, or clears it with `"clear": true`.
+ * - `POST /cab_call/{id}`: Places a cab call from `{"floor": 2}` at elevator `id`, which must be this elevator.
+ * - `GET /metrics`:        Gauges of the fleet in the Prometheus text format.
+ *
+ * The floors of the snapshot and the metrics are numbered from 0 at the lowest, and `floorLabels` names them. A call
+ * is placed at a floor by its number, or by its label as a string, such as `{"floor": "G"}`.
+ *
+ * # Fields
+ * - `port`:                    TCP port the server listens on.
+ * - `local_id`:                The ID of the local elevator, the only one cab calls are placed at.
+ * - `floor_labels`:            The labels of the floors.
+ * - `coordinator_command_tx`:  Sends the calls to the coordinator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ * - `requests_served`:         Requests answered since start.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::{info, error};
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::ApiConfig;
+use crate::shared::{ControlCommand, FloorLabels, Snapshot, Uptime};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// A floor by its number, or by its label
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FloorRequest {
+    Number(u8),
+    Label(String),
+}
+
+#[derive(Deserialize)]
+struct HallCallRequest {
+    floor: FloorRequest,
+    call: String,
+    #[serde(default)]
+    clear: bool,
+}
+
+#[derive(Deserialize)]
+struct CabCallRequest {
+    floor: FloorRequest,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Response {
+        Response { status, content_type: "application/json", body }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Api {
+    port: u16,
+    local_id: String,
+    floor_labels: FloorLabels,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    requests_served: u64,
+}
+
+impl Api {
+    pub fn new(
+        api_config: &ApiConfig,
+        local_id: String,
+        floor_labels: FloorLabels,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    ) -> Api {
+        Api {
+            port: api_config.port,
+            local_id,
+            floor_labels,
+            coordinator_command_tx,
+            coordinator_query_tx,
+            requests_served: 0,
+        }
+    }
+
+    pub fn run(mut self) {
+        // The elevator keeps running without the API
+        let listener = match TcpListener::bind(("0.0.0.0", self.port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to start the HTTP API on port {}: {:?}", self.port, e);
+                return;
+            }
+        };
+        info!("HTTP API listening on port {}", self.port);
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => self.serve(stream),
+                Err(e) => error!("ERROR - API connection {:?}", e),
+            }
+        }
+    }
+
+    fn serve(&mut self, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let response = match read_request(&mut stream) {
+            Some(request) => self.handle(&request),
+            None => Response::error(400, "Malformed request"),
+        };
+
+        let reply = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            reason_phrase(response.status),
+            response.content_type,
+            response.body.len(),
+            response.body
+        );
+        if let Err(e) = stream.write_all(reply.as_bytes()) {
+            info!("Failed to answer API request: {:?}", e);
+        }
+    }
+
+    fn handle(&mut self, request: &Request) -> Response {
+        self.requests_served += 1;
+        let cab_call_id = request.path.strip_prefix("/cab_call/");
+
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/state") => match self.query() {
+                Some(snapshot) => {
+                    let mut state = serde_json::to_value(&snapshot).unwrap();
+                    let n_floors = snapshot.elevator_data.hall_requests.len() as u8;
+                    state["floorLabels"] = serde_json::json!(self.floor_labels.labels(n_floors));
+                    Response::json(200, state.to_string())
+                }
+                None => Response::error(503, "The coordinator did not answer"),
+            },
+            ("GET", "/metrics") => match self.query() {
+                Some(snapshot) => Response {
+                    status: 200,
+                    content_type: "text/plain; version=0.0.4",
+                    body: metrics(&snapshot, self.requests_served),
+                },
+                None => Response::error(503, "The coordinator did not answer"),
+            },
+            ("POST", "/hall_call") => {
+                let hall_call = match serde_json::from_str::<HallCallRequest>(&request.body) {
+                    Ok(hall_call) => hall_call,
+                    Err(e) => return Response::error(400, &e.to_string()),
+                };
+                let call = match hall_call.call.as_str() {
+                    "up" => HALL_UP,
+                    "down" => HALL_DOWN,
+                    _ => return Response::error(400, "The call is either up or down"),
+                };
+                match self.floor(hall_call.floor) {
+                    Ok(floor) => self.command(ControlCommand::HallCall(floor, call, !hall_call.clear)),
+                    Err(response) => response,
+                }
+            }
+            ("POST", _) if cab_call_id.is_some() => {
+                if cab_call_id != Some(self.local_id.as_str()) {
+                    return Response::error(404, "Cab calls are only placed at the elevator itself");
+                }
+                let cab_call = match serde_json::from_str::<CabCallRequest>(&request.body) {
+                    Ok(cab_call) => cab_call,
+                    Err(e) => return Response::error(400, &e.to_string()),
+                };
+                match self.floor(cab_call.floor) {
+                    Ok(floor) => self.command(ControlCommand::CabCall(floor)),
+                    Err(response) => response,
+                }
+            }
+            (_, "/state" | "/metrics" | "/hall_call") => Response::error(405, "Method not allowed"),
+            _ if cab_call_id.is_some() => Response::error(405, "Method not allowed"),
+            _ => Response::error(404, "Not found"),
+        }
+    }
+
+    // Calls are handled asynchronously, so the request is only accepted
+    fn command(&self, command: ControlCommand) -> Response {
+        match self.coordinator_command_tx.send(command) {
+            Ok(()) => Response::json(202, serde_json::json!({ "status": "accepted" }).to_string()),
+            Err(_) => Response::error(503, "The coordinator is not running"),
+        }
+    }
+
+    // The floor of a request, refusing an unknown label
+    fn floor(&self, floor: FloorRequest) -> Result<u8, Response> {
+        match floor {
+            FloorRequest::Number(floor) => Ok(floor),
+            FloorRequest::Label(label) => {
+                self.floor_labels.floor(&label).ok_or_else(|| Response::error(400, &format!("There is no floor labelled '{}'", label)))
+            }
+        }
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Reads a request, with a body of `Content-Length` bytes
+fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut received = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        if let Some(request) = parse_request(&received) {
+            return Some(request);
+        }
+        if received.len() > MAX_REQUEST_SIZE {
+            return None;
+        }
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => received.extend_from_slice(&buffer[..n]),
+        }
+    }
+}
+
+// Parses a complete request. None until the headers and the whole body have arrived.
+fn parse_request(received: &[u8]) -> Option<Request> {
+    let text = std::str::from_utf8(received).ok()?;
+    let (head, body) = text.split_once("\r\n\r\n")?;
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?;
+    let path = target.split('?').next().unwrap_or(target).to_string();
+
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if body.len() < content_length {
+        return None;
+    }
+
+    Some(Request { method, path, body: body[..content_length].to_string() })
+}
+
+// Gauges of the fleet in the Prometheus text format
+fn metrics(snapshot: &Snapshot, requests_served: u64) -> String {
+    let data = &snapshot.elevator_data;
+    let pending = data.hall_requests.iter().flatten().filter(|requested| **requested).count();
+    let confirmed = snapshot.confirmed_hall_requests.iter().flatten().filter(|confirmed| **confirmed).count();
+
+    let mut lines = vec![
+        "# TYPE elevator_data_version counter".to_string(),
+        format!("elevator_data_version {}", data.version),
+        "# TYPE elevator_peers gauge".to_string(),
+        format!("elevator_peers {}", snapshot.peers.len()),
+        "# TYPE elevator_known_elevators gauge".to_string(),
+        format!("elevator_known_elevators {}", data.states.len()),
+        "# TYPE elevator_hall_calls gauge".to_string(),
+        format!("elevator_hall_calls{{state=\"requested\"}} {}", pending),
+        format!("elevator_hall_calls{{state=\"confirmed\"}} {}", confirmed),
+        "# TYPE elevator_floor gauge".to_string(),
+    ];
+
+    let mut ids = data.states.keys().collect::<Vec<&String>>();
+    ids.sort();
+    for id in &ids {
+        lines.push(format!("elevator_floor{{elevator=\"{}\"}} {}", id, data.states[*id].floor));
+    }
+    lines.push("# TYPE elevator_cab_calls gauge".to_string());
+    for id in &ids {
+        let cab_calls = data.states[*id].cab_requests.iter().filter(|requested| **requested).count();
+        lines.push(format!("elevator_cab_calls{{elevator=\"{}\"}} {}", id, cab_calls));
+    }
+    // The counters of the local elevator are current, and those of the others as of their last state
+    let uptime = |id: &str| match id == snapshot.id {
+        true => snapshot.uptime,
+        false => data.states[id].uptime,
+    };
+    let counters: [(&str, fn(Uptime) -> u64); 3] = [
+        ("elevator_restarts_total", |uptime| uptime.restarts),
+        ("elevator_watchdog_resets_total", |uptime| uptime.watchdog_resets),
+        ("elevator_uptime_seconds_total", |uptime| uptime.seconds),
+    ];
+    for (metric, counter) in counters {
+        lines.push(format!("# TYPE {} counter", metric));
+        for id in &ids {
+            lines.push(format!("{}{{elevator=\"{}\"}} {}", metric, id, counter(uptime(id))));
+        }
+    }
+    lines.push("# TYPE elevator_api_requests_total counter".to_string());
+    lines.push(format!("elevator_api_requests_total {}", requests_served));
+
+    lines.join("\n") + "\n"
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    }
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::{Api, Request, Response};
+    use crate::shared::Snapshot;
+
+    impl Api {
+        pub fn test_handle(&mut self, request: &Request) -> Response {
+            self.handle(request)
+        }
+    }
+
+    pub fn test_parse_request(received: &[u8]) -> Option<Request> {
+        super::parse_request(received)
+    }
+
+    pub fn test_metrics(snapshot: &Snapshot, requests_served: u64) -> String {
+        super::metrics(snapshot, requests_served)
+    }
+}
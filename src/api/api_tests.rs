@@ -0,0 +1,140 @@
+/*
+ * Unit tests for the HTTP API
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * Requests are handled directly, without a socket, against mock coordinator channels.
+ *
+ * Tests:
+ *  - test_api_parse_request
+ *  - test_api_calls
+ *  - test_api_state_and_metrics
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod api_tests {
+    use crate::api::api::testing::{test_metrics, test_parse_request};
+    use crate::api::api::Request;
+    use crate::config::ApiConfig;
+    use crate::shared::{ControlCommand, FloorLabels, NetworkStatistics, Snapshot, Uptime};
+    use crate::{Api, ElevatorData, ElevatorState};
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+    use std::thread::Builder;
+
+    fn setup_api() -> (Api, Receiver<ControlCommand>, Receiver<Sender<Snapshot>>) {
+        let (coordinator_command_tx, coordinator_command_rx) = unbounded::<ControlCommand>();
+        let (coordinator_query_tx, coordinator_query_rx) = unbounded::<Sender<Snapshot>>();
+        let api_config = ApiConfig { enabled: true, port: 0 };
+        let floor_labels = FloorLabels::new(&["B1", "G", "1", "2"].map(String::from), 4).unwrap();
+        let api = Api::new(&api_config, "elevator".to_string(), floor_labels, coordinator_command_tx, coordinator_query_tx);
+        (api, coordinator_command_rx, coordinator_query_rx)
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request { method: method.to_string(), path: path.to_string(), body: body.to_string() }
+    }
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.version = 7;
+        elevator_data.hall_requests[2][HALL_UP as usize] = true;
+        elevator_data.states.insert("elevator".to_string(), ElevatorState::new(4));
+        elevator_data.states.get_mut("elevator").unwrap().floor = 3;
+        Snapshot {
+            id: "elevator".to_string(),
+            peers: vec!["elevator".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime { restarts: 3, watchdog_resets: 1, seconds: 600 },
+        }
+    }
+
+    #[test]
+    fn test_api_parse_request() {
+        // Arrange
+        let complete = b"POST /hall_call?source=test HTTP/1.1\r\nHost: elevator\r\ncontent-length: 13\r\n\r\n{\"floor\": 2}\n";
+        let partial_body = b"POST /hall_call HTTP/1.1\r\nContent-Length: 13\r\n\r\n{\"floor\"";
+        let partial_headers = b"GET /state HTTP/1.1\r\nHost: elev";
+
+        // Act / Assert
+        // The query is dropped, and a request is only complete with all of its body
+        assert_eq!(test_parse_request(complete), Some(request("POST", "/hall_call", "{\"floor\": 2}\n")));
+        assert_eq!(test_parse_request(partial_body), None);
+        assert_eq!(test_parse_request(partial_headers), None);
+        assert_eq!(test_parse_request(b"GET /state HTTP/1.1\r\n\r\n"), Some(request("GET", "/state", "")));
+    }
+
+    #[test]
+    fn test_api_calls() {
+        // Arrange
+        let (mut api, coordinator_command_rx, _coordinator_query_rx) = setup_api();
+
+        // Act
+        let placed = api.test_handle(&request("POST", "/hall_call", r#"{"floor": 2, "call": "down"}"#));
+        let cleared = api.test_handle(&request("POST", "/hall_call", r#"{"floor": 1, "call": "up", "clear": true}"#));
+        let cab_call = api.test_handle(&request("POST", "/cab_call/elevator", r#"{"floor": 3}"#));
+        let labelled = api.test_handle(&request("POST", "/cab_call/elevator", r#"{"floor": "G"}"#));
+        let unknown_label = api.test_handle(&request("POST", "/hall_call", r#"{"floor": "13", "call": "up"}"#));
+        let remote_cab_call = api.test_handle(&request("POST", "/cab_call/other", r#"{"floor": 3}"#));
+        let malformed = api.test_handle(&request("POST", "/hall_call", r#"{"floor": 2, "call": "sideways"}"#));
+        let wrong_method = api.test_handle(&request("GET", "/hall_call", ""));
+        let unknown = api.test_handle(&request("GET", "/elevators", ""));
+
+        // Assert
+        // Calls become coordinator commands, while anything else is refused without one
+        // A floor is given by its number, or by its label
+        assert_eq!((placed.status, cleared.status, cab_call.status, labelled.status), (202, 202, 202, 202));
+        assert_eq!(
+            coordinator_command_rx.try_iter().collect::<Vec<ControlCommand>>(),
+            vec![
+                ControlCommand::HallCall(2, HALL_DOWN, true),
+                ControlCommand::HallCall(1, HALL_UP, false),
+                ControlCommand::CabCall(3),
+                ControlCommand::CabCall(1),
+            ]
+        );
+        assert_eq!(unknown_label.status, 400);
+        assert_eq!(remote_cab_call.status, 404);
+        assert_eq!(malformed.status, 400);
+        assert_eq!(wrong_method.status, 405);
+        assert_eq!(unknown.status, 404);
+    }
+
+    #[test]
+    fn test_api_state_and_metrics() {
+        // Arrange
+        let (mut api, _coordinator_command_rx, coordinator_query_rx) = setup_api();
+        let coordinator_thread = Builder::new().name("coordinator".into());
+        coordinator_thread
+            .spawn(move || {
+                for reply_tx in coordinator_query_rx.iter() {
+                    reply_tx.send(snapshot()).unwrap();
+                }
+            })
+            .unwrap();
+
+        // Act
+        let state = api.test_handle(&request("GET", "/state", ""));
+        let metrics = api.test_handle(&request("GET", "/metrics", ""));
+
+        // Assert
+        // The state is the snapshot of the coordinator, and the metrics are derived from it
+        assert_eq!(state.status, 200);
+        assert_eq!(serde_json::from_str::<Snapshot>(&state.body).unwrap(), snapshot());
+        assert!(state.body.contains(r#""floorLabels":["B1","G","1","2"]"#));
+        assert_eq!(metrics.status, 200);
+        assert_eq!(metrics.body, test_metrics(&snapshot(), 2));
+        assert!(metrics.body.contains("elevator_data_version 7\n"));
+        assert!(metrics.body.contains("elevator_hall_calls{state=\"requested\"} 1\n"));
+        assert!(metrics.body.contains("elevator_floor{elevator=\"elevator\"} 3\n"));
+        assert!(metrics.body.contains("elevator_watchdog_resets_total{elevator=\"elevator\"} 1\n"));
+        assert!(metrics.body.contains("elevator_uptime_seconds_total{elevator=\"elevator\"} 600\n"));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod api;
+pub mod api_tests;
+
+pub use api::Api;
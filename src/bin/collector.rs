@@ -0,0 +1,70 @@
+/**
+ * The log collector, merging the logs shipped by the elevators into one stream on standard output.
+ *
+ * Run it on a machine the elevators reach, and set `collector_address` of the `[logging]` section of each elevator
+ * to its address and port. See `Collector` for how the streams are ordered.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use clap::{App, Arg};
+use std::io::{self, Write};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use project::logging::{Collector, LogEvent};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const RELEASE_INTERVAL: Duration = Duration::from_millis(50);
+const DATAGRAM_SIZE: usize = 65536;
+
+/***************************************/
+/*        Program entry point          */
+/***************************************/
+fn main() -> io::Result<()> {
+    let arguments = App::new("collector")
+        .about("Collects the logs shipped by the elevators, and prints them merged in the order of their time")
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .value_name("PORT")
+                .help("Sets the UDP port the logs are received on")
+                .default_value("7700"),
+        )
+        .arg(
+            Arg::with_name("window")
+                .long("window")
+                .value_name("MILLISECONDS")
+                .help("Sets how long the events are held to be ordered with those arriving after them")
+                .default_value("500"),
+        )
+        .get_matches();
+    let port = arguments.value_of("port").unwrap().parse::<u16>().expect("Failed to parse the port");
+    let window = arguments.value_of("window").unwrap().parse::<u64>().expect("Failed to parse the window");
+
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_read_timeout(Some(RELEASE_INTERVAL))?;
+    eprintln!("Collecting the logs on port {}", port);
+
+    let mut collector = Collector::new(Duration::from_millis(window));
+    let mut buffer = vec![0; DATAGRAM_SIZE];
+    let mut stdout = io::stdout().lock();
+    loop {
+        // The read times out while no events arrive, so the events held are still released
+        if let Ok((number_of_bytes, source)) = socket.recv_from(&mut buffer) {
+            match serde_json::from_slice::<LogEvent>(&buffer[..number_of_bytes]) {
+                Ok(event) => collector.push(&source.to_string(), event, Instant::now()),
+                Err(e) => eprintln!("Dropped a malformed event from {}: {}", source, e),
+            }
+        }
+        for line in collector.release(Instant::now()) {
+            writeln!(stdout, "{}", line)?;
+        }
+    }
+}
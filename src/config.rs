@@ -2,8 +2,22 @@
 /*               Lbraries              */
 /***************************************/
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{
+    AssignmentModel, Encryption, ExitReason, HallCallConfirmation, HallClearing, HallRequestAssigner, OverflowPolicy, PeerDiscovery, ReportPeriod, RestartPolicy,
+    StorageBackend, TrafficMode, VersionPolicy,
+};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+pub const CONFIG_FILE: &str = "config.toml";
+
 /***************************************/
 /*       Public data structures        */
 /***************************************/
@@ -12,6 +26,60 @@ pub struct Config {
     pub network: NetworkConfig,
     pub elevator: ElevatorConfig,
     pub hardware: HardwareConfig,
+    #[serde(default)]
+    pub access: AccessConfig,
+    #[serde(default)]
+    pub lockout: Vec<LockoutConfig>,
+    #[serde(default)]
+    pub traffic: TrafficConfig,
+    #[serde(default)]
+    pub fire_service: FireServiceConfig,
+    #[serde(default)]
+    pub repositioning: RepositioningConfig,
+    #[serde(default)]
+    pub consensus: ConsensusConfig,
+    #[serde(default)]
+    pub relay: RelayConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub rpc: RpcConfig,
+    #[serde(default)]
+    pub clock: ClockConfig,
+    #[serde(default)]
+    pub demo: DemoConfig,
+    #[serde(default)]
+    pub alert: AlertConfig,
+    #[serde(default)]
+    pub panel: PanelConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub adaptation: AdaptationConfig,
+    #[serde(default)]
+    pub threads: ThreadsConfig,
+    #[serde(default)]
+    pub channels: ChannelsConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub report: ReportConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    #[serde(default)]
+    pub self_test: SelfTestConfig,
+    #[serde(default)]
+    pub soak: SoakConfig,
+    #[serde(default)]
+    pub startup: StartupConfig,
+    #[serde(default)]
+    pub restart: RestartConfig,
 }
 
 #[derive(Deserialize, Clone)]
@@ -19,10 +87,57 @@ pub struct NetworkConfig {
     pub id_gen_address: String,
     pub msg_port: u16,
     pub peer_port: u16,
-    pub max_retries: u32,
-    pub ack_timeout: u64,
+    pub gossip_interval: u64,
+    #[serde(default)]
+    pub anti_entropy_interval: u64,
+    #[serde(default)]
+    pub id_file: String,
+    #[serde(default)]
+    pub peers: Vec<PeerConfig>,
     pub max_attempts_id_generation: u32,
     pub delay_between_attempts_id_generation: u64,
+    // The ports after `msg_port` to try, in order, if it is in use. 0 refuses to start instead.
+    #[serde(default)]
+    pub port_range: u16,
+    // Whether the peers are found by broadcasts on `peer_port`, or by mDNS
+    #[serde(default)]
+    pub peer_discovery: PeerDiscovery,
+    // Milliseconds between the broadcasts of the ID, and without one before a peer is lost. 0 takes the 15 and 500
+    // of `udpnet`.
+    #[serde(default)]
+    pub peer_interval: u64,
+    #[serde(default)]
+    pub peer_timeout: u64,
+    // The address the ID is broadcast to on `peer_port`, such as the broadcast address of the subnet. Empty for
+    // 255.255.255.255.
+    #[serde(default)]
+    pub peer_broadcast_address: String,
+    // The MTU of the path to the peers, which no datagram exceeds. 0 detects it from the network interfaces.
+    #[serde(default)]
+    pub mtu: usize,
+    // The datagrams sent per second at most, with changes to the orders sent first. 0 leaves the rate unlimited.
+    #[serde(default)]
+    pub max_packet_rate: u32,
+    // Whether the datagrams are encrypted with `encryption_key`, 64 hexadecimal digits shared by the whole fleet
+    #[serde(default)]
+    pub encryption: Encryption,
+    #[serde(default)]
+    pub encryption_key: String,
+    // How long to wait at start-up for the peers, before the persisted state is reconciled with theirs. 0 skips it.
+    #[serde(default)]
+    pub startup_timeout: u64,
+    // Whether peers announcing another version of the messages are only warned about, or left out of the fleet
+    #[serde(default)]
+    pub version_policy: VersionPolicy,
+    // The bank of elevators this one belongs to, among the banks sharing the network. Empty for the default group.
+    #[serde(default)]
+    pub group_id: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PeerConfig {
+    pub id: String,
+    pub address: String,
 }
 
 #[derive(Deserialize, Clone)]
@@ -31,21 +146,464 @@ pub struct ElevatorConfig {
     pub door_open_time: u64,
     pub motor_timeout: u64,
     pub door_timeout: u64,
+    #[serde(default)]
+    pub hall_call_cancel_window: u64,
+    #[serde(default)]
+    pub hall_call_quorum: usize,
+    #[serde(default)]
+    pub hall_call_confirmation: HallCallConfirmation,
+    #[serde(default)]
+    pub hall_call_deadline: u64,
+    #[serde(default)]
+    pub hall_request_assigner: HallRequestAssigner,
+    #[serde(default)]
+    pub assigner_plugin: String,
+    #[serde(default)]
+    pub assignment_model: AssignmentModel,
+    #[serde(default)]
+    pub levelling_time: u64,
+    #[serde(default)]
+    pub door_pre_opening: bool,
+    #[serde(default)]
+    pub statistics_interval: u64,
+    #[serde(default)]
+    pub statistics_report: String,
+    #[serde(default)]
+    pub obstruction_alarm_threshold: u32,
+    #[serde(default)]
+    pub hall_clearing: HallClearing,
+    #[serde(default)]
+    pub capacity: usize,
+    #[serde(default)]
+    pub motion: MotionConfig,
+}
+
+// Times of the travel-time model, in milliseconds. The model is unset without a floor travel time.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct MotionConfig {
+    pub acceleration_time: u64,
+    pub floor_travel_time: u64,
+    pub door_cycle_time: u64,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct HardwareConfig {
     pub n_floors: u8,
+    // The label of every floor from the lowest, as shown to the users. Empty to show the floors by number.
+    #[serde(default)]
+    pub floor_labels: Vec<String>,
     pub driver_address: String,
     pub driver_port: u16,
     pub hw_thread_sleep_time: u64,
 }
 
+#[derive(Deserialize, Clone, Default)]
+pub struct AccessConfig {
+    pub restricted_floors: Vec<u8>,
+    pub authorized_elevators: Vec<String>,
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+    #[serde(default)]
+    pub profiles: Vec<ZoningProfileConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ZoneConfig {
+    pub elevator: String,
+    pub floors: Vec<u8>,
+    // The floor the elevator parks at when idle, over the sectors of `[repositioning]`
+    pub parking_floor: Option<u8>,
+}
+
+// Zones switched to at runtime by name, replacing those of `[access]` for every elevator
+#[derive(Deserialize, Clone)]
+pub struct ZoningProfileConfig {
+    pub name: String,
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LockoutConfig {
+    pub floors: Vec<u8>,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct TrafficConfig {
+    #[serde(default)]
+    pub mode: TrafficMode,
+    #[serde(default)]
+    pub lobby_floor: u8,
+    pub down_peak_floor: Option<u8>,
+    #[serde(default)]
+    pub schedule: Vec<TrafficScheduleConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TrafficScheduleConfig {
+    pub mode: TrafficMode,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct FireServiceConfig {
+    pub recall_floor: u8,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct RepositioningConfig {
+    #[serde(default)]
+    pub sectors: Vec<u8>,
+}
+
+// The network is measured every `interval` milliseconds. The coalescing of the data sent, up to `max_coalescing`
+// milliseconds, and the peer timeout, up to `max_peer_timeout` milliseconds, widen with the loss, fully at a loss of
+// `loss_threshold`, from which the coordinator also serves the hall calls pressed at its own panel.
+#[derive(Deserialize, Clone, Default)]
+pub struct AdaptationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub interval: u64,
+    pub loss_threshold: f64,
+    pub max_coalescing: u64,
+    pub max_peer_timeout: u64,
+}
+
+// The scheduling of the threads on the path from the floor sensor to the motor
+#[derive(Deserialize, Clone, Default)]
+pub struct ThreadsConfig {
+    #[serde(default)]
+    pub driver: ThreadConfig,
+    #[serde(default)]
+    pub fsm: ThreadConfig,
+}
+
+// A thread runs at the real-time `priority` of 1 to 99, ahead of every thread of normal priority, and at normal
+// priority with 0. On Linux it runs only on the `cores` listed, and on any core if none are.
+#[derive(Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct ThreadConfig {
+    #[serde(default)]
+    pub priority: u8,
+    #[serde(default)]
+    pub cores: Vec<usize>,
+}
+
+// Every channel between the modules holds up to `capacity` messages, 1024 with 0. A channel named in `overrides`,
+// such as `fsm_state`, holds its own capacity instead, unless 0, and handles overflow by its own policy, if set.
+#[derive(Deserialize, Clone, Default)]
+pub struct ChannelsConfig {
+    #[serde(default)]
+    pub capacity: usize,
+    #[serde(default)]
+    pub overrides: HashMap<String, ChannelConfig>,
+}
+
+#[derive(Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct ChannelConfig {
+    #[serde(default)]
+    pub capacity: usize,
+    #[serde(default)]
+    pub policy: Option<OverflowPolicy>,
+}
+
+// The log events at `level` and above, info if empty, are also shipped to the collector at `collector_address`,
+// such as "10.100.23.10:7700". Empty keeps the log local.
+#[derive(Deserialize, Clone, Default)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub collector_address: String,
+    #[serde(default)]
+    pub level: String,
+    // The most verbose level logged at all, locally and to the collector, such as "warn". Empty leaves it to
+    // `RUST_LOG` and `level`.
+    #[serde(default)]
+    pub max_level: String,
+}
+
+// A service report is written to `directory` every `period`, from the snapshots polled every `poll_interval`
+// milliseconds, 1000 with 0. The last `retention` reports of the elevator are kept, and all of them with 0. With
+// `push`, it is also logged, and so shipped to the log collector if there is one.
+#[derive(Deserialize, Clone, Default)]
+pub struct ReportConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub period: ReportPeriod,
+    #[serde(default)]
+    pub directory: String,
+    #[serde(default)]
+    pub poll_interval: u64,
+    #[serde(default)]
+    pub retention: usize,
+    #[serde(default)]
+    pub push: bool,
+}
+
+// The hardware is tested at start-up, each lamp lit for `lamp_time` milliseconds. With `required`, an elevator that
+// fails the test does not start.
+#[derive(Deserialize, Clone, Default)]
+pub struct SelfTestConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub lamp_time: u64,
+    #[serde(default)]
+    pub required: bool,
+}
+
+// Each module is given `module_timeout` milliseconds to report ready at start-up, or 10 seconds with 0, before the
+// elevator gives up starting
+#[derive(Deserialize, Clone, Default)]
+pub struct StartupConfig {
+    #[serde(default)]
+    pub module_timeout: u64,
+}
+
+// How the elevator is restarted when run with `--supervise`. A restart with backoff waits `initial_delay`
+// milliseconds, doubled with every restart in a row up to `max_delay`. A run lasting `stable_time` milliseconds ends
+// the restarts in a row, and the supervisor gives up after `max_restarts` of them, or never with 0. `policies`
+// overrides the policy of each exit reason.
+#[derive(Deserialize, Clone, Default)]
+pub struct RestartConfig {
+    #[serde(default)]
+    pub initial_delay: u64,
+    #[serde(default)]
+    pub max_delay: u64,
+    #[serde(default)]
+    pub stable_time: u64,
+    #[serde(default)]
+    pub max_restarts: u32,
+    #[serde(default)]
+    pub policies: HashMap<ExitReason, RestartPolicy>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct ConsensusConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub election_timeout: u64,
+    pub heartbeat_interval: u64,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct RelayConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub port: u16,
+    pub remote_address: String,
+    pub announce_interval: u64,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct ApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub port: u16,
+}
+
+// Topics and the client ID may contain `{id}`, replaced by the ID of the elevator.
+// An empty `command_topic` publishes only.
+#[derive(Deserialize, Clone, Default)]
+pub struct MqttConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub broker_address: String,
+    pub client_id: String,
+    pub state_topic: String,
+    pub order_topic: String,
+    pub fault_topic: String,
+    #[serde(default)]
+    pub command_topic: String,
+    pub publish_interval: u64,
+    pub keep_alive: u16,
+    pub reconnect_delay: u64,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct GrpcConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub port: u16,
+    pub event_interval: u64,
+}
+
+// A port of 0 or an empty socket path leaves that listener out
+#[derive(Deserialize, Clone, Default)]
+pub struct RpcConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub port: u16,
+    #[serde(default)]
+    pub socket_path: String,
+}
+
+// A simulated clock runs `speed` times faster than real time, starting at the time of day `start_time` ("HH:MM"),
+// or the current time of day when empty
+#[derive(Deserialize, Clone, Default)]
+pub struct ClockConfig {
+    #[serde(default)]
+    pub simulated: bool,
+    pub speed: f64,
+    #[serde(default)]
+    pub start_time: String,
+}
+
+// Only plain `http://` webhooks are supported. With a `command`, it is run instead of posting to the webhook. An
+// alert that fails is retried `retries` times, first after `retry_delay` milliseconds, 1000 with 0. Up to
+// `max_alerts` alerts are sent within `rate_window` seconds, 3600 with 0, and any number with `max_alerts` of 0.
+#[derive(Deserialize, Clone, Default)]
+pub struct AlertConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub webhook: String,
+    #[serde(default)]
+    pub command: String,
+    pub poll_interval: u64,
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub retry_delay: u64,
+    #[serde(default)]
+    pub max_alerts: usize,
+    #[serde(default)]
+    pub rate_window: u64,
+}
+
+// The door lamp blinks `on` and `off` milliseconds at a time, and the cab button lamps with it if `buttons`.
+// A pattern with no time on is not shown.
+#[derive(Deserialize, Clone, Default)]
+pub struct PanelConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub blocked: BlinkPattern,
+    #[serde(default)]
+    pub error: BlinkPattern,
+    #[serde(default)]
+    pub maintenance: BlinkPattern,
+    #[serde(default)]
+    pub fire: BlinkPattern,
+    #[serde(default)]
+    pub status: StatusCode,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct BlinkPattern {
+    pub on: u64,
+    pub off: u64,
+    #[serde(default)]
+    pub buttons: bool,
+}
+
+// The stop lamp blinks the status of the system as a code, each blink lit and unlit for `blink` milliseconds, with
+// `pause` milliseconds unlit between the codes. A code with no blink time is not shown.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct StatusCode {
+    pub blink: u64,
+    pub pause: u64,
+}
+
+// The files backend keeps its files in `directory`, and the sqlite backend its database at `database`. The
+// snapshot of the coordinator and the events of the fleet are recorded every `record_interval` milliseconds, and
+// as soon as the fleet changes, and not at all with an interval of 0. The last `snapshot_retention` snapshots are
+// kept as a history. With `write_ahead_log` every order mutation is logged before it takes effect,
+// and the orders are recovered from the log at start-up. The event log is rotated when it reaches `log_max_size`
+// bytes or its first event is `log_max_age` seconds old, and `log_rotations` rotated logs are kept. A maximum of 0
+// is no limit.
+#[derive(Deserialize, Clone, Default)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: StorageBackend,
+    #[serde(default)]
+    pub directory: String,
+    #[serde(default)]
+    pub database: String,
+    #[serde(default)]
+    pub record_interval: u64,
+    #[serde(default)]
+    pub snapshot_retention: usize,
+    #[serde(default)]
+    pub write_ahead_log: bool,
+    #[serde(default)]
+    pub log_max_size: u64,
+    #[serde(default)]
+    pub log_max_age: u64,
+    #[serde(default)]
+    pub log_rotations: usize,
+}
+
+// A backup of the storage is pushed to `peers` peers every `interval` milliseconds, and not at all with an interval
+// of 0. A node starting without its local state waits up to `timeout` milliseconds for its backup from the peers.
+#[derive(Deserialize, Clone, Default)]
+pub struct BackupConfig {
+    #[serde(default)]
+    pub interval: u64,
+    #[serde(default)]
+    pub peers: usize,
+    #[serde(default)]
+    pub timeout: u64,
+}
+
+// `config.toml` is checked for changes every `interval` milliseconds, and not at all with 0. Updates from the
+// network are sealed with `key`, 64 hexadecimal digits, and refused without one.
+#[derive(Deserialize, Clone, Default)]
+pub struct ReloadConfig {
+    #[serde(default)]
+    pub interval: u64,
+    #[serde(default)]
+    pub key: String,
+}
+
+// Hall and cab calls arrive at random, `hall_call_rate` and `cab_call_rate` per minute. The floors are drawn by
+// `floor_weights`, uniformly if empty. The times are in seconds, and a seed of 0 seeds from the wall clock.
+#[derive(Deserialize, Clone, Default)]
+pub struct DemoConfig {
+    pub duration: u64,
+    #[serde(default)]
+    pub drain_time: u64,
+    pub hall_call_rate: f64,
+    pub cab_call_rate: f64,
+    #[serde(default)]
+    pub floor_weights: Vec<f64>,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+// The soak test checks the invariants every `check_interval` milliseconds, 250 with 0: no call waiting longer than
+// `deadline` seconds, and no hall call planned by two elevators for longer than `grace` milliseconds. A fault
+// lasting `fault_time` seconds is injected every `fault_interval` seconds on average, or never with 0. The state is
+// dumped to `dump_directory` on the first violation.
+#[derive(Deserialize, Clone, Default)]
+pub struct SoakConfig {
+    #[serde(default)]
+    pub check_interval: u64,
+    #[serde(default)]
+    pub deadline: u64,
+    #[serde(default)]
+    pub grace: u64,
+    #[serde(default)]
+    pub fault_interval: u64,
+    #[serde(default)]
+    pub fault_time: u64,
+    #[serde(default)]
+    pub dump_directory: String,
+    #[serde(default)]
+    pub seed: u64,
+}
+
 /***************************************/
 /*             Public API              */
 /***************************************/
 pub fn load_config() -> Config {
-    let config_str = fs::read_to_string("config.toml").expect("Failed to read configuration file");
-    toml::from_str(&config_str).expect("Failed to parse configuration file")
+    read_config(CONFIG_FILE).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn read_config(path: &str) -> Result<Config, String> {
+    let config_str = fs::read_to_string(path).map_err(|e| format!("Failed to read configuration file: {}", e))?;
+    toml::from_str(&config_str).map_err(|e| format!("Failed to parse configuration file: {}", e))
 }
 
@@ -0,0 +1,158 @@
+/**
+ * Debug console for operator commands.
+ *
+ * Reads commands line by line from standard input and forwards them to the coordinator.
+ * Unknown commands print the usage. The console stops when standard input is closed,
+ * e.g. when running as a service. Floors are typed by their labels.
+ *
+ * # Fields
+ * - `coordinator_command_tx`:  Sends parsed commands to the coordinator.
+ * - `floor_labels`:            The labels the floors are typed by.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::{info, error};
+use std::io::BufRead;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{exit, ControlCommand, ExitReason, FloorLabels, TrafficMode};
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const USAGE: &str = "Commands:
+  traffic <normal|up-peak|down-peak>   Override the traffic mode on the whole fleet
+  traffic auto                         Return to the scheduled traffic mode
+  fire recall                          Start fire service phase 1 on the whole fleet
+  fire reset                           End fire service on the whole fleet
+  fire phase2 <on|off>                 Put this car in or out of fire service phase 2
+  door <open|close>                    Operate the door of this car in fire service phase 2
+  independent <on|off> [id]            Put a car (this car if no id) in or out of independent service
+  zoning <profile|default>             Switch the whole fleet to a zoning profile, or back to the default zones
+  fleet                                Show the state of every car
+  links                                Show the round trip and loss measured to every peer
+  queue                                Show the orders and timers of this car as its FSM sees them
+  hall <place|clear> <floor> <up|down> Place or clear a hall call as if its button were pressed
+  cab <floor>                          Place a cab call in this car as if its button were pressed
+  export [file]                        Write the state of this elevator as JSON to a file, or to the console
+  alarm clear                          Return this car to service after an obstruction alarm
+  halt                                 Hold this car once it has finished its current stop
+  resume                               Return this car to service after a halt";
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Console {
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    floor_labels: FloorLabels,
+}
+
+impl Console {
+    pub fn new(coordinator_command_tx: cbc::Sender<ControlCommand>, floor_labels: FloorLabels) -> Console {
+        Console { coordinator_command_tx, floor_labels }
+    }
+
+    pub fn run(self) {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    error!("ERROR - console input {:?}", e);
+                    break;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match parse_command(&line, &self.floor_labels) {
+                Ok(command) => {
+                    if let Err(e) = self.coordinator_command_tx.send(command) {
+                        error!("Failed to send command to coordinator: {:?}", e);
+                        exit(ExitReason::Failure);
+                    }
+                }
+                Err(message) => println!("{}", message),
+            }
+        }
+
+        info!("Console input closed");
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Parses a console line, with the floors by their labels, returning the usage on unknown input
+pub fn parse_command(line: &str, floor_labels: &FloorLabels) -> Result<ControlCommand, String> {
+    let words = line.split_whitespace().collect::<Vec<&str>>();
+
+    match words.as_slice() {
+        ["traffic", mode] => match *mode {
+            "normal" => Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::Normal))),
+            "up-peak" => Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak))),
+            "down-peak" => Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::DownPeak))),
+            "auto" => Ok(ControlCommand::SetTrafficMode(None)),
+            _ => Err(format!("Unknown traffic mode '{}'\n{}", mode, USAGE)),
+        },
+        ["fire", "recall"] => Ok(ControlCommand::SetFireRecall(true)),
+        ["fire", "reset"] => Ok(ControlCommand::SetFireRecall(false)),
+        ["fire", "phase2", "on"] => Ok(ControlCommand::SetFirePhase2(true)),
+        ["fire", "phase2", "off"] => Ok(ControlCommand::SetFirePhase2(false)),
+        ["door", "open"] => Ok(ControlCommand::Door(true)),
+        ["door", "close"] => Ok(ControlCommand::Door(false)),
+        ["independent", "on"] => Ok(ControlCommand::SetIndependentService(None, true)),
+        ["independent", "off"] => Ok(ControlCommand::SetIndependentService(None, false)),
+        ["independent", "on", id] => Ok(ControlCommand::SetIndependentService(Some(id.to_string()), true)),
+        ["independent", "off", id] => Ok(ControlCommand::SetIndependentService(Some(id.to_string()), false)),
+        ["zoning", "default"] => Ok(ControlCommand::SetZoningProfile(None)),
+        ["zoning", profile @ ..] if !profile.is_empty() => Ok(ControlCommand::SetZoningProfile(Some(profile.join(" ")))),
+        ["fleet"] => Ok(ControlCommand::ShowFleet),
+        ["links"] => Ok(ControlCommand::ShowLinks),
+        ["queue"] => Ok(ControlCommand::ShowQueue),
+        ["export"] => Ok(ControlCommand::ExportState(None)),
+        ["export", path] => Ok(ControlCommand::ExportState(Some(path.to_string()))),
+        ["alarm", "clear"] => Ok(ControlCommand::ClearObstructionAlarm),
+        ["halt"] => Ok(ControlCommand::Halt(true)),
+        ["resume"] => Ok(ControlCommand::Halt(false)),
+        ["hall", action, floor, call] => {
+            let place = match *action {
+                "place" => true,
+                "clear" => false,
+                _ => return Err(format!("Unknown hall call action '{}'\n{}", action, USAGE)),
+            };
+            let floor = floor_labels.floor(floor).ok_or(format!("Invalid floor '{}'\n{}", floor, USAGE))?;
+            let call = match *call {
+                "up" => HALL_UP,
+                "down" => HALL_DOWN,
+                _ => return Err(format!("Unknown hall call '{}'\n{}", call, USAGE)),
+            };
+            Ok(ControlCommand::HallCall(floor, call, place))
+        }
+        ["cab", floor] => match floor_labels.floor(floor) {
+            Some(floor) => Ok(ControlCommand::CabCall(floor)),
+            None => Err(format!("Invalid floor '{}'\n{}", floor, USAGE)),
+        },
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// Parses a console line that changes a mode of the fleet or the local car. Calls and other commands are refused,
+// for control interfaces that may only change modes.
+pub fn parse_mode_command(line: &str) -> Result<ControlCommand, String> {
+    match parse_command(line, &FloorLabels::default())? {
+        command @ (ControlCommand::SetTrafficMode(_)
+        | ControlCommand::SetFireRecall(_)
+        | ControlCommand::SetFirePhase2(_)
+        | ControlCommand::SetIndependentService(_, _)
+        | ControlCommand::SetZoningProfile(_)) => Ok(command),
+        _ => Err("Only mode changes are accepted".to_string()),
+    }
+}
@@ -0,0 +1,139 @@
+/*
+ * Unit tests for the debug console
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_console_parse_traffic_command
+ *  - test_console_parse_fire_service_command
+ *  - test_console_parse_independent_service_command
+ *  - test_console_parse_zoning_command
+ *  - test_console_parse_hall_call_command
+ *  - test_console_parse_export_command
+ *  - test_console_parse_alarm_command
+ *  - test_console_parse_halt_command
+ *  - test_console_parse_unknown_command
+ *  - test_console_parse_mode_command
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod console_tests {
+    use crate::console::console::{self, parse_mode_command};
+    use crate::shared::{ControlCommand, FloorLabels, TrafficMode};
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+
+    // A line of a console of floors named by number
+    fn parse_command(line: &str) -> Result<ControlCommand, String> {
+        console::parse_command(line, &FloorLabels::default())
+    }
+
+    #[test]
+    fn test_console_parse_traffic_command() {
+        // Act / Assert
+        assert_eq!(parse_command("traffic up-peak"), Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak))));
+        assert_eq!(parse_command("  traffic   down-peak "), Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::DownPeak))));
+        assert_eq!(parse_command("traffic normal"), Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::Normal))));
+        assert_eq!(parse_command("traffic auto"), Ok(ControlCommand::SetTrafficMode(None)));
+    }
+
+    #[test]
+    fn test_console_parse_fire_service_command() {
+        // Act / Assert
+        assert_eq!(parse_command("fire recall"), Ok(ControlCommand::SetFireRecall(true)));
+        assert_eq!(parse_command("fire reset"), Ok(ControlCommand::SetFireRecall(false)));
+        assert_eq!(parse_command("fire phase2 on"), Ok(ControlCommand::SetFirePhase2(true)));
+        assert_eq!(parse_command("fire phase2 off"), Ok(ControlCommand::SetFirePhase2(false)));
+        assert_eq!(parse_command("door open"), Ok(ControlCommand::Door(true)));
+        assert_eq!(parse_command("door close"), Ok(ControlCommand::Door(false)));
+    }
+
+    #[test]
+    fn test_console_parse_independent_service_command() {
+        // Act / Assert
+        assert_eq!(parse_command("independent on"), Ok(ControlCommand::SetIndependentService(None, true)));
+        assert_eq!(
+            parse_command("independent off 10.100.23.17:19735"),
+            Ok(ControlCommand::SetIndependentService(Some("10.100.23.17:19735".to_string()), false))
+        );
+        assert_eq!(parse_command("fleet"), Ok(ControlCommand::ShowFleet));
+        assert_eq!(parse_command("links"), Ok(ControlCommand::ShowLinks));
+        assert_eq!(parse_command("queue"), Ok(ControlCommand::ShowQueue));
+    }
+
+    #[test]
+    fn test_console_parse_zoning_command() {
+        // Act / Assert
+        assert_eq!(parse_command("zoning express"), Ok(ControlCommand::SetZoningProfile(Some("express".to_string()))));
+        assert_eq!(parse_command("zoning lunch-rush  express"), Ok(ControlCommand::SetZoningProfile(Some("lunch-rush express".to_string()))));
+        assert_eq!(parse_command("zoning default"), Ok(ControlCommand::SetZoningProfile(None)));
+        assert!(parse_command("zoning").is_err());
+    }
+
+    #[test]
+    fn test_console_parse_hall_call_command() {
+        // Act / Assert
+        assert_eq!(parse_command("hall place 2 up"), Ok(ControlCommand::HallCall(2, HALL_UP, true)));
+        assert_eq!(parse_command("hall clear 0 down"), Ok(ControlCommand::HallCall(0, HALL_DOWN, false)));
+        assert!(parse_command("hall place two up").is_err());
+        assert!(parse_command("hall place 2 sideways").is_err());
+        assert!(parse_command("hall toggle 2 up").is_err());
+        assert_eq!(parse_command("cab 3"), Ok(ControlCommand::CabCall(3)));
+        assert!(parse_command("cab top").is_err());
+        // Floors with labels are typed by them
+        let labels = FloorLabels::new(&["B1", "G", "1", "2"].map(String::from), 4).unwrap();
+        assert_eq!(console::parse_command("hall place G up", &labels), Ok(ControlCommand::HallCall(1, HALL_UP, true)));
+        assert_eq!(console::parse_command("cab 2", &labels), Ok(ControlCommand::CabCall(3)));
+        assert!(console::parse_command("cab 3", &labels).is_err());
+    }
+
+    #[test]
+    fn test_console_parse_export_command() {
+        // Act / Assert
+        assert_eq!(parse_command("export"), Ok(ControlCommand::ExportState(None)));
+        assert_eq!(parse_command("export /tmp/state.json"), Ok(ControlCommand::ExportState(Some("/tmp/state.json".to_string()))));
+        assert!(parse_command("export a b").is_err());
+    }
+
+    #[test]
+    fn test_console_parse_alarm_command() {
+        // Act / Assert
+        assert_eq!(parse_command("alarm clear"), Ok(ControlCommand::ClearObstructionAlarm));
+        assert!(parse_command("alarm").is_err());
+        assert!(parse_mode_command("alarm clear").is_err());
+    }
+
+    #[test]
+    fn test_console_parse_halt_command() {
+        // Act / Assert
+        assert_eq!(parse_command("halt"), Ok(ControlCommand::Halt(true)));
+        assert_eq!(parse_command(" resume "), Ok(ControlCommand::Halt(false)));
+        assert!(parse_command("halt now").is_err());
+    }
+
+    #[test]
+    fn test_console_parse_unknown_command() {
+        // Act / Assert
+        assert!(parse_command("traffic rush-hour").is_err());
+        assert!(parse_command("traffic").is_err());
+        assert!(parse_command("help").is_err());
+        assert!(parse_command("fire phase2").is_err());
+    }
+
+    #[test]
+    fn test_console_parse_mode_command() {
+        // Act / Assert
+        assert_eq!(parse_mode_command("traffic up-peak"), Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak))));
+        assert_eq!(parse_mode_command("fire recall"), Ok(ControlCommand::SetFireRecall(true)));
+        assert_eq!(parse_mode_command("independent on b"), Ok(ControlCommand::SetIndependentService(Some("b".to_string()), true)));
+        assert_eq!(parse_mode_command("zoning default"), Ok(ControlCommand::SetZoningProfile(None)));
+        // Calls and other commands are refused
+        assert!(parse_mode_command("hall place 2 up").is_err());
+        assert!(parse_mode_command("cab 1").is_err());
+        assert!(parse_mode_command("door open").is_err());
+        assert!(parse_mode_command("lift off").is_err());
+    }
+}
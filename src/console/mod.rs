@@ -0,0 +1,4 @@
+pub mod console;
+pub mod console_tests;
+
+pub use console::Console;
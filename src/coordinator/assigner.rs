@@ -0,0 +1,392 @@
+/**
+ * In-process hall request assigner.
+ *
+ * A reimplementation of the cost function of the reference `hall_request_assigner` executable, so hall calls
+ * are assigned in microseconds instead of the milliseconds it takes to start a process for every event. It
+ * gives the same assignment as the executable with its default durations.
+ *
+ * Every elevator is simulated from its current state, the one that has spent the least time so far taking
+ * the next step. A hall call is assigned to the first elevator that stops for it. Once every unassigned call
+ * sits at the floor of an elevator without cab calls, those are assigned where they are. Ties go to the
+ * lowest ID, by starting each elevator a microsecond later than the one before it.
+ *
+ * Any of the assigners can be limited by the capacity of the elevators. The calls an elevator is given beyond its
+ * capacity, the ones furthest from it, overflow to the elevators with room left. Calls no elevator has room for
+ * wait unassigned until one is served.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use std::collections::HashMap;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{Behaviour, Direction, ElevatorData};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// Durations of the reference executable, in microseconds
+const TRAVEL_DURATION: u64 = 2_500_000;
+const DOOR_OPEN_DURATION: u64 = 3_000_000;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Clone)]
+struct HallRequest {
+    active: bool,
+    assigned_to: Option<String>,
+}
+
+struct SimulatedElevator {
+    id: String,
+    behaviour: Behaviour,
+    floor: usize,
+    direction: Direction,
+    cab_requests: Vec<bool>,
+    time: u64,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Assigns the hall requests to the elevators, returning the hall requests assigned to each elevator
+pub fn assign_hall_requests(elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+    let n_floors = elevator_data.hall_requests.len();
+    if n_floors == 0 {
+        return Err("There are no floors".to_string());
+    }
+
+    let mut requests = Vec::with_capacity(n_floors);
+    for hall_requests in elevator_data.hall_requests.iter() {
+        if hall_requests.len() != 2 {
+            return Err("Every floor must have exactly two hall requests".to_string());
+        }
+        requests.push([HALL_UP, HALL_DOWN].map(|call| HallRequest {
+            active: hall_requests[call as usize],
+            assigned_to: None,
+        }));
+    }
+
+    let mut ids = elevator_data.states.keys().cloned().collect::<Vec<String>>();
+    ids.sort();
+    let mut elevators = Vec::with_capacity(ids.len());
+    for (index, id) in ids.into_iter().enumerate() {
+        let state = &elevator_data.states[&id];
+        if state.cab_requests.len() != n_floors {
+            return Err(format!("Elevator {} has cab requests for another number of floors", id));
+        }
+        if state.floor as usize >= n_floors {
+            return Err(format!("Elevator {} is at floor {}, outside the building", id, state.floor));
+        }
+        elevators.push(SimulatedElevator {
+            id,
+            behaviour: state.behaviour.clone(),
+            floor: state.floor as usize,
+            direction: state.direction.clone(),
+            cab_requests: state.cab_requests.clone(),
+            time: index as u64,
+        });
+    }
+
+    for elevator in elevators.iter_mut() {
+        perform_initial_move(elevator, &mut requests)?;
+    }
+
+    loop {
+        elevators.sort_by_key(|elevator| elevator.time);
+
+        let done = !any_unassigned(&requests);
+        if unassigned_are_immediately_assignable(&requests, &elevators) {
+            assign_immediately(&mut requests, &mut elevators);
+            break;
+        }
+        if done {
+            break;
+        }
+
+        match elevators.first_mut() {
+            Some(elevator) => perform_single_move(elevator, &mut requests)?,
+            None => return Err("There are hall requests, but no elevators to serve them".to_string()),
+        }
+    }
+
+    let mut assignment = elevator_data.states
+        .keys()
+        .map(|id| (id.clone(), vec![vec![false; 2]; n_floors]))
+        .collect::<HashMap<String, Vec<Vec<bool>>>>();
+    for (floor, requests_at_floor) in requests.iter().enumerate() {
+        for (call, request) in requests_at_floor.iter().enumerate() {
+            if let (true, Some(id)) = (request.active, &request.assigned_to) {
+                if let Some(hall_requests) = assignment.get_mut(id) {
+                    hall_requests[floor][call] = true;
+                }
+            }
+        }
+    }
+    Ok(assignment)
+}
+
+// Assigns the hall requests with `assign`, keeping every elevator within its capacity. `load` holds the hall calls
+// each elevator has been given so far, and is counted up with this assignment.
+pub fn assign_within_capacity<F>(
+    elevator_data: &ElevatorData,
+    load: &mut HashMap<String, usize>,
+    mut assign: F,
+) -> Result<HashMap<String, Vec<Vec<bool>>>, String>
+where
+    F: FnMut(&ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String>,
+{
+    let n_floors = elevator_data.hall_requests.len();
+    let mut assignment = elevator_data.states
+        .keys()
+        .map(|id| (id.clone(), vec![vec![false; 2]; n_floors]))
+        .collect::<HashMap<String, Vec<Vec<bool>>>>();
+    let room = |id: &String, capacity: usize, load: &HashMap<String, usize>| {
+        if capacity == 0 { usize::MAX } else { capacity.saturating_sub(load.get(id).copied().unwrap_or(0)) }
+    };
+
+    let mut remaining = elevator_data.clone();
+    loop {
+        remaining.states.retain(|id, state| room(id, state.capacity, load) > 0);
+        if remaining.states.is_empty() || !remaining.hall_requests.iter().flatten().any(|request| *request) {
+            return Ok(assignment);
+        }
+
+        let mut overflow = false;
+        for (id, hall_requests) in assign(&remaining)? {
+            let Some(state) = remaining.states.get_mut(&id) else { continue };
+            let mut calls = (0..n_floors)
+                .flat_map(|floor| [(floor, HALL_UP as usize), (floor, HALL_DOWN as usize)])
+                .filter(|(floor, call)| {
+                    hall_requests.get(*floor).and_then(|calls| calls.get(*call)).copied().unwrap_or(false)
+                        && remaining.hall_requests[*floor][*call]
+                })
+                .collect::<Vec<(usize, usize)>>();
+
+            let room = room(&id, state.capacity, load);
+            if calls.len() > room {
+                overflow = true;
+                calls.sort_by_key(|(floor, _)| floor.abs_diff(state.floor as usize));
+                calls.truncate(room);
+            }
+
+            // The next round sees the kept calls as stops the elevator has committed to
+            for (floor, call) in calls {
+                assignment.entry(id.clone()).or_insert_with(|| vec![vec![false; 2]; n_floors])[floor][call] = true;
+                remaining.hall_requests[floor][call] = false;
+                if let Some(cab_request) = state.cab_requests.get_mut(floor) {
+                    *cab_request = true;
+                }
+                *load.entry(id.clone()).or_default() += 1;
+            }
+        }
+
+        if !overflow {
+            return Ok(assignment);
+        }
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Brings an elevator to the first point it can decide from
+fn perform_initial_move(elevator: &mut SimulatedElevator, requests: &mut [[HallRequest; 2]]) -> Result<(), String> {
+    match elevator.behaviour {
+        Behaviour::DoorOpen | Behaviour::Idle => {
+            if elevator.behaviour == Behaviour::DoorOpen {
+                elevator.time += DOOR_OPEN_DURATION / 2;
+            }
+            for request in requests[elevator.floor].iter_mut() {
+                if request.active {
+                    request.assigned_to = Some(elevator.id.clone());
+                    elevator.time += DOOR_OPEN_DURATION;
+                }
+            }
+        }
+        Behaviour::Moving => {
+            elevator.floor = next_floor(elevator, requests.len())?;
+            elevator.time += TRAVEL_DURATION / 2;
+        }
+        Behaviour::Error => return Err(format!("Elevator {} is in error state", elevator.id)),
+    }
+    Ok(())
+}
+
+// Takes the next step of an elevator, stopping for the unassigned hall requests and its own cab requests
+fn perform_single_move(elevator: &mut SimulatedElevator, requests: &mut [[HallRequest; 2]]) -> Result<(), String> {
+    let mut visible = requests
+        .iter()
+        .zip(elevator.cab_requests.iter())
+        .map(|(hall_requests, cab_request)| {
+            let unassigned = |request: &HallRequest| request.active && request.assigned_to.is_none();
+            [unassigned(&hall_requests[0]), unassigned(&hall_requests[1]), *cab_request]
+        })
+        .collect::<Vec<[bool; 3]>>();
+
+    match elevator.behaviour {
+        Behaviour::Moving => {
+            if should_stop(&visible, elevator.floor, &elevator.direction) {
+                elevator.behaviour = Behaviour::DoorOpen;
+                elevator.time += DOOR_OPEN_DURATION;
+                clear_requests_at_floor(elevator, &mut visible, requests);
+            } else {
+                elevator.floor = next_floor(elevator, requests.len())?;
+                elevator.time += TRAVEL_DURATION;
+            }
+        }
+        Behaviour::Idle | Behaviour::DoorOpen => {
+            elevator.direction = choose_direction(&visible, elevator.floor, &elevator.direction);
+            if elevator.direction == Direction::Stop {
+                if visible[elevator.floor].iter().any(|request| *request) {
+                    clear_requests_at_floor(elevator, &mut visible, requests);
+                    elevator.time += DOOR_OPEN_DURATION;
+                    elevator.behaviour = Behaviour::DoorOpen;
+                } else {
+                    elevator.behaviour = Behaviour::Idle;
+                }
+            } else {
+                elevator.behaviour = Behaviour::Moving;
+                elevator.time += TRAVEL_DURATION;
+                elevator.floor = next_floor(elevator, requests.len())?;
+            }
+        }
+        Behaviour::Error => return Err(format!("Elevator {} is in error state", elevator.id)),
+    }
+    Ok(())
+}
+
+// Clears the requests the elevator serves at its floor, taking the hall requests it clears
+fn clear_requests_at_floor(
+    elevator: &mut SimulatedElevator,
+    visible: &mut [[bool; 3]],
+    requests: &mut [[HallRequest; 2]],
+) {
+    let floor = elevator.floor;
+    let mut clear = |call: u8, visible: &mut [[bool; 3]]| {
+        if !visible[floor][call as usize] {
+            return;
+        }
+        visible[floor][call as usize] = false;
+        if call == CAB {
+            elevator.cab_requests[floor] = false;
+        } else {
+            requests[floor][call as usize].assigned_to = Some(elevator.id.clone());
+        }
+    };
+
+    clear(CAB, visible);
+    match elevator.direction {
+        Direction::Up => {
+            if visible[floor][HALL_UP as usize] {
+                clear(HALL_UP, visible);
+            } else if !requests_above(visible, floor) {
+                clear(HALL_DOWN, visible);
+            }
+        }
+        Direction::Down => {
+            if visible[floor][HALL_DOWN as usize] {
+                clear(HALL_DOWN, visible);
+            } else if !requests_below(visible, floor) {
+                clear(HALL_UP, visible);
+            }
+        }
+        Direction::Stop => {
+            clear(HALL_UP, visible);
+            clear(HALL_DOWN, visible);
+        }
+    }
+}
+
+fn should_stop(visible: &[[bool; 3]], floor: usize, direction: &Direction) -> bool {
+    let at_end = floor == 0 || floor == visible.len() - 1;
+    match direction {
+        Direction::Up => {
+            visible[floor][HALL_UP as usize] || visible[floor][CAB as usize] || !requests_above(visible, floor) || at_end
+        }
+        Direction::Down => {
+            visible[floor][HALL_DOWN as usize] || visible[floor][CAB as usize] || !requests_below(visible, floor) || at_end
+        }
+        Direction::Stop => true,
+    }
+}
+
+fn choose_direction(visible: &[[bool; 3]], floor: usize, direction: &Direction) -> Direction {
+    let here = visible[floor].iter().any(|request| *request);
+    match direction {
+        Direction::Up if requests_above(visible, floor) => Direction::Up,
+        Direction::Up if here => Direction::Stop,
+        Direction::Up if requests_below(visible, floor) => Direction::Down,
+        Direction::Up => Direction::Stop,
+        _ if requests_below(visible, floor) => Direction::Down,
+        _ if here => Direction::Stop,
+        _ if requests_above(visible, floor) => Direction::Up,
+        _ => Direction::Stop,
+    }
+}
+
+fn requests_above(visible: &[[bool; 3]], floor: usize) -> bool {
+    visible[floor + 1..].iter().flatten().any(|request| *request)
+}
+
+fn requests_below(visible: &[[bool; 3]], floor: usize) -> bool {
+    visible[..floor].iter().flatten().any(|request| *request)
+}
+
+// The floor a moving elevator arrives at next
+fn next_floor(elevator: &SimulatedElevator, n_floors: usize) -> Result<usize, String> {
+    let floor = match elevator.direction {
+        Direction::Up => elevator.floor.checked_add(1).filter(|floor| *floor < n_floors),
+        Direction::Down => elevator.floor.checked_sub(1),
+        Direction::Stop => Some(elevator.floor),
+    };
+    floor.ok_or(format!("Elevator {} moves out of the building from floor {}", elevator.id, elevator.floor))
+}
+
+fn any_unassigned(requests: &[[HallRequest; 2]]) -> bool {
+    requests.iter().flatten().any(|request| request.active && request.assigned_to.is_none())
+}
+
+// Whether every unassigned hall request is at the floor of an elevator without cab requests
+fn unassigned_are_immediately_assignable(requests: &[[HallRequest; 2]], elevators: &[SimulatedElevator]) -> bool {
+    let has_cab_requests = |elevator: &SimulatedElevator| elevator.cab_requests.iter().any(|request| *request);
+    if elevators.iter().any(has_cab_requests) {
+        return false;
+    }
+    for (floor, requests_at_floor) in requests.iter().enumerate() {
+        if requests_at_floor.iter().all(|request| request.active) {
+            return false;
+        }
+        for request in requests_at_floor.iter() {
+            if request.active
+                && request.assigned_to.is_none()
+                && !elevators.iter().any(|elevator| elevator.floor == floor && !has_cab_requests(elevator))
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn assign_immediately(requests: &mut [[HallRequest; 2]], elevators: &mut [SimulatedElevator]) {
+    for (floor, requests_at_floor) in requests.iter_mut().enumerate() {
+        for request in requests_at_floor.iter_mut() {
+            for elevator in elevators.iter_mut() {
+                if request.active
+                    && request.assigned_to.is_none()
+                    && elevator.floor == floor
+                    && !elevator.cab_requests.iter().any(|request| *request)
+                {
+                    request.assigned_to = Some(elevator.id.clone());
+                    elevator.time += DOOR_OPEN_DURATION;
+                }
+            }
+        }
+    }
+}
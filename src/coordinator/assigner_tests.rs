@@ -0,0 +1,203 @@
+/*
+ * Unit tests for the in-process hall request assigner
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * The expected assignments are those of the reference executable.
+ *
+ * Tests:
+ *  - test_assigner_closest_elevator
+ *  - test_assigner_moving_elevator
+ *  - test_assigner_tie_at_floor
+ *  - test_assigner_invalid_input
+ *  - test_assigner_capacity
+ *  - test_assigner_matches_reference
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod assigner_tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+    use crate::coordinator::assigner::{assign_hall_requests, assign_within_capacity};
+    use crate::coordinator::coordinator::{execute_hall_request_assigner, hall_request_assigner_input, HALL_REQUEST_ASSIGNER};
+    use crate::demo::demo::Random;
+    use crate::shared::{Behaviour, Direction, ElevatorData, ElevatorState};
+
+    const N_FLOORS: u8 = 4;
+    const REFERENCE_CASES: usize = 300;
+
+    fn state(behaviour: Behaviour, floor: u8, direction: Direction, cab_floors: &[usize]) -> ElevatorState {
+        let mut state = ElevatorState::new(N_FLOORS);
+        state.behaviour = behaviour;
+        state.floor = floor;
+        state.direction = direction;
+        for floor in cab_floors {
+            state.cab_requests[*floor] = true;
+        }
+        state
+    }
+
+    fn hall_requests(calls: &[(usize, usize)]) -> Vec<Vec<bool>> {
+        let mut hall_requests = vec![vec![false; 2]; N_FLOORS as usize];
+        for (floor, call) in calls {
+            hall_requests[*floor][*call] = true;
+        }
+        hall_requests
+    }
+
+    #[test]
+    fn test_assigner_closest_elevator() {
+        // Arrange
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.hall_requests = hall_requests(&[(2, 1)]);
+        elevator_data.states.insert("a".to_string(), state(Behaviour::Idle, 0, Direction::Stop, &[]));
+        elevator_data.states.insert("b".to_string(), state(Behaviour::DoorOpen, 3, Direction::Stop, &[]));
+
+        // Act
+        let assignment = assign_hall_requests(&elevator_data).unwrap();
+
+        // Assert
+        assert_eq!(assignment["a"], hall_requests(&[]));
+        assert_eq!(assignment["b"], hall_requests(&[(2, 1)]));
+    }
+
+    #[test]
+    fn test_assigner_moving_elevator() {
+        // Arrange
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.hall_requests = hall_requests(&[(1, 0), (3, 1)]);
+        elevator_data.states.insert("one".to_string(), state(Behaviour::Moving, 2, Direction::Up, &[3]));
+        elevator_data.states.insert("two".to_string(), state(Behaviour::Idle, 0, Direction::Stop, &[]));
+
+        // Act
+        let assignment = assign_hall_requests(&elevator_data).unwrap();
+
+        // Assert
+        // The moving elevator takes the call at the floor it is headed for anyway
+        assert_eq!(assignment["one"], hall_requests(&[(3, 1)]));
+        assert_eq!(assignment["two"], hall_requests(&[(1, 0)]));
+    }
+
+    #[test]
+    fn test_assigner_tie_at_floor() {
+        // Arrange
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.hall_requests = hall_requests(&[(0, 0)]);
+        elevator_data.states.insert("a".to_string(), state(Behaviour::Idle, 0, Direction::Stop, &[]));
+        elevator_data.states.insert("b".to_string(), state(Behaviour::Idle, 0, Direction::Stop, &[]));
+
+        // Act
+        let assignment = assign_hall_requests(&elevator_data).unwrap();
+
+        // Assert
+        // Like the reference, the last of the elevators at the floor takes the call
+        assert_eq!(assignment["a"], hall_requests(&[]));
+        assert_eq!(assignment["b"], hall_requests(&[(0, 0)]));
+    }
+
+    #[test]
+    fn test_assigner_invalid_input() {
+        // Arrange
+        let mut outside = ElevatorData::new(N_FLOORS);
+        outside.states.insert("a".to_string(), state(Behaviour::Idle, N_FLOORS, Direction::Stop, &[]));
+        let mut wrong_cab_requests = ElevatorData::new(N_FLOORS);
+        wrong_cab_requests.states.insert("a".to_string(), ElevatorState::new(N_FLOORS + 1));
+        let mut out_of_the_top = ElevatorData::new(N_FLOORS);
+        out_of_the_top.states.insert("a".to_string(), state(Behaviour::Moving, N_FLOORS - 1, Direction::Up, &[]));
+
+        // Act / Assert
+        assert!(assign_hall_requests(&outside).is_err());
+        assert!(assign_hall_requests(&wrong_cab_requests).is_err());
+        assert!(assign_hall_requests(&out_of_the_top).is_err());
+    }
+
+    #[test]
+    fn test_assigner_capacity() {
+        // Arrange
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.hall_requests = hall_requests(&[(0, 0), (1, 0), (2, 0)]);
+        elevator_data.states.insert("a".to_string(), state(Behaviour::Idle, 0, Direction::Stop, &[]));
+        elevator_data.states.insert("b".to_string(), state(Behaviour::Idle, 3, Direction::Stop, &[]));
+        let uncapped = assign_hall_requests(&elevator_data).unwrap();
+        elevator_data.states.get_mut("a").unwrap().capacity = 1;
+        let mut full = elevator_data.clone();
+        full.states.get_mut("b").unwrap().capacity = 2;
+
+        // Act
+        let mut load = HashMap::new();
+        let capped = assign_within_capacity(&elevator_data, &mut load, assign_hall_requests).unwrap();
+        let mut full_load = HashMap::from([("b".to_string(), 2)]);
+        let overflowed = assign_within_capacity(&full, &mut full_load, assign_hall_requests).unwrap();
+
+        // Assert
+        assert_eq!(uncapped["a"], hall_requests(&[(0, 0), (2, 0)]));
+        // The elevator keeps the call closest to it, and the rest overflows
+        assert_eq!(capped["a"], hall_requests(&[(0, 0)]));
+        assert_eq!(capped["b"], hall_requests(&[(1, 0), (2, 0)]));
+        assert_eq!(load, HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]));
+        // A full elevator takes nothing more, and calls nobody has room for wait
+        assert_eq!(overflowed["a"], hall_requests(&[(0, 0)]));
+        assert_eq!(overflowed["b"], hall_requests(&[]));
+    }
+
+    #[test]
+    fn test_assigner_matches_reference() {
+        // Arrange
+        // Without the reference executable, as on a machine it does not run on, there is nothing to compare with
+        if !Path::new(HALL_REQUEST_ASSIGNER).exists() {
+            return;
+        }
+        let mut random = Random::new(3751);
+        let mut draw = |n: usize| (random.next_f64() * n as f64) as usize;
+        let cases = (0..REFERENCE_CASES)
+            .map(|_| {
+                let n_floors = 2 + draw(7) as u8;
+                let mut elevator_data = ElevatorData::new(n_floors);
+                for requests in elevator_data.hall_requests.iter_mut() {
+                    requests.iter_mut().for_each(|requested| *requested = draw(3) == 0);
+                }
+                for index in 0..1 + draw(3) {
+                    let mut state = ElevatorState::new(n_floors);
+                    state.floor = draw(n_floors as usize) as u8;
+                    let idle = draw(3) == 0;
+                    state.behaviour = match idle {
+                        true => Behaviour::Idle,
+                        false => [Behaviour::Moving, Behaviour::DoorOpen][draw(2)].clone(),
+                    };
+                    state.direction = match (idle, state.floor) {
+                        (true, _) => Direction::Stop,
+                        (false, 0) => Direction::Up,
+                        (false, floor) if floor == n_floors - 1 => Direction::Down,
+                        _ => [Direction::Up, Direction::Down][draw(2)].clone(),
+                    };
+                    state.cab_requests.iter_mut().for_each(|requested| *requested = draw(4) == 0);
+                    elevator_data.states.insert(format!("elevator-{}", index), state);
+                }
+                elevator_data
+            })
+            .collect::<Vec<ElevatorData>>();
+
+        // Act
+        // The executable gives the cab calls of each elevator too, after its hall calls
+        let reference = |elevator_data: &ElevatorData| {
+            execute_hall_request_assigner(elevator_data).map(|assignment| {
+                assignment
+                    .into_iter()
+                    .map(|(id, requests)| (id, requests.into_iter().map(|calls| calls.into_iter().take(2).collect()).collect()))
+                    .collect::<HashMap<String, Vec<Vec<bool>>>>()
+            })
+        };
+        let differing = cases
+            .iter()
+            .filter(|elevator_data| assign_hall_requests(elevator_data) != reference(elevator_data))
+            .collect::<Vec<&ElevatorData>>();
+
+        // Assert
+        // Every assignment is that of the reference
+        let first = differing.first().map(|elevator_data| hall_request_assigner_input(elevator_data));
+        assert_eq!(differing.len(), 0, "First differing input: {:?}", first);
+    }
+}
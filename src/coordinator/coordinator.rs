@@ -2,9 +2,32 @@
  * Manages coordination between different elevators.
  *
  * The coordinator is responsible for making sure each elevator is assigned different hall requests. 
- * It uses the executable "hall_request_assigner" for assigning the different elevators. 
+ * It assigns the hall calls by the strategy selected, by default the in-process reimplementation of the executable "hall_request_assigner". 
  * Because of network loss the coordinator for different elevators might sit on different information.
  * Therefore there might arise merge-conflits. It uses the "MergeType" enum type to determine the next course of action. 
+ * The data carries a vector clock of the updates transmitted by every elevator. Data ahead of the local data is
+ * accepted, data behind it rejected, and concurrent data, updated apart in two partitions, is merged the same on every elevator.
+ * In consensus mode the hall calls are instead taken from the replicated log of the consensus, and are left out of the merge.
+ * Elevators that lose each other keep serving calls apart. When they meet again, the packages of the other group are
+ * reconciled for a while instead of merged: the hall calls are unioned, except those completed after they were placed.
+ * Hall calls are pending until confirmed. A call is confirmed, lit and assigned once a quorum of the known elevators
+ * has acknowledged it, by adding its ID to the replicated acknowledgements of the call.
+ * In the push model only one coordinator assigns the hall calls: the consensus leader in consensus mode, and otherwise
+ * the elevator with the lowest ID of those known. It pushes the calls of every elevator with its data, and the other
+ * coordinators serve the calls pushed to them, so the elevators never disagree on an assignment for having seen
+ * different inputs. The pushed calls are only served while confirmed, so a served call stops at once.
+ * While the network measures a loss beyond the threshold of the adaptation policy, the coordinator serves
+ * conservatively: the local elevator also serves the hall calls pressed at its own panel, pending or assigned to
+ * another elevator, in case the others never hear of them.
+ * In a dry run the coordinator computes the assignments, merges and lights as usual, but only logs what it would
+ * have the lights and the FSM do: the FSM is given no hall calls, cab calls or parking floor, so the car never moves.
+ * Its state is broadcast marked as a dry run, so the peers assign it no hall calls and never wait on it to push an
+ * assignment, and it neither claims the hall calls it would serve nor pushes its assignment. This validates a new
+ * assigner or merge change against the live traffic of a fleet before it is trusted with the motor.
+ * Received packages are checked against hard limits before anything else. A package that fails is quarantined:
+ * it is logged and dropped, so one malformed package cannot spread to the local data and on to the fleet.
+ * The hall calls may be assigned on a worker thread, so a burst of button presses does not hold up the other events.
+ * An assignment made stale by newer input is discarded.
  * The coordinator communicates with the network, hardware and fsm module. 
  *
  *
@@ -13,40 +36,123 @@
  * - `hw_request_rx`:           Receives recuests from local elevator buttons. 
  * - `fsm_hall_requests_tx`:    Sends hall requests to the FSM.
  * - `fsm_cab_request_tx`:      Sends cab requests to the FSM.
+ * - `fsm_parking_floor_tx`:    Sends the floor idle elevators should park at to the FSM.
+ * - `fsm_operating_mode_tx`:   Sends the operating mode of the local elevator to the FSM.
+ * - `fsm_door_command_tx`:     Sends door commands in fire service phase 2 to the FSM.
+ * - `fsm_alarm_clear_tx`:      Clears the obstruction alarm of the FSM.
+ * - `fsm_halt_tx`:             Halts the FSM after its current stop, or resumes it.
+ * - `fsm_inspect_tx`:          Queries the FSM for its internal view, printed on the debug console.
+ * - `fsm_blocked_floors_tx`:    Sends the floors the local elevator may not serve to the FSM, when the zoning changes.
+ * - `fsm_locked_floors_tx`:     Sends the floors locked out to the FSM, which drops the cab calls to them.
  * - `fsm_state_rx`:            Receives the current state of the local elevator.
  * - `fsm_order_complete_rx`:   Receives notifications of completed orders from the FSM.
  * - `net_data_send_tx`:        Broadcasts the ElevatorData to the network.
  * - `net_data_recv_rx`:        Receives the broadcasted ElevatorData from the network.
+ * - `net_sync_recv_rx`:        Receives the ElevatorData of peers found to differ by anti-entropy. It is always merged.
  * - `net_peer_update_rx`:      Receives updates of the peer list from the network.
- * - `coordinator_terminate_rx` Receives a signal to terminate the coordinator thread. Used for testing.
+ * - `net_peer_version_rx`:     Receives the versions announced by the peers, logged when they differ from the local one.
+ * - `net_consensus_send_tx`:   Sends consensus messages to the network, addressed by peer ID.
+ * - `net_consensus_recv_rx`:   Receives consensus messages from the network, with the sender's ID.
+ * - `net_quality_rx`:          Receives the loss and round-trip time measured by the network.
+ * - `coordinator_command_rx`:  Receives operator commands from the debug console.
+ * - `coordinator_query_rx`:    Receives queries from the control interfaces, each answered with a snapshot on the sender it carries.
+ * - `coordinator_terminate_rx` Receives the request to terminate the coordinator thread, acknowledged before it returns.
  * - `ElevatorData`:            Contains hall requests and states for all of the elevators.
  * - `local_id`:                Contains the id of the local elevator.
  * - `n_floors`:                The number of floors serviced by the elevator.
+ * - `floor_labels`:            The labels the floors are logged and shown by.
+ * - `access_control`:          Restricted floors, the elevators authorized to serve them and the zones of the elevators.
+ * - `lockout_schedule`:        Time windows in which floors are closed. Requests to or from closed floors are refused.
+ * - `locked_floors`:           The floors closed at the time of day of the clock. Their hall calls are held dark and unassigned.
+ * - `traffic_policy`:          Scheduled traffic modes, their parking floors and priority hall calls.
+ * - `traffic_mode`:            The traffic mode in effect. An operator override in `ElevatorData` beats the schedule.
+ * - `repositioning_policy`:    Sectors idle elevators are spread across.
+ * - `sector_floor`:            The sector the local elevator holds while idle, if any.
+ * - `parking_floor`:           The parking floor last sent to the FSM. The traffic mode beats the sector.
+ * - `fire_recall_floor`:       Floor the elevators are recalled to in fire service phase 1.
+ * - `operating_mode`:          The operating mode of the local elevator.
+ * - `hall_call_cancel_window`: A hall button pressed twice within the window cancels its un-served call.
+ * - `hall_call_presses`:       When each hall button of the local elevator was last pressed.
+ * - `hall_call_quorum`:        Elevators, counting the local one and capped at those known, to acknowledge a hall call before it is confirmed.
+ * - `hall_call_confirmation`:  Whether a hall call is confirmed by the quorum, or by every elevator known.
+ * - `hall_call_deadline`:      How long a confirmed hall call may wait before its elevator is taken to have failed it. Zero disables. Kept by the assigning coordinator of the push model only.
+ * - `hall_call_deadlines`:     When each confirmed hall call is next overdue.
+ * - `overdue_elevators`:       Elevators that let a hall call pass its deadline, left out of the assignment until the time given.
+ * - `hall_clearing`:           Whether a served hall call clears only itself, or both hall calls at its floor on the whole fleet.
+ * - `assigner`:                Assigns the hall calls by the strategy selected, on the spot or in the background.
+ * - `pending_transmit`:        Whether to transmit once the assignment running in the background is applied.
+ * - `assignment_model`:        Whether every coordinator assigns the hall calls, or only one pushes its assignment.
+ * - `adaptation_policy`:       When the network is lossy enough to serve conservatively.
+ * - `conservative`:            Whether the local elevator serves the hall calls pressed at its panel itself.
+ * - `observed_hall_calls`:     The hall calls pressed at the local panel, until served or cancelled.
+ * - `confirmed_hall_requests`: The hall calls confirmed by a quorum. Only these are lit and assigned.
+ * - `assigned_hall_requests`:  The hall calls last sent to the FSM, compared with its view when inspected.
+ * - `lost_peers`:              Elevators lost while the local elevator kept running. They have operated apart if they return.
+ * - `reconciliation_window`:   How long packages are reconciled after elevators that operated apart have met.
+ * - `reconciliation_deadline`: Packages are reconciled until the deadline.
+ * - `consensus`:               The hall-order consensus in consensus mode. New calls are lit once committed.
+ * - `peers`:                   The peers last reported by the network.
+ * - `network_counters`:        Datagrams handled by the network, reported in snapshots.
+ * - `uptime`:                  The restarts and the uptime of the local elevator, reported in snapshots and to peers.
+ * - `clock`:                   The time the cancel window, reconciliation, consensus and schedules are timed by, real or simulated.
+ * - `wal`:                     The write-ahead log order mutations are logged to before they take effect, if enabled.
+ * - `hall_assignments`:        The hall calls last assigned to each elevator, by the local coordinator.
+ * - `reassignments`:           Hall calls moved from one elevator to another since start, reported in snapshots.
+ * - `dry_run`:                 Whether the decisions are only logged, and the lights and the FSM left alone.
+ * - `failure`:                 The first failure met while handling an event. It stops the coordinator once the event is handled.
  */
 
 /***************************************/
 /*             Libraries               */
 /***************************************/
 use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
-use log::{info, error};
+use log::{info, warn, error};
 use network_rust::udpnet::peers::PeerUpdate;
-use std::{collections::HashMap, process::Command};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, process::Command, sync::Arc, time::{Duration, Instant}};
 use crossbeam_channel as cbc;
+use chrono::{Local, Utc};
 
 /***************************************/
 /*           Local modules             */
 /***************************************/
-use crate::shared::{Behaviour, Direction, ElevatorData, ElevatorState};
+use crate::shared::{AccessControl, Behaviour, ControlCommand, Direction, DoorState, ElevatorData, ElevatorState, LockoutSchedule};
+use crate::shared::{Consensus, ConsensusMessage, HallOperation, OperatingMode, RepositioningPolicy, Snapshot, StateExport, TrafficMode, TrafficPolicy, Uptime, UptimeCounter};
+use crate::shared::{AdaptationPolicy, AssignmentModel, Clock, FsmView, HallAssignment, HallCallConfirmation, HallClearing, ModuleError, NetworkQuality, PeerVersion, RealClock};
+use crate::shared::{attached, event_loop, join_hall_orders, requested_hall_calls, Causality, EventLoop, FloorLabels, HallOrder, Terminate};
+use crate::network::NetworkCounters;
+use crate::coordinator::strategy::{AssignmentStrategy, CostFunction};
+use crate::coordinator::worker::{AssignmentJob, AssignmentWorker, CallGroup, HallAssignments};
+use crate::storage::{OrderMutation, WriteAheadLog};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const TRAFFIC_MODE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const CONSENSUS_TICK_INTERVAL: Duration = Duration::from_millis(10);
+const ORDER_WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+const RECONCILIATION_WINDOW: Duration = Duration::from_secs(2);
+const MAX_ELEVATORS: usize = 64;
+// The reference executable run by the process assigner
+pub const HALL_REQUEST_ASSIGNER: &str = "./src/coordinator/hall_request_assigner";
+const INSPECT_TIMEOUT: Duration = Duration::from_secs(1);
+// Far more changes than a fleet makes in its lifetime, while still far from overflowing the version
+const MAX_VERSION_JUMP: u64 = 1 << 32;
 
 /***************************************/
 /*               Enums                 */
 /***************************************/
 pub enum Event {
     NewPackage(ElevatorData),
+    SyncPackage(ElevatorData),
     RequestReceived((u8, u8)),
     NewPeerUpdate(PeerUpdate),
+    PeerVersionReceived((String, Option<PeerVersion>)),
     NewElevatorState(ElevatorState),
     OrderComplete((u8, u8)),
+    CommandReceived(ControlCommand),
+    ConsensusReceived((String, ConsensusMessage)),
+    QualityReceived(NetworkQuality),
+    QueryReceived(cbc::Sender<Snapshot>),
 }
 
 #[derive(PartialEq, Debug)]
@@ -61,10 +167,50 @@ pub enum MergeType {
 /***************************************/
 pub struct Coordinator {
     // Private fields
-    coordinator_terminate_rx: cbc::Receiver<()>,
+    coordinator_terminate_rx: cbc::Receiver<Terminate>,
     elevator_data: ElevatorData,
     local_id: String,
     n_floors: u8,
+    floor_labels: FloorLabels,
+    access_control: AccessControl,
+    lockout_schedule: LockoutSchedule,
+    locked_floors: Vec<u8>,
+    traffic_policy: TrafficPolicy,
+    traffic_mode: TrafficMode,
+    repositioning_policy: RepositioningPolicy,
+    sector_floor: Option<u8>,
+    parking_floor: Option<u8>,
+    fire_recall_floor: u8,
+    operating_mode: OperatingMode,
+    hall_call_cancel_window: Duration,
+    hall_call_presses: Vec<Vec<Option<Instant>>>,
+    hall_call_quorum: usize,
+    hall_call_confirmation: HallCallConfirmation,
+    hall_call_deadline: Duration,
+    hall_call_deadlines: Vec<Vec<Option<Instant>>>,
+    overdue_elevators: BTreeMap<String, Instant>,
+    hall_clearing: HallClearing,
+    assigner: AssignmentWorker,
+    pending_transmit: bool,
+    assignment_model: AssignmentModel,
+    adaptation_policy: AdaptationPolicy,
+    conservative: bool,
+    observed_hall_calls: Vec<Vec<bool>>,
+    confirmed_hall_requests: Vec<Vec<bool>>,
+    assigned_hall_requests: Vec<Vec<bool>>,
+    lost_peers: HashSet<String>,
+    reconciliation_window: Duration,
+    reconciliation_deadline: Option<Instant>,
+    consensus: Option<Consensus>,
+    peers: Vec<String>,
+    network_counters: Arc<NetworkCounters>,
+    uptime: Arc<UptimeCounter>,
+    clock: Arc<dyn Clock>,
+    wal: Option<WriteAheadLog>,
+    hall_assignments: BTreeMap<String, Vec<Vec<bool>>>,
+    reassignments: u64,
+    dry_run: bool,
+    failure: Option<ModuleError>,
 
     // Hardware channels
     hw_button_light_tx: cbc::Sender<(u8, u8, bool)>,
@@ -73,195 +219,236 @@ pub struct Coordinator {
     // FSM channels
     fsm_hall_requests_tx: cbc::Sender<Vec<Vec<bool>>>,
     fsm_cab_request_tx: cbc::Sender<u8>,
+    fsm_parking_floor_tx: cbc::Sender<Option<u8>>,
+    fsm_operating_mode_tx: cbc::Sender<OperatingMode>,
+    fsm_door_command_tx: cbc::Sender<bool>,
+    fsm_alarm_clear_tx: cbc::Sender<()>,
+    fsm_halt_tx: cbc::Sender<bool>,
+    fsm_inspect_tx: cbc::Sender<cbc::Sender<FsmView>>,
+    fsm_blocked_floors_tx: Option<cbc::Sender<Vec<u8>>>,
+    fsm_locked_floors_tx: Option<cbc::Sender<Vec<u8>>>,
     fsm_state_rx: cbc::Receiver<ElevatorState>,
     fsm_order_complete_rx: cbc::Receiver<(u8, u8)>,
 
     // Network channels
     net_data_send_tx: cbc::Sender<ElevatorData>,
     net_data_recv_rx: cbc::Receiver<ElevatorData>,
+    net_sync_recv_rx: cbc::Receiver<ElevatorData>,
     net_peer_update_rx: cbc::Receiver<PeerUpdate>,
+    net_peer_version_rx: cbc::Receiver<(String, Option<PeerVersion>)>,
+    net_consensus_send_tx: cbc::Sender<(String, ConsensusMessage)>,
+    net_consensus_recv_rx: cbc::Receiver<(String, ConsensusMessage)>,
+    net_quality_rx: cbc::Receiver<NetworkQuality>,
+
+    // Console channels
+    coordinator_command_rx: cbc::Receiver<ControlCommand>,
+    coordinator_query_rx: cbc::Receiver<cbc::Sender<Snapshot>>,
 }
 
 impl Coordinator {
-    pub fn new(
-        elevator_data: ElevatorData,
-        local_id: String,
-        n_floors: u8,
-
-        hw_button_light_tx: cbc::Sender<(u8, u8, bool)>,
-        hw_request_rx: cbc::Receiver<(u8, u8)>,
-
-        fsm_hall_requests_tx: cbc::Sender<Vec<Vec<bool>>>,
-        fsm_cab_request_tx: cbc::Sender<u8>,
-        fsm_state_rx: cbc::Receiver<ElevatorState>,
-        fsm_order_complete_rx: cbc::Receiver<(u8, u8)>,
-
-        net_data_send_tx: cbc::Sender<ElevatorData>,
-        net_data_recv_rx: cbc::Receiver<ElevatorData>,
-        net_peer_update_rx: cbc::Receiver<PeerUpdate>,
-
-        coordinator_terminate_rx: cbc::Receiver<()>,
-    ) -> Coordinator {
-        Coordinator {
-            // Private fields
-            coordinator_terminate_rx,
-            elevator_data,
-            local_id,
-            n_floors,
+    // Runs the coordinator until terminated, or until a channel is disconnected or the hall calls cannot be assigned
+    pub fn run(&mut self) -> Result<(), ModuleError> {
+        let traffic_mode_ticker = cbc::tick(TRAFFIC_MODE_CHECK_INTERVAL);
+        let order_watchdog_ticker = match self.hall_call_deadline.is_zero() {
+            true => cbc::never(),
+            false => cbc::tick(ORDER_WATCHDOG_INTERVAL),
+        };
+        let consensus_ticker = match self.consensus {
+            Some(_) => cbc::tick(CONSENSUS_TICK_INTERVAL),
+            None => cbc::never(),
+        };
+        if self.update_traffic_mode() {
+            self.hall_request_assigner(false);
+        }
+        if self.update_zoning() {
+            self.hall_request_assigner(false);
+        }
+        if self.update_lockout() {
+            self.hall_request_assigner(false);
+        }
+        self.resume_recovered_orders();
 
-            //Hardware channels
-            hw_button_light_tx,
-            hw_request_rx,
+        // Main loop
+        event_loop! {
+            self;
+            //Handling new package
+            recv(self.net_data_recv_rx) -> elevator_data => {
+                self.handle_event(Event::NewPackage(elevator_data))
+            }
 
-            // FSM channels
-            fsm_hall_requests_tx,
-            fsm_cab_request_tx,
-            fsm_state_rx,
-            fsm_order_complete_rx,
+            // Handling packages found to differ by anti-entropy
+            recv(self.net_sync_recv_rx) -> elevator_data => {
+                self.handle_event(Event::SyncPackage(elevator_data))
+            }
 
-            // Netowrk channels
-            net_data_recv_rx,
-            net_peer_update_rx,
-            net_data_send_tx,
-        }
-    }
+            //Hanlding peer update
+            recv(self.net_peer_update_rx) -> peer_update => {
+                self.handle_event(Event::NewPeerUpdate(peer_update))
+            }
 
-    pub fn run(&mut self) {
-        // Main loop
-        loop {
-            cbc::select! {
-                //Handling new package
-                recv(self.net_data_recv_rx) -> package => {
-                   match package {
-                        Ok(elevator_data) => self.handle_event(Event::NewPackage(elevator_data)),
-                        Err(e) => {
-                            error!("ERROR - net_data_recv_rx {:?}\r\n", e);
-                            std::process::exit(1);
-                        }
-                    }
-                },
-    
-                //Hanlding peer update
-                recv(self.net_peer_update_rx) -> peer => {
-                    match peer {
-                        Ok(peer_update) => self.handle_event(Event::NewPeerUpdate(peer_update)),
-                        Err(e) => {
-                            error!("ERROR - net_peer_update_rx {:?}\r\n", e);
-                            std::process::exit(1);
-                        }
-                    }
-                },
-    
-                //Handling new button press
-                recv(self.hw_request_rx) -> request => {
-                    match request {
-                        Ok(request) => self.handle_event(Event::RequestReceived(request)),
-                        Err(e) => {
-                            error!("ERROR - hw_request_rx {:?}\r\n", e);
-                            std::process::exit(1);
-                        }
-                    }
-                },
-    
-                // Handling new fsm state
-                recv(self.fsm_state_rx) -> state => {
-                    match state {
-                        Ok(state) => self.handle_event(Event::NewElevatorState(state)),
-                        Err(e) => {
-                            error!("ERROR - fsm_state_rx {:?}\r\n", e);
-                            std::process::exit(1);
-                        }
-                    }
-                },
-    
-                // Handling completed order from fsm
-                recv(self.fsm_order_complete_rx) -> completed_order => {
-                    match completed_order {
-                        Ok(finish_order) => self.handle_event(Event::OrderComplete(finish_order)),
-                        Err(e) => {
-                            error!("ERROR - fsm_order_complete_rx {:?}\r\n", e);
-                            std::process::exit(1);
-                        }
-                    }
+            // Checking the builds of the peers
+            recv(self.net_peer_version_rx) -> peer_version => {
+                self.handle_event(Event::PeerVersionReceived(peer_version))
+            }
+
+            //Handling new button press
+            recv(self.hw_request_rx) -> request => {
+                self.handle_event(Event::RequestReceived(request))
+            }
+
+            // Handling new fsm state
+            recv(self.fsm_state_rx) -> state => {
+                self.handle_event(Event::NewElevatorState(state))
+            }
+
+            // Handling completed order from fsm
+            recv(self.fsm_order_complete_rx) -> finish_order => {
+                self.handle_event(Event::OrderComplete(finish_order))
+            }
+
+            // Handling operator commands
+            recv(self.coordinator_command_rx) -> command => {
+                self.handle_event(Event::CommandReceived(command))
+            }
+
+            // Answering queries from the control interfaces
+            recv(self.coordinator_query_rx) -> reply_tx => {
+                self.handle_event(Event::QueryReceived(reply_tx))
+            }
+
+            // Handling consensus messages from peers
+            recv(self.net_consensus_recv_rx) -> message => {
+                self.handle_event(Event::ConsensusReceived(message))
+            }
+
+            // Adapting to the quality of the network
+            recv(self.net_quality_rx) -> quality => {
+                self.handle_event(Event::QualityReceived(quality))
+            }
+
+            // Applying the hall calls assigned in the background, unless newer input has made them stale
+            recv(self.assigner.result_rx) -> result => {
+                if self.assigner.is_current(&result) {
+                    let transmit = std::mem::take(&mut self.pending_transmit);
+                    self.apply_hall_assignments(result.assignment, transmit);
+                }
+            }
+
+            // Running elections and heartbeats of the consensus
+            tick(consensus_ticker) => {
+                if let Some(consensus) = self.consensus.as_mut() {
+                    let messages = consensus.tick(self.clock.now());
+                    self.send_consensus(messages);
+                    self.apply_committed();
+                }
+            }
+
+            // Reassigning the hall calls that have waited past their deadline
+            tick(order_watchdog_ticker) => {
+                if self.check_hall_call_deadlines() {
+                    self.hall_request_assigner(false);
                 }
-    
-                recv(self.coordinator_terminate_rx) -> _ => {
-                    break;
+            }
+
+            // Following the traffic mode and lockout schedules
+            tick(traffic_mode_ticker) => {
+                let traffic_mode_changed = self.update_traffic_mode();
+                if self.update_lockout() || traffic_mode_changed {
+                    self.hall_request_assigner(false);
                 }
-    
             }
         }
     }
 
-    fn handle_event(&mut self, event: Event) {
+    pub fn handle_event(&mut self, event: Event) {
         match event {
             Event::NewPackage(elevator_data) => {
+                if !self.package_is_sane(&elevator_data) {
+                    return;
+                }
+
+                // Right after a split brain, versions say nothing about which data is right
+                if self.reconciliation_deadline.is_some_and(|deadline| self.clock.now() < deadline) {
+                    self.reconcile_package(elevator_data);
+                    return;
+                }
+
                 let merge_type = self.check_merge_type(elevator_data.clone());
 
                 match merge_type {
                     MergeType::Accept => {
-                        //Updating lights
-                        let new_hall_request = elevator_data.hall_requests.clone();
-                        for floor in 0..self.n_floors {
-                            if new_hall_request[floor as usize][HALL_DOWN as usize]
-                                != self.elevator_data.hall_requests[floor as usize]
-                                    [HALL_DOWN as usize]
-                            {
-                                self.update_light((
-                                    floor,
-                                    HALL_DOWN,
-                                    new_hall_request[floor as usize][HALL_DOWN as usize],
-                                ));
-                            }
-                            if new_hall_request[floor as usize][HALL_UP as usize]
-                                != self.elevator_data.hall_requests[floor as usize]
-                                    [HALL_UP as usize]
-                            {
-                                self.update_light((
-                                    floor,
-                                    HALL_UP,
-                                    new_hall_request[floor as usize][HALL_UP as usize],
-                                ));
+                        //Writing the new changes to elevatorData. The lights follow once the calls are confirmed.
+                        // In consensus mode the hall calls come from the log only.
+                        if self.consensus.is_none() {
+                            self.elevator_data.hall_requests = elevator_data.hall_requests;
+                            self.elevator_data.hall_acknowledgements = elevator_data.hall_acknowledgements;
+                            if !elevator_data.hall_orders.is_empty() {
+                                join_hall_orders(&mut self.elevator_data.hall_orders, &elevator_data.hall_orders, self.n_floors);
+                                self.elevator_data.hall_requests = requested_hall_calls(&self.elevator_data.hall_orders);
                             }
                         }
-                        //Writing the new changes to elevatorData
-                        self.elevator_data.version = elevator_data.version;
-                        self.elevator_data.hall_requests = new_hall_request;
+                        self.elevator_data.clock.join(&elevator_data.clock);
+                        self.elevator_data.version = self.elevator_data.clock.total();
                         self.elevator_data.states = elevator_data.states;
-
-                        self.hall_request_assigner(false);
-                    }
-                    MergeType::Merge => {
-                        // Hall requests should be "OR"ed
-                        for floor in 0..self.n_floors {
-                            self.elevator_data.hall_requests[floor as usize][HALL_DOWN as usize] =
-                                self.elevator_data.hall_requests[floor as usize][HALL_DOWN as usize]
-                                    || elevator_data.hall_requests[floor as usize][HALL_DOWN as usize];
-                            self.elevator_data.hall_requests[floor as usize][HALL_UP as usize] =
-                                self.elevator_data.hall_requests[floor as usize][HALL_UP as usize]
-                                    || elevator_data.hall_requests[floor as usize][HALL_UP as usize];
+                        self.elevator_data.traffic_mode = elevator_data.traffic_mode;
+                        self.elevator_data.fire_recall = elevator_data.fire_recall;
+                        self.elevator_data.independent_service = elevator_data.independent_service;
+                        self.elevator_data.hall_cancellations = elevator_data.hall_cancellations;
+                        // The calls served by the local elevator are recorded again as they are assigned below
+                        self.elevator_data.hall_owners = elevator_data.hall_owners;
+                        self.adopt_hall_assignment(elevator_data.hall_assignment);
+                        let rezoned = self.elevator_data.zoning_profile != elevator_data.zoning_profile;
+                        self.elevator_data.zoning_profile = elevator_data.zoning_profile;
+
+                        self.update_traffic_mode();
+                        if rezoned {
+                            self.update_zoning();
                         }
-
-                        // Incoming states should overwrite existing states, but not the local state
-                        for (id, state) in elevator_data.states.iter() {
-                            if id != &self.local_id {
-                                self.elevator_data.states.insert(id.clone(), state.clone());
-                            }
-                        } 
+                        self.update_operating_mode();
+                        self.hall_request_assigner(false);
                     }
+                    MergeType::Merge => self.merge_package(elevator_data),
                     MergeType::Reject => {}
                 }
             }
 
+            Event::SyncPackage(elevator_data) => {
+                if !self.package_is_sane(&elevator_data) {
+                    return;
+                }
+
+                // The data differs from the local data whatever the clocks say, so it is merged.
+                // The result is transmitted with a clock ahead of both, for the rest of the fleet to accept.
+                self.merge_package(elevator_data);
+                self.hall_request_assigner(true);
+            }
+
             Event::NewPeerUpdate(peer_update) => {
                 let mut lost_elevators = peer_update.lost;
                 let mut new_elevators = peer_update.new;
                 info!("Peers: {:?}", peer_update.peers);
+                self.peers = peer_update.peers.clone();
+
+                // The consensus follows the peer list. Losing peers may let the remaining ones commit.
+                if let Some(consensus) = self.consensus.as_mut() {
+                    consensus.set_peers(peer_update.peers.clone());
+                    self.apply_committed();
+                }
 
                 //Removing dead elevators
                 for id in lost_elevators.iter_mut() {
                     if id != &self.local_id {
                         self.elevator_data.states.remove(id);
+                        self.elevator_data.hall_owners.remove(id);
+                        self.lost_peers.insert(id.clone());
+                    }
+                }
+
+                // An elevator lost earlier has been operating apart from the local one. In consensus mode the log decides.
+                if let Some(id) = new_elevators.as_ref() {
+                    if self.lost_peers.remove(id) && self.consensus.is_none() {
+                        info!("Split brain: {} rejoined after operating apart", id);
+                        self.reconciliation_deadline = Some(self.clock.now() + self.reconciliation_window);
                     }
                 }
 
@@ -274,6 +461,16 @@ impl Coordinator {
                             floor: 0,
                             direction: Direction::Stop,
                             cab_requests: vec![false; self.n_floors as usize],
+                            mode: OperatingMode::Normal,
+                            stop_plan: Vec::new(),
+                            obstruction_alarm: false,
+                            door: DoorState::Closed,
+                            halted: false,
+                            capacity: 0,
+                            arrival_times: Vec::new(),
+                            cab_completion_times: Vec::new(),
+                            uptime: Uptime::default(),
+                            dry_run: false,
                         },
                     );
                 }
@@ -288,7 +485,30 @@ impl Coordinator {
             }
 
             Event::RequestReceived(request) => {
+                if self.lockout_schedule.is_locked(request.0, self.clock.time_of_day()) {
+                    info!("Request {} to locked floor refused", self.floor_labels.call(request.0, request.1));
+                    return;
+                }
+
+                // Only cars in fire service phase 2 take calls during a fire recall
+                if self.elevator_data.fire_recall && !(request.1 == CAB && self.operating_mode == OperatingMode::FirePhase2) {
+                    info!("Request {} refused during fire recall", self.floor_labels.call(request.0, request.1));
+                    return;
+                }
+
                 if request.1 == CAB {
+                    // The FSM refuses the request, and signals the refusal on the cab light
+                    if !self.access_control.may_serve(&self.local_id, request.0) {
+                        info!("Cab request to floor {} not served by this elevator rejected", self.floor_labels.label(request.0));
+                        self.send_cab_request(request.0);
+                        return;
+                    }
+
+                    // A call that could not be logged might not survive a crash, so it is not taken
+                    if !self.log_mutation(OrderMutation::Add { floor: request.0, call: CAB }) {
+                        return;
+                    }
+
                     // Updating elevator data
                     self.elevator_data
                         .states
@@ -297,37 +517,70 @@ impl Coordinator {
                         .cab_requests[request.0 as usize] = true;
 
                     //Sending the change to the fsm
-                    self.fsm_cab_request_tx.send(request.0).expect("Failed to send cab request to fsm");
+                    self.send_cab_request(request.0);
 
                     self.update_light((request.0, CAB, true));
                 } 
                 
                 else if request.1 == HALL_DOWN || request.1 == HALL_UP {
+                    // Pressing the button of an un-served call again within the window cancels it
+                    let now = self.clock.now();
+                    let last_press = self.hall_call_presses[request.0 as usize][request.1 as usize];
+                    if self.elevator_data.hall_requests[request.0 as usize][request.1 as usize]
+                        && last_press.is_some_and(|pressed| now.duration_since(pressed) < self.hall_call_cancel_window)
+                    {
+                        self.cancel_hall_call(request.0, request.1);
+                        return;
+                    }
+                    self.hall_call_presses[request.0 as usize][request.1 as usize] = Some(now);
+
+                    // When the call was placed tells a new call from one completed apart after a split brain
+                    if !self.elevator_data.hall_requests[request.0 as usize][request.1 as usize] {
+                        resize_call_times(&mut self.elevator_data, self.n_floors);
+                        self.elevator_data.hall_request_times[request.0 as usize][request.1 as usize] = unix_time();
+                    }
+
+                    // In consensus mode the call is taken, and lit, once committed
+                    if self.consensus.is_some() {
+                        self.propose(HallOperation::Add(request.0, request.1));
+                        return;
+                    }
+
+                    if !self.log_mutation(OrderMutation::Add { floor: request.0, call: request.1 }) {
+                        return;
+                    }
+
                     //Updating hall requests
                     self.elevator_data.hall_requests[request.0 as usize][request.1 as usize] = true;
+                    self.observed_hall_calls[request.0 as usize][request.1 as usize] = true;
 
-                    // Calculating and sending to fsm
+                    // Calculating and sending to fsm. The call is lit once confirmed.
                     self.hall_request_assigner(true);
-
-                    self.update_light((request.0, request.1, true));
                 }
 
             }
 
             Event::NewElevatorState(elevator_state) => {
+                // The FSM closes the door before the car moves off
+                if elevator_state.behaviour == Behaviour::Moving && elevator_state.door != DoorState::Closed {
+                    error!("Elevator moving with the door {:?}", elevator_state.door);
+                }
+
                 // Checking for new cab requests
-                let current_cab_requests = &self.elevator_data.states[&self.local_id].cab_requests;
+                let current_cab_requests = self.elevator_data.states[&self.local_id].cab_requests.clone();
 
                 for floor in 0..self.n_floors {
-                    if !current_cab_requests[floor as usize] && elevator_state.cab_requests[floor as usize] {
+                    // A cab call the FSM held as a floor was locked out stays dark until it is dropped
+                    if !current_cab_requests[floor as usize] && elevator_state.cab_requests[floor as usize]
+                        && !self.locked_floors.contains(&floor) {
 
                         self.update_light((floor, CAB, true));
                     }
                 }
 
-                // Updating state elevator data
+                // Updating state elevator data, with the uptime the peers are told
                 if let Some(state) = self.elevator_data.states.get_mut(&self.local_id) {
-                    *state = elevator_state;
+                    *state = ElevatorState { uptime: self.uptime.uptime(), dry_run: self.dry_run, ..elevator_state };
                 }
 
                 self.hall_request_assigner(true);
@@ -338,174 +591,1847 @@ impl Coordinator {
                 info!("Order completed: {:?}", completed_order);
                 // Updating elevator data
                 if completed_order.1 == CAB {
+                    self.log_mutation(OrderMutation::Complete { floor: completed_order.0, call: CAB });
                     self.elevator_data
                         .states
                         .get_mut(&self.local_id)
                         .unwrap()
                         .cab_requests[completed_order.0 as usize] = false;
                 }
-                
-                if completed_order.1 == HALL_DOWN || completed_order.1 == HALL_UP {
-                    self.elevator_data.hall_requests[completed_order.0 as usize][completed_order.1 as usize] = false;
+
+                // With all hall calls cleared at a stop, everyone waiting at the floor boards the car.
+                // This also serves the calls assigned to other elevators.
+                let mut completed_hall_calls = Vec::new();
+                for call in [HALL_UP, HALL_DOWN] {
+                    let boarded = self.hall_clearing == HallClearing::All
+                        && self.elevator_data.hall_requests[completed_order.0 as usize][call as usize];
+                    if call == completed_order.1 || boarded {
+                        completed_hall_calls.push(call);
+                    }
+                }
+
+                for call in completed_hall_calls.iter().copied() {
+                    self.log_mutation(OrderMutation::Complete { floor: completed_order.0, call });
+                    self.elevator_data.hall_requests[completed_order.0 as usize][call as usize] = false;
+                    self.confirmed_hall_requests[completed_order.0 as usize][call as usize] = false;
+                    self.record_hall_completion(completed_order.0, call);
+                    if call != completed_order.1 {
+                        self.update_light((completed_order.0, call, false));
+                    }
                 }
                 
                 self.update_light((completed_order.0, completed_order.1, false));
                 self.hall_request_assigner(true);
+
+                // Served calls are cleared right away, and on the other elevators once committed
+                for call in completed_hall_calls {
+                    self.propose(HallOperation::Clear(completed_order.0, call));
+                }
+            }
+
+            Event::ConsensusReceived((from, message)) => {
+                if let Some(consensus) = self.consensus.as_mut() {
+                    let messages = consensus.handle_message(&from, message, self.clock.now());
+                    self.send_consensus(messages);
+                    self.apply_committed();
+                }
+            }
+
+            Event::PeerVersionReceived((peer, version)) => {
+                let local = PeerVersion::local();
+                match version {
+                    None => warn!("Peer {} announces no version, as a build from before the versions were announced or an elevator behind a relay", peer),
+                    Some(version) if !version.compatible() => error!(
+                        "INCOMPATIBLE VERSION: peer {} runs {} with version {} of the messages, this elevator {} with version {}. Their data may be misread.",
+                        peer, version.crate_version, version.schema_version, local.crate_version, local.schema_version
+                    ),
+                    Some(version) if version != local => warn!("Peer {} runs version {}, this elevator {}", peer, version.crate_version, local.crate_version),
+                    Some(_) => {}
+                }
+            }
+
+            Event::QualityReceived(quality) => {
+                let conservative = self.adaptation_policy.conservative(self.conservative, &quality);
+                if conservative != self.conservative {
+                    match conservative {
+                        true => info!("Network loss at {:.0}%, serving the local hall calls", quality.loss * 100.0),
+                        false => info!("Network loss down to {:.0}%, serving as assigned", quality.loss * 100.0),
+                    }
+                    self.conservative = conservative;
+                    self.hall_request_assigner(false);
+                }
+            }
+
+            Event::QueryReceived(reply_tx) => {
+                // The interface may have given up waiting
+                let _ = reply_tx.send(self.snapshot());
+            }
+
+            Event::CommandReceived(command) => {
+                info!("Command received: {:?}", command);
+                match command {
+                    ControlCommand::SetTrafficMode(traffic_mode) => {
+                        // The override is replicated, so the whole fleet switches mode
+                        self.elevator_data.traffic_mode = traffic_mode;
+                        self.update_traffic_mode();
+                        self.hall_request_assigner(true);
+                    }
+                    ControlCommand::SetZoningProfile(profile) => {
+                        if let Some(profile) = profile.as_ref().filter(|profile| !self.access_control.has_profile(profile)) {
+                            info!("Unknown zoning profile '{}'", profile);
+                            return;
+                        }
+                        // The profile is replicated too, and the calls are assigned afresh within the new zones
+                        self.elevator_data.zoning_profile = profile;
+                        self.update_zoning();
+                        self.hall_request_assigner(true);
+                    }
+                    ControlCommand::SetFireRecall(active) => {
+                        // Fire recall is replicated as well
+                        self.elevator_data.fire_recall = active;
+                        self.update_operating_mode();
+                        self.hall_request_assigner(true);
+                    }
+                    ControlCommand::SetFirePhase2(active) => {
+                        if !self.elevator_data.fire_recall {
+                            info!("Fire service phase 2 requires a fire recall");
+                            return;
+                        }
+
+                        if active {
+                            self.set_operating_mode(OperatingMode::FirePhase2);
+                        } else {
+                            self.set_operating_mode(OperatingMode::FireRecall(self.fire_recall_floor));
+                        }
+                    }
+                    ControlCommand::Door(open) => {
+                        if self.operating_mode != OperatingMode::FirePhase2 {
+                            info!("Door commands are only accepted in fire service phase 2");
+                            return;
+                        }
+
+                        self.fsm_door_command_tx.send(open).expect("Failed to send door command to fsm");
+                    }
+                    ControlCommand::ClearObstructionAlarm => {
+                        self.fsm_alarm_clear_tx.send(()).expect("Failed to send alarm clearance to fsm");
+                    }
+                    ControlCommand::Halt(halted) => {
+                        // The FSM reports the halt in its state, and the hall calls of the car are then reassigned
+                        self.fsm_halt_tx.send(halted).expect("Failed to send halt command to fsm");
+                    }
+                    ControlCommand::SetIndependentService(id, active) => {
+                        // Replicated, so any elevator in the fleet can be put in independent service
+                        let id = id.unwrap_or_else(|| self.local_id.clone());
+                        if active {
+                            self.elevator_data.independent_service.insert(id);
+                        } else {
+                            self.elevator_data.independent_service.remove(&id);
+                        }
+                        self.update_operating_mode();
+                        self.hall_request_assigner(true);
+                    }
+                    ControlCommand::HallCall(floor, call, place) => {
+                        if floor >= self.n_floors || (call != HALL_UP && call != HALL_DOWN) {
+                            info!("Hall call {} does not exist", self.floor_labels.call(floor, call));
+                            return;
+                        }
+
+                        // Placing a call held already does not cancel it, as a second press would
+                        let requested = self.elevator_data.hall_requests[floor as usize][call as usize];
+                        if place && !requested {
+                            self.handle_event(Event::RequestReceived((floor, call)));
+                        } else if !place && requested {
+                            self.cancel_hall_call(floor, call);
+                        }
+                    }
+                    ControlCommand::CabCall(floor) => {
+                        if floor >= self.n_floors {
+                            info!("Cab call to floor {} does not exist", self.floor_labels.label(floor));
+                            return;
+                        }
+                        self.handle_event(Event::RequestReceived((floor, CAB)));
+                    }
+                    ControlCommand::ShowFleet => {
+                        let mut ids = self.elevator_data.states.keys().collect::<Vec<&String>>();
+                        ids.sort();
+                        for id in ids {
+                            let state = &self.elevator_data.states[id];
+                            let serving = (0..self.n_floors)
+                                .flat_map(|floor| [HALL_UP, HALL_DOWN].map(|call| (floor, call)))
+                                .filter(|(floor, call)| self.elevator_data.hall_call_owners(*floor, *call).contains(id))
+                                .map(|(floor, call)| self.floor_labels.call(floor, call))
+                                .collect::<Vec<String>>();
+                            println!(
+                                "{:<24} floor {} {:?} {:?} door {:?} {:?} serving {:?}{}",
+                                id, self.floor_labels.label(state.floor), state.behaviour, state.direction, state.door, state.mode, serving,
+                                if state.dry_run { " (dry run)" } else { "" }
+                            );
+                        }
+                    }
+                    ControlCommand::ShowLinks => self.show_links(),
+                    ControlCommand::ShowQueue => self.show_queue(),
+                    ControlCommand::ExportState(path) => {
+                        let export = StateExport {
+                            exported_at: Local::now().to_rfc3339(),
+                            fsm: self.elevator_data.states.get(&self.local_id).cloned(),
+                            snapshot: self.snapshot(),
+                        };
+                        let document = serde_json::to_string_pretty(&export).unwrap();
+                        match path {
+                            Some(path) => match std::fs::write(&path, document + "\n") {
+                                Ok(()) => info!("State exported to {}", path),
+                                Err(e) => error!("Failed to export the state to {}: {:?}", path, e),
+                            },
+                            None => println!("{}", document),
+                        }
+                    }
+                }
             }
         }
     }
 
-    fn update_light(&self, light: (u8, u8, bool)) {
-        //Sending change in lights
-        if let Err(e) = self.hw_button_light_tx.send(light) {
-            error!("Failed to send light command to light thread from coordinator: {:?}", e);
-            std::process::exit(1);
+    // Prints the link to every peer connected or heard from since start, marking those lost
+    fn show_links(&self) {
+        let links = self.network_counters.statistics().links;
+        let mut ids = self.peers.iter().filter(|id| **id != self.local_id).chain(links.keys()).collect::<Vec<&String>>();
+        ids.sort();
+        ids.dedup();
+        println!("{:<36} {:>9} {:>7} {:>9} {:>8} {:>11}", "peer", "rtt (ms)", "loss", "received", "missing", "clock (ms)");
+        for id in ids {
+            let link = links.get(id).cloned().unwrap_or_default();
+            let round_trip = link.round_trip.map_or("-".to_string(), |round_trip| format!("{:.1}", round_trip));
+            let clock_offset = link.clock_offset.map_or("-".to_string(), |clock_offset| format!("{:+.0}", clock_offset));
+            let lost = if self.peers.contains(id) { "" } else { " lost" };
+            println!(
+                "{:<36} {:>9} {:>6.1}% {:>9} {:>8} {:>11}{}",
+                id, round_trip, link.loss * 100.0, link.received, link.missing, clock_offset, lost
+            );
         }
     }
 
-    // Calcualting hall requests
-    fn hall_request_assigner(&mut self, transmit: bool) {
-        //Removing elevators in error state
-        let mut elevator_data = self.elevator_data.clone();
-        self.remove_error_states(&mut elevator_data.states);
+    // Prints the view of the FSM, and any hall calls it holds that differ from those last assigned to it
+    fn show_queue(&self) {
+        let (reply_tx, reply_rx) = cbc::bounded::<FsmView>(1);
+        self.fsm_inspect_tx.send(reply_tx).expect("Failed to send inspection query to fsm");
+        let view = match reply_rx.recv_timeout(INSPECT_TIMEOUT) {
+            Ok(view) => view,
+            Err(e) => {
+                error!("The FSM did not answer the inspection query: {:?}", e);
+                return;
+            }
+        };
+
+        println!("{}", serde_json::to_string_pretty(&view).unwrap());
+        if view.hall_requests != self.assigned_hall_requests {
+            println!(
+                "The FSM holds the hall calls {:?}, but was assigned {:?}",
+                view.hall_requests, self.assigned_hall_requests
+            );
+        }
+    }
 
-        if elevator_data.states.is_empty() {
-            // Only transmit hall requests to FSM
-            self.fsm_hall_requests_tx.send(elevator_data.hall_requests).expect("Failed to send hall requests to fsm");
-            if transmit {
-                self.elevator_data.version += 1;
-                self.net_data_send_tx
-                    .send(self.elevator_data.clone())
-                    .expect("Failed to send elevator data to network thread");
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            id: self.local_id.clone(),
+            peers: self.peers.clone(),
+            elevator_data: self.elevator_data.clone(),
+            confirmed_hall_requests: self.confirmed_hall_requests.clone(),
+            network: self.network_counters.statistics(),
+            wal_sequence: self.wal.as_ref().map_or(0, |wal| wal.sequence()),
+            reassignments: self.reassignments,
+            uptime: self.uptime.uptime(),
+        }
+    }
+
+    // Merges a package from a peer that has diverged into the local data
+    fn merge_package(&mut self, elevator_data: ElevatorData) {
+        // Where only one side can be kept, every elevator keeps the same one. The merged data has seen both sides.
+        let remote_first = elevator_data.clock.precedes(&self.elevator_data.clock);
+        self.elevator_data.clock.join(&elevator_data.clock);
+        self.elevator_data.version = self.elevator_data.clock.total();
+
+        // The orders are joined, and a call is held as the joined order says, so a stale packet can not bring back a
+        // served call. Only for peers too old to send the orders are the hall requests "OR"ed, unless one side has
+        // seen more cancellations of the call.
+        // In consensus mode the hall calls come from the log only.
+        if self.consensus.is_none() {
+            self.elevator_data.hall_cancellations.resize(self.n_floors as usize, vec![0; 2]);
+            self.elevator_data.hall_orders.resize(self.n_floors as usize, vec![HallOrder::default(); 2]);
+            self.elevator_data.hall_acknowledgements.resize(self.n_floors as usize, vec![BTreeSet::new(); 2]);
+            resize_call_times(&mut self.elevator_data, self.n_floors);
+            for floor in 0..self.n_floors {
+                for call in [HALL_UP, HALL_DOWN] {
+                    let local_request = self.elevator_data.hall_requests[floor as usize][call as usize];
+                    let remote_request = elevator_data.hall_requests[floor as usize][call as usize];
+                    let local_cancellations = cancellation_count(&self.elevator_data, floor, call);
+                    let remote_cancellations = cancellation_count(&elevator_data, floor, call);
+
+                    let request = match order_precedence(&self.elevator_data, &elevator_data, floor, call) {
+                        Some(_) => {
+                            let order = &mut self.elevator_data.hall_orders[floor as usize][call as usize];
+                            order.join(&elevator_data.hall_orders[floor as usize][call as usize]);
+                            order.is_requested()
+                        }
+                        None if remote_cancellations > local_cancellations => remote_request,
+                        None if local_cancellations > remote_cancellations => local_request,
+                        None => local_request || remote_request,
+                    };
+
+                    self.elevator_data.hall_requests[floor as usize][call as usize] = request;
+                    self.elevator_data.hall_cancellations[floor as usize][call as usize] =
+                        local_cancellations.max(remote_cancellations);
+                    let request_time = match request {
+                        true => request_time(&self.elevator_data, &elevator_data, floor, call).unwrap_or(0),
+                        false => 0,
+                    };
+                    let completion_time = completion_time(&self.elevator_data, &elevator_data, floor, call);
+                    self.elevator_data.hall_request_times[floor as usize][call as usize] = request_time;
+                    self.elevator_data.hall_completion_times[floor as usize][call as usize] = completion_time;
+
+                    // The acknowledgements of a call still held add up
+                    if let (true, Some(remote_acknowledgements)) = (request, hall_acknowledgements(&elevator_data, floor, call)) {
+                        self.elevator_data.hall_acknowledgements[floor as usize][call as usize]
+                            .extend(remote_acknowledgements.iter().cloned());
+                    }
+                }
+            }
+
+            // New calls are acknowledged to the peers
+            if self.acknowledge_hall_requests() {
+                self.hall_request_assigner(true);
+            } else {
+                self.confirm_hall_requests();
             }
-            return;
         }
-        
-        // Serialize data
-        let mut json_value: serde_json::Value = serde_json::to_value(&elevator_data)
-            .expect("Failed to serialize data");
 
-        // Remove the `version` field from the serialized data
-        json_value.as_object_mut().unwrap().remove("version");
+        // Incoming states should overwrite existing states, but not the local state. So do the hall calls served
+        // by the elevators of the incoming states.
+        for (id, state) in elevator_data.states.iter() {
+            if id != &self.local_id {
+                self.elevator_data.states.insert(id.clone(), state.clone());
+                let owned = elevator_data.hall_owners.get(id).cloned().unwrap_or_default();
+                self.elevator_data.set_hall_owner(id, &owned);
+            }
+        } 
+
+        // So does an assignment pushed by the assigning coordinator
+        if self.adopt_hall_assignment(elevator_data.hall_assignment.clone()) {
+            self.hall_request_assigner(false);
+        }
+
+        // A traffic mode override survives the merge
+        if elevator_data.traffic_mode.is_some() && (self.elevator_data.traffic_mode.is_none() || remote_first) {
+            self.elevator_data.traffic_mode = elevator_data.traffic_mode;
+            if self.update_traffic_mode() {
+                self.hall_request_assigner(false);
+            }
+        }
 
-        let hra_input = serde_json::to_string(&json_value).expect("Failed to serialize data");
+        // So does a zoning profile
+        if elevator_data.zoning_profile.is_some() && (self.elevator_data.zoning_profile.is_none() || remote_first) {
+            self.elevator_data.zoning_profile = elevator_data.zoning_profile;
+            if self.update_zoning() {
+                self.hall_request_assigner(false);
+            }
+        }
 
-        // Run the executable with serialized_data as input
-        let hra_output = Command::new("./src/coordinator/hall_request_assigner")
-            .arg("--input")
-            .arg(&hra_input)
-            .output()
-            .expect("Failed to execute hall_request_assigner");
+        // So do a fire recall and elevators put in independent service
+        if (!self.elevator_data.fire_recall && elevator_data.fire_recall)
+            || !elevator_data.independent_service.is_subset(&self.elevator_data.independent_service)
+        {
+            self.elevator_data.fire_recall |= elevator_data.fire_recall;
+            self.elevator_data.independent_service.extend(elevator_data.independent_service);
+            self.update_operating_mode();
+            self.hall_request_assigner(false);
+        }
+    }
 
-        if hra_output.status.success() {
-            // Fetch and deserialize output
-            let hra_output_str = String::from_utf8(hra_output.stdout).expect("Invalid UTF-8 hra_output");
-            let hra_output = serde_json::from_str::<HashMap<String, Vec<Vec<bool>>>>(&hra_output_str)
-                    .expect("Failed to deserialize hra_output");
+    // Reconciles a package from elevators that have operated apart from the local one. The hall calls of both are
+    // unioned, except calls completed after they were placed, so calls served by both groups are not served again.
+    // The rest is merged, and all calls are assigned afresh.
+    fn reconcile_package(&mut self, mut elevator_data: ElevatorData) {
+        resize_call_times(&mut self.elevator_data, self.n_floors);
+        resize_call_times(&mut elevator_data, self.n_floors);
+        elevator_data.hall_requests.resize(self.n_floors as usize, vec![false; 2]);
+        // The calls of peers sending their orders are held as the joined orders say, when merged
+        let ordered = !elevator_data.hall_orders.is_empty();
+
+        for floor in 0..self.n_floors {
+            for call in [HALL_UP, HALL_DOWN] {
+                let completion_time = completion_time(&self.elevator_data, &elevator_data, floor, call);
+                let request_time = request_time(&self.elevator_data, &elevator_data, floor, call)
+                    .filter(|request_time| *request_time >= completion_time);
+                if !ordered && request_time.is_none() && self.elevator_data.hall_requests[floor as usize][call as usize] {
+                    info!("Hall call {} completed apart after a split brain", self.floor_labels.call(floor, call));
+                }
 
-            // Update hall requests assigned to local elevator
-            let mut local_hall_requests = vec![vec![false; 2]; self.n_floors as usize];
-            for (id, hall_requests) in hra_output.iter() {
-                if id == &self.local_id {
-                    for floor in 0..self.n_floors {
-                        local_hall_requests[floor as usize][HALL_UP as usize] = hall_requests[floor as usize][HALL_UP as usize];
-                        local_hall_requests[floor as usize][HALL_DOWN as usize] = hall_requests[floor as usize][HALL_DOWN as usize];
+                // Both sides agree on the call before the merge, so the merge keeps it
+                for data in [&mut self.elevator_data, &mut elevator_data] {
+                    if !ordered {
+                        data.hall_requests[floor as usize][call as usize] = request_time.is_some();
                     }
+                    data.hall_request_times[floor as usize][call as usize] = request_time.unwrap_or(0);
+                    data.hall_completion_times[floor as usize][call as usize] = completion_time;
                 }
             }
+        }
 
-            // Transmit the updated hall requests to the FSM
-            self.fsm_hall_requests_tx.send(local_hall_requests).expect("Failed to send hall requests to fsm");
-        } 
-        
-        else {
-            // If the executable did not run successfully, you can handle the error
-            let error_message = String::from_utf8(hra_output.stderr).expect("Invalid UTF-8 error hra_output");
-            error!("Error executing hall_request_assigner: {:?}", error_message);
-            std::process::exit(1);
+        self.merge_package(elevator_data);
+        self.hall_request_assigner(true);
+    }
+
+    // Records when a hall call was completed, so reconciliation after a split brain does not bring it back
+    fn record_hall_completion(&mut self, floor: u8, call: u8) {
+        resize_call_times(&mut self.elevator_data, self.n_floors);
+        self.elevator_data.hall_request_times[floor as usize][call as usize] = 0;
+        self.elevator_data.hall_completion_times[floor as usize][call as usize] = unix_time();
+    }
+
+    // Applies the overridden or scheduled traffic mode. Returns true if the mode changed.
+    fn update_traffic_mode(&mut self) -> bool {
+        let traffic_mode = match self.elevator_data.traffic_mode {
+            Some(traffic_mode) => traffic_mode,
+            None => self.traffic_policy.scheduled_mode(self.clock.time_of_day()),
+        };
+
+        if traffic_mode == self.traffic_mode {
+            return false;
+        }
+
+        info!("Traffic mode changed to {:?}", traffic_mode);
+        self.traffic_mode = traffic_mode;
+        self.update_parking_floor();
+        true
+    }
+
+    // Puts the replicated zoning profile in force. The FSM is told the floors the local elevator may serve, so it
+    // refuses cab calls to the others. Returns true if the zoning changed.
+    fn update_zoning(&mut self) -> bool {
+        let profile = self.elevator_data.zoning_profile.clone();
+        if profile.as_deref() == self.access_control.profile() {
+            return false;
+        }
+        // A peer with other profiles configured may have switched to one unknown here
+        if let Err(e) = self.access_control.set_profile(profile.as_deref()) {
+            warn!("{}, keeping the zones in force", e);
+            return false;
         }
 
-        // Transmit the updated elevator on the network
-        if transmit {
-            self.elevator_data.version += 1;
-            self.net_data_send_tx
-                .send(self.elevator_data.clone())
-                .expect("Failed to send elevator data to network thread");
+        info!("Zoning profile changed to {}", profile.as_deref().unwrap_or("default"));
+        if let Some(fsm_blocked_floors_tx) = self.fsm_blocked_floors_tx.as_ref() {
+            fsm_blocked_floors_tx
+                .send(self.access_control.blocked_floors(&self.local_id, self.n_floors))
+                .expect("Failed to send blocked floors to fsm");
         }
+        self.update_parking_floor();
+        true
     }
 
-    fn check_merge_type(&self, elevator_data: ElevatorData) -> MergeType {
-        let mut new_elevators = false;
-        for key in self.elevator_data.states.keys() {
-            if elevator_data.states.contains_key(key) {
-                new_elevators = false;
-            } else {
-                new_elevators = true;
-                info!("New elevator on netowrk: {:?} \n", key);
+    // Closes the floors of the lockout windows active at the time of day of the clock. The hall calls at closed
+    // floors are held dark and unassigned until the window ends, and the local cab calls to them are cleared.
+    // Returns true if the closed floors changed.
+    fn update_lockout(&mut self) -> bool {
+        let time = self.clock.time_of_day();
+        let locked_floors: Vec<u8> = (0..self.n_floors)
+            .filter(|floor| self.lockout_schedule.is_locked(*floor, time))
+            .collect();
+        if locked_floors == self.locked_floors {
+            return false;
+        }
+
+        info!(
+            "Floors locked out changed to {:?}",
+            locked_floors.iter().map(|floor| self.floor_labels.label(*floor)).collect::<Vec<_>>()
+        );
+        self.locked_floors = locked_floors;
+
+        let mut cleared_cab_requests = Vec::new();
+        if let Some(state) = self.elevator_data.states.get_mut(&self.local_id) {
+            for floor in self.locked_floors.iter().copied() {
+                if state.cab_requests.get(floor as usize).copied().unwrap_or(false) {
+                    state.cab_requests[floor as usize] = false;
+                    cleared_cab_requests.push(floor);
+                }
             }
         }
-        let version = elevator_data.version;
+        for floor in cleared_cab_requests {
+            info!("Cab call to locked floor {} cleared", self.floor_labels.label(floor));
+            self.log_mutation(OrderMutation::Clear { floor, call: CAB });
+            self.update_light((floor, CAB, false));
+        }
+        if let Some(fsm_locked_floors_tx) = self.fsm_locked_floors_tx.as_ref() {
+            fsm_locked_floors_tx.send(self.locked_floors.clone()).expect("Failed to send locked floors to fsm");
+        }
 
-        // New elevators in data should yield a merge
-        if new_elevators {
-            MergeType::Merge
+        self.confirm_hall_requests();
+        true
+    }
+
+    // Sends the parking floor of the zoning, else of the traffic mode, or else of the sector held by the local
+    // elevator, to the FSM
+    fn update_parking_floor(&mut self) {
+        let parking_floor = self.access_control.parking_floor(&self.local_id)
+            .or(self.traffic_policy.parking_floor(self.traffic_mode))
+            .or(self.sector_floor);
+        if parking_floor == self.parking_floor {
+            return;
         }
-        
-        else if version > self.elevator_data.version {
-            MergeType::Accept
-        } 
 
-        else {
-            MergeType::Reject
+        self.parking_floor = parking_floor;
+        if self.dry_run {
+            info!("Dry run: would park at floor {:?}", parking_floor.map(|floor| self.floor_labels.label(floor)));
+            return;
         }
+        self.fsm_parking_floor_tx
+            .send(parking_floor)
+            .expect("Failed to send parking floor to fsm");
     }
 
-    //Removes elevators in error state 
-    fn remove_error_states(&self, states: &mut HashMap<String, ElevatorState>) {
-        states.retain(|_, state| state.behaviour != Behaviour::Error);
+    // Spreads the elevators without orders across the sectors. Elevators busy with a call never get a sector,
+    // so repositioning never holds up an order.
+    fn reposition_idle_elevators(&mut self, states: &HashMap<String, ElevatorState>, busy: &HashSet<String>) {
+        let idle_elevators = states
+            .iter()
+            .filter(|(id, state)| {
+                !busy.contains(*id) && state.behaviour != Behaviour::DoorOpen && !state.cab_requests.contains(&true)
+            })
+            .map(|(id, state)| (id.clone(), state.floor))
+            .collect::<Vec<(String, u8)>>();
+
+        self.sector_floor = self.repositioning_policy.assign_sectors(&idle_elevators).remove(&self.local_id);
+        self.update_parking_floor();
     }
-}
 
-/***************************************/
-/*              Test API               */
-/***************************************/
-#[cfg(test)]
-pub mod testing {
-    use super::Coordinator;
-    use crate::shared::ElevatorData;
-    use crate::shared::ElevatorState;
-    use network_rust::udpnet::peers::PeerUpdate;
+    // Derives the local operating mode from the replicated fire recall and independent service.
+    // Fire service takes precedence.
+    fn update_operating_mode(&mut self) {
+        let operating_mode = if self.elevator_data.fire_recall {
+            if self.operating_mode == OperatingMode::FirePhase2 {
+                OperatingMode::FirePhase2
+            } else {
+                OperatingMode::FireRecall(self.fire_recall_floor)
+            }
+        } else if self.elevator_data.independent_service.contains(&self.local_id) {
+            OperatingMode::Independent
+        } else {
+            OperatingMode::Normal
+        };
 
-    impl Coordinator {
-        // Publicly expose the private fields for testing
-        pub fn test_get_data(&self) -> &ElevatorData {
-            &self.elevator_data
+        self.set_operating_mode(operating_mode);
+    }
+
+    fn set_operating_mode(&mut self, operating_mode: OperatingMode) {
+        if operating_mode == self.operating_mode {
+            return;
         }
 
-        pub fn test_get_local_id(&self) -> &String {
-            &self.local_id
+        info!("Operating mode changed to {:?}", operating_mode);
+        let starts_fire_service = matches!(self.operating_mode, OperatingMode::Normal | OperatingMode::Independent);
+        if starts_fire_service && matches!(operating_mode, OperatingMode::FireRecall(_)) {
+            self.cancel_calls();
         }
-        
-        pub fn test_get_n_floors(&self) -> &u8 {
-            &self.n_floors
+
+        // The local state is updated right away, so the elevator is excluded from the next assignment
+        self.operating_mode = operating_mode;
+        if let Some(state) = self.elevator_data.states.get_mut(&self.local_id) {
+            state.mode = operating_mode;
         }
+        self.fsm_operating_mode_tx
+            .send(operating_mode)
+            .expect("Failed to send operating mode to fsm");
+    }
 
-        pub fn test_update_lights(&self, light: (u8, u8, bool)) {
-            self.update_light(light);
+    // Clears all hall calls and the local cab calls. The FSM clears its own copy when it changes mode.
+    fn cancel_calls(&mut self) {
+        self.log_mutation(OrderMutation::ClearAll);
+        self.elevator_data.hall_requests = vec![vec![false; 2]; self.n_floors as usize];
+        self.confirm_hall_requests();
+
+        let mut cancelled_cab_requests = Vec::new();
+        if let Some(state) = self.elevator_data.states.get_mut(&self.local_id) {
+            for floor in 0..self.n_floors {
+                if state.cab_requests[floor as usize] {
+                    state.cab_requests[floor as usize] = false;
+                    cancelled_cab_requests.push(floor);
+                }
+            }
         }
+        for floor in cancelled_cab_requests {
+            self.update_light((floor, CAB, false));
+        }
+    }
 
-        pub fn test_hall_request_assigner(&mut self, transmit: bool) {
-            self.hall_request_assigner(transmit);
+    // Clears a hall call and counts the cancellation, so merges with stale packets keep it cleared
+    fn cancel_hall_call(&mut self, floor: u8, call: u8) {
+        info!("Hall call {} cancelled", self.floor_labels.call(floor, call));
+        self.log_mutation(OrderMutation::Clear { floor, call });
+        self.elevator_data.hall_requests[floor as usize][call as usize] = false;
+        self.elevator_data.hall_cancellations.resize(self.n_floors as usize, vec![0; 2]);
+        self.elevator_data.hall_cancellations[floor as usize][call as usize] += 1;
+        self.hall_call_presses[floor as usize][call as usize] = None;
+        self.record_hall_completion(floor, call);
+
+        self.hall_request_assigner(true);
+        self.propose(HallOperation::Clear(floor, call));
+    }
+
+    // Hands a hall operation to the consensus. Does nothing outside consensus mode.
+    fn propose(&mut self, operation: HallOperation) {
+        if let Some(consensus) = self.consensus.as_mut() {
+            let messages = consensus.propose(operation, self.clock.now());
+            self.send_consensus(messages);
+            self.apply_committed();
         }
+    }
 
-        pub fn test_set_hall_requests(&mut self, hall_requests: Vec<Vec<bool>>) {
-            self.elevator_data.hall_requests = hall_requests;
+    fn send_consensus(&mut self, messages: Vec<(String, ConsensusMessage)>) {
+        for message in messages {
+            if self.net_consensus_send_tx.send(message).is_err() {
+                self.fail(ModuleError::Disconnected("net_consensus_send_tx".to_string()));
+            }
         }
+    }
 
-        pub fn test_set_state(&mut self, elevator: String, state: ElevatorState) {
+    // Applies the operations committed since last time, in log order, and reassigns the calls if any changed
+    fn apply_committed(&mut self) {
+        let operations = match self.consensus.as_mut() {
+            Some(consensus) => consensus.take_committed(),
+            None => return,
+        };
+
+        let mut changed = false;
+        for operation in operations {
+            let (floor, call, request) = match operation {
+                HallOperation::Add(floor, call) => (floor, call, true),
+                HallOperation::Clear(floor, call) => (floor, call, false),
+            };
+            if floor >= self.n_floors || (call != HALL_UP && call != HALL_DOWN) {
+                continue;
+            }
+            if self.elevator_data.hall_requests[floor as usize][call as usize] != request {
+                self.elevator_data.hall_requests[floor as usize][call as usize] = request;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.hall_request_assigner(true);
+        }
+    }
+
+    // In consensus mode the commit is the quorum, so calls are not acknowledged separately
+    fn quorum_enabled(&self) -> bool {
+        (self.hall_call_quorum > 1 || self.hall_call_confirmation == HallCallConfirmation::AllPeers) && self.consensus.is_none()
+    }
+
+    // The acknowledgements confirming a hall call. Lost peers are no longer known, so they are not waited for.
+    fn quorum(&self) -> usize {
+        match self.hall_call_confirmation {
+            HallCallConfirmation::Quorum => self.hall_call_quorum.min(self.elevator_data.states.len()),
+            HallCallConfirmation::AllPeers => self.elevator_data.states.len(),
+        }
+    }
+
+    // Adds the local elevator to the acknowledgements of the hall calls it holds, and drops those of cleared calls.
+    // Returns true if a call was acknowledged, so the acknowledgement gets transmitted.
+    fn acknowledge_hall_requests(&mut self) -> bool {
+        if !self.quorum_enabled() {
+            return false;
+        }
+
+        let mut acknowledged = false;
+        self.elevator_data.hall_acknowledgements.resize(self.n_floors as usize, vec![BTreeSet::new(); 2]);
+        for floor in 0..self.n_floors as usize {
+            for call in [HALL_UP as usize, HALL_DOWN as usize] {
+                let acknowledgements = &mut self.elevator_data.hall_acknowledgements[floor][call];
+                if self.elevator_data.hall_requests[floor][call] {
+                    acknowledged |= acknowledgements.insert(self.local_id.clone());
+                } else {
+                    acknowledgements.clear();
+                }
+            }
+        }
+        acknowledged
+    }
+
+    // Confirms the hall calls acknowledged by a quorum of the known elevators, or by all of them, and lights them.
+    // Cleared calls, and calls at locked out floors, are no longer confirmed, and go dark right away.
+    fn confirm_hall_requests(&mut self) {
+        // Calls from peers and the consensus are logged before they are lit
+        if let Some(wal) = self.wal.as_mut() {
+            wal.log_hall_requests(&self.elevator_data.hall_requests);
+        }
+
+        // The state of every call steps on with the calls held, and with those confirmed
+        let quorum = self.quorum();
+        self.elevator_data.hall_orders.resize(self.n_floors as usize, vec![HallOrder::default(); 2]);
+        for floor in 0..self.n_floors {
+            for call in [HALL_UP, HALL_DOWN] {
+                let requested = self.elevator_data.hall_requests[floor as usize][call as usize];
+                let acknowledged = hall_acknowledgements(&self.elevator_data, floor, call)
+                    .map_or(0, |acknowledgements| {
+                        acknowledgements.iter().filter(|id| self.elevator_data.states.contains_key(*id)).count()
+                    });
+                let confirmed = requested
+                    && (!self.quorum_enabled() || acknowledged >= quorum)
+                    && !self.locked_floors.contains(&floor);
+                self.elevator_data.hall_orders[floor as usize][call as usize].advance(requested, confirmed);
+
+                if confirmed != self.confirmed_hall_requests[floor as usize][call as usize] {
+                    self.confirmed_hall_requests[floor as usize][call as usize] = confirmed;
+                    self.hall_call_deadlines[floor as usize][call as usize] = confirmed.then(|| self.clock.now() + self.hall_call_deadline);
+                    self.update_light((floor, call, confirmed));
+                }
+            }
+        }
+    }
+
+    fn update_light(&mut self, light: (u8, u8, bool)) {
+        if self.dry_run {
+            info!("Dry run: would turn the light {:?} {}", (light.0, light.1), if light.2 { "on" } else { "off" });
+            return;
+        }
+
+        //Sending change in lights
+        if self.hw_button_light_tx.send(light).is_err() {
+            self.fail(ModuleError::Disconnected("hw_button_light_tx".to_string()));
+        }
+    }
+
+    // Calcualting hall requests
+    fn hall_request_assigner(&mut self, transmit: bool) {
+        // Only confirmed calls are assigned. A new acknowledgement is always transmitted, as is one waiting on an
+        // assignment in the background that this one makes stale.
+        let transmit = self.acknowledge_hall_requests() || transmit || std::mem::take(&mut self.pending_transmit);
+        self.confirm_hall_requests();
+        self.assigner.cancel();
+
+        //Removing elevators in error state
+        let mut elevator_data = self.elevator_data.clone();
+        elevator_data.hall_requests = self.confirmed_hall_requests.clone();
+        self.remove_error_states(&mut elevator_data.states);
+
+        // Elevators that let a call pass its deadline are left out, unless no elevator would be left
+        if elevator_data.states.keys().any(|id| !self.overdue_elevators.contains_key(id)) {
+            elevator_data.states.retain(|id, _| !self.overdue_elevators.contains_key(id));
+        }
+
+        if elevator_data.states.is_empty() {
+            // The local elevator is out of service, and holds no sector
+            self.sector_floor = None;
+            self.update_parking_floor();
+
+            // Only transmit hall requests to FSM
+            let mut hall_requests = elevator_data.hall_requests;
+            for floor in self.access_control.blocked_floors(&self.local_id, self.n_floors) {
+                if let Some(requests) = hall_requests.get_mut(floor as usize) {
+                    *requests = vec![false; 2];
+                }
+            }
+            self.send_assigned_hall_requests(hall_requests);
+            if transmit {
+                self.transmit_data();
+            }
+            return;
+        }
+
+        // In the push model only the assigning coordinator assigns, and the others serve what it pushed to them
+        let push = self.assignment_model == AssignmentModel::Push;
+        if push && self.assigning_node() != self.local_id {
+            self.follow_hall_assignment(&elevator_data);
+            if transmit {
+                self.transmit_data();
+            }
+            return;
+        }
+
+        // Hall calls are grouped by the elevators allowed to serve them, and each group is assigned separately.
+        // Priority calls of the traffic mode are assigned first, and become stops for the elevators taking them.
+        let mut priority_groups: BTreeMap<Vec<String>, Vec<(u8, u8)>> = BTreeMap::new();
+        let mut regular_groups: BTreeMap<Vec<String>, Vec<(u8, u8)>> = BTreeMap::new();
+        for floor in 0..self.n_floors {
+            let mut eligible = elevator_data.states.keys()
+                .filter(|id| self.access_control.may_serve(id, floor))
+                .cloned()
+                .collect::<Vec<String>>();
+            eligible.sort();
+
+            for call in [HALL_UP, HALL_DOWN] {
+                if !elevator_data.hall_requests[floor as usize][call as usize] {
+                    continue;
+                }
+                let call_groups = if self.traffic_policy.is_priority_call(self.traffic_mode, floor, call) {
+                    &mut priority_groups
+                } else {
+                    &mut regular_groups
+                };
+                call_groups.entry(eligible.clone()).or_default().push((floor, call));
+            }
+        }
+
+        // Nothing in a regular group without the local elevator can end up at it, or affect later groups. With
+        // capacities every group is assigned, as the load carries over, and the assigning coordinator of the push
+        // model assigns every group.
+        let capped = elevator_data.states.values().any(|state| state.capacity > 0);
+        let groups = priority_groups.into_iter().map(|group| (true, group))
+            .chain(regular_groups.into_iter().map(|group| (false, group)))
+            .filter(|(priority, (eligible, _))| {
+                !eligible.is_empty() && (*priority || capped || push || eligible.contains(&self.local_id))
+            })
+            .map(|(priority, (eligible, calls))| CallGroup { priority, eligible, calls })
+            .collect::<Vec<CallGroup>>();
+
+        let job = AssignmentJob {
+            generation: 0,
+            local_id: self.local_id.clone(),
+            n_floors: self.n_floors,
+            elevator_data,
+            groups,
+        };
+        match self.assigner.assign(job) {
+            Some(assignment) => self.apply_hall_assignments(assignment, transmit),
+            // Transmitted once the assignment in the background is applied
+            None => self.pending_transmit = transmit,
+        }
+    }
+
+    // Applies the hall calls assigned, sending the local ones to the FSM and pushing the assignment in the push model
+    fn apply_hall_assignments(&mut self, assignment: Result<HallAssignments, String>, transmit: bool) {
+        let HallAssignments { elevator_data, assignments, mut local_hall_requests, busy } = match assignment {
+            Ok(assignment) => assignment,
+            Err(error_message) => {
+                self.fail(ModuleError::Assigner(error_message));
+                return;
+            }
+        };
+        let push = self.assignment_model == AssignmentModel::Push;
+
+        self.reassignments += reassigned_calls(&self.hall_assignments, &assignments);
+        self.hall_assignments = assignments.clone();
+
+        self.reposition_idle_elevators(&elevator_data.states, &busy);
+        self.serve_observed_hall_calls(&elevator_data, &mut local_hall_requests);
+
+        // Transmit the updated hall requests to the FSM
+        self.send_assigned_hall_requests(local_hall_requests);
+
+        // A changed assignment is pushed to the other coordinators, with the version it is transmitted at
+        let changed = self.elevator_data.hall_assignment.as_ref().is_none_or(|assignment| assignment.elevators != assignments);
+        if push && changed && self.dry_run {
+            info!("Dry run: would push the assignment {:?}", assignments.iter().map(|(id, assigned)| (id, hall_calls_of(assigned))).collect::<Vec<_>>());
+            if transmit {
+                self.transmit_data();
+            }
+        } else if push && changed {
+            self.elevator_data.hall_assignment = Some(HallAssignment {
+                assigner: self.local_id.clone(),
+                version: self.elevator_data.version + 1,
+                elevators: assignments,
+            });
+            self.transmit_data();
+        } else if transmit {
+            // Transmit the updated elevator on the network
+            self.transmit_data();
+        }
+    }
+
+    fn transmit_data(&mut self) {
+        self.elevator_data.clock.tick(&self.local_id);
+        self.elevator_data.version = self.elevator_data.clock.total();
+        self.net_data_send_tx
+            .send(self.elevator_data.clone())
+            .expect("Failed to send elevator data to network thread");
+    }
+
+    // The coordinator assigning the hall calls in the push model: the consensus leader in consensus mode, and
+    // otherwise the elevator with the lowest ID of those known, other than the peers in a dry run
+    fn assigning_node(&self) -> String {
+        if let Some(leader) = self.consensus.as_ref().and_then(|consensus| consensus.leader()) {
+            return leader.clone();
+        }
+        self.elevator_data.states
+            .iter()
+            .filter(|(id, state)| !state.dry_run || **id == self.local_id)
+            .map(|(id, _)| id)
+            .chain([&self.local_id])
+            .min()
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Serves the confirmed hall calls pushed to the local elevator by the assigning coordinator
+    fn follow_hall_assignment(&mut self, elevator_data: &ElevatorData) {
+        let Some(assignment) = self.elevator_data.hall_assignment.as_ref() else {
+            let mut local_hall_requests = vec![vec![false; 2]; self.n_floors as usize];
+            self.serve_observed_hall_calls(elevator_data, &mut local_hall_requests);
+            self.send_assigned_hall_requests(local_hall_requests);
+            return;
+        };
+
+        let busy = assignment.elevators.iter()
+            .filter(|(_, assigned)| assigned.iter().flatten().any(|call| *call))
+            .map(|(id, _)| id.clone())
+            .collect::<HashSet<String>>();
+        let assigned = assignment.elevators.get(&self.local_id);
+        let mut local_hall_requests = (0..self.n_floors as usize)
+            .map(|floor| {
+                [HALL_UP, HALL_DOWN]
+                    .map(|call| {
+                        let pushed = assigned.and_then(|assigned| assigned.get(floor)?.get(call as usize).copied()).unwrap_or(false);
+                        pushed && elevator_data.hall_requests[floor][call as usize]
+                    })
+                    .to_vec()
+            })
+            .collect::<Vec<Vec<bool>>>();
+
+        self.reposition_idle_elevators(&elevator_data.states, &busy);
+        self.serve_observed_hall_calls(elevator_data, &mut local_hall_requests);
+        self.send_assigned_hall_requests(local_hall_requests);
+    }
+
+    // While serving conservatively, the local elevator also serves the calls pressed at its own panel. Pending calls
+    // are served too, as their acknowledgements may be lost. Calls no longer held have been served or cancelled.
+    fn serve_observed_hall_calls(&mut self, elevator_data: &ElevatorData, hall_requests: &mut [Vec<bool>]) {
+        for (observed, requests) in self.observed_hall_calls.iter_mut().zip(&self.elevator_data.hall_requests) {
+            for (observed, request) in observed.iter_mut().zip(requests) {
+                *observed &= *request;
+            }
+        }
+        if !self.conservative || !elevator_data.states.contains_key(&self.local_id) {
+            return;
+        }
+
+        for (floor, (requests, observed)) in hall_requests.iter_mut().zip(&self.observed_hall_calls).enumerate() {
+            if self.access_control.may_serve(&self.local_id, floor as u8) {
+                for (request, observed) in requests.iter_mut().zip(observed) {
+                    *request |= *observed;
+                }
+            }
+        }
+    }
+
+    // Takes an assignment received from a peer if it is newer than the local one, or from the current assigning
+    // coordinator where the local one is not. Returns whether it was taken.
+    fn adopt_hall_assignment(&mut self, assignment: Option<HallAssignment>) -> bool {
+        let Some(assignment) = assignment else { return false };
+        let assigning_node = self.assigning_node();
+        let adopt = match self.elevator_data.hall_assignment.as_ref() {
+            None => true,
+            Some(local) if local.assigner == assignment.assigner => assignment.version > local.version,
+            Some(local) => assignment.assigner == assigning_node && local.assigner != assigning_node,
+        };
+        if adopt {
+            self.elevator_data.hall_assignment = Some(assignment);
+        }
+        adopt
+    }
+
+    // Sends the hall calls assigned to the local elevator to the FSM, logging the assignment if it changed
+    // Gives the FSM the hall calls the local elevator serves, and records them in the data, to be broadcast with it
+    // In a dry run the calls are only logged when they change, and neither served nor claimed before the peers.
+    fn send_assigned_hall_requests(&mut self, hall_requests: Vec<Vec<bool>>) {
+        let changed = hall_requests != self.assigned_hall_requests;
+        if changed {
+            self.log_mutation(OrderMutation::Assign { hall_requests: hall_requests.clone() });
+        }
+        self.assigned_hall_requests = hall_requests.clone();
+        if self.dry_run {
+            if changed {
+                info!("Dry run: would serve the hall calls {:?}", hall_calls_of(&hall_requests));
+            }
+            return;
+        }
+        self.elevator_data.set_hall_owner(&self.local_id, &hall_requests);
+        self.fsm_hall_requests_tx.send(hall_requests).expect("Failed to send hall requests to fsm");
+    }
+
+    // Gives the FSM a cab call, or only logs it in a dry run
+    fn send_cab_request(&self, floor: u8) {
+        if self.dry_run {
+            info!("Dry run: would give the FSM the cab call to floor {}", self.floor_labels.label(floor));
+            return;
+        }
+        self.fsm_cab_request_tx.send(floor).expect("Failed to send cab request to fsm");
+    }
+
+    // Logs an order mutation to the write-ahead log. Returns false if it could not be logged, and true without a log.
+    fn log_mutation(&mut self, mutation: OrderMutation) -> bool {
+        self.wal.as_mut().is_none_or(|wal| wal.append(mutation))
+    }
+
+    // Takes up the calls recovered from the write-ahead log at start-up. The cab calls are given to the FSM again,
+    // in case it did not save them before the crash, and the hall calls are assigned, or proposed again in
+    // consensus mode.
+    fn resume_recovered_orders(&mut self) {
+        let cab_requests = self.elevator_data.states.get(&self.local_id).map(|state| state.cab_requests.clone()).unwrap_or_default();
+        let hall_calls = (0..self.n_floors)
+            .flat_map(|floor| [HALL_UP, HALL_DOWN].map(|call| (floor, call)))
+            .filter(|(floor, call)| self.elevator_data.hall_requests[*floor as usize][*call as usize])
+            .collect::<Vec<(u8, u8)>>();
+        if self.wal.is_none() || (!cab_requests.contains(&true) && hall_calls.is_empty()) {
+            return;
+        }
+
+        info!("Resuming the recovered cab calls {:?} and hall calls {:?}", cab_requests, hall_calls);
+        for floor in (0..self.n_floors).filter(|floor| cab_requests[*floor as usize]) {
+            self.send_cab_request(floor);
+            self.update_light((floor, CAB, true));
+        }
+        for (floor, call) in hall_calls {
+            self.propose(HallOperation::Add(floor, call));
+        }
+        self.hall_request_assigner(true);
+    }
+
+    // Quarantines packages breaking the hard limits
+    fn package_is_sane(&self, elevator_data: &ElevatorData) -> bool {
+        match check_package(elevator_data, self.n_floors, self.elevator_data.version) {
+            Ok(()) => true,
+            Err(reason) => {
+                error!("Quarantined package with version {}: {}", elevator_data.version, reason);
+                false
+            }
+        }
+    }
+
+    fn check_merge_type(&self, elevator_data: ElevatorData) -> MergeType {
+        // Any elevator we know of that is missing from the package means the sender has diverged
+        let mut new_elevators = false;
+        for key in self.elevator_data.states.keys() {
+            if !elevator_data.states.contains_key(key) {
+                new_elevators = true;
+                info!("New elevator on netowrk: {:?} \n", key);
+            }
+        }
+        // New elevators in data should yield a merge
+        if new_elevators {
+            return MergeType::Merge;
+        }
+
+        // Data ahead of the local data replaces it, and concurrent data from another partition is merged with it.
+        // Only peers that send no clock are compared by version, the total of the local clock.
+        if elevator_data.clock.is_empty() {
+            return match elevator_data.version > self.elevator_data.clock.total() {
+                true => MergeType::Accept,
+                false => MergeType::Reject,
+            };
+        }
+        match elevator_data.clock.compare(&self.elevator_data.clock) {
+            Causality::After => MergeType::Accept,
+            Causality::Concurrent => MergeType::Merge,
+            Causality::Before | Causality::Equal => MergeType::Reject,
+        }
+    }
+
+    // Records the first failure, returned by `run` once the event is handled
+    fn fail(&mut self, failure: ModuleError) {
+        self.failure.get_or_insert(failure);
+    }
+
+    // Leaves out the elevators assigned a hall call that has waited past its deadline, as they have failed it without
+    // reporting an error, and restarts the deadline of the call for the elevator it is reassigned to. An elevator is
+    // left out for one deadline, and is then assigned calls again. Returns true if the elevators to assign to changed.
+    // Only the assigning coordinator of the push model keeps the deadlines, as coordinators assigning on their own
+    // would leave out different elevators, and give a call to none or to several.
+    fn check_hall_call_deadlines(&mut self) -> bool {
+        let assigning = self.assignment_model == AssignmentModel::Push && self.assigning_node() == self.local_id;
+        if self.hall_call_deadline.is_zero() || !assigning {
+            let released = !self.overdue_elevators.is_empty();
+            self.overdue_elevators.clear();
+            return released;
+        }
+
+        let now = self.clock.now();
+        let released = self.overdue_elevators.len();
+        self.overdue_elevators.retain(|_, until| now < *until);
+        let mut changed = self.overdue_elevators.len() != released;
+
+        for floor in 0..self.n_floors {
+            for call in [HALL_UP, HALL_DOWN] {
+                let deadline = &mut self.hall_call_deadlines[floor as usize][call as usize];
+                if deadline.is_none_or(|deadline| now < deadline) {
+                    continue;
+                }
+                *deadline = Some(now + self.hall_call_deadline);
+
+                let assigned = self.hall_assignments.iter()
+                    .find(|(_, assigned)| assigned[floor as usize][call as usize])
+                    .map(|(id, _)| id.clone());
+                if let Some(id) = assigned {
+                    warn!("{} has not served the hall call {} within its deadline, reassigning", id, self.floor_labels.call(floor, call));
+                    self.overdue_elevators.insert(id, now + self.hall_call_deadline);
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    //Removes elevators in error state or out of normal service, and the peers in a dry run. The local elevator
+    // assigns itself calls in a dry run, to log what it would serve.
+    fn remove_error_states(&self, states: &mut HashMap<String, ElevatorState>) {
+        states.retain(|id, state| {
+            state.behaviour != Behaviour::Error && state.mode == OperatingMode::Normal && !state.halted && (!state.dry_run || *id == self.local_id)
+        });
+    }
+}
+
+impl EventLoop for Coordinator {
+    fn terminate_rx(&self) -> &cbc::Receiver<Terminate> {
+        &self.coordinator_terminate_rx
+    }
+
+    // Acknowledges the termination if asked for it. Nothing is left to save, as the order mutations are logged before
+    // they take effect.
+    fn terminate(&mut self, done: Option<Terminate>) -> Result<(), ModuleError> {
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+        Ok(())
+    }
+
+    fn take_failure(&mut self) -> Option<ModuleError> {
+        self.failure.take()
+    }
+}
+
+/***************************************/
+/*              Builder                */
+/***************************************/
+// Builds the coordinator with every channel attached by name. The policies default to none, the floors to be named
+// by number, the hall calls to the in-process cost function on the spot and the clock to the wall clock. Every channel but `fsm_blocked_floors_tx`,
+// `fsm_locked_floors_tx` and `coordinator_terminate_rx` must be attached.
+pub struct CoordinatorBuilder {
+    elevator_data: ElevatorData,
+    local_id: String,
+    n_floors: u8,
+    floor_labels: FloorLabels,
+    access_control: AccessControl,
+    lockout_schedule: LockoutSchedule,
+    traffic_policy: TrafficPolicy,
+    repositioning_policy: RepositioningPolicy,
+    fire_recall_floor: u8,
+    hall_call_cancel_window: Duration,
+    hall_call_quorum: usize,
+    hall_call_confirmation: HallCallConfirmation,
+    hall_call_deadline: Duration,
+    hall_clearing: HallClearing,
+    assigner: Box<dyn AssignmentStrategy>,
+    assign_in_background: bool,
+    assignment_model: AssignmentModel,
+    adaptation_policy: AdaptationPolicy,
+    consensus: Option<Consensus>,
+    network_counters: Arc<NetworkCounters>,
+    uptime: Arc<UptimeCounter>,
+    clock: Arc<dyn Clock>,
+    wal: Option<WriteAheadLog>,
+    dry_run: bool,
+    hw_button_light_tx: Option<cbc::Sender<(u8, u8, bool)>>,
+    hw_request_rx: Option<cbc::Receiver<(u8, u8)>>,
+    fsm_hall_requests_tx: Option<cbc::Sender<Vec<Vec<bool>>>>,
+    fsm_cab_request_tx: Option<cbc::Sender<u8>>,
+    fsm_parking_floor_tx: Option<cbc::Sender<Option<u8>>>,
+    fsm_operating_mode_tx: Option<cbc::Sender<OperatingMode>>,
+    fsm_door_command_tx: Option<cbc::Sender<bool>>,
+    fsm_alarm_clear_tx: Option<cbc::Sender<()>>,
+    fsm_halt_tx: Option<cbc::Sender<bool>>,
+    fsm_inspect_tx: Option<cbc::Sender<cbc::Sender<FsmView>>>,
+    fsm_blocked_floors_tx: Option<cbc::Sender<Vec<u8>>>,
+    fsm_locked_floors_tx: Option<cbc::Sender<Vec<u8>>>,
+    fsm_state_rx: Option<cbc::Receiver<ElevatorState>>,
+    fsm_order_complete_rx: Option<cbc::Receiver<(u8, u8)>>,
+    net_data_send_tx: Option<cbc::Sender<ElevatorData>>,
+    net_data_recv_rx: Option<cbc::Receiver<ElevatorData>>,
+    net_sync_recv_rx: Option<cbc::Receiver<ElevatorData>>,
+    net_peer_update_rx: Option<cbc::Receiver<PeerUpdate>>,
+    net_peer_version_rx: Option<cbc::Receiver<(String, Option<PeerVersion>)>>,
+    net_consensus_send_tx: Option<cbc::Sender<(String, ConsensusMessage)>>,
+    net_consensus_recv_rx: Option<cbc::Receiver<(String, ConsensusMessage)>>,
+    net_quality_rx: Option<cbc::Receiver<NetworkQuality>>,
+    coordinator_command_rx: Option<cbc::Receiver<ControlCommand>>,
+    coordinator_query_rx: Option<cbc::Receiver<cbc::Sender<Snapshot>>>,
+    coordinator_terminate_rx: cbc::Receiver<Terminate>,
+}
+
+impl CoordinatorBuilder {
+    // A coordinator of the local elevator, starting from the data, with none of the policies and the channels
+    pub fn new(elevator_data: ElevatorData, local_id: String, n_floors: u8) -> CoordinatorBuilder {
+        CoordinatorBuilder {
+            elevator_data,
+            local_id,
+            n_floors,
+            floor_labels: FloorLabels::default(),
+            access_control: AccessControl::default(),
+            lockout_schedule: LockoutSchedule::default(),
+            traffic_policy: TrafficPolicy::default(),
+            repositioning_policy: RepositioningPolicy::default(),
+            fire_recall_floor: 0,
+            hall_call_cancel_window: Duration::ZERO,
+            hall_call_quorum: 0,
+            hall_call_confirmation: HallCallConfirmation::default(),
+            hall_call_deadline: Duration::ZERO,
+            hall_clearing: HallClearing::default(),
+            assigner: Box::new(CostFunction),
+            assign_in_background: false,
+            assignment_model: AssignmentModel::default(),
+            adaptation_policy: AdaptationPolicy::default(),
+            consensus: None,
+            network_counters: Arc::new(NetworkCounters::default()),
+            uptime: Arc::new(UptimeCounter::default()),
+            clock: Arc::new(RealClock),
+            wal: None,
+            dry_run: false,
+            hw_button_light_tx: None,
+            hw_request_rx: None,
+            fsm_hall_requests_tx: None,
+            fsm_cab_request_tx: None,
+            fsm_parking_floor_tx: None,
+            fsm_operating_mode_tx: None,
+            fsm_door_command_tx: None,
+            fsm_alarm_clear_tx: None,
+            fsm_halt_tx: None,
+            fsm_inspect_tx: None,
+            fsm_blocked_floors_tx: None,
+            fsm_locked_floors_tx: None,
+            fsm_state_rx: None,
+            fsm_order_complete_rx: None,
+            net_data_send_tx: None,
+            net_data_recv_rx: None,
+            net_sync_recv_rx: None,
+            net_peer_update_rx: None,
+            net_peer_version_rx: None,
+            net_consensus_send_tx: None,
+            net_consensus_recv_rx: None,
+            net_quality_rx: None,
+            coordinator_command_rx: None,
+            coordinator_query_rx: None,
+            coordinator_terminate_rx: cbc::never(),
+        }
+    }
+
+    pub fn floor_labels(mut self, floor_labels: FloorLabels) -> CoordinatorBuilder {
+        self.floor_labels = floor_labels;
+        self
+    }
+
+    pub fn access_control(mut self, access_control: AccessControl) -> CoordinatorBuilder {
+        self.access_control = access_control;
+        self
+    }
+
+    pub fn lockout_schedule(mut self, lockout_schedule: LockoutSchedule) -> CoordinatorBuilder {
+        self.lockout_schedule = lockout_schedule;
+        self
+    }
+
+    pub fn traffic_policy(mut self, traffic_policy: TrafficPolicy) -> CoordinatorBuilder {
+        self.traffic_policy = traffic_policy;
+        self
+    }
+
+    pub fn repositioning_policy(mut self, repositioning_policy: RepositioningPolicy) -> CoordinatorBuilder {
+        self.repositioning_policy = repositioning_policy;
+        self
+    }
+
+    pub fn fire_recall_floor(mut self, fire_recall_floor: u8) -> CoordinatorBuilder {
+        self.fire_recall_floor = fire_recall_floor;
+        self
+    }
+
+    pub fn hall_call_cancel_window(mut self, hall_call_cancel_window: Duration) -> CoordinatorBuilder {
+        self.hall_call_cancel_window = hall_call_cancel_window;
+        self
+    }
+
+    pub fn hall_call_quorum(mut self, hall_call_quorum: usize) -> CoordinatorBuilder {
+        self.hall_call_quorum = hall_call_quorum;
+        self
+    }
+
+    pub fn hall_call_confirmation(mut self, hall_call_confirmation: HallCallConfirmation) -> CoordinatorBuilder {
+        self.hall_call_confirmation = hall_call_confirmation;
+        self
+    }
+
+    pub fn hall_call_deadline(mut self, hall_call_deadline: Duration) -> CoordinatorBuilder {
+        self.hall_call_deadline = hall_call_deadline;
+        self
+    }
+
+    pub fn hall_clearing(mut self, hall_clearing: HallClearing) -> CoordinatorBuilder {
+        self.hall_clearing = hall_clearing;
+        self
+    }
+
+    pub fn assigner(mut self, assigner: Box<dyn AssignmentStrategy>) -> CoordinatorBuilder {
+        self.assigner = assigner;
+        self
+    }
+
+    pub fn assign_in_background(mut self, assign_in_background: bool) -> CoordinatorBuilder {
+        self.assign_in_background = assign_in_background;
+        self
+    }
+
+    pub fn assignment_model(mut self, assignment_model: AssignmentModel) -> CoordinatorBuilder {
+        self.assignment_model = assignment_model;
+        self
+    }
+
+    pub fn adaptation_policy(mut self, adaptation_policy: AdaptationPolicy) -> CoordinatorBuilder {
+        self.adaptation_policy = adaptation_policy;
+        self
+    }
+
+    pub fn consensus(mut self, consensus: Option<Consensus>) -> CoordinatorBuilder {
+        self.consensus = consensus;
+        self
+    }
+
+    pub fn network_counters(mut self, network_counters: Arc<NetworkCounters>) -> CoordinatorBuilder {
+        self.network_counters = network_counters;
+        self
+    }
+
+    pub fn uptime(mut self, uptime: Arc<UptimeCounter>) -> CoordinatorBuilder {
+        self.uptime = uptime;
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> CoordinatorBuilder {
+        self.clock = clock;
+        self
+    }
+
+    pub fn wal(mut self, wal: Option<WriteAheadLog>) -> CoordinatorBuilder {
+        self.wal = wal;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> CoordinatorBuilder {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn hw_button_light_tx(mut self, hw_button_light_tx: cbc::Sender<(u8, u8, bool)>) -> CoordinatorBuilder {
+        self.hw_button_light_tx = Some(hw_button_light_tx);
+        self
+    }
+
+    pub fn hw_request_rx(mut self, hw_request_rx: cbc::Receiver<(u8, u8)>) -> CoordinatorBuilder {
+        self.hw_request_rx = Some(hw_request_rx);
+        self
+    }
+
+    pub fn fsm_hall_requests_tx(mut self, fsm_hall_requests_tx: cbc::Sender<Vec<Vec<bool>>>) -> CoordinatorBuilder {
+        self.fsm_hall_requests_tx = Some(fsm_hall_requests_tx);
+        self
+    }
+
+    pub fn fsm_cab_request_tx(mut self, fsm_cab_request_tx: cbc::Sender<u8>) -> CoordinatorBuilder {
+        self.fsm_cab_request_tx = Some(fsm_cab_request_tx);
+        self
+    }
+
+    pub fn fsm_parking_floor_tx(mut self, fsm_parking_floor_tx: cbc::Sender<Option<u8>>) -> CoordinatorBuilder {
+        self.fsm_parking_floor_tx = Some(fsm_parking_floor_tx);
+        self
+    }
+
+    pub fn fsm_operating_mode_tx(mut self, fsm_operating_mode_tx: cbc::Sender<OperatingMode>) -> CoordinatorBuilder {
+        self.fsm_operating_mode_tx = Some(fsm_operating_mode_tx);
+        self
+    }
+
+    pub fn fsm_door_command_tx(mut self, fsm_door_command_tx: cbc::Sender<bool>) -> CoordinatorBuilder {
+        self.fsm_door_command_tx = Some(fsm_door_command_tx);
+        self
+    }
+
+    pub fn fsm_alarm_clear_tx(mut self, fsm_alarm_clear_tx: cbc::Sender<()>) -> CoordinatorBuilder {
+        self.fsm_alarm_clear_tx = Some(fsm_alarm_clear_tx);
+        self
+    }
+
+    pub fn fsm_halt_tx(mut self, fsm_halt_tx: cbc::Sender<bool>) -> CoordinatorBuilder {
+        self.fsm_halt_tx = Some(fsm_halt_tx);
+        self
+    }
+
+    pub fn fsm_inspect_tx(mut self, fsm_inspect_tx: cbc::Sender<cbc::Sender<FsmView>>) -> CoordinatorBuilder {
+        self.fsm_inspect_tx = Some(fsm_inspect_tx);
+        self
+    }
+
+    pub fn fsm_blocked_floors_tx(mut self, fsm_blocked_floors_tx: cbc::Sender<Vec<u8>>) -> CoordinatorBuilder {
+        self.fsm_blocked_floors_tx = Some(fsm_blocked_floors_tx);
+        self
+    }
+
+    pub fn fsm_locked_floors_tx(mut self, fsm_locked_floors_tx: cbc::Sender<Vec<u8>>) -> CoordinatorBuilder {
+        self.fsm_locked_floors_tx = Some(fsm_locked_floors_tx);
+        self
+    }
+
+    pub fn fsm_state_rx(mut self, fsm_state_rx: cbc::Receiver<ElevatorState>) -> CoordinatorBuilder {
+        self.fsm_state_rx = Some(fsm_state_rx);
+        self
+    }
+
+    pub fn fsm_order_complete_rx(mut self, fsm_order_complete_rx: cbc::Receiver<(u8, u8)>) -> CoordinatorBuilder {
+        self.fsm_order_complete_rx = Some(fsm_order_complete_rx);
+        self
+    }
+
+    pub fn net_data_send_tx(mut self, net_data_send_tx: cbc::Sender<ElevatorData>) -> CoordinatorBuilder {
+        self.net_data_send_tx = Some(net_data_send_tx);
+        self
+    }
+
+    pub fn net_data_recv_rx(mut self, net_data_recv_rx: cbc::Receiver<ElevatorData>) -> CoordinatorBuilder {
+        self.net_data_recv_rx = Some(net_data_recv_rx);
+        self
+    }
+
+    pub fn net_sync_recv_rx(mut self, net_sync_recv_rx: cbc::Receiver<ElevatorData>) -> CoordinatorBuilder {
+        self.net_sync_recv_rx = Some(net_sync_recv_rx);
+        self
+    }
+
+    pub fn net_peer_update_rx(mut self, net_peer_update_rx: cbc::Receiver<PeerUpdate>) -> CoordinatorBuilder {
+        self.net_peer_update_rx = Some(net_peer_update_rx);
+        self
+    }
+
+    pub fn net_consensus_send_tx(mut self, net_consensus_send_tx: cbc::Sender<(String, ConsensusMessage)>) -> CoordinatorBuilder {
+        self.net_consensus_send_tx = Some(net_consensus_send_tx);
+        self
+    }
+
+    pub fn net_consensus_recv_rx(mut self, net_consensus_recv_rx: cbc::Receiver<(String, ConsensusMessage)>) -> CoordinatorBuilder {
+        self.net_consensus_recv_rx = Some(net_consensus_recv_rx);
+        self
+    }
+
+    pub fn net_peer_version_rx(mut self, net_peer_version_rx: cbc::Receiver<(String, Option<PeerVersion>)>) -> CoordinatorBuilder {
+        self.net_peer_version_rx = Some(net_peer_version_rx);
+        self
+    }
+
+    pub fn net_quality_rx(mut self, net_quality_rx: cbc::Receiver<NetworkQuality>) -> CoordinatorBuilder {
+        self.net_quality_rx = Some(net_quality_rx);
+        self
+    }
+
+    pub fn coordinator_command_rx(mut self, coordinator_command_rx: cbc::Receiver<ControlCommand>) -> CoordinatorBuilder {
+        self.coordinator_command_rx = Some(coordinator_command_rx);
+        self
+    }
+
+    pub fn coordinator_query_rx(mut self, coordinator_query_rx: cbc::Receiver<cbc::Sender<Snapshot>>) -> CoordinatorBuilder {
+        self.coordinator_query_rx = Some(coordinator_query_rx);
+        self
+    }
+
+    pub fn coordinator_terminate_rx(mut self, coordinator_terminate_rx: cbc::Receiver<Terminate>) -> CoordinatorBuilder {
+        self.coordinator_terminate_rx = coordinator_terminate_rx;
+        self
+    }
+
+    // The coordinator, once every channel is attached
+    pub fn build(mut self) -> Result<Coordinator, String> {
+        if let Some(state) = self.elevator_data.states.get_mut(&self.local_id) {
+            state.dry_run = self.dry_run;
+        }
+        Ok(Coordinator {
+            // Private fields
+            coordinator_terminate_rx: self.coordinator_terminate_rx,
+            elevator_data: self.elevator_data,
+            local_id: self.local_id,
+            n_floors: self.n_floors,
+            floor_labels: self.floor_labels,
+            access_control: self.access_control,
+            lockout_schedule: self.lockout_schedule,
+            locked_floors: Vec::new(),
+            traffic_policy: self.traffic_policy,
+            traffic_mode: TrafficMode::Normal,
+            repositioning_policy: self.repositioning_policy,
+            sector_floor: None,
+            parking_floor: None,
+            fire_recall_floor: self.fire_recall_floor,
+            operating_mode: OperatingMode::Normal,
+            hall_call_cancel_window: self.hall_call_cancel_window,
+            hall_call_presses: vec![vec![None; 2]; self.n_floors as usize],
+            hall_call_quorum: self.hall_call_quorum,
+            hall_call_confirmation: self.hall_call_confirmation,
+            hall_call_deadline: self.hall_call_deadline,
+            hall_call_deadlines: vec![vec![None; 2]; self.n_floors as usize],
+            overdue_elevators: BTreeMap::new(),
+            hall_clearing: self.hall_clearing,
+            assigner: match self.assign_in_background {
+                true => AssignmentWorker::background(self.assigner),
+                false => AssignmentWorker::foreground(self.assigner),
+            },
+            pending_transmit: false,
+            assignment_model: self.assignment_model,
+            adaptation_policy: self.adaptation_policy,
+            conservative: false,
+            observed_hall_calls: vec![vec![false; 2]; self.n_floors as usize],
+            confirmed_hall_requests: vec![vec![false; 2]; self.n_floors as usize],
+            assigned_hall_requests: vec![vec![false; 2]; self.n_floors as usize],
+            lost_peers: HashSet::new(),
+            reconciliation_window: RECONCILIATION_WINDOW,
+            reconciliation_deadline: None,
+            consensus: self.consensus,
+            peers: Vec::new(),
+            network_counters: self.network_counters,
+            uptime: self.uptime,
+            clock: self.clock,
+            wal: self.wal,
+            hall_assignments: BTreeMap::new(),
+            reassignments: 0,
+            dry_run: self.dry_run,
+            failure: None,
+
+            //Hardware channels
+            hw_button_light_tx: attached(self.hw_button_light_tx, "coordinator", "hw_button_light_tx")?,
+            hw_request_rx: attached(self.hw_request_rx, "coordinator", "hw_request_rx")?,
+
+            // FSM channels
+            fsm_hall_requests_tx: attached(self.fsm_hall_requests_tx, "coordinator", "fsm_hall_requests_tx")?,
+            fsm_cab_request_tx: attached(self.fsm_cab_request_tx, "coordinator", "fsm_cab_request_tx")?,
+            fsm_parking_floor_tx: attached(self.fsm_parking_floor_tx, "coordinator", "fsm_parking_floor_tx")?,
+            fsm_operating_mode_tx: attached(self.fsm_operating_mode_tx, "coordinator", "fsm_operating_mode_tx")?,
+            fsm_door_command_tx: attached(self.fsm_door_command_tx, "coordinator", "fsm_door_command_tx")?,
+            fsm_alarm_clear_tx: attached(self.fsm_alarm_clear_tx, "coordinator", "fsm_alarm_clear_tx")?,
+            fsm_halt_tx: attached(self.fsm_halt_tx, "coordinator", "fsm_halt_tx")?,
+            fsm_inspect_tx: attached(self.fsm_inspect_tx, "coordinator", "fsm_inspect_tx")?,
+            fsm_blocked_floors_tx: self.fsm_blocked_floors_tx,
+            fsm_locked_floors_tx: self.fsm_locked_floors_tx,
+            fsm_state_rx: attached(self.fsm_state_rx, "coordinator", "fsm_state_rx")?,
+            fsm_order_complete_rx: attached(self.fsm_order_complete_rx, "coordinator", "fsm_order_complete_rx")?,
+
+            // Netowrk channels
+            net_data_recv_rx: attached(self.net_data_recv_rx, "coordinator", "net_data_recv_rx")?,
+            net_sync_recv_rx: attached(self.net_sync_recv_rx, "coordinator", "net_sync_recv_rx")?,
+            net_peer_update_rx: attached(self.net_peer_update_rx, "coordinator", "net_peer_update_rx")?,
+            net_peer_version_rx: attached(self.net_peer_version_rx, "coordinator", "net_peer_version_rx")?,
+            net_data_send_tx: attached(self.net_data_send_tx, "coordinator", "net_data_send_tx")?,
+            net_consensus_send_tx: attached(self.net_consensus_send_tx, "coordinator", "net_consensus_send_tx")?,
+            net_consensus_recv_rx: attached(self.net_consensus_recv_rx, "coordinator", "net_consensus_recv_rx")?,
+            net_quality_rx: attached(self.net_quality_rx, "coordinator", "net_quality_rx")?,
+
+            // Console channels
+            coordinator_command_rx: attached(self.coordinator_command_rx, "coordinator", "coordinator_command_rx")?,
+            coordinator_query_rx: attached(self.coordinator_query_rx, "coordinator", "coordinator_query_rx")?,
+        })
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Runs the hall_request_assigner executable, returning the hall requests assigned to each elevator
+pub fn execute_hall_request_assigner(elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+    let hra_input = hall_request_assigner_input(elevator_data);
+
+    // Run the executable with serialized_data as input
+    let hra_output = Command::new(HALL_REQUEST_ASSIGNER)
+        .arg("--input")
+        .arg(&hra_input)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if !hra_output.status.success() {
+        return Err(String::from_utf8_lossy(&hra_output.stderr).to_string());
+    }
+
+    // Fetch and deserialize output
+    let hra_output_str = String::from_utf8(hra_output.stdout).map_err(|e| e.to_string())?;
+    serde_json::from_str::<HashMap<String, Vec<Vec<bool>>>>(&hra_output_str).map_err(|e| e.to_string())
+}
+
+// The JSON input of the hall_request_assigner executable: the hall requests and the states of the elevators
+pub fn hall_request_assigner_input(elevator_data: &ElevatorData) -> String {
+    // Serialize data
+    let mut json_value: serde_json::Value = serde_json::to_value(elevator_data)
+        .expect("Failed to serialize data");
+
+    // Remove the fields the executable does not know about from the serialized data
+    json_value.as_object_mut().unwrap().remove("version");
+    json_value.as_object_mut().unwrap().remove("clock");
+    json_value.as_object_mut().unwrap().remove("trafficMode");
+    json_value.as_object_mut().unwrap().remove("fireRecall");
+    json_value.as_object_mut().unwrap().remove("independentService");
+    json_value.as_object_mut().unwrap().remove("hallCancellations");
+    json_value.as_object_mut().unwrap().remove("hallOrders");
+    json_value.as_object_mut().unwrap().remove("hallAcknowledgements");
+    json_value.as_object_mut().unwrap().remove("hallRequestTimes");
+    json_value.as_object_mut().unwrap().remove("hallCompletionTimes");
+    if let Some(states) = json_value["states"].as_object_mut() {
+        for state in states.values_mut() {
+            state.as_object_mut().unwrap().remove("mode");
+            state.as_object_mut().unwrap().remove("stopPlan");
+            state.as_object_mut().unwrap().remove("obstructionAlarm");
+            state.as_object_mut().unwrap().remove("door");
+            state.as_object_mut().unwrap().remove("halted");
+            state.as_object_mut().unwrap().remove("capacity");
+            state.as_object_mut().unwrap().remove("arrivalTimes");
+            state.as_object_mut().unwrap().remove("uptime");
+        }
+    }
+
+    serde_json::to_string(&json_value).expect("Failed to serialize data")
+}
+
+// Number of times a hall call has been cancelled. Packets without the counts have none.
+pub(super) fn cancellation_count(elevator_data: &ElevatorData, floor: u8, call: u8) -> u64 {
+    elevator_data.hall_cancellations
+        .get(floor as usize)
+        .and_then(|cancellations| cancellations.get(call as usize))
+        .copied()
+        .unwrap_or(0)
+}
+
+// How far along a hall call is on the local side compared with the remote one. None where either side has no order for the call.
+pub(super) fn order_precedence(local: &ElevatorData, remote: &ElevatorData, floor: u8, call: u8) -> Option<std::cmp::Ordering> {
+    let order = |data: &ElevatorData| data.hall_orders.get(floor as usize).and_then(|orders| orders.get(call as usize)).copied();
+    Some(order(local)?.precedence(&order(remote)?))
+}
+
+// The latest time a hall call was placed, on the sides holding it. None if neither does.
+fn request_time(local: &ElevatorData, remote: &ElevatorData, floor: u8, call: u8) -> Option<u64> {
+    [local, remote]
+        .into_iter()
+        .filter(|data| data.hall_requests.get(floor as usize).is_some_and(|requests| requests[call as usize]))
+        .map(|data| call_time(&data.hall_request_times, floor, call))
+        .max()
+}
+
+// The latest time a hall call was completed on either side
+fn completion_time(local: &ElevatorData, remote: &ElevatorData, floor: u8, call: u8) -> u64 {
+    call_time(&local.hall_completion_times, floor, call).max(call_time(&remote.hall_completion_times, floor, call))
+}
+
+// Packets without the times have none
+fn call_time(times: &[Vec<u64>], floor: u8, call: u8) -> u64 {
+    times.get(floor as usize).and_then(|times| times.get(call as usize)).copied().unwrap_or(0)
+}
+
+fn resize_call_times(elevator_data: &mut ElevatorData, n_floors: u8) {
+    elevator_data.hall_request_times.resize(n_floors as usize, vec![0; 2]);
+    elevator_data.hall_completion_times.resize(n_floors as usize, vec![0; 2]);
+}
+
+// Seconds since the Unix epoch. The wall clock is the only time the elevators share.
+fn unix_time() -> u64 {
+    Utc::now().timestamp().max(0) as u64
+}
+
+// Checks a received package against hard limits. The per-call data of older peers may be missing, but never too long.
+pub(super) fn check_package(elevator_data: &ElevatorData, n_floors: u8, local_version: u64) -> Result<(), String> {
+    let n_floors = n_floors as usize;
+
+    if elevator_data.version > local_version.saturating_add(MAX_VERSION_JUMP) {
+        return Err(format!("version jumps from {}", local_version));
+    }
+    if elevator_data.hall_requests.len() != n_floors || elevator_data.hall_requests.iter().any(|requests| requests.len() != 2) {
+        return Err("hall requests do not match the floors".to_string());
+    }
+    if !fits_floors(&elevator_data.hall_cancellations, n_floors)
+        || !fits_floors(&elevator_data.hall_orders, n_floors)
+        || !fits_floors(&elevator_data.hall_acknowledgements, n_floors)
+        || !fits_floors(&elevator_data.hall_request_times, n_floors)
+        || !fits_floors(&elevator_data.hall_completion_times, n_floors)
+    {
+        return Err("hall call data does not match the floors".to_string());
+    }
+    if elevator_data.states.len() > MAX_ELEVATORS
+        || elevator_data.clock.len() > MAX_ELEVATORS
+        || elevator_data.independent_service.len() > MAX_ELEVATORS
+        || elevator_data.hall_acknowledgements.iter().flatten().any(|acknowledgements| acknowledgements.len() > MAX_ELEVATORS)
+    {
+        return Err(format!("more than {} elevators", MAX_ELEVATORS));
+    }
+    for (id, state) in &elevator_data.states {
+        let recall_floor_fits = match state.mode {
+            OperatingMode::FireRecall(floor) => (floor as usize) < n_floors,
+            _ => true,
+        };
+        if state.floor as usize >= n_floors || !recall_floor_fits {
+            return Err(format!("elevator {} is at a floor out of range", id));
+        }
+        if state.cab_requests.len() != n_floors {
+            return Err(format!("cab requests of elevator {} do not match the floors", id));
+        }
+    }
+    Ok(())
+}
+
+// The floors and calls set in a matrix of hall calls
+fn hall_calls_of(hall_requests: &[Vec<bool>]) -> Vec<(u8, u8)> {
+    hall_requests
+        .iter()
+        .enumerate()
+        .flat_map(|(floor, calls)| [HALL_UP, HALL_DOWN].into_iter().filter(|call| calls[*call as usize]).map(move |call| (floor as u8, call)))
+        .collect()
+}
+
+// The calls assigned to one elevator before and to another now. Calls left unassigned either time are not counted.
+fn reassigned_calls(previous: &BTreeMap<String, Vec<Vec<bool>>>, assignments: &BTreeMap<String, Vec<Vec<bool>>>) -> u64 {
+    let assignee = |assignments: &BTreeMap<String, Vec<Vec<bool>>>, floor: usize, call: usize| {
+        assignments
+            .iter()
+            .find(|(_, assigned)| assigned.get(floor).and_then(|calls| calls.get(call)).copied().unwrap_or(false))
+            .map(|(id, _)| id.clone())
+    };
+    let n_floors = assignments.values().map(|assigned| assigned.len()).max().unwrap_or(0);
+    let mut reassigned = 0;
+    for floor in 0..n_floors {
+        for call in [HALL_UP, HALL_DOWN] {
+            let before = assignee(previous, floor, call as usize);
+            let now = assignee(assignments, floor, call as usize);
+            if before.is_some() && now.is_some() && before != now {
+                reassigned += 1;
+            }
+        }
+    }
+    reassigned
+}
+
+fn fits_floors<T>(per_call: &[Vec<T>], n_floors: usize) -> bool {
+    per_call.len() <= n_floors && per_call.iter().all(|calls| calls.len() <= 2)
+}
+
+// The elevators that have acknowledged a hall call. Packets without acknowledgements have none.
+fn hall_acknowledgements(elevator_data: &ElevatorData, floor: u8, call: u8) -> Option<&BTreeSet<String>> {
+    elevator_data.hall_acknowledgements
+        .get(floor as usize)
+        .and_then(|acknowledgements| acknowledgements.get(call as usize))
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::Coordinator;
+    use crate::shared::ElevatorData;
+    use crate::shared::ElevatorState;
+    use network_rust::udpnet::peers::PeerUpdate;
+
+    impl Coordinator {
+        // Publicly expose the private fields for testing
+        pub fn test_get_data(&self) -> &ElevatorData {
+            &self.elevator_data
+        }
+
+        pub fn test_get_local_id(&self) -> &String {
+            &self.local_id
+        }
+        
+        pub fn test_get_n_floors(&self) -> &u8 {
+            &self.n_floors
+        }
+
+        pub fn test_set_clock(&mut self, clock: std::sync::Arc<dyn crate::shared::Clock>) {
+            self.clock = clock;
+        }
+
+        pub fn test_get_network_counters(&self) -> &std::sync::Arc<crate::network::NetworkCounters> {
+            &self.network_counters
+        }
+
+        pub fn test_get_traffic_mode(&self) -> super::TrafficMode {
+            self.traffic_mode
+        }
+
+        pub fn test_get_operating_mode(&self) -> super::OperatingMode {
+            self.operating_mode
+        }
+
+        pub fn test_update_lights(&mut self, light: (u8, u8, bool)) {
+            self.update_light(light);
+        }
+
+        pub fn test_hall_request_assigner(&mut self, transmit: bool) {
+            self.hall_request_assigner(transmit);
+        }
+
+        pub fn test_get_snapshot(&self) -> crate::shared::Snapshot {
+            self.snapshot()
+        }
+
+        // Sets the hall calls held, stepping their orders on with them
+        pub fn test_set_hall_requests(&mut self, hall_requests: Vec<Vec<bool>>) {
+            for (orders, requests) in self.elevator_data.hall_orders.iter_mut().zip(hall_requests.iter()) {
+                for (order, request) in orders.iter_mut().zip(requests.iter()) {
+                    order.advance(*request, false);
+                }
+            }
+            self.elevator_data.hall_requests = hall_requests;
+        }
+
+        pub fn test_set_state(&mut self, elevator: String, state: ElevatorState) {
             self.elevator_data.states.insert(elevator, state);
         }
 
+        pub fn test_set_hall_call_quorum(&mut self, hall_call_quorum: usize) {
+            self.hall_call_quorum = hall_call_quorum;
+        }
+
+        pub fn test_set_hall_call_confirmation(&mut self, hall_call_confirmation: super::HallCallConfirmation) {
+            self.hall_call_confirmation = hall_call_confirmation;
+        }
+
+        pub fn test_set_hall_call_deadline(&mut self, hall_call_deadline: std::time::Duration) {
+            self.hall_call_deadline = hall_call_deadline;
+        }
+
+        pub fn test_update_lockout(&mut self) {
+            if self.update_lockout() {
+                self.hall_request_assigner(false);
+            }
+        }
+
+        pub fn test_check_hall_call_deadlines(&mut self) {
+            if self.check_hall_call_deadlines() {
+                self.hall_request_assigner(false);
+            }
+        }
+
+        pub fn test_set_hall_clearing(&mut self, hall_clearing: super::HallClearing) {
+            self.hall_clearing = hall_clearing;
+        }
+
+        pub fn test_set_reconciliation_window(&mut self, reconciliation_window: std::time::Duration) {
+            self.reconciliation_window = reconciliation_window;
+        }
+
+        pub fn test_set_consensus(&mut self, consensus: super::Consensus) {
+            self.consensus = Some(consensus);
+        }
+
+        pub fn test_set_assignment_model(&mut self, assignment_model: super::AssignmentModel) {
+            self.assignment_model = assignment_model;
+        }
+
+        pub fn test_set_adaptation_policy(&mut self, adaptation_policy: crate::shared::AdaptationPolicy) {
+            self.adaptation_policy = adaptation_policy;
+        }
+
+        pub fn test_set_dry_run(&mut self, dry_run: bool) {
+            self.dry_run = dry_run;
+            if let Some(state) = self.elevator_data.states.get_mut(&self.local_id) {
+                state.dry_run = dry_run;
+            }
+        }
+
+        pub fn test_set_wal(&mut self, wal: Option<crate::storage::WriteAheadLog>) {
+            self.wal = wal;
+        }
+
         pub fn test_handle_event(&mut self, event: super::Event) {
             self.handle_event(event);
         }
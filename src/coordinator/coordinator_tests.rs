@@ -13,6 +13,40 @@
  *  - test_coordinator_handle_event_new_peer_update
  *  - test_coordinator_handle_event_new_elevator_state
  *  - test_coordinator_handle_event_order_complete
+ *  - test_coordinator_all_hall_clearing
+ *  - test_coordinator_access_control_cab_request
+ *  - test_coordinator_access_control_hall_assignment
+ *  - test_coordinator_zone_hall_assignment
+ *  - test_coordinator_zoning_profile
+ *  - test_coordinator_lockout_schedule
+ *  - test_coordinator_lockout_window
+ *  - test_coordinator_traffic_mode_command
+ *  - test_coordinator_traffic_mode_schedule
+ *  - test_coordinator_traffic_mode_priority_assignment
+ *  - test_coordinator_fire_service
+ *  - test_coordinator_independent_service
+ *  - test_coordinator_halt
+ *  - test_coordinator_hall_call_cancellation
+ *  - test_coordinator_idle_repositioning
+ *  - test_coordinator_merge_keeps_cancellation
+ *  - test_coordinator_concurrent_packages
+ *  - test_coordinator_served_call_not_resurrected
+ *  - test_coordinator_consensus_hall_calls
+ *  - test_coordinator_hall_call_quorum
+ *  - test_coordinator_hall_call_all_peers
+ *  - test_coordinator_hall_call_deadline
+ *  - test_coordinator_anti_entropy_merge
+ *  - test_coordinator_split_brain_reconciliation
+ *  - test_coordinator_quarantine_package
+ *  - test_coordinator_hall_call_injection
+ *  - test_coordinator_export_state
+ *  - test_coordinator_write_ahead_log
+ *  - test_coordinator_push_assignment
+ *  - test_coordinator_reassignments
+ *  - test_coordinator_hall_owners
+ *  - test_coordinator_conservative_serving
+ *  - test_coordinator_dry_run
+ *  - test_coordinator_builder_missing_channel
  * 
  */
 
@@ -22,44 +56,110 @@
 #[cfg(test)]
 mod coordinator_tests {
     use crate::coordinator::coordinator::Event;
-    use crate::Coordinator;
+    use crate::{Coordinator, CoordinatorBuilder};
     use crate::ElevatorState;
     use crate::ElevatorData;
+    use crate::shared::SimulatedClock;
+    use crate::config::{AccessConfig, AdaptationConfig, LockoutConfig, RepositioningConfig, TrafficConfig, TrafficScheduleConfig, ZoneConfig, ZoningProfileConfig};
+    use crate::shared::{AccessControl, AdaptationPolicy, AssignmentModel, Consensus, ConsensusMessage, ControlCommand, FsmView, HallClearing, LockoutSchedule, OperatingMode, RepositioningPolicy, Snapshot, StateExport, TrafficMode, TrafficPolicy};
+    use crate::shared::{Behaviour, HallAssignment, HallCallConfirmation, NetworkQuality, OrderState, Terminate};
+    use chrono::{Duration as ChronoDuration, Local, NaiveTime};
     use crate::shared::Direction::Up;
-    use std::time::Duration;
+    use crate::storage::{FileStorage, LogRetention, OrderMutation, RecoveredOrders, Storage, WriteAheadLog};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
     use std::thread::Builder;
     use core::panic;
     use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP, CAB};
     use network_rust::udpnet::peers::PeerUpdate;
-    use crossbeam_channel::unbounded;
+    use crossbeam_channel::{never, unbounded};
     use crossbeam_channel::Receiver;
     use crossbeam_channel::Sender;
 
 
+    // Sets a hall call of a package as the peer holding it would, stepping its order on with it
+    fn set_hall_call(data: &mut ElevatorData, floor: usize, call: u8, requested: bool) {
+        data.hall_requests[floor][call as usize] = requested;
+        data.hall_orders[floor][call as usize].advance(requested, requested);
+    }
+
     fn setup_coordinator() -> (
         Coordinator,
         Receiver<(u8, u8, bool)>,   // hw_button_light_rx
         Sender<(u8, u8)>,           // hw_request_tx
         Receiver<Vec<Vec<bool>>>,   // fsm_hall_requests_rx
         Receiver<u8>,               // fsm_cab_request_rx
+        Receiver<Option<u8>>,       // fsm_parking_floor_rx
+        Receiver<OperatingMode>,    // fsm_operating_mode_rx
+        Receiver<bool>,             // fsm_door_command_rx
+        Receiver<()>,               // fsm_alarm_clear_rx
+        Receiver<bool>,             // fsm_halt_rx
+        Receiver<Sender<FsmView>>,  // fsm_inspect_rx
+        Sender<ElevatorState>,      // fsm_state_tx
+        Sender<(u8, u8)>,           // fsm_order_complete_tx
+        Receiver<ElevatorData>,     // net_data_send_rx
+        Sender<ElevatorData>,       // net_data_recv_tx
+        Sender<ElevatorData>,       // net_sync_recv_tx
+        Sender<PeerUpdate>,         // net_peer_update_tx
+        Receiver<(String, ConsensusMessage)>, // net_consensus_send_rx
+        Sender<(String, ConsensusMessage)>,   // net_consensus_recv_tx
+        Sender<ControlCommand>,     // coordinator_command_tx
+        Sender<Sender<Snapshot>>,   // coordinator_query_tx
+        Sender<Terminate>) {        // coordinator_terminate_tx
+        setup_coordinator_with_policies(AccessControl::default(), LockoutSchedule::default(), TrafficPolicy::default(), RepositioningPolicy::default())
+    }
+
+    fn setup_coordinator_with_policies(
+        access_control: AccessControl,
+        lockout_schedule: LockoutSchedule,
+        traffic_policy: TrafficPolicy,
+        repositioning_policy: RepositioningPolicy,
+    ) -> (
+        Coordinator,
+        Receiver<(u8, u8, bool)>,   // hw_button_light_rx
+        Sender<(u8, u8)>,           // hw_request_tx
+        Receiver<Vec<Vec<bool>>>,   // fsm_hall_requests_rx
+        Receiver<u8>,               // fsm_cab_request_rx
+        Receiver<Option<u8>>,       // fsm_parking_floor_rx
+        Receiver<OperatingMode>,    // fsm_operating_mode_rx
+        Receiver<bool>,             // fsm_door_command_rx
+        Receiver<()>,               // fsm_alarm_clear_rx
+        Receiver<bool>,             // fsm_halt_rx
+        Receiver<Sender<FsmView>>,  // fsm_inspect_rx
         Sender<ElevatorState>,      // fsm_state_tx
         Sender<(u8, u8)>,           // fsm_order_complete_tx
         Receiver<ElevatorData>,     // net_data_send_rx
         Sender<ElevatorData>,       // net_data_recv_tx
+        Sender<ElevatorData>,       // net_sync_recv_tx
         Sender<PeerUpdate>,         // net_peer_update_tx
-        Sender<()>) {               // coordinator_terminate_tx
+        Receiver<(String, ConsensusMessage)>, // net_consensus_send_rx
+        Sender<(String, ConsensusMessage)>,   // net_consensus_recv_tx
+        Sender<ControlCommand>,     // coordinator_command_tx
+        Sender<Sender<Snapshot>>,   // coordinator_query_tx
+        Sender<Terminate>) {        // coordinator_terminate_tx
 
         // Arrange mock channels
         let (hw_button_light_tx, hw_button_light_rx) = unbounded::<(u8, u8, bool)>();
         let (hw_request_tx, hw_request_rx) = unbounded::<(u8, u8)>();
         let (fsm_hall_requests_tx, fsm_hall_requests_rx) = unbounded::<Vec<Vec<bool>>>();
         let (fsm_cab_request_tx, fsm_cab_request_rx) = unbounded::<u8>();
+        let (fsm_parking_floor_tx, fsm_parking_floor_rx) = unbounded::<Option<u8>>();
+        let (fsm_operating_mode_tx, fsm_operating_mode_rx) = unbounded::<OperatingMode>();
+        let (fsm_door_command_tx, fsm_door_command_rx) = unbounded::<bool>();
+        let (fsm_alarm_clear_tx, fsm_alarm_clear_rx) = unbounded::<()>();
+        let (fsm_halt_tx, fsm_halt_rx) = unbounded::<bool>();
+        let (fsm_inspect_tx, fsm_inspect_rx) = unbounded::<Sender<FsmView>>();
         let (fsm_state_tx, fsm_state_rx) = unbounded::<ElevatorState>();
         let (fsm_order_complete_tx, fsm_order_complete_rx) = unbounded::<(u8, u8)>();
         let (net_data_send_tx, net_data_send_rx) = unbounded::<ElevatorData>();
         let (net_data_recv_tx, net_data_recv_rx) = unbounded::<ElevatorData>();
+        let (net_sync_recv_tx, net_sync_recv_rx) = unbounded::<ElevatorData>();
         let (net_peer_update_tx, net_peer_update_rx) = unbounded::<PeerUpdate>();
-        let (coordinator_terminate_tx, coordinator_terminate_rx) = unbounded::<()>();
+        let (net_consensus_send_tx, net_consensus_send_rx) = unbounded::<(String, ConsensusMessage)>();
+        let (net_consensus_recv_tx, net_consensus_recv_rx) = unbounded::<(String, ConsensusMessage)>();
+        let (coordinator_command_tx, coordinator_command_rx) = unbounded::<ControlCommand>();
+        let (coordinator_query_tx, coordinator_query_rx) = unbounded::<Sender<Snapshot>>();
+        let (coordinator_terminate_tx, coordinator_terminate_rx) = unbounded::<Terminate>();
         
         // Default configuration
         let n_floors = 4;
@@ -67,30 +167,57 @@ mod coordinator_tests {
         let mut elevator_data = ElevatorData::new(n_floors.clone());
         elevator_data.states.insert(id.clone(), ElevatorState::new(n_floors.clone()));
 
-        (Coordinator::new(
-            elevator_data,
-            id,
-            n_floors,
-            hw_button_light_tx,
-            hw_request_rx,
-            fsm_hall_requests_tx,
-            fsm_cab_request_tx,
-            fsm_state_rx,
-            fsm_order_complete_rx,
-            net_data_send_tx,
-            net_data_recv_rx,
-            net_peer_update_rx,
-            coordinator_terminate_rx,
-        ),
+        (CoordinatorBuilder::new(elevator_data, id, n_floors)
+            .access_control(access_control)
+            .lockout_schedule(lockout_schedule)
+            .traffic_policy(traffic_policy)
+            .repositioning_policy(repositioning_policy)
+            .hall_call_cancel_window(Duration::from_millis(500))
+            .hw_button_light_tx(hw_button_light_tx)
+            .hw_request_rx(hw_request_rx)
+            .fsm_hall_requests_tx(fsm_hall_requests_tx)
+            .fsm_cab_request_tx(fsm_cab_request_tx)
+            .fsm_parking_floor_tx(fsm_parking_floor_tx)
+            .fsm_operating_mode_tx(fsm_operating_mode_tx)
+            .fsm_door_command_tx(fsm_door_command_tx)
+            .fsm_alarm_clear_tx(fsm_alarm_clear_tx)
+            .fsm_halt_tx(fsm_halt_tx)
+            .fsm_inspect_tx(fsm_inspect_tx)
+            .fsm_state_rx(fsm_state_rx)
+            .fsm_order_complete_rx(fsm_order_complete_rx)
+            .net_data_send_tx(net_data_send_tx)
+            .net_data_recv_rx(net_data_recv_rx)
+            .net_sync_recv_rx(net_sync_recv_rx)
+            .net_peer_update_rx(net_peer_update_rx)
+            .net_consensus_send_tx(net_consensus_send_tx)
+            .net_consensus_recv_rx(net_consensus_recv_rx)
+            .net_peer_version_rx(never())
+            .net_quality_rx(never())
+            .coordinator_command_rx(coordinator_command_rx)
+            .coordinator_query_rx(coordinator_query_rx)
+            .coordinator_terminate_rx(coordinator_terminate_rx)
+            .build()
+            .unwrap(),
         hw_button_light_rx,
         hw_request_tx,
         fsm_hall_requests_rx,
         fsm_cab_request_rx,
+        fsm_parking_floor_rx,
+        fsm_operating_mode_rx,
+        fsm_door_command_rx,
+        fsm_alarm_clear_rx,
+        fsm_halt_rx,
+        fsm_inspect_rx,
         fsm_state_tx,
         fsm_order_complete_tx,
         net_data_send_rx,
         net_data_recv_tx,
+        net_sync_recv_tx,
         net_peer_update_tx,
+        net_consensus_send_rx,
+        net_consensus_recv_tx,
+        coordinator_command_tx,
+        coordinator_query_tx,
         coordinator_terminate_tx)
     }
 
@@ -103,11 +230,22 @@ mod coordinator_tests {
             _hw_request_tx,
             _fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             _fsm_order_complete_tx,
             _net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             _coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -127,16 +265,27 @@ mod coordinator_tests {
     fn test_coordinator_update_lights() {
         // Arrange
         let (
-            coordinator,
+            mut coordinator,
             hw_button_light_rx,
             _hw_request_tx,
             _fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             _fsm_order_complete_tx,
             _net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             _coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -174,11 +323,22 @@ mod coordinator_tests {
             _hw_request_tx,
             fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             _fsm_order_complete_tx,
             net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             _coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -226,8 +386,11 @@ mod coordinator_tests {
             Ok(msg) => {
                 let mut expected_data = ElevatorData::new(n_floors.clone());
                 expected_data.version = 1;
+                expected_data.clock.tick(&id);
                 expected_data.hall_requests = hall_requests.clone();
+                expected_data.hall_orders[2][HALL_UP as usize].advance(true, true);
                 expected_data.states.insert(id.clone(), state.clone());
+                expected_data.hall_owners.insert(id.clone(), hall_requests.clone());
                 assert_eq!(msg, expected_data, "Mismatch for net_data_send_rx");
             },
             Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
@@ -244,11 +407,22 @@ mod coordinator_tests {
             _hw_request_tx,
             fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             _fsm_order_complete_tx,
             _net_data_send_rx,
             net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -258,7 +432,7 @@ mod coordinator_tests {
         new_package.states.insert("elevator".to_string(), ElevatorState::new(n_floors));
         new_package.version = 1;
         new_package.hall_requests = vec![vec![false; 2]; n_floors as usize];
-        new_package.hall_requests[2][HALL_UP as usize] = true;
+        set_hall_call(&mut new_package, 2, HALL_UP, true);
 
         let coordinator_thread = Builder::new().name("coordinator".into()).spawn(move || coordinator.run()).unwrap();
             
@@ -277,8 +451,8 @@ mod coordinator_tests {
         }
 
         // Cleanup
-        coordinator_terminate_tx.send(()).unwrap();
-        coordinator_thread.join().unwrap();
+        coordinator_terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(coordinator_thread.join().unwrap(), Ok(()));
         
     }
 
@@ -291,11 +465,22 @@ mod coordinator_tests {
             hw_request_tx,
             fsm_hall_requests_rx,
             fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             _fsm_order_complete_tx,
             net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -318,11 +503,19 @@ mod coordinator_tests {
 
         match net_data_send_rx.recv_timeout(timeout) {
             Ok(msg) => {
+                // The call is stamped with the wall clock
+                let request_time = msg.hall_request_times[2][HALL_UP as usize];
+                assert!(request_time > 0, "Request time not recorded");
+
                 let mut expected_data = ElevatorData::new(n_floors);
                 expected_data.version = 1;
+                expected_data.clock.tick("elevator");
                 expected_data.hall_requests = vec![vec![false; 2]; n_floors as usize];
                 expected_data.hall_requests[2][HALL_UP as usize] = true;
+                expected_data.hall_orders[2][HALL_UP as usize].advance(true, true);
+                expected_data.hall_request_times[2][HALL_UP as usize] = request_time;
                 expected_data.states.insert("elevator".to_string(), ElevatorState::new(n_floors));
+                expected_data.hall_owners.insert("elevator".to_string(), expected_data.hall_requests.clone());
                 assert_eq!(msg, expected_data, "Mismatch for net_data_send_rx");
             },
             Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
@@ -347,8 +540,8 @@ mod coordinator_tests {
         }
 
         // Cleanup
-        coordinator_terminate_tx.send(()).unwrap();
-        coordinator_thread.join().unwrap();
+        coordinator_terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(coordinator_thread.join().unwrap(), Ok(()));
     }
 
     #[test]
@@ -360,11 +553,22 @@ mod coordinator_tests {
             _hw_request_tx,
             _fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             _fsm_order_complete_tx,
             _net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             _coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -402,11 +606,22 @@ mod coordinator_tests {
             _hw_request_tx,
             fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             fsm_state_tx,
             _fsm_order_complete_tx,
             net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -421,6 +636,7 @@ mod coordinator_tests {
         let expected_hall_requests = vec![vec![false; 2]; n_floors as usize];
         let mut expected_elevator_data = ElevatorData::new(n_floors);
         expected_elevator_data.version = 1;
+        expected_elevator_data.clock.tick("elevator");
         expected_elevator_data.hall_requests = expected_hall_requests.clone();
         expected_elevator_data.states.insert("elevator".to_string(), new_state.clone());
 
@@ -446,8 +662,8 @@ mod coordinator_tests {
         }
         
         // Cleanup
-        coordinator_terminate_tx.send(()).unwrap();
-        coordinator_thread.join().unwrap();
+        coordinator_terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(coordinator_thread.join().unwrap(), Ok(()));
     }
 
     #[test]
@@ -459,11 +675,22 @@ mod coordinator_tests {
             _hw_request_tx,
             fsm_hall_requests_rx,
             _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
             _fsm_state_tx,
             fsm_order_complete_tx,
             net_data_send_rx,
             _net_data_recv_tx,
+            _net_sync_recv_tx,
             _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
             coordinator_terminate_tx
         ) = setup_coordinator();
 
@@ -488,9 +715,15 @@ mod coordinator_tests {
 
         match net_data_send_rx.recv_timeout(timeout) {
             Ok(msg) => {
+                // The completion is stamped with the wall clock
+                let completion_time = msg.hall_completion_times[2][HALL_DOWN as usize];
+                assert!(completion_time > 0, "Completion time not recorded");
+
                 let mut expected_elevator_data = ElevatorData::new(n_floors);
                 expected_elevator_data.version = 1;
+                expected_elevator_data.clock.tick("elevator");
                 expected_elevator_data.hall_requests = vec![vec![false; 2]; n_floors.clone() as usize];
+                expected_elevator_data.hall_completion_times[2][HALL_DOWN as usize] = completion_time;
                 expected_elevator_data.states.insert("elevator".to_string(), ElevatorState::new(n_floors));
                 assert_eq!(msg, expected_elevator_data, "Mismatch for net_data_send_rx");
             },
@@ -498,8 +731,2038 @@ mod coordinator_tests {
         }
 
         // Cleanup
-        coordinator_terminate_tx.send(()).unwrap();
-        coordinator_thread.join().unwrap();
+        coordinator_terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(coordinator_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_coordinator_all_hall_clearing() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[1] = vec![true; 2];
+        hall_requests[2] = vec![true; 2];
+        coordinator.test_set_hall_requests(hall_requests);
+
+        // Act
+        coordinator.test_handle_event(Event::OrderComplete((1, HALL_UP)));
+        let in_direction_lights = hw_button_light_rx.try_iter().filter(|light| !light.2).collect::<Vec<(u8, u8, bool)>>();
+        coordinator.test_set_hall_clearing(HallClearing::All);
+        coordinator.test_handle_event(Event::OrderComplete((2, CAB)));
+        let all_lights = hw_button_light_rx.try_iter().filter(|light| !light.2).collect::<Vec<(u8, u8, bool)>>();
+
+        // Assert
+        // The calls are lit as they are confirmed. Only the served call is cleared by default, and every call at the floor of the stop with all-call clearing
+        let elevator_data = coordinator.test_get_data();
+        assert_eq!(elevator_data.hall_requests[1], vec![false, true]);
+        assert_eq!(elevator_data.hall_requests[2], vec![false, false]);
+        assert!(elevator_data.hall_completion_times[2].iter().all(|time| *time > 0));
+        assert_eq!(in_direction_lights, vec![(1, HALL_UP, false)]);
+        assert_eq!(all_lights, vec![(2, HALL_UP, false), (2, HALL_DOWN, false), (2, CAB, false)]);
+    }
+
+    #[test]
+    fn test_coordinator_access_control_cab_request() {
+        // Arrange
+        let access_control = AccessControl::new(&AccessConfig {
+            restricted_floors: vec![3],
+            authorized_elevators: vec!["peer".to_string()],
+            zones: Vec::new(),
+            profiles: Vec::new(),
+        });
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(access_control, LockoutSchedule::default(), TrafficPolicy::default(), RepositioningPolicy::default());
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((3, CAB)));
+        coordinator.test_handle_event(Event::RequestReceived((2, CAB)));
+
+        // Assert
+        // Both are forwarded, for the FSM to refuse the one to the restricted floor, but only the other is lit
+        assert_eq!(fsm_cab_request_rx.try_recv(), Ok(3), "Mismatch for fsm_cab_request_rx");
+        assert_eq!(fsm_cab_request_rx.try_recv(), Ok(2), "Mismatch for fsm_cab_request_rx");
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((2, CAB, true)), "Mismatch for hw_button_light_rx");
+        assert!(hw_button_light_rx.try_recv().is_err(), "Cab light to restricted floor was lit");
+
+        let local_state = &coordinator.test_get_data().states["elevator"];
+        assert!(!local_state.cab_requests[3]);
+        assert!(local_state.cab_requests[2]);
+    }
+
+    #[test]
+    fn test_coordinator_access_control_hall_assignment() {
+        // Arrange
+        let access_control = AccessControl::new(&AccessConfig {
+            restricted_floors: vec![1],
+            authorized_elevators: vec!["peer".to_string()],
+            zones: Vec::new(),
+            profiles: Vec::new(),
+        });
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(access_control, LockoutSchedule::default(), TrafficPolicy::default(), RepositioningPolicy::default());
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let timeout = Duration::from_millis(500);
+
+        // The local elevator is idle at the restricted floor, the authorized peer is far away
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 1;
+        let mut peer_state = ElevatorState::new(n_floors);
+        peer_state.floor = 3;
+
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[1][HALL_UP as usize] = true;
+        hall_requests[0][HALL_UP as usize] = true;
+
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), peer_state);
+        coordinator.test_set_hall_requests(hall_requests);
+
+        // Act
+        coordinator.test_hall_request_assigner(false);
+
+        // Assert
+        let mut expected_hall_requests = vec![vec![false; 2]; n_floors as usize];
+        expected_hall_requests[0][HALL_UP as usize] = true;
+        match fsm_hall_requests_rx.recv_timeout(timeout) {
+            Ok(msg) => assert_eq!(msg, expected_hall_requests, "Restricted hall request assigned to unauthorized elevator"),
+            Err(e) => panic!("Error receiving fsm_hall_requests_rx: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_coordinator_zone_hall_assignment() {
+        // Arrange
+        let access_control = AccessControl::new(&AccessConfig {
+            restricted_floors: Vec::new(),
+            authorized_elevators: Vec::new(),
+            zones: vec![
+                ZoneConfig { elevator: "elevator".to_string(), floors: vec![0, 1, 2], parking_floor: None },
+                ZoneConfig { elevator: "peer".to_string(), floors: vec![2, 3], parking_floor: None },
+            ],
+            profiles: Vec::new(),
+        });
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(access_control, LockoutSchedule::default(), TrafficPolicy::default(), RepositioningPolicy::default());
+
+        let n_floors = *coordinator.test_get_n_floors();
+
+        // The local elevator is idle next to the top floor, which is outside its zone
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 2;
+        let peer_state = ElevatorState::new(n_floors);
+
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[3][HALL_DOWN as usize] = true;
+        hall_requests[1][HALL_UP as usize] = true;
+
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), peer_state);
+        coordinator.test_set_hall_requests(hall_requests);
+
+        // Act
+        coordinator.test_hall_request_assigner(false);
+
+        // Assert
+        // The top floor call goes to the peer, even though the local elevator is closer
+        let mut expected_hall_requests = vec![vec![false; 2]; n_floors as usize];
+        expected_hall_requests[1][HALL_UP as usize] = true;
+        assert_eq!(fsm_hall_requests_rx.try_recv(), Ok(expected_hall_requests), "Hall call outside zone assigned");
+    }
+
+    #[test]
+    fn test_coordinator_zoning_profile() {
+        // Arrange
+        let access_control = AccessControl::new(&AccessConfig {
+            restricted_floors: Vec::new(),
+            authorized_elevators: Vec::new(),
+            zones: Vec::new(),
+            profiles: vec![ZoningProfileConfig {
+                name: "lunch-rush express".to_string(),
+                zones: vec![
+                    ZoneConfig { elevator: "elevator".to_string(), floors: vec![0, 1], parking_floor: Some(0) },
+                    ZoneConfig { elevator: "peer".to_string(), floors: vec![0, 2, 3], parking_floor: None },
+                ],
+            }],
+        });
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(access_control, LockoutSchedule::default(), TrafficPolicy::default(), RepositioningPolicy::default());
+
+        let n_floors = *coordinator.test_get_n_floors();
+
+        // The local elevator waits at the floor of the call, and takes it without zones
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 2;
+        let mut peer_state = ElevatorState::new(n_floors);
+        peer_state.floor = 3;
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[2][HALL_DOWN as usize] = true;
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), peer_state);
+        coordinator.test_set_hall_requests(hall_requests.clone());
+        coordinator.test_hall_request_assigner(false);
+        let unzoned = fsm_hall_requests_rx.try_iter().last();
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetZoningProfile(Some("express".to_string()))));
+        let after_unknown = coordinator.test_get_data().zoning_profile.clone();
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetZoningProfile(Some("lunch-rush express".to_string()))));
+        let zoned = fsm_hall_requests_rx.try_iter().last();
+        let transmitted = net_data_send_rx.try_iter().last();
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetZoningProfile(None)));
+
+        // Assert
+        // The call now outside the zone of the local elevator is taken from it, and it parks where the profile says
+        assert_eq!(unzoned, Some(hall_requests), "The call was not assigned without zones");
+        assert_eq!(after_unknown, None, "An unknown profile was put in force");
+        assert_eq!(zoned, Some(vec![vec![false; 2]; n_floors as usize]), "A call outside the zone is still assigned");
+        assert_eq!(transmitted.and_then(|data| data.zoning_profile), Some("lunch-rush express".to_string()));
+        assert_eq!(fsm_parking_floor_rx.try_iter().collect::<Vec<Option<u8>>>(), vec![Some(0), None]);
+        assert_eq!(coordinator.test_get_data().zoning_profile, None);
+    }
+
+    #[test]
+    fn test_coordinator_lockout_schedule() {
+        // Arrange
+        // Floor 3 is closed in a window around the current time
+        let now = Local::now().time();
+        let lockout_schedule = LockoutSchedule::new(&[LockoutConfig {
+            floors: vec![3],
+            start: (now - ChronoDuration::hours(1)).format("%H:%M").to_string(),
+            end: (now + ChronoDuration::hours(1)).format("%H:%M").to_string(),
+        }]);
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(AccessControl::default(), lockout_schedule, TrafficPolicy::default(), RepositioningPolicy::default());
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((3, HALL_DOWN)));
+        coordinator.test_handle_event(Event::RequestReceived((3, CAB)));
+
+        // Assert
+        // Nothing is lit, assigned or transmitted
+        assert!(hw_button_light_rx.try_recv().is_err(), "Light set for locked floor");
+        assert!(fsm_hall_requests_rx.try_recv().is_err(), "Hall request from locked floor assigned");
+        assert!(fsm_cab_request_rx.try_recv().is_err(), "Cab request to locked floor forwarded");
+        assert!(net_data_send_rx.try_recv().is_err(), "Request to locked floor transmitted");
+        assert!(!coordinator.test_get_data().hall_requests[3][HALL_DOWN as usize]);
+        assert!(!coordinator.test_get_data().states["elevator"].cab_requests[3]);
+
+        // Other floors are unaffected
+        coordinator.test_handle_event(Event::RequestReceived((2, CAB)));
+        assert_eq!(fsm_cab_request_rx.try_recv(), Ok(2), "Mismatch for fsm_cab_request_rx");
+    }
+
+    #[test]
+    fn test_coordinator_lockout_window() {
+        // Arrange
+        // Floor 3 is closed from 08:00 to 09:00, and the clock starts a minute before
+        let lockout_schedule = LockoutSchedule::new(&[LockoutConfig {
+            floors: vec![3],
+            start: "08:00".to_string(),
+            end: "09:00".to_string(),
+        }]);
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(AccessControl::default(), lockout_schedule, TrafficPolicy::default(), RepositioningPolicy::default());
+
+        let clock = Arc::new(SimulatedClock::new(0.0).start_at(NaiveTime::from_hms_opt(7, 59, 0).unwrap()));
+        coordinator.test_set_clock(clock.clone());
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_handle_event(Event::RequestReceived((3, HALL_DOWN)));
+        coordinator.test_handle_event(Event::RequestReceived((3, CAB)));
+        let _ = hw_button_light_rx.try_iter().count();
+        let _ = fsm_hall_requests_rx.try_iter().count();
+
+        // Act
+        clock.advance(Duration::from_secs(60));
+        coordinator.test_update_lockout();
+        let lights_in_window = hw_button_light_rx.try_iter().collect::<Vec<(u8, u8, bool)>>();
+        let assigned_in_window = fsm_hall_requests_rx.try_iter().last();
+        let data_in_window = coordinator.test_get_data().clone();
+
+        clock.advance(Duration::from_secs(3600));
+        coordinator.test_update_lockout();
+        let lights_after_window = hw_button_light_rx.try_iter().collect::<Vec<(u8, u8, bool)>>();
+        let assigned_after_window = fsm_hall_requests_rx.try_iter().last();
+
+        // Assert
+        // The pending calls go dark when the window starts. The hall call is held unassigned, the cab call dropped.
+        assert!(lights_in_window.contains(&(3, HALL_DOWN, false)), "Hall light at locked floor left on");
+        assert!(lights_in_window.contains(&(3, CAB, false)), "Cab light to locked floor left on");
+        assert_eq!(assigned_in_window, Some(vec![vec![false; 2]; n_floors as usize]), "Hall call at locked floor assigned");
+        assert!(data_in_window.hall_requests[3][HALL_DOWN as usize], "Hall call at locked floor not held");
+        assert!(!data_in_window.states["elevator"].cab_requests[3], "Cab call to locked floor kept");
+
+        // The held hall call is lit and served once the window ends
+        let mut assigned = vec![vec![false; 2]; n_floors as usize];
+        assigned[3][HALL_DOWN as usize] = true;
+        assert_eq!(lights_after_window, vec![(3, HALL_DOWN, true)], "Held hall call not lit after the window");
+        assert_eq!(assigned_after_window, Some(assigned), "Held hall call not assigned after the window");
+    }
+
+    #[test]
+    fn test_coordinator_traffic_mode_command() {
+        // Arrange
+        let traffic_policy = TrafficPolicy::new(&TrafficConfig::default(), 4);
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(AccessControl::default(), LockoutSchedule::default(), traffic_policy, RepositioningPolicy::default());
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetTrafficMode(Some(TrafficMode::DownPeak))));
+
+        // Assert
+        // The FSM parks at the top floor and the override is transmitted to the fleet
+        assert_eq!(coordinator.test_get_traffic_mode(), TrafficMode::DownPeak);
+        assert_eq!(fsm_parking_floor_rx.try_recv(), Ok(Some(3)), "Mismatch for fsm_parking_floor_rx");
+        match net_data_send_rx.try_recv() {
+            Ok(data) => assert_eq!(data.traffic_mode, Some(TrafficMode::DownPeak), "Traffic mode not transmitted"),
+            Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
+        }
+
+        // Returning to the schedule stops the parking
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetTrafficMode(None)));
+        assert_eq!(coordinator.test_get_traffic_mode(), TrafficMode::Normal);
+        assert_eq!(fsm_parking_floor_rx.try_recv(), Ok(None), "Mismatch for fsm_parking_floor_rx");
+    }
+
+    #[test]
+    fn test_coordinator_traffic_mode_schedule() {
+        // Arrange
+        // Up-peak is scheduled from 08:00 to 09:00, and the clock starts half way through
+        let traffic_config = TrafficConfig {
+            schedule: vec![TrafficScheduleConfig {
+                mode: TrafficMode::UpPeak,
+                start: "08:00".to_string(),
+                end: "09:00".to_string(),
+            }],
+            ..TrafficConfig::default()
+        };
+        let traffic_policy = TrafficPolicy::new(&traffic_config, 4);
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(AccessControl::default(), LockoutSchedule::default(), traffic_policy, RepositioningPolicy::default());
+
+        let clock = Arc::new(SimulatedClock::new(0.0).start_at(NaiveTime::from_hms_opt(8, 30, 0).unwrap()));
+        coordinator.test_set_clock(clock.clone());
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetTrafficMode(None)));
+        let in_window = coordinator.test_get_traffic_mode();
+        clock.advance(Duration::from_secs(3600));
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetTrafficMode(None)));
+        let after_window = coordinator.test_get_traffic_mode();
+
+        // Assert
+        // The schedule follows the time of day of the clock, parking at the lobby during up-peak
+        assert_eq!(in_window, TrafficMode::UpPeak);
+        assert_eq!(after_window, TrafficMode::Normal);
+        assert_eq!(fsm_parking_floor_rx.try_iter().collect::<Vec<Option<u8>>>(), vec![Some(0), None]);
     }
 
+    #[test]
+    fn test_coordinator_traffic_mode_priority_assignment() {
+        // Arrange
+        let traffic_policy = TrafficPolicy::new(&TrafficConfig::default(), 4);
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(AccessControl::default(), LockoutSchedule::default(), traffic_policy, RepositioningPolicy::default());
+
+        let n_floors = *coordinator.test_get_n_floors();
+
+        // Optimized together, the lobby call goes to the peer at the top floor
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 2;
+        let mut peer_state = ElevatorState::new(n_floors);
+        peer_state.floor = 3;
+
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[0][HALL_UP as usize] = true;
+        hall_requests[2][HALL_UP as usize] = true;
+
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), peer_state);
+        coordinator.test_set_hall_requests(hall_requests);
+
+        // Act
+        coordinator.test_hall_request_assigner(false);
+        let normal_hall_requests = fsm_hall_requests_rx.try_recv();
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak))));
+        let up_peak_hall_requests = fsm_hall_requests_rx.try_iter().last();
+
+        // Assert
+        // In up-peak the lobby call goes to the closest elevator, which also keeps the call it is at
+        let mut expected_hall_requests = vec![vec![false; 2]; n_floors as usize];
+        expected_hall_requests[2][HALL_UP as usize] = true;
+        assert_eq!(normal_hall_requests, Ok(expected_hall_requests.clone()), "Mismatch in normal mode");
+
+        expected_hall_requests[0][HALL_UP as usize] = true;
+        assert_eq!(up_peak_hall_requests, Some(expected_hall_requests), "Lobby call not prioritized in up-peak");
+    }
+
+    #[test]
+    fn test_coordinator_halt() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let mut halted = ElevatorState::new(4);
+        halted.halted = true;
+        coordinator.test_set_state("peer".to_string(), ElevatorState::new(4));
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::Halt(true)));
+        let halt_command = fsm_halt_rx.try_recv();
+        coordinator.test_handle_event(Event::NewElevatorState(halted));
+        coordinator.test_handle_event(Event::RequestReceived((0, HALL_UP)));
+
+        // Assert
+        // The halt goes to the FSM, and the halted car is given no hall calls even where it stands
+        assert_eq!(halt_command, Ok(true));
+        let hall_requests = fsm_hall_requests_rx.try_iter().last().unwrap();
+        assert_eq!(hall_requests, vec![vec![false; 2]; 4]);
+    }
+
+    #[test]
+    fn test_coordinator_fire_service() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            fsm_operating_mode_rx,
+            fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        coordinator.test_handle_event(Event::RequestReceived((2, HALL_UP)));
+        coordinator.test_handle_event(Event::RequestReceived((3, CAB)));
+        let _ = hw_button_light_rx.try_iter().count();
+        let _ = fsm_cab_request_rx.try_iter().count();
+        let _ = net_data_send_rx.try_iter().count();
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetFireRecall(true)));
+
+        // Assert
+        // Phase 1 cancels all calls, recalls the car and is transmitted to the fleet
+        assert_eq!(fsm_operating_mode_rx.try_recv(), Ok(OperatingMode::FireRecall(0)), "Mismatch for fsm_operating_mode_rx");
+        let lights = hw_button_light_rx.try_iter().collect::<Vec<(u8, u8, bool)>>();
+        assert!(lights.contains(&(2, HALL_UP, false)), "Hall light not cleared");
+        assert!(lights.contains(&(3, CAB, false)), "Cab light not cleared");
+        assert!(!coordinator.test_get_data().hall_requests[2][HALL_UP as usize]);
+        assert!(!coordinator.test_get_data().states["elevator"].cab_requests[3]);
+        match net_data_send_rx.try_recv() {
+            Ok(data) => assert!(data.fire_recall, "Fire recall not transmitted"),
+            Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
+        }
+
+        // No calls are taken during the recall
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_DOWN)));
+        coordinator.test_handle_event(Event::RequestReceived((1, CAB)));
+        assert!(hw_button_light_rx.try_recv().is_err(), "Call accepted during fire recall");
+        assert!(fsm_cab_request_rx.try_recv().is_err(), "Cab call forwarded during fire recall");
+
+        // Phase 2 takes cab calls and door commands
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetFirePhase2(true)));
+        assert_eq!(fsm_operating_mode_rx.try_recv(), Ok(OperatingMode::FirePhase2), "Mismatch for fsm_operating_mode_rx");
+        coordinator.test_handle_event(Event::RequestReceived((1, CAB)));
+        assert_eq!(fsm_cab_request_rx.try_recv(), Ok(1), "Cab call not forwarded in phase 2");
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::Door(true)));
+        assert_eq!(fsm_door_command_rx.try_recv(), Ok(true), "Mismatch for fsm_door_command_rx");
+
+        // Resetting returns the car to normal service
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetFireRecall(false)));
+        assert_eq!(fsm_operating_mode_rx.try_recv(), Ok(OperatingMode::Normal), "Mismatch for fsm_operating_mode_rx");
+        assert_eq!(coordinator.test_get_operating_mode(), OperatingMode::Normal);
+    }
+
+    #[test]
+    fn test_coordinator_independent_service() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 2;
+        let mut peer_state = ElevatorState::new(n_floors);
+        peer_state.floor = 0;
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[2][HALL_UP as usize] = true;
+
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), peer_state);
+        coordinator.test_set_hall_requests(hall_requests);
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetIndependentService(None, true)));
+
+        // Assert
+        // The car leaves hall assignment, but stays in the fleet view
+        assert_eq!(fsm_operating_mode_rx.try_recv(), Ok(OperatingMode::Independent), "Mismatch for fsm_operating_mode_rx");
+        assert_eq!(
+            fsm_hall_requests_rx.try_iter().last(),
+            Some(vec![vec![false; 2]; n_floors as usize]),
+            "Hall call assigned to car in independent service"
+        );
+        match net_data_send_rx.try_recv() {
+            Ok(data) => {
+                assert!(data.independent_service.contains("elevator"), "Independent service not transmitted");
+                assert_eq!(data.states["elevator"].mode, OperatingMode::Independent);
+            }
+            Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
+        }
+
+        // Other cars are toggled through the replicated data, without changing the local mode
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetIndependentService(Some("peer".to_string()), true)));
+        assert!(coordinator.test_get_data().independent_service.contains("peer"));
+        assert!(fsm_operating_mode_rx.try_recv().is_err(), "Local mode changed by remote command");
+
+        // Returning to normal service makes the car available for hall calls again
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::SetIndependentService(None, false)));
+        assert_eq!(fsm_operating_mode_rx.try_recv(), Ok(OperatingMode::Normal), "Mismatch for fsm_operating_mode_rx");
+        let mut expected_hall_requests = vec![vec![false; 2]; n_floors as usize];
+        expected_hall_requests[2][HALL_UP as usize] = true;
+        assert_eq!(fsm_hall_requests_rx.try_iter().last(), Some(expected_hall_requests), "Hall call not assigned after independent service");
+    }
+
+    #[test]
+    fn test_coordinator_hall_call_cancellation() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        coordinator.test_set_clock(clock.clone());
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        let _ = hw_button_light_rx.try_iter().count();
+        let _ = fsm_hall_requests_rx.try_iter().count();
+        let _ = net_data_send_rx.try_iter().count();
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+
+        // Assert
+        // The second press clears the call, its light and the assignment, and the cancellation is transmitted
+        assert!(!coordinator.test_get_data().hall_requests[1][HALL_UP as usize], "Hall call not cancelled");
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((1, HALL_UP, false)), "Mismatch for hw_button_light_rx");
+        assert_eq!(fsm_hall_requests_rx.try_iter().last(), Some(vec![vec![false; 2]; n_floors as usize]));
+        match net_data_send_rx.try_recv() {
+            Ok(data) => assert_eq!(data.hall_cancellations[1][HALL_UP as usize], 1, "Cancellation not transmitted"),
+            Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
+        }
+
+        // A press after the window places the call again
+        coordinator.test_handle_event(Event::RequestReceived((2, HALL_DOWN)));
+        clock.advance(Duration::from_millis(600));
+        coordinator.test_handle_event(Event::RequestReceived((2, HALL_DOWN)));
+        assert!(coordinator.test_get_data().hall_requests[2][HALL_DOWN as usize], "Hall call cancelled after the window");
+    }
+
+    #[test]
+    fn test_coordinator_merge_keeps_cancellation() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+
+        // A stale packet from a peer that still has the call, and that does not know about the local elevator. The peer
+        // is from before the states of the calls were sent, so only the cancellations tell the stale call.
+        let mut stale_data = ElevatorData::new(n_floors);
+        stale_data.hall_orders.clear();
+        stale_data.hall_requests[1][HALL_UP as usize] = true;
+        stale_data.hall_requests[3][HALL_DOWN as usize] = true;
+        stale_data.states.insert("peer".to_string(), ElevatorState::new(n_floors));
+        let _ = hw_button_light_rx.try_iter().count();
+
+        // Act
+        coordinator.test_handle_event(Event::NewPackage(stale_data.clone()));
+
+        // Assert
+        // The cancelled call stays cleared, while other calls are still merged and lit
+        assert!(!coordinator.test_get_data().hall_requests[1][HALL_UP as usize], "Cancelled hall call resurrected");
+        assert!(coordinator.test_get_data().hall_requests[3][HALL_DOWN as usize], "Hall call not merged");
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((3, HALL_DOWN, true)), "Mismatch for hw_button_light_rx");
+
+        // A peer that has seen more cancellations clears the call
+        coordinator.test_handle_event(Event::RequestReceived((3, HALL_DOWN)));
+        stale_data.hall_cancellations[3][HALL_DOWN as usize] = 1;
+        stale_data.hall_requests[3][HALL_DOWN as usize] = false;
+        coordinator.test_handle_event(Event::NewPackage(stale_data));
+        assert!(!coordinator.test_get_data().hall_requests[3][HALL_DOWN as usize], "Cancellation not merged");
+    }
+
+    #[test]
+    fn test_coordinator_concurrent_packages() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_state("peer".to_string(), ElevatorState::new(n_floors));
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+
+        // A peer in another partition has made more updates, without seeing the local one
+        let mut package = ElevatorData::new(n_floors);
+        package.states = coordinator.test_get_data().states.clone();
+        set_hall_call(&mut package, 3, HALL_DOWN, true);
+        for _ in 0..5 {
+            package.version += 1;
+            package.clock.tick("peer");
+        }
+        let mut outdated = package.clone();
+        outdated.version += 10;
+        set_hall_call(&mut outdated, 0, HALL_UP, true);
+
+        // Act
+        coordinator.test_handle_event(Event::NewPackage(package.clone()));
+        let merged = coordinator.test_get_data().clone();
+        coordinator.test_handle_event(Event::NewPackage(outdated));
+        let after_outdated = coordinator.test_get_data().clone();
+        package.clock = merged.clock.clone();
+        package.clock.tick("peer");
+        package.hall_orders = merged.hall_orders.clone();
+        set_hall_call(&mut package, 1, HALL_UP, false);
+        coordinator.test_handle_event(Event::NewPackage(package));
+
+        // Assert
+        // The concurrent package is merged rather than replacing the local call for its higher version
+        assert!(merged.hall_requests[1][HALL_UP as usize], "Local hall call lost to a concurrent package");
+        assert!(merged.hall_requests[3][HALL_DOWN as usize], "Concurrent hall call not merged");
+        assert_eq!((merged.clock.get("elevator"), merged.clock.get("peer")), (1, 5));
+        // A package seen already is rejected whatever its version
+        assert!(!after_outdated.hall_requests[0][HALL_UP as usize], "Outdated package accepted");
+        // A package that has seen the local update replaces the local data
+        assert!(!coordinator.test_get_data().hall_requests[1][HALL_UP as usize], "Package ahead not accepted");
+        // The version is the total of the clock
+        assert_eq!(coordinator.test_get_data().version, coordinator.test_get_data().clock.total());
+    }
+
+    #[test]
+    fn test_coordinator_served_call_not_resurrected() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_state("peer".to_string(), ElevatorState::new(n_floors));
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+
+        // The peer took the call, and sends the data it had before the call was served along with an update of its own
+        let mut stale_package = coordinator.test_get_data().clone();
+        stale_package.clock.tick("peer");
+        stale_package.hall_requests[2][HALL_DOWN as usize] = true;
+        stale_package.hall_orders[2][HALL_DOWN as usize].advance(true, true);
+
+        // Act
+        coordinator.test_handle_event(Event::OrderComplete((1, HALL_UP)));
+        coordinator.test_handle_event(Event::NewPackage(stale_package));
+
+        // Assert
+        let data = coordinator.test_get_data();
+        assert!(!data.hall_requests[1][HALL_UP as usize], "Served hall call resurrected");
+        assert_eq!(data.hall_orders[1][HALL_UP as usize].state(), OrderState::Done);
+        assert!(data.hall_requests[2][HALL_DOWN as usize], "Hall call of the peer not merged");
+    }
+
+    #[test]
+    fn test_coordinator_idle_repositioning() {
+        // Arrange
+        let repositioning_policy = RepositioningPolicy::new(&RepositioningConfig { sectors: vec![3, 0] });
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator_with_policies(
+            AccessControl::default(),
+            LockoutSchedule::default(),
+            TrafficPolicy::default(),
+            repositioning_policy,
+        );
+
+        let n_floors = *coordinator.test_get_n_floors();
+
+        // The peer is closest to the top sector, leaving the lobby sector to the local elevator
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 1;
+        let mut peer_state = ElevatorState::new(n_floors);
+        peer_state.floor = 2;
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), peer_state);
+
+        // Act
+        coordinator.test_hall_request_assigner(false);
+        let idle_parking_floor = fsm_parking_floor_rx.try_recv();
+
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[1][HALL_UP as usize] = true;
+        coordinator.test_set_hall_requests(hall_requests);
+        let _ = fsm_hall_requests_rx.try_iter().count();
+        coordinator.test_hall_request_assigner(false);
+        let busy_parking_floor = fsm_parking_floor_rx.try_recv();
+
+        // Assert
+        assert_eq!(idle_parking_floor, Ok(Some(0)), "Idle elevator not sent to its sector");
+
+        // Once given a call, the local elevator gives up its sector and serves the call
+        let mut expected_hall_requests = vec![vec![false; 2]; n_floors as usize];
+        expected_hall_requests[1][HALL_UP as usize] = true;
+        assert_eq!(fsm_hall_requests_rx.try_recv(), Ok(expected_hall_requests), "Mismatch for fsm_hall_requests_rx");
+        assert_eq!(busy_parking_floor, Ok(None), "Busy elevator kept its sector");
+    }
+
+    #[test]
+    fn test_coordinator_consensus_hall_calls() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+
+        // The local elevator is elected leader with the vote of its only peer
+        let start = Instant::now();
+        let mut consensus = Consensus::new("elevator".to_string(), Duration::from_millis(100), Duration::from_millis(20), start);
+        consensus.set_peers(vec!["peer".to_string()]);
+        consensus.tick(start + Duration::from_millis(200));
+        consensus.handle_message("peer", ConsensusMessage::Vote { term: 1, granted: true }, start);
+        coordinator.test_set_consensus(consensus);
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        let proposed = coordinator.test_get_data().hall_requests[1][HALL_UP as usize];
+        let proposed_light = hw_button_light_rx.try_recv();
+        let append = net_consensus_send_rx.try_recv();
+
+        coordinator.test_handle_event(Event::ConsensusReceived((
+            "peer".to_string(),
+            ConsensusMessage::AppendResponse { term: 1, success: true, match_len: 1 },
+        )));
+
+        // Assert
+        // The call is neither taken nor lit before the peer holds it
+        assert!(!proposed, "Hall call taken before commit");
+        assert!(proposed_light.is_err(), "Hall call lit before commit");
+        match append {
+            Ok((peer, ConsensusMessage::AppendEntries { entries, .. })) => {
+                assert_eq!(peer, "peer");
+                assert_eq!(entries.len(), 1);
+            }
+            other => panic!("Expected an append to the peer, got {:?}", other),
+        }
+        assert!(coordinator.test_get_data().hall_requests[1][HALL_UP as usize], "Committed hall call not taken");
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((1, HALL_UP, true)), "Mismatch for hw_button_light_rx");
+
+        // Hall calls in packets from peers are left to the consensus
+        let mut package = coordinator.test_get_data().clone();
+        package.version += 1;
+        package.clock.tick("peer");
+        package.hall_requests = vec![vec![true; 2]; n_floors as usize];
+        coordinator.test_handle_event(Event::NewPackage(package));
+        assert!(!coordinator.test_get_data().hall_requests[3][HALL_DOWN as usize], "Hall call taken from a packet");
+    }
+
+    #[test]
+    fn test_coordinator_hall_call_quorum() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_hall_call_quorum(2);
+        coordinator.test_set_state("peer".to_string(), ElevatorState::new(n_floors));
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        let pending_light = hw_button_light_rx.try_recv();
+        let pending_assignment = fsm_hall_requests_rx.try_iter().last();
+        let transmitted = net_data_send_rx.try_iter().last();
+
+        // The peer acknowledges the call
+        let mut package = coordinator.test_get_data().clone();
+        package.version += 1;
+        package.clock.tick("peer");
+        package.hall_acknowledgements[1][HALL_UP as usize].insert("peer".to_string());
+        coordinator.test_handle_event(Event::NewPackage(package));
+
+        // Assert
+        // The call is pending, and transmitted with the local acknowledgement, until the peer has acknowledged it
+        assert!(pending_light.is_err(), "Pending hall call lit");
+        assert_eq!(pending_assignment, Some(vec![vec![false; 2]; n_floors as usize]), "Pending hall call assigned");
+        match transmitted {
+            Some(data) => {
+                assert!(data.hall_requests[1][HALL_UP as usize], "Pending hall call not transmitted");
+                assert!(data.hall_acknowledgements[1][HALL_UP as usize].contains("elevator"), "Acknowledgement not transmitted");
+            }
+            None => panic!("Nothing transmitted on net_data_send_rx"),
+        }
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((1, HALL_UP, true)), "Confirmed hall call not lit");
+
+        // Once the peer is lost, the local elevator is a quorum by itself
+        coordinator.test_handle_event(Event::RequestReceived((2, HALL_DOWN)));
+        assert!(hw_button_light_rx.try_recv().is_err(), "Pending hall call lit");
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate {
+            peers: vec!["elevator".to_string()],
+            new: None,
+            lost: vec!["peer".to_string()],
+        }));
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((2, HALL_DOWN, true)), "Hall call not confirmed without the peer");
+    }
+
+    #[test]
+    fn test_coordinator_hall_call_all_peers() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_hall_call_confirmation(HallCallConfirmation::AllPeers);
+        coordinator.test_set_state("a".to_string(), ElevatorState::new(n_floors));
+        coordinator.test_set_state("b".to_string(), ElevatorState::new(n_floors));
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        let pending_light = hw_button_light_rx.try_recv();
+
+        // One of the two peers acknowledges the call
+        let mut package = coordinator.test_get_data().clone();
+        package.version += 1;
+        package.clock.tick("a");
+        package.hall_acknowledgements[1][HALL_UP as usize].insert("a".to_string());
+        coordinator.test_handle_event(Event::NewPackage(package));
+        let partial_light = hw_button_light_rx.try_recv();
+
+        // The other peer acknowledges it too
+        let mut package = coordinator.test_get_data().clone();
+        package.version += 1;
+        package.clock.tick("b");
+        package.hall_acknowledgements[1][HALL_UP as usize].insert("b".to_string());
+        coordinator.test_handle_event(Event::NewPackage(package));
+
+        // Assert
+        // A majority is not enough, the call is only lit once every peer holds it
+        assert!(pending_light.is_err(), "Unacknowledged hall call lit");
+        assert!(partial_light.is_err(), "Hall call lit before every peer acknowledged it");
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((1, HALL_UP, true)), "Acknowledged hall call not lit");
+        assert_eq!(coordinator.test_get_data().hall_orders[1][HALL_UP as usize].state(), OrderState::Confirmed);
+
+        // A lost peer is no longer waited for
+        coordinator.test_handle_event(Event::RequestReceived((2, HALL_DOWN)));
+        assert!(hw_button_light_rx.try_recv().is_err(), "Unacknowledged hall call lit");
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate {
+            peers: vec!["elevator".to_string(), "a".to_string()],
+            new: None,
+            lost: vec!["b".to_string()],
+        }));
+        assert!(hw_button_light_rx.try_recv().is_err(), "Hall call lit before the remaining peer acknowledged it");
+    }
+
+    #[test]
+    fn test_coordinator_hall_call_deadline() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        coordinator.test_set_clock(clock.clone());
+        coordinator.test_set_hall_call_deadline(Duration::from_secs(30));
+        coordinator.test_set_assignment_model(AssignmentModel::Push);
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_state("elevator".to_string(), ElevatorState::new(n_floors));
+        let mut peer = ElevatorState::new(n_floors);
+        peer.floor = 3;
+        coordinator.test_set_state("peer".to_string(), peer);
+
+        // Act
+        // The call goes to the peer waiting at its floor, which never serves it
+        coordinator.test_handle_event(Event::RequestReceived((3, HALL_DOWN)));
+        let assigned_to_peer = fsm_hall_requests_rx.try_iter().last();
+        clock.advance(Duration::from_secs(29));
+        coordinator.test_check_hall_call_deadlines();
+        let within_deadline = fsm_hall_requests_rx.try_iter().last();
+        clock.advance(Duration::from_secs(1));
+        coordinator.test_check_hall_call_deadlines();
+        let past_deadline = fsm_hall_requests_rx.try_iter().last();
+
+        // Assert
+        let mut reassigned = vec![vec![false; 2]; n_floors as usize];
+        reassigned[3][HALL_DOWN as usize] = true;
+        assert_eq!(assigned_to_peer, Some(vec![vec![false; 2]; n_floors as usize]), "Hall call not assigned to the nearest elevator");
+        assert_eq!(within_deadline, None, "Hall call reassigned within its deadline");
+        assert_eq!(past_deadline, Some(reassigned.clone()), "Overdue hall call not reassigned");
+
+        // The peer is left out for a deadline. By then the local elevator has let the call pass its deadline too, and
+        // the call goes back to the peer.
+        clock.advance(Duration::from_secs(29));
+        coordinator.test_check_hall_call_deadlines();
+        assert_eq!(fsm_hall_requests_rx.try_iter().last(), None, "Peer taken back before its exclusion ended");
+        clock.advance(Duration::from_secs(1));
+        coordinator.test_check_hall_call_deadlines();
+        assert_eq!(fsm_hall_requests_rx.try_iter().last(), Some(vec![vec![false; 2]; n_floors as usize]), "Peer not taken back");
+    }
+
+    #[test]
+    fn test_coordinator_anti_entropy_merge() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        let _ = hw_button_light_rx.try_iter().count();
+        let _ = net_data_send_rx.try_iter().count();
+
+        // A peer that has diverged at the same version, with another call
+        let mut diverged_data = coordinator.test_get_data().clone();
+        diverged_data.hall_requests = vec![vec![false; 2]; n_floors as usize];
+        diverged_data.hall_orders = ElevatorData::new(n_floors).hall_orders;
+        set_hall_call(&mut diverged_data, 3, HALL_DOWN, true);
+
+        // Act
+        coordinator.test_handle_event(Event::NewPackage(diverged_data.clone()));
+        let rejected = coordinator.test_get_data().hall_requests[3][HALL_DOWN as usize];
+        coordinator.test_handle_event(Event::SyncPackage(diverged_data.clone()));
+
+        // Assert
+        // The regular package is rejected, while anti-entropy merges both calls and transmits the result ahead of both
+        assert!(!rejected, "Package at the same version accepted");
+        assert!(coordinator.test_get_data().hall_requests[1][HALL_UP as usize], "Local hall call lost");
+        assert!(coordinator.test_get_data().hall_requests[3][HALL_DOWN as usize], "Hall call not merged");
+        assert_eq!(hw_button_light_rx.try_recv(), Ok((3, HALL_DOWN, true)), "Mismatch for hw_button_light_rx");
+        match net_data_send_rx.try_recv() {
+            Ok(data) => assert!(data.version > diverged_data.version, "Merged data not ahead of the peer"),
+            Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_coordinator_split_brain_reconciliation() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_state("peer".to_string(), ElevatorState::new(n_floors));
+
+        // Both sides hold a call when the peer is lost, and the local side takes a new call
+        coordinator.test_handle_event(Event::RequestReceived((2, HALL_DOWN)));
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate {
+            peers: vec!["elevator".to_string()],
+            new: None,
+            lost: vec!["peer".to_string()],
+        }));
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+
+        // Apart, the peer serves the shared call, takes a new call and gets far ahead in version
+        let request_time = coordinator.test_get_data().hall_request_times[2][HALL_DOWN as usize];
+        let mut peer_data = ElevatorData::new(n_floors);
+        peer_data.version = 100;
+        for _ in 0..100 {
+            peer_data.clock.tick("peer");
+        }
+        set_hall_call(&mut peer_data, 3, HALL_DOWN, true);
+        set_hall_call(&mut peer_data, 2, HALL_DOWN, true);
+        set_hall_call(&mut peer_data, 2, HALL_DOWN, false);
+        peer_data.hall_request_times[3][HALL_DOWN as usize] = request_time;
+        peer_data.hall_completion_times[2][HALL_DOWN as usize] = request_time + 10;
+        peer_data.states.insert("peer".to_string(), ElevatorState::new(n_floors));
+        peer_data.states.insert("elevator".to_string(), ElevatorState::new(n_floors));
+
+        // Act
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate {
+            peers: vec!["elevator".to_string(), "peer".to_string()],
+            new: Some("peer".to_string()),
+            lost: vec![],
+        }));
+        let _ = net_data_send_rx.try_iter().count();
+        coordinator.test_handle_event(Event::NewPackage(peer_data));
+
+        // Assert
+        // The package is not accepted over the local calls, and the call served apart is not served again
+        let data = coordinator.test_get_data();
+        assert!(data.hall_requests[1][HALL_UP as usize], "Local hall call lost");
+        assert!(data.hall_requests[3][HALL_DOWN as usize], "Hall call of the peer not unioned");
+        assert!(!data.hall_requests[2][HALL_DOWN as usize], "Hall call completed apart brought back");
+        match net_data_send_rx.try_recv() {
+            Ok(data) => assert!(data.version > 100, "Reconciled data not ahead of the peer"),
+            Err(e) => panic!("Error receiving net_data_send_rx: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_coordinator_quarantine_package() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let local_data = coordinator.test_get_data().clone();
+        let mut valid_data = local_data.clone();
+        valid_data.version += 1;
+        valid_data.clock.tick("peer");
+        set_hall_call(&mut valid_data, 2, HALL_UP, true);
+
+        let mut missing_floor = valid_data.clone();
+        missing_floor.hall_requests.pop();
+        let mut floor_out_of_range = valid_data.clone();
+        floor_out_of_range.states.insert("peer".to_string(), ElevatorState::new(n_floors));
+        floor_out_of_range.states.get_mut("peer").unwrap().floor = n_floors;
+        let mut short_cab_requests = valid_data.clone();
+        short_cab_requests.states.insert("peer".to_string(), ElevatorState::new(n_floors - 1));
+        let mut version_jump = valid_data.clone();
+        version_jump.version = u64::MAX;
+        let mut too_many_elevators = valid_data.clone();
+        for i in 0..100 {
+            too_many_elevators.states.insert(format!("peer {}", i), ElevatorState::new(n_floors));
+        }
+        let malformed_packages = [missing_floor, floor_out_of_range, short_cab_requests, version_jump, too_many_elevators];
+
+        // Act
+        for package in malformed_packages.iter() {
+            coordinator.test_handle_event(Event::NewPackage(package.clone()));
+            coordinator.test_handle_event(Event::SyncPackage(package.clone()));
+        }
+        let data_after_malformed = coordinator.test_get_data().clone();
+        let sent_after_malformed = net_data_send_rx.try_iter().count();
+        coordinator.test_handle_event(Event::NewPackage(valid_data.clone()));
+
+        // Assert
+        // Malformed packages leave the local data untouched, while a sound package is still accepted
+        assert_eq!(data_after_malformed, local_data, "Malformed package merged");
+        assert_eq!(sent_after_malformed, 0, "Malformed package transmitted");
+        assert_eq!(coordinator.test_get_data().version, valid_data.version, "Sound package rejected");
+        assert!(coordinator.test_get_data().hall_requests[2][HALL_UP as usize], "Sound package not applied");
+    }
+
+    #[test]
+    fn test_coordinator_hall_call_injection() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::HallCall(2, HALL_DOWN, true)));
+        let placed_light = hw_button_light_rx.try_recv();
+        let placed_assignment = fsm_hall_requests_rx.try_iter().last();
+        let placed_sent = net_data_send_rx.try_iter().last();
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::HallCall(2, HALL_DOWN, true)));
+        let placed_twice = coordinator.test_get_data().hall_requests[2][HALL_DOWN as usize];
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::HallCall(n_floors, HALL_UP, true)));
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::HallCall(2, HALL_DOWN, false)));
+
+        // Assert
+        // An injected call is lit, assigned and transmitted like a pressed one, and placing it again keeps it
+        assert_eq!(placed_light, Ok((2, HALL_DOWN, true)), "Mismatch for hw_button_light_rx");
+        assert!(placed_assignment.is_some_and(|requests| requests[2][HALL_DOWN as usize]), "Injected call not assigned");
+        assert!(placed_sent.is_some_and(|data| data.hall_requests[2][HALL_DOWN as usize]), "Injected call not transmitted");
+        assert!(placed_twice, "Injected call cancelled by placing it again");
+
+        // Calls that do not exist are refused, and clearing cancels the call
+        assert_eq!(coordinator.test_get_data().hall_requests.len(), n_floors as usize);
+        assert!(!coordinator.test_get_data().hall_requests[2][HALL_DOWN as usize], "Injected call not cleared");
+        assert_eq!(hw_button_light_rx.try_iter().last(), Some((2, HALL_DOWN, false)), "Mismatch for hw_button_light_rx");
+    }
+
+    #[test]
+    fn test_coordinator_export_state() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let path = std::env::temp_dir().join(format!("coordinator_export_{}.json", std::process::id()));
+        let local_id = coordinator.test_get_local_id().clone();
+        coordinator.test_handle_event(Event::RequestReceived((1, CAB)));
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate { peers: vec![local_id.clone()], new: None, lost: vec![] }));
+
+        // Act
+        coordinator.test_handle_event(Event::CommandReceived(ControlCommand::ExportState(Some(path.to_string_lossy().to_string()))));
+        let document = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // Assert
+        // The export is one JSON document with the data, the state of the local car, the peers and the network counters
+        let export = serde_json::from_str::<StateExport>(&document).unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(&export.exported_at).is_ok(), "Export time is not RFC 3339");
+        assert_eq!(export.fsm.as_ref(), coordinator.test_get_data().states.get(&local_id));
+        assert!(export.fsm.is_some_and(|state| state.cab_requests[1]), "Cab call missing from the exported car");
+        assert_eq!(export.snapshot.elevator_data, *coordinator.test_get_data());
+        assert_eq!(export.snapshot.peers, vec![local_id]);
+        assert_eq!(export.snapshot.network, coordinator.test_get_network_counters().statistics());
+        let document = serde_json::from_str::<serde_json::Value>(&document).unwrap();
+        assert!(document["network"]["messagesSent"].is_u64() && document["elevatorData"]["hallRequests"].is_array());
+    }
+
+    #[test]
+    fn test_coordinator_write_ahead_log() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let directory = std::env::temp_dir().join(format!("coordinator_wal_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default()));
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_wal(Some(WriteAheadLog::new(storage.clone(), RecoveredOrders::new(n_floors))));
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((1, HALL_UP)));
+        coordinator.test_handle_event(Event::RequestReceived((2, CAB)));
+        let logged = storage.load_wal().unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let _ = hw_button_light_rx.try_iter().count();
+        let _ = fsm_cab_request_rx.try_iter().count();
+        // The directory is gone, so nothing more can be logged
+        coordinator.test_handle_event(Event::RequestReceived((3, CAB)));
+
+        // Assert
+        // The calls are logged, and the hall call assigned to the local elevator
+        assert_eq!(
+            logged.into_iter().map(|(_, mutation)| mutation).collect::<Vec<OrderMutation>>(),
+            vec![
+                OrderMutation::Add { floor: 1, call: HALL_UP },
+                OrderMutation::Assign { hall_requests: vec![vec![false; 2], vec![true, false], vec![false; 2], vec![false; 2]] },
+                OrderMutation::Add { floor: 2, call: CAB },
+            ]
+        );
+        // A call that cannot be logged is refused
+        assert!(!coordinator.test_get_data().states.get(coordinator.test_get_local_id()).unwrap().cab_requests[3]);
+        assert!(hw_button_light_rx.try_recv().is_err(), "Refused call lit");
+        assert!(fsm_cab_request_rx.try_recv().is_err(), "Refused call sent to the FSM");
+    }
+
+    #[test]
+    fn test_coordinator_push_assignment() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let timeout = Duration::from_millis(500);
+        coordinator.test_set_assignment_model(AssignmentModel::Push);
+        coordinator.test_set_peer_list(PeerUpdate { peers: vec!["a".to_string(), "elevator".to_string()], new: None, lost: vec![] });
+        coordinator.test_set_state("a".to_string(), ElevatorState::new(n_floors));
+
+        // Act
+        // "a" is the assigning node, and takes the call at the 2nd floor, while pushing the one at the 3rd floor
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[1][HALL_UP as usize] = true;
+        hall_requests[2][HALL_DOWN as usize] = true;
+        coordinator.test_set_hall_requests(hall_requests.clone());
+        let mut pushed = coordinator.test_get_data().clone();
+        pushed.version = 5;
+        let mut local = vec![vec![false; 2]; n_floors as usize];
+        local[2][HALL_DOWN as usize] = true;
+        let mut remote = vec![vec![false; 2]; n_floors as usize];
+        remote[1][HALL_UP as usize] = true;
+        pushed.hall_assignment = Some(HallAssignment {
+            assigner: "a".to_string(),
+            version: 5,
+            elevators: [("a".to_string(), remote), ("elevator".to_string(), local.clone())].into_iter().collect(),
+        });
+        coordinator.test_handle_event(Event::NewPackage(pushed));
+        let followed = fsm_hall_requests_rx.try_iter().last();
+
+        // Without the assigning node, the local elevator assigns the calls itself, and pushes its assignment
+        let _ = net_data_send_rx.try_iter().count();
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate { peers: vec!["elevator".to_string()], new: None, lost: vec!["a".to_string()] }));
+        let assigned = fsm_hall_requests_rx.recv_timeout(timeout).unwrap();
+        let transmitted = net_data_send_rx.recv_timeout(timeout).unwrap();
+
+        // Assert
+        assert_eq!(followed, Some(local), "The local elevator did not serve the calls pushed to it");
+        assert_eq!(assigned, hall_requests, "The calls were not assigned to the only elevator left");
+        let assignment = transmitted.hall_assignment.expect("No assignment pushed");
+        assert_eq!(assignment.assigner, "elevator");
+        assert_eq!(assignment.elevators.get("elevator"), Some(&hall_requests));
+    }
+
+    #[test]
+    fn test_coordinator_reassignments() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let mut nearby = ElevatorState::new(n_floors);
+        nearby.floor = 3;
+        coordinator.test_set_state("a".to_string(), nearby.clone());
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[3][HALL_DOWN as usize] = true;
+        coordinator.test_set_hall_requests(hall_requests);
+
+        // Act
+        // The call goes to "a", at its floor, and moves to the local elevator once "a" fails
+        coordinator.test_hall_request_assigner(false);
+        coordinator.test_hall_request_assigner(false);
+        let before = coordinator.test_get_snapshot().reassignments;
+        nearby.behaviour = Behaviour::Error;
+        coordinator.test_set_state("a".to_string(), nearby);
+        coordinator.test_hall_request_assigner(false);
+        let after = coordinator.test_get_snapshot().reassignments;
+
+        // Assert
+        assert_eq!(before, 0, "A call assigned to the same elevator again was counted");
+        assert_eq!(after, 1, "The call moved to the local elevator was not counted");
+    }
+
+    #[test]
+    fn test_coordinator_hall_owners() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            _fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let mut nearby = ElevatorState::new(n_floors);
+        nearby.floor = 3;
+        coordinator.test_set_state("a".to_string(), nearby.clone());
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[3][HALL_DOWN as usize] = true;
+        coordinator.test_set_hall_requests(hall_requests.clone());
+
+        // Act
+        // "a" takes the call at its floor, and says so in its package, which also claims a stale call for the
+        // local elevator
+        coordinator.test_hall_request_assigner(false);
+        let mut package = coordinator.test_get_data().clone();
+        package.version += 1;
+        package.clock.tick("a");
+        package.hall_owners.insert("a".to_string(), hall_requests.clone());
+        package.hall_owners.insert("elevator".to_string(), hall_requests.clone());
+        coordinator.test_handle_event(Event::NewPackage(package));
+        let served_by_a = coordinator.test_get_data().hall_call_owners(3, HALL_DOWN);
+        // The call moves to the local elevator once "a" fails, and "a" claims it until it is lost
+        nearby.behaviour = Behaviour::Error;
+        coordinator.test_set_state("a".to_string(), nearby);
+        coordinator.test_hall_request_assigner(false);
+        let reassigning = coordinator.test_get_data().hall_call_owners(3, HALL_DOWN);
+        coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate { peers: vec!["elevator".to_string()], new: None, lost: vec!["a".to_string()] }));
+        let served_locally = coordinator.test_get_data().hall_call_owners(3, HALL_DOWN);
+
+        // Assert
+        assert_eq!(served_by_a, vec!["a".to_string()], "The owners of the call were not merged");
+        assert_eq!(reassigning, vec!["a".to_string(), "elevator".to_string()]);
+        assert_eq!(served_locally, vec!["elevator".to_string()], "A lost elevator still owns the call");
+        assert_eq!(coordinator.test_get_data().hall_call_owners(1, HALL_UP), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_coordinator_conservative_serving() {
+        // Arrange
+        let (
+            mut coordinator,
+            _hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            _fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            _net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        coordinator.test_set_adaptation_policy(AdaptationPolicy::new(&AdaptationConfig {
+            enabled: true,
+            interval: 1000,
+            loss_threshold: 0.2,
+            max_coalescing: 0,
+            max_peer_timeout: 0,
+        }));
+        // "a" waits at the top floor, and is assigned the calls there
+        let mut state = ElevatorState::new(n_floors);
+        state.floor = 3;
+        coordinator.test_set_state("a".to_string(), state);
+        let quality = |loss: f64| NetworkQuality { loss, round_trip: None };
+
+        // Act
+        coordinator.test_handle_event(Event::RequestReceived((3, HALL_DOWN)));
+        let assigned = fsm_hall_requests_rx.try_iter().last();
+        coordinator.test_handle_event(Event::QualityReceived(quality(0.3)));
+        let lossy = fsm_hall_requests_rx.try_iter().last();
+        // Still beyond half of the threshold
+        coordinator.test_handle_event(Event::QualityReceived(quality(0.15)));
+        let kept = fsm_hall_requests_rx.try_iter().last();
+        coordinator.test_handle_event(Event::QualityReceived(quality(0.0)));
+        let recovered = fsm_hall_requests_rx.try_iter().last();
+
+        // Assert
+        let mut observed = vec![vec![false; 2]; n_floors as usize];
+        observed[3][HALL_DOWN as usize] = true;
+        assert_eq!(assigned, Some(vec![vec![false; 2]; n_floors as usize]), "The call was not assigned to the closer elevator");
+        assert_eq!(lossy, Some(observed), "The call pressed at the local panel was not served locally");
+        assert_eq!(kept, None, "The mode flapped");
+        assert_eq!(recovered, Some(vec![vec![false; 2]; n_floors as usize]), "The local elevator kept serving the call");
+    }
+
+    #[test]
+    fn test_coordinator_dry_run() {
+        // Arrange
+        let (
+            mut coordinator,
+            hw_button_light_rx,
+            _hw_request_tx,
+            fsm_hall_requests_rx,
+            fsm_cab_request_rx,
+            _fsm_parking_floor_rx,
+            _fsm_operating_mode_rx,
+            _fsm_door_command_rx,
+            _fsm_alarm_clear_rx,
+            _fsm_halt_rx,
+            _fsm_inspect_rx,
+            _fsm_state_tx,
+            _fsm_order_complete_tx,
+            net_data_send_rx,
+            _net_data_recv_tx,
+            _net_sync_recv_tx,
+            _net_peer_update_tx,
+            _net_consensus_send_rx,
+            _net_consensus_recv_tx,
+            _coordinator_command_tx,
+            _coordinator_query_tx,
+            _coordinator_terminate_tx
+        ) = setup_coordinator();
+
+        let n_floors = *coordinator.test_get_n_floors();
+        let mut local_state = ElevatorState::new(n_floors);
+        local_state.floor = 2;
+        coordinator.test_set_state("elevator".to_string(), local_state);
+        coordinator.test_set_state("peer".to_string(), ElevatorState::new(n_floors));
+        coordinator.test_set_dry_run(true);
+        let mut hall_requests = vec![vec![false; 2]; n_floors as usize];
+        hall_requests[2][HALL_DOWN as usize] = true;
+        coordinator.test_set_hall_requests(hall_requests.clone());
+
+        // Act
+        coordinator.test_hall_request_assigner(true);
+        coordinator.test_handle_event(Event::RequestReceived((1, CAB)));
+        let lights = hw_button_light_rx.try_iter().count();
+        let fsm_hall_requests = fsm_hall_requests_rx.try_iter().count();
+        let fsm_cab_requests = fsm_cab_request_rx.try_iter().count();
+        let transmitted = net_data_send_rx.try_iter().last().unwrap();
+        let snapshot = coordinator.test_get_snapshot();
+
+        // The peer next to the call runs a dry run, and the local elevator acts again
+        coordinator.test_set_dry_run(false);
+        let mut shadow_state = ElevatorState::new(n_floors);
+        shadow_state.floor = 2;
+        shadow_state.dry_run = true;
+        coordinator.test_set_state("elevator".to_string(), ElevatorState::new(n_floors));
+        coordinator.test_set_state("peer".to_string(), shadow_state);
+        coordinator.test_hall_request_assigner(false);
+        let assigned = fsm_hall_requests_rx.try_iter().last();
+
+        // Assert
+        // The decisions are taken, but neither the lights nor the FSM are told, and no hall call is claimed
+        assert_eq!((lights, fsm_hall_requests, fsm_cab_requests), (0, 0, 0));
+        assert!(snapshot.confirmed_hall_requests[2][HALL_DOWN as usize]);
+        assert!(snapshot.elevator_data.states["elevator"].cab_requests[1]);
+        assert!(transmitted.states["elevator"].dry_run);
+        assert!(transmitted.hall_owners.is_empty());
+        // A peer in a dry run is assigned no hall calls
+        assert_eq!(assigned, Some(hall_requests));
+    }
+
+    #[test]
+    fn test_coordinator_builder_missing_channel() {
+        // Arrange
+        let (hw_button_light_tx, _hw_button_light_rx) = unbounded::<(u8, u8, bool)>();
+        let builder = CoordinatorBuilder::new(ElevatorData::new(4), "elevator".to_string(), 4).hw_button_light_tx(hw_button_light_tx);
+
+        // Act
+        let result = builder.build();
+
+        // Assert
+        // The first channel not attached is named
+        assert_eq!(result.err(), Some("The coordinator has no hw_request_rx attached".to_string()));
+    }
 }
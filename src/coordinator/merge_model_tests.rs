@@ -0,0 +1,394 @@
+/*
+ * Model-based tests for the coordinator merge protocol
+ *
+ * A reference model of the replication protocol (the state of every hall call,
+ * the version, the vector clock and the set of known elevators on each of N abstract nodes)
+ * is driven side by side with real coordinators. Random interleavings of button presses,
+ * completed orders, packet deliveries, packet losses and peer losses/reconnects are applied
+ * to both, and the model and the coordinators are asserted to be equivalent after every step.
+ *
+ * The model encodes the intended accept/merge/reject rules:
+ * - Merge:  the incoming package is missing an elevator the receiver knows about, or its clock is concurrent
+ *           with the receiver's. Every hall call takes the state further along, foreign states are inserted
+ *           and the clocks joined.
+ * - Accept: the incoming clock is ahead, or the incoming package has no clock and a version ahead of the total of
+ *           the receiver's clock. Everything is replaced, and the clocks joined.
+ * - Reject: otherwise.
+ * Every broadcast counts one update of the sender on its entry of the clock. The version is the total of the clock.
+ *
+ * Reconciliation after a split brain goes by the wall clock, so it is turned off here and tested on its own.
+ *
+ * Tests:
+ *  - test_merge_protocol_random_interleavings
+ *  - test_merge_protocol_lossless_interleavings
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod merge_model_tests {
+    use crate::coordinator::coordinator::Event;
+    use crate::{Coordinator, CoordinatorBuilder};
+    use crate::ElevatorData;
+    use crate::ElevatorState;
+    use crate::shared::{Causality, ConsensusMessage, ControlCommand, FsmView, HallOrder, OperatingMode, Snapshot, Terminate, VectorClock};
+    use crossbeam_channel::{never, unbounded};
+    use crossbeam_channel::Sender;
+    use crossbeam_channel::Receiver;
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+    use network_rust::udpnet::peers::PeerUpdate;
+    use std::cmp::Ordering;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::time::Duration;
+
+    const N_NODES: usize = 3;
+    const N_FLOORS: u8 = 4;
+
+    /***************************************/
+    /*          Reference model            */
+    /***************************************/
+    #[derive(Clone, Debug)]
+    struct ModelNode {
+        id: String,
+        version: u64,
+        clock: VectorClock,
+        orders: BTreeMap<(u8, u8), HallOrder>,
+        known: BTreeSet<String>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Packet {
+        to: usize,
+        data: ElevatorData,
+        model: ModelNode,
+    }
+
+    impl ModelNode {
+        fn hall(&self) -> BTreeSet<(u8, u8)> {
+            self.orders.iter().filter(|(_, state)| state.is_requested()).map(|(order, _)| *order).collect()
+        }
+
+        fn broadcast(&mut self) {
+            self.clock.tick(&self.id);
+            self.version = self.clock.total();
+        }
+
+        fn press(&mut self, order: (u8, u8)) {
+            // With a quorum of one, a call is confirmed as it is pressed
+            self.orders.entry(order).or_default().advance(true, true);
+            self.broadcast();
+        }
+
+        fn complete(&mut self, order: (u8, u8)) {
+            self.orders.entry(order).or_default().advance(false, false);
+            self.broadcast();
+        }
+
+        fn deliver(&mut self, packet: &ModelNode) {
+            let missing_elevators = self.known.iter().any(|id| !packet.known.contains(id));
+            let causality = match packet.clock.is_empty() {
+                true if packet.version > self.clock.total() => Causality::After,
+                true => Causality::Before,
+                false => packet.clock.compare(&self.clock),
+            };
+
+            if missing_elevators || causality == Causality::Concurrent {
+                for (order, state) in packet.orders.iter() {
+                    let local = self.orders.entry(*order).or_default();
+                    if state.precedence(local) == Ordering::Greater {
+                        *local = *state;
+                    }
+                }
+                self.known.extend(packet.known.iter().cloned());
+                self.clock.join(&packet.clock);
+                self.version = self.clock.total();
+            } else if causality == Causality::After {
+                let (id, mut clock) = (self.id.clone(), self.clock.clone());
+                clock.join(&packet.clock);
+                *self = ModelNode { id, version: clock.total(), clock, ..packet.clone() };
+            }
+        }
+    }
+
+    /***************************************/
+    /*          System under test          */
+    /***************************************/
+    struct Node {
+        id: String,
+        coordinator: Coordinator,
+        net_data_send_rx: Receiver<ElevatorData>,
+
+        // Kept alive so the coordinator never sends on a disconnected channel
+        _hw_button_light_rx: Receiver<(u8, u8, bool)>,
+        _fsm_hall_requests_rx: Receiver<Vec<Vec<bool>>>,
+        _fsm_cab_request_rx: Receiver<u8>,
+        _fsm_parking_floor_rx: Receiver<Option<u8>>,
+        _fsm_operating_mode_rx: Receiver<OperatingMode>,
+        _fsm_door_command_rx: Receiver<bool>,
+        _fsm_alarm_clear_rx: Receiver<()>,
+        _fsm_halt_rx: Receiver<bool>,
+        _fsm_inspect_rx: Receiver<Sender<FsmView>>,
+    }
+
+    fn node_id(index: usize) -> String {
+        format!("elevator{}", index)
+    }
+
+    fn setup_node(index: usize) -> Node {
+        let (hw_button_light_tx, hw_button_light_rx) = unbounded::<(u8, u8, bool)>();
+        let (_hw_request_tx, hw_request_rx) = unbounded::<(u8, u8)>();
+        let (fsm_hall_requests_tx, fsm_hall_requests_rx) = unbounded::<Vec<Vec<bool>>>();
+        let (fsm_cab_request_tx, fsm_cab_request_rx) = unbounded::<u8>();
+        let (fsm_parking_floor_tx, fsm_parking_floor_rx) = unbounded::<Option<u8>>();
+        let (fsm_operating_mode_tx, fsm_operating_mode_rx) = unbounded::<OperatingMode>();
+        let (fsm_door_command_tx, fsm_door_command_rx) = unbounded::<bool>();
+        let (fsm_alarm_clear_tx, fsm_alarm_clear_rx) = unbounded::<()>();
+        let (fsm_halt_tx, fsm_halt_rx) = unbounded::<bool>();
+        let (fsm_inspect_tx, fsm_inspect_rx) = unbounded::<Sender<FsmView>>();
+        let (_fsm_state_tx, fsm_state_rx) = unbounded::<ElevatorState>();
+        let (_fsm_order_complete_tx, fsm_order_complete_rx) = unbounded::<(u8, u8)>();
+        let (net_data_send_tx, net_data_send_rx) = unbounded::<ElevatorData>();
+        let (_net_data_recv_tx, net_data_recv_rx) = unbounded::<ElevatorData>();
+        let (_net_sync_recv_tx, net_sync_recv_rx) = unbounded::<ElevatorData>();
+        let (_net_peer_update_tx, net_peer_update_rx) = unbounded::<PeerUpdate>();
+        let (net_consensus_send_tx, _net_consensus_send_rx) = unbounded::<(String, ConsensusMessage)>();
+        let (_net_consensus_recv_tx, net_consensus_recv_rx) = unbounded::<(String, ConsensusMessage)>();
+        let (_coordinator_command_tx, coordinator_command_rx) = unbounded::<ControlCommand>();
+        let (_coordinator_query_tx, coordinator_query_rx) = unbounded::<Sender<Snapshot>>();
+        let (_coordinator_terminate_tx, coordinator_terminate_rx) = unbounded::<Terminate>();
+
+        // Every node starts out knowing the whole fleet
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        for other in 0..N_NODES {
+            elevator_data.states.insert(node_id(other), ElevatorState::new(N_FLOORS));
+        }
+
+        let mut coordinator = CoordinatorBuilder::new(elevator_data, node_id(index), N_FLOORS)
+            .hw_button_light_tx(hw_button_light_tx)
+            .hw_request_rx(hw_request_rx)
+            .fsm_hall_requests_tx(fsm_hall_requests_tx)
+            .fsm_cab_request_tx(fsm_cab_request_tx)
+            .fsm_parking_floor_tx(fsm_parking_floor_tx)
+            .fsm_operating_mode_tx(fsm_operating_mode_tx)
+            .fsm_door_command_tx(fsm_door_command_tx)
+            .fsm_alarm_clear_tx(fsm_alarm_clear_tx)
+            .fsm_halt_tx(fsm_halt_tx)
+            .fsm_inspect_tx(fsm_inspect_tx)
+            .fsm_state_rx(fsm_state_rx)
+            .fsm_order_complete_rx(fsm_order_complete_rx)
+            .net_data_send_tx(net_data_send_tx)
+            .net_data_recv_rx(net_data_recv_rx)
+            .net_sync_recv_rx(net_sync_recv_rx)
+            .net_peer_update_rx(net_peer_update_rx)
+            .net_consensus_send_tx(net_consensus_send_tx)
+            .net_consensus_recv_rx(net_consensus_recv_rx)
+            .net_peer_version_rx(never())
+            .net_quality_rx(never())
+            .coordinator_command_rx(coordinator_command_rx)
+            .coordinator_query_rx(coordinator_query_rx)
+            .coordinator_terminate_rx(coordinator_terminate_rx)
+            .build()
+            .unwrap();
+        coordinator.test_set_reconciliation_window(Duration::ZERO);
+
+        Node {
+            id: node_id(index),
+            coordinator,
+            net_data_send_rx,
+            _hw_button_light_rx: hw_button_light_rx,
+            _fsm_hall_requests_rx: fsm_hall_requests_rx,
+            _fsm_cab_request_rx: fsm_cab_request_rx,
+            _fsm_parking_floor_rx: fsm_parking_floor_rx,
+            _fsm_operating_mode_rx: fsm_operating_mode_rx,
+            _fsm_door_command_rx: fsm_door_command_rx,
+            _fsm_alarm_clear_rx: fsm_alarm_clear_rx,
+            _fsm_halt_rx: fsm_halt_rx,
+            _fsm_inspect_rx: fsm_inspect_rx,
+        }
+    }
+
+    fn setup_model() -> Vec<ModelNode> {
+        let known = (0..N_NODES).map(node_id).collect::<BTreeSet<String>>();
+        (0..N_NODES)
+            .map(|index| ModelNode {
+                id: node_id(index),
+                version: 0,
+                clock: VectorClock::default(),
+                orders: BTreeMap::new(),
+                known: known.clone(),
+            })
+            .collect()
+    }
+
+    /***************************************/
+    /*            Test driver              */
+    /***************************************/
+    // Small deterministic xorshift generator, so failing seeds can be replayed
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    fn random_order(rng: &mut Rng) -> (u8, u8) {
+        let floor = rng.below(N_FLOORS as usize) as u8;
+        let call = if rng.below(2) == 0 { HALL_UP } else { HALL_DOWN };
+        (floor, call)
+    }
+
+    // Collect everything the coordinator broadcast and address it like the network module does
+    fn collect_broadcasts(node: &Node, model: &ModelNode, in_flight: &mut Vec<Packet>, step: usize) {
+        while let Ok(data) = node.net_data_send_rx.try_recv() {
+            assert_eq!(data.version, model.version, "step {}: broadcast version from {}", step, node.id);
+            assert_eq!(data.clock, model.clock, "step {}: broadcast clock from {}", step, node.id);
+
+            for id in data.states.keys() {
+                if id == &node.id {
+                    continue;
+                }
+                let to = (0..N_NODES).find(|index| &node_id(*index) == id).unwrap();
+                in_flight.push(Packet { to, data: data.clone(), model: model.clone() });
+            }
+        }
+    }
+
+    fn assert_equivalent(nodes: &[Node], model: &[ModelNode], step: usize, action: &str) {
+        for (node, expected) in nodes.iter().zip(model.iter()) {
+            let data = node.coordinator.test_get_data();
+
+            let mut hall = BTreeSet::new();
+            for floor in 0..N_FLOORS {
+                for call in [HALL_UP, HALL_DOWN] {
+                    if data.hall_requests[floor as usize][call as usize] {
+                        hall.insert((floor, call));
+                    }
+                }
+            }
+            let known = data.states.keys().cloned().collect::<BTreeSet<String>>();
+
+            assert_eq!(data.version, expected.version, "step {} ({}): version on {}", step, action, node.id);
+            assert_eq!(data.clock, expected.clock, "step {} ({}): clock on {}", step, action, node.id);
+            assert_eq!(hall, expected.hall(), "step {} ({}): hall requests on {}", step, action, node.id);
+            assert_eq!(known, expected.known, "step {} ({}): known elevators on {}", step, action, node.id);
+        }
+    }
+
+    fn run_interleaving(seed: u64, steps: usize, allow_loss: bool) {
+        let mut rng = Rng(seed);
+        let mut nodes = (0..N_NODES).map(setup_node).collect::<Vec<Node>>();
+        let mut model = setup_model();
+        let mut in_flight: Vec<Packet> = Vec::new();
+
+        for step in 0..steps {
+            let n = rng.below(N_NODES);
+            let action;
+
+            match rng.below(12) {
+                // Button press
+                0..=2 => {
+                    let order = random_order(&mut rng);
+                    action = format!("press {:?} on {}", order, n);
+                    nodes[n].coordinator.test_handle_event(Event::RequestReceived(order));
+                    model[n].press(order);
+                }
+
+                // Order completed by the local FSM
+                3..=4 => {
+                    let order = random_order(&mut rng);
+                    action = format!("complete {:?} on {}", order, n);
+                    nodes[n].coordinator.test_handle_event(Event::OrderComplete(order));
+                    model[n].complete(order);
+                }
+
+                // Packet loss
+                5 if allow_loss && !in_flight.is_empty() => {
+                    let index = rng.below(in_flight.len());
+                    action = format!("drop packet to {}", in_flight[index].to);
+                    in_flight.remove(index);
+                }
+
+                // Peer lost
+                6 if allow_loss => {
+                    let m = rng.below(N_NODES);
+                    action = format!("{} loses {}", n, m);
+                    nodes[n].coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate {
+                        peers: vec![],
+                        new: None,
+                        lost: vec![node_id(m)],
+                    }));
+                    if m != n {
+                        model[n].known.remove(&node_id(m));
+                    }
+                }
+
+                // Peer (re)discovered
+                7 => {
+                    let m = rng.below(N_NODES);
+                    action = format!("{} finds {}", n, m);
+                    nodes[n].coordinator.test_handle_event(Event::NewPeerUpdate(PeerUpdate {
+                        peers: vec![],
+                        new: Some(node_id(m)),
+                        lost: vec![],
+                    }));
+                    model[n].known.insert(node_id(m));
+                    model[n].broadcast();
+                }
+
+                // Packet delivery
+                _ => {
+                    if in_flight.is_empty() {
+                        continue;
+                    }
+                    let packet = in_flight.remove(rng.below(in_flight.len()));
+                    action = format!("deliver version {} to {}", packet.data.version, packet.to);
+                    nodes[packet.to].coordinator.test_handle_event(Event::NewPackage(packet.data));
+                    model[packet.to].deliver(&packet.model);
+                }
+            }
+
+            for (node, model_node) in nodes.iter().zip(model.iter()) {
+                collect_broadcasts(node, model_node, &mut in_flight, step);
+            }
+            assert_equivalent(&nodes, &model, step, &action);
+        }
+
+        // Drain the network and check that the model still agrees after quiescence
+        while !in_flight.is_empty() {
+            let packet = in_flight.remove(0);
+            nodes[packet.to].coordinator.test_handle_event(Event::NewPackage(packet.data));
+            model[packet.to].deliver(&packet.model);
+
+            for (node, model_node) in nodes.iter().zip(model.iter()) {
+                collect_broadcasts(node, model_node, &mut in_flight, steps);
+            }
+            assert_equivalent(&nodes, &model, steps, "drain");
+        }
+    }
+
+    #[test]
+    fn test_merge_protocol_random_interleavings() {
+        // Purpose: Verify that the coordinators follow the reference model under loss and peer churn
+        for seed in [0x9E37_79B9_7F4A_7C15, 0xDEAD_BEEF_CAFE_F00D, 0x0123_4567_89AB_CDEF, 42, 4145] {
+            run_interleaving(seed, 150, true);
+        }
+    }
+
+    #[test]
+    fn test_merge_protocol_lossless_interleavings() {
+        // Purpose: Verify that the coordinators follow the reference model on a perfect network
+        for seed in [7, 1337, 0xFEED_FACE] {
+            run_interleaving(seed, 150, false);
+        }
+    }
+}
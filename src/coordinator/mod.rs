@@ -1,4 +1,15 @@
+pub mod assigner;
+pub mod assigner_tests;
 pub mod coordinator;
 pub mod coordinator_tests;
+pub mod merge_model_tests;
+pub mod plugin;
+pub mod plugin_tests;
+pub mod startup;
+pub mod startup_tests;
+pub mod strategy;
+pub mod strategy_tests;
+pub mod worker;
+pub mod worker_tests;
 
-pub use coordinator::Coordinator;
+pub use coordinator::{Coordinator, CoordinatorBuilder};
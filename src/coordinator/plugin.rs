@@ -0,0 +1,165 @@
+/**
+ * Hall request assigners loaded as WebAssembly modules.
+ *
+ * Lets assignment policies be tried out without recompiling the elevator. The module gets the same JSON input
+ * as the reference `hall_request_assigner` executable, and returns the same JSON output: the hall requests
+ * assigned to each elevator, as `{"id": [[up, down], ...]}` with one entry per floor. A module exports:
+ *
+ * - `memory`:                  Its linear memory.
+ * - `alloc(len: i32) -> i32`:  The address of `len` bytes the input is written to.
+ * - `assign(ptr: i32, len: i32) -> i64`:  Assigns the input at `ptr`, returning the output's address and length.
+ *
+ * The address of the output is in the upper 32 bits of the result of `assign`, and its length in the lower.
+ *
+ * The module runs sandboxed. It is given no imports, so a module importing anything fails to load. Every
+ * assignment runs on a fresh instance, with bounded fuel and memory. The module is loaded again whenever the
+ * file changes, so a policy can be swapped while the elevator runs.
+ *
+ * # Fields
+ * - `path`:                    Path of the module.
+ * - `engine`:                  Compiles and runs the module, metering fuel.
+ * - `module`:                  The compiled module.
+ * - `modified`:                When the file of the loaded module was last modified.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use log::{info, error};
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+use wasmi::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::coordinator::coordinator::hall_request_assigner_input;
+use crate::shared::ElevatorData;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// Instructions, roughly, an assignment may run for
+const FUEL: u64 = 100_000_000;
+const MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct AssignerPlugin {
+    path: String,
+    engine: Engine,
+    module: Module,
+    modified: Option<SystemTime>,
+}
+
+impl AssignerPlugin {
+    pub fn load(path: &str) -> Result<AssignerPlugin, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let modified = modified(path);
+        let module = compile(&engine, path)?;
+        info!("Loaded assigner plugin {}", path);
+
+        Ok(AssignerPlugin {
+            path: path.to_string(),
+            engine,
+            module,
+            modified,
+        })
+    }
+
+    // Assigns the hall requests to the elevators, returning the hall requests assigned to each elevator
+    pub fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+        self.reload_if_modified();
+
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(FUEL).map_err(|e| e.to_string())?;
+
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|e| format!("Failed to instantiate the plugin: {}", e))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("The plugin does not export its memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .map_err(|e| format!("The plugin does not export alloc: {}", e))?;
+        let assign = instance
+            .get_typed_func::<(i32, i32), i64>(&store, "assign")
+            .map_err(|e| format!("The plugin does not export assign: {}", e))?;
+
+        let input = hall_request_assigner_input(elevator_data);
+        let input_len = i32::try_from(input.len()).map_err(|e| e.to_string())?;
+        let input_ptr = alloc.call(&mut store, input_len).map_err(|e| format!("alloc failed: {}", e))?;
+        memory
+            .write(&mut store, input_ptr as u32 as usize, input.as_bytes())
+            .map_err(|e| format!("Failed to write the input: {}", e))?;
+
+        let output_location = assign
+            .call(&mut store, (input_ptr, input_len))
+            .map_err(|e| format!("assign failed: {}", e))? as u64;
+        let mut output = vec![0; (output_location & 0xffff_ffff) as usize];
+        memory
+            .read(&store, (output_location >> 32) as usize, &mut output)
+            .map_err(|e| format!("Failed to read the output: {}", e))?;
+
+        let assignment = serde_json::from_slice::<HashMap<String, Vec<Vec<bool>>>>(&output)
+            .map_err(|e| format!("Invalid output: {}", e))?;
+        check_assignment(&assignment, elevator_data)?;
+        Ok(assignment)
+    }
+
+    // Keeps the loaded module if the new one fails to load
+    fn reload_if_modified(&mut self) {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        match compile(&self.engine, &self.path) {
+            Ok(module) => {
+                info!("Reloaded assigner plugin {}", self.path);
+                self.module = module;
+            }
+            Err(e) => error!("Failed to reload assigner plugin, keeping the loaded one: {}", e),
+        }
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn compile(engine: &Engine, path: &str) -> Result<Module, String> {
+    let wasm = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let module = Module::new(engine, &wasm).map_err(|e| format!("Failed to compile {}: {}", path, e))?;
+    if let Some(import) = module.imports().next() {
+        return Err(format!("{} imports {}::{}, but plugins get no imports", path, import.module(), import.name()));
+    }
+    Ok(module)
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// The coordinator indexes the assignment by floor and call, so its shape is checked first
+fn check_assignment(assignment: &HashMap<String, Vec<Vec<bool>>>, elevator_data: &ElevatorData) -> Result<(), String> {
+    for (id, hall_requests) in assignment.iter() {
+        if !elevator_data.states.contains_key(id) {
+            return Err(format!("Hall requests assigned to unknown elevator {}", id));
+        }
+        if hall_requests.len() != elevator_data.hall_requests.len()
+            || hall_requests.iter().any(|requests| requests.len() < 2)
+        {
+            return Err(format!("Hall requests of elevator {} do not cover every floor", id));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+/*
+ * Unit tests for the WebAssembly assigner plugins
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * The plugins are written in the WebAssembly text format, and return a fixed assignment.
+ *
+ * Tests:
+ *  - test_plugin_assign
+ *  - test_plugin_hot_swap
+ *  - test_plugin_sandbox
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod plugin_tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+    use crate::coordinator::plugin::AssignerPlugin;
+    use crate::shared::{ElevatorData, ElevatorState};
+
+    const N_FLOORS: u8 = 2;
+
+    // A plugin returning `output` for any input
+    fn fixed_plugin(output: &str) -> Vec<u8> {
+        wat::parse_str(format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 1024) "{}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 2048))
+                (func (export "assign") (param i32 i32) (result i64)
+                    (i64.or (i64.shl (i64.const 1024) (i64.const 32)) (i64.const {}))))"#,
+            output.replace('"', "\\\""),
+            output.len()
+        ))
+        .unwrap()
+    }
+
+    fn write_plugin(name: &str, wasm: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_{}.wasm", name, std::process::id()));
+        fs::write(&path, wasm).unwrap();
+        path
+    }
+
+    fn elevator_data() -> ElevatorData {
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.hall_requests[0][0] = true;
+        elevator_data.hall_requests[1][1] = true;
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(N_FLOORS));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(N_FLOORS));
+        elevator_data
+    }
+
+    #[test]
+    fn test_plugin_assign() {
+        // Arrange
+        let path = write_plugin("plugin_assign", &fixed_plugin(r#"{"a":[[true,false],[false,false]],"b":[[false,false],[false,true]]}"#));
+        let mut plugin = AssignerPlugin::load(path.to_str().unwrap()).unwrap();
+
+        // Act
+        let assignment = plugin.assign(&elevator_data()).unwrap();
+
+        // Assert
+        assert_eq!(assignment["a"], vec![vec![true, false], vec![false, false]]);
+        assert_eq!(assignment["b"], vec![vec![false, false], vec![false, true]]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_hot_swap() {
+        // Arrange
+        let path = write_plugin("plugin_hot_swap", &fixed_plugin(r#"{"a":[[true,false],[false,true]]}"#));
+        let mut plugin = AssignerPlugin::load(path.to_str().unwrap()).unwrap();
+        let first = plugin.assign(&elevator_data()).unwrap();
+
+        // Act
+        // The file is given a later modification time, as a rewrite may land within its resolution
+        fs::write(&path, fixed_plugin(r#"{"b":[[true,false],[false,true]]}"#)).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1)).unwrap();
+        let swapped = plugin.assign(&elevator_data()).unwrap();
+
+        fs::write(&path, b"not a module").unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(2)).unwrap();
+        let kept = plugin.assign(&elevator_data()).unwrap();
+
+        // Assert
+        assert!(first.contains_key("a"));
+        assert!(swapped.contains_key("b"));
+        // A module that fails to load leaves the last one in place
+        assert_eq!(kept, swapped);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_sandbox() {
+        // Arrange
+        let importing = write_plugin("plugin_importing", &wat::parse_str(
+            r#"(module (import "env" "open" (func (param i32) (result i32))) (memory (export "memory") 1))"#
+        ).unwrap());
+        let looping = write_plugin("plugin_looping", &wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "assign") (param i32 i32) (result i64) (loop (br 0)) (i64.const 0)))"#
+        ).unwrap());
+        let unknown_elevator = write_plugin("plugin_unknown_elevator", &fixed_plugin(r#"{"c":[[true,false],[false,true]]}"#));
+        let missing_floor = write_plugin("plugin_missing_floor", &fixed_plugin(r#"{"a":[[true,false]]}"#));
+
+        // Act / Assert
+        assert!(AssignerPlugin::load(importing.to_str().unwrap()).is_err());
+        // Running out of fuel ends the loop
+        assert!(AssignerPlugin::load(looping.to_str().unwrap()).unwrap().assign(&elevator_data()).is_err());
+        assert!(AssignerPlugin::load(unknown_elevator.to_str().unwrap()).unwrap().assign(&elevator_data()).is_err());
+        assert!(AssignerPlugin::load(missing_floor.to_str().unwrap()).unwrap().assign(&elevator_data()).is_err());
+
+        for path in [importing, looping, unknown_elevator, missing_floor] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
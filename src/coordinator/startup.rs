@@ -0,0 +1,138 @@
+/**
+ * Reconciliation of the persisted state with the state of the peers at start-up.
+ *
+ * Before the FSM and the coordinator start, the persisted orders are announced to the peers, and the node waits
+ * up to `startup_timeout` milliseconds for the first packages they answer with. The hall calls of the packages
+ * are unioned with the persisted ones, unless one side has seen more cancellations of the call, as in a merge.
+ * The states of the other elevators are taken from the packages.
+ *
+ * A persisted cab call is dropped if a peer last saw the local elevator without it, having served it after the
+ * local elevator last recorded serving it. The persisted state is then older than what the peers saw, as after a
+ * backup was restored, and the call was served before the node went down. Without a recorded snapshot nothing
+ * tells the age of the persisted calls, and they are all kept.
+ *
+ * The peers back the cab calls of the local elevator up, as they hold its state from its packages. A cab call a peer
+ * last saw the local elevator hold, and that the local elevator has not persisted, is restored, unless the local
+ * elevator recorded serving it after the peer last saw it. Calls are lost this way when the cab orders were not
+ * saved before the node went down, or when its storage was lost. Without a recorded snapshot the calls held by the
+ * peers are all restored, as a call served twice is better than one lost.
+ *
+ * # Fields
+ * - `dropped`:                 The floors of the persisted cab calls that were served before the restart.
+ * - `restored`:                The floors of the cab calls restored from the peers.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::{error, info};
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::coordinator::coordinator::{cancellation_count, check_package, order_precedence};
+use crate::shared::{join_hall_orders, ElevatorData};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// The cab calls of the local elevator changed by the reconciliation
+#[derive(Debug, Default, PartialEq)]
+pub struct CabReconciliation {
+    pub dropped: Vec<u8>,
+    pub restored: Vec<u8>,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Waits up to `timeout` for the first package from the peers, and takes those that came with it
+pub fn await_packages(net_data_recv_rx: &cbc::Receiver<ElevatorData>, timeout: Duration) -> Vec<ElevatorData> {
+    let Ok(first) = net_data_recv_rx.recv_timeout(timeout) else { return Vec::new() };
+    let mut packages = vec![first];
+    packages.extend(net_data_recv_rx.try_iter());
+    packages
+}
+
+// Reconciles the persisted data, holding the persisted cab calls of the local elevator, with the packages of the
+// peers. Returns the cab calls dropped and restored.
+pub fn reconcile_startup(
+    elevator_data: &mut ElevatorData,
+    local_id: &str,
+    n_floors: u8,
+    recorded_completion_times: Option<&[u64]>,
+    packages: &[ElevatorData],
+) -> CabReconciliation {
+    let mut cab_calls = CabReconciliation::default();
+    for package in packages {
+        if let Err(reason) = check_package(package, n_floors, elevator_data.version) {
+            error!("Quarantined package with version {} at start-up: {}", package.version, reason);
+            continue;
+        }
+
+        elevator_data.hall_cancellations.resize(n_floors as usize, vec![0; 2]);
+        for floor in 0..n_floors {
+            for call in [HALL_UP, HALL_DOWN] {
+                let local_request = elevator_data.hall_requests[floor as usize][call as usize];
+                let remote_request = package.hall_requests[floor as usize][call as usize];
+                let local_cancellations = cancellation_count(elevator_data, floor, call);
+                let remote_cancellations = cancellation_count(package, floor, call);
+
+                // The state further along is taken, then the side that has seen more cancellations
+                let precedence = order_precedence(elevator_data, package, floor, call).unwrap_or(std::cmp::Ordering::Equal);
+                elevator_data.hall_requests[floor as usize][call as usize] = match precedence.then(local_cancellations.cmp(&remote_cancellations)) {
+                    std::cmp::Ordering::Less => remote_request,
+                    std::cmp::Ordering::Greater => local_request,
+                    std::cmp::Ordering::Equal => local_request || remote_request,
+                };
+                elevator_data.hall_cancellations[floor as usize][call as usize] = local_cancellations.max(remote_cancellations);
+            }
+        }
+
+        for (id, state) in &package.states {
+            if id != local_id {
+                elevator_data.states.insert(id.clone(), state.clone());
+            }
+        }
+        elevator_data.clock.join(&package.clock);
+        elevator_data.version = elevator_data.clock.total();
+        join_hall_orders(&mut elevator_data.hall_orders, &package.hall_orders, n_floors);
+
+        // The cab calls the peer saw served after the last recorded service, and those it holds that were not
+        // served since it saw them
+        let Some(observed) = package.states.get(local_id) else { continue };
+        let Some(local_state) = elevator_data.states.get_mut(local_id) else { continue };
+        for floor in 0..n_floors {
+            let recorded_time = recorded_completion_times.and_then(|recorded| recorded.get(floor as usize).copied());
+            let observed_time = observed.cab_completion_times.get(floor as usize).copied().unwrap_or(0);
+            let observed_request = observed.cab_requests.get(floor as usize).copied().unwrap_or(false);
+            let local_request = local_state.cab_requests[floor as usize];
+            if local_request && !observed_request && recorded_time.is_some_and(|recorded_time| observed_time > recorded_time) {
+                info!("Cab call to floor {} was served before the restart, and is dropped", floor);
+                local_state.cab_requests[floor as usize] = false;
+                toggle(&mut cab_calls.restored, &mut cab_calls.dropped, floor);
+            } else if !local_request && observed_request && observed_time >= recorded_time.unwrap_or(0) {
+                info!("Cab call to floor {} was held by a peer, and is restored", floor);
+                local_state.cab_requests[floor as usize] = true;
+                toggle(&mut cab_calls.dropped, &mut cab_calls.restored, floor);
+            }
+        }
+    }
+    cab_calls
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Takes back a change of the cab call made for an earlier package, or records the change
+fn toggle(undone: &mut Vec<u8>, done: &mut Vec<u8>, floor: u8) {
+    match undone.iter().position(|undone| *undone == floor) {
+        Some(index) => {
+            undone.remove(index);
+        }
+        None => done.push(floor),
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * Unit tests for the reconciliation at start-up
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_startup_hall_calls
+ *  - test_startup_served_cab_calls
+ *  - test_startup_restored_cab_calls
+ *  - test_startup_await_packages
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod startup_tests {
+    use crate::coordinator::startup::{await_packages, reconcile_startup, CabReconciliation};
+    use crate::shared::{ElevatorData, ElevatorState};
+    use crossbeam_channel::unbounded;
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+    use std::time::Duration;
+
+    const N_FLOORS: u8 = 4;
+
+    // Data holding the local elevator "a" with cab calls at the given floors, and the peer "b"
+    fn data(cab_floors: &[usize]) -> ElevatorData {
+        let mut data = ElevatorData::new(N_FLOORS);
+        let mut state = ElevatorState::new(N_FLOORS);
+        for floor in cab_floors {
+            state.cab_requests[*floor] = true;
+        }
+        data.states.insert("a".to_string(), state);
+        data.states.insert("b".to_string(), ElevatorState::new(N_FLOORS));
+        data
+    }
+
+    #[test]
+    fn test_startup_hall_calls() {
+        // Arrange
+        let mut persisted = data(&[]);
+        persisted.hall_requests[0][HALL_UP as usize] = true;
+        persisted.hall_requests[2][HALL_DOWN as usize] = true;
+        let mut package = data(&[]);
+        for _ in 0..7 {
+            package.clock.tick("b");
+        }
+        package.hall_requests[1][HALL_UP as usize] = true;
+        // The peer cancelled the call at floor 2 after the node went down
+        package.hall_cancellations = vec![vec![0; 2], vec![0; 2], vec![0, 1], vec![0; 2]];
+        package.states.get_mut("b").unwrap().floor = 3;
+        let mut malformed = data(&[]);
+        malformed.hall_requests = vec![vec![true; 2]];
+
+        // Act
+        let cab_calls = reconcile_startup(&mut persisted, "a", N_FLOORS, None, &[package, malformed]);
+
+        // Assert
+        let expected = vec![vec![true, false], vec![true, false], vec![false; 2], vec![false; 2]];
+        assert_eq!(persisted.hall_requests, expected);
+        assert_eq!((persisted.version, persisted.states["b"].floor), (7, 3));
+        assert_eq!(cab_calls, CabReconciliation::default());
+    }
+
+    #[test]
+    fn test_startup_served_cab_calls() {
+        // Arrange
+        let persisted = data(&[0, 1, 2]);
+        let recorded = [0, 100, 100, 100];
+        let mut package = data(&[2]);
+        // Floor 0 was served after the recording, floor 1 before it, and floor 2 was placed again after being served
+        package.states.get_mut("a").unwrap().cab_completion_times = vec![200, 50, 200, 0];
+
+        // Act
+        let mut with_recording = persisted.clone();
+        let dropped = reconcile_startup(&mut with_recording, "a", N_FLOORS, Some(&recorded), &[package.clone()]).dropped;
+        let mut without_recording = persisted.clone();
+        let kept = reconcile_startup(&mut without_recording, "a", N_FLOORS, None, &[package]).dropped;
+
+        // Assert
+        assert_eq!(dropped, vec![0]);
+        assert_eq!(with_recording.states["a"].cab_requests, vec![false, true, true, false]);
+        // Without a recording nothing tells the age of the persisted calls
+        assert!(kept.is_empty());
+        assert_eq!(without_recording.states["a"].cab_requests, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn test_startup_restored_cab_calls() {
+        // Arrange
+        let persisted = data(&[0]);
+        let recorded = [0, 100, 100, 100];
+        let mut package = data(&[0, 1, 2, 3]);
+        // Floor 1 was served after the peer last saw it, and floors 2 and 3 were not
+        package.states.get_mut("a").unwrap().cab_completion_times = vec![0, 50, 100, 150];
+        let mut stale = data(&[]);
+        // An older peer saw floor 3 served before it was placed again
+        stale.states.get_mut("a").unwrap().cab_completion_times = vec![0, 0, 0, 200];
+
+        // Act
+        let mut with_recording = persisted.clone();
+        let cab_calls = reconcile_startup(&mut with_recording, "a", N_FLOORS, Some(&recorded), &[package.clone(), stale]);
+        let mut without_recording = persisted.clone();
+        let restored = reconcile_startup(&mut without_recording, "a", N_FLOORS, None, &[package]).restored;
+
+        // Assert
+        // A call restored for one peer, and seen served by another after it, is dropped again
+        assert_eq!(cab_calls, CabReconciliation { dropped: Vec::new(), restored: vec![2] });
+        assert_eq!(with_recording.states["a"].cab_requests, vec![true, false, true, false]);
+        // Without a recording every call held by the peers is restored
+        assert_eq!(restored, vec![1, 2, 3]);
+        assert_eq!(without_recording.states["a"].cab_requests, vec![true; 4]);
+    }
+
+    #[test]
+    fn test_startup_await_packages() {
+        // Arrange
+        let (net_data_recv_tx, net_data_recv_rx) = unbounded::<ElevatorData>();
+
+        // Act
+        let alone = await_packages(&net_data_recv_rx, Duration::from_millis(10));
+        net_data_recv_tx.send(data(&[])).unwrap();
+        net_data_recv_tx.send(data(&[1])).unwrap();
+        let packages = await_packages(&net_data_recv_rx, Duration::from_millis(10));
+
+        // Assert
+        // Alone the node starts on its persisted state, and otherwise takes all the packages that have come
+        assert!(alone.is_empty());
+        assert_eq!(packages, vec![data(&[]), data(&[1])]);
+    }
+}
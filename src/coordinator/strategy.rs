@@ -0,0 +1,180 @@
+/**
+ * Strategies the coordinator assigns hall calls by.
+ *
+ * Every strategy takes the hall calls and the states of the elevators that may serve them, as the input of the
+ * reference `hall_request_assigner` executable, and returns the hall calls assigned to each elevator. The coordinator
+ * groups, caps and applies the assignment the same whichever strategy is used, so a dispatch algorithm is tried by
+ * implementing `AssignmentStrategy` and selecting it with `hall_request_assigner` in `[elevator]`:
+ *
+ * - `native`:                  The cost function of the reference executable, reimplemented in-process.
+ * - `process`:                 The reference executable. Assigns in-process when it fails.
+ * - `wasm`:                    The WebAssembly module of `assigner_plugin`. Assigns in-process when it fails.
+ * - `nearest-car`:             Every call to the closest elevator, an elevator moving away being a lap further.
+ * - `round-robin`:             The calls in turn to the elevators, by ID, from the lowest floor.
+ *
+ * Without a consensus every coordinator assigns on its own, so a strategy must give the same assignment for the
+ * same input on every elevator. The round robin takes turns over the calls of one assignment rather than keeping a
+ * counter between them, as the counters of the elevators would drift apart. Ties go to the lowest ID.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::error;
+use std::collections::HashMap;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::coordinator::assigner::assign_hall_requests;
+use crate::coordinator::coordinator::execute_hall_request_assigner;
+use crate::coordinator::plugin::AssignerPlugin;
+use crate::shared::{Behaviour, Direction, ElevatorData, HallRequestAssigner};
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub trait AssignmentStrategy: Send {
+    // The name of the strategy, as selected in the configuration
+    fn name(&self) -> &'static str;
+
+    // Assigns the hall requests to the elevators, returning the hall requests assigned to each elevator
+    fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String>;
+}
+
+pub struct CostFunction;
+
+pub struct ReferenceProcess;
+
+pub struct NearestCar;
+
+pub struct RoundRobin;
+
+impl AssignmentStrategy for CostFunction {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+        assign_hall_requests(elevator_data)
+    }
+}
+
+impl AssignmentStrategy for ReferenceProcess {
+    fn name(&self) -> &'static str {
+        "process"
+    }
+
+    fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+        execute_hall_request_assigner(elevator_data).or_else(|e| {
+            error!("The hall request assigner failed, assigning in-process: {}", e);
+            assign_hall_requests(elevator_data)
+        })
+    }
+}
+
+impl AssignmentStrategy for AssignerPlugin {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+        AssignerPlugin::assign(self, elevator_data).or_else(|e| {
+            error!("Assigner plugin failed, assigning in-process: {}", e);
+            assign_hall_requests(elevator_data)
+        })
+    }
+}
+
+impl AssignmentStrategy for NearestCar {
+    fn name(&self) -> &'static str {
+        "nearest-car"
+    }
+
+    fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+        let (ids, mut assignment) = unassigned(elevator_data)?;
+        let n_floors = elevator_data.hall_requests.len();
+
+        for (floor, call) in calls(elevator_data) {
+            let nearest = ids.iter().min_by_key(|id| {
+                let state = &elevator_data.states[*id];
+                let moving_away = state.behaviour == Behaviour::Moving
+                    && ((state.direction == Direction::Up && floor < state.floor as usize)
+                        || (state.direction == Direction::Down && floor > state.floor as usize));
+                floor.abs_diff(state.floor as usize) + if moving_away { n_floors } else { 0 }
+            });
+            if let Some(hall_requests) = nearest.and_then(|id| assignment.get_mut(id)) {
+                hall_requests[floor][call] = true;
+            }
+        }
+        Ok(assignment)
+    }
+}
+
+impl AssignmentStrategy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+        let (ids, mut assignment) = unassigned(elevator_data)?;
+
+        for (turn, (floor, call)) in calls(elevator_data).into_iter().enumerate() {
+            if let Some(hall_requests) = assignment.get_mut(&ids[turn % ids.len()]) {
+                hall_requests[floor][call] = true;
+            }
+        }
+        Ok(assignment)
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// The strategy selected by `hall_request_assigner`. The WebAssembly strategy needs its loaded plugin, and assigns
+// in-process without it.
+pub fn strategy(assigner: HallRequestAssigner, assigner_plugin: Option<AssignerPlugin>) -> Box<dyn AssignmentStrategy> {
+    match (assigner, assigner_plugin) {
+        (HallRequestAssigner::Process, _) => Box::new(ReferenceProcess),
+        (HallRequestAssigner::Wasm, Some(plugin)) => Box::new(plugin),
+        (HallRequestAssigner::NearestCar, _) => Box::new(NearestCar),
+        (HallRequestAssigner::RoundRobin, _) => Box::new(RoundRobin),
+        (HallRequestAssigner::Native, _) | (HallRequestAssigner::Wasm, None) => Box::new(CostFunction),
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The IDs of the elevators, sorted, and an assignment giving them no calls. Fails on input the cost function refuses.
+fn unassigned(elevator_data: &ElevatorData) -> Result<(Vec<String>, HashMap<String, Vec<Vec<bool>>>), String> {
+    let n_floors = elevator_data.hall_requests.len();
+    if n_floors == 0 {
+        return Err("There are no floors".to_string());
+    }
+    if elevator_data.hall_requests.iter().any(|hall_requests| hall_requests.len() != 2) {
+        return Err("Every floor must have exactly two hall requests".to_string());
+    }
+    if elevator_data.states.is_empty() && !calls(elevator_data).is_empty() {
+        return Err("There are hall requests, but no elevators to serve them".to_string());
+    }
+
+    let mut ids = elevator_data.states.keys().cloned().collect::<Vec<String>>();
+    ids.sort();
+    let assignment = ids.iter().map(|id| (id.clone(), vec![vec![false; 2]; n_floors])).collect();
+    Ok((ids, assignment))
+}
+
+// The hall calls, from the lowest floor, the call up before the call down
+fn calls(elevator_data: &ElevatorData) -> Vec<(usize, usize)> {
+    let mut calls = Vec::new();
+    for (floor, hall_requests) in elevator_data.hall_requests.iter().enumerate() {
+        for call in [HALL_UP, HALL_DOWN] {
+            if hall_requests.get(call as usize).copied().unwrap_or(false) {
+                calls.push((floor, call as usize));
+            }
+        }
+    }
+    calls
+}
@@ -0,0 +1,101 @@
+/*
+ * Unit tests for the strategies hall calls are assigned by
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_strategy_selection
+ *  - test_strategy_nearest_car
+ *  - test_strategy_round_robin
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod strategy_tests {
+    use crate::coordinator::strategy::{strategy, AssignmentStrategy, NearestCar, RoundRobin};
+    use crate::shared::{Behaviour, Direction, ElevatorData, ElevatorState, HallRequestAssigner};
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+
+    const N_FLOORS: u8 = 6;
+
+    fn elevator_data(elevators: &[(&str, Behaviour, u8, Direction)], calls: &[(usize, u8)]) -> ElevatorData {
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        for (floor, call) in calls {
+            elevator_data.hall_requests[*floor][*call as usize] = true;
+        }
+        for (id, behaviour, floor, direction) in elevators {
+            let mut state = ElevatorState::new(N_FLOORS);
+            state.behaviour = behaviour.clone();
+            state.floor = *floor;
+            state.direction = direction.clone();
+            elevator_data.states.insert(id.to_string(), state);
+        }
+        elevator_data
+    }
+
+    fn assigned(assignment: &std::collections::HashMap<String, Vec<Vec<bool>>>, id: &str) -> Vec<(usize, u8)> {
+        let mut calls = Vec::new();
+        for (floor, hall_requests) in assignment[id].iter().enumerate() {
+            for call in [HALL_UP, HALL_DOWN] {
+                if hall_requests[call as usize] {
+                    calls.push((floor, call));
+                }
+            }
+        }
+        calls
+    }
+
+    #[test]
+    fn test_strategy_selection() {
+        // Act / Assert
+        assert_eq!(strategy(HallRequestAssigner::default(), None).name(), "native");
+        assert_eq!(strategy(HallRequestAssigner::Process, None).name(), "process");
+        assert_eq!(strategy(HallRequestAssigner::NearestCar, None).name(), "nearest-car");
+        assert_eq!(strategy(HallRequestAssigner::RoundRobin, None).name(), "round-robin");
+        // Without its plugin the WebAssembly strategy assigns in-process
+        assert_eq!(strategy(HallRequestAssigner::Wasm, None).name(), "native");
+        let assigner: HallRequestAssigner = toml::Value::String("nearest-car".to_string()).try_into().unwrap();
+        assert_eq!(assigner, HallRequestAssigner::NearestCar);
+    }
+
+    #[test]
+    fn test_strategy_nearest_car() {
+        // Arrange
+        let elevator_data = elevator_data(
+            &[("a", Behaviour::Idle, 0, Direction::Stop), ("b", Behaviour::Moving, 3, Direction::Up), ("c", Behaviour::Idle, 5, Direction::Stop)],
+            &[(1, HALL_UP), (2, HALL_DOWN), (4, HALL_DOWN), (5, HALL_DOWN)],
+        );
+
+        // Act
+        let assignment = NearestCar.assign(&elevator_data).unwrap();
+
+        // Assert
+        // Floor 2 is closer to b, but b moves away from it
+        assert_eq!(assigned(&assignment, "a"), vec![(1, HALL_UP), (2, HALL_DOWN)]);
+        // A tie goes to the lowest ID
+        assert_eq!(assigned(&assignment, "b"), vec![(4, HALL_DOWN)]);
+        assert_eq!(assigned(&assignment, "c"), vec![(5, HALL_DOWN)]);
+    }
+
+    #[test]
+    fn test_strategy_round_robin() {
+        // Arrange
+        let elevators = [("b", Behaviour::Idle, 0, Direction::Stop), ("a", Behaviour::Idle, 5, Direction::Stop)];
+        let elevator_data = elevator_data(&elevators, &[(0, HALL_UP), (2, HALL_UP), (2, HALL_DOWN)]);
+
+        // Act
+        let assignment = RoundRobin.assign(&elevator_data).unwrap();
+        let again = RoundRobin.assign(&elevator_data).unwrap();
+        let unserved = RoundRobin.assign(&self::elevator_data(&[], &[(0, HALL_UP)]));
+
+        // Assert
+        // The calls go in turn from the lowest ID, the same every time
+        assert_eq!(assigned(&assignment, "a"), vec![(0, HALL_UP), (2, HALL_DOWN)]);
+        assert_eq!(assigned(&assignment, "b"), vec![(2, HALL_UP)]);
+        assert_eq!(assignment, again);
+        assert!(unserved.is_err());
+    }
+}
@@ -0,0 +1,185 @@
+/**
+ * Assigns the hall calls off the event loop of the coordinator.
+ *
+ * An assignment takes from microseconds in-process to milliseconds for the reference executable, which is started
+ * once for every group of calls. Made inside the event loop, a burst of button presses leaves the coordinator behind
+ * on every other event. In the background the assignment is handed to a worker thread instead, and the coordinator
+ * keeps handling events until the result arrives.
+ *
+ * Every assignment asked for is numbered, and any newer input makes the running one stale. The worker skips to the
+ * newest assignment queued, and the coordinator applies a result only if it is of the newest assignment asked for.
+ * In the foreground, as the builder defaults to, the assignment is made on the spot.
+ *
+ * # Fields
+ * - `strategy`:                The strategy assigning in the foreground. It is moved to the worker in the background.
+ * - `job_tx`:                  Hands the assignments to the worker in the background.
+ * - `result_rx`:               The assignments made by the worker. Never receives in the foreground.
+ * - `generation`:              The number of the newest assignment asked for.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::coordinator::assigner::assign_within_capacity;
+use crate::coordinator::strategy::AssignmentStrategy;
+use crate::shared::ElevatorData;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// Hall calls that the same elevators may serve. Priority calls are assigned before the regular ones.
+pub struct CallGroup {
+    pub priority: bool,
+    pub eligible: Vec<String>,
+    pub calls: Vec<(u8, u8)>,
+}
+
+// The input of an assignment: the elevators in service and the groups of calls to assign among them
+pub struct AssignmentJob {
+    pub generation: u64,
+    pub local_id: String,
+    pub n_floors: u8,
+    pub elevator_data: ElevatorData,
+    pub groups: Vec<CallGroup>,
+}
+
+// The calls assigned to every elevator, those of the local elevator, and the elevators given any. The states of the
+// elevators carry the priority calls given to them as stops.
+pub struct HallAssignments {
+    pub elevator_data: ElevatorData,
+    pub assignments: BTreeMap<String, Vec<Vec<bool>>>,
+    pub local_hall_requests: Vec<Vec<bool>>,
+    pub busy: HashSet<String>,
+}
+
+pub struct AssignmentResult {
+    pub generation: u64,
+    pub assignment: Result<HallAssignments, String>,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct AssignmentWorker {
+    strategy: Option<Box<dyn AssignmentStrategy>>,
+    job_tx: Option<cbc::Sender<AssignmentJob>>,
+    pub result_rx: cbc::Receiver<AssignmentResult>,
+    generation: u64,
+}
+
+impl AssignmentWorker {
+    pub fn foreground(strategy: Box<dyn AssignmentStrategy>) -> AssignmentWorker {
+        AssignmentWorker {
+            strategy: Some(strategy),
+            job_tx: None,
+            result_rx: cbc::never(),
+            generation: 0,
+        }
+    }
+
+    // Starts the worker thread. It ends when the worker is dropped.
+    pub fn background(strategy: Box<dyn AssignmentStrategy>) -> AssignmentWorker {
+        let (job_tx, job_rx) = cbc::unbounded::<AssignmentJob>();
+        let (result_tx, result_rx) = cbc::unbounded::<AssignmentResult>();
+        let worker_thread = std::thread::Builder::new().name("hall_assigner".into());
+        worker_thread.spawn(move || work(strategy, job_rx, result_tx)).unwrap();
+
+        AssignmentWorker {
+            strategy: None,
+            job_tx: Some(job_tx),
+            result_rx,
+            generation: 0,
+        }
+    }
+
+    // Makes the running assignment stale, as its input is out of date
+    pub fn cancel(&mut self) {
+        self.generation += 1;
+    }
+
+    // Asks for an assignment. In the foreground it is returned at once, and in the background it arrives on `result_rx`.
+    pub fn assign(&mut self, mut job: AssignmentJob) -> Option<Result<HallAssignments, String>> {
+        job.generation = self.generation;
+        match (self.strategy.as_mut(), self.job_tx.as_ref()) {
+            (Some(strategy), _) => Some(assign_groups(strategy.as_mut(), job)),
+            (None, Some(job_tx)) => job_tx.send(job).err().map(|_| Err("The assigner thread has stopped".to_string())),
+            (None, None) => Some(Err("There is no assigner".to_string())),
+        }
+    }
+
+    // Whether a result is of the newest assignment asked for
+    pub fn is_current(&self, result: &AssignmentResult) -> bool {
+        result.generation == self.generation
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Assigns the groups of calls in turn. The load of an elevator carries over between groups, so with capacities every
+// group is assigned, and later groups see the priority calls as stops the elevators have committed to.
+pub fn assign_groups(strategy: &mut dyn AssignmentStrategy, job: AssignmentJob) -> Result<HallAssignments, String> {
+    let AssignmentJob { local_id, n_floors, mut elevator_data, groups, .. } = job;
+    let mut load = HashMap::new();
+    let mut local_hall_requests = vec![vec![false; 2]; n_floors as usize];
+    let mut assignments = elevator_data.states.keys()
+        .map(|id| (id.clone(), vec![vec![false; 2]; n_floors as usize]))
+        .collect::<BTreeMap<String, Vec<Vec<bool>>>>();
+    let mut busy = HashSet::new();
+
+    for CallGroup { priority, eligible, calls } in groups {
+        let mut group_data = ElevatorData::new(n_floors);
+        group_data.version = elevator_data.version;
+        for (floor, call) in calls.iter() {
+            group_data.hall_requests[*floor as usize][*call as usize] = true;
+        }
+        for id in eligible.iter() {
+            group_data.states.insert(id.clone(), elevator_data.states[id].clone());
+        }
+
+        let hra_output = assign_within_capacity(&group_data, &mut load, |group_data| strategy.assign(group_data))?;
+        for (id, hall_requests) in hra_output {
+            for (floor, call) in calls.iter() {
+                if !hall_requests[*floor as usize][*call as usize] {
+                    continue;
+                }
+
+                busy.insert(id.clone());
+                if let Some(assigned) = assignments.get_mut(&id) {
+                    assigned[*floor as usize][*call as usize] = true;
+                }
+                if id == local_id {
+                    local_hall_requests[*floor as usize][*call as usize] = true;
+                }
+                if priority {
+                    if let Some(state) = elevator_data.states.get_mut(&id) {
+                        state.cab_requests[*floor as usize] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(HallAssignments { elevator_data, assignments, local_hall_requests, busy })
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Assigns the newest job queued, until the coordinator is gone
+fn work(mut strategy: Box<dyn AssignmentStrategy>, job_rx: cbc::Receiver<AssignmentJob>, result_tx: cbc::Sender<AssignmentResult>) {
+    while let Ok(job) = job_rx.recv() {
+        let job = job_rx.try_iter().last().unwrap_or(job);
+        let generation = job.generation;
+        let assignment = assign_groups(strategy.as_mut(), job);
+        if result_tx.send(AssignmentResult { generation, assignment }).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * Unit tests for the assignment of hall calls in the background
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_worker_foreground
+ *  - test_worker_discards_stale
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod worker_tests {
+    use crate::coordinator::strategy::{AssignmentStrategy, CostFunction};
+    use crate::coordinator::worker::{AssignmentJob, AssignmentResult, AssignmentWorker, CallGroup};
+    use crate::shared::{ElevatorData, ElevatorState};
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const N_FLOORS: u8 = 4;
+
+    // Assigns as the cost function does, slowly, counting the groups it assigns
+    struct Slow {
+        assigned: Arc<AtomicUsize>,
+    }
+
+    impl AssignmentStrategy for Slow {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn assign(&mut self, elevator_data: &ElevatorData) -> Result<HashMap<String, Vec<Vec<bool>>>, String> {
+            std::thread::sleep(Duration::from_millis(100));
+            self.assigned.fetch_add(1, Ordering::SeqCst);
+            CostFunction.assign(elevator_data)
+        }
+    }
+
+    fn job(floor: u8) -> AssignmentJob {
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(N_FLOORS));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(N_FLOORS));
+        let groups = vec![
+            CallGroup { priority: true, eligible: vec!["b".to_string()], calls: vec![(3, HALL_DOWN)] },
+            CallGroup { priority: false, eligible: vec!["a".to_string(), "b".to_string()], calls: vec![(floor, HALL_UP)] },
+        ];
+        AssignmentJob { generation: 0, local_id: "a".to_string(), n_floors: N_FLOORS, elevator_data, groups }
+    }
+
+    #[test]
+    fn test_worker_foreground() {
+        // Arrange
+        let mut worker = AssignmentWorker::foreground(Box::new(CostFunction));
+
+        // Act
+        worker.cancel();
+        let assignment = worker.assign(job(1)).unwrap().unwrap();
+
+        // Assert
+        // The priority call is a stop of b, so the call at floor 1 goes to a
+        assert_eq!(assignment.assignments["b"][3], vec![false, true]);
+        assert!(assignment.elevator_data.states["b"].cab_requests[3]);
+        assert_eq!(assignment.local_hall_requests[1], vec![true, false]);
+        assert_eq!(assignment.busy.len(), 2);
+    }
+
+    #[test]
+    fn test_worker_discards_stale() {
+        // Arrange
+        let assigned = Arc::new(AtomicUsize::new(0));
+        let mut worker = AssignmentWorker::background(Box::new(Slow { assigned: assigned.clone() }));
+
+        // Act
+        // The first assignment starts at once, and the second is queued behind it until the third replaces it
+        let mut results = Vec::new();
+        for floor in 0..3 {
+            worker.cancel();
+            assert!(worker.assign(job(floor)).is_none());
+            if floor == 0 {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        while results.last().is_none_or(|result: &AssignmentResult| !worker.is_current(result)) {
+            results.push(worker.result_rx.recv_timeout(Duration::from_secs(5)).unwrap());
+        }
+
+        // Assert
+        assert_eq!(results.iter().map(|result| result.generation).collect::<Vec<u64>>(), vec![1, 3]);
+        assert!(!worker.is_current(&results[0]));
+        assert_eq!(results[1].assignment.as_ref().unwrap().local_hall_requests[2], vec![true, false]);
+        assert_eq!(assigned.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,117 @@
+/**
+ * Faults injected into the local elevator during a soak test.
+ *
+ * Faults arrive as a Poisson process, every `fault_interval` seconds on average, and each lasts `fault_time` seconds
+ * before the elevator is restored. One fault is injected at a time, drawn at random among:
+ *
+ * - `Halt`: the car is halted after its current stop, as by a watchdog, and its hall calls reassigned.
+ * - `Isolate`: the elevator stops announcing itself to its peers, which lose it and take over its hall calls, while
+ *   it still hears them. It is announced again at the end of the fault.
+ *
+ * The timers run on the clock of the elevator, so a simulated clock speeds up the faults with the traffic.
+ *
+ * # Fields
+ * - `fault_interval`:          The mean time between faults. None injects no faults.
+ * - `fault_time`:              How long each fault lasts.
+ * - `random`:                  Draws the arrivals and kinds of the faults.
+ * - `clock`:                   The clock the faults are timed on.
+ * - `coordinator_command_tx`:  Halts and resumes the car.
+ * - `net_peer_tx_enable_tx`:   Stops and resumes the announcements to the peers.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::info;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::SoakConfig;
+use crate::demo::demo::Random;
+use crate::shared::{Clock, ControlCommand};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fault {
+    Halt,
+    Isolate,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct ChaosController {
+    fault_interval: Option<Duration>,
+    fault_time: Duration,
+    random: Random,
+    clock: Arc<dyn Clock>,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    net_peer_tx_enable_tx: cbc::Sender<bool>,
+}
+
+impl ChaosController {
+    pub fn new(
+        soak_config: &SoakConfig,
+        clock: Arc<dyn Clock>,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+        net_peer_tx_enable_tx: cbc::Sender<bool>,
+    ) -> ChaosController {
+        ChaosController {
+            fault_interval: (soak_config.fault_interval > 0).then(|| Duration::from_secs(soak_config.fault_interval)),
+            fault_time: Duration::from_secs(soak_config.fault_time),
+            random: Random::new(soak_config.seed),
+            clock,
+            coordinator_command_tx,
+            net_peer_tx_enable_tx,
+        }
+    }
+
+    // Injects faults until the coordinator is gone
+    pub fn run(mut self) {
+        let Some(fault_interval) = self.fault_interval else { return };
+        info!("Injecting a fault every {:?} on average, each lasting {:?}", fault_interval, self.fault_time);
+        loop {
+            let interval = self.next_interval(fault_interval);
+            self.clock.sleep(interval);
+            let fault = self.next_fault();
+            info!("Chaos: injecting {:?} for {:?}", fault, self.fault_time);
+            if !self.inject(fault, true) {
+                return;
+            }
+            self.clock.sleep(self.fault_time);
+            info!("Chaos: restoring from {:?}", fault);
+            if !self.inject(fault, false) {
+                return;
+            }
+        }
+    }
+
+    // Starts or ends the fault. False once the module it is injected into is gone.
+    pub fn inject(&self, fault: Fault, active: bool) -> bool {
+        match fault {
+            Fault::Halt => self.coordinator_command_tx.send(ControlCommand::Halt(active)).is_ok(),
+            Fault::Isolate => self.net_peer_tx_enable_tx.send(!active).is_ok(),
+        }
+    }
+
+    // The time until the next fault, exponentially distributed around the mean interval
+    fn next_interval(&mut self, fault_interval: Duration) -> Duration {
+        let interval = -(1.0 - self.random.next_f64()).ln() * fault_interval.as_secs_f64();
+        Duration::from_secs_f64(interval.min(u32::MAX as f64))
+    }
+
+    fn next_fault(&mut self) -> Fault {
+        match self.random.next_f64() < 0.5 {
+            true => Fault::Halt,
+            false => Fault::Isolate,
+        }
+    }
+}
@@ -0,0 +1,344 @@
+/**
+ * Headless random-traffic demo.
+ *
+ * Places hall and cab calls on the coordinator at random, as passengers would, for soak testing and for demos
+ * with nobody at the buttons. The calls arrive as a Poisson process at the configured rates, on floors drawn by
+ * the configured weights, and are placed through the same commands as the debug console. No calls are placed
+ * after the configured duration, and the orders still waiting are given the drain time to be served before the
+ * summary of the run is printed. The timers run on the clock of the elevator, so a simulated clock speeds up
+ * the whole run.
+ *
+ * Every order is followed through the snapshots of the coordinator. It is served once its request has been
+ * seen and is cleared again. An order not seen within a second was refused, or served before the next poll,
+ * and is counted as dropped. A call for an order already waiting joins that order, and is not placed again.
+ *
+ * # Fields
+ * - `n_floors`:                The number of floors of the elevator.
+ * - `duration`:                How long calls are placed for.
+ * - `drain_time`:              How long the orders still waiting are given to be served.
+ * - `hall_call_rate`:          Hall calls per second.
+ * - `cab_call_rate`:           Cab calls per second.
+ * - `floor_weights`:           How likely a call is at each floor.
+ * - `random`:                  Draws the arrivals and floors of the calls.
+ * - `clock`:                   The clock the run is timed on.
+ * - `orders`:                  The orders placed and not yet served.
+ * - `summary`:                 The tally of the run so far.
+ * - `coordinator_command_tx`:  Places the calls on the coordinator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use log::{info, error};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::DemoConfig;
+use crate::shared::{exit, Clock, ControlCommand, ExitReason, Snapshot};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SEEN_TIMEOUT: Duration = Duration::from_secs(1);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DemoSummary {
+    // Calls drawn, including those joining a waiting order
+    pub calls: usize,
+    pub joined: usize,
+    pub served: usize,
+    pub dropped: usize,
+    // The orders still waiting at the end of the run, as (floor, call)
+    pub unserved: Vec<(u8, u8)>,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl fmt::Display for DemoSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mean_wait = if self.served > 0 { self.total_wait / self.served as u32 } else { Duration::ZERO };
+        writeln!(f, "Demo summary:")?;
+        writeln!(f, "  Calls:     {} ({} joined a waiting order)", self.calls, self.joined)?;
+        writeln!(
+            f,
+            "  Served:    {} (waited {:.1} s on average, {:.1} s at most)",
+            self.served,
+            mean_wait.as_secs_f64(),
+            self.max_wait.as_secs_f64()
+        )?;
+        writeln!(f, "  Dropped:   {}", self.dropped)?;
+        write!(f, "  Unserved:  {} {:?}", self.unserved.len(), self.unserved)
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct TrafficGenerator {
+    n_floors: u8,
+    duration: Duration,
+    drain_time: Duration,
+    hall_call_rate: f64,
+    cab_call_rate: f64,
+    floor_weights: Vec<f64>,
+    random: Random,
+    clock: Arc<dyn Clock>,
+    orders: Vec<Order>,
+    summary: DemoSummary,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+}
+
+impl TrafficGenerator {
+    pub fn new(
+        demo_config: &DemoConfig,
+        n_floors: u8,
+        clock: Arc<dyn Clock>,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    ) -> TrafficGenerator {
+        // Floors without a weight are not called, unless no weights are given at all
+        let mut floor_weights = (0..n_floors as usize)
+            .map(|floor| demo_config.floor_weights.get(floor).copied().unwrap_or(0.0).max(0.0))
+            .collect::<Vec<f64>>();
+        if floor_weights.iter().sum::<f64>() <= 0.0 {
+            floor_weights = vec![1.0; n_floors as usize];
+        }
+
+        TrafficGenerator {
+            n_floors,
+            duration: Duration::from_secs(demo_config.duration),
+            drain_time: Duration::from_secs(demo_config.drain_time),
+            hall_call_rate: demo_config.hall_call_rate.max(0.0) / 60.0,
+            cab_call_rate: demo_config.cab_call_rate.max(0.0) / 60.0,
+            floor_weights,
+            random: Random::new(demo_config.seed),
+            clock,
+            orders: Vec::new(),
+            summary: DemoSummary::default(),
+            coordinator_command_tx,
+            coordinator_query_tx,
+        }
+    }
+
+    // Runs the demo to the end, printing and returning its summary
+    pub fn run(mut self) -> DemoSummary {
+        let start = self.clock.now();
+        let calls_end = start + self.duration;
+        let end = calls_end + self.drain_time;
+        let mut next_call = self.next_interval().map(|interval| start + interval);
+        info!(
+            "Demo placing {:.1} hall and {:.1} cab calls a minute for {:?}",
+            self.hall_call_rate * 60.0,
+            self.cab_call_rate * 60.0,
+            self.duration
+        );
+
+        loop {
+            let now = self.clock.now();
+            while let Some(at) = next_call.filter(|at| *at <= now && *at < calls_end) {
+                self.place_call(at);
+                next_call = self.next_interval().map(|interval| at + interval);
+            }
+
+            match self.query() {
+                Some(snapshot) => self.update_orders(&snapshot, now),
+                None => {
+                    error!("ERROR - the coordinator did not answer the demo");
+                    exit(ExitReason::Failure);
+                }
+            }
+
+            if now >= end || (now >= calls_end && self.orders.is_empty()) {
+                break;
+            }
+            self.clock.sleep(POLL_INTERVAL);
+        }
+
+        self.summary.unserved = self.orders.iter().map(|order| (order.floor, order.call)).collect();
+        println!("{}", self.summary);
+        self.summary
+    }
+
+    // The time until the next call of either kind, or `None` if no calls are placed
+    fn next_interval(&mut self) -> Option<Duration> {
+        let rate = self.hall_call_rate + self.cab_call_rate;
+        if rate <= 0.0 {
+            return None;
+        }
+        let interval = -(1.0 - self.random.next_f64()).ln() / rate;
+        Some(Duration::from_secs_f64(interval.min(u32::MAX as f64)))
+    }
+
+    fn next_call(&mut self) -> (u8, u8) {
+        let is_hall_call = self.random.next_f64() * (self.hall_call_rate + self.cab_call_rate) < self.hall_call_rate;
+        let floor = self.next_floor();
+        if !is_hall_call {
+            return (floor, CAB);
+        }
+
+        // The end floors only have the one hall button
+        let call = if floor == 0 {
+            HALL_UP
+        } else if floor + 1 == self.n_floors {
+            HALL_DOWN
+        } else if self.random.next_f64() < 0.5 {
+            HALL_UP
+        } else {
+            HALL_DOWN
+        };
+        (floor, call)
+    }
+
+    fn next_floor(&mut self) -> u8 {
+        let mut draw = self.random.next_f64() * self.floor_weights.iter().sum::<f64>();
+        for (floor, weight) in self.floor_weights.iter().enumerate() {
+            if draw < *weight {
+                return floor as u8;
+            }
+            draw -= weight;
+        }
+        self.n_floors - 1
+    }
+
+    fn place_call(&mut self, at: Instant) {
+        let (floor, call) = self.next_call();
+        self.summary.calls += 1;
+        if self.orders.iter().any(|order| order.floor == floor && order.call == call) {
+            self.summary.joined += 1;
+            return;
+        }
+
+        let command = if call == CAB {
+            ControlCommand::CabCall(floor)
+        } else {
+            ControlCommand::HallCall(floor, call, true)
+        };
+        if let Err(e) = self.coordinator_command_tx.send(command) {
+            error!("Failed to send demo call to coordinator: {:?}", e);
+            exit(ExitReason::Failure);
+        }
+        self.orders.push(Order { floor, call, placed: at, seen: false });
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+
+    fn update_orders(&mut self, snapshot: &Snapshot, now: Instant) {
+        let hall_requests = &snapshot.elevator_data.hall_requests;
+        let cab_requests = snapshot
+            .elevator_data
+            .states
+            .get(&snapshot.id)
+            .map(|state| state.cab_requests.clone())
+            .unwrap_or_default();
+
+        for mut order in std::mem::take(&mut self.orders) {
+            let requested = if order.call == CAB {
+                cab_requests.get(order.floor as usize).copied()
+            } else {
+                hall_requests.get(order.floor as usize).and_then(|calls| calls.get(order.call as usize).copied())
+            };
+
+            let wait = now.saturating_duration_since(order.placed);
+            if requested.unwrap_or(false) {
+                order.seen = true;
+                self.orders.push(order);
+            } else if order.seen {
+                self.summary.served += 1;
+                self.summary.total_wait += wait;
+                self.summary.max_wait = self.summary.max_wait.max(wait);
+            } else if wait >= SEEN_TIMEOUT {
+                info!("Demo call {:?} was never seen", (order.floor, order.call));
+                self.summary.dropped += 1;
+            } else {
+                self.orders.push(order);
+            }
+        }
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+struct Order {
+    floor: u8,
+    call: u8,
+    placed: Instant,
+    seen: bool,
+}
+
+// xorshift64*, which is plenty for drawing passengers and faults
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    // Seeded from the clock with 0
+    pub(crate) fn new(seed: u64) -> Random {
+        let seed = match seed {
+            0 => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64,
+            seed => seed,
+        };
+        Random { state: seed.max(1) }
+    }
+
+    // Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::{DemoSummary, TrafficGenerator};
+    use crate::shared::Snapshot;
+    use std::time::{Duration, Instant};
+
+    impl TrafficGenerator {
+        pub fn test_next_interval(&mut self) -> Option<Duration> {
+            self.next_interval()
+        }
+
+        pub fn test_next_call(&mut self) -> (u8, u8) {
+            self.next_call()
+        }
+
+        pub fn test_place_call(&mut self, at: Instant) {
+            self.place_call(at)
+        }
+
+        pub fn test_update_orders(&mut self, snapshot: &Snapshot, now: Instant) {
+            self.update_orders(snapshot, now)
+        }
+
+        pub fn test_summary(&self) -> &DemoSummary {
+            &self.summary
+        }
+
+        pub fn test_waiting_orders(&self) -> usize {
+            self.orders.len()
+        }
+    }
+}
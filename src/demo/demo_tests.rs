@@ -0,0 +1,148 @@
+/*
+ * Unit tests for the random-traffic demo
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_demo_call_profile
+ *  - test_demo_order_tracking
+ *  - test_demo_run
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod demo_tests {
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+    use std::sync::Arc;
+    use std::thread::spawn;
+    use std::time::{Duration, Instant};
+    use crate::config::DemoConfig;
+    use crate::demo::TrafficGenerator;
+    use crate::shared::{ControlCommand, ElevatorData, ElevatorState, NetworkStatistics, RealClock, SimulatedClock, Snapshot, Uptime};
+
+    const N_FLOORS: u8 = 4;
+    const ID: &str = "elevator";
+
+    fn demo_config(hall_call_rate: f64, cab_call_rate: f64, floor_weights: Vec<f64>) -> DemoConfig {
+        DemoConfig { duration: 60, drain_time: 10, hall_call_rate, cab_call_rate, floor_weights, seed: 4145 }
+    }
+
+    fn snapshot(elevator_data: &ElevatorData) -> Snapshot {
+        Snapshot {
+            id: ID.to_string(),
+            peers: vec![ID.to_string()],
+            elevator_data: elevator_data.clone(),
+            confirmed_hall_requests: elevator_data.hall_requests.clone(),
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    fn elevator_data() -> ElevatorData {
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        elevator_data.states.insert(ID.to_string(), ElevatorState::new(N_FLOORS));
+        elevator_data
+    }
+
+    #[test]
+    fn test_demo_call_profile() {
+        // Arrange
+        let (command_tx, _command_rx) = unbounded::<ControlCommand>();
+        let (query_tx, _query_rx) = unbounded::<Sender<Snapshot>>();
+        let mut hall_generator =
+            TrafficGenerator::new(&demo_config(6.0, 0.0, vec![0.0, 0.0, 1.0]), N_FLOORS, Arc::new(RealClock), command_tx.clone(), query_tx.clone());
+        let mut cab_generator = TrafficGenerator::new(&demo_config(0.0, 6.0, vec![]), N_FLOORS, Arc::new(RealClock), command_tx.clone(), query_tx.clone());
+        let mut idle_generator = TrafficGenerator::new(&demo_config(0.0, 0.0, vec![]), N_FLOORS, Arc::new(RealClock), command_tx, query_tx);
+
+        // Act
+        let intervals = (0..10000).map(|_| hall_generator.test_next_interval().unwrap()).collect::<Vec<Duration>>();
+        let hall_calls = (0..1000).map(|_| hall_generator.test_next_call()).collect::<Vec<(u8, u8)>>();
+        let cab_calls = (0..1000).map(|_| cab_generator.test_next_call()).collect::<Vec<(u8, u8)>>();
+
+        // Assert
+        // Six calls a minute arrive ten seconds apart on average
+        let mean = intervals.iter().sum::<Duration>().as_secs_f64() / intervals.len() as f64;
+        assert!((mean - 10.0).abs() < 0.5, "Mean interval {}", mean);
+        // Only the weighted floor is called, in both directions
+        assert!(hall_calls.iter().all(|(floor, call)| *floor == 2 && *call != CAB));
+        assert!(hall_calls.contains(&(2, HALL_UP)) && hall_calls.contains(&(2, HALL_DOWN)));
+        // Without weights every floor is called
+        assert!(cab_calls.iter().all(|(_, call)| *call == CAB));
+        assert!((0..N_FLOORS).all(|floor| cab_calls.contains(&(floor, CAB))));
+        assert_eq!(idle_generator.test_next_interval(), None);
+    }
+
+    #[test]
+    fn test_demo_order_tracking() {
+        // Arrange
+        let (command_tx, command_rx) = unbounded::<ControlCommand>();
+        let (query_tx, _query_rx) = unbounded::<Sender<Snapshot>>();
+        let start = Instant::now();
+        let mut generator = TrafficGenerator::new(&demo_config(0.0, 1.0, vec![0.0, 1.0]), N_FLOORS, Arc::new(RealClock), command_tx, query_tx);
+        let mut requested = elevator_data();
+        requested.states.get_mut(ID).unwrap().cab_requests[1] = true;
+
+        // Act
+        generator.test_place_call(start);
+        generator.test_place_call(start);
+        generator.test_update_orders(&snapshot(&elevator_data()), start + Duration::from_millis(500));
+        let waiting_unseen = generator.test_waiting_orders();
+        generator.test_update_orders(&snapshot(&requested), start + Duration::from_secs(2));
+        generator.test_update_orders(&snapshot(&elevator_data()), start + Duration::from_secs(5));
+
+        generator.test_place_call(start + Duration::from_secs(6));
+        generator.test_update_orders(&snapshot(&elevator_data()), start + Duration::from_secs(8));
+
+        // Assert
+        // The second call joined the first, so two commands were placed for the three calls
+        assert_eq!(command_rx.try_iter().collect::<Vec<ControlCommand>>(), vec![ControlCommand::CabCall(1), ControlCommand::CabCall(1)]);
+        assert_eq!(waiting_unseen, 1);
+        let summary = generator.test_summary();
+        assert_eq!((summary.calls, summary.joined, summary.served, summary.dropped), (3, 1, 1, 1));
+        assert_eq!(summary.max_wait, Duration::from_secs(5));
+        assert_eq!(generator.test_waiting_orders(), 0);
+    }
+
+    // Serves every request the first time it is reported, as a coordinator with a very fast car would
+    fn serving_coordinator(command_rx: Receiver<ControlCommand>, query_rx: Receiver<Sender<Snapshot>>) {
+        let mut elevator_data = elevator_data();
+        while let Ok(reply_tx) = query_rx.recv() {
+            for command in command_rx.try_iter() {
+                match command {
+                    ControlCommand::HallCall(floor, call, true) => elevator_data.hall_requests[floor as usize][call as usize] = true,
+                    ControlCommand::CabCall(floor) => elevator_data.states.get_mut(ID).unwrap().cab_requests[floor as usize] = true,
+                    _ => {}
+                }
+            }
+            let _ = reply_tx.send(snapshot(&elevator_data));
+            elevator_data = self::elevator_data();
+        }
+    }
+
+    #[test]
+    fn test_demo_run() {
+        // Arrange
+        let (command_tx, command_rx) = unbounded::<ControlCommand>();
+        let (query_tx, query_rx) = unbounded::<Sender<Snapshot>>();
+        let coordinator = spawn(move || serving_coordinator(command_rx, query_rx));
+        let generator = TrafficGenerator::new(&demo_config(30.0, 30.0, vec![]), N_FLOORS, Arc::new(SimulatedClock::new(1000.0)), command_tx, query_tx);
+
+        // Act
+        let summary = generator.run();
+        coordinator.join().unwrap();
+
+        // Assert
+        // About sixty calls in a minute, all of them served
+        assert!(summary.calls > 30 && summary.calls < 90, "{} calls", summary.calls);
+        assert_eq!(summary.served + summary.joined, summary.calls);
+        assert_eq!(summary.dropped, 0);
+        assert!(summary.unserved.is_empty());
+        assert!(summary.max_wait < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,170 @@
+/**
+ * Invariants of the fleet, checked online on the snapshots of the coordinator during a soak test.
+ *
+ * - No call waits longer than the deadline: the hall calls of the fleet and the cab calls of every elevator, timed
+ *   from the first snapshot they are seen in.
+ * - The lamps and the door are consistent with the state: a hall call is only lit, that is confirmed, while it is
+ *   requested, and no car moves with its door open.
+ * - No hall call is served twice: no two elevators in normal service plan to stop at a floor with a single hall
+ *   call and no cab call of theirs, for longer than the grace period, which covers a call being reassigned.
+ *
+ * # Fields
+ * - `deadline`:                How long a call may wait. None leaves the waits unchecked.
+ * - `grace`:                   How long two elevators may plan to serve the same hall call.
+ * - `waiting`:                 When each call waiting was first seen, by elevator, or None for hall calls.
+ * - `duplicates`:              When two elevators were first seen planning to serve the hall call at each floor.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{Behaviour, DoorState, OperatingMode, Snapshot};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Violation {
+    // A call has waited longer than the deadline. The elevator is None for hall calls.
+    CallTooOld { elevator: Option<String>, floor: u8, call: u8, waited: u64 },
+    // A hall call is lit without being requested
+    LampWithoutCall { floor: u8, call: u8 },
+    // A car is moving with its door open
+    DoorOpenWhileMoving { elevator: String, floor: u8 },
+    // Two or more elevators plan to serve the same hall call
+    DuplicateService { floor: u8, elevators: Vec<String> },
+    // The coordinator did not answer a query, as when it is hung
+    CoordinatorUnresponsive,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::CallTooOld { elevator: Some(elevator), floor, waited, .. } => {
+                write!(f, "The cab call of {} at floor {} has waited {} s", elevator, floor, waited)
+            }
+            Violation::CallTooOld { elevator: None, floor, call, waited } => {
+                write!(f, "The hall call {} at floor {} has waited {} s", call_name(*call), floor, waited)
+            }
+            Violation::LampWithoutCall { floor, call } => write!(f, "The hall call {} at floor {} is lit without being requested", call_name(*call), floor),
+            Violation::DoorOpenWhileMoving { elevator, floor } => write!(f, "{} is moving with its door open at floor {}", elevator, floor),
+            Violation::DuplicateService { floor, elevators } => write!(f, "{} all plan to serve the hall call at floor {}", elevators.join(", "), floor),
+            Violation::CoordinatorUnresponsive => write!(f, "The coordinator did not answer"),
+        }
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct InvariantChecker {
+    deadline: Option<Duration>,
+    grace: Duration,
+    waiting: HashMap<(Option<String>, u8, u8), Instant>,
+    duplicates: HashMap<u8, Instant>,
+}
+
+impl InvariantChecker {
+    pub fn new(deadline: Option<Duration>, grace: Duration) -> InvariantChecker {
+        InvariantChecker { deadline, grace, waiting: HashMap::new(), duplicates: HashMap::new() }
+    }
+
+    // The invariants the snapshot, seen at `now`, violates
+    pub fn check(&mut self, snapshot: &Snapshot, now: Instant) -> Vec<Violation> {
+        let mut violations = self.check_waits(snapshot, now);
+        violations.extend(check_lamps(snapshot));
+        violations.extend(self.check_duplicates(snapshot, now));
+        violations
+    }
+
+    fn check_waits(&mut self, snapshot: &Snapshot, now: Instant) -> Vec<Violation> {
+        let data = &snapshot.elevator_data;
+        let hall_calls = data.hall_requests.iter().enumerate().flat_map(|(floor, calls)| {
+            [HALL_UP, HALL_DOWN].into_iter().filter(|call| calls[*call as usize]).map(move |call| (None, floor as u8, call))
+        });
+        let cab_calls = data.states.iter().flat_map(|(elevator, state)| {
+            state.cab_requests.iter().enumerate().filter(|(_, requested)| **requested).map(|(floor, _)| (Some(elevator.clone()), floor as u8, CAB))
+        });
+        let calls = hall_calls.chain(cab_calls).collect::<Vec<(Option<String>, u8, u8)>>();
+
+        self.waiting.retain(|call, _| calls.contains(call));
+        let mut violations = Vec::new();
+        for call in calls {
+            let waited = now.saturating_duration_since(*self.waiting.entry(call.clone()).or_insert(now));
+            if self.deadline.is_some_and(|deadline| waited > deadline) {
+                let (elevator, floor, call) = call;
+                violations.push(Violation::CallTooOld { elevator, floor, call, waited: waited.as_secs() });
+            }
+        }
+        violations
+    }
+
+    fn check_duplicates(&mut self, snapshot: &Snapshot, now: Instant) -> Vec<Violation> {
+        let data = &snapshot.elevator_data;
+        let mut duplicates = HashMap::new();
+        for (floor, calls) in data.hall_requests.iter().enumerate() {
+            if calls[HALL_UP as usize] == calls[HALL_DOWN as usize] {
+                continue;
+            }
+            let mut elevators = data
+                .states
+                .iter()
+                .filter(|(_, state)| state.mode == OperatingMode::Normal && !state.halted)
+                .filter(|(_, state)| state.stop_plan.contains(&(floor as u8)) && !state.cab_requests.get(floor).copied().unwrap_or(false))
+                .map(|(elevator, _)| elevator.clone())
+                .collect::<Vec<String>>();
+            if elevators.len() > 1 {
+                elevators.sort();
+                duplicates.insert(floor as u8, elevators);
+            }
+        }
+
+        self.duplicates.retain(|floor, _| duplicates.contains_key(floor));
+        let mut violations = Vec::new();
+        for (floor, elevators) in duplicates {
+            let since = *self.duplicates.entry(floor).or_insert(now);
+            if now.saturating_duration_since(since) > self.grace {
+                violations.push(Violation::DuplicateService { floor, elevators });
+            }
+        }
+        violations
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn check_lamps(snapshot: &Snapshot) -> Vec<Violation> {
+    let data = &snapshot.elevator_data;
+    let mut violations = Vec::new();
+    for (floor, (lit, requested)) in snapshot.confirmed_hall_requests.iter().zip(&data.hall_requests).enumerate() {
+        for call in [HALL_UP, HALL_DOWN] {
+            if lit[call as usize] && !requested[call as usize] {
+                violations.push(Violation::LampWithoutCall { floor: floor as u8, call });
+            }
+        }
+    }
+    for (elevator, state) in &data.states {
+        if state.behaviour == Behaviour::Moving && state.door != DoorState::Closed {
+            violations.push(Violation::DoorOpenWhileMoving { elevator: elevator.clone(), floor: state.floor });
+        }
+    }
+    violations
+}
+
+fn call_name(call: u8) -> &'static str {
+    match call {
+        HALL_UP => "up",
+        _ => "down",
+    }
+}
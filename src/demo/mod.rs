@@ -0,0 +1,10 @@
+pub mod chaos;
+pub mod demo;
+pub mod demo_tests;
+pub mod invariants;
+pub mod soak;
+pub mod soak_tests;
+
+pub use chaos::ChaosController;
+pub use demo::{DemoSummary, TrafficGenerator};
+pub use soak::SoakMonitor;
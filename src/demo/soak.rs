@@ -0,0 +1,149 @@
+/**
+ * The soak test, checking the invariants of the fleet while the traffic generator and the chaos controller run.
+ *
+ * Every `check_interval` milliseconds the snapshot of the coordinator is checked by the `InvariantChecker`. On the
+ * first violation, the violations and the last `HISTORY` snapshots are dumped as JSON to `dump_directory`, as
+ * `soak-<id>-<time>.json`, and the test ends. Otherwise it ends with the run of the traffic generator.
+ *
+ * # Fields
+ * - `local_id`:                The ID of the local elevator.
+ * - `check_interval`:          Time between the checks.
+ * - `dump_directory`:          The directory the state is dumped to on a violation.
+ * - `checker`:                 Checks the invariants on the snapshots.
+ * - `history`:                 The last snapshots checked, oldest first.
+ * - `clock`:                   The clock the checks are timed on.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::Local;
+use crossbeam_channel as cbc;
+use log::{info, error};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::SoakConfig;
+use crate::demo::invariants::{InvariantChecker, Violation};
+use crate::demo::DemoSummary;
+use crate::shared::{Clock, Snapshot};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const HISTORY: usize = 20;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// The state dumped on a violation
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SoakDump {
+    pub elevator: String,
+    pub time: String,
+    pub violations: Vec<Violation>,
+    pub snapshots: Vec<Snapshot>,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct SoakMonitor {
+    local_id: String,
+    check_interval: Duration,
+    dump_directory: String,
+    checker: InvariantChecker,
+    history: VecDeque<Snapshot>,
+    clock: Arc<dyn Clock>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+}
+
+impl SoakMonitor {
+    pub fn new(soak_config: &SoakConfig, local_id: String, clock: Arc<dyn Clock>, coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>) -> SoakMonitor {
+        SoakMonitor {
+            local_id,
+            check_interval: match soak_config.check_interval {
+                0 => DEFAULT_CHECK_INTERVAL,
+                milliseconds => Duration::from_millis(milliseconds),
+            },
+            dump_directory: soak_config.dump_directory.clone(),
+            checker: InvariantChecker::new(
+                (soak_config.deadline > 0).then(|| Duration::from_secs(soak_config.deadline)),
+                Duration::from_millis(soak_config.grace),
+            ),
+            history: VecDeque::new(),
+            clock,
+            coordinator_query_tx,
+        }
+    }
+
+    // Checks the invariants until the traffic generator ends, returning its summary, or until the first violation
+    pub fn run(mut self, traffic_done_rx: cbc::Receiver<DemoSummary>) -> Result<DemoSummary, Violation> {
+        info!("Soak test checking the invariants every {:?}", self.check_interval);
+        loop {
+            if let Ok(summary) = traffic_done_rx.try_recv() {
+                info!("Soak test passed, with no violation");
+                return Ok(summary);
+            }
+
+            let violations = self.check();
+            if let Some(violation) = violations.first().cloned() {
+                for violation in &violations {
+                    error!("INVARIANT VIOLATED: {}", violation);
+                }
+                match self.dump(violations) {
+                    Ok(path) => error!("Soak test failed, the state is dumped to {}", path),
+                    Err(e) => error!("Soak test failed, and the state could not be dumped: {}", e),
+                }
+                return Err(violation);
+            }
+            self.clock.sleep(self.check_interval);
+        }
+    }
+
+    // The violations of the current snapshot, which joins the history
+    pub fn check(&mut self) -> Vec<Violation> {
+        let Some(snapshot) = self.query() else {
+            return vec![Violation::CoordinatorUnresponsive];
+        };
+        let violations = self.checker.check(&snapshot, self.clock.now());
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+        violations
+    }
+
+    // Writes the violations and the history, and returns the path written
+    pub fn dump(&self, violations: Vec<Violation>) -> Result<String, String> {
+        let time = Local::now();
+        let dump = SoakDump {
+            elevator: self.local_id.clone(),
+            time: time.to_rfc3339(),
+            violations,
+            snapshots: self.history.iter().cloned().collect(),
+        };
+        let directory = Path::new(&self.dump_directory);
+        fs::create_dir_all(directory).map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+        let path = directory.join(format!("soak-{}-{}.json", self.local_id, time.format("%Y%m%d-%H%M%S")));
+        let json = serde_json::to_string_pretty(&dump).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path.display().to_string())
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
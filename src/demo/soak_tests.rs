@@ -0,0 +1,179 @@
+/*
+ * Unit tests for the soak test, its invariants and its faults
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_soak_call_deadline
+ *  - test_soak_lamps_and_door
+ *  - test_soak_duplicate_service
+ *  - test_soak_chaos_faults
+ *  - test_soak_dump_on_violation
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod soak_tests {
+    use crossbeam_channel::{bounded, unbounded};
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+    use std::sync::Arc;
+    use std::thread::spawn;
+    use std::time::{Duration, Instant};
+    use crate::config::SoakConfig;
+    use crate::demo::chaos::Fault;
+    use crate::demo::invariants::{InvariantChecker, Violation};
+    use crate::demo::{ChaosController, SoakMonitor};
+    use crate::shared::{Behaviour, ControlCommand, DoorState, ElevatorData, ElevatorState, NetworkStatistics, RealClock, Snapshot, Uptime};
+
+    const N_FLOORS: u8 = 4;
+
+    fn snapshot(elevators: &[&str]) -> Snapshot {
+        let mut elevator_data = ElevatorData::new(N_FLOORS);
+        for elevator in elevators {
+            elevator_data.states.insert(elevator.to_string(), ElevatorState::new(N_FLOORS));
+        }
+        Snapshot {
+            id: elevators[0].to_string(),
+            peers: elevators.iter().map(|elevator| elevator.to_string()).collect(),
+            confirmed_hall_requests: elevator_data.hall_requests.clone(),
+            elevator_data,
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    #[test]
+    fn test_soak_call_deadline() {
+        // Arrange
+        let mut checker = InvariantChecker::new(Some(Duration::from_secs(30)), Duration::ZERO);
+        let start = Instant::now();
+        let mut waiting = snapshot(&["a"]);
+        waiting.elevator_data.hall_requests[2][HALL_UP as usize] = true;
+        waiting.confirmed_hall_requests[2][HALL_UP as usize] = true;
+        waiting.elevator_data.states.get_mut("a").unwrap().cab_requests[1] = true;
+
+        // Act
+        let first = checker.check(&waiting, start);
+        let within = checker.check(&waiting, start + Duration::from_secs(30));
+        let late = checker.check(&waiting, start + Duration::from_secs(31));
+        let served = checker.check(&snapshot(&["a"]), start + Duration::from_secs(32));
+        let again = checker.check(&waiting, start + Duration::from_secs(33));
+
+        // Assert
+        // A call served and placed again is timed afresh
+        assert!(first.is_empty() && within.is_empty());
+        assert_eq!(late.len(), 2);
+        assert!(late.contains(&Violation::CallTooOld { elevator: None, floor: 2, call: HALL_UP, waited: 31 }));
+        assert!(late.contains(&Violation::CallTooOld { elevator: Some("a".to_string()), floor: 1, call: 2, waited: 31 }));
+        assert!(served.is_empty() && again.is_empty());
+    }
+
+    #[test]
+    fn test_soak_lamps_and_door() {
+        // Arrange
+        let mut checker = InvariantChecker::new(None, Duration::ZERO);
+        let mut lit = snapshot(&["a"]);
+        lit.confirmed_hall_requests[3][HALL_DOWN as usize] = true;
+        let mut moving = snapshot(&["a"]);
+        let state = moving.elevator_data.states.get_mut("a").unwrap();
+        (state.behaviour, state.door, state.floor) = (Behaviour::Moving, DoorState::Open, 2);
+
+        // Act
+        let lit_violations = checker.check(&lit, Instant::now());
+        let moving_violations = checker.check(&moving, Instant::now());
+
+        // Assert
+        assert_eq!(lit_violations, vec![Violation::LampWithoutCall { floor: 3, call: HALL_DOWN }]);
+        assert_eq!(moving_violations, vec![Violation::DoorOpenWhileMoving { elevator: "a".to_string(), floor: 2 }]);
+        assert_eq!(lit_violations[0].to_string(), "The hall call down at floor 3 is lit without being requested");
+    }
+
+    #[test]
+    fn test_soak_duplicate_service() {
+        // Arrange
+        let mut checker = InvariantChecker::new(None, Duration::from_secs(2));
+        let start = Instant::now();
+        let mut duplicate = snapshot(&["a", "b", "c"]);
+        duplicate.elevator_data.hall_requests[2][HALL_UP as usize] = true;
+        duplicate.confirmed_hall_requests[2][HALL_UP as usize] = true;
+        for elevator in ["a", "b", "c"] {
+            duplicate.elevator_data.states.get_mut(elevator).unwrap().stop_plan = vec![2];
+        }
+        // The cab call of c is its own reason to stop
+        duplicate.elevator_data.states.get_mut("c").unwrap().cab_requests[2] = true;
+        let mut both_directions = duplicate.clone();
+        both_directions.elevator_data.hall_requests[2][HALL_DOWN as usize] = true;
+        both_directions.confirmed_hall_requests[2][HALL_DOWN as usize] = true;
+
+        // Act
+        let reassigning = checker.check(&duplicate, start);
+        let within_grace = checker.check(&duplicate, start + Duration::from_secs(2));
+        let after_grace = checker.check(&duplicate, start + Duration::from_secs(3));
+        let split = checker.check(&both_directions, start + Duration::from_secs(4));
+
+        // Assert
+        // With a call in each direction, each car may serve one
+        assert!(reassigning.is_empty() && within_grace.is_empty());
+        assert_eq!(after_grace, vec![Violation::DuplicateService { floor: 2, elevators: vec!["a".to_string(), "b".to_string()] }]);
+        assert!(split.is_empty());
+    }
+
+    #[test]
+    fn test_soak_chaos_faults() {
+        // Arrange
+        let soak_config = SoakConfig { fault_interval: 60, fault_time: 5, seed: 4145, ..Default::default() };
+        let (command_tx, command_rx) = unbounded();
+        let (peer_tx_enable_tx, peer_tx_enable_rx) = unbounded();
+        let chaos_controller = ChaosController::new(&soak_config, Arc::new(RealClock), command_tx, peer_tx_enable_tx);
+
+        // Act
+        let halted = chaos_controller.inject(Fault::Halt, true);
+        let resumed = chaos_controller.inject(Fault::Halt, false);
+        let isolated = chaos_controller.inject(Fault::Isolate, true);
+        let rejoined = chaos_controller.inject(Fault::Isolate, false);
+
+        // Assert
+        assert!(halted && resumed && isolated && rejoined);
+        assert_eq!(command_rx.try_iter().collect::<Vec<ControlCommand>>(), vec![ControlCommand::Halt(true), ControlCommand::Halt(false)]);
+        assert_eq!(peer_tx_enable_rx.try_iter().collect::<Vec<bool>>(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_soak_dump_on_violation() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("soak_dump_{}", std::process::id()));
+        let soak_config = SoakConfig { check_interval: 10, dump_directory: directory.to_str().unwrap().to_string(), ..Default::default() };
+        let (query_tx, query_rx) = unbounded::<crossbeam_channel::Sender<Snapshot>>();
+        let (_traffic_done_tx, traffic_done_rx) = bounded(1);
+        let soak_monitor = SoakMonitor::new(&soak_config, "a".to_string(), Arc::new(RealClock), query_tx);
+        let coordinator = spawn(move || {
+            for (replies, reply_tx) in query_rx.iter().enumerate() {
+                let mut reply = snapshot(&["a"]);
+                if replies == 3 {
+                    reply.confirmed_hall_requests[1][HALL_UP as usize] = true;
+                }
+                let _ = reply_tx.send(reply);
+            }
+        });
+
+        // Act
+        let result = soak_monitor.run(traffic_done_rx);
+        let dumps = std::fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+        let dump: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&dumps[0]).unwrap()).unwrap();
+        let _ = std::fs::remove_dir_all(&directory);
+        drop(coordinator);
+
+        // Assert
+        // The dump holds the snapshot that violated the invariant and those before it
+        assert_eq!(result, Err(Violation::LampWithoutCall { floor: 1, call: HALL_UP }));
+        assert_eq!(dumps.len(), 1);
+        assert_eq!(dump["elevator"], "a");
+        assert_eq!(dump["violations"][0]["lampWithoutCall"]["floor"], 1);
+        assert_eq!(dump["snapshots"].as_array().unwrap().len(), 4);
+    }
+}
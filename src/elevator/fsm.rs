@@ -8,18 +8,49 @@
  * - `hw_motor_direction_tx`:   Sends motor direction commands (up, down, stop).
  * - `hw_floor_sensor_rx`:      Receives current floor updates from the elevator sensor.
  * - `hw_door_light_tx`:        Controls the door's open/close light indicator.
+ * - `hw_button_light_tx`:      Flashes the cab light of refused cab calls.
  * - `hw_obstruction_rx`:       Receives obstruction detection signals (e.g., if something blocks the door).
  * - `hw_stop_button_rx`:       Receives stop button press signals.
+ * - `hw_panel_signal_tx`:      Sends the signal of the state to the driver, which blinks the panel lamps in its pattern.
  * - `fsm_cab_request_rx`:      Receives cabin request inputs (e.g., buttons pressed inside the elevator).
  * - `fsm_hall_requests_rx`:    Receives hall request inputs (e.g., buttons pressed on each floor).
+ * - `fsm_parking_floor_rx`:    Receives the floor to park at when idle, set by the traffic mode.
+ * - `fsm_operating_mode_rx`:   Receives the operating mode (normal, fire or independent service). The mode is kept in `state`.
+ * - `fsm_door_command_rx`:     Receives door open/close commands, obeyed in fire service phase 2.
+ * - `fsm_alarm_clear_rx`:      Receives the manual clearance of the obstruction alarm.
+ * - `fsm_halt_rx`:             Receives halt and resume commands. A halted car finishes its current stop and holds there.
+ * - `fsm_inspect_rx`:          Receives inspection queries, answered with the internal view of the FSM.
+ * - `fsm_timings_rx`:          Receives the door and motor timings changed while running, taken from the next timer on.
+ * - `fsm_blocked_floors_rx`:    Receives the floors this elevator may not serve, when the zoning changes.
+ * - `fsm_locked_floors_rx`:     Receives the floors locked out, when the lockout changes. Pending cab calls to them are dropped.
  * - `fsm_order_complete_tx`:   Sends notifications when a request is completed.
- * - `fsm_state_tx`:            Broadcasts the current state of the elevator (e.g., current floor, direction).
+ * - `fsm_state_tx`:            Broadcasts the current state of the elevator (e.g., current floor, direction), with the planned stops.
+ * - `fsm_terminate_rx`:        Receives the request to terminate, acknowledged once the car is stopped and the statistics saved.
  * - `hall_requests`:           Stores the state of hall requests (up/down) for each floor.
  * - `state`:                   Maintains the current state of the elevator (e.g., floor, direction).
  * - `n_floors`:                The total number of floors serviced by the elevator.
+ * - `floor_labels`:            The labels the floors are logged by.
  * - `obstruction`:             Indicates if there is an obstruction detected by the elevator.
  * - `door_open_time`:          Configurable time for how long the door remains open.
  * - `door_timer`:              Timer used to track door open duration.
+ * - `blocked_floors`:          Floors this elevator may not serve, restricted or outside its zone. Cab calls to them are refused.
+ * - `parking_floor`:           Floor the elevator returns to when it has no orders, if any.
+ * - `levelling_time`:          Time the car takes to level out at a floor after stopping, before the door opens.
+ * - `door_pre_opening`:        Whether the door is opened as the car reaches a stop it was decelerating for.
+ * - `pre_opening`:             Set while the car decelerates for the stop at the next floor.
+ * - `levelled_timer`:          When the stopped car has levelled out and the door opens, while waiting for it.
+ * - `statistics`:              Distance travelled, door cycles and orders served. Saved with each report.
+ * - `statistics_interval`:     Time between statistics reports. Reporting is disabled if zero.
+ * - `statistics_report`:       JSON file the latest report is written to.
+ * - `statistics_timer`:        When the next statistics report is due.
+ * - `clock`:                   The time the timers are read from, real or simulated.
+ * - `storage`:                 Where the cab orders and statistics are saved to survive restarts.
+ * - `obstruction_timeouts`:    Obstruction timeouts since the door last closed.
+ * - `obstruction_alarm_threshold`: Obstruction timeouts in a row raising the obstruction alarm. Disabled if zero.
+ * - `hall_clearing`:           Whether a stop clears the hall call in the direction of travel, or both hall calls at the floor.
+ * - `second_door_cycle`:       Set when passengers board against the direction of travel. The door stays open for another cycle.
+ * - `panel_signal`:            The signal last sent to the driver.
+ * - `motion`:                  The travel-time model, giving the arrival times at the planned stops and the motor timeout.
  *
  */
 
@@ -27,9 +58,12 @@
 /*              libraries              */
 /***************************************/
 use driver_rust::elevio::elev::{HALL_UP, HALL_DOWN, CAB};
+use std::sync::Arc;
+use std::thread::spawn;
 use std::time::{Duration, Instant};
 use crossbeam_channel as cbc;
-use log::{info, error};
+use chrono::Utc;
+use log::{info, warn, error};
 
 
 /***************************************/
@@ -38,8 +72,16 @@ use log::{info, error};
 use crate::config::ElevatorConfig;
 use crate::shared::Behaviour::{DoorOpen, Idle, Moving, Error};
 use crate::shared::Direction::{Down, Stop, Up};
-use crate::shared::{Direction, ElevatorState};
-use crate::elevator::cab_orders::{load_cab_orders, save_cab_orders};
+use crate::shared::{attached, event_loop, Clock, Direction, DoorState, ElevatorState, EventLoop, FsmTimings, FsmView, FloorLabels, HallClearing, MotionModel, ModuleError, OperatingMode, RealClock, Terminate};
+use crate::elevator::panel::PanelSignal;
+use crate::elevator::statistics::{write_statistics_report, Statistics};
+use crate::storage::{Record, Storage};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const REFUSED_CAB_LIGHT_FLASHES: u32 = 3;
+const REFUSED_CAB_LIGHT_PERIOD: Duration = Duration::from_millis(200);
 
 
 /***************************************/
@@ -51,19 +93,31 @@ pub struct ElevatorFSM {
     hw_floor_sensor_rx: cbc::Receiver<u8>,
     hw_floor_indicator_tx: cbc::Sender<u8>,
     hw_door_light_tx: cbc::Sender<bool>,
+    hw_button_light_tx: cbc::Sender<(u8, u8, bool)>,
     hw_obstruction_rx: cbc::Receiver<bool>,
+    hw_panel_signal_tx: cbc::Sender<PanelSignal>,
 
     // Coordinator channels
     fsm_hall_requests_rx: cbc::Receiver<Vec<Vec<bool>>>,
     fsm_cab_request_rx: cbc::Receiver<u8>,
+    fsm_parking_floor_rx: cbc::Receiver<Option<u8>>,
+    fsm_operating_mode_rx: cbc::Receiver<OperatingMode>,
+    fsm_door_command_rx: cbc::Receiver<bool>,
+    fsm_alarm_clear_rx: cbc::Receiver<()>,
+    fsm_halt_rx: cbc::Receiver<bool>,
+    fsm_inspect_rx: cbc::Receiver<cbc::Sender<FsmView>>,
+    fsm_timings_rx: cbc::Receiver<FsmTimings>,
+    fsm_blocked_floors_rx: cbc::Receiver<Vec<u8>>,
+    fsm_locked_floors_rx: cbc::Receiver<Vec<u8>>,
     fsm_order_complete_tx: cbc::Sender<(u8, u8)>,
     fsm_state_tx: cbc::Sender<ElevatorState>,
 
     // Private fields
-    fsm_terminate_rx: cbc::Receiver<()>,
+    fsm_terminate_rx: cbc::Receiver<Terminate>,
     hall_requests: Vec<Vec<bool>>,
     state: ElevatorState,
     n_floors: u8,
+    floor_labels: FloorLabels,
     obstruction: bool,
     door_open_time: u64,
     motor_timeout: u64,
@@ -71,177 +125,210 @@ pub struct ElevatorFSM {
     door_timer: Instant,
     obstruction_timer: Instant,
     motor_timer: Instant,
+    blocked_floors: Vec<u8>,
+    parking_floor: Option<u8>,
+    levelling_time: u64,
+    door_pre_opening: bool,
+    pre_opening: bool,
+    levelled_timer: Option<Instant>,
+    statistics: Statistics,
+    statistics_interval: u64,
+    statistics_report: String,
+    statistics_timer: Instant,
+    clock: Arc<dyn Clock>,
+    storage: Arc<dyn Storage>,
+    obstruction_timeouts: u32,
+    obstruction_alarm_threshold: u32,
+    hall_clearing: HallClearing,
+    second_door_cycle: bool,
+    panel_signal: PanelSignal,
+    motion: MotionModel,
 }
 
 impl ElevatorFSM {
-    pub fn new(
-        fsm_config: &ElevatorConfig,
-
-        hw_motor_direction_tx: cbc::Sender<u8>,
-        hw_floor_sensor_rx: cbc::Receiver<u8>,
-        hw_floor_indicator_tx: cbc::Sender<u8>,
-        hw_door_light_tx: cbc::Sender<bool>,
-        hw_obstruction_rx: cbc::Receiver<bool>,
-
-        fsm_hall_requests_rx: cbc::Receiver<Vec<Vec<bool>>>,
-        fsm_cab_request_rx: cbc::Receiver<u8>,
-        fsm_order_complete_tx: cbc::Sender<(u8, u8)>,
-        fsm_state_tx: cbc::Sender<ElevatorState>,
-        fsm_terminate_rx: cbc::Receiver<()>,
-    ) -> ElevatorFSM {
-        ElevatorFSM {
-            hw_motor_direction_tx,
-            hw_floor_sensor_rx,
-            hw_floor_indicator_tx,
-            hw_door_light_tx,
-            hw_obstruction_rx,
-
-            fsm_hall_requests_rx,
-            fsm_cab_request_rx,
-            fsm_order_complete_tx,
-            fsm_state_tx,
-            fsm_terminate_rx,
-            
-            hall_requests: vec![vec![false; 2]; fsm_config.n_floors as usize],
-            state: ElevatorState::new(fsm_config.n_floors),
-            n_floors: fsm_config.n_floors,
-            obstruction: false,
-            door_open_time: fsm_config.door_open_time,
-            door_timeout: fsm_config.door_timeout,
-            motor_timeout: fsm_config.motor_timeout,
-            obstruction_timer: Instant::now(),
-            door_timer: Instant::now(),
-            motor_timer: Instant::now(),
-        }
-    }
-
-    pub fn run(mut self) {
+    // Runs the FSM until terminated, or until a channel is disconnected
+    pub fn run(mut self) -> Result<(), ModuleError> {
         // Find the initial floor
         let _ = self.hw_motor_direction_tx.send(Direction::Down.to_u8());
         self.load_saved_cab_calls();
+        self.statistics = match self.storage.load_statistics() {
+            Ok(statistics) => statistics.unwrap_or_default(),
+            Err(e) => {
+                error!("Failed to load the statistics: {}", e);
+                Statistics::default()
+            }
+        };
 
         // Main loop
-        loop {
-            cbc::select! {
-                recv(self.hw_floor_sensor_rx) -> new_floor => {
-                    match new_floor {
-                        Ok(floor) => self.handle_floor_hit(floor),
-                        Err(error) => {
-                            error!("ERROR - hw_floor_sensor_rx: {}", error);
-                            std::process::exit(1);
-                        }
+        event_loop! {
+            self;
+            recv(self.hw_floor_sensor_rx) -> floor => {
+                self.handle_floor_hit(floor)
+            }
+            recv(self.fsm_hall_requests_rx) -> hall_requests => {
+                // Cars out of normal service do not take hall calls
+                if self.state.mode == OperatingMode::Normal {
+                    self.hall_requests = hall_requests;
+
+                    // The coordinator is told when the new calls change the stops planned
+                    if plan_stops(&self.state, &self.hall_requests, self.n_floors, self.hall_clearing) != self.state.stop_plan {
+                        self.publish_state();
                     }
                 }
-                recv(self.fsm_hall_requests_rx) -> hall_requests => {
-                    match hall_requests {
-                        Ok(hall_requests) => {
-                            self.hall_requests = hall_requests;
-                        }
-                        Err(error) => {
-                            error!("ERROR - fsm_hall_requests_rx: {}", error);
-                            std::process::exit(1);
-                        }
-                    }
+            }
+            recv(self.fsm_cab_request_rx) -> new_cab_request => {
+                if self.blocked_floors.contains(&new_cab_request) {
+                    info!("Cab request to floor {} not served by this elevator rejected", self.floor_labels.label(new_cab_request));
+                    self.flash_refused_cab_light(new_cab_request);
+                } else if let OperatingMode::FireRecall(_) = self.state.mode {
+                    info!("Cab request to floor {} rejected during fire recall", self.floor_labels.label(new_cab_request));
+                } else {
+                    self.state.cab_requests[new_cab_request as usize] = true;
+                    self.save(&[Record::CabOrders(self.state.cab_requests.clone())]);
+                    self.publish_state();
                 }
-                recv(self.fsm_cab_request_rx) -> new_cab_request => {
-                    match new_cab_request {
-                        Ok(new_cab_request) => {
-                            self.state.cab_requests[new_cab_request as usize] = true;
-                            save_cab_orders(self.state.cab_requests.clone());
-                            let _ = self.fsm_state_tx.send(self.state.clone());
-                        }
-                        Err(error) => {
-                            error!("ERROR - fsm_cab_request_rx: {}", error);
-                            std::process::exit(1);
-                        }
-                    }
+            }
+            recv(self.fsm_parking_floor_rx) -> parking_floor => {
+                self.parking_floor = parking_floor;
+            }
+            recv(self.fsm_operating_mode_rx) -> operating_mode => {
+                self.set_operating_mode(operating_mode)
+            }
+            recv(self.fsm_door_command_rx) -> open => {
+                self.handle_door_command(open)
+            }
+            recv(self.fsm_alarm_clear_rx) -> () => {
+                self.clear_obstruction_alarm()
+            }
+            recv(self.fsm_halt_rx) -> halted => {
+                self.set_halted(halted)
+            }
+            recv(self.fsm_inspect_rx) -> reply_tx => {
+                let _ = reply_tx.send(self.view());
+            }
+            recv(self.fsm_timings_rx) -> timings => {
+                self.set_timings(timings)
+            }
+            recv(self.fsm_blocked_floors_rx) -> blocked_floors => {
+                info!("Floors not served by this elevator changed to {:?}", blocked_floors);
+                self.blocked_floors = blocked_floors;
+            }
+            recv(self.fsm_locked_floors_rx) -> locked_floors => {
+                self.drop_cab_requests(&locked_floors)
+            }
+            recv(self.hw_obstruction_rx) -> value => {
+                self.obstruction = value;
+                if !value {
+                    self.reset_obstruction_timer();
                 }
-                recv(self.hw_obstruction_rx) -> obstruction => {
-                    match obstruction {
-                        Ok(value) => {
-                            self.obstruction = value;
-                            if !value {
-                                self.reset_obstruction_timer();
-                            }
-                        }
-                        Err(error) => {
-                            error!("ERROR - hw_obstruction_rx: {}", error);
-                            std::process::exit(1);
-                        }
-                    }
+
+                // An open door is reported blocked while obstructed
+                if self.state.door != DoorState::Closed {
+                    self.state.door = if value { DoorState::Blocked } else { DoorState::Open };
+                    self.publish_state();
+                }
+            }
+            idle(Duration::from_millis(100)) => {
+                self.handle_timers()
+            }
+        }
+    }
+
+    // Acts on the timers that have run out, and starts the car on orders found while idle
+    fn handle_timers(&mut self) {
+        if self.statistics_interval > 0 && self.statistics_timer <= self.clock.now() {
+            self.report_statistics();
+        }
+
+        match self.state.behaviour {
+            Idle => {
+                if self.complete_orders() {
+                    self.open_door();
                 }
-                recv(self.fsm_terminate_rx) -> _ => {
-                    break;
+
+                // Recalled cars wait at the recall floor with the door open
+                if self.state.mode == OperatingMode::FireRecall(self.state.floor) {
+                    self.open_door();
                 }
-                default(Duration::from_millis(100)) => {
-                    match self.state.behaviour {
-                        Idle => {
-                            if self.complete_orders() {
-                                self.open_door();
-                            }
-
-                            self.state.direction = self.choose_direction();
-                            if self.state.direction != Stop && self.state.behaviour != DoorOpen {
-                                self.state.behaviour = Moving;
-                                let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
-                                self.reset_motor_timer();
-                            }
-                        }
-                        DoorOpen => {
-                            if self.obstruction {
-                                self.reset_door_timer();
-
-                                if self.obstruction_timer <= Instant::now() {
-                                    info!("Elevator Error: Door timeout. Re-assigning hall requests.");
-                                    self.state.behaviour = Error;
-                                    let _ = self.fsm_state_tx.send(self.state.clone());
-                                }
-
-                            } else if self.door_timer <= Instant::now() {
-                                self.close_door();
-                                
-                                self.state.direction = self.choose_direction();
-                                if self.complete_orders() {
-                                    self.open_door();
-                                }
-
-                                else {
-                                    let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
-    
-                                    if self.state.direction == Stop {
-                                        self.state.behaviour = Idle;
-                                    }
-                                    
-                                    else {
-                                        self.state.behaviour = Moving;
-                                        self.reset_motor_timer();
-                                    }
-                                }
-                                
-                                let _ = self.fsm_state_tx.send(self.state.clone());
-                            } 
+
+                self.state.direction = self.choose_direction();
+                if self.state.direction != Stop && self.state.behaviour != DoorOpen {
+                    self.state.behaviour = Moving;
+                    let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
+                    self.reset_motor_timer();
+                }
+            }
+            DoorOpen => {
+                if let Some(levelled_timer) = self.levelled_timer {
+                    // The door opens once the car has levelled out
+                    if levelled_timer <= self.clock.now() {
+                        self.levelled_timer = None;
+                        self.force_open_door();
+                    }
+                } else if self.holds_door_open() {
+                    self.reset_door_timer();
+                } else if self.obstruction {
+                    self.reset_door_timer();
+
+                    if self.obstruction_timer <= self.clock.now() {
+                        info!("Elevator Error: Door timeout. Re-assigning hall requests.");
+                        self.state.behaviour = Error;
+
+                        // A door blocked time after time is not left to recover on its own
+                        self.obstruction_timeouts += 1;
+                        if self.obstruction_alarm_threshold > 0 && self.obstruction_timeouts >= self.obstruction_alarm_threshold {
+                            warn!("Obstruction alarm: the door timed out {} times in a row. Clear the alarm to return to service.", self.obstruction_timeouts);
+                            self.state.obstruction_alarm = true;
                         }
-                        Moving => {
-                            if self.motor_timer <= Instant::now() && self.state.behaviour != Error {
-                                
-                                // Disconnecting elevator from network
-                                info!("Motor Loss elevator!");
-                                self.state.behaviour = Error;
-                                let _ = self.fsm_state_tx.send(self.state.clone());
-
-                                //Trying to start up motor
-                                let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
-                            }
+                        self.publish_state();
+                    }
+
+                } else if self.second_door_cycle && self.door_timer <= self.clock.now() {
+                    self.second_door_cycle = false;
+                    self.open_door();
+                } else if self.door_timer <= self.clock.now() {
+                    self.close_door();
+                    self.obstruction_timeouts = 0;
+                    
+                    self.state.direction = self.choose_direction();
+                    if self.complete_orders() {
+                        self.open_door();
+                    }
+
+                    else {
+                        let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
+
+                        if self.state.direction == Stop {
+                            self.state.behaviour = Idle;
                         }
-                        Error => {
-                            if self.obstruction_timer > Instant::now() {
-                                self.open_door();
-                                info!("Door closing!");
-                            } 
+                        
+                        else {
+                            self.state.behaviour = Moving;
+                            self.reset_motor_timer();
                         }
                     }
+                    
+                    self.publish_state();
+                } 
+            }
+            Moving => {
+                if self.motor_timer <= self.clock.now() && self.state.behaviour != Error {
+                    
+                    // Disconnecting elevator from network
+                    info!("Motor Loss elevator!");
+                    self.state.behaviour = Error;
+                    self.publish_state();
+
+                    //Trying to start up motor
+                    let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
                 }
             }
+            Error => {
+                if self.obstruction_timer > self.clock.now() && !self.state.obstruction_alarm {
+                    self.open_door();
+                    info!("Door closing!");
+                } 
+            }
         }
     }
 
@@ -250,13 +337,22 @@ impl ElevatorFSM {
             info!("Motor power restored. Elevator back in normal state.");
         }
 
+        // The floor found at start-up is not travelled to
+        if self.state.behaviour != Idle {
+            self.statistics.distance_travelled += floor.abs_diff(self.state.floor) as u64;
+        }
+
         self.state.floor = floor;
         self.hw_floor_indicator_tx.send(floor).unwrap();
 
+        // A car that decelerated for this floor may open the door right away, unless the door is obstructed
+        let pre_opened = self.pre_opening && !self.obstruction;
+        self.pre_opening = false;
+
         // If orders at this floor, complete them, stop and open the door
         if self.complete_orders() {
             let _ = self.hw_motor_direction_tx.send(Direction::Stop.to_u8());
-            self.open_door();
+            self.open_door_on_arrival(pre_opened);
         }
 
         // Find next direction, and check if there are any orders
@@ -265,7 +361,7 @@ impl ElevatorFSM {
 
             if self.complete_orders() {
                 let _ = self.hw_motor_direction_tx.send(Direction::Stop.to_u8());
-                self.open_door();
+                self.open_door_on_arrival(pre_opened);
             }
 
             else if self.state.direction == Stop {
@@ -277,14 +373,33 @@ impl ElevatorFSM {
                 self.state.behaviour = Moving;
                 let _ = self.hw_motor_direction_tx.send(self.state.direction.to_u8());
                 self.reset_motor_timer();
+
+                // Decelerating for a stop at the next floor
+                self.pre_opening = self.door_pre_opening && !self.obstruction && self.has_stop_at_next_floor();
             }
         }
 
         // Send new state to coordinator
-        let _ = self.fsm_state_tx.send(self.state.clone());
+        self.publish_state();
     }
 
     fn choose_direction(&self) -> Direction {
+        // Halted cars hold at the floor they stopped at
+        if self.state.halted {
+            return Stop;
+        }
+
+        // Recalled cars head straight for the recall floor, reversing if necessary
+        if let OperatingMode::FireRecall(recall_floor) = self.state.mode {
+            if recall_floor > self.state.floor {
+                return Up;
+            }
+            if recall_floor < self.state.floor {
+                return Down;
+            }
+            return Stop;
+        }
+
         let current_direction = self.state.direction.clone();
         // Continue in current direction of travel if there are any further orders in that direction
         if self.has_orders_in_direction(current_direction.clone()) {
@@ -309,15 +424,29 @@ impl ElevatorFSM {
             }
         }
 
+        // If there are no orders, return to the parking floor
+        if let Some(parking_floor) = self.parking_floor {
+            if parking_floor > self.state.floor && current_direction != Down {
+                return Up;
+            }
+            if parking_floor < self.state.floor && current_direction != Up {
+                return Down;
+            }
+        }
+
         // If there are no orders, stop.
         Stop
     }
 
     fn has_orders_in_direction(&self, direction: Direction) -> bool {
+        self.has_orders_beyond(self.state.floor, direction)
+    }
+
+    fn has_orders_beyond(&self, floor: u8, direction: Direction) -> bool {
         match direction {
-            // Check all orders above the current floor
+            // Check all orders above the floor
             Up => {
-                for f in (self.state.floor + 1)..self.n_floors {
+                for f in (floor + 1)..self.n_floors {
                     if self.state.cab_requests[f as usize]
                         || self.hall_requests[f as usize][HALL_UP as usize]
                         || self.hall_requests[f as usize][HALL_DOWN as usize]
@@ -327,9 +456,9 @@ impl ElevatorFSM {
                 }
             }
 
-            // Check all orders below the current floor
+            // Check all orders below the floor
             Down => {
-                for f in (0..self.state.floor).rev() {
+                for f in (0..floor).rev() {
                     if self.state.cab_requests[f as usize]
                         || self.hall_requests[f as usize][HALL_UP as usize]
                         || self.hall_requests[f as usize][HALL_DOWN as usize]
@@ -348,16 +477,55 @@ impl ElevatorFSM {
         false
     }
 
+    // Returns true if the car will stop at the next floor in its direction of travel
+    fn has_stop_at_next_floor(&self) -> bool {
+        let next_floor = match self.state.direction {
+            Up if self.state.floor + 1 < self.n_floors => self.state.floor + 1,
+            Down if self.state.floor > 0 => self.state.floor - 1,
+            _ => return false,
+        };
+        let is_end_floor = next_floor == 0 || next_floor == self.n_floors - 1;
+        let hall_up = self.hall_requests[next_floor as usize][HALL_UP as usize];
+        let hall_down = self.hall_requests[next_floor as usize][HALL_DOWN as usize];
+        let hall_in_direction = match self.state.direction {
+            Up => hall_up,
+            _ => hall_down,
+        };
+
+        // Calls against the direction of travel are only taken when there are no orders beyond the floor
+        self.state.cab_requests[next_floor as usize]
+            || hall_in_direction
+            || ((hall_up || hall_down) && (is_end_floor || !self.has_orders_beyond(next_floor, self.state.direction.clone())))
+    }
+
+    // The internal view of the FSM, with the time left on the timers that are running
+    fn view(&self) -> FsmView {
+        let now = self.clock.now();
+        let remaining = |timer: Instant| timer.saturating_duration_since(now).as_millis() as u64;
+        let door_open = self.state.behaviour == DoorOpen && self.levelled_timer.is_none();
+
+        FsmView {
+            state: self.state.clone(),
+            hall_requests: self.hall_requests.clone(),
+            obstruction: self.obstruction,
+            parking_floor: self.parking_floor,
+            door_timer: door_open.then(|| remaining(self.door_timer)),
+            obstruction_timer: (door_open && self.obstruction).then(|| remaining(self.obstruction_timer)),
+            motor_timer: (self.state.behaviour == Moving).then(|| remaining(self.motor_timer)),
+            levelling_timer: self.levelled_timer.map(remaining),
+        }
+    }
+
     fn reset_motor_timer(&mut self) {
-        self.motor_timer = Instant::now() + Duration::from_millis(self.motor_timeout);
+        self.motor_timer = self.clock.now() + self.motion.motor_timeout(Duration::from_millis(self.motor_timeout));
     }
 
     fn reset_door_timer(&mut self) {
-        self.door_timer = Instant::now() + Duration::from_millis(self.door_open_time);
+        self.door_timer = self.clock.now() + Duration::from_millis(self.door_open_time);
     }
 
     fn reset_obstruction_timer(&mut self) {
-        self.obstruction_timer = Instant::now() + Duration::from_millis(self.door_timeout);
+        self.obstruction_timer = self.clock.now() + Duration::from_millis(self.door_timeout);
     }
 
     // Returns true if order has been completed
@@ -381,20 +549,24 @@ impl ElevatorFSM {
         // Remove cab orders at current floor.
         if cab_at_current_floor {
             orders_completed = true;
+            self.statistics.orders_served += 1;
             
             // Update the state and send it to the coordinator
             self.state.cab_requests[current_floor as usize] = false;
+            self.state.cab_completion_times.resize(self.n_floors as usize, 0);
+            self.state.cab_completion_times[current_floor as usize] = Utc::now().timestamp().max(0) as u64;
             self.fsm_order_complete_tx
             .send((current_floor, CAB))
             .unwrap();
 
-            //Saving to cab order change to file
-            save_cab_orders(self.state.cab_requests.clone());
+            // The served call and the counter are saved together
+            self.save(&[Record::CabOrders(self.state.cab_requests.clone()), Record::Statistics(self.statistics.clone())]);
         }
 
         // Remove hall up orders if moving up, stopped or at bottom floor
         if hall_up_at_current_floor && (current_direction == Up || is_bottom_floor || current_behaviour == Idle) {
             orders_completed = true;
+            self.statistics.orders_served += 1;
 
             // Update the state and send it to the coordinator
             self.hall_requests[current_floor as usize][HALL_UP as usize] = false;
@@ -406,6 +578,7 @@ impl ElevatorFSM {
         // Remove hall down orders if moving down, stopped or at top floor
         if hall_down_at_current_floor && (current_direction == Down || is_top_floor || current_behaviour == Idle) {
             orders_completed = true;
+            self.statistics.orders_served += 1;
 
             // Update the state and send it to the coordinator
             self.hall_requests[current_floor as usize][HALL_DOWN as usize] = false;
@@ -414,37 +587,616 @@ impl ElevatorFSM {
                 .unwrap();
         }
 
+        // With all hall calls cleared at a stop, the call against the direction of travel is served too.
+        // Its passengers board during a second door cycle.
+        if orders_completed && self.hall_clearing == HallClearing::All {
+            for call in [HALL_UP, HALL_DOWN] {
+                if self.hall_requests[current_floor as usize][call as usize] {
+                    self.statistics.orders_served += 1;
+                    self.second_door_cycle = true;
+
+                    // Update the state and send it to the coordinator
+                    self.hall_requests[current_floor as usize][call as usize] = false;
+                    self.fsm_order_complete_tx
+                        .send((current_floor, call))
+                        .unwrap();
+                }
+            }
+        }
+
         orders_completed
     }
 
     fn open_door(&mut self) {
+        // In fire service phase 2 the car stops with the door closed, and the door is only opened on command
+        if self.state.mode == OperatingMode::FirePhase2 {
+            self.state.behaviour = Idle;
+            self.publish_state();
+            return;
+        }
+
+        self.force_open_door();
+    }
+
+    // Opens the door at a stop. Unless the car decelerated for the stop, it levels out first.
+    fn open_door_on_arrival(&mut self, pre_opened: bool) {
+        if pre_opened || self.levelling_time == 0 || self.state.mode == OperatingMode::FirePhase2 {
+            self.open_door();
+            return;
+        }
+
+        self.levelled_timer = Some(self.clock.now() + Duration::from_millis(self.levelling_time));
+        self.state.behaviour = DoorOpen;
+    }
+
+    fn force_open_door(&mut self) {
+        self.statistics.door_cycles += 1;
         let _ = self.hw_door_light_tx.send(true);
         self.reset_door_timer();
         self.reset_obstruction_timer();
         self.state.behaviour = DoorOpen;
+        self.state.door = if self.obstruction { DoorState::Blocked } else { DoorState::Open };
+        self.publish_state();
+    }
+
+    // Sends the state to the coordinator, with the stops the car has committed to
+    fn publish_state(&mut self) {
+        self.state.stop_plan = plan_stops(&self.state, &self.hall_requests, self.n_floors, self.hall_clearing);
+        self.state.arrival_times = self.arrival_times();
         let _ = self.fsm_state_tx.send(self.state.clone());
+
+        let panel_signal = PanelSignal::of(&self.state);
+        if panel_signal != self.panel_signal {
+            self.panel_signal = panel_signal;
+            let _ = self.hw_panel_signal_tx.send(panel_signal);
+        }
+    }
+
+    // The time until the car arrives at each planned stop, if there is a motion model
+    fn arrival_times(&self) -> Vec<u64> {
+        if !self.motion.is_set() {
+            return Vec::new();
+        }
+
+        // A car levelling out has the whole door open time ahead of it
+        let now = self.clock.now();
+        let door_time_left = match self.levelled_timer {
+            _ if self.state.behaviour != DoorOpen => Duration::ZERO,
+            Some(levelled_timer) => levelled_timer.saturating_duration_since(now) + Duration::from_millis(self.door_open_time),
+            None => self.door_timer.saturating_duration_since(now),
+        };
+        self.motion
+            .arrival_times(self.state.floor, door_time_left, &self.state.stop_plan)
+            .iter()
+            .map(|time| time.as_millis() as u64)
+            .collect()
     }
 
     fn close_door(&mut self) {
         let _ = self.hw_door_light_tx.send(false);
+        self.state.door = DoorState::Closed;
+    }
+
+    // Fire service keeps the door open at the recall floor, and until commanded closed in phase 2
+    fn holds_door_open(&self) -> bool {
+        self.state.mode == OperatingMode::FireRecall(self.state.floor) || self.state.mode == OperatingMode::FirePhase2
+    }
+
+    fn set_operating_mode(&mut self, operating_mode: OperatingMode) {
+        info!("Operating mode: {:?}", operating_mode);
+        self.state.mode = operating_mode;
+
+        // Hall calls are reassigned to the elevators in normal service
+        if operating_mode != OperatingMode::Normal {
+            self.hall_requests = vec![vec![false; 2]; self.n_floors as usize];
+        }
+
+        // Fire recall cancels the cab calls as well
+        if let OperatingMode::FireRecall(_) = operating_mode {
+            if self.state.cab_requests.contains(&true) {
+                self.state.cab_requests = vec![false; self.n_floors as usize];
+                self.save(&[Record::CabOrders(self.state.cab_requests.clone())]);
+            }
+        }
+
+        self.publish_state();
+    }
+
+    // The door is reopened, and closes as usual unless it is still obstructed
+    fn clear_obstruction_alarm(&mut self) {
+        if !self.state.obstruction_alarm {
+            info!("No obstruction alarm to clear");
+            return;
+        }
+
+        info!("Obstruction alarm cleared");
+        self.state.obstruction_alarm = false;
+        self.obstruction_timeouts = 0;
+        self.reset_obstruction_timer();
+        self.publish_state();
+    }
+
+    // Drops the pending cab calls to floors locked out
+    fn drop_cab_requests(&mut self, floors: &[u8]) {
+        let mut dropped = false;
+        for floor in floors {
+            if let Some(cab_request) = self.state.cab_requests.get_mut(*floor as usize) {
+                dropped |= *cab_request;
+                *cab_request = false;
+            }
+        }
+        if dropped {
+            self.save(&[Record::CabOrders(self.state.cab_requests.clone())]);
+            self.publish_state();
+        }
+    }
+
+    // A moving car is halted at the next floor, and a car at a stop once the door has closed
+    fn set_halted(&mut self, halted: bool) {
+        if self.state.halted == halted {
+            return;
+        }
+
+        info!("Elevator {}", if halted { "halted" } else { "resumed" });
+        self.state.halted = halted;
+        self.publish_state();
+    }
+
+    // The timers running keep their deadlines, and the next ones take the new timings
+    fn set_timings(&mut self, timings: FsmTimings) {
+        self.door_open_time = timings.door_open_time.unwrap_or(self.door_open_time);
+        self.door_timeout = timings.door_timeout.unwrap_or(self.door_timeout);
+        self.motor_timeout = timings.motor_timeout.unwrap_or(self.motor_timeout);
+        info!(
+            "Door open time {} ms, door timeout {} ms, motor timeout {} ms",
+            self.door_open_time, self.door_timeout, self.motor_timeout
+        );
+    }
+
+    fn handle_door_command(&mut self, open: bool) {
+        if self.state.mode != OperatingMode::FirePhase2 {
+            info!("Door command ignored outside fire service phase 2");
+            return;
+        }
+
+        if open && self.state.behaviour == Idle {
+            self.force_open_door();
+        } else if !open && self.state.behaviour == DoorOpen {
+            self.close_door();
+            self.state.behaviour = Idle;
+            self.publish_state();
+        }
+    }
+
+    // Logs the statistics, saves them to survive restarts and writes the report
+    fn report_statistics(&mut self) {
+        info!("Statistics: {:?}", self.statistics);
+        self.save(&[Record::Statistics(self.statistics.clone())]);
+        // The elevator keeps serving if the report cannot be written
+        if let Err(e) = write_statistics_report(&self.statistics, &self.statistics_report) {
+            error!("Failed to write the statistics report: {}", e);
+        }
+        self.statistics_timer = self.clock.now() + Duration::from_millis(self.statistics_interval);
+    }
+
+    // Saves the records together. The elevator keeps serving if they cannot be saved.
+    fn save(&self, records: &[Record]) {
+        if let Err(e) = self.storage.save(records) {
+            error!("Failed to save {:?}: {}", records, e);
+        }
+    }
+
+    // Flashes the cab light of a refused cab call, without holding up the FSM
+    fn flash_refused_cab_light(&self, floor: u8) {
+        let hw_button_light_tx = self.hw_button_light_tx.clone();
+        let clock = self.clock.clone();
+        spawn(move || {
+            for _ in 0..REFUSED_CAB_LIGHT_FLASHES {
+                let _ = hw_button_light_tx.send((floor, CAB, true));
+                clock.sleep(REFUSED_CAB_LIGHT_PERIOD);
+                let _ = hw_button_light_tx.send((floor, CAB, false));
+                clock.sleep(REFUSED_CAB_LIGHT_PERIOD);
+            }
+        });
     }
 
     // Handles saved cab calls 
     fn load_saved_cab_calls(&mut self) {
-        //Setting cab orders from file to elevatorData
-        self.state.cab_requests = load_cab_orders().cab_calls;
+        //Setting saved cab orders to elevatorData
+        self.state.cab_requests = match self.storage.load_cab_orders() {
+            Ok(cab_calls) => cab_calls.unwrap_or_else(|| vec![false; self.n_floors as usize]),
+            Err(e) => {
+                error!("Failed to load the cab orders: {}", e);
+                vec![false; self.n_floors as usize]
+            }
+        };
+        for floor in self.blocked_floors.iter() {
+            if let Some(cab_request) = self.state.cab_requests.get_mut(*floor as usize) {
+                *cab_request = false;
+            }
+        }
         
         // Updating coordinator with the init state
-        let _ = self.fsm_state_tx.send(self.state.clone());
+        self.publish_state();
+    }
+}
+
+impl EventLoop for ElevatorFSM {
+    fn terminate_rx(&self) -> &cbc::Receiver<Terminate> {
+        &self.fsm_terminate_rx
+    }
+
+    // Stops the car and saves the statistics, and acknowledges the termination if asked for it
+    fn terminate(&mut self, done: Option<Terminate>) -> Result<(), ModuleError> {
+        let _ = self.hw_motor_direction_tx.send(Stop.to_u8());
+        self.save(&[Record::Statistics(self.statistics.clone())]);
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+        Ok(())
     }
 }
 
+/***************************************/
+/*              Builder                */
+/***************************************/
+// Builds the FSM with every channel attached by name. It serves every floor and runs on the wall clock unless told
+// otherwise. The storage and every channel but `fsm_timings_rx`, `fsm_blocked_floors_rx`, `fsm_locked_floors_rx` and
+// `fsm_terminate_rx` must be attached.
+pub struct FsmBuilder {
+    fsm_config: ElevatorConfig,
+    blocked_floors: Vec<u8>,
+    floor_labels: FloorLabels,
+    clock: Arc<dyn Clock>,
+    storage: Option<Arc<dyn Storage>>,
+    hw_motor_direction_tx: Option<cbc::Sender<u8>>,
+    hw_floor_sensor_rx: Option<cbc::Receiver<u8>>,
+    hw_floor_indicator_tx: Option<cbc::Sender<u8>>,
+    hw_door_light_tx: Option<cbc::Sender<bool>>,
+    hw_button_light_tx: Option<cbc::Sender<(u8, u8, bool)>>,
+    hw_obstruction_rx: Option<cbc::Receiver<bool>>,
+    hw_panel_signal_tx: Option<cbc::Sender<PanelSignal>>,
+    fsm_hall_requests_rx: Option<cbc::Receiver<Vec<Vec<bool>>>>,
+    fsm_cab_request_rx: Option<cbc::Receiver<u8>>,
+    fsm_parking_floor_rx: Option<cbc::Receiver<Option<u8>>>,
+    fsm_operating_mode_rx: Option<cbc::Receiver<OperatingMode>>,
+    fsm_door_command_rx: Option<cbc::Receiver<bool>>,
+    fsm_alarm_clear_rx: Option<cbc::Receiver<()>>,
+    fsm_halt_rx: Option<cbc::Receiver<bool>>,
+    fsm_inspect_rx: Option<cbc::Receiver<cbc::Sender<FsmView>>>,
+    fsm_timings_rx: cbc::Receiver<FsmTimings>,
+    fsm_blocked_floors_rx: cbc::Receiver<Vec<u8>>,
+    fsm_locked_floors_rx: cbc::Receiver<Vec<u8>>,
+    fsm_order_complete_tx: Option<cbc::Sender<(u8, u8)>>,
+    fsm_state_tx: Option<cbc::Sender<ElevatorState>>,
+    fsm_terminate_rx: cbc::Receiver<Terminate>,
+}
+
+impl FsmBuilder {
+    // An FSM of the configured elevator, serving every floor, with none of the channels
+    pub fn new(fsm_config: &ElevatorConfig) -> FsmBuilder {
+        FsmBuilder {
+            fsm_config: fsm_config.clone(),
+            blocked_floors: Vec::new(),
+            floor_labels: FloorLabels::default(),
+            clock: Arc::new(RealClock),
+            storage: None,
+            hw_motor_direction_tx: None,
+            hw_floor_sensor_rx: None,
+            hw_floor_indicator_tx: None,
+            hw_door_light_tx: None,
+            hw_button_light_tx: None,
+            hw_obstruction_rx: None,
+            hw_panel_signal_tx: None,
+            fsm_hall_requests_rx: None,
+            fsm_cab_request_rx: None,
+            fsm_parking_floor_rx: None,
+            fsm_operating_mode_rx: None,
+            fsm_door_command_rx: None,
+            fsm_alarm_clear_rx: None,
+            fsm_halt_rx: None,
+            fsm_inspect_rx: None,
+            fsm_timings_rx: cbc::never(),
+            fsm_blocked_floors_rx: cbc::never(),
+            fsm_locked_floors_rx: cbc::never(),
+            fsm_order_complete_tx: None,
+            fsm_state_tx: None,
+            fsm_terminate_rx: cbc::never(),
+        }
+    }
+
+    pub fn blocked_floors(mut self, blocked_floors: Vec<u8>) -> FsmBuilder {
+        self.blocked_floors = blocked_floors;
+        self
+    }
+
+    pub fn floor_labels(mut self, floor_labels: FloorLabels) -> FsmBuilder {
+        self.floor_labels = floor_labels;
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> FsmBuilder {
+        self.clock = clock;
+        self
+    }
+
+    pub fn storage(mut self, storage: Arc<dyn Storage>) -> FsmBuilder {
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn hw_motor_direction_tx(mut self, hw_motor_direction_tx: cbc::Sender<u8>) -> FsmBuilder {
+        self.hw_motor_direction_tx = Some(hw_motor_direction_tx);
+        self
+    }
+
+    pub fn hw_floor_sensor_rx(mut self, hw_floor_sensor_rx: cbc::Receiver<u8>) -> FsmBuilder {
+        self.hw_floor_sensor_rx = Some(hw_floor_sensor_rx);
+        self
+    }
+
+    pub fn hw_floor_indicator_tx(mut self, hw_floor_indicator_tx: cbc::Sender<u8>) -> FsmBuilder {
+        self.hw_floor_indicator_tx = Some(hw_floor_indicator_tx);
+        self
+    }
+
+    pub fn hw_door_light_tx(mut self, hw_door_light_tx: cbc::Sender<bool>) -> FsmBuilder {
+        self.hw_door_light_tx = Some(hw_door_light_tx);
+        self
+    }
+
+    pub fn hw_button_light_tx(mut self, hw_button_light_tx: cbc::Sender<(u8, u8, bool)>) -> FsmBuilder {
+        self.hw_button_light_tx = Some(hw_button_light_tx);
+        self
+    }
+
+    pub fn hw_obstruction_rx(mut self, hw_obstruction_rx: cbc::Receiver<bool>) -> FsmBuilder {
+        self.hw_obstruction_rx = Some(hw_obstruction_rx);
+        self
+    }
+
+    pub fn hw_panel_signal_tx(mut self, hw_panel_signal_tx: cbc::Sender<PanelSignal>) -> FsmBuilder {
+        self.hw_panel_signal_tx = Some(hw_panel_signal_tx);
+        self
+    }
+
+    pub fn fsm_hall_requests_rx(mut self, fsm_hall_requests_rx: cbc::Receiver<Vec<Vec<bool>>>) -> FsmBuilder {
+        self.fsm_hall_requests_rx = Some(fsm_hall_requests_rx);
+        self
+    }
+
+    pub fn fsm_cab_request_rx(mut self, fsm_cab_request_rx: cbc::Receiver<u8>) -> FsmBuilder {
+        self.fsm_cab_request_rx = Some(fsm_cab_request_rx);
+        self
+    }
+
+    pub fn fsm_parking_floor_rx(mut self, fsm_parking_floor_rx: cbc::Receiver<Option<u8>>) -> FsmBuilder {
+        self.fsm_parking_floor_rx = Some(fsm_parking_floor_rx);
+        self
+    }
+
+    pub fn fsm_operating_mode_rx(mut self, fsm_operating_mode_rx: cbc::Receiver<OperatingMode>) -> FsmBuilder {
+        self.fsm_operating_mode_rx = Some(fsm_operating_mode_rx);
+        self
+    }
+
+    pub fn fsm_door_command_rx(mut self, fsm_door_command_rx: cbc::Receiver<bool>) -> FsmBuilder {
+        self.fsm_door_command_rx = Some(fsm_door_command_rx);
+        self
+    }
+
+    pub fn fsm_alarm_clear_rx(mut self, fsm_alarm_clear_rx: cbc::Receiver<()>) -> FsmBuilder {
+        self.fsm_alarm_clear_rx = Some(fsm_alarm_clear_rx);
+        self
+    }
+
+    pub fn fsm_halt_rx(mut self, fsm_halt_rx: cbc::Receiver<bool>) -> FsmBuilder {
+        self.fsm_halt_rx = Some(fsm_halt_rx);
+        self
+    }
+
+    pub fn fsm_inspect_rx(mut self, fsm_inspect_rx: cbc::Receiver<cbc::Sender<FsmView>>) -> FsmBuilder {
+        self.fsm_inspect_rx = Some(fsm_inspect_rx);
+        self
+    }
+
+    pub fn fsm_timings_rx(mut self, fsm_timings_rx: cbc::Receiver<FsmTimings>) -> FsmBuilder {
+        self.fsm_timings_rx = fsm_timings_rx;
+        self
+    }
+
+    pub fn fsm_blocked_floors_rx(mut self, fsm_blocked_floors_rx: cbc::Receiver<Vec<u8>>) -> FsmBuilder {
+        self.fsm_blocked_floors_rx = fsm_blocked_floors_rx;
+        self
+    }
+
+    pub fn fsm_locked_floors_rx(mut self, fsm_locked_floors_rx: cbc::Receiver<Vec<u8>>) -> FsmBuilder {
+        self.fsm_locked_floors_rx = fsm_locked_floors_rx;
+        self
+    }
+
+    pub fn fsm_order_complete_tx(mut self, fsm_order_complete_tx: cbc::Sender<(u8, u8)>) -> FsmBuilder {
+        self.fsm_order_complete_tx = Some(fsm_order_complete_tx);
+        self
+    }
+
+    pub fn fsm_state_tx(mut self, fsm_state_tx: cbc::Sender<ElevatorState>) -> FsmBuilder {
+        self.fsm_state_tx = Some(fsm_state_tx);
+        self
+    }
+
+    pub fn fsm_terminate_rx(mut self, fsm_terminate_rx: cbc::Receiver<Terminate>) -> FsmBuilder {
+        self.fsm_terminate_rx = fsm_terminate_rx;
+        self
+    }
+
+    // The FSM, once every channel and the storage are attached
+    pub fn build(self) -> Result<ElevatorFSM, String> {
+        let now = self.clock.now();
+        Ok(ElevatorFSM {
+            hw_motor_direction_tx: attached(self.hw_motor_direction_tx, "FSM", "hw_motor_direction_tx")?,
+            hw_floor_sensor_rx: attached(self.hw_floor_sensor_rx, "FSM", "hw_floor_sensor_rx")?,
+            hw_floor_indicator_tx: attached(self.hw_floor_indicator_tx, "FSM", "hw_floor_indicator_tx")?,
+            hw_door_light_tx: attached(self.hw_door_light_tx, "FSM", "hw_door_light_tx")?,
+            hw_button_light_tx: attached(self.hw_button_light_tx, "FSM", "hw_button_light_tx")?,
+            hw_obstruction_rx: attached(self.hw_obstruction_rx, "FSM", "hw_obstruction_rx")?,
+            hw_panel_signal_tx: attached(self.hw_panel_signal_tx, "FSM", "hw_panel_signal_tx")?,
+
+            fsm_hall_requests_rx: attached(self.fsm_hall_requests_rx, "FSM", "fsm_hall_requests_rx")?,
+            fsm_cab_request_rx: attached(self.fsm_cab_request_rx, "FSM", "fsm_cab_request_rx")?,
+            fsm_parking_floor_rx: attached(self.fsm_parking_floor_rx, "FSM", "fsm_parking_floor_rx")?,
+            fsm_operating_mode_rx: attached(self.fsm_operating_mode_rx, "FSM", "fsm_operating_mode_rx")?,
+            fsm_door_command_rx: attached(self.fsm_door_command_rx, "FSM", "fsm_door_command_rx")?,
+            fsm_alarm_clear_rx: attached(self.fsm_alarm_clear_rx, "FSM", "fsm_alarm_clear_rx")?,
+            fsm_halt_rx: attached(self.fsm_halt_rx, "FSM", "fsm_halt_rx")?,
+            fsm_inspect_rx: attached(self.fsm_inspect_rx, "FSM", "fsm_inspect_rx")?,
+            fsm_timings_rx: self.fsm_timings_rx,
+            fsm_blocked_floors_rx: self.fsm_blocked_floors_rx,
+            fsm_locked_floors_rx: self.fsm_locked_floors_rx,
+            fsm_order_complete_tx: attached(self.fsm_order_complete_tx, "FSM", "fsm_order_complete_tx")?,
+            fsm_state_tx: attached(self.fsm_state_tx, "FSM", "fsm_state_tx")?,
+            fsm_terminate_rx: self.fsm_terminate_rx,
+            
+            hall_requests: vec![vec![false; 2]; self.fsm_config.n_floors as usize],
+            state: ElevatorState { capacity: self.fsm_config.capacity, ..ElevatorState::new(self.fsm_config.n_floors) },
+            n_floors: self.fsm_config.n_floors,
+            obstruction: false,
+            door_open_time: self.fsm_config.door_open_time,
+            door_timeout: self.fsm_config.door_timeout,
+            motor_timeout: self.fsm_config.motor_timeout,
+            obstruction_timer: now,
+            door_timer: now,
+            motor_timer: now,
+            blocked_floors: self.blocked_floors,
+            floor_labels: self.floor_labels,
+            parking_floor: None,
+            levelling_time: self.fsm_config.levelling_time,
+            door_pre_opening: self.fsm_config.door_pre_opening,
+            pre_opening: false,
+            levelled_timer: None,
+            statistics: Statistics::default(),
+            statistics_interval: self.fsm_config.statistics_interval,
+            statistics_report: self.fsm_config.statistics_report.clone(),
+            statistics_timer: now + Duration::from_millis(self.fsm_config.statistics_interval),
+            clock: self.clock,
+            storage: attached(self.storage, "FSM", "storage")?,
+            obstruction_timeouts: 0,
+            obstruction_alarm_threshold: self.fsm_config.obstruction_alarm_threshold,
+            hall_clearing: self.fsm_config.hall_clearing,
+            second_door_cycle: false,
+            panel_signal: PanelSignal::Normal,
+            motion: MotionModel::new(&self.fsm_config.motion),
+        })
+    }
+}
+
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// The floors the car will stop at, in order. Like the FSM, the car sweeps on in its direction while there are
+// orders ahead, takes calls against its direction only where it turns, and does not count parking as a stop.
+// With all hall calls cleared at a stop, a stop also takes the call against the direction.
+pub fn plan_stops(state: &ElevatorState, hall_requests: &[Vec<bool>], n_floors: u8, hall_clearing: HallClearing) -> Vec<u8> {
+    // Halted cars plan no further stops
+    if state.halted {
+        return Vec::new();
+    }
+
+    // Recalled cars head straight for the recall floor
+    if let OperatingMode::FireRecall(recall_floor) = state.mode {
+        if state.floor == recall_floor && state.behaviour != Moving {
+            return Vec::new();
+        }
+        return vec![recall_floor];
+    }
+
+    let mut cab_requests = state.cab_requests.clone();
+    let mut hall_requests = hall_requests.to_vec();
+    let mut floor = state.floor;
+    let mut direction = state.direction.clone();
+    let mut plan = Vec::new();
+
+    // A moving car has already passed its floor
+    if state.behaviour == Moving {
+        match direction {
+            Up if floor + 1 < n_floors => floor += 1,
+            Down if floor > 0 => floor -= 1,
+            _ => {}
+        }
+    }
+
+    // Each floor is passed at most twice, once in each direction
+    for _ in 0..=2 * n_floors as usize {
+        let f = floor as usize;
+        let is_end_floor = floor == 0 || floor + 1 == n_floors;
+        let hall_at_floor = hall_requests[f][HALL_UP as usize] || hall_requests[f][HALL_DOWN as usize];
+        let has_orders_beyond = |direction: &Direction, cab_requests: &[bool], hall_requests: &[Vec<bool>]| {
+            let floors = match direction {
+                Up => f + 1..n_floors as usize,
+                Down => 0..f,
+                Stop => 0..0,
+            };
+            floors.into_iter().any(|f| cab_requests[f] || hall_requests[f][HALL_UP as usize] || hall_requests[f][HALL_DOWN as usize])
+        };
+
+        let stops = match direction {
+            Up => hall_requests[f][HALL_UP as usize],
+            Down => hall_requests[f][HALL_DOWN as usize],
+            Stop => hall_at_floor,
+        } || cab_requests[f]
+            || (hall_at_floor && (is_end_floor || !has_orders_beyond(&direction, &cab_requests, &hall_requests)));
+        if stops {
+            plan.push(floor);
+            cab_requests[f] = false;
+            match direction {
+                _ if hall_clearing == HallClearing::All => hall_requests[f] = vec![false; 2],
+                Up => hall_requests[f][HALL_UP as usize] = false,
+                Down => hall_requests[f][HALL_DOWN as usize] = false,
+                Stop => hall_requests[f] = vec![false; 2],
+            }
+        }
+
+        let next_direction = if direction != Stop && has_orders_beyond(&direction, &cab_requests, &hall_requests) {
+            direction.clone()
+        } else if has_orders_beyond(&Up, &cab_requests, &hall_requests) {
+            Up
+        } else if has_orders_beyond(&Down, &cab_requests, &hall_requests) {
+            Down
+        } else {
+            Stop
+        };
+        if next_direction == Stop {
+            break;
+        }
+
+        // Turning around, the car takes the call in its new direction at the same stop
+        let call = if next_direction == Up { HALL_UP } else { HALL_DOWN };
+        if next_direction != direction && hall_requests[f][call as usize] {
+            hall_requests[f][call as usize] = false;
+            if plan.last() != Some(&floor) {
+                plan.push(floor);
+            }
+        }
+
+        direction = next_direction;
+        floor = match direction {
+            Up => floor + 1,
+            _ => floor - 1,
+        };
+    }
+
+    plan
+}
+
 /***************************************/
 /*              Test API               */
 /***************************************/
 #[cfg(test)]
 pub mod testing {
     use crate::ElevatorState;
+    use crate::shared::Clock;
+    use std::sync::Arc;
     use super::ElevatorFSM;
 
     impl ElevatorFSM {
@@ -456,6 +1208,18 @@ pub mod testing {
             self.state = state;
         }
 
+        pub fn test_set_clock(&mut self, clock: Arc<dyn Clock>) {
+            self.clock = clock;
+        }
+
+        pub fn test_set_motion(&mut self, motion: crate::shared::MotionModel) {
+            self.motion = motion;
+        }
+
+        pub fn test_set_parking_floor(&mut self, parking_floor: Option<u8>) {
+            self.parking_floor = parking_floor;
+        }
+
         pub fn test_choose_direction(&self) -> super::Direction {
             self.choose_direction()
         }
@@ -464,6 +1228,39 @@ pub mod testing {
             self.has_orders_in_direction(direction)
         }
 
+        pub fn test_set_door_pre_opening(&mut self, levelling_time: u64, door_pre_opening: bool) {
+            self.levelling_time = levelling_time;
+            self.door_pre_opening = door_pre_opening;
+        }
+
+        pub fn test_set_obstruction(&mut self, obstruction: bool) {
+            self.obstruction = obstruction;
+        }
+
+        pub fn test_set_obstruction_alarm_threshold(&mut self, obstruction_alarm_threshold: u32) {
+            self.obstruction_alarm_threshold = obstruction_alarm_threshold;
+        }
+
+        pub fn test_set_hall_clearing(&mut self, hall_clearing: super::HallClearing) {
+            self.hall_clearing = hall_clearing;
+        }
+
+        pub fn test_handle_floor_hit(&mut self, floor: u8) {
+            self.handle_floor_hit(floor);
+        }
+
+        pub fn test_handle_timers(&mut self) {
+            self.handle_timers();
+        }
+
+        pub fn test_get_state(&self) -> &ElevatorState {
+            &self.state
+        }
+
+        pub fn test_get_statistics(&self) -> &super::Statistics {
+            &self.statistics
+        }
+
         pub fn test_complete_orders(&mut self) -> bool {
             self.complete_orders()
         }
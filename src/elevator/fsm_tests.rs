@@ -6,6 +6,23 @@
  * Tests:
  * - test_elevator_fsm_new_initial_state 
  * - test_elevator_fsm_new_floor_sensor
+ * - test_fsm_blocked_cab_request
+ * - test_fsm_choose_direction_parking
+ * - test_fsm_fire_recall_direction
+ * - test_fsm_fire_service_door
+ * - test_fsm_door_pre_opening
+ * - test_fsm_statistics
+ * - test_fsm_motor_loss
+ * - test_fsm_stop_plan
+ * - test_fsm_publishes_stop_plan
+ * - test_fsm_obstruction_alarm
+ * - test_fsm_all_hall_clearing
+ * - test_fsm_door_state
+ * - test_fsm_halt
+ * - test_fsm_inspect
+ * - test_fsm_timers
+ * - test_fsm_disconnected_channel
+ * - test_fsm_terminate
  * 
  */
 
@@ -15,37 +32,84 @@
 #[cfg(test)]
 mod fsm_tests {
     use std::thread::spawn;
-    use crate::ElevatorFSM;
+    use crate::{ElevatorFSM, FsmBuilder};
+    use crate::elevator::panel::PanelSignal;
+    use crate::elevator::fsm::plan_stops;
+    use crate::elevator::statistics::write_statistics_report;
     use crate::ElevatorState;
-    use crate::config::ElevatorConfig;
-    use crate::shared::Behaviour::{Idle, Moving};
+    use crate::config::{ElevatorConfig, MotionConfig};
+    use crate::shared::Behaviour::{DoorOpen, Error, Idle, Moving};
     use crate::shared::Direction::{Up, Down, Stop};
     use crossbeam_channel::unbounded;
-    use crate::shared::Direction;
+    use crate::shared::{Direction, DoorState, FsmView, Uptime};
+    use crate::shared::{ModuleError, MotionModel, OperatingMode, Terminate};
+    use crate::shared::{AssignmentModel, HallCallConfirmation, HallClearing, HallRequestAssigner};
+    use crate::shared::SimulatedClock;
+    use crate::storage::{FileStorage, LogRetention};
+    use std::sync::Arc;
+    use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
 
     fn setup_fsm() -> (ElevatorFSM,
         crossbeam_channel::Receiver<u8>,
         crossbeam_channel::Sender<u8>,
         crossbeam_channel::Receiver<u8>,
         crossbeam_channel::Receiver<bool>,
+        crossbeam_channel::Receiver<(u8, u8, bool)>,
         crossbeam_channel::Sender<bool>,
+        crossbeam_channel::Receiver<PanelSignal>,
         crossbeam_channel::Sender<Vec<Vec<bool>>>,
         crossbeam_channel::Sender<u8>,
+        crossbeam_channel::Sender<Option<u8>>,
+        crossbeam_channel::Sender<OperatingMode>,
+        crossbeam_channel::Sender<bool>,
+        crossbeam_channel::Sender<()>,
+        crossbeam_channel::Sender<bool>,
+        crossbeam_channel::Sender<crossbeam_channel::Sender<FsmView>>,
         crossbeam_channel::Receiver<(u8, u8)>,
         crossbeam_channel::Receiver<ElevatorState>,
-        crossbeam_channel::Sender<()>) {
+        crossbeam_channel::Sender<Terminate>) {
+        setup_fsm_with_blocked_floors(Vec::new())
+    }
+
+    fn setup_fsm_with_blocked_floors(blocked_floors: Vec<u8>) -> (ElevatorFSM,
+        crossbeam_channel::Receiver<u8>,
+        crossbeam_channel::Sender<u8>,
+        crossbeam_channel::Receiver<u8>,
+        crossbeam_channel::Receiver<bool>,
+        crossbeam_channel::Receiver<(u8, u8, bool)>,
+        crossbeam_channel::Sender<bool>,
+        crossbeam_channel::Receiver<PanelSignal>,
+        crossbeam_channel::Sender<Vec<Vec<bool>>>,
+        crossbeam_channel::Sender<u8>,
+        crossbeam_channel::Sender<Option<u8>>,
+        crossbeam_channel::Sender<OperatingMode>,
+        crossbeam_channel::Sender<bool>,
+        crossbeam_channel::Sender<()>,
+        crossbeam_channel::Sender<bool>,
+        crossbeam_channel::Sender<crossbeam_channel::Sender<FsmView>>,
+        crossbeam_channel::Receiver<(u8, u8)>,
+        crossbeam_channel::Receiver<ElevatorState>,
+        crossbeam_channel::Sender<Terminate>) {
 
         // Arrange mock channels
         let (hw_motor_direction_tx, hw_motor_direction_rx) = unbounded::<u8>();
         let (hw_floor_sensor_tx, hw_floor_sensor_rx) = unbounded::<u8>();
         let (hw_floor_indicator_tx, _hw_floor_indicator_rx) = unbounded::<u8>();
         let (hw_door_light_tx, hw_door_light_rx) = unbounded::<bool>();
+        let (hw_button_light_tx, hw_button_light_rx) = unbounded::<(u8, u8, bool)>();
         let (hw_obstruction_tx, hw_obstruction_rx) = unbounded::<bool>();
+        let (hw_panel_signal_tx, hw_panel_signal_rx) = unbounded::<PanelSignal>();
         let (fsm_hall_requests_tx, fsm_hall_requests_rx) = unbounded::<Vec<Vec<bool>>>();
         let (fsm_cab_request_tx, fsm_cab_request_rx) = unbounded::<u8>();
+        let (fsm_parking_floor_tx, fsm_parking_floor_rx) = unbounded::<Option<u8>>();
+        let (fsm_operating_mode_tx, fsm_operating_mode_rx) = unbounded::<OperatingMode>();
+        let (fsm_door_command_tx, fsm_door_command_rx) = unbounded::<bool>();
+        let (fsm_alarm_clear_tx, fsm_alarm_clear_rx) = unbounded::<()>();
+        let (fsm_halt_tx, fsm_halt_rx) = unbounded::<bool>();
+        let (fsm_inspect_tx, fsm_inspect_rx) = unbounded::<crossbeam_channel::Sender<FsmView>>();
         let (fsm_order_complete_tx, fsm_order_complete_rx) = unbounded::<(u8, u8)>();
         let (fsm_state_tx, fsm_state_rx) = unbounded::<ElevatorState>();
-        let (fsm_terminate_tx, fsm_terminate_rx) = unbounded::<()>();
+        let (fsm_terminate_tx, fsm_terminate_rx) = unbounded::<Terminate>();
 
         // Default configuration
         let config = ElevatorConfig { 
@@ -53,29 +117,62 @@ mod fsm_tests {
             door_open_time: 3000,
             motor_timeout: 10000,
             door_timeout: 20000,
+            hall_call_cancel_window: 1000,
+            hall_call_quorum: 0,
+            hall_call_confirmation: HallCallConfirmation::Quorum,
+            hall_call_deadline: 0,
+            hall_request_assigner: HallRequestAssigner::Process,
+            assigner_plugin: String::new(),
+            assignment_model: AssignmentModel::Independent,
+            levelling_time: 0,
+            door_pre_opening: false,
+            statistics_interval: 0,
+            statistics_report: String::new(),
+            obstruction_alarm_threshold: 0,
+            hall_clearing: HallClearing::InDirection,
+            capacity: 0,
+            motion: MotionConfig::default(),
         };
 
         // Create the FSM and return it with the channels
-        (ElevatorFSM::new(
-            &config,
-            hw_motor_direction_tx,
-            hw_floor_sensor_rx,
-            hw_floor_indicator_tx,
-            hw_door_light_tx,
-            hw_obstruction_rx,
-            fsm_hall_requests_rx,
-            fsm_cab_request_rx,
-            fsm_order_complete_tx,
-            fsm_state_tx,
-            fsm_terminate_rx,
-        ),
+        (FsmBuilder::new(&config)
+            .blocked_floors(blocked_floors)
+            .storage(Arc::new(FileStorage::new("src/elevator", 0, LogRetention::default())))
+            .hw_motor_direction_tx(hw_motor_direction_tx)
+            .hw_floor_sensor_rx(hw_floor_sensor_rx)
+            .hw_floor_indicator_tx(hw_floor_indicator_tx)
+            .hw_door_light_tx(hw_door_light_tx)
+            .hw_button_light_tx(hw_button_light_tx)
+            .hw_obstruction_rx(hw_obstruction_rx)
+            .hw_panel_signal_tx(hw_panel_signal_tx)
+            .fsm_hall_requests_rx(fsm_hall_requests_rx)
+            .fsm_cab_request_rx(fsm_cab_request_rx)
+            .fsm_parking_floor_rx(fsm_parking_floor_rx)
+            .fsm_operating_mode_rx(fsm_operating_mode_rx)
+            .fsm_door_command_rx(fsm_door_command_rx)
+            .fsm_alarm_clear_rx(fsm_alarm_clear_rx)
+            .fsm_halt_rx(fsm_halt_rx)
+            .fsm_inspect_rx(fsm_inspect_rx)
+            .fsm_order_complete_tx(fsm_order_complete_tx)
+            .fsm_state_tx(fsm_state_tx)
+            .fsm_terminate_rx(fsm_terminate_rx)
+            .build()
+            .unwrap(),
         hw_motor_direction_rx,
         hw_floor_sensor_tx,
         _hw_floor_indicator_rx,
         hw_door_light_rx,
+        hw_button_light_rx,
         hw_obstruction_tx,
+        hw_panel_signal_rx,
         fsm_hall_requests_tx,
         fsm_cab_request_tx,
+        fsm_parking_floor_tx,
+        fsm_operating_mode_tx,
+        fsm_door_command_tx,
+        fsm_alarm_clear_tx,
+        fsm_halt_tx,
+        fsm_inspect_tx,
         fsm_order_complete_rx,
         fsm_state_rx,
         fsm_terminate_tx)
@@ -91,9 +188,17 @@ mod fsm_tests {
             hw_floor_sensor_tx,
             _hw_floor_indicator_rx,
             _hw_door_light_rx,
+            _hw_button_light_rx,
             _hw_obstruction_tx,
+            _hw_panel_signal_rx,
             _fsm_hall_requests_tx,
             _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
             _fsm_order_complete_rx,
             fsm_state_rx,
             terminate_tx) = setup_fsm();
@@ -133,8 +238,8 @@ mod fsm_tests {
         }
 
         // Cleanup
-        terminate_tx.send(()).unwrap();
-        fsm_thread.join().unwrap();
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
     }
 
     #[test]
@@ -147,9 +252,17 @@ mod fsm_tests {
             hw_floor_sensor_tx,
             _hw_floor_indicator_rx,
             _hw_door_light_rx,
+            _hw_button_light_rx,
             _hw_obstruction_tx,
+            _hw_panel_signal_rx,
             _fsm_hall_requests_tx,
             _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
             _fsm_order_complete_rx,
             fsm_state_rx,
             terminate_tx) = setup_fsm();
@@ -188,8 +301,8 @@ mod fsm_tests {
         }
 
         // Cleanup
-        terminate_tx.send(()).unwrap();
-        fsm_thread.join().unwrap();
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
     }
 
     #[test]
@@ -202,9 +315,17 @@ mod fsm_tests {
             _hw_floor_sensor_tx,
             _hw_floor_indicator_rx,
             _hw_door_light_rx,
+            _hw_button_light_rx,
             _hw_obstruction_tx,
+            _hw_panel_signal_rx,
             _fsm_hall_requests_tx,
             _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
             _fsm_order_complete_rx,
             _fsm_state_rx,
             _terminate_tx) = setup_fsm();
@@ -215,6 +336,16 @@ mod fsm_tests {
             floor: 0,
             direction: Stop,
             cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
         //Testing orders above
         let state2 = ElevatorState {
@@ -222,6 +353,16 @@ mod fsm_tests {
             floor: 1,
             direction: Stop,
             cab_requests: [false, false, true, true].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
         //testing orders below
         let state3 = ElevatorState {
@@ -229,6 +370,16 @@ mod fsm_tests {
             floor: 1,
             direction: Stop,
             cab_requests: [true, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
         //testing orders at current floor
         let state4 = ElevatorState {
@@ -236,6 +387,16 @@ mod fsm_tests {
             floor: 3,
             direction: Stop,
             cab_requests: [false, false, false, true].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
 
         // Act
@@ -264,9 +425,17 @@ mod fsm_tests {
             _hw_floor_sensor_tx,
             _hw_floor_indicator_rx,
             _hw_door_light_rx,
+            _hw_button_light_rx,
             _hw_obstruction_tx,
+            _hw_panel_signal_rx,
             _fsm_hall_requests_tx,
             _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
             _fsm_order_complete_rx,
             _fsm_state_rx,
             _terminate_tx) = setup_fsm();
@@ -277,6 +446,16 @@ mod fsm_tests {
             floor: 0,
             direction: Stop,
             cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
         //Testing above
         let state2 = ElevatorState {
@@ -284,6 +463,16 @@ mod fsm_tests {
             floor: 0,
             direction: Stop,
             cab_requests: [false, true, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
         //Testing below
         let state3 = ElevatorState {
@@ -291,6 +480,16 @@ mod fsm_tests {
             floor: 2,
             direction: Stop,
             cab_requests: [true, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
         //Testing at current floor
         let state4 = ElevatorState {
@@ -298,6 +497,16 @@ mod fsm_tests {
             floor: 1,
             direction: Stop,
             cab_requests: [true, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
 
         let test_direction1 = Direction::Up;
@@ -330,9 +539,17 @@ mod fsm_tests {
             _hw_floor_sensor_tx,
             _hw_floor_indicator_rx,
             _hw_door_light_rx,
+            _hw_button_light_rx,
             _hw_obstruction_tx,
+            _hw_panel_signal_rx,
             _fsm_hall_requests_tx,
             _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
             _fsm_order_complete_rx,
             _fsm_state_rx,
             _terminate_tx) = setup_fsm();
@@ -343,6 +560,16 @@ mod fsm_tests {
             floor: 1,
             direction: Up,
             cab_requests: [false, true, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
 
         let hall_requests1 = [[false, false].to_vec(),
@@ -357,6 +584,16 @@ mod fsm_tests {
             floor: 2,
             direction: Up,
             cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
 
         let hall_requests2 = [[false, true].to_vec(),
@@ -371,6 +608,16 @@ mod fsm_tests {
             floor: 1,
             direction: Stop,
             cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         };
 
         let hall_requests3 = [[false, false].to_vec(),
@@ -398,4 +645,973 @@ mod fsm_tests {
         assert_eq!(result3, true);
     }
 
+    #[test]
+    fn test_fsm_blocked_cab_request() {
+        // Purpose: Verify that cab requests to floors the FSM may not serve are refused with a flashing cab light
+
+        // Arrange
+        let (fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm_with_blocked_floors(vec![3]);
+
+        let fsm_thread = spawn(move || fsm.run());
+
+        match fsm_state_rx.recv_timeout(std::time::Duration::from_secs(3)) {
+            Ok(state) => {
+                //Saved cab calls to the restricted floor are never loaded
+                assert!(!state.cab_requests[3]);
+            },
+            Err(e) => {
+                panic!("Error receiving from fsm_state_rx: {:?}", e);
+            }
+        }
+
+        // Act
+        fsm_cab_request_tx.send(3).unwrap();
+
+        // Assert
+        match fsm_state_rx.recv_timeout(std::time::Duration::from_millis(500)) {
+            Ok(state) => panic!("Cab request to restricted floor was accepted: {:?}", state),
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => (),
+            Err(e) => panic!("Error receiving from fsm_state_rx: {:?}", e),
+        }
+        let flashes = hw_button_light_rx.iter().take(2).collect::<Vec<(u8, u8, bool)>>();
+        assert_eq!(flashes, vec![(3, CAB, true), (3, CAB, false)], "Refused cab request not signalled");
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_choose_direction_parking() {
+        // Purpose: Verify that an FSM without orders heads for the parking floor, and that orders come first
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            _terminate_tx) = setup_fsm();
+
+        let idle_state = ElevatorState {
+            behaviour: Idle,
+            floor: 2,
+            direction: Stop,
+            cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
+        };
+        let busy_state = ElevatorState {
+            behaviour: Idle,
+            floor: 2,
+            direction: Stop,
+            cab_requests: [false, false, false, true].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
+        };
+
+        // Act
+        fsm.test_set_state(idle_state.clone());
+        let direction_without_parking = fsm.test_choose_direction();
+        fsm.test_set_parking_floor(Some(0));
+        let direction_to_lobby = fsm.test_choose_direction();
+        fsm.test_set_parking_floor(Some(2));
+        let direction_at_parking = fsm.test_choose_direction();
+        fsm.test_set_parking_floor(Some(0));
+        fsm.test_set_state(busy_state);
+        let direction_with_orders = fsm.test_choose_direction();
+
+        // Assert
+        assert_eq!(direction_without_parking, Stop);
+        assert_eq!(direction_to_lobby, Down);
+        assert_eq!(direction_at_parking, Stop);
+        assert_eq!(direction_with_orders, Up);
+    }
+
+    #[test]
+    fn test_fsm_fire_recall_direction() {
+        // Purpose: Verify that a recalled FSM heads for the recall floor regardless of its orders
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            _terminate_tx) = setup_fsm();
+
+        let moving_up = ElevatorState {
+            behaviour: Moving,
+            floor: 1,
+            direction: Up,
+            cab_requests: [false, false, false, true].to_vec(),
+            mode: OperatingMode::FireRecall(0),
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
+        };
+        let at_recall_floor = ElevatorState {
+            behaviour: Idle,
+            floor: 0,
+            direction: Stop,
+            cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::FireRecall(0),
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
+        };
+
+        // Act
+        fsm.test_set_state(moving_up);
+        let direction_moving_up = fsm.test_choose_direction();
+        fsm.test_set_state(at_recall_floor);
+        let direction_at_recall_floor = fsm.test_choose_direction();
+
+        // Assert
+        assert_eq!(direction_moving_up, Down);
+        assert_eq!(direction_at_recall_floor, Stop);
+    }
+
+    #[test]
+    fn test_fsm_fire_service_door() {
+        // Purpose: Verify that the door opens at the recall floor, and is operated by command in phase 2
+
+        // Arrange
+        let (fsm,
+            _hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            fsm_operating_mode_tx,
+            fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        hw_floor_sensor_tx.send(2).unwrap();
+
+        // Act / Assert
+        // Phase 1: the car is already at the recall floor, and opens the door
+        fsm_operating_mode_tx.send(OperatingMode::FireRecall(2)).unwrap();
+        assert_eq!(hw_door_light_rx.recv_timeout(timeout), Ok(true), "Door not opened at the recall floor");
+
+        // Phase 2: the door stays open until it is commanded closed
+        fsm_operating_mode_tx.send(OperatingMode::FirePhase2).unwrap();
+        assert!(hw_door_light_rx.recv_timeout(timeout).is_err(), "Door closed without a command");
+        fsm_door_command_tx.send(false).unwrap();
+        assert_eq!(hw_door_light_rx.recv_timeout(timeout), Ok(false), "Door not closed on command");
+        fsm_door_command_tx.send(true).unwrap();
+        assert_eq!(hw_door_light_rx.recv_timeout(timeout), Ok(true), "Door not opened on command");
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_door_pre_opening() {
+        // Purpose: Verify that the door opens on arrival at a stop the car decelerated for, unless the door is obstructed
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            _terminate_tx) = setup_fsm();
+
+        let moving_state = ElevatorState {
+            behaviour: Moving,
+            floor: 0,
+            direction: Up,
+            cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
+        };
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[2][HALL_UP as usize] = true;
+        fsm.test_set_door_pre_opening(3000, true);
+
+        // Act
+        fsm.test_set_state(moving_state.clone());
+        fsm.test_set_hall_requests(hall_requests.clone());
+        fsm.test_handle_floor_hit(1);
+        fsm.test_handle_floor_hit(2);
+        let pre_opened_door = hw_door_light_rx.try_recv();
+
+        fsm.test_set_state(moving_state);
+        fsm.test_set_hall_requests(hall_requests);
+        fsm.test_set_obstruction(true);
+        fsm.test_handle_floor_hit(1);
+        fsm.test_handle_floor_hit(2);
+        let obstructed_door = hw_door_light_rx.try_recv();
+
+        // Assert
+        assert_eq!(pre_opened_door, Ok(true), "Door not pre-opened");
+        assert!(obstructed_door.is_err(), "Door pre-opened while obstructed");
+    }
+
+    #[test]
+    fn test_fsm_statistics() {
+        // Purpose: Verify that the FSM counts the floors travelled, door cycles and orders served
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            _terminate_tx) = setup_fsm();
+
+        let moving_state = ElevatorState {
+            behaviour: Moving,
+            floor: 0,
+            direction: Up,
+            cab_requests: [false, false, false, false].to_vec(),
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
+        };
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[3][HALL_DOWN as usize] = true;
+
+        // Act
+        fsm.test_set_state(moving_state);
+        fsm.test_set_hall_requests(hall_requests);
+        fsm.test_handle_floor_hit(1);
+        fsm.test_handle_floor_hit(2);
+        fsm.test_handle_floor_hit(3);
+
+        // Assert
+        let statistics = fsm.test_get_statistics();
+        assert_eq!(statistics.distance_travelled, 3);
+        assert_eq!(statistics.door_cycles, 1);
+        assert_eq!(statistics.orders_served, 1);
+        // A report that cannot be written is an error to log, not a panic of the FSM
+        assert!(write_statistics_report(statistics, "/nonexistent/statistics.json").is_err());
+    }
+
+    #[test]
+    fn test_fsm_motor_loss() {
+        // Purpose: Verify that a car not reaching the next floor within the motor timeout goes out of service
+
+        // Arrange
+        let (mut fsm,
+            hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        // The motor timeout of 10 s is stepped over rather than waited for
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        hw_floor_sensor_tx.send(0).unwrap();
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[3][HALL_DOWN as usize] = true;
+        fsm_hall_requests_tx.send(hall_requests).unwrap();
+        while hw_motor_direction_rx.recv_timeout(timeout).expect("The car did not start") != Up.to_u8() {}
+
+        // Act
+        clock.advance(std::time::Duration::from_millis(9_000));
+        std::thread::sleep(timeout / 2);
+        let before_timeout = fsm_state_rx.try_iter().any(|state| state.behaviour == Error);
+        clock.advance(std::time::Duration::from_millis(1_000));
+        let after_timeout = std::iter::from_fn(|| fsm_state_rx.recv_timeout(timeout).ok()).any(|state| state.behaviour == Error);
+
+        // Assert
+        assert!(!before_timeout, "Motor loss before the timeout");
+        assert!(after_timeout, "No motor loss after the timeout");
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_stop_plan() {
+        // Purpose: Verify that the planned stops follow the sweep of the FSM
+
+        // Arrange
+        let mut moving = ElevatorState::new(4);
+        moving.behaviour = Moving;
+        moving.floor = 1;
+        moving.direction = Up;
+        moving.cab_requests[3] = true;
+        let mut moving_hall_requests = vec![vec![false; 2]; 4];
+        moving_hall_requests[2][HALL_DOWN as usize] = true;
+        moving_hall_requests[0][HALL_UP as usize] = true;
+
+        let idle = ElevatorState::new(4);
+        let mut idle_hall_requests = vec![vec![false; 2]; 4];
+        idle_hall_requests[1][HALL_UP as usize] = true;
+        idle_hall_requests[3][HALL_DOWN as usize] = true;
+
+        let mut recalled = ElevatorState::new(4);
+        recalled.mode = OperatingMode::FireRecall(2);
+        recalled.cab_requests[1] = true;
+
+        // Act / Assert
+        // The down call at floor 2 is passed on the way up, and taken on the way down
+        assert_eq!(plan_stops(&moving, &moving_hall_requests, 4, HallClearing::InDirection), vec![3, 2, 0]);
+        assert_eq!(plan_stops(&idle, &idle_hall_requests, 4, HallClearing::InDirection), vec![1, 3]);
+        assert_eq!(plan_stops(&idle, &vec![vec![false; 2]; 4], 4, HallClearing::InDirection), Vec::<u8>::new());
+        assert_eq!(plan_stops(&recalled, &vec![vec![false; 2]; 4], 4, HallClearing::InDirection), vec![2]);
+    }
+
+    #[test]
+    fn test_fsm_publishes_stop_plan() {
+        // Purpose: Verify that the FSM publishes its planned stops, and when it arrives at them, when it is given calls
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        let motion = MotionModel::new(&MotionConfig { acceleration_time: 1000, floor_travel_time: 2000, door_cycle_time: 4000 });
+        fsm.test_set_motion(motion);
+        fsm.test_set_clock(Arc::new(SimulatedClock::new(0.0)));
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        hw_floor_sensor_tx.send(0).unwrap();
+        while fsm_state_rx.recv_timeout(timeout).expect("No state at the first floor").floor != 0 {}
+
+        // Act
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[2][HALL_UP as usize] = true;
+        hall_requests[3][HALL_DOWN as usize] = true;
+        fsm_hall_requests_tx.send(hall_requests).unwrap();
+
+        // Assert
+        let planned = std::iter::from_fn(|| fsm_state_rx.recv_timeout(timeout).ok()).find(|state| state.stop_plan == vec![2, 3]);
+        let planned = planned.expect("The planned stops were not published");
+        // Two floors, a door cycle and one more floor
+        assert_eq!(planned.arrival_times, vec![5000, 5000 + 4000 + 3000]);
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_obstruction_alarm() {
+        // Purpose: Verify that repeated obstruction timeouts raise an alarm that holds the car until it is cleared
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        // The door timeout of 20 s is stepped over rather than waited for
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        fsm.test_set_obstruction_alarm_threshold(2);
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        let wait_for = |predicate: &dyn Fn(&ElevatorState) -> bool| {
+            std::iter::from_fn(|| fsm_state_rx.recv_timeout(timeout).ok()).any(|state| predicate(&state))
+        };
+        let obstruct_until_timeout = || {
+            hw_obstruction_tx.send(true).unwrap();
+            std::thread::sleep(timeout / 4);
+            clock.advance(std::time::Duration::from_millis(21_000));
+        };
+
+        hw_floor_sensor_tx.send(0).unwrap();
+        fsm_cab_request_tx.send(0).unwrap();
+        assert!(wait_for(&|state| state.behaviour == DoorOpen), "The door did not open");
+
+        // Act
+        obstruct_until_timeout();
+        let first_timeout = wait_for(&|state| state.behaviour == Error && !state.obstruction_alarm);
+        hw_obstruction_tx.send(false).unwrap();
+        let recovered = wait_for(&|state| state.behaviour == DoorOpen);
+
+        obstruct_until_timeout();
+        let alarm = wait_for(&|state| state.behaviour == Error && state.obstruction_alarm);
+        hw_obstruction_tx.send(false).unwrap();
+        let held = !wait_for(&|state| state.behaviour == DoorOpen);
+
+        fsm_alarm_clear_tx.send(()).unwrap();
+        let cleared = wait_for(&|state| state.behaviour == DoorOpen && !state.obstruction_alarm);
+
+        // Assert
+        assert!(first_timeout, "No error on the first obstruction timeout");
+        assert!(recovered, "The car did not recover from the first obstruction timeout");
+        assert!(alarm, "No obstruction alarm on the second obstruction timeout in a row");
+        assert!(held, "The car recovered from the obstruction alarm on its own");
+        assert!(cleared, "The car did not return to service when the alarm was cleared");
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_all_hall_clearing() {
+        // Purpose: Verify that a stop clears both hall calls at the floor, with a second door cycle for the other direction
+
+        // Arrange
+        let (mut fsm,
+            hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        let mut moving = ElevatorState::new(4);
+        moving.behaviour = Moving;
+        moving.direction = Up;
+        moving.cab_requests[1] = true;
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[1][HALL_DOWN as usize] = true;
+        hall_requests[3][HALL_DOWN as usize] = true;
+
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        fsm.test_set_hall_clearing(HallClearing::All);
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        hw_floor_sensor_tx.send(0).unwrap();
+        while fsm_state_rx.recv_timeout(timeout).expect("No state at the first floor").floor != 0 {}
+        fsm_hall_requests_tx.send(hall_requests.clone()).unwrap();
+        fsm_cab_request_tx.send(1).unwrap();
+        while hw_motor_direction_rx.recv_timeout(timeout).expect("The car did not start") != Up.to_u8() {}
+
+        // Act
+        hw_floor_sensor_tx.send(1).unwrap();
+        let completed = (0..2).map(|_| fsm_order_complete_rx.recv_timeout(timeout).unwrap()).collect::<Vec<(u8, u8)>>();
+        let opened = hw_door_light_rx.recv_timeout(timeout);
+        clock.advance(std::time::Duration::from_millis(3_000));
+        let second_cycle = hw_door_light_rx.recv_timeout(timeout);
+        clock.advance(std::time::Duration::from_millis(3_000));
+        let closed = hw_door_light_rx.recv_timeout(timeout);
+
+        // Assert
+        assert_eq!(completed, vec![(1, CAB), (1, HALL_DOWN)]);
+        assert_eq!((opened, second_cycle, closed), (Ok(true), Ok(true), Ok(false)));
+        // The down call at floor 1 is not planned as a stop on the way back
+        assert_eq!(plan_stops(&moving, &hall_requests, 4, HallClearing::InDirection), vec![1, 3, 1]);
+        assert_eq!(plan_stops(&moving, &hall_requests, 4, HallClearing::All), vec![1, 3]);
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_door_state() {
+        // Purpose: Verify that the published state follows the door as it opens, is obstructed and closes
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            hw_obstruction_tx,
+            hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        let wait_for = |door: DoorState| std::iter::from_fn(|| fsm_state_rx.recv_timeout(timeout).ok()).any(|state| state.door == door);
+        hw_floor_sensor_tx.send(0).unwrap();
+        let closed_at_start = wait_for(DoorState::Closed);
+
+        // Act
+        fsm_cab_request_tx.send(0).unwrap();
+        let opened = wait_for(DoorState::Open);
+        hw_obstruction_tx.send(true).unwrap();
+        let blocked = wait_for(DoorState::Blocked);
+        hw_obstruction_tx.send(false).unwrap();
+        let unblocked = wait_for(DoorState::Open);
+        std::thread::sleep(timeout / 4);
+        clock.advance(std::time::Duration::from_millis(3_000));
+        let closed = wait_for(DoorState::Closed);
+
+        // Assert
+        assert!(closed_at_start, "The door was not closed at the first floor");
+        assert!(opened, "The door was not reported open at the stop");
+        assert!(blocked, "The obstructed door was not reported blocked");
+        assert!(unblocked, "The door was not reported open once the obstruction was removed");
+        assert!(closed, "The door was not reported closed after the door open time");
+        // The panel blinks while the door is blocked
+        assert_eq!(hw_panel_signal_rx.try_iter().collect::<Vec<PanelSignal>>(), vec![PanelSignal::Blocked, PanelSignal::Normal]);
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_halt() {
+        // Purpose: Verify that a halted car finishes its stop and holds there until it is resumed
+
+        // Arrange
+        let (mut fsm,
+            hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        let wait_for = |predicate: &dyn Fn(&ElevatorState) -> bool| {
+            std::iter::from_fn(|| fsm_state_rx.recv_timeout(timeout).ok()).any(|state| predicate(&state))
+        };
+        hw_floor_sensor_tx.send(0).unwrap();
+        fsm_cab_request_tx.send(0).unwrap();
+        assert!(wait_for(&|state| state.door == DoorState::Open), "The door did not open");
+
+        // Act
+        fsm_halt_tx.send(true).unwrap();
+        let halted = wait_for(&|state| state.halted && state.stop_plan.is_empty());
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[2][HALL_UP as usize] = true;
+        fsm_hall_requests_tx.send(hall_requests).unwrap();
+        std::thread::sleep(timeout / 4);
+        clock.advance(std::time::Duration::from_millis(3_000));
+        let held = wait_for(&|state| state.door == DoorState::Closed && state.behaviour == Idle);
+        std::thread::sleep(timeout / 4);
+        let motor_while_halted = hw_motor_direction_rx.try_iter().filter(|direction| *direction == Up.to_u8()).count();
+
+        fsm_halt_tx.send(false).unwrap();
+        let resumed = std::iter::from_fn(|| hw_motor_direction_rx.recv_timeout(timeout).ok()).any(|direction| direction == Up.to_u8());
+
+        // Assert
+        assert!(halted, "The halt was not published");
+        assert!(held, "The door did not close at the stop");
+        assert_eq!(motor_while_halted, 0, "The halted car moved off");
+        assert!(resumed, "The car did not move off when resumed");
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_inspect() {
+        // Purpose: Verify that the FSM answers inspection queries with its orders and the time left on its timers
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            fsm_state_rx,
+            terminate_tx) = setup_fsm();
+
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        let fsm_thread = spawn(move || fsm.run());
+        let timeout = std::time::Duration::from_secs(1);
+        let wait_for = |predicate: &dyn Fn(&ElevatorState) -> bool| {
+            std::iter::from_fn(|| fsm_state_rx.recv_timeout(timeout).ok()).any(|state| predicate(&state))
+        };
+        let inspect = || {
+            let (reply_tx, reply_rx) = unbounded::<FsmView>();
+            fsm_inspect_tx.send(reply_tx).unwrap();
+            reply_rx.recv_timeout(timeout).unwrap()
+        };
+        hw_floor_sensor_tx.send(0).unwrap();
+        fsm_cab_request_tx.send(0).unwrap();
+        assert!(wait_for(&|state| state.door == DoorState::Open), "The door did not open");
+        let mut hall_requests = vec![vec![false; 2]; 4];
+        hall_requests[2][HALL_UP as usize] = true;
+        fsm_hall_requests_tx.send(hall_requests.clone()).unwrap();
+        assert!(wait_for(&|state| state.stop_plan == vec![2]), "The hall call was not planned");
+
+        // Act
+        let opened = inspect();
+        clock.advance(std::time::Duration::from_millis(1_000));
+        let later = inspect();
+
+        // Assert
+        assert_eq!(opened.hall_requests, hall_requests);
+        assert_eq!(opened.state.behaviour, DoorOpen);
+        assert_eq!(opened.door_timer, Some(3_000));
+        assert_eq!((opened.motor_timer, opened.obstruction_timer, opened.levelling_timer), (None, None, None));
+        assert_eq!(later.door_timer, Some(2_000));
+
+        // Cleanup
+        terminate_tx.send(unbounded().0).unwrap();
+        assert_eq!(fsm_thread.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_fsm_timers() {
+        // Purpose: Verify the door, motor and obstruction timeouts by stepping the clock and the FSM by hand
+
+        // Arrange
+        let (mut fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            _terminate_tx) = setup_fsm();
+
+        // The FSM is not run, so nothing happens between the steps
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        fsm.test_set_clock(clock.clone());
+        let step = |fsm: &mut ElevatorFSM, milliseconds: u64| {
+            clock.advance(std::time::Duration::from_millis(milliseconds));
+            fsm.test_handle_timers();
+            (fsm.test_get_state().behaviour.clone(), fsm.test_get_state().door)
+        };
+        fsm.test_set_state(ElevatorState { cab_requests: vec![false, false, true, true], ..ElevatorState::new(4) });
+        fsm.test_handle_floor_hit(2);
+
+        // Act
+        let door = [step(&mut fsm, 2_999), step(&mut fsm, 1)];
+        let motor = [step(&mut fsm, 9_999), step(&mut fsm, 1)];
+        fsm.test_set_obstruction(true);
+        fsm.test_handle_floor_hit(3);
+        let obstruction = [step(&mut fsm, 19_999), step(&mut fsm, 1)];
+
+        // Assert
+        // The door closes once it has been open for the door open time, and the car sets off for floor 3
+        assert_eq!(door, [(DoorOpen, DoorState::Open), (Moving, DoorState::Closed)]);
+        // The motor is lost once the car has not reached floor 3 within the motor timeout
+        assert_eq!(motor.map(|(behaviour, _)| behaviour), [Moving, Error]);
+        // A car back in service at floor 3 with the door obstructed times out the door
+        assert_eq!(obstruction, [(DoorOpen, DoorState::Blocked), (Error, DoorState::Blocked)]);
+    }
+
+    #[test]
+    fn test_fsm_disconnected_channel() {
+        // Purpose: Verify that the FSM stops with the channel that was disconnected, instead of exiting the program
+
+        // Arrange
+        let (fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            _terminate_tx) = setup_fsm();
+        let fsm_thread = spawn(move || fsm.run());
+
+        // Act
+        drop(fsm_hall_requests_tx);
+
+        // Assert
+        assert_eq!(fsm_thread.join().unwrap(), Err(ModuleError::Disconnected("fsm_hall_requests_rx".to_string())));
+    }
+
+    #[test]
+    fn test_fsm_terminate() {
+        // Purpose: Verify that the FSM stops the car and acknowledges when asked to terminate, also when a channel is
+        // found disconnected first
+
+        // Arrange
+        let (fsm,
+            hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            _fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            terminate_tx) = setup_fsm();
+        let (disconnected_fsm,
+            _hw_motor_direction_rx,
+            _hw_floor_sensor_tx,
+            _hw_floor_indicator_rx,
+            _hw_door_light_rx,
+            _hw_button_light_rx,
+            _hw_obstruction_tx,
+            _hw_panel_signal_rx,
+            fsm_hall_requests_tx,
+            _fsm_cab_request_tx,
+            _fsm_parking_floor_tx,
+            _fsm_operating_mode_tx,
+            _fsm_door_command_tx,
+            _fsm_alarm_clear_tx,
+            _fsm_halt_tx,
+            _fsm_inspect_tx,
+            _fsm_order_complete_rx,
+            _fsm_state_rx,
+            disconnected_terminate_tx) = setup_fsm();
+        let (done_tx, done_rx) = unbounded::<()>();
+        let (disconnected_done_tx, disconnected_done_rx) = unbounded::<()>();
+        let fsm_thread = spawn(move || fsm.run());
+
+        // Act
+        terminate_tx.send(done_tx).unwrap();
+        let result = fsm_thread.join().unwrap();
+        drop(fsm_hall_requests_tx);
+        disconnected_terminate_tx.send(disconnected_done_tx).unwrap();
+        let disconnected_result = disconnected_fsm.run();
+
+        // Assert
+        // The car is stopped before the termination is acknowledged
+        assert_eq!(result, Ok(()));
+        assert_eq!(done_rx.try_recv(), Ok(()));
+        assert_eq!(hw_motor_direction_rx.try_iter().last(), Some(Stop.to_u8()));
+        // The channel disconnected by a module terminating first does not keep the FSM from terminating as asked
+        assert_eq!(disconnected_result, Ok(()));
+        assert_eq!(disconnected_done_rx.try_recv(), Ok(()));
+    }
 }
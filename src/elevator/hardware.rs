@@ -7,50 +7,78 @@
  * and sensor events. It utilizes crossbeam channels for asynchronous communication with the
  * coordinator thread and fsm thread.
  *
+ * The inputs are polled by a thread of their own every `hw_thread_sleep_time` milliseconds, which sends the driver
+ * only what changed. The driver thread blocks until an input or a command arrives, or a blinking lamp of the panel
+ * is due to change, so an idle elevator costs next to no CPU, also with many simulated elevators on one computer.
+ *
  * # Fields
  *
  * - `elevator`:                Instance of `Elevator` for low-level hardware control.
- * - `thread_sleep_time`:       Duration in milliseconds the driver thread sleeps for in each loop iteration.
- * - `current_floor`:           The current floor the elevator is on.
- * - `obstruction`:             Whether the obstruction sensor is active. Used to only send changes over `hw_obstruction_tx`.
+ * - `thread_sleep_time`:       Duration in milliseconds between the polls of the inputs by the poll thread.
  * - `requests`:                A 2D vector representing the current state of the call buttons. Used to only send changes over `hw_request_tx`.
+ * - `panel`:                   Blinks the door and cab button lamps to signal faults and special modes, and the stop lamp to show the status of the system.
+ * - `door_light`:              The door lamp as last set over `hw_door_light_rx`, shown when no pattern overrides it.
+ * - `cab_lights`:              The cab button lamps as last set over `hw_button_light_rx`, shown when no pattern overrides them.
+ * - `shown_door_light`:        The door lamp as shown on the panel.
+ * - `shown_cab_lights`:        Whether a pattern shows the cab button lamps lit or unlit, if one overrides them.
+ * - `shown_stop_light`:        The stop lamp as shown on the panel.
  * - `hw_motor_direction_rx`:   Receiver for motor direction commands.
  * - `hw_button_light_rx`:      Receiver for button light control commands.
  * - `hw_request_tx`:           Sender for request events.
  * - `hw_floor_sensor_tx`:      Sender for floor sensor events.
  * - `hw_door_light_rx`:        Receiver for door light control commands.
  * - `hw_obstruction_tx`:       Sender for obstruction events.
- * - `terminate_rx`:            Receiver for termination signal.
+ * - `hw_panel_signal_rx`:      Receiver for the signal shown on the panel, from the state of the FSM.
+ * - `hw_status_rx`:            Receiver for the status of the system shown on the stop lamp, from the health monitor.
+ * - `hw_ready_tx`:             Sender reporting the driver ready once the inputs are first read from the hardware, if attached.
+ * - `terminate_rx`:            Receiver for the request to terminate, acknowledged once the motor is stopped.
  */
 
 /***************************************/
 /*              Libraries              */
 /***************************************/
-use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use driver_rust::elevio::elev::{CAB, DIRN_STOP, HALL_DOWN, HALL_UP};
 use driver_rust::elevio::elev::Elevator;
 use crossbeam_channel as cbc;
-use std::time::Duration;
-use log::error;
+use std::thread::Builder;
+use std::time::{Duration, Instant};
 
 /***************************************/
 /*            Local modules            */
 /***************************************/
-use crate::config::HardwareConfig;
+use crate::config::{HardwareConfig, PanelConfig, SelfTestConfig};
+use crate::elevator::panel::{PanelSignal, PatternManager};
+use crate::elevator::self_test::{self_test, SelfTestReport};
+use crate::shared::{attached, event_loop, EventLoop, ModuleError, SystemStatus, Terminate};
 
 /***************************************/
 /*              Constants              */
 /***************************************/
 const HW_NUM_REQUEST_TYPES: usize = 3;
 
+/***************************************/
+/*      Local data structures          */
+/***************************************/
+// A change of the inputs, found by the poll thread
+enum Input {
+    Floor(u8),
+    Obstruction(bool),
+    CallButton(u8, u8),
+}
+
 /***************************************/
 /*              Public API             */
 /***************************************/
 pub struct ElevatorDriver {
     elevator: Elevator,
     thread_sleep_time: u64,
-    current_floor: u8,
-    obstruction: bool,
     requests: Vec<Vec<bool>>,
+    panel: PatternManager,
+    door_light: bool,
+    cab_lights: Vec<bool>,
+    shown_door_light: bool,
+    shown_cab_lights: Option<bool>,
+    shown_stop_light: bool,
     hw_motor_direction_rx: cbc::Receiver<u8>,
     hw_button_light_rx: cbc::Receiver<(u8, u8, bool)>,
     hw_request_tx: cbc::Sender<(u8, u8)>,
@@ -58,132 +86,305 @@ pub struct ElevatorDriver {
     hw_floor_indicator_rx: cbc::Receiver<u8>,
     hw_door_light_rx: cbc::Receiver<bool>,
     hw_obstruction_tx: cbc::Sender<bool>,
-    terminate_rx: cbc::Receiver<()>,
+    hw_panel_signal_rx: cbc::Receiver<PanelSignal>,
+    hw_status_rx: cbc::Receiver<SystemStatus>,
+    hw_ready_tx: Option<cbc::Sender<()>>,
+    terminate_rx: cbc::Receiver<Terminate>,
 }
 
 impl ElevatorDriver {
-    pub fn new(
-        hw_config: &HardwareConfig,
-        hw_motor_direction_rx: cbc::Receiver<u8>,
-        hw_button_light_rx: cbc::Receiver<(u8, u8, bool)>,
-        hw_request_tx: cbc::Sender<(u8, u8)>,
-        hw_floor_sensor_tx: cbc::Sender<u8>,
-        hw_floor_indicator_rx: cbc::Receiver<u8>,
-        hw_door_light_rx: cbc::Receiver<bool>,
-        hw_obstruction_tx: cbc::Sender<bool>,
-        terminate_rx: cbc::Receiver<()>,
-    ) -> ElevatorDriver {
-        ElevatorDriver {
-            elevator: Elevator::init(&format!("{}:{}", &hw_config.driver_address, &hw_config.driver_port), hw_config.n_floors).unwrap(),
-            thread_sleep_time: hw_config.hw_thread_sleep_time,
-            current_floor: u8::MAX,
-            obstruction: false,
-            requests: vec![vec![false; HW_NUM_REQUEST_TYPES]; hw_config.n_floors as usize],
-            hw_motor_direction_rx,
-            hw_button_light_rx,
-            hw_request_tx,
-            hw_floor_sensor_tx,
-            hw_floor_indicator_rx,
-            hw_door_light_rx,
-            hw_obstruction_tx,
-            terminate_rx,
-        }
+    // Tests the hardware before the driver runs, allowing the floor sensor `descent_timeout` to respond
+    pub fn self_test(&self, config: &SelfTestConfig, descent_timeout: Duration) -> SelfTestReport {
+        self_test(&self.elevator, config, descent_timeout)
     }
 
-    pub fn run(mut self) {
+    // Drives the hardware until terminated, or until a channel is disconnected
+    pub fn run(mut self) -> Result<(), ModuleError> {
         // Reset system
         for floor in 0..self.elevator.num_floors {
             self.elevator.call_button_light(floor, HALL_UP, false);
             self.elevator.call_button_light(floor, HALL_DOWN, false);
             self.elevator.call_button_light(floor, CAB, false);
         }
-        self.obstruction = self.elevator.obstruction();
-
-        // Main loop
-        loop {
-            // Check if new floor is hit
-            if let Some(floor) = self.elevator.floor_sensor() {
-                if floor != self.current_floor {
-                    self.current_floor = floor;
-                    let _ = self.hw_floor_sensor_tx.send(floor);
-                }
-            }
+        self.elevator.door_light(false);
+        self.elevator.stop_button_light(false);
 
-            // Check if obstruction is toggled
-            if self.elevator.obstruction() != self.obstruction {
-                self.obstruction = !self.obstruction;
-                let _ = self.hw_obstruction_tx.send(self.obstruction);
-            }
+        // The inputs are polled by a thread of their own, which stops with the driver
+        let (input_tx, input_rx) = cbc::unbounded::<Input>();
+        let (_stop_tx, stop_rx) = cbc::bounded::<()>(0);
+        let (elevator, poll_period) = (self.elevator.clone(), Duration::from_millis(self.thread_sleep_time));
+        let ready_tx = self.hw_ready_tx.take();
+        let poll_thread = Builder::new().name("elevator_poll".into());
+        poll_thread.spawn(move || poll_inputs(elevator, poll_period, input_tx, ready_tx, stop_rx)).unwrap();
 
-            // Check if any call buttons are pressed
-            for floor in 0..self.elevator.num_floors {
-                if !self.requests[floor as usize][HALL_UP as usize]
-                    && self.elevator.call_button(floor, HALL_UP)
-                {
-                    self.requests[floor as usize][HALL_UP as usize] = true;
-                    let _ = self.hw_request_tx.send((floor, HALL_UP));
-                }
-                if !self.requests[floor as usize][HALL_DOWN as usize]
-                    && self.elevator.call_button(floor, HALL_DOWN)
-                {
-                    self.requests[floor as usize][HALL_DOWN as usize] = true;
-                    let _ = self.hw_request_tx.send((floor, HALL_DOWN));
-                }
-                if !self.requests[floor as usize][CAB as usize]
-                    && self.elevator.call_button(floor, CAB)
-                {
-                    self.requests[floor as usize][CAB as usize] = true;
-                    let _ = self.hw_request_tx.send((floor, CAB));
-                }
+        // Main loop. The driver only wakes for the inputs, the commands and the changes of the panel pattern.
+        event_loop! {
+            self;
+            before => {
+                self.show_panel();
             }
-
-            // Handle incoming events
-            cbc::select! {
-                recv(self.hw_motor_direction_rx) -> msg => {
-                    match msg {
-                        Ok(msg) => self.elevator.motor_direction(msg),
-                        Err(error) => {
-                            error!("ERROR - hw_motor_direction_rx: {}", error);
-                            std::process::exit(1);
-                        }
+            recv(input_rx) -> input => {
+                match input {
+                    Input::Floor(floor) => {
+                        let _ = self.hw_floor_sensor_tx.send(floor);
                     }
-                }
-                recv(self.hw_button_light_rx) -> msg => {
-                    match msg {
-                        Ok(msg) => {
-                            self.elevator.call_button_light(msg.0, msg.1, msg.2);  // Turn off button lamp
-                            self.requests[msg.0 as usize][msg.1 as usize] = msg.2; // Make new calls possible
-                        }
-                        Err(error) => {
-                            error!("ERROR - hw_button_light_rx: {}", error);
-                            std::process::exit(1);
-                        }
+                    Input::Obstruction(obstruction) => {
+                        let _ = self.hw_obstruction_tx.send(obstruction);
                     }
-                }
-                recv(self.hw_door_light_rx) -> msg => {
-                    match msg {
-                        Ok(msg) => self.elevator.door_light(msg),
-                        Err(error) => {
-                            error!("ERROR - hw_door_light_rx: {}", error);
-                            std::process::exit(1);
+                    Input::CallButton(floor, call) => {
+                        if !self.requests[floor as usize][call as usize] {
+                            self.requests[floor as usize][call as usize] = true;
+                            let _ = self.hw_request_tx.send((floor, call));
                         }
                     }
-
                 }
-                recv(self.hw_floor_indicator_rx) -> msg => {
-                    match msg {
-                        Ok(msg) => self.elevator.floor_indicator(msg),
-                        Err(error) => {
-                            error!("ERROR - hw_floor_indicator_rx: {}", error);
-                            std::process::exit(1);
-                        }
-                    }
+            }
+            recv(self.hw_motor_direction_rx) -> msg => {
+                self.elevator.motor_direction(msg)
+            }
+            recv(self.hw_button_light_rx) -> msg => {
+                // A blinking cab lamp is set once the pattern ends
+                if msg.1 == CAB {
+                    self.cab_lights[msg.0 as usize] = msg.2;
                 }
-                recv(self.terminate_rx) -> _ => {
-                    break;
+                if msg.1 != CAB || self.shown_cab_lights.is_none() {
+                    self.elevator.call_button_light(msg.0, msg.1, msg.2);  // Turn off button lamp
                 }
-                default(Duration::from_millis(self.thread_sleep_time)) => {}
+                self.requests[msg.0 as usize][msg.1 as usize] = msg.2; // Make new calls possible
             }
+            recv(self.hw_door_light_rx) -> msg => {
+                self.door_light = msg
+            }
+            recv(self.hw_panel_signal_rx) -> msg => {
+                self.panel.set_signal(msg, Instant::now())
+            }
+            recv(self.hw_status_rx) -> msg => {
+                self.panel.set_status(msg, Instant::now())
+            }
+            recv(self.hw_floor_indicator_rx) -> msg => {
+                self.elevator.floor_indicator(msg)
+            }
+            tick(self.panel_change()) => {}
+        }
+    }
+
+    // The timer of the next change of the panel pattern, if any
+    fn panel_change(&self) -> cbc::Receiver<Instant> {
+        match self.panel.next_change(Instant::now()) {
+            Some(change) => cbc::at(change),
+            None => cbc::never(),
+        }
+    }
+
+    // Shows the lamps of the panel, blinking those overridden by a pattern. Only changes are written.
+    fn show_panel(&mut self) {
+        let now = Instant::now();
+        let (door_light, cab_lights) = self.panel.lamps(now);
+
+        let door_light = door_light.unwrap_or(self.door_light);
+        if door_light != self.shown_door_light {
+            self.elevator.door_light(door_light);
+            self.shown_door_light = door_light;
+        }
+
+        if cab_lights != self.shown_cab_lights {
+            for floor in 0..self.elevator.num_floors {
+                let lit = cab_lights.unwrap_or(self.cab_lights[floor as usize]);
+                self.elevator.call_button_light(floor, CAB, lit);
+            }
+            self.shown_cab_lights = cab_lights;
+        }
+
+        let stop_light = self.panel.status_lamp(now).unwrap_or(false);
+        if stop_light != self.shown_stop_light {
+            self.elevator.stop_button_light(stop_light);
+            self.shown_stop_light = stop_light;
+        }
+    }
+}
+
+impl EventLoop for ElevatorDriver {
+    fn terminate_rx(&self) -> &cbc::Receiver<Terminate> {
+        &self.terminate_rx
+    }
+
+    // Stops the motor and turns off the door and stop lamps, and acknowledges the termination if asked for it
+    fn terminate(&mut self, done: Option<Terminate>) -> Result<(), ModuleError> {
+        self.elevator.motor_direction(DIRN_STOP);
+        self.elevator.door_light(false);
+        self.elevator.stop_button_light(false);
+        if let Some(done) = done {
+            let _ = done.send(());
         }
+        Ok(())
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Polls the floor sensor, the obstruction switch and the call buttons every `period`, as the `poll` module of the
+// driver library, and sends the changes: the floor reached, the obstruction toggled and the buttons pressed. Reports
+// ready on `ready_tx` once every input is read, and stops once `stop_rx` is disconnected.
+fn poll_inputs(elevator: Elevator, period: Duration, input_tx: cbc::Sender<Input>, ready_tx: Option<cbc::Sender<()>>, stop_rx: cbc::Receiver<()>) {
+    let mut ready_tx = ready_tx;
+    let mut floor = u8::MAX;
+    let mut obstruction = elevator.obstruction();
+    let mut pressed = vec![vec![false; HW_NUM_REQUEST_TYPES]; elevator.num_floors as usize];
+    while stop_rx.recv_timeout(period) == Err(cbc::RecvTimeoutError::Timeout) {
+        if let Some(floor_hit) = elevator.floor_sensor().filter(|floor_hit| *floor_hit != floor) {
+            floor = floor_hit;
+            let _ = input_tx.send(Input::Floor(floor));
+        }
+
+        if elevator.obstruction() != obstruction {
+            obstruction = !obstruction;
+            let _ = input_tx.send(Input::Obstruction(obstruction));
+        }
+
+        for floor in 0..elevator.num_floors {
+            for call in [HALL_UP, HALL_DOWN, CAB] {
+                let was_pressed = pressed[floor as usize][call as usize];
+                pressed[floor as usize][call as usize] = elevator.call_button(floor, call);
+                if pressed[floor as usize][call as usize] && !was_pressed {
+                    let _ = input_tx.send(Input::CallButton(floor, call));
+                }
+            }
+        }
+
+        if let Some(ready_tx) = ready_tx.take() {
+            let _ = ready_tx.send(());
+        }
+    }
+}
+
+/***************************************/
+/*              Builder                */
+/***************************************/
+// Builds the driver with every channel attached by name. Every channel but `hw_ready_tx` and `terminate_rx` must be
+// attached.
+pub struct DriverBuilder {
+    hw_config: HardwareConfig,
+    panel_config: PanelConfig,
+    hw_motor_direction_rx: Option<cbc::Receiver<u8>>,
+    hw_button_light_rx: Option<cbc::Receiver<(u8, u8, bool)>>,
+    hw_request_tx: Option<cbc::Sender<(u8, u8)>>,
+    hw_floor_sensor_tx: Option<cbc::Sender<u8>>,
+    hw_floor_indicator_rx: Option<cbc::Receiver<u8>>,
+    hw_door_light_rx: Option<cbc::Receiver<bool>>,
+    hw_obstruction_tx: Option<cbc::Sender<bool>>,
+    hw_panel_signal_rx: Option<cbc::Receiver<PanelSignal>>,
+    hw_status_rx: Option<cbc::Receiver<SystemStatus>>,
+    hw_ready_tx: Option<cbc::Sender<()>>,
+    terminate_rx: cbc::Receiver<Terminate>,
+}
+
+impl DriverBuilder {
+    // A driver of the configured hardware, without panel patterns and with none of the channels
+    pub fn new(hw_config: &HardwareConfig) -> DriverBuilder {
+        DriverBuilder {
+            hw_config: hw_config.clone(),
+            panel_config: PanelConfig::default(),
+            hw_motor_direction_rx: None,
+            hw_button_light_rx: None,
+            hw_request_tx: None,
+            hw_floor_sensor_tx: None,
+            hw_floor_indicator_rx: None,
+            hw_door_light_rx: None,
+            hw_obstruction_tx: None,
+            hw_panel_signal_rx: None,
+            hw_status_rx: None,
+            hw_ready_tx: None,
+            terminate_rx: cbc::never(),
+        }
+    }
+
+    pub fn panel_config(mut self, panel_config: PanelConfig) -> DriverBuilder {
+        self.panel_config = panel_config;
+        self
+    }
+
+    pub fn hw_motor_direction_rx(mut self, hw_motor_direction_rx: cbc::Receiver<u8>) -> DriverBuilder {
+        self.hw_motor_direction_rx = Some(hw_motor_direction_rx);
+        self
+    }
+
+    pub fn hw_button_light_rx(mut self, hw_button_light_rx: cbc::Receiver<(u8, u8, bool)>) -> DriverBuilder {
+        self.hw_button_light_rx = Some(hw_button_light_rx);
+        self
+    }
+
+    pub fn hw_request_tx(mut self, hw_request_tx: cbc::Sender<(u8, u8)>) -> DriverBuilder {
+        self.hw_request_tx = Some(hw_request_tx);
+        self
+    }
+
+    pub fn hw_floor_sensor_tx(mut self, hw_floor_sensor_tx: cbc::Sender<u8>) -> DriverBuilder {
+        self.hw_floor_sensor_tx = Some(hw_floor_sensor_tx);
+        self
+    }
+
+    pub fn hw_floor_indicator_rx(mut self, hw_floor_indicator_rx: cbc::Receiver<u8>) -> DriverBuilder {
+        self.hw_floor_indicator_rx = Some(hw_floor_indicator_rx);
+        self
+    }
+
+    pub fn hw_door_light_rx(mut self, hw_door_light_rx: cbc::Receiver<bool>) -> DriverBuilder {
+        self.hw_door_light_rx = Some(hw_door_light_rx);
+        self
+    }
+
+    pub fn hw_obstruction_tx(mut self, hw_obstruction_tx: cbc::Sender<bool>) -> DriverBuilder {
+        self.hw_obstruction_tx = Some(hw_obstruction_tx);
+        self
+    }
+
+    pub fn hw_panel_signal_rx(mut self, hw_panel_signal_rx: cbc::Receiver<PanelSignal>) -> DriverBuilder {
+        self.hw_panel_signal_rx = Some(hw_panel_signal_rx);
+        self
+    }
+
+    pub fn hw_status_rx(mut self, hw_status_rx: cbc::Receiver<SystemStatus>) -> DriverBuilder {
+        self.hw_status_rx = Some(hw_status_rx);
+        self
+    }
+
+    pub fn hw_ready_tx(mut self, hw_ready_tx: cbc::Sender<()>) -> DriverBuilder {
+        self.hw_ready_tx = Some(hw_ready_tx);
+        self
+    }
+
+    pub fn terminate_rx(mut self, terminate_rx: cbc::Receiver<Terminate>) -> DriverBuilder {
+        self.terminate_rx = terminate_rx;
+        self
+    }
+
+    // The driver, connected to the hardware once every channel is attached
+    pub fn build(self) -> Result<ElevatorDriver, String> {
+        let address = format!("{}:{}", &self.hw_config.driver_address, &self.hw_config.driver_port);
+        Ok(ElevatorDriver {
+            thread_sleep_time: self.hw_config.hw_thread_sleep_time,
+            requests: vec![vec![false; HW_NUM_REQUEST_TYPES]; self.hw_config.n_floors as usize],
+            panel: PatternManager::new(&self.panel_config, Instant::now()),
+            door_light: false,
+            cab_lights: vec![false; self.hw_config.n_floors as usize],
+            shown_door_light: false,
+            shown_cab_lights: None,
+            shown_stop_light: false,
+            hw_motor_direction_rx: attached(self.hw_motor_direction_rx, "driver", "hw_motor_direction_rx")?,
+            hw_button_light_rx: attached(self.hw_button_light_rx, "driver", "hw_button_light_rx")?,
+            hw_request_tx: attached(self.hw_request_tx, "driver", "hw_request_tx")?,
+            hw_floor_sensor_tx: attached(self.hw_floor_sensor_tx, "driver", "hw_floor_sensor_tx")?,
+            hw_floor_indicator_rx: attached(self.hw_floor_indicator_rx, "driver", "hw_floor_indicator_rx")?,
+            hw_door_light_rx: attached(self.hw_door_light_rx, "driver", "hw_door_light_rx")?,
+            hw_obstruction_tx: attached(self.hw_obstruction_tx, "driver", "hw_obstruction_tx")?,
+            hw_panel_signal_rx: attached(self.hw_panel_signal_rx, "driver", "hw_panel_signal_rx")?,
+            hw_status_rx: attached(self.hw_status_rx, "driver", "hw_status_rx")?,
+            hw_ready_tx: self.hw_ready_tx,
+            terminate_rx: self.terminate_rx,
+            // Connected last, so a driver missing a channel never takes the hardware
+            elevator: Elevator::init(&address, self.hw_config.n_floors)
+                .map_err(|e| format!("Failed to connect to the elevator hardware at {}: {}", address, e))?,
+        })
     }
 }
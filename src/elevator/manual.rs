@@ -0,0 +1,283 @@
+/**
+ * Manual drive mode for bench-testing the hardware wiring.
+ *
+ * Keyboard commands, one per line, drive the motor, the door light, the floor indicator and the button lights
+ * directly, in place of the FSM and the coordinator. The commands go over the same hardware channels as in
+ * normal operation, so the driver is exercised exactly as it is then. Floor hits, button presses and the
+ * obstruction switch are printed as they happen. A pressed button is lit, and is reported again once its
+ * light has been toggled off. The motor is stopped at the end floors, so a car left running does not hit
+ * the end of the shaft.
+ *
+ * # Fields
+ * - `n_floors`:                The number of floors of the elevator.
+ * - `hw_motor_direction_tx`:   Sends motor direction commands.
+ * - `hw_button_light_tx`:      Sets the button lights.
+ * - `hw_floor_indicator_tx`:   Sets the floor indicator.
+ * - `hw_door_light_tx`:        Sets the door light.
+ * - `hw_floor_sensor_rx`:      Receives the floors the car arrives at.
+ * - `hw_request_rx`:           Receives button presses.
+ * - `hw_obstruction_rx`:       Receives changes of the obstruction switch.
+ * - `direction`:               The direction the motor was last set to.
+ * - `button_lights`:           The button lights, by floor and call, so they can be toggled.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{CAB, DIRN_DOWN, DIRN_STOP, DIRN_UP, HALL_DOWN, HALL_UP};
+use log::{info, error};
+use std::io::BufRead;
+use std::thread::Builder;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{exit, ExitReason};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const USAGE: &str = "Manual drive commands:
+  u | up                     Run the motor up
+  d | down                   Run the motor down
+  s | stop                   Stop the motor
+  o | open                   Turn the door light on
+  c | close                  Turn the door light off
+  l <floor> <up|down|cab>    Toggle a button light
+  f <floor>                  Set the floor indicator
+  q | quit                   Stop the motor and quit";
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManualCommand {
+    Motor(u8),
+    DoorLight(bool),
+    ToggleButtonLight(u8, u8),
+    FloorIndicator(u8),
+    Quit,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct ManualDrive {
+    n_floors: u8,
+    hw_motor_direction_tx: cbc::Sender<u8>,
+    hw_button_light_tx: cbc::Sender<(u8, u8, bool)>,
+    hw_floor_indicator_tx: cbc::Sender<u8>,
+    hw_door_light_tx: cbc::Sender<bool>,
+    hw_floor_sensor_rx: cbc::Receiver<u8>,
+    hw_request_rx: cbc::Receiver<(u8, u8)>,
+    hw_obstruction_rx: cbc::Receiver<bool>,
+    direction: u8,
+    button_lights: Vec<Vec<bool>>,
+}
+
+impl ManualDrive {
+    pub fn new(
+        n_floors: u8,
+        hw_motor_direction_tx: cbc::Sender<u8>,
+        hw_button_light_tx: cbc::Sender<(u8, u8, bool)>,
+        hw_floor_indicator_tx: cbc::Sender<u8>,
+        hw_door_light_tx: cbc::Sender<bool>,
+        hw_floor_sensor_rx: cbc::Receiver<u8>,
+        hw_request_rx: cbc::Receiver<(u8, u8)>,
+        hw_obstruction_rx: cbc::Receiver<bool>,
+    ) -> ManualDrive {
+        ManualDrive {
+            n_floors,
+            hw_motor_direction_tx,
+            hw_button_light_tx,
+            hw_floor_indicator_tx,
+            hw_door_light_tx,
+            hw_floor_sensor_rx,
+            hw_request_rx,
+            hw_obstruction_rx,
+            direction: DIRN_STOP,
+            button_lights: vec![vec![false; 3]; n_floors as usize],
+        }
+    }
+
+    pub fn run(mut self) {
+        // Standard input is read on a thread of its own, so hardware events are printed while waiting for a line
+        let (line_tx, line_rx) = cbc::unbounded::<String>();
+        let manual_input_thread = Builder::new().name("manual_input".into());
+        manual_input_thread
+            .spawn(move || {
+                for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                    if line_tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .unwrap();
+
+        println!("{}", USAGE);
+        loop {
+            cbc::select! {
+                recv(line_rx) -> line => {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => {
+                            info!("Manual drive input closed");
+                            self.handle_command(ManualCommand::Quit);
+                            return;
+                        }
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match parse_manual_command(&line) {
+                        Ok(ManualCommand::Quit) => {
+                            self.handle_command(ManualCommand::Quit);
+                            return;
+                        }
+                        Ok(command) => self.handle_command(command),
+                        Err(message) => println!("{}", message),
+                    }
+                }
+                recv(self.hw_floor_sensor_rx) -> floor => {
+                    match floor {
+                        Ok(floor) => self.handle_floor_hit(floor),
+                        Err(error) => {
+                            error!("ERROR - hw_floor_sensor_rx: {}", error);
+                            exit(ExitReason::Failure);
+                        }
+                    }
+                }
+                recv(self.hw_request_rx) -> request => {
+                    match request {
+                        Ok((floor, call)) => self.handle_button_press(floor, call),
+                        Err(error) => {
+                            error!("ERROR - hw_request_rx: {}", error);
+                            exit(ExitReason::Failure);
+                        }
+                    }
+                }
+                recv(self.hw_obstruction_rx) -> obstruction => {
+                    match obstruction {
+                        Ok(obstruction) => println!("Obstruction {}", if obstruction { "on" } else { "off" }),
+                        Err(error) => {
+                            error!("ERROR - hw_obstruction_rx: {}", error);
+                            exit(ExitReason::Failure);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: ManualCommand) {
+        match command {
+            ManualCommand::Motor(direction) => self.set_motor_direction(direction),
+            ManualCommand::DoorLight(on) => {
+                let _ = self.hw_door_light_tx.send(on);
+            }
+            ManualCommand::ToggleButtonLight(floor, call) => {
+                match self.button_lights.get_mut(floor as usize) {
+                    Some(lights) => {
+                        lights[call as usize] = !lights[call as usize];
+                        let _ = self.hw_button_light_tx.send((floor, call, lights[call as usize]));
+                    }
+                    None => println!("There is no floor {}", floor),
+                }
+            }
+            ManualCommand::FloorIndicator(floor) => {
+                if floor < self.n_floors {
+                    let _ = self.hw_floor_indicator_tx.send(floor);
+                } else {
+                    println!("There is no floor {}", floor);
+                }
+            }
+            ManualCommand::Quit => self.set_motor_direction(DIRN_STOP),
+        }
+    }
+
+    fn handle_floor_hit(&mut self, floor: u8) {
+        println!("Floor {}", floor);
+        let _ = self.hw_floor_indicator_tx.send(floor);
+
+        let at_end = (self.direction == DIRN_UP && floor + 1 >= self.n_floors) || (self.direction == DIRN_DOWN && floor == 0);
+        if at_end {
+            println!("End floor reached, stopping the motor");
+            self.set_motor_direction(DIRN_STOP);
+        }
+    }
+
+    // The light is lit, and the driver reports the button again once it has been toggled off
+    fn handle_button_press(&mut self, floor: u8, call: u8) {
+        let name = match call {
+            HALL_UP => "Hall up",
+            HALL_DOWN => "Hall down",
+            _ => "Cab",
+        };
+        println!("{} button pressed at floor {}", name, floor);
+
+        if let Some(lights) = self.button_lights.get_mut(floor as usize) {
+            lights[call as usize] = true;
+            let _ = self.hw_button_light_tx.send((floor, call, true));
+        }
+    }
+
+    fn set_motor_direction(&mut self, direction: u8) {
+        self.direction = direction;
+        let _ = self.hw_motor_direction_tx.send(direction);
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Parses a manual drive line, returning the usage on unknown input
+pub fn parse_manual_command(line: &str) -> Result<ManualCommand, String> {
+    let words = line.split_whitespace().collect::<Vec<&str>>();
+
+    match words.as_slice() {
+        ["u" | "up"] => Ok(ManualCommand::Motor(DIRN_UP)),
+        ["d" | "down"] => Ok(ManualCommand::Motor(DIRN_DOWN)),
+        ["s" | "stop"] => Ok(ManualCommand::Motor(DIRN_STOP)),
+        ["o" | "open"] => Ok(ManualCommand::DoorLight(true)),
+        ["c" | "close"] => Ok(ManualCommand::DoorLight(false)),
+        ["q" | "quit"] => Ok(ManualCommand::Quit),
+        ["l", floor, call] => {
+            let floor = floor.parse::<u8>().map_err(|_| format!("Invalid floor '{}'\n{}", floor, USAGE))?;
+            let call = match *call {
+                "up" => HALL_UP,
+                "down" => HALL_DOWN,
+                "cab" => CAB,
+                _ => return Err(format!("Unknown button '{}'\n{}", call, USAGE)),
+            };
+            Ok(ManualCommand::ToggleButtonLight(floor, call))
+        }
+        ["f", floor] => {
+            let floor = floor.parse::<u8>().map_err(|_| format!("Invalid floor '{}'\n{}", floor, USAGE))?;
+            Ok(ManualCommand::FloorIndicator(floor))
+        }
+        _ => Err(format!("Unknown command '{}'\n{}", line.trim(), USAGE)),
+    }
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::{ManualCommand, ManualDrive};
+
+    impl ManualDrive {
+        pub fn test_handle_command(&mut self, command: ManualCommand) {
+            self.handle_command(command)
+        }
+
+        pub fn test_handle_floor_hit(&mut self, floor: u8) {
+            self.handle_floor_hit(floor)
+        }
+
+        pub fn test_handle_button_press(&mut self, floor: u8, call: u8) {
+            self.handle_button_press(floor, call)
+        }
+    }
+}
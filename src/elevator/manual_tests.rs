@@ -0,0 +1,126 @@
+/*
+ * Unit tests for the manual drive mode
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_manual_parse_command
+ *  - test_manual_drive_commands
+ *  - test_manual_drive_hardware_events
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod manual_tests {
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use driver_rust::elevio::elev::{CAB, DIRN_DOWN, DIRN_STOP, DIRN_UP, HALL_DOWN, HALL_UP};
+    use crate::elevator::manual::{parse_manual_command, ManualCommand, ManualDrive};
+
+    const N_FLOORS: u8 = 4;
+
+    struct Hardware {
+        motor_direction_rx: Receiver<u8>,
+        button_light_rx: Receiver<(u8, u8, bool)>,
+        floor_indicator_rx: Receiver<u8>,
+        door_light_rx: Receiver<bool>,
+        _floor_sensor_tx: Sender<u8>,
+        _request_tx: Sender<(u8, u8)>,
+        _obstruction_tx: Sender<bool>,
+    }
+
+    fn setup_manual_drive() -> (ManualDrive, Hardware) {
+        let (motor_direction_tx, motor_direction_rx) = unbounded::<u8>();
+        let (button_light_tx, button_light_rx) = unbounded::<(u8, u8, bool)>();
+        let (floor_indicator_tx, floor_indicator_rx) = unbounded::<u8>();
+        let (door_light_tx, door_light_rx) = unbounded::<bool>();
+        let (floor_sensor_tx, floor_sensor_rx) = unbounded::<u8>();
+        let (request_tx, request_rx) = unbounded::<(u8, u8)>();
+        let (obstruction_tx, obstruction_rx) = unbounded::<bool>();
+
+        let manual_drive = ManualDrive::new(
+            N_FLOORS,
+            motor_direction_tx,
+            button_light_tx,
+            floor_indicator_tx,
+            door_light_tx,
+            floor_sensor_rx,
+            request_rx,
+            obstruction_rx,
+        );
+        let hardware = Hardware {
+            motor_direction_rx,
+            button_light_rx,
+            floor_indicator_rx,
+            door_light_rx,
+            _floor_sensor_tx: floor_sensor_tx,
+            _request_tx: request_tx,
+            _obstruction_tx: obstruction_tx,
+        };
+        (manual_drive, hardware)
+    }
+
+    #[test]
+    fn test_manual_parse_command() {
+        // Act / Assert
+        assert_eq!(parse_manual_command("u"), Ok(ManualCommand::Motor(DIRN_UP)));
+        assert_eq!(parse_manual_command("down"), Ok(ManualCommand::Motor(DIRN_DOWN)));
+        assert_eq!(parse_manual_command(" s "), Ok(ManualCommand::Motor(DIRN_STOP)));
+        assert_eq!(parse_manual_command("open"), Ok(ManualCommand::DoorLight(true)));
+        assert_eq!(parse_manual_command("c"), Ok(ManualCommand::DoorLight(false)));
+        assert_eq!(parse_manual_command("l 2 down"), Ok(ManualCommand::ToggleButtonLight(2, HALL_DOWN)));
+        assert_eq!(parse_manual_command("l 0 cab"), Ok(ManualCommand::ToggleButtonLight(0, CAB)));
+        assert_eq!(parse_manual_command("f 3"), Ok(ManualCommand::FloorIndicator(3)));
+        assert_eq!(parse_manual_command("q"), Ok(ManualCommand::Quit));
+        assert!(parse_manual_command("l 2 sideways").is_err());
+        assert!(parse_manual_command("f top").is_err());
+        assert!(parse_manual_command("fly").is_err());
+    }
+
+    #[test]
+    fn test_manual_drive_commands() {
+        // Arrange
+        let (mut manual_drive, hardware) = setup_manual_drive();
+
+        // Act
+        manual_drive.test_handle_command(ManualCommand::Motor(DIRN_UP));
+        manual_drive.test_handle_command(ManualCommand::DoorLight(true));
+        manual_drive.test_handle_command(ManualCommand::ToggleButtonLight(1, HALL_UP));
+        manual_drive.test_handle_command(ManualCommand::ToggleButtonLight(1, HALL_UP));
+        manual_drive.test_handle_command(ManualCommand::ToggleButtonLight(N_FLOORS, HALL_UP));
+        manual_drive.test_handle_command(ManualCommand::FloorIndicator(2));
+        manual_drive.test_handle_command(ManualCommand::FloorIndicator(N_FLOORS));
+        manual_drive.test_handle_command(ManualCommand::Quit);
+
+        // Assert
+        assert_eq!(hardware.motor_direction_rx.try_iter().collect::<Vec<u8>>(), vec![DIRN_UP, DIRN_STOP]);
+        assert_eq!(hardware.door_light_rx.try_recv(), Ok(true));
+        // Floors outside the building are refused
+        assert_eq!(hardware.button_light_rx.try_iter().collect::<Vec<_>>(), vec![(1, HALL_UP, true), (1, HALL_UP, false)]);
+        assert_eq!(hardware.floor_indicator_rx.try_iter().collect::<Vec<u8>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_manual_drive_hardware_events() {
+        // Arrange
+        let (mut manual_drive, hardware) = setup_manual_drive();
+        manual_drive.test_handle_command(ManualCommand::Motor(DIRN_UP));
+        let _ = hardware.motor_direction_rx.try_iter().count();
+
+        // Act
+        manual_drive.test_handle_floor_hit(N_FLOORS - 2);
+        let motor_before_end = hardware.motor_direction_rx.try_recv();
+        manual_drive.test_handle_floor_hit(N_FLOORS - 1);
+        manual_drive.test_handle_button_press(0, CAB);
+
+        // Assert
+        // The floor indicator follows the car, and the motor stops at the top floor
+        assert!(motor_before_end.is_err());
+        assert_eq!(hardware.floor_indicator_rx.try_iter().collect::<Vec<u8>>(), vec![N_FLOORS - 2, N_FLOORS - 1]);
+        assert_eq!(hardware.motor_direction_rx.try_recv(), Ok(DIRN_STOP));
+        // A pressed button is lit
+        assert_eq!(hardware.button_light_rx.try_recv(), Ok((0, CAB, true)));
+    }
+}
@@ -1,7 +1,14 @@
 pub mod fsm;
 pub mod hardware;
+pub mod manual;
+pub mod manual_tests;
 pub mod fsm_tests;
-pub mod cab_orders;
+pub mod statistics;
+pub mod panel;
+pub mod panel_tests;
+pub mod self_test;
+pub mod self_test_tests;
 
-pub use fsm::ElevatorFSM;
-pub use hardware::ElevatorDriver;
\ No newline at end of file
+pub use fsm::{ElevatorFSM, FsmBuilder};
+pub use hardware::{DriverBuilder, ElevatorDriver};
+pub use manual::ManualDrive;
\ No newline at end of file
@@ -0,0 +1,176 @@
+/**
+ * Blink patterns of the car panel.
+ *
+ * The panel is all passengers see of the elevator, so faults and special modes are signalled on it by blinking
+ * the door lamp, and the cab button lamps for the patterns that include them. The FSM sends the signal of its
+ * state to the driver whenever it changes, and the driver asks the pattern manager which lamps to override, and
+ * when they next change, so it only wakes for them while a pattern is shown. Lamps not overridden show what the FSM and the coordinator last set them to.
+ *
+ * Each pattern starts with the lamps lit when its signal is raised. A pattern with no time on leaves the lamps
+ * alone, and so do all of them if the panel is disabled.
+ *
+ * The stop lamp, which nothing else lights, shows the status of the system as a whole, as reported by the health
+ * monitor, so whoever stands at the elevator can tell its mode without a laptop. It blinks a code over and over: once
+ * while connected, twice while offline, three times in error and four times in maintenance, with a pause between the
+ * codes. The code starts over when the status changes.
+ *
+ * # Fields
+ * - `enabled`:                 Whether the lamps are blinked at all.
+ * - `blocked`:                 The pattern while the door is blocked, or the obstruction alarm is raised.
+ * - `error`:                   The pattern while the car is in error, such as after a motor loss.
+ * - `maintenance`:             The pattern while the car is in independent service, or halted.
+ * - `fire`:                    The pattern during fire service.
+ * - `status_code`:             How long each blink of the status code, and the pause after it, lasts.
+ * - `signal`:                  The signal shown.
+ * - `raised`:                  When the signal was raised. The pattern is timed from it.
+ * - `status`:                  The status of the system shown.
+ * - `status_raised`:           When the status was last changed. The code is timed from it.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::{BlinkPattern, PanelConfig, StatusCode};
+use crate::shared::{Behaviour, DoorState, ElevatorState, OperatingMode, SystemStatus};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PanelSignal {
+    Normal,
+    Blocked,
+    Error,
+    Maintenance,
+    Fire,
+}
+
+impl PanelSignal {
+    // The signal of a state. Fire service is shown over faults, as it is what passengers must act on.
+    pub fn of(state: &ElevatorState) -> PanelSignal {
+        match state.mode {
+            OperatingMode::FireRecall(_) | OperatingMode::FirePhase2 => PanelSignal::Fire,
+            _ if state.door == DoorState::Blocked || state.obstruction_alarm => PanelSignal::Blocked,
+            _ if state.behaviour == Behaviour::Error => PanelSignal::Error,
+            OperatingMode::Independent => PanelSignal::Maintenance,
+            _ if state.halted => PanelSignal::Maintenance,
+            _ => PanelSignal::Normal,
+        }
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct PatternManager {
+    enabled: bool,
+    blocked: BlinkPattern,
+    error: BlinkPattern,
+    maintenance: BlinkPattern,
+    fire: BlinkPattern,
+    status_code: StatusCode,
+    signal: PanelSignal,
+    raised: Instant,
+    status: SystemStatus,
+    status_raised: Instant,
+}
+
+impl PatternManager {
+    pub fn new(panel_config: &PanelConfig, now: Instant) -> PatternManager {
+        PatternManager {
+            enabled: panel_config.enabled,
+            blocked: panel_config.blocked,
+            error: panel_config.error,
+            maintenance: panel_config.maintenance,
+            fire: panel_config.fire,
+            status_code: panel_config.status,
+            signal: PanelSignal::Normal,
+            raised: now,
+            status: SystemStatus::default(),
+            status_raised: now,
+        }
+    }
+
+    pub fn set_signal(&mut self, signal: PanelSignal, now: Instant) {
+        if signal != self.signal {
+            self.signal = signal;
+            self.raised = now;
+        }
+    }
+
+    pub fn set_status(&mut self, status: SystemStatus, now: Instant) {
+        if status != self.status {
+            self.status = status;
+            self.status_raised = now;
+        }
+    }
+
+    // The lamps overridden at `now`, as whether the door lamp and the cab button lamps are lit. `None` leaves
+    // the lamps as they were set.
+    pub fn lamps(&self, now: Instant) -> (Option<bool>, Option<bool>) {
+        let Some(pattern) = self.pattern() else {
+            return (None, None);
+        };
+
+        let period = pattern.on + pattern.off;
+        let elapsed = now.saturating_duration_since(self.raised).as_millis() as u64;
+        let lit = elapsed % period < pattern.on;
+        (Some(lit), pattern.buttons.then_some(lit))
+    }
+
+    // Whether the code of the status lights the stop lamp at `now`. `None` while no code is shown.
+    pub fn status_lamp(&self, now: Instant) -> Option<bool> {
+        let (blinks, cycle) = self.code()?;
+        let phase = now.saturating_duration_since(self.status_raised).as_millis() as u64 % cycle;
+        Some(phase < blinks * 2 * self.status_code.blink && (phase / self.status_code.blink).is_multiple_of(2))
+    }
+
+    // When the lamps or the stop lamp next change after `now`, or `None` while neither a pattern nor a code is shown
+    pub fn next_change(&self, now: Instant) -> Option<Instant> {
+        [self.pattern_change(now), self.code_change(now)].into_iter().flatten().min()
+    }
+
+    fn pattern_change(&self, now: Instant) -> Option<Instant> {
+        let pattern = self.pattern()?;
+        let period = pattern.on + pattern.off;
+        let phase = now.saturating_duration_since(self.raised).as_millis() as u64 % period;
+        let remaining = if phase < pattern.on { pattern.on - phase } else { period - phase };
+        Some(now + Duration::from_millis(remaining))
+    }
+
+    fn pattern(&self) -> Option<BlinkPattern> {
+        let pattern = match self.signal {
+            PanelSignal::Normal => return None,
+            PanelSignal::Blocked => self.blocked,
+            PanelSignal::Error => self.error,
+            PanelSignal::Maintenance => self.maintenance,
+            PanelSignal::Fire => self.fire,
+        };
+        (self.enabled && pattern.on > 0).then_some(pattern)
+    }
+
+    fn code_change(&self, now: Instant) -> Option<Instant> {
+        let (blinks, cycle) = self.code()?;
+        let blink = self.status_code.blink;
+        let phase = now.saturating_duration_since(self.status_raised).as_millis() as u64 % cycle;
+        let remaining = if phase < blinks * 2 * blink { blink - phase % blink } else { cycle - phase };
+        Some(now + Duration::from_millis(remaining))
+    }
+
+    // The blinks of the code of the status, and how long the code lasts with its pause
+    fn code(&self) -> Option<(u64, u64)> {
+        let blinks = match self.status {
+            SystemStatus::Connected => 1,
+            SystemStatus::Offline => 2,
+            SystemStatus::Error => 3,
+            SystemStatus::Maintenance => 4,
+        };
+        let cycle = blinks * 2 * self.status_code.blink + self.status_code.pause;
+        (self.enabled && self.status_code.blink > 0).then_some((blinks, cycle))
+    }
+}
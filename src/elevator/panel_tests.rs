@@ -0,0 +1,142 @@
+/*
+ * Unit tests for the blink patterns of the car panel
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_panel_signal_of_state
+ *  - test_panel_blink_pattern
+ *  - test_panel_next_change
+ *  - test_panel_status_code
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod panel_tests {
+    use std::time::{Duration, Instant};
+    use crate::config::{BlinkPattern, PanelConfig, StatusCode};
+    use crate::elevator::panel::{PanelSignal, PatternManager};
+    use crate::shared::{Behaviour, DoorState, ElevatorState, OperatingMode, SystemStatus};
+
+    fn panel_config(enabled: bool) -> PanelConfig {
+        PanelConfig {
+            enabled,
+            blocked: BlinkPattern { on: 500, off: 500, buttons: false },
+            error: BlinkPattern { on: 100, off: 300, buttons: true },
+            maintenance: BlinkPattern { on: 0, off: 1000, buttons: false },
+            fire: BlinkPattern { on: 250, off: 250, buttons: true },
+            status: StatusCode { blink: 0, pause: 0 },
+        }
+    }
+
+    #[test]
+    fn test_panel_signal_of_state() {
+        // Arrange
+        let normal = ElevatorState::new(4);
+        let blocked = ElevatorState { door: DoorState::Blocked, ..normal.clone() };
+        let alarm = ElevatorState { behaviour: Behaviour::Error, obstruction_alarm: true, ..normal.clone() };
+        let motor_loss = ElevatorState { behaviour: Behaviour::Error, ..normal.clone() };
+        let independent = ElevatorState { mode: OperatingMode::Independent, ..normal.clone() };
+        let halted = ElevatorState { halted: true, ..normal.clone() };
+        let fire = ElevatorState { mode: OperatingMode::FireRecall(0), door: DoorState::Blocked, ..normal.clone() };
+
+        // Act / Assert
+        assert_eq!(PanelSignal::of(&normal), PanelSignal::Normal);
+        assert_eq!(PanelSignal::of(&blocked), PanelSignal::Blocked);
+        assert_eq!(PanelSignal::of(&alarm), PanelSignal::Blocked);
+        assert_eq!(PanelSignal::of(&motor_loss), PanelSignal::Error);
+        assert_eq!(PanelSignal::of(&independent), PanelSignal::Maintenance);
+        assert_eq!(PanelSignal::of(&halted), PanelSignal::Maintenance);
+        // Fire service is shown over faults
+        assert_eq!(PanelSignal::of(&fire), PanelSignal::Fire);
+    }
+
+    #[test]
+    fn test_panel_blink_pattern() {
+        // Arrange
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut panel = PatternManager::new(&panel_config(true), start);
+        let mut disabled = PatternManager::new(&panel_config(false), start);
+
+        // Act
+        let normal = panel.lamps(at(0));
+        panel.set_signal(PanelSignal::Error, at(1000));
+        let error = [1000, 1099, 1100, 1399, 1400].map(|ms| panel.lamps(at(ms)));
+        panel.set_signal(PanelSignal::Error, at(1200));
+        let unchanged = panel.lamps(at(1400));
+        panel.set_signal(PanelSignal::Blocked, at(2000));
+        let blocked = [2000, 2500].map(|ms| panel.lamps(at(ms)));
+        panel.set_signal(PanelSignal::Maintenance, at(3000));
+        let maintenance = panel.lamps(at(3000));
+        disabled.set_signal(PanelSignal::Fire, at(0));
+        let fire_disabled = disabled.lamps(at(0));
+
+        // Assert
+        assert_eq!(normal, (None, None));
+        // The pattern starts lit when raised, and the cab lamps blink with the door lamp
+        assert_eq!(
+            error,
+            [(Some(true), Some(true)), (Some(true), Some(true)), (Some(false), Some(false)), (Some(false), Some(false)), (Some(true), Some(true))]
+        );
+        // Raising the signal shown does not restart its pattern
+        assert_eq!(unchanged, (Some(true), Some(true)));
+        assert_eq!(blocked, [(Some(true), None), (Some(false), None)]);
+        // A pattern with no time on, and a disabled panel, leave the lamps alone
+        assert_eq!(maintenance, (None, None));
+        assert_eq!(fire_disabled, (None, None));
+    }
+
+    #[test]
+    fn test_panel_next_change() {
+        // Arrange
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut panel = PatternManager::new(&panel_config(true), start);
+
+        // Act
+        let normal = panel.next_change(at(0));
+        panel.set_signal(PanelSignal::Error, at(1000));
+        let error = [1000, 1099, 1100, 1399].map(|ms| panel.next_change(at(ms)));
+        panel.set_signal(PanelSignal::Maintenance, at(2000));
+        let maintenance = panel.next_change(at(2000));
+
+        // Assert
+        // The driver is woken as the lamps go off and on again, and not at all without a pattern
+        assert_eq!(normal, None);
+        assert_eq!(error, [Some(at(1100)), Some(at(1100)), Some(at(1400)), Some(at(1400))]);
+        assert_eq!(maintenance, None);
+    }
+
+    #[test]
+    fn test_panel_status_code() {
+        // Arrange
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let config = PanelConfig { status: StatusCode { blink: 200, pause: 1000 }, ..panel_config(true) };
+        let mut panel = PatternManager::new(&config, start);
+        let unconfigured = PatternManager::new(&panel_config(true), start);
+
+        // Act
+        // Offline blinks twice, over 800 ms, and pauses until 1800 ms
+        let offline = [0, 199, 200, 400, 600, 799, 1799, 1800].map(|ms| panel.status_lamp(at(ms)));
+        let offline_changes = [0, 700, 800].map(|ms| panel.next_change(at(ms)));
+        panel.set_status(SystemStatus::Connected, at(2000));
+        let connected = [2000, 2200, 2400].map(|ms| panel.status_lamp(at(ms)));
+        panel.set_signal(PanelSignal::Error, at(2000));
+        let with_pattern = panel.next_change(at(2050));
+
+        // Assert
+        assert_eq!(offline, [true, true, false, true, false, false, false, true].map(Some));
+        assert_eq!(offline_changes, [Some(at(200)), Some(at(800)), Some(at(1800))]);
+        // The code starts over when the status changes
+        assert_eq!(connected, [true, false, false].map(Some));
+        // The driver is woken for whichever changes first, the pattern or the code
+        assert_eq!(with_pattern, Some(at(2100)));
+        assert_eq!(unconfigured.status_lamp(at(0)), None);
+        assert_eq!(unconfigured.next_change(at(0)), None);
+    }
+}
@@ -0,0 +1,197 @@
+/**
+ * The self-test of the hardware, run at start-up before the elevator joins the fleet.
+ *
+ * Every lamp of the panel is lit in turn for `lamp_time` milliseconds and turned off again: the hall and cab button
+ * lamps floor by floor, the floor indicator at every floor, the door lamp and the stop lamp. Whoever stands at the
+ * panel sees a lamp that stays dark. Every button channel is read before and after the lamps are cycled, and a
+ * button read as pressed both times is stuck, as with a shorted or dead panel. Finally the car descends to a floor
+ * if it is between floors, and the floor sensor must report a floor within the motor timeout. The car is stopped
+ * at the floor, or where it is if the sensor never responds.
+ *
+ * The result is a `SelfTestReport`, passed if no button is stuck and the floor sensor responded.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{Elevator, CAB, DIRN_DOWN, DIRN_STOP, HALL_DOWN, HALL_UP};
+use serde::Serialize;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::SelfTestConfig;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const SENSOR_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// The parts of the hardware the self-test drives and reads, as named by the driver library
+pub trait TestedHardware {
+    fn num_floors(&self) -> u8;
+    fn call_button_light(&self, floor: u8, call: u8, on: bool);
+    fn floor_indicator(&self, floor: u8);
+    fn door_light(&self, on: bool);
+    fn stop_button_light(&self, on: bool);
+    fn call_button(&self, floor: u8, call: u8) -> bool;
+    fn floor_sensor(&self) -> Option<u8>;
+    fn motor_direction(&self, direction: u8);
+}
+
+impl TestedHardware for Elevator {
+    fn num_floors(&self) -> u8 {
+        self.num_floors
+    }
+
+    fn call_button_light(&self, floor: u8, call: u8, on: bool) {
+        Elevator::call_button_light(self, floor, call, on)
+    }
+
+    fn floor_indicator(&self, floor: u8) {
+        Elevator::floor_indicator(self, floor)
+    }
+
+    fn door_light(&self, on: bool) {
+        Elevator::door_light(self, on)
+    }
+
+    fn stop_button_light(&self, on: bool) {
+        Elevator::stop_button_light(self, on)
+    }
+
+    fn call_button(&self, floor: u8, call: u8) -> bool {
+        Elevator::call_button(self, floor, call)
+    }
+
+    fn floor_sensor(&self) -> Option<u8> {
+        Elevator::floor_sensor(self)
+    }
+
+    fn motor_direction(&self, direction: u8) {
+        Elevator::motor_direction(self, direction)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    #[serde(rename = "lampsCycled")]
+    pub lamps_cycled: usize,
+    #[serde(rename = "buttonsRead")]
+    pub buttons_read: usize,
+    // The floor and call of every button read as pressed throughout the test
+    #[serde(rename = "stuckButtons")]
+    pub stuck_buttons: Vec<(u8, u8)>,
+    // The floor the sensor reported, None if it never responded
+    #[serde(rename = "floorSensor")]
+    pub floor_sensor: Option<u8>,
+    // Milliseconds the car descended before the floor sensor responded
+    #[serde(rename = "descentTime")]
+    pub descent_time: u64,
+    pub failures: Vec<String>,
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Tests the hardware, allowing the floor sensor `descent_timeout` to respond
+pub fn self_test(hardware: &dyn TestedHardware, config: &SelfTestConfig, descent_timeout: Duration) -> SelfTestReport {
+    let buttons = buttons(hardware.num_floors());
+    let pressed_before = buttons.iter().map(|(floor, call)| hardware.call_button(*floor, *call)).collect::<Vec<bool>>();
+    let lamps_cycled = cycle_lamps(hardware, &buttons, Duration::from_millis(config.lamp_time));
+    let stuck_buttons = buttons
+        .iter()
+        .zip(pressed_before)
+        .filter(|((floor, call), pressed_before)| *pressed_before && hardware.call_button(*floor, *call))
+        .map(|(button, _)| *button)
+        .collect::<Vec<(u8, u8)>>();
+
+    let started = Instant::now();
+    let floor_sensor = descend(hardware, descent_timeout);
+    let descent_time = started.elapsed().as_millis() as u64;
+
+    let mut failures = stuck_buttons
+        .iter()
+        .map(|(floor, call)| format!("The {} button at floor {} is stuck", call_name(*call), floor))
+        .collect::<Vec<String>>();
+    match floor_sensor {
+        Some(floor) if floor >= hardware.num_floors() => failures.push(format!("The floor sensor reports floor {}, out of range", floor)),
+        Some(_) => {}
+        None => failures.push(format!("The floor sensor did not respond within {} ms of descent", descent_timeout.as_millis())),
+    }
+
+    SelfTestReport {
+        passed: failures.is_empty(),
+        lamps_cycled,
+        buttons_read: buttons.len(),
+        stuck_buttons,
+        floor_sensor,
+        descent_time,
+        failures,
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The buttons of the panel, without the hall down button at the bottom floor and the hall up button at the top
+fn buttons(n_floors: u8) -> Vec<(u8, u8)> {
+    (0..n_floors)
+        .flat_map(|floor| [HALL_UP, HALL_DOWN, CAB].map(|call| (floor, call)))
+        .filter(|(floor, call)| match *call {
+            HALL_DOWN => *floor > 0,
+            HALL_UP => *floor + 1 < n_floors,
+            _ => true,
+        })
+        .collect()
+}
+
+// Lights every lamp in turn, and returns the number of lamps lit
+fn cycle_lamps(hardware: &dyn TestedHardware, buttons: &[(u8, u8)], lamp_time: Duration) -> usize {
+    for (floor, call) in buttons {
+        hardware.call_button_light(*floor, *call, true);
+        thread::sleep(lamp_time);
+        hardware.call_button_light(*floor, *call, false);
+    }
+    for floor in 0..hardware.num_floors() {
+        hardware.floor_indicator(floor);
+        thread::sleep(lamp_time);
+    }
+    hardware.door_light(true);
+    thread::sleep(lamp_time);
+    hardware.door_light(false);
+    hardware.stop_button_light(true);
+    thread::sleep(lamp_time);
+    hardware.stop_button_light(false);
+    buttons.len() + hardware.num_floors() as usize + 2
+}
+
+// Descends until the floor sensor reports a floor, and stops. None if it does not within the timeout.
+fn descend(hardware: &dyn TestedHardware, timeout: Duration) -> Option<u8> {
+    if let Some(floor) = hardware.floor_sensor() {
+        return Some(floor);
+    }
+    let started = Instant::now();
+    hardware.motor_direction(DIRN_DOWN);
+    let mut floor = None;
+    while floor.is_none() && started.elapsed() < timeout {
+        thread::sleep(SENSOR_POLL_INTERVAL);
+        floor = hardware.floor_sensor();
+    }
+    hardware.motor_direction(DIRN_STOP);
+    floor
+}
+
+fn call_name(call: u8) -> &'static str {
+    match call {
+        HALL_UP => "hall up",
+        HALL_DOWN => "hall down",
+        _ => "cab",
+    }
+}
@@ -0,0 +1,133 @@
+/*
+ * Unit tests for the self-test of the hardware
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_self_test_passed
+ *  - test_self_test_stuck_button
+ *  - test_self_test_dead_floor_sensor
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod self_test_tests {
+    use crate::config::SelfTestConfig;
+    use crate::elevator::self_test::{self_test, TestedHardware};
+    use driver_rust::elevio::elev::{CAB, DIRN_DOWN, DIRN_STOP, HALL_UP};
+    use std::cell::{Cell, RefCell};
+    use std::time::Duration;
+
+    // Hardware reaching a floor after `reads_to_floor` reads of the sensor, with the given buttons held down
+    struct FakeHardware {
+        reads_to_floor: Option<usize>,
+        held: Vec<(u8, u8)>,
+        sensor_reads: Cell<usize>,
+        lamps_lit: Cell<usize>,
+        motor: RefCell<Vec<u8>>,
+    }
+
+    impl FakeHardware {
+        fn new(reads_to_floor: Option<usize>, held: Vec<(u8, u8)>) -> FakeHardware {
+            FakeHardware { reads_to_floor, held, sensor_reads: Cell::new(0), lamps_lit: Cell::new(0), motor: RefCell::new(Vec::new()) }
+        }
+    }
+
+    impl TestedHardware for FakeHardware {
+        fn num_floors(&self) -> u8 {
+            4
+        }
+
+        fn call_button_light(&self, _floor: u8, _call: u8, on: bool) {
+            self.lamps_lit.set(self.lamps_lit.get() + on as usize);
+        }
+
+        fn floor_indicator(&self, _floor: u8) {
+            self.lamps_lit.set(self.lamps_lit.get() + 1);
+        }
+
+        fn door_light(&self, on: bool) {
+            self.lamps_lit.set(self.lamps_lit.get() + on as usize);
+        }
+
+        fn stop_button_light(&self, on: bool) {
+            self.lamps_lit.set(self.lamps_lit.get() + on as usize);
+        }
+
+        fn call_button(&self, floor: u8, call: u8) -> bool {
+            self.held.contains(&(floor, call))
+        }
+
+        fn floor_sensor(&self) -> Option<u8> {
+            self.sensor_reads.set(self.sensor_reads.get() + 1);
+            self.reads_to_floor.filter(|reads| self.sensor_reads.get() > *reads).map(|_| 1)
+        }
+
+        fn motor_direction(&self, direction: u8) {
+            self.motor.borrow_mut().push(direction);
+        }
+    }
+
+    fn config() -> SelfTestConfig {
+        SelfTestConfig { enabled: true, lamp_time: 0, required: false }
+    }
+
+    #[test]
+    fn test_self_test_passed() {
+        // Arrange
+        let between_floors = FakeHardware::new(Some(3), vec![]);
+        let at_floor = FakeHardware::new(Some(0), vec![]);
+
+        // Act
+        let report = self_test(&between_floors, &config(), Duration::from_secs(1));
+        let at_floor_report = self_test(&at_floor, &config(), Duration::from_secs(1));
+
+        // Assert
+        // 4 floors have 10 buttons, 4 floor indicators, the door lamp and the stop lamp
+        assert!(report.passed, "{:?}", report.failures);
+        assert_eq!((report.lamps_cycled, report.buttons_read), (16, 10));
+        assert_eq!(between_floors.lamps_lit.get(), 16);
+        assert_eq!(report.floor_sensor, Some(1));
+        assert_eq!(*between_floors.motor.borrow(), vec![DIRN_DOWN, DIRN_STOP]);
+        // A car already at a floor does not move
+        assert!(at_floor_report.passed);
+        assert!(at_floor.motor.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_self_test_stuck_button() {
+        // Arrange
+        let hardware = FakeHardware::new(Some(0), vec![(2, CAB), (0, HALL_UP)]);
+
+        // Act
+        let report = self_test(&hardware, &config(), Duration::from_secs(1));
+
+        // Assert
+        assert!(!report.passed);
+        assert_eq!(report.stuck_buttons, vec![(0, HALL_UP), (2, CAB)]);
+        assert_eq!(report.failures.len(), 2);
+        assert!(report.failures[1].contains("cab button at floor 2"), "{:?}", report.failures);
+    }
+
+    #[test]
+    fn test_self_test_dead_floor_sensor() {
+        // Arrange
+        let hardware = FakeHardware::new(None, vec![]);
+
+        // Act
+        let report = self_test(&hardware, &config(), Duration::from_millis(50));
+
+        // Assert
+        // The motor is stopped where the car is
+        assert!(!report.passed);
+        assert_eq!(report.floor_sensor, None);
+        assert!(report.descent_time >= 50);
+        assert_eq!(*hardware.motor.borrow(), vec![DIRN_DOWN, DIRN_STOP]);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["floorSensor"], serde_json::Value::Null);
+        assert_eq!(json["passed"], false);
+    }
+}
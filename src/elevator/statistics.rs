@@ -0,0 +1,35 @@
+/***************************************/
+/*        3rd party libraries          */
+/***************************************/
+use chrono::Local;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+
+// Service counters of the local elevator. The distance is counted in floors.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    pub distance_travelled: u64,
+    pub door_cycles: u64,
+    pub orders_served: u64,
+}
+
+#[derive(Serialize)]
+struct StatisticsReport<'a> {
+    timestamp: String,
+    #[serde(flatten)]
+    statistics: &'a Statistics,
+}
+
+// Writes the counters as a timestamped JSON summary
+pub fn write_statistics_report(statistics: &Statistics, report_file: &str) -> Result<(), String> {
+    let report = StatisticsReport {
+        timestamp: Local::now().to_rfc3339(),
+        statistics,
+    };
+    let json_string = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+
+    let mut file = fs::File::create(report_file).map_err(|e| format!("Failed to create {}: {}", report_file, e))?;
+    file.write_all(json_string.as_bytes()).map_err(|e| format!("Failed to write {}: {}", report_file, e))
+}
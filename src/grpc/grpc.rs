@@ -0,0 +1,316 @@
+/**
+ * gRPC interface for control and monitoring.
+ *
+ * Serves the `ElevatorControl` service of proto/elevator.proto, built with the `grpc` feature. Like the HTTP API,
+ * requests are translated into commands and queries to the coordinator, but the messages are typed and events
+ * are streamed rather than polled:
+ *
+ * - `GetFleetState`:  The snapshot of the coordinator.
+ * - `StreamEvents`:   The fleet events from the point of view of this elevator, starting with its state.
+ * - `PlaceCall`:      Places or clears a hall call, or places a cab call at this elevator.
+ * - `SetMode`:        Changes the traffic mode, fire service or independent service.
+ *
+ * The server runs on its own Tokio runtime. Event streams poll the coordinator every `event_interval`
+ * milliseconds on a thread of their own, which ends when the client goes away.
+ *
+ * # Fields
+ * - `port`:                    TCP port the server listens on.
+ * - `event_interval`:          Time between snapshots compared for the event streams.
+ * - `local_id`:                The ID of the local elevator.
+ * - `coordinator_command_tx`:  Sends calls and mode changes to the coordinator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::{info, error};
+use std::net::SocketAddr;
+use std::thread::Builder;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::GrpcConfig;
+use crate::grpc::proto::elevator_control_server::{ElevatorControl, ElevatorControlServer};
+use crate::grpc::proto::{self, event, set_mode_request};
+use crate::shared::{self, fleet_events, ControlCommand, FleetEvent, OperatingMode, Snapshot, TrafficMode};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+const EVENT_BUFFER: usize = 64;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Grpc {
+    port: u16,
+    event_interval: Duration,
+    local_id: String,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+}
+
+impl Grpc {
+    pub fn new(
+        grpc_config: &GrpcConfig,
+        local_id: String,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    ) -> Grpc {
+        Grpc {
+            port: grpc_config.port,
+            event_interval: Duration::from_millis(grpc_config.event_interval),
+            local_id,
+            coordinator_command_tx,
+            coordinator_query_tx,
+        }
+    }
+
+    // The elevator keeps running without the gRPC interface
+    pub fn run(self) {
+        let runtime = match tokio::runtime::Builder::new_multi_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                error!("Failed to start the runtime of the gRPC interface: {:?}", e);
+                return;
+            }
+        };
+        let address = SocketAddr::from(([0, 0, 0, 0], self.port));
+        info!("gRPC interface listening on port {}", self.port);
+
+        let server = tonic::transport::Server::builder().add_service(ElevatorControlServer::new(self)).serve(address);
+        if let Err(e) = runtime.block_on(server) {
+            error!("Failed to serve the gRPC interface on {}: {:?}", address, e);
+        }
+    }
+
+    async fn query(&self) -> Result<Snapshot, Status> {
+        let coordinator_query_tx = self.coordinator_query_tx.clone();
+        tokio::task::spawn_blocking(move || query(&coordinator_query_tx))
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| Status::unavailable("The coordinator did not answer"))
+    }
+}
+
+#[tonic::async_trait]
+impl ElevatorControl for Grpc {
+    type StreamEventsStream = ReceiverStream<Result<proto::Event, Status>>;
+
+    async fn get_fleet_state(&self, _request: Request<proto::FleetStateRequest>) -> Result<Response<proto::FleetState>, Status> {
+        let snapshot = self.query().await?;
+        Ok(Response::new(fleet_state(&snapshot)))
+    }
+
+    async fn stream_events(
+        &self,
+        _request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let (event_tx, event_rx) = mpsc::channel::<Result<proto::Event, Status>>(EVENT_BUFFER);
+        let local_id = self.local_id.clone();
+        let coordinator_query_tx = self.coordinator_query_tx.clone();
+        let event_ticker = cbc::tick(self.event_interval);
+
+        // Thread comparing snapshots for this stream
+        let grpc_events_thread = Builder::new().name("grpc_events".into());
+        grpc_events_thread
+            .spawn(move || {
+                let mut previous: Option<Snapshot> = None;
+                while !event_tx.is_closed() && event_ticker.recv().is_ok() {
+                    let Some(snapshot) = query(&coordinator_query_tx) else { continue };
+                    for fleet_event in fleet_events(&local_id, previous.as_ref(), &snapshot) {
+                        if event_tx.blocking_send(Ok(event_message(&local_id, fleet_event))).is_err() {
+                            return;
+                        }
+                    }
+                    previous = Some(snapshot);
+                }
+            })
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(ReceiverStream::new(event_rx)))
+    }
+
+    async fn place_call(&self, request: Request<proto::PlaceCallRequest>) -> Result<Response<proto::PlaceCallReply>, Status> {
+        // Calls are handled asynchronously, so they are only accepted
+        let command = call_command(request.get_ref()).map_err(Status::invalid_argument)?;
+        self.coordinator_command_tx.send(command).map_err(|_| Status::unavailable("The coordinator is not running"))?;
+        Ok(Response::new(proto::PlaceCallReply {}))
+    }
+
+    async fn set_mode(&self, request: Request<proto::SetModeRequest>) -> Result<Response<proto::SetModeReply>, Status> {
+        let command = mode_command(request.get_ref()).map_err(Status::invalid_argument)?;
+        self.coordinator_command_tx.send(command).map_err(|_| Status::unavailable("The coordinator is not running"))?;
+        Ok(Response::new(proto::SetModeReply {}))
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn query(coordinator_query_tx: &cbc::Sender<cbc::Sender<Snapshot>>) -> Option<Snapshot> {
+    let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+    coordinator_query_tx.send(reply_tx).ok()?;
+    reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+}
+
+fn fleet_state(snapshot: &Snapshot) -> proto::FleetState {
+    let data = &snapshot.elevator_data;
+    let mut ids = data.states.keys().collect::<Vec<&String>>();
+    ids.sort();
+
+    let hall_calls = data
+        .hall_requests
+        .iter()
+        .enumerate()
+        .map(|(floor, requested)| {
+            let confirmed = snapshot.confirmed_hall_requests.get(floor);
+            let is_confirmed = |call: u8| confirmed.and_then(|confirmed| confirmed.get(call as usize)).copied().unwrap_or(false);
+            proto::HallCalls {
+                floor: floor as u32,
+                up: requested[HALL_UP as usize],
+                down: requested[HALL_DOWN as usize],
+                up_confirmed: is_confirmed(HALL_UP),
+                down_confirmed: is_confirmed(HALL_DOWN),
+            }
+        })
+        .collect();
+
+    let traffic = match data.traffic_mode {
+        None => proto::Traffic::Scheduled,
+        Some(TrafficMode::Normal) => proto::Traffic::Normal,
+        Some(TrafficMode::UpPeak) => proto::Traffic::UpPeak,
+        Some(TrafficMode::DownPeak) => proto::Traffic::DownPeak,
+    };
+
+    proto::FleetState {
+        id: snapshot.id.clone(),
+        peers: snapshot.peers.clone(),
+        version: data.version,
+        elevators: ids.into_iter().map(|id| elevator_status(id, &data.states[id])).collect(),
+        hall_calls,
+        traffic: traffic as i32,
+        fire_recall: data.fire_recall,
+    }
+}
+
+fn elevator_status(id: &str, state: &shared::ElevatorState) -> proto::ElevatorStatus {
+    let behaviour = match state.behaviour {
+        shared::Behaviour::Idle => proto::Behaviour::Idle,
+        shared::Behaviour::Moving => proto::Behaviour::Moving,
+        shared::Behaviour::DoorOpen => proto::Behaviour::DoorOpen,
+        shared::Behaviour::Error => proto::Behaviour::Error,
+    };
+    let direction = match state.direction {
+        shared::Direction::Stop => proto::Direction::Stop,
+        shared::Direction::Up => proto::Direction::Up,
+        shared::Direction::Down => proto::Direction::Down,
+    };
+    let door = match state.door {
+        shared::DoorState::Closed => proto::Door::Closed,
+        shared::DoorState::Open => proto::Door::Open,
+        shared::DoorState::Blocked => proto::Door::Blocked,
+    };
+    let service = match state.mode {
+        OperatingMode::Normal => proto::Service::Normal,
+        OperatingMode::FireRecall(_) => proto::Service::FireRecall,
+        OperatingMode::FirePhase2 => proto::Service::FirePhase2,
+        OperatingMode::Independent => proto::Service::Independent,
+    };
+
+    proto::ElevatorStatus {
+        id: id.to_string(),
+        behaviour: behaviour as i32,
+        floor: state.floor as u32,
+        direction: direction as i32,
+        cab_requests: state.cab_requests.clone(),
+        service: service as i32,
+        stop_plan: state.stop_plan.iter().map(|floor| *floor as u32).collect(),
+        door: door as i32,
+        arrival_times: state.arrival_times.clone(),
+    }
+}
+
+fn event_message(local_id: &str, fleet_event: FleetEvent) -> proto::Event {
+    let event = match fleet_event {
+        FleetEvent::State(state) => event::Event::State(elevator_status(local_id, &state)),
+        FleetEvent::Call(floor, call, placed) => event::Event::Call(proto::CallEvent {
+            floor: floor as u32,
+            call: call as i32,
+            placed,
+        }),
+        FleetEvent::Fault(floor, failed) => event::Event::Fault(proto::FaultEvent { floor: floor as u32, failed }),
+        FleetEvent::PeerLost(peer) => event::Event::PeerLost(peer),
+        FleetEvent::ObstructionAlarm(id, floor, active) => event::Event::ObstructionAlarm(proto::ObstructionAlarmEvent {
+            id,
+            floor: floor as u32,
+            active,
+        }),
+    };
+    proto::Event { event: Some(event) }
+}
+
+fn call_command(request: &proto::PlaceCallRequest) -> Result<ControlCommand, String> {
+    let floor = u8::try_from(request.floor).map_err(|_| "The floor is out of range".to_string())?;
+    match proto::Call::try_from(request.call) {
+        Ok(proto::Call::HallUp) => Ok(ControlCommand::HallCall(floor, HALL_UP, !request.clear)),
+        Ok(proto::Call::HallDown) => Ok(ControlCommand::HallCall(floor, HALL_DOWN, !request.clear)),
+        Ok(proto::Call::Cab) if request.clear => Err("Cab calls are cleared by serving them".to_string()),
+        Ok(proto::Call::Cab) => Ok(ControlCommand::CabCall(floor)),
+        Err(_) => Err("Unknown call".to_string()),
+    }
+}
+
+fn mode_command(request: &proto::SetModeRequest) -> Result<ControlCommand, String> {
+    match &request.mode {
+        Some(set_mode_request::Mode::Traffic(traffic)) => match proto::Traffic::try_from(*traffic) {
+            Ok(proto::Traffic::Scheduled) => Ok(ControlCommand::SetTrafficMode(None)),
+            Ok(proto::Traffic::Normal) => Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::Normal))),
+            Ok(proto::Traffic::UpPeak) => Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak))),
+            Ok(proto::Traffic::DownPeak) => Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::DownPeak))),
+            Err(_) => Err("Unknown traffic mode".to_string()),
+        },
+        Some(set_mode_request::Mode::FireRecall(recall)) => Ok(ControlCommand::SetFireRecall(*recall)),
+        Some(set_mode_request::Mode::FirePhase2(phase2)) => Ok(ControlCommand::SetFirePhase2(*phase2)),
+        Some(set_mode_request::Mode::IndependentService(independent)) => {
+            let id = (!independent.id.is_empty()).then(|| independent.id.clone());
+            Ok(ControlCommand::SetIndependentService(id, independent.enabled))
+        }
+        None => Err("No mode given".to_string()),
+    }
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use crate::grpc::proto;
+    use crate::shared::{ControlCommand, FleetEvent, Snapshot};
+
+    pub fn test_fleet_state(snapshot: &Snapshot) -> proto::FleetState {
+        super::fleet_state(snapshot)
+    }
+
+    pub fn test_event_message(local_id: &str, fleet_event: FleetEvent) -> proto::Event {
+        super::event_message(local_id, fleet_event)
+    }
+
+    pub fn test_call_command(request: &proto::PlaceCallRequest) -> Result<ControlCommand, String> {
+        super::call_command(request)
+    }
+
+    pub fn test_mode_command(request: &proto::SetModeRequest) -> Result<ControlCommand, String> {
+        super::mode_command(request)
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Unit tests for the gRPC interface
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * Messages are translated directly, without a server.
+ *
+ * Tests:
+ *  - test_grpc_fleet_state
+ *  - test_grpc_events
+ *  - test_grpc_place_call
+ *  - test_grpc_set_mode
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod grpc_tests {
+    use crate::grpc::grpc::testing::{test_call_command, test_event_message, test_fleet_state, test_mode_command};
+    use crate::grpc::proto::{self, event, set_mode_request};
+    use crate::shared::{Behaviour, ControlCommand, FleetEvent, NetworkStatistics, OperatingMode, Snapshot, TrafficMode, Uptime};
+    use crate::{ElevatorData, ElevatorState};
+    use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+    use prost::Message;
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        let mut state = ElevatorState::new(4);
+        state.behaviour = Behaviour::Moving;
+        state.floor = 2;
+        state.mode = OperatingMode::Independent;
+        elevator_data.states.insert("b".to_string(), state);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.hall_requests[1][HALL_DOWN as usize] = true;
+        elevator_data.traffic_mode = Some(TrafficMode::UpPeak);
+        let mut confirmed_hall_requests = vec![vec![false; 2]; 4];
+        confirmed_hall_requests[1][HALL_DOWN as usize] = true;
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string()],
+            elevator_data,
+            confirmed_hall_requests,
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    #[test]
+    fn test_grpc_fleet_state() {
+        // Act
+        let fleet_state = test_fleet_state(&snapshot());
+        let decoded = proto::FleetState::decode(fleet_state.encode_to_vec().as_slice()).unwrap();
+
+        // Assert
+        // Elevators are sorted by ID, and the message survives the wire
+        assert_eq!(decoded, fleet_state);
+        assert_eq!(fleet_state.elevators.iter().map(|elevator| elevator.id.as_str()).collect::<Vec<&str>>(), vec!["a", "b"]);
+        assert_eq!(fleet_state.elevators[1].behaviour, proto::Behaviour::Moving as i32);
+        assert_eq!(fleet_state.elevators[1].floor, 2);
+        assert_eq!(fleet_state.elevators[1].service, proto::Service::Independent as i32);
+        assert_eq!(
+            fleet_state.hall_calls[1],
+            proto::HallCalls { floor: 1, up: false, down: true, up_confirmed: false, down_confirmed: true }
+        );
+        assert_eq!(fleet_state.traffic, proto::Traffic::UpPeak as i32);
+    }
+
+    #[test]
+    fn test_grpc_events() {
+        // Act
+        let state = test_event_message("a", FleetEvent::State(ElevatorState::new(4)));
+        let call = test_event_message("a", FleetEvent::Call(3, CAB, true));
+        let lost = test_event_message("a", FleetEvent::PeerLost("b".to_string()));
+
+        // Assert
+        let Some(event::Event::State(status)) = state.event else { panic!("Expected a state event") };
+        assert_eq!(status.id, "a");
+        assert_eq!(call.event, Some(event::Event::Call(proto::CallEvent { floor: 3, call: proto::Call::Cab as i32, placed: true })));
+        assert_eq!(lost.event, Some(event::Event::PeerLost("b".to_string())));
+    }
+
+    #[test]
+    fn test_grpc_place_call() {
+        // Arrange
+        let request = |floor: u32, call: proto::Call, clear: bool| proto::PlaceCallRequest { floor, call: call as i32, clear };
+
+        // Act & Assert
+        assert_eq!(test_call_command(&request(2, proto::Call::HallUp, false)), Ok(ControlCommand::HallCall(2, HALL_UP, true)));
+        assert_eq!(test_call_command(&request(2, proto::Call::HallDown, true)), Ok(ControlCommand::HallCall(2, HALL_DOWN, false)));
+        assert_eq!(test_call_command(&request(3, proto::Call::Cab, false)), Ok(ControlCommand::CabCall(3)));
+        // Cab calls cannot be cleared, and unknown calls and floors beyond a byte are refused
+        assert!(test_call_command(&request(3, proto::Call::Cab, true)).is_err());
+        assert!(test_call_command(&request(300, proto::Call::HallUp, false)).is_err());
+        let unknown = proto::PlaceCallRequest { floor: 1, call: 7, clear: false };
+        assert!(test_call_command(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_grpc_set_mode() {
+        // Arrange
+        let request = |mode: set_mode_request::Mode| proto::SetModeRequest { mode: Some(mode) };
+        let independent = proto::IndependentService { id: String::new(), enabled: true };
+
+        // Act & Assert
+        assert_eq!(
+            test_mode_command(&request(set_mode_request::Mode::Traffic(proto::Traffic::Scheduled as i32))),
+            Ok(ControlCommand::SetTrafficMode(None))
+        );
+        assert_eq!(
+            test_mode_command(&request(set_mode_request::Mode::Traffic(proto::Traffic::DownPeak as i32))),
+            Ok(ControlCommand::SetTrafficMode(Some(TrafficMode::DownPeak)))
+        );
+        assert_eq!(test_mode_command(&request(set_mode_request::Mode::FireRecall(true))), Ok(ControlCommand::SetFireRecall(true)));
+        // An empty ID is the local elevator
+        assert_eq!(
+            test_mode_command(&request(set_mode_request::Mode::IndependentService(independent))),
+            Ok(ControlCommand::SetIndependentService(None, true))
+        );
+        assert!(test_mode_command(&proto::SetModeRequest { mode: None }).is_err());
+    }
+}
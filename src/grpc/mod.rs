@@ -0,0 +1,5 @@
+pub mod grpc;
+pub mod grpc_tests;
+pub mod proto;
+
+pub use grpc::Grpc;
@@ -0,0 +1,219 @@
+/**
+ * Messages of the gRPC interface, mirroring proto/elevator.proto.
+ *
+ * Written by hand rather than generated, so the project builds without protoc. The server of the service is
+ * generated by the build script and included at the end.
+ */
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Behaviour {
+    Idle = 0,
+    Moving = 1,
+    DoorOpen = 2,
+    Error = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Direction {
+    Stop = 0,
+    Up = 1,
+    Down = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Door {
+    Closed = 0,
+    Open = 1,
+    Blocked = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Service {
+    Normal = 0,
+    FireRecall = 1,
+    FirePhase2 = 2,
+    Independent = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Traffic {
+    Scheduled = 0,
+    Normal = 1,
+    UpPeak = 2,
+    DownPeak = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Call {
+    HallUp = 0,
+    HallDown = 1,
+    Cab = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ElevatorStatus {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(enumeration = "Behaviour", tag = "2")]
+    pub behaviour: i32,
+    #[prost(uint32, tag = "3")]
+    pub floor: u32,
+    #[prost(enumeration = "Direction", tag = "4")]
+    pub direction: i32,
+    #[prost(bool, repeated, tag = "5")]
+    pub cab_requests: Vec<bool>,
+    #[prost(enumeration = "Service", tag = "6")]
+    pub service: i32,
+    #[prost(uint32, repeated, tag = "7")]
+    pub stop_plan: Vec<u32>,
+    #[prost(enumeration = "Door", tag = "8")]
+    pub door: i32,
+    #[prost(uint64, repeated, tag = "9")]
+    pub arrival_times: Vec<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HallCalls {
+    #[prost(uint32, tag = "1")]
+    pub floor: u32,
+    #[prost(bool, tag = "2")]
+    pub up: bool,
+    #[prost(bool, tag = "3")]
+    pub down: bool,
+    #[prost(bool, tag = "4")]
+    pub up_confirmed: bool,
+    #[prost(bool, tag = "5")]
+    pub down_confirmed: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FleetStateRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FleetState {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, repeated, tag = "2")]
+    pub peers: Vec<String>,
+    #[prost(uint64, tag = "3")]
+    pub version: u64,
+    #[prost(message, repeated, tag = "4")]
+    pub elevators: Vec<ElevatorStatus>,
+    #[prost(message, repeated, tag = "5")]
+    pub hall_calls: Vec<HallCalls>,
+    #[prost(enumeration = "Traffic", tag = "6")]
+    pub traffic: i32,
+    #[prost(bool, tag = "7")]
+    pub fire_recall: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamEventsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CallEvent {
+    #[prost(uint32, tag = "1")]
+    pub floor: u32,
+    #[prost(enumeration = "Call", tag = "2")]
+    pub call: i32,
+    #[prost(bool, tag = "3")]
+    pub placed: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FaultEvent {
+    #[prost(uint32, tag = "1")]
+    pub floor: u32,
+    #[prost(bool, tag = "2")]
+    pub failed: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ObstructionAlarmEvent {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(uint32, tag = "2")]
+    pub floor: u32,
+    #[prost(bool, tag = "3")]
+    pub active: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Event {
+    #[prost(oneof = "event::Event", tags = "1, 2, 3, 4, 5")]
+    pub event: Option<event::Event>,
+}
+
+pub mod event {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "1")]
+        State(super::ElevatorStatus),
+        #[prost(message, tag = "2")]
+        Call(super::CallEvent),
+        #[prost(message, tag = "3")]
+        Fault(super::FaultEvent),
+        #[prost(string, tag = "4")]
+        PeerLost(String),
+        #[prost(message, tag = "5")]
+        ObstructionAlarm(super::ObstructionAlarmEvent),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PlaceCallRequest {
+    #[prost(uint32, tag = "1")]
+    pub floor: u32,
+    #[prost(enumeration = "Call", tag = "2")]
+    pub call: i32,
+    #[prost(bool, tag = "3")]
+    pub clear: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PlaceCallReply {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IndependentService {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetModeRequest {
+    #[prost(oneof = "set_mode_request::Mode", tags = "1, 2, 3, 4")]
+    pub mode: Option<set_mode_request::Mode>,
+}
+
+pub mod set_mode_request {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Mode {
+        #[prost(enumeration = "super::Traffic", tag = "1")]
+        Traffic(i32),
+        #[prost(bool, tag = "2")]
+        FireRecall(bool),
+        #[prost(bool, tag = "3")]
+        FirePhase2(bool),
+        #[prost(message, tag = "4")]
+        IndependentService(super::IndependentService),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetModeReply {}
+
+/***************************************/
+/*         Generated service           */
+/***************************************/
+include!(concat!(env!("OUT_DIR"), "/elevator.ElevatorControl.rs"));
@@ -0,0 +1,300 @@
+/**
+ * Decoding of the files of the storage and of captured datagrams, for the `inspect` subcommand.
+ *
+ * The kind of an artifact is told by its name, as the `FileStorage` names its files, or given. A file written whole
+ * is unsealed and its format checked as the storage does when loading it, and its contents parsed as the record it
+ * holds, so a file shown as valid is one the storage loads. A log is decoded line by line, and every corrupt line is
+ * reported without hiding the others. A file of a newer format than this build reads is refused, as the storage
+ * refuses it, and a checkpoint of another version is reported as one that would not be restored.
+ *
+ * A capture is a single raw datagram, or a hexadecimal dump with a datagram per line, as exported from a packet
+ * capture. Encrypted datagrams are opened with the `encryption_key` of the configuration, and every datagram parsed
+ * as an envelope. Messages sent in fragments are reassembled once the dump holds all of their fragments. Everything
+ * decoded is shown as pretty JSON.
+ *
+ * # Fields
+ * - `path`:                    The file inspected.
+ * - `kind`:                    What the file holds.
+ * - `framing`:                 How the file is framed: its format and checksums, or the form of the dump.
+ * - `entries`:                 The records of the file, decoded, or why they could not be.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::elevator::statistics::Statistics;
+use crate::network::encryption::{is_encrypted, Cipher};
+use crate::network::fragment::Reassembly;
+use crate::network::{Envelope, GossipMessage};
+use crate::shared::Snapshot;
+use crate::storage::checkpoint::CHECKPOINT_VERSION;
+use crate::storage::format::{decode, decode_log, FORMAT_VERSION};
+use crate::storage::storage::{
+    CabOrders, UptimeFile, WalEntry, CAB_ORDERS_FILE, EVENT_LOG_FILE, SNAPSHOT_FILE, STATISTICS_FILE, UPTIME_FILE, WAL_FILE,
+};
+use crate::storage::Checkpoint;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// What an artifact holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    CabOrders,
+    Statistics,
+    Uptime,
+    Snapshot,
+    Checkpoint,
+    Events,
+    Wal,
+    Datagrams,
+}
+
+impl ArtifactKind {
+    pub const ALL: [ArtifactKind; 8] = [
+        ArtifactKind::CabOrders,
+        ArtifactKind::Statistics,
+        ArtifactKind::Uptime,
+        ArtifactKind::Snapshot,
+        ArtifactKind::Checkpoint,
+        ArtifactKind::Events,
+        ArtifactKind::Wal,
+        ArtifactKind::Datagrams,
+    ];
+    pub const NAMES: [&'static str; 8] = ["cab-orders", "statistics", "uptime", "snapshot", "checkpoint", "events", "wal", "datagrams"];
+
+    pub fn name(&self) -> &'static str {
+        ArtifactKind::NAMES[ArtifactKind::ALL.iter().position(|kind| kind == self).unwrap()]
+    }
+
+    pub fn from_name(name: &str) -> Option<ArtifactKind> {
+        ArtifactKind::NAMES.iter().position(|known| *known == name).map(|index| ArtifactKind::ALL[index])
+    }
+
+    // The kind of a file by its name, as the storage names its files and the checkpoints are named by default
+    pub fn of_path(path: &Path) -> Option<ArtifactKind> {
+        let name = path.file_name()?.to_str()?;
+        let is_rotated_event_log = name.strip_prefix("events.").and_then(|name| name.strip_suffix(".jsonl")).is_some_and(|number| number.parse::<usize>().is_ok());
+        match name {
+            CAB_ORDERS_FILE => Some(ArtifactKind::CabOrders),
+            STATISTICS_FILE => Some(ArtifactKind::Statistics),
+            UPTIME_FILE => Some(ArtifactKind::Uptime),
+            SNAPSHOT_FILE => Some(ArtifactKind::Snapshot),
+            EVENT_LOG_FILE => Some(ArtifactKind::Events),
+            WAL_FILE => Some(ArtifactKind::Wal),
+            _ if is_rotated_event_log => Some(ArtifactKind::Events),
+            _ if name.starts_with("snapshot-") && name.ends_with(".json") => Some(ArtifactKind::Snapshot),
+            _ if name.starts_with("checkpoint") && name.ends_with(".json") => Some(ArtifactKind::Checkpoint),
+            _ if [".bin", ".hex", ".dump"].iter().any(|extension| name.ends_with(extension)) => Some(ArtifactKind::Datagrams),
+            _ => None,
+        }
+    }
+}
+
+// A record of an artifact, decoded as pretty JSON, or why it could not be
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub label: String,
+    pub decoded: Result<String, String>,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    pub path: String,
+    pub kind: ArtifactKind,
+    pub framing: String,
+    pub entries: Vec<Entry>,
+}
+
+impl Inspection {
+    // Whether every record of the artifact decodes
+    pub fn is_valid(&self) -> bool {
+        self.entries.iter().all(|entry| entry.decoded.is_ok())
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}, {}", self.path, self.kind.name(), self.framing)?;
+        for entry in &self.entries {
+            match &entry.decoded {
+                Ok(decoded) => writeln!(f, "-- {}\n{}", entry.label, decoded)?,
+                Err(e) => writeln!(f, "-- {}: INVALID: {}", entry.label, e)?,
+            }
+        }
+        let invalid = self.entries.iter().filter(|entry| entry.decoded.is_err()).count();
+        write!(f, "{} entries, {} invalid", self.entries.len(), invalid)
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Decodes the artifact at `path`, of the given kind or the kind told by its name. Datagrams are opened with
+// `cipher`. Fails if the artifact cannot be read at all, as a file of a newer format.
+pub fn inspect(path: &Path, kind: Option<ArtifactKind>, cipher: &Cipher) -> Result<Inspection, String> {
+    let Some(kind) = kind.or_else(|| ArtifactKind::of_path(path)) else {
+        return Err(format!("Cannot tell what {} holds by its name, give it with --kind", path.display()));
+    };
+    let contents = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let (framing, entries) = match kind {
+        ArtifactKind::Datagrams => inspect_datagrams(&contents, cipher),
+        _ => {
+            let text = String::from_utf8(contents).map_err(|_| format!("{} is not a text file", path.display()))?;
+            match kind {
+                ArtifactKind::Events | ArtifactKind::Wal => inspect_log(kind, &text)?,
+                ArtifactKind::Checkpoint => inspect_checkpoint(&text),
+                _ => inspect_file(kind, &text)?,
+            }
+        }
+    };
+    Ok(Inspection { path: path.display().to_string(), kind, framing, entries })
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// A file written whole by the storage
+fn inspect_file(kind: ArtifactKind, file: &str) -> Result<(String, Vec<Entry>), String> {
+    let (framing, decoded) = match decode(file)? {
+        Some((1, contents)) => ("format 1, without checksum".to_string(), parse_record(kind, &contents)),
+        Some((version, contents)) => (format!("format {} of {}, checksum valid", version, FORMAT_VERSION), parse_record(kind, &contents)),
+        None => ("checksum invalid".to_string(), Err("Corrupt, the checksum does not match the contents".to_string())),
+    };
+    Ok((framing, vec![Entry { label: "contents".to_string(), decoded }]))
+}
+
+// A log appended to by the storage, an entry per line
+fn inspect_log(kind: ArtifactKind, log: &str) -> Result<(String, Vec<Entry>), String> {
+    let (version, lines) = decode_log(log)?;
+    let framing = match version {
+        1 => "format 1, lines without checksum".to_string(),
+        version => format!("format {} of {}, a checksum per line", version, FORMAT_VERSION),
+    };
+    let entries = lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| Entry {
+            label: format!("entry {}", index + 1),
+            decoded: match line {
+                Some(line) => parse_record(kind, &line),
+                None => Err("Corrupt, the checksum does not match the line".to_string()),
+            },
+        })
+        .collect();
+    Ok((framing, entries))
+}
+
+// A checkpoint, written as plain JSON
+fn inspect_checkpoint(document: &str) -> (String, Vec<Entry>) {
+    let checkpoint = serde_json::from_str::<Checkpoint>(document).map_err(|e| format!("Does not parse: {}", e));
+    let framing = match &checkpoint {
+        Ok(checkpoint) => format!("version {} of {}, without checksum", checkpoint.version, CHECKPOINT_VERSION),
+        Err(_) => "without checksum".to_string(),
+    };
+    let decoded = checkpoint.and_then(|checkpoint| match checkpoint.version == CHECKPOINT_VERSION {
+        true => Ok(pretty(&checkpoint)),
+        false => Err(format!("Of version {}, and this build restores version {}", checkpoint.version, CHECKPOINT_VERSION)),
+    });
+    (framing, vec![Entry { label: "contents".to_string(), decoded }])
+}
+
+// A raw datagram, or a hexadecimal dump of datagrams
+fn inspect_datagrams(contents: &[u8], cipher: &Cipher) -> (String, Vec<Entry>) {
+    let (framing, datagrams) = match hex_dump(contents) {
+        Some(datagrams) => ("hexadecimal dump".to_string(), datagrams),
+        None => ("raw datagram".to_string(), vec![contents.to_vec()]),
+    };
+
+    let mut reassembly = Reassembly::default();
+    let mut entries = Vec::new();
+    for (index, datagram) in datagrams.iter().enumerate() {
+        let encryption = match is_encrypted(datagram) {
+            true => "encrypted",
+            false => "plaintext",
+        };
+        let envelope = match cipher.open(datagram) {
+            Some(plaintext) => serde_json::from_slice::<Envelope>(&plaintext).map_err(|e| format!("Not an envelope: {}", e)),
+            None => Err("Does not open with the encryption_key of the configuration".to_string()),
+        };
+        let reassembled = match &envelope {
+            Ok(Envelope { origin, message: GossipMessage::Fragment { message, index, count, payload }, .. }) => {
+                reassembly.add(origin, *message, *index, *count, payload.clone())
+            }
+            _ => None,
+        };
+        entries.push(Entry {
+            label: format!("datagram {}, {}", index + 1, encryption),
+            decoded: envelope.map(|envelope| pretty(&envelope)),
+        });
+        if let Some(envelope) = reassembled {
+            entries.push(Entry {
+                label: format!("message reassembled from the fragments up to datagram {}", index + 1),
+                decoded: Ok(pretty(&envelope)),
+            });
+        }
+    }
+    (framing, entries)
+}
+
+// The datagrams of a hexadecimal dump, a datagram per line, ignoring whitespace and colons. `None` if the
+// contents are not such a dump.
+fn hex_dump(contents: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let datagrams = std::str::from_utf8(contents)
+        .ok()?
+        .lines()
+        .map(|line| line.chars().filter(|c| !c.is_whitespace() && *c != ':').collect::<String>())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let digits = line.as_bytes();
+            match digits.len() % 2 {
+                0 => digits
+                    .chunks(2)
+                    .map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+                    .collect::<Option<Vec<u8>>>(),
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<Vec<u8>>>>()?;
+    (!datagrams.is_empty()).then_some(datagrams)
+}
+
+// The record of the kind, parsed as the storage loads it
+fn parse_record(kind: ArtifactKind, contents: &str) -> Result<String, String> {
+    match kind {
+        ArtifactKind::CabOrders => parse_toml::<CabOrders>(contents),
+        ArtifactKind::Statistics => parse_toml::<Statistics>(contents),
+        ArtifactKind::Uptime => parse_toml::<UptimeFile>(contents),
+        ArtifactKind::Snapshot => parse_json::<Snapshot>(contents),
+        ArtifactKind::Checkpoint => parse_json::<Checkpoint>(contents),
+        ArtifactKind::Events => parse_json::<Value>(contents),
+        ArtifactKind::Wal => parse_json::<WalEntry>(contents),
+        ArtifactKind::Datagrams => parse_json::<Envelope>(contents),
+    }
+}
+
+fn parse_toml<T: DeserializeOwned + Serialize>(contents: &str) -> Result<String, String> {
+    toml::from_str::<T>(contents).map(|record| pretty(&record)).map_err(|e| format!("Does not parse: {}", e))
+}
+
+fn parse_json<T: DeserializeOwned + Serialize>(contents: &str) -> Result<String, String> {
+    serde_json::from_str::<T>(contents).map(|record| pretty(&record)).map_err(|e| format!("Does not parse: {}", e))
+}
+
+fn pretty<T: Serialize>(record: &T) -> String {
+    serde_json::to_string_pretty(record).unwrap()
+}
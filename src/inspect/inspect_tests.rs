@@ -0,0 +1,150 @@
+/*
+ * Unit tests for the decoding of the stored and captured artifacts
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_inspect_kind_of_path
+ *  - test_inspect_storage_files
+ *  - test_inspect_newer_format
+ *  - test_inspect_datagrams
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod inspect_tests {
+    use crate::inspect::{inspect, ArtifactKind, Inspection};
+    use crate::network::encryption::Cipher;
+    use crate::network::fragment::fragment;
+    use crate::network::{Envelope, GossipMessage};
+    use crate::shared::Encryption;
+    use crate::storage::checksum::seal;
+    use crate::storage::{FileStorage, LogRetention, OrderMutation, Record, Storage};
+    use crate::ElevatorData;
+    use driver_rust::elevio::elev::HALL_UP;
+    use std::path::{Path, PathBuf};
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn invalid(inspection: &Inspection) -> Vec<String> {
+        inspection.entries.iter().filter(|entry| entry.decoded.is_err()).map(|entry| entry.label.clone()).collect()
+    }
+
+    fn hex(datagram: &[u8]) -> String {
+        datagram.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_inspect_kind_of_path() {
+        // Act / Assert
+        assert_eq!(ArtifactKind::of_path(Path::new("storage/cab_orders.toml")), Some(ArtifactKind::CabOrders));
+        assert_eq!(ArtifactKind::of_path(Path::new("snapshots/snapshot-000012.json")), Some(ArtifactKind::Snapshot));
+        assert_eq!(ArtifactKind::of_path(Path::new("events.3.jsonl")), Some(ArtifactKind::Events));
+        assert_eq!(ArtifactKind::of_path(Path::new("checkpoint-20240101-120000.json")), Some(ArtifactKind::Checkpoint));
+        assert_eq!(ArtifactKind::of_path(Path::new("capture.hex")), Some(ArtifactKind::Datagrams));
+        assert_eq!(ArtifactKind::of_path(Path::new("notes.txt")), None);
+        // Every kind is named for --kind
+        assert!(ArtifactKind::ALL.iter().all(|kind| ArtifactKind::from_name(kind.name()) == Some(*kind)));
+    }
+
+    #[test]
+    fn test_inspect_storage_files() {
+        // Arrange
+        let directory = directory("inspect_test");
+        let storage = FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default());
+        storage
+            .save(&[
+                Record::CabOrders(vec![false, true, false, false]),
+                Record::Mutation(1, OrderMutation::Add { floor: 1, call: HALL_UP }),
+                Record::Mutation(2, OrderMutation::Add { floor: 2, call: HALL_UP }),
+                Record::Mutation(3, OrderMutation::ClearAll),
+            ])
+            .unwrap();
+        let wal = directory.join("wal.jsonl");
+        std::fs::write(&wal, std::fs::read_to_string(&wal).unwrap().replacen("\"floor\":2", "\"floor\":0", 1)).unwrap();
+        std::fs::write(directory.join("statistics.toml"), "distance_travelled = 12\ndoor_cycles = 3\norders_served = 2\n").unwrap();
+
+        // Act
+        let cab_orders = inspect(&directory.join("cab_orders.toml"), None, &Cipher::default()).unwrap();
+        let logged = inspect(&wal, None, &Cipher::default()).unwrap();
+        let unsealed = inspect(&directory.join("statistics.toml"), None, &Cipher::default()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        assert!(cab_orders.is_valid());
+        assert!(cab_orders.framing.starts_with("format 3 of 3, checksum valid"));
+        assert!(cab_orders.to_string().contains("\"cab_calls\": [\n    false,\n    true,"));
+        // The corrupt line of the log is reported, and the others decoded
+        assert!(!logged.is_valid());
+        assert_eq!(invalid(&logged), vec!["entry 2"]);
+        assert!(logged.to_string().ends_with("3 entries, 1 invalid"));
+        // A file written before the checksums is read as format 1
+        assert!(unsealed.is_valid());
+        assert_eq!(unsealed.framing, "format 1, without checksum");
+    }
+
+    #[test]
+    fn test_inspect_newer_format() {
+        // Arrange
+        let directory = directory("inspect_format_test");
+        std::fs::write(directory.join("uptime.toml"), seal("# format 9\nrestarts = 1\n")).unwrap();
+        std::fs::write(directory.join("snapshot.json"), "{ \"id\": \"a\" }").unwrap();
+
+        // Act
+        let newer = inspect(&directory.join("uptime.toml"), None, &Cipher::default());
+        let incomplete = inspect(&directory.join("snapshot.json"), None, &Cipher::default()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // A newer format is refused, as the storage refuses it
+        assert!(newer.unwrap_err().contains("format 9"));
+        // A record without the fields of its schema is invalid
+        assert!(incomplete.entries[0].decoded.as_ref().unwrap_err().starts_with("Does not parse"));
+    }
+
+    #[test]
+    fn test_inspect_datagrams() {
+        // Arrange
+        let directory = directory("inspect_datagram_test");
+        let sender = Cipher::new(Encryption::Mixed, KEY).unwrap();
+        let envelope = |message| Envelope { origin: "a".to_string(), destination: "b".to_string(), message, sequence: None, group: String::new() };
+        let state = envelope(GossipMessage::State(ElevatorData::new(12)));
+        let fragments = fragment(&state, 300);
+        let mut dump = vec![hex(&sender.seal(br#"{"origin":"a","destination":"b","message":"pull"}"#))];
+        dump.extend(fragments.iter().map(|datagram| hex(&sender.seal(datagram.as_bytes()))));
+        dump.push(hex(&Cipher::new(Encryption::Mixed, &KEY.replace('0', "f")).unwrap().seal(b"{}")));
+        std::fs::write(directory.join("capture.hex"), dump.join("\n")).unwrap();
+        std::fs::write(directory.join("halt.bin"), serde_json::to_vec(&envelope(GossipMessage::Halt)).unwrap()).unwrap();
+
+        // Act
+        let captured = inspect(&directory.join("capture.hex"), None, &Cipher::new(Encryption::Plaintext, KEY).unwrap()).unwrap();
+        let without_key = inspect(&directory.join("capture.hex"), None, &Cipher::default()).unwrap();
+        let raw = inspect(&directory.join("halt.bin"), None, &Cipher::default()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // The datagrams are opened with the key, and the fragments reassembled into the message
+        let count = fragments.len();
+        assert!(count > 1);
+        assert_eq!(captured.framing, "hexadecimal dump");
+        assert_eq!(captured.entries.len(), count + 3);
+        assert!(captured.entries[0].decoded.as_ref().unwrap().contains("\"pull\""));
+        assert_eq!(captured.entries[count + 1].label, format!("message reassembled from the fragments up to datagram {}", count + 1));
+        assert_eq!(captured.entries[count + 1].decoded, Ok(serde_json::to_string_pretty(&state).unwrap()));
+        // A datagram sealed with another key does not open
+        assert_eq!(invalid(&captured), vec![format!("datagram {}, encrypted", count + 2)]);
+        assert_eq!(invalid(&without_key).len(), count + 2);
+        assert_eq!(raw.framing, "raw datagram");
+        assert_eq!(raw.entries[0].label, "datagram 1, plaintext");
+        assert!(raw.is_valid());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod inspect;
+pub mod inspect_tests;
+
+pub use inspect::{inspect, ArtifactKind, Inspection};
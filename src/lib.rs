@@ -0,0 +1,38 @@
+/***************************************/
+/*           Local modules             */
+/***************************************/
+pub mod alert;
+pub mod api;
+pub mod config;
+pub mod console;
+pub mod coordinator;
+pub mod demo;
+pub mod elevator;
+pub mod logging;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod inspect;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod network;
+pub mod report;
+pub mod rpc;
+pub mod shared;
+pub mod storage;
+
+pub use alert::Alert;
+pub use api::Api;
+pub use console::Console;
+pub use coordinator::{Coordinator, CoordinatorBuilder};
+pub use demo::{ChaosController, SoakMonitor, TrafficGenerator};
+pub use elevator::{DriverBuilder, ElevatorDriver};
+pub use elevator::{ElevatorFSM, FsmBuilder};
+#[cfg(feature = "grpc")]
+pub use grpc::Grpc;
+#[cfg(feature = "mqtt")]
+pub use mqtt::Mqtt;
+pub use network::Network;
+pub use report::Reporter;
+pub use rpc::Rpc;
+pub use shared::ElevatorData;
+pub use shared::ElevatorState;
@@ -0,0 +1,81 @@
+/**
+ * Merging of the logs shipped by the elevators into one stream, ordered by time.
+ *
+ * The events of the elevators arrive interleaved, and out of order where the network delays or reorders them. The
+ * collector holds every event for `window` after it arrives, and then releases those due in the order of their
+ * time, and of their sequence where the time is the same. The events of one elevator keep the order they were
+ * logged in, unless its clock steps back. The times are those of the clocks of the elevators, so the order across
+ * elevators is only as good as their clocks are synchronized. An event arriving after later ones were released is
+ * released at once, out of order.
+ *
+ * Each stream is named by the ID of its elevator, or by the address it came from before the ID is known. A gap in
+ * the sequence of a stream, as of the events dropped by a full queue or lost on the network, is released as a line of
+ * its own, and a sequence starting over from 0 is taken as the elevator restarting.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::DateTime;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::logging::LogEvent;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Collector {
+    window: Duration,
+    pending: Vec<(Instant, String, LogEvent)>,
+    next_sequences: HashMap<String, u64>,
+}
+
+impl Collector {
+    pub fn new(window: Duration) -> Collector {
+        Collector { window, pending: Vec::new(), next_sequences: HashMap::new() }
+    }
+
+    // Holds the event, which arrived from `source`, until it is due
+    pub fn push(&mut self, source: &str, event: LogEvent, now: Instant) {
+        let stream = match event.elevator.is_empty() {
+            true => source.to_string(),
+            false => event.elevator.clone(),
+        };
+        self.pending.push((now, stream, event));
+    }
+
+    // The lines of the events held for the window, in order, with the gaps found in their streams
+    pub fn release(&mut self, now: Instant) -> Vec<String> {
+        let (mut due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<(Instant, String, LogEvent)>, _>(|(arrival, _, _)| now.saturating_duration_since(*arrival) >= self.window);
+        self.pending = pending;
+        due.sort_by(|(_, stream_a, a), (_, stream_b, b)| (a.time, stream_a, a.sequence).cmp(&(b.time, stream_b, b.sequence)));
+
+        let mut lines = Vec::new();
+        for (_, stream, event) in due {
+            let next_sequence = self.next_sequences.entry(stream.clone()).or_insert(event.sequence);
+            if event.sequence > *next_sequence {
+                lines.push(format!("{} {} events lost", stream, event.sequence - *next_sequence));
+            }
+            // An event released late leaves the sequence as it is
+            if event.sequence >= *next_sequence || event.sequence == 0 {
+                *next_sequence = event.sequence + 1;
+            }
+            lines.push(format_event(&stream, &event));
+        }
+        lines
+    }
+}
+
+// The line of the event, with the time in UTC
+pub fn format_event(stream: &str, event: &LogEvent) -> String {
+    let time = DateTime::from_timestamp_millis(event.time as i64)
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+        .unwrap_or_default();
+    format!("{} {} {:<5} {}: {}", time, stream, event.level, event.target, event.message)
+}
@@ -0,0 +1,74 @@
+/*
+ * Unit tests for the log collector
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_collector_orders_by_time
+ *  - test_collector_reports_gaps
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod collector_tests {
+    use crate::logging::{Collector, LogEvent};
+    use std::time::{Duration, Instant};
+
+    fn event(elevator: &str, sequence: u64, time: u64, message: &str) -> LogEvent {
+        LogEvent {
+            elevator: elevator.to_string(),
+            sequence,
+            time,
+            level: "INFO".to_string(),
+            target: "project".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_collector_orders_by_time() {
+        // Arrange
+        let mut collector = Collector::new(Duration::from_millis(500));
+        let start = Instant::now();
+
+        // Act
+        collector.push("10.0.0.2:5000", event("b", 0, 2_000, "second"), start);
+        collector.push("10.0.0.1:5000", event("a", 0, 1_000, "first"), start + Duration::from_millis(100));
+        collector.push("10.0.0.3:5000", event("", 0, 3_000, "third"), start + Duration::from_millis(200));
+        let early = collector.release(start + Duration::from_millis(400));
+        let due = collector.release(start + Duration::from_millis(700));
+
+        // Assert
+        // Nothing is released within the window, and then the events by their time, named by elevator or source
+        assert!(early.is_empty());
+        assert_eq!(
+            due,
+            vec![
+                "1970-01-01T00:00:01.000Z a INFO  project: first",
+                "1970-01-01T00:00:02.000Z b INFO  project: second",
+                "1970-01-01T00:00:03.000Z 10.0.0.3:5000 INFO  project: third",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collector_reports_gaps() {
+        // Arrange
+        let mut collector = Collector::new(Duration::ZERO);
+        let now = Instant::now();
+        for (sequence, time) in [(0, 1_000), (1, 1_001), (4, 1_004), (0, 2_000)] {
+            collector.push("10.0.0.1:5000", event("a", sequence, time, "event"), now);
+        }
+
+        // Act
+        let lines = collector.release(now);
+
+        // Assert
+        // The events dropped before the fifth are reported, and the restart from 0 is not
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2], "a 2 events lost");
+    }
+}
@@ -0,0 +1,7 @@
+pub mod collector;
+pub mod collector_tests;
+pub mod sink;
+pub mod sink_tests;
+
+pub use collector::Collector;
+pub use sink::{init_logging, set_log_elevator, set_log_level, LogEvent, ShippingLogger};
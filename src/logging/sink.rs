@@ -0,0 +1,194 @@
+/**
+ * Shipping of the log to a central collector, to follow the elevators of the fleet in one stream.
+ *
+ * The log is written locally by `env_logger`, filtered by `RUST_LOG` as before. With a `collector_address`
+ * configured, every event at `level` and above is also sent as a JSON datagram to the collector, tagged with the
+ * ID of the elevator, the wall time in milliseconds and a sequence number counting the events shipped. The events
+ * are sent by a thread of their own, so logging never waits on the network: an event that finds the queue full is
+ * dropped, and shows at the collector as a gap in the sequence. See `Collector`.
+ *
+ * The ID of the elevator is only known once the network has started, so the events before it carry an empty ID,
+ * and are told apart by the address they came from.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::LoggingConfig;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const QUEUE_CAPACITY: usize = 4096;
+// Keeps every event within one datagram
+const MAX_MESSAGE_SIZE: usize = 8192;
+
+/***************************************/
+/*             Local state             */
+/***************************************/
+static ELEVATOR: OnceLock<String> = OnceLock::new();
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// An event of the log, as shipped to the collector
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub elevator: String,
+    pub sequence: u64,
+    // Milliseconds since the Unix epoch, on the clock of the elevator
+    pub time: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+// Logs locally through `env_logger`, and ships the events at `level` and above to the collector
+pub struct ShippingLogger {
+    local: env_logger::Logger,
+    level: LevelFilter,
+    sequence: AtomicU64,
+    event_tx: cbc::Sender<LogEvent>,
+}
+
+impl ShippingLogger {
+    pub fn new(local: env_logger::Logger, logging_config: &LoggingConfig) -> Result<ShippingLogger, String> {
+        let level = match logging_config.level.is_empty() {
+            true => LevelFilter::Info,
+            false => LevelFilter::from_str(&logging_config.level)
+                .map_err(|_| format!("The log level {} is not one of off, error, warn, info, debug and trace", logging_config.level))?,
+        };
+        let collector = logging_config
+            .collector_address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("Failed to resolve the log collector {}", logging_config.collector_address))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| format!("Failed to bind the log socket: {}", e))?;
+
+        let (event_tx, event_rx) = cbc::bounded::<LogEvent>(QUEUE_CAPACITY);
+        let shipper_thread = std::thread::Builder::new().name("log_shipper".into());
+        shipper_thread.spawn(move || ship(socket, collector, event_rx)).map_err(|e| e.to_string())?;
+        Ok(ShippingLogger { local, level, sequence: AtomicU64::new(0), event_tx })
+    }
+
+    // The most verbose level either the local log or the collector takes
+    pub fn max_level(&self) -> LevelFilter {
+        self.local.filter().max(self.level)
+    }
+}
+
+impl Log for ShippingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.local.enabled(metadata) || metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.local.matches(record) {
+            self.local.log(record);
+        }
+        if record.level() > self.level {
+            return;
+        }
+
+        let mut message = record.args().to_string();
+        if message.len() > MAX_MESSAGE_SIZE {
+            let end = (0..=MAX_MESSAGE_SIZE).rev().find(|end| message.is_char_boundary(*end)).unwrap_or(0);
+            message.truncate(end);
+        }
+        let event = LogEvent {
+            elevator: ELEVATOR.get().cloned().unwrap_or_default(),
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message,
+        };
+        let _ = self.event_tx.try_send(event);
+    }
+
+    fn flush(&self) {
+        self.local.flush();
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Installs the logger: `env_logger` alone, or shipping to the collector if one is configured. A collector that
+// cannot be shipped to is logged, and the log stays local.
+pub fn init_logging(logging_config: &LoggingConfig) {
+    let local = env_logger::Builder::from_default_env().build();
+    if logging_config.collector_address.is_empty() {
+        log::set_max_level(local.filter());
+        log::set_boxed_logger(Box::new(local)).expect("Failed to install the logger");
+        limit_log_level(&logging_config.max_level);
+        return;
+    }
+
+    let local_filter = local.filter();
+    match ShippingLogger::new(local, logging_config) {
+        Ok(logger) => {
+            log::set_max_level(logger.max_level());
+            log::set_boxed_logger(Box::new(logger)).expect("Failed to install the logger");
+            log::info!("Shipping the log to {}", logging_config.collector_address);
+        }
+        Err(e) => {
+            log::set_max_level(local_filter);
+            log::set_boxed_logger(Box::new(env_logger::Builder::from_default_env().build())).expect("Failed to install the logger");
+            log::warn!("{}. The log is not shipped.", e);
+        }
+    }
+    limit_log_level(&logging_config.max_level);
+}
+
+// Logs nothing more verbose than the level from now on. Empty lifts the limit, though the loggers keep filtering
+// by `RUST_LOG` and their level.
+pub fn set_log_level(level: &str) -> Result<(), String> {
+    let level = match level.is_empty() {
+        true => LevelFilter::Trace,
+        false => LevelFilter::from_str(level)
+            .map_err(|_| format!("The log level {} is not one of off, error, warn, info, debug and trace", level))?,
+    };
+    log::set_max_level(level);
+    Ok(())
+}
+
+// Tags the events shipped from now on with the ID of the elevator. Only the first ID set is kept.
+pub fn set_log_elevator(id: &str) {
+    let _ = ELEVATOR.set(id.to_string());
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Limits the log to the configured level, if any
+fn limit_log_level(max_level: &str) {
+    if max_level.is_empty() {
+        return;
+    }
+    if let Err(e) = set_log_level(max_level) {
+        log::warn!("{}. The log level is not limited.", e);
+    }
+}
+
+// Sends the events until the logger is dropped. An event that fails to send, as while the network is down, is lost.
+fn ship(socket: UdpSocket, collector: SocketAddr, event_rx: cbc::Receiver<LogEvent>) {
+    for event in event_rx {
+        if let Ok(datagram) = serde_json::to_vec(&event) {
+            let _ = socket.send_to(&datagram, collector);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * Unit tests for the shipping of the log
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_shipping_logger_ships_events
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod sink_tests {
+    use crate::config::LoggingConfig;
+    use crate::logging::{LogEvent, ShippingLogger};
+    use log::{Level, LevelFilter, Log, Record};
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    #[test]
+    fn test_shipping_logger_ships_events() {
+        // Arrange
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let logging_config = LoggingConfig {
+            collector_address: collector.local_addr().unwrap().to_string(),
+            level: "warn".to_string(),
+            max_level: String::new(),
+        };
+        let logger = ShippingLogger::new(env_logger::Builder::new().filter_level(LevelFilter::Off).build(), &logging_config).unwrap();
+
+        // Act
+        for (level, message) in [(Level::Warn, "first"), (Level::Info, "skipped"), (Level::Error, "second")] {
+            logger.log(&Record::builder().level(level).target("test").args(format_args!("{}", message)).build());
+        }
+        let mut buffer = [0; 1024];
+        let events = (0..2)
+            .map(|_| {
+                let number_of_bytes = collector.recv(&mut buffer).unwrap();
+                serde_json::from_slice::<LogEvent>(&buffer[..number_of_bytes]).unwrap()
+            })
+            .collect::<Vec<LogEvent>>();
+
+        // Assert
+        // Only the events at the level shipped are sent, numbered in order
+        assert_eq!(events[0].message, "first");
+        assert_eq!((events[0].sequence, events[0].level.as_str(), events[0].target.as_str()), (0, "WARN", "test"));
+        assert_eq!((events[1].message.as_str(), events[1].sequence), ("second", 1));
+        assert!(collector.recv(&mut buffer).is_err());
+    }
+}
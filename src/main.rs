@@ -3,34 +3,94 @@
 /***************************************/
 use crossbeam_channel as cbc;
 use network_rust::udpnet;
+use std::path::Path;
+use std::sync::Arc;
 use std::thread::Builder;
-use std::thread::*;
-use log::info;
+use log::{info, warn, error};
 use clap::{App, Arg};
 
 /***************************************/
 /*           Local modules             */
 /***************************************/
-use coordinator::Coordinator;
-use elevator::ElevatorDriver;
-use elevator::ElevatorFSM;
-use network::Network;
-use shared::ElevatorData;
-use shared::ElevatorState;
-
-mod config;
-mod coordinator;
-mod elevator;
-mod network;
-mod shared;
+use project::config;
+use project::config::CONFIG_FILE;
+use project::Alert;
+use project::Api;
+use project::Console;
+use project::CoordinatorBuilder;
+use project::{ChaosController, SoakMonitor, TrafficGenerator};
+use project::DriverBuilder;
+use project::FsmBuilder;
+use project::elevator::ManualDrive;
+use project::logging::{init_logging, set_log_elevator};
+use project::elevator::panel::PanelSignal;
+use project::Network;
+use project::Reporter;
+use project::Rpc;
+#[cfg(feature = "grpc")]
+use project::Grpc;
+#[cfg(feature = "mqtt")]
+use project::Mqtt;
+use project::network::{request_backup, BackupCommand, Relay};
+use project::network::encryption::Cipher;
+use project::inspect::{inspect, ArtifactKind};
+use project::ElevatorData;
+use project::ElevatorState;
+use project::shared::AccessControl;
+use project::shared::LockoutSchedule;
+use project::shared::ControlCommand;
+use project::shared::FsmView;
+use project::shared::OperatingMode;
+use project::shared::TrafficPolicy;
+use project::shared::RepositioningPolicy;
+use project::shared::{AdaptationPolicy, NetworkQuality, PeerVersion};
+use project::shared::Consensus;
+use project::shared::ConsensusMessage;
+use project::shared::Snapshot;
+use project::shared::{HealthMonitor, SystemStatus, SystemdNotifier};
+use project::shared::UptimeCounter;
+use project::shared::reload::send_config_update;
+use project::shared::{ConfigReloader, FsmTimings};
+use project::shared::ModuleError;
+use project::shared::Encryption;
+use project::shared::FloorLabels;
+use project::shared::ChannelFactory;
+use project::shared::OverflowPolicy::{Block, CoalesceLatest, DropOldest};
+use project::shared::schedule_thread;
+use project::shared::{terminate_modules, Terminate, Termination};
+use project::shared::{await_answer, await_ready};
+use project::shared::{exit, exit_on_panic, supervise, ExitReason};
+use project::shared::HallRequestAssigner;
+use project::shared::{Clock, RealClock, SimulatedClock};
+use project::coordinator::plugin::AssignerPlugin;
+use project::coordinator::coordinator::HALL_REQUEST_ASSIGNER;
+use project::coordinator::startup::{await_packages, reconcile_startup, CabReconciliation};
+use project::coordinator::strategy::strategy;
+use project::storage::{open_storage, recover_orders, restore_checkpoint, take_checkpoint, Checkpoint, InstanceLock, OrderMutation, Record, Recorder, WriteAheadLog};
+use driver_rust::elevio::elev::CAB;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// How long the modules are given to clean up and acknowledge when the program ends
+const SHUTDOWN_DEADLINE: std::time::Duration = std::time::Duration::from_secs(2);
+// How long each module is given to report ready at start-up, unless configured
+const DEFAULT_MODULE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
 /***************************************/
 /*        Program entry point          */
 /***************************************/
 fn main() -> std::io::Result<()> {
 
-    env_logger::init();
-    let mut config = config::load_config();
+    let mut config = match config::read_config(config::CONFIG_FILE) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(ExitReason::ConfigInvalid);
+        }
+    };
+    init_logging(&config.logging);
+    exit_on_panic();
 
     // Parse command line arguments
     let arguments = App::new("project")
@@ -57,8 +117,68 @@ fn main() -> std::io::Result<()> {
                 .help("Sets the network data port")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("manual")
+                .long("manual")
+                .help("Drives the hardware from the keyboard, for testing the wiring"),
+        )
+        .arg(
+            Arg::with_name("demo")
+                .long("demo")
+                .help("Places random calls by the [demo] profile, and prints a summary at the end of the run"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("Computes and logs the assignments and lights without acting on them, leaving the car where it is"),
+        )
+        .arg(
+            Arg::with_name("supervise")
+                .long("supervise")
+                .help("Runs the elevator as a child process, restarting it by the [restart] policies of its exit code"),
+        )
+        .arg(
+            Arg::with_name("soak")
+                .long("soak")
+                .help("Runs the demo with the faults of [soak], checking the invariants, and dumps the state on the first violation"),
+        )
+        .subcommand(
+            App::new("checkpoint")
+                .about("Writes a checkpoint of the orders, state and counters kept in the storage, and exits")
+                .arg(Arg::with_name("path").value_name("PATH").help("The file to write, by default checkpoint-<time>.json")),
+        )
+        .subcommand(
+            App::new("restore")
+                .about("Restores a checkpoint to the storage of a stopped node, and exits")
+                .arg(Arg::with_name("path").value_name("PATH").help("The checkpoint to restore").required(true)),
+        )
+        .subcommand(
+            App::new("configure")
+                .about("Sends settings to a running elevator, sealed with the reload key, and exits")
+                .arg(Arg::with_name("elevator").value_name("ID").help("The ID of the elevator").required(true))
+                .arg(Arg::with_name("address").value_name("ADDRESS").help("The address and message port of the elevator").required(true))
+                .arg(Arg::with_name("settings").value_name("SETTINGS").help("The settings, as a JSON object").required(true)),
+        )
+        .subcommand(
+            App::new("inspect")
+                .about("Decodes a file of the storage, a checkpoint or a dump of captured datagrams, checks it, and exits")
+                .arg(Arg::with_name("path").value_name("PATH").help("The file to decode").required(true))
+                .arg(
+                    Arg::with_name("kind")
+                        .long("kind")
+                        .value_name("KIND")
+                        .possible_values(ArtifactKind::NAMES)
+                        .help("What the file holds, by default told by its name"),
+                ),
+        )
         .get_matches();
 
+    // The supervisor runs the elevator with the same arguments, and nothing else
+    if arguments.is_present("supervise") {
+        let child_arguments = std::env::args().skip(1).filter(|argument| argument != "--supervise").collect();
+        supervise(&config.restart, child_arguments);
+    }
+
     // Override config with command line arguments if provided
     if let Some(addr) = arguments.value_of("hardware_address") {
         config.hardware.driver_address = addr.to_string();
@@ -72,109 +192,761 @@ fn main() -> std::io::Result<()> {
         config.network.msg_port = port.parse().expect("Failed to parse network port");
     }
 
+    // Checkpoints are taken from and restored to the storage without starting the node
+    if let Some(checkpoint_arguments) = arguments.subcommand_matches("checkpoint") {
+        let path = checkpoint_arguments
+            .value_of("path")
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("checkpoint-{}.json", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        let checkpoint = open_storage(&config.storage).and_then(|storage| take_checkpoint(storage.as_ref(), config.hardware.n_floors));
+        match checkpoint.and_then(|checkpoint| {
+            std::fs::write(&path, serde_json::to_string_pretty(&checkpoint).unwrap() + "\n").map_err(|e| e.to_string())
+        }) {
+            Ok(()) => println!("Checkpoint written to {}", path),
+            Err(e) => {
+                error!("Failed to take a checkpoint: {}", e);
+                exit(ExitReason::StorageFailed);
+            }
+        }
+        return Ok(());
+    }
+    if let Some(restore_arguments) = arguments.subcommand_matches("restore") {
+        let path = restore_arguments.value_of("path").unwrap();
+        let restored = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|document| serde_json::from_str::<Checkpoint>(&document).map_err(|e| e.to_string()))
+            .and_then(|checkpoint| {
+                // The node must be stopped, or it would save over the checkpoint
+                let _instance_lock = InstanceLock::acquire(&config.storage, &checkpoint.snapshot.id)?;
+                let storage = open_storage(&config.storage)?;
+                restore_checkpoint(storage.as_ref(), &checkpoint, config.hardware.n_floors)
+            });
+        match restored {
+            Ok(()) => println!("Checkpoint {} restored", path),
+            Err(e) => {
+                error!("Failed to restore the checkpoint {}: {}", path, e);
+                exit(ExitReason::StorageFailed);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(configure_arguments) = arguments.subcommand_matches("configure") {
+        let elevator = configure_arguments.value_of("elevator").unwrap();
+        let sent = send_config_update(
+            &config,
+            elevator,
+            configure_arguments.value_of("address").unwrap(),
+            configure_arguments.value_of("settings").unwrap(),
+        );
+        match sent {
+            Ok(()) => println!("Settings sent to {}", elevator),
+            Err(e) => {
+                error!("Failed to send the settings: {}", e);
+                exit(ExitReason::Failure);
+            }
+        }
+        return Ok(());
+    }
+
+    // Datagrams are opened with the key of the fleet, and plaintext ones read as they are
+    if let Some(inspect_arguments) = arguments.subcommand_matches("inspect") {
+        let kind = inspect_arguments.value_of("kind").and_then(ArtifactKind::from_name);
+        let inspection = Cipher::new(Encryption::Plaintext, &config.network.encryption_key)
+            .and_then(|cipher| inspect(Path::new(inspect_arguments.value_of("path").unwrap()), kind, &cipher));
+        match inspection {
+            Ok(inspection) if inspection.is_valid() => println!("{}", inspection),
+            Ok(inspection) => {
+                println!("{}", inspection);
+                exit(ExitReason::Failure);
+            }
+            Err(e) => {
+                error!("Failed to inspect: {}", e);
+                exit(ExitReason::Failure);
+            }
+        }
+        return Ok(());
+    }
+
+    // The floors are shown by their labels, and numbered densely inside
+    let floor_labels = match FloorLabels::new(&config.hardware.floor_labels, config.hardware.n_floors) {
+        Ok(floor_labels) => floor_labels,
+        Err(e) => {
+            error!("{}", e);
+            exit(ExitReason::ConfigInvalid);
+        }
+    };
+
     info!("Driver address: {}", config.hardware.driver_address.to_string());
     info!("Driver port: {}", config.hardware.driver_port.to_string());
     info!("Network port: {}", config.network.msg_port.to_string());
 
+    // The timers run on the wall clock, or faster on a simulated clock
+    let clock: Arc<dyn Clock> = if config.clock.simulated {
+        info!("Running on a simulated clock at {} times real time", config.clock.speed);
+        let clock = SimulatedClock::new(config.clock.speed);
+        if config.clock.start_time.is_empty() {
+            Arc::new(clock)
+        } else {
+            match chrono::NaiveTime::parse_from_str(&config.clock.start_time, "%H:%M") {
+                Ok(start_time) => Arc::new(clock.start_at(start_time)),
+                Err(e) => {
+                    error!("Invalid start time of the simulated clock {}: {}", config.clock.start_time, e);
+                    exit(ExitReason::ConfigInvalid);
+                }
+            }
+        }
+    } else {
+        Arc::new(RealClock)
+    };
+
+    // The channels between the modules, bounded by the configuration. Those carrying a state replaced by the next
+    // keep only the latest, and those of the gossip between the elevators drop the oldest, which the next repeats.
+    let channels = ChannelFactory::new(&config.channels);
+
+    // The outcome of every module that stops, for the supervisor to act on
+    let (module_stopped_tx, module_stopped_rx) = channels.named::<(&str, Result<(), ModuleError>)>("module_stopped", Block);
+
+    // Channels terminating the modules, each request carrying the sender the module acknowledges on
+    let (fsm_terminate_tx, fsm_terminate_rx) = channels.named::<Terminate>("fsm_terminate", Block);
+    let (coordinator_terminate_tx, coordinator_terminate_rx) = channels.named::<Terminate>("coordinator_terminate", Block);
+    let (hw_terminate_tx, hw_terminate_rx) = channels.named::<Terminate>("hw_terminate", Block);
+    let (net_terminate_tx, net_terminate_rx) = channels.named::<Terminate>("net_terminate", Block);
+    let (relay_terminate_tx, relay_terminate_rx) = channels.named::<Terminate>("relay_terminate", Block);
+    let (alert_terminate_tx, alert_terminate_rx) = channels.named::<Terminate>("alert_terminate", Block);
+
+    // The modules that stopped with an error, for the alerts
+    let (alert_failure_tx, alert_failure_rx) = channels.named::<(&'static str, ModuleError)>("alert_failure", Block);
+
     // Channels for unit testing
-    let (_fsm_terminate_tx, fsm_terminate_rx) = cbc::unbounded::<()>();
-    let (_coordinator_terminate_tx, coordinator_terminate_rx) = cbc::unbounded::<()>();
-    let (_hw_terminate_tx, hw_terminate_rx) = cbc::unbounded::<()>();
-    let (_net_peer_tx_enable_tx, net_peer_tx_enable_rx) = cbc::unbounded::<bool>();
+    let (net_peer_tx_enable_tx, net_peer_tx_enable_rx) = channels.named::<bool>("net_peer_tx_enable", CoalesceLatest);
+    // An elevator testing its hardware is announced to the peers once the test is done
+    if config.self_test.enabled {
+        let _ = net_peer_tx_enable_tx.send(false);
+    }
 
     // FSM channels
-    let (fsm_hall_requests_tx, fsm_hall_requests_rx) = cbc::unbounded::<Vec<Vec<bool>>>();
-    let (fsm_cab_request_tx, fsm_cab_request_rx) = cbc::unbounded::<u8>();
-    let (fsm_order_complete_tx, fsm_order_complete_rx) = cbc::unbounded::<(u8, u8)>();
+    let (fsm_hall_requests_tx, fsm_hall_requests_rx) = channels.named::<Vec<Vec<bool>>>("fsm_hall_requests", CoalesceLatest);
+    let (fsm_cab_request_tx, fsm_cab_request_rx) = channels.named::<u8>("fsm_cab_request", Block);
+    let (fsm_parking_floor_tx, fsm_parking_floor_rx) = channels.named::<Option<u8>>("fsm_parking_floor", CoalesceLatest);
+    let (fsm_operating_mode_tx, fsm_operating_mode_rx) = channels.named::<OperatingMode>("fsm_operating_mode", CoalesceLatest);
+    let (fsm_door_command_tx, fsm_door_command_rx) = channels.named::<bool>("fsm_door_command", Block);
+    let (fsm_alarm_clear_tx, fsm_alarm_clear_rx) = channels.named::<()>("fsm_alarm_clear", Block);
+    let (fsm_halt_tx, fsm_halt_rx) = channels.named::<bool>("fsm_halt", Block);
+    let (fsm_inspect_tx, fsm_inspect_rx) = channels.named::<cbc::Sender<FsmView>>("fsm_inspect", Block);
+    let (fsm_timings_tx, fsm_timings_rx) = channels.named::<FsmTimings>("fsm_timings", Block);
+    let (fsm_blocked_floors_tx, fsm_blocked_floors_rx) = channels.named::<Vec<u8>>("fsm_blocked_floors", CoalesceLatest);
+    let (fsm_locked_floors_tx, fsm_locked_floors_rx) = channels.named::<Vec<u8>>("fsm_locked_floors", CoalesceLatest);
+    let (fsm_order_complete_tx, fsm_order_complete_rx) = channels.named::<(u8, u8)>("fsm_order_complete", Block);
 
     // Network channels
-    let (fsm_state_tx, fsm_state_rx) = cbc::unbounded::<ElevatorState>();
-    let (net_data_send_tx, net_data_send_rx) = cbc::unbounded::<ElevatorData>();
-    let (net_data_recv_tx, net_data_recv_rx) = cbc::unbounded::<ElevatorData>();
-    let (net_peer_update_tx, net_peer_update_rx) = cbc::unbounded::<udpnet::peers::PeerUpdate>();
+    let (fsm_state_tx, fsm_state_rx) = channels.named::<ElevatorState>("fsm_state", CoalesceLatest);
+    let (net_data_send_tx, net_data_send_rx) = channels.named::<ElevatorData>("net_data_send", CoalesceLatest);
+    let (net_data_recv_tx, net_data_recv_rx) = channels.named::<ElevatorData>("net_data_recv", DropOldest);
+    let (net_sync_recv_tx, net_sync_recv_rx) = channels.named::<ElevatorData>("net_sync_recv", DropOldest);
+    let (net_peer_update_tx, net_peer_update_rx) = channels.named::<udpnet::peers::PeerUpdate>("net_peer_update", Block);
+    let (net_peer_version_tx, net_peer_version_rx) = channels.named::<(String, Option<PeerVersion>)>("net_peer_version", Block);
+    let (net_consensus_send_tx, net_consensus_send_rx) = channels.named::<(String, ConsensusMessage)>("net_consensus_send", DropOldest);
+    let (net_consensus_recv_tx, net_consensus_recv_rx) = channels.named::<(String, ConsensusMessage)>("net_consensus_recv", DropOldest);
+    let (net_backup_send_tx, net_backup_send_rx) = channels.named::<BackupCommand>("net_backup_send", Block);
+    let (net_backup_recv_tx, net_backup_recv_rx) = channels.named::<Checkpoint>("net_backup_recv", Block);
+    let (net_config_update_tx, net_config_update_rx) = channels.named::<String>("net_config_update", Block);
+    let (net_quality_tx, net_quality_rx) = channels.named::<NetworkQuality>("net_quality", CoalesceLatest);
     
+    // Console channels
+    let (coordinator_command_tx, coordinator_command_rx) = channels.named::<ControlCommand>("coordinator_command", Block);
+    let (coordinator_query_tx, coordinator_query_rx) = channels.named::<cbc::Sender<Snapshot>>("coordinator_query", Block);
+
     // Hardware channels
-    let (hw_motor_direction_tx, hw_motor_direction_rx) = cbc::unbounded::<u8>();
-    let (hw_button_light_tx, hw_button_light_rx) = cbc::unbounded::<(u8, u8, bool)>();
-    let (hw_request_tx, hw_request_rx) = cbc::unbounded::<(u8, u8)>();
-    let (hw_floor_sensor_tx, hw_floor_sensor_rx) = cbc::unbounded::<u8>();
-    let (hw_floor_indicator_tx, hw_floor_indicator_rx) = cbc::unbounded::<u8>();
-    let (hw_door_light_tx, hw_door_light_rx) = cbc::unbounded::<bool>();
-    let (hw_obstruction_tx, hw_obstruction_rx) = cbc::unbounded::<bool>();
-
-    // Start the hardware module
-    let elevator_driver = ElevatorDriver::new(
-        &config.hardware,
-        hw_motor_direction_rx,
-        hw_button_light_rx,
-        hw_request_tx,
-        hw_floor_sensor_tx,
-        hw_floor_indicator_rx,
-        hw_door_light_rx,
-        hw_obstruction_tx,
-        hw_terminate_rx,
-    );
+    let (hw_motor_direction_tx, hw_motor_direction_rx) = channels.named::<u8>("hw_motor_direction", Block);
+    let (hw_button_light_tx, hw_button_light_rx) = channels.named::<(u8, u8, bool)>("hw_button_light", Block);
+    let (hw_request_tx, hw_request_rx) = channels.named::<(u8, u8)>("hw_request", Block);
+    let (hw_floor_sensor_tx, hw_floor_sensor_rx) = channels.named::<u8>("hw_floor_sensor", Block);
+    let (hw_floor_indicator_tx, hw_floor_indicator_rx) = channels.named::<u8>("hw_floor_indicator", CoalesceLatest);
+    let (hw_door_light_tx, hw_door_light_rx) = channels.named::<bool>("hw_door_light", CoalesceLatest);
+    let (hw_obstruction_tx, hw_obstruction_rx) = channels.named::<bool>("hw_obstruction", Block);
+    let (hw_panel_signal_tx, hw_panel_signal_rx) = channels.named::<PanelSignal>("hw_panel_signal", CoalesceLatest);
+    let (hw_status_tx, hw_status_rx) = channels.named::<SystemStatus>("hw_status", CoalesceLatest);
+    let (hw_ready_tx, hw_ready_rx) = channels.named::<()>("hw_ready", Block);
+
+    // The modules are started in the order they depend on, each waited for until it reports ready
+    let module_timeout = match config.startup.module_timeout {
+        0 => DEFAULT_MODULE_TIMEOUT,
+        milliseconds => std::time::Duration::from_millis(milliseconds),
+    };
+
+    // The hardware module is started once the instance lock is held, so a second instance does not connect to it
+    let (hw_config, panel_config) = (config.hardware.clone(), config.panel.clone());
+    let self_test_config = config.self_test.clone();
+    let self_test_peer_tx_enable_tx = net_peer_tx_enable_tx.clone();
+    let descent_timeout = std::time::Duration::from_millis(config.elevator.motor_timeout);
+    let driver_thread_config = config.threads.driver.clone();
+    let driver_stopped_tx = module_stopped_tx.clone();
+    let start_elevator_driver = move || {
+        let elevator_driver = DriverBuilder::new(&hw_config)
+            .panel_config(panel_config)
+            .hw_motor_direction_rx(hw_motor_direction_rx)
+            .hw_button_light_rx(hw_button_light_rx)
+            .hw_request_tx(hw_request_tx)
+            .hw_floor_sensor_tx(hw_floor_sensor_tx)
+            .hw_floor_indicator_rx(hw_floor_indicator_rx)
+            .hw_door_light_rx(hw_door_light_rx)
+            .hw_obstruction_tx(hw_obstruction_tx)
+            .hw_panel_signal_rx(hw_panel_signal_rx)
+            .hw_status_rx(hw_status_rx)
+            .hw_ready_tx(hw_ready_tx)
+            .terminate_rx(hw_terminate_rx)
+            .build();
+        let elevator_driver = match elevator_driver {
+            Ok(elevator_driver) => elevator_driver,
+            Err(e) => {
+                error!("{}", e);
+                exit(ExitReason::HardwareUnreachable);
+            }
+        };
+
+        // The hardware is tested before the FSM and the coordinator start
+        if self_test_config.enabled {
+            info!("Testing the hardware");
+            let report = elevator_driver.self_test(&self_test_config, descent_timeout);
+            let report_json = serde_json::to_string(&report).unwrap_or_default();
+            match report.passed {
+                true => info!("Self-test passed: {}", report_json),
+                false if self_test_config.required => {
+                    error!("Self-test failed, not starting: {}", report_json);
+                    exit(ExitReason::HardwareFault);
+                }
+                false => error!("SELF-TEST FAILED, starting anyway: {}", report_json),
+            }
+            let _ = self_test_peer_tx_enable_tx.send(true);
+        }
+
+        let elevator_driver_thread = Builder::new().name("elevator_driver".into());
+        elevator_driver_thread
+            .spawn(move || {
+                schedule("driver", &driver_thread_config);
+                driver_stopped_tx.send(("driver", elevator_driver.run()))
+            })
+            .unwrap();
 
-    let elevator_driver_thread = Builder::new().name("elevator_driver".into());
-    elevator_driver_thread.spawn(move || elevator_driver.run()).unwrap();
+        // The elevator server may take the connection and still not answer, leaving the driver stuck
+        if let Err(e) = await_ready("driver", &hw_ready_rx, module_timeout) {
+            error!("{}. Is the elevator server at {}:{} answering?", e, hw_config.driver_address, hw_config.driver_port);
+            exit(ExitReason::HardwareUnreachable);
+        }
+        info!("The driver is ready");
+    };
+
+    // In manual drive mode the keyboard takes the place of the FSM, and nothing else is started
+    if arguments.is_present("manual") {
+        start_elevator_driver();
+        let manual_drive = ManualDrive::new(
+            config.hardware.n_floors,
+            hw_motor_direction_tx,
+            hw_button_light_tx,
+            hw_floor_indicator_tx,
+            hw_door_light_tx,
+            hw_floor_sensor_rx,
+            hw_request_rx,
+            hw_obstruction_rx,
+        );
+        manual_drive.run();
+        shut_down(&[("driver", hw_terminate_tx)], ExitReason::Clean, None);
+    }
 
     // Start the network module, contructor spawns the threads:
-    // peer_tx, peer_rx, data_tx, data_rx
+    // peer_tx, peer_rx, peer_update, data_tx, consensus_tx, data_rx
     let network = Network::new(
         &config.network,
+        &config.backup,
+        &config.adaptation,
+        clock.as_ref(),
         net_data_send_rx,
         net_data_recv_tx,
+        net_sync_recv_tx,
         net_peer_update_tx,
+        net_peer_version_tx,
         net_peer_tx_enable_rx,
+        net_consensus_send_rx,
+        net_consensus_recv_tx,
+        coordinator_command_tx.clone(),
+        net_backup_send_rx,
+        net_backup_recv_tx,
+        net_config_update_tx,
+        net_quality_tx,
+        net_terminate_rx,
     )?;
     let id = network.id.clone();
+    set_log_elevator(&id);
 
-    // Start the fsm module
-    let elevator_fsm = ElevatorFSM::new(
-        &config.elevator,
-        hw_motor_direction_tx,
-        hw_floor_sensor_rx,
-        hw_floor_indicator_tx,
-        hw_door_light_tx,
-        hw_obstruction_rx,
-        fsm_hall_requests_rx,
-        fsm_cab_request_rx,
-        fsm_order_complete_tx,
-        fsm_state_tx,
-        fsm_terminate_rx,
-    );
+    // Refuse to run alongside another instance of the same elevator, and start the hardware module
+    let _instance_lock = match InstanceLock::acquire(&config.storage, &id) {
+        Ok(instance_lock) => instance_lock,
+        Err(e) => {
+            error!("{}", e);
+            exit(ExitReason::AlreadyRunning);
+        }
+    };
+    start_elevator_driver();
 
-    let elevator_fsm_thread = Builder::new().name("elevator_fsm".into());
-    elevator_fsm_thread.spawn(move || elevator_fsm.run()).unwrap();
+    // Start the relay to the elevators of another network segment, contructor spawns the threads:
+    // relay_peer_rx, relay_announce, relay_rx, and relay_peer_tx for every relayed elevator
+    if config.relay.enabled {
+        Relay::new(&config.network, &config.relay, clock.as_ref(), relay_terminate_rx)?;
+    }
+    let access_control = AccessControl::new(&config.access);
+
+    // Open the storage of the cab orders, the statistics, the snapshots and the event log
+    let storage = match open_storage(&config.storage) {
+        Ok(storage) => storage,
+        Err(e) => {
+            error!("Failed to open the storage: {}", e);
+            exit(ExitReason::StorageFailed);
+        }
+    };
+
+    // Count the start, and save the uptime while running
+    let uptime = Arc::new(UptimeCounter::start(storage.clone()));
+    let uptime_saver = uptime.clone();
+    let uptime_thread = Builder::new().name("uptime".into());
+    uptime_thread.spawn(move || uptime_saver.run()).unwrap();
+
+    // Without local state, the backup hosted by the peers is restored before any request is served
+    let state_missing = matches!(storage.load_snapshot(), Ok(None)) && matches!(storage.load_cab_orders(), Ok(None));
+    if config.backup.interval > 0 && state_missing {
+        info!("No local state found, asking the peers for a backup");
+        let timeout = std::time::Duration::from_millis(config.backup.timeout);
+        match request_backup(&net_backup_send_tx, &net_backup_recv_rx, timeout, clock.as_ref()) {
+            Some(checkpoint) => match restore_checkpoint(storage.as_ref(), &checkpoint, config.hardware.n_floors) {
+                Ok(()) => info!("Restored the backup of {} from the peers", checkpoint.created_at),
+                Err(e) => error!("Failed to restore the backup from the peers: {}", e),
+            },
+            None => info!("No backup found at the peers, starting afresh"),
+        }
+    }
 
     // Create the elevator data instance
     let n_floors = config.hardware.n_floors.clone();
     let mut elevator_data = ElevatorData::new(n_floors);
     elevator_data.states.insert(id.clone(), ElevatorState::new(n_floors));
 
+    // Recover the orders from the snapshot and the write-ahead log
+    let mut wal = config.storage.write_ahead_log.then(|| match recover_orders(storage.as_ref(), &id, n_floors) {
+        Ok(recovered_orders) => {
+            info!(
+                "Recovered the orders from the write-ahead log, {} mutations replayed",
+                recovered_orders.replayed
+            );
+            elevator_data.hall_requests = recovered_orders.hall_requests.clone();
+            elevator_data.states.get_mut(&id).unwrap().cab_requests = recovered_orders.cab_requests.clone();
+            WriteAheadLog::new(storage.clone(), recovered_orders)
+        }
+        Err(e) => {
+            error!("Failed to recover the orders from the write-ahead log: {}", e);
+            exit(ExitReason::StorageFailed);
+        }
+    });
+
+    // Reconcile the persisted state with the peers before anything is served. The persisted data is announced,
+    // so the peers answer with theirs.
+    if config.network.startup_timeout > 0 {
+        let _ = net_data_send_tx.send(elevator_data.clone());
+        let timeout = std::time::Duration::from_millis(config.network.startup_timeout);
+        let packages = await_packages(&net_data_recv_rx, timeout);
+        info!("Reconciling the persisted state with {} packages from the peers", packages.len());
+
+        // The persisted cab calls are those of the FSM and those recovered from the write-ahead log
+        let saved_cab_orders = storage.load_cab_orders().ok().flatten().unwrap_or_default();
+        let local_state = elevator_data.states.get_mut(&id).unwrap();
+        for (floor, cab_request) in saved_cab_orders.iter().enumerate().take(n_floors as usize) {
+            local_state.cab_requests[floor] |= *cab_request;
+        }
+        let recorded_completion_times = storage
+            .load_snapshot()
+            .ok()
+            .flatten()
+            .and_then(|snapshot| snapshot.elevator_data.states.get(&id).map(|state| state.cab_completion_times.clone()));
+        let cab_calls = reconcile_startup(&mut elevator_data, &id, n_floors, recorded_completion_times.as_deref(), &packages);
+
+        // The FSM loads the reconciled cab calls, and the write-ahead log resumes them
+        if cab_calls != CabReconciliation::default() {
+            let cab_orders = (0..n_floors)
+                .map(|floor| {
+                    let saved = saved_cab_orders.get(floor as usize).copied().unwrap_or(false);
+                    (saved && !cab_calls.dropped.contains(&floor)) || cab_calls.restored.contains(&floor)
+                })
+                .collect::<Vec<bool>>();
+            if let Err(e) = storage.save(&[Record::CabOrders(cab_orders)]) {
+                error!("Failed to save the reconciled cab orders: {}", e);
+            }
+            if let Some(wal) = wal.as_mut() {
+                for floor in cab_calls.dropped {
+                    wal.append(OrderMutation::Clear { floor, call: CAB });
+                }
+                for floor in cab_calls.restored {
+                    wal.append(OrderMutation::Add { floor, call: CAB });
+                }
+            }
+        }
+        // Without the write-ahead log the FSM reports the cab calls it loads, and lights them
+        if wal.is_none() {
+            elevator_data.states.get_mut(&id).unwrap().cab_requests = vec![false; n_floors as usize];
+        }
+    }
+
+    // Start the fsm module
+    let elevator_fsm = FsmBuilder::new(&config.elevator)
+        .blocked_floors(access_control.blocked_floors(&id, config.elevator.n_floors))
+        .floor_labels(floor_labels.clone())
+        .clock(clock.clone())
+        .storage(storage.clone())
+        .hw_motor_direction_tx(hw_motor_direction_tx)
+        .hw_floor_sensor_rx(hw_floor_sensor_rx)
+        .hw_floor_indicator_tx(hw_floor_indicator_tx)
+        .hw_door_light_tx(hw_door_light_tx)
+        .hw_button_light_tx(hw_button_light_tx.clone())
+        .hw_obstruction_rx(hw_obstruction_rx)
+        .hw_panel_signal_tx(hw_panel_signal_tx)
+        .fsm_hall_requests_rx(fsm_hall_requests_rx)
+        .fsm_cab_request_rx(fsm_cab_request_rx)
+        .fsm_parking_floor_rx(fsm_parking_floor_rx)
+        .fsm_operating_mode_rx(fsm_operating_mode_rx)
+        .fsm_door_command_rx(fsm_door_command_rx)
+        .fsm_alarm_clear_rx(fsm_alarm_clear_rx)
+        .fsm_halt_rx(fsm_halt_rx)
+        .fsm_inspect_rx(fsm_inspect_rx)
+        .fsm_timings_rx(fsm_timings_rx)
+        .fsm_blocked_floors_rx(fsm_blocked_floors_rx)
+        .fsm_locked_floors_rx(fsm_locked_floors_rx)
+        .fsm_order_complete_tx(fsm_order_complete_tx)
+        .fsm_state_tx(fsm_state_tx)
+        .fsm_terminate_rx(fsm_terminate_rx)
+        .build()
+        .expect("Failed to build the FSM");
+
+    let elevator_fsm_thread = Builder::new().name("elevator_fsm".into());
+    let fsm_stopped_tx = module_stopped_tx.clone();
+    let fsm_thread_config = config.threads.fsm.clone();
+    elevator_fsm_thread
+        .spawn(move || {
+            schedule("FSM", &fsm_thread_config);
+            fsm_stopped_tx.send(("FSM", elevator_fsm.run()))
+        })
+        .unwrap();
+
+    // The FSM is ready once it answers an inspection. The network is ready since it was built, before the driver.
+    if let Err(e) = await_answer("FSM", &fsm_inspect_tx, module_timeout) {
+        error!("{}", e);
+        shut_down(
+            &[("FSM", fsm_terminate_tx), ("driver", hw_terminate_tx), ("network", net_terminate_tx)],
+            ExitReason::Failure,
+            Some(&uptime),
+        );
+    }
+    info!("The FSM is ready");
+
     info!("Elevator data read from file {:?}", elevator_data);
 
-    // Start the coordinator module
-    let mut coordinator = Coordinator::new(
-        elevator_data,
-        id,
-        n_floors,
-        hw_button_light_tx,
-        hw_request_rx,
-        fsm_hall_requests_tx,
-        fsm_cab_request_tx,
-        fsm_state_rx,
-        fsm_order_complete_rx,
-        net_data_send_tx,
-        net_data_recv_rx,
-        net_peer_update_rx,
-        coordinator_terminate_rx,
+    // In consensus mode the hall calls are replicated through an elected leader
+    let consensus = config.consensus.enabled.then(|| {
+        Consensus::new(
+            id.clone(),
+            std::time::Duration::from_millis(config.consensus.election_timeout),
+            std::time::Duration::from_millis(config.consensus.heartbeat_interval),
+            clock.now(),
+        )
+    });
+
+    // Load the WebAssembly assigner. It is reloaded when the file changes.
+    let assigner_plugin = if config.elevator.hall_request_assigner == HallRequestAssigner::Wasm {
+        match AssignerPlugin::load(&config.elevator.assigner_plugin) {
+            Ok(plugin) => Some(plugin),
+            Err(e) => {
+                error!("Failed to load the assigner plugin: {}", e);
+                exit(ExitReason::AssignerMissing);
+            }
+        }
+    } else {
+        None
+    };
+    // Without the reference executable, the hall calls are assigned in-process by the same cost function
+    if config.elevator.hall_request_assigner == HallRequestAssigner::Process && !Path::new(HALL_REQUEST_ASSIGNER).exists() {
+        warn!("The hall request assigner {} is missing, assigning in-process", HALL_REQUEST_ASSIGNER);
+        config.elevator.hall_request_assigner = HallRequestAssigner::Native;
+    }
+    let assigner = strategy(config.elevator.hall_request_assigner, assigner_plugin);
+    info!("Assigning the hall calls by the {} strategy", assigner.name());
+
+    // Start the HTTP API, translating requests into commands and queries to the coordinator
+    if config.api.enabled {
+        let api = Api::new(&config.api, id.clone(), floor_labels.clone(), coordinator_command_tx.clone(), coordinator_query_tx.clone());
+        let api_thread = Builder::new().name("api".into());
+        api_thread.spawn(move || api.run()).unwrap();
+    }
+
+    // Post obstruction alarms and faults to the alert webhook
+    if config.alert.enabled {
+        match Alert::new(&config.alert, id.clone(), coordinator_query_tx.clone(), alert_failure_rx, alert_terminate_rx) {
+            Ok(alert) => {
+                let alert_thread = Builder::new().name("alert".into());
+                alert_thread.spawn(move || alert.run()).unwrap();
+            }
+            Err(e) => {
+                error!("Failed to start the alert webhook: {}", e);
+                exit(ExitReason::Failure);
+            }
+        }
+    }
+
+    // Record the snapshots of the coordinator and the events of the fleet
+    if config.storage.record_interval > 0 {
+        let recorder = Recorder::new(
+            &config.storage,
+            &config.backup,
+            id.clone(),
+            config.hardware.n_floors,
+            storage.clone(),
+            coordinator_query_tx.clone(),
+            net_backup_send_tx,
+        );
+        let recorder_thread = Builder::new().name("recorder".into());
+        recorder_thread.spawn(move || recorder.run()).unwrap();
+    }
+
+    // Apply the changes to config.toml, and the configuration updates from operators
+    let config_reloader = ConfigReloader::new(
+        &config,
+        CONFIG_FILE,
+        net_config_update_rx,
+        fsm_timings_tx,
+        coordinator_command_tx.clone(),
     );
+    match config_reloader {
+        Ok(config_reloader) => {
+            let reload_thread = Builder::new().name("reload".into());
+            reload_thread.spawn(move || config_reloader.run()).unwrap();
+        }
+        Err(e) => error!("{}. The configuration is not reloaded.", e),
+    }
+
+    // Show the status of the system on the panel, and keep the systemd watchdog alive while the coordinator answers
+    let health_monitor = HealthMonitor::new(id.clone(), coordinator_query_tx.clone(), hw_status_tx, SystemdNotifier::from_env());
+    let health_thread = Builder::new().name("health".into());
+    health_thread.spawn(move || health_monitor.run()).unwrap();
+
+    // Write the periodic service reports
+    if config.report.enabled {
+        let reporter = Reporter::new(&config.report, id.clone(), coordinator_query_tx.clone());
+        let reporter_thread = Builder::new().name("reporter".into());
+        reporter_thread.spawn(move || reporter.run()).unwrap();
+    }
+
+    // Start the JSON-RPC control socket for scripts
+    if config.rpc.enabled {
+        let rpc = Rpc::new(&config.rpc, coordinator_command_tx.clone(), coordinator_query_tx.clone());
+        let rpc_thread = Builder::new().name("rpc".into());
+        rpc_thread.spawn(move || rpc.run()).unwrap();
+    }
+
+    // Publish telemetry to the MQTT broker, and take mode changes from it
+    #[cfg(feature = "mqtt")]
+    if config.mqtt.enabled {
+        let mqtt = Mqtt::new(&config.mqtt, id.clone(), coordinator_command_tx.clone(), coordinator_query_tx.clone());
+        let mqtt_thread = Builder::new().name("mqtt".into());
+        mqtt_thread.spawn(move || mqtt.run()).unwrap();
+    }
+    #[cfg(not(feature = "mqtt"))]
+    if config.mqtt.enabled {
+        log::warn!("MQTT is enabled in the configuration, but the program is built without the mqtt feature");
+    }
+
+    // Serve the gRPC interface, translating calls into commands and queries to the coordinator
+    #[cfg(feature = "grpc")]
+    if config.grpc.enabled {
+        let grpc = Grpc::new(&config.grpc, id.clone(), coordinator_command_tx.clone(), coordinator_query_tx.clone());
+        let grpc_thread = Builder::new().name("grpc".into());
+        grpc_thread.spawn(move || grpc.run()).unwrap();
+    }
+    #[cfg(not(feature = "grpc"))]
+    if config.grpc.enabled {
+        log::warn!("gRPC is enabled in the configuration, but the program is built without the grpc feature");
+    }
+
+    // Start the coordinator module, only logging its decisions in a dry run
+    let dry_run = arguments.is_present("dry_run");
+    if dry_run {
+        info!("Dry run: the lights and the FSM are left alone, and the peers assign this elevator no hall calls");
+    }
+    let mut coordinator = CoordinatorBuilder::new(elevator_data, id, n_floors)
+        .floor_labels(floor_labels.clone())
+        .access_control(access_control)
+        .lockout_schedule(LockoutSchedule::new(&config.lockout))
+        .traffic_policy(TrafficPolicy::new(&config.traffic, n_floors))
+        .repositioning_policy(RepositioningPolicy::new(&config.repositioning))
+        .fire_recall_floor(config.fire_service.recall_floor)
+        .hall_call_cancel_window(std::time::Duration::from_millis(config.elevator.hall_call_cancel_window))
+        .hall_call_quorum(config.elevator.hall_call_quorum)
+        .hall_call_confirmation(config.elevator.hall_call_confirmation)
+        .hall_call_deadline(std::time::Duration::from_millis(config.elevator.hall_call_deadline))
+        .hall_clearing(config.elevator.hall_clearing)
+        .assigner(assigner)
+        .assign_in_background(true)
+        .assignment_model(config.elevator.assignment_model)
+        .adaptation_policy(AdaptationPolicy::new(&config.adaptation))
+        .consensus(consensus)
+        .network_counters(network.counters.clone())
+        .uptime(uptime.clone())
+        .clock(clock.clone())
+        .wal(wal)
+        .dry_run(dry_run)
+        .hw_button_light_tx(hw_button_light_tx)
+        .hw_request_rx(hw_request_rx)
+        .fsm_hall_requests_tx(fsm_hall_requests_tx)
+        .fsm_cab_request_tx(fsm_cab_request_tx)
+        .fsm_parking_floor_tx(fsm_parking_floor_tx)
+        .fsm_operating_mode_tx(fsm_operating_mode_tx)
+        .fsm_door_command_tx(fsm_door_command_tx)
+        .fsm_alarm_clear_tx(fsm_alarm_clear_tx)
+        .fsm_halt_tx(fsm_halt_tx)
+        .fsm_inspect_tx(fsm_inspect_tx)
+        .fsm_blocked_floors_tx(fsm_blocked_floors_tx)
+        .fsm_locked_floors_tx(fsm_locked_floors_tx)
+        .fsm_state_rx(fsm_state_rx)
+        .fsm_order_complete_rx(fsm_order_complete_rx)
+        .net_data_send_tx(net_data_send_tx)
+        .net_data_recv_rx(net_data_recv_rx)
+        .net_sync_recv_rx(net_sync_recv_rx)
+        .net_peer_update_rx(net_peer_update_rx)
+        .net_peer_version_rx(net_peer_version_rx)
+        .net_consensus_send_tx(net_consensus_send_tx)
+        .net_consensus_recv_rx(net_consensus_recv_rx)
+        .net_quality_rx(net_quality_rx)
+        .coordinator_command_rx(coordinator_command_rx)
+        .coordinator_query_rx(coordinator_query_rx)
+        .coordinator_terminate_rx(coordinator_terminate_rx)
+        .build()
+        .expect("Failed to build the coordinator");
 
     let coordinator_thread = Builder::new().name("coordinator".into());
-    coordinator_thread.spawn(move || coordinator.run()).unwrap();
+    let coordinator_stopped_tx = module_stopped_tx.clone();
+    coordinator_thread.spawn(move || coordinator_stopped_tx.send(("coordinator", coordinator.run()))).unwrap();
+
+    // The modules terminated when the program ends, all at once
+    let mut modules = vec![
+        ("coordinator", coordinator_terminate_tx),
+        ("FSM", fsm_terminate_tx),
+        ("driver", hw_terminate_tx),
+        ("network", net_terminate_tx),
+    ];
+    if config.relay.enabled {
+        modules.push(("relay", relay_terminate_tx));
+    }
+    if config.alert.enabled {
+        modules.push(("alert", alert_terminate_tx));
+    }
+
+    // The coordinator is ready once it answers a query
+    if let Err(e) = await_answer("coordinator", &coordinator_query_tx, module_timeout) {
+        error!("{}", e);
+        shut_down(&modules, ExitReason::Failure, Some(&uptime));
+    }
+    info!("The coordinator is ready, every module is started");
+
+    // The demo runs headless, in place of the debug console, and ends the program with its summary
+    if arguments.is_present("demo") {
+        let traffic_generator = TrafficGenerator::new(
+            &config.demo,
+            n_floors,
+            clock.clone(),
+            coordinator_command_tx.clone(),
+            coordinator_query_tx.clone(),
+        );
+        traffic_generator.run();
+        shut_down(&modules, ExitReason::Clean, Some(&uptime));
+    }
 
-    loop {
-        sleep(std::time::Duration::from_secs(1));
+    // The soak test runs the demo and the faults, and ends the program on the first violation of the invariants
+    if arguments.is_present("soak") {
+        let traffic_generator = TrafficGenerator::new(
+            &config.demo,
+            n_floors,
+            clock.clone(),
+            coordinator_command_tx.clone(),
+            coordinator_query_tx.clone(),
+        );
+        let (traffic_done_tx, traffic_done_rx) = cbc::bounded(1);
+        let soak_traffic_thread = Builder::new().name("soak_traffic".into());
+        soak_traffic_thread.spawn(move || traffic_done_tx.send(traffic_generator.run())).unwrap();
+
+        let chaos_controller = ChaosController::new(&config.soak, clock.clone(), coordinator_command_tx.clone(), net_peer_tx_enable_tx.clone());
+        let chaos_thread = Builder::new().name("chaos".into());
+        chaos_thread.spawn(move || chaos_controller.run()).unwrap();
+
+        let soak_monitor = SoakMonitor::new(&config.soak, network.id.clone(), clock.clone(), coordinator_query_tx.clone());
+        let reason = match soak_monitor.run(traffic_done_rx) {
+            Ok(_) => ExitReason::Clean,
+            Err(_) => ExitReason::Failure,
+        };
+        shut_down(&modules, reason, Some(&uptime));
+    }
+
+    // Start the debug console. The sender is cloned so the coordinator outlives a closed stdin.
+    let console = Console::new(coordinator_command_tx.clone(), floor_labels);
+    let console_thread = Builder::new().name("console".into());
+    console_thread.spawn(move || console.run()).unwrap();
+
+    // Supervise the modules. The elevator cannot run on without any of them, so the program ends once one stops.
+    let reason = match module_stopped_rx.recv() {
+        Ok((module, Err(e))) => {
+            error!("The {} stopped: {}", module, e);
+            let reason = match e {
+                ModuleError::Assigner(_) => ExitReason::AssignerMissing,
+                ModuleError::Disconnected(_) => ExitReason::Failure,
+            };
+            let _ = alert_failure_tx.send((module, e));
+            reason
+        }
+        Ok((module, Ok(()))) => {
+            info!("The {} terminated", module);
+            ExitReason::Clean
+        }
+        Err(_) => ExitReason::Clean,
+    };
+    shut_down(&modules, reason, Some(&uptime));
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Schedules the thread of the module as configured. A thread that cannot be scheduled runs on as it was.
+fn schedule(module: &str, thread_config: &config::ThreadConfig) {
+    if let Err(e) = schedule_thread(thread_config) {
+        warn!("The {} thread runs with the default scheduling: {}", module, e);
+    }
+}
+
+// Terminates the modules and exits with the code of the reason. A module that has not acknowledged by the deadline is
+// left behind, and ended with the process, which then fails.
+fn shut_down(modules: &[(&'static str, cbc::Sender<Terminate>)], reason: ExitReason, uptime: Option<&UptimeCounter>) -> ! {
+    let mut reason = reason;
+    for (module, termination) in terminate_modules(modules, SHUTDOWN_DEADLINE) {
+        match termination {
+            Termination::Acknowledged => info!("The {} terminated", module),
+            Termination::Stopped => info!("The {} had already stopped", module),
+            Termination::Hung => {
+                error!("The {} did not terminate within {:?}, leaving it behind", module, SHUTDOWN_DEADLINE);
+                if reason == ExitReason::Clean {
+                    reason = ExitReason::Failure;
+                }
+            }
+        }
+    }
+    // A run that did not end cleanly is counted as a watchdog reset at the next start
+    if let Some(uptime) = uptime {
+        match reason {
+            ExitReason::Clean => uptime.stop(),
+            _ => uptime.save(),
+        }
     }
+    exit(reason)
 }
@@ -0,0 +1,4 @@
+pub mod mqtt;
+pub mod mqtt_tests;
+
+pub use mqtt::Mqtt;
@@ -0,0 +1,352 @@
+/**
+ * MQTT publisher for elevator telemetry.
+ *
+ * A minimal MQTT 3.1.1 client, built with the `mqtt` feature. Every `publish_interval` milliseconds the snapshot
+ * of the coordinator is compared with the previous one, and the changes are published with QoS 0:
+ *
+ * - `state_topic`:   The state of the local elevator whenever it changes, retained by the broker.
+ * - `order_topic`:   Hall calls of the fleet and cab calls of the local elevator as they are placed and cleared.
+ * - `fault_topic`:   The local elevator entering or leaving the error state, obstruction alarms, and lost peers.
+ *
+ * If `command_topic` is set, the client subscribes to it and forwards the mode changes published there to the
+ * coordinator. Payloads are console commands, e.g. `traffic up-peak` or `fire recall`; calls are not accepted.
+ * A lost connection to the broker is retried every `reconnect_delay` milliseconds.
+ *
+ * # Fields
+ * - `broker_address`:          Address of the broker, e.g. `localhost:1883`.
+ * - `client_id`:               The client ID presented to the broker.
+ * - `state_topic`:             Topic for the state of the local elevator.
+ * - `order_topic`:             Topic for order events.
+ * - `fault_topic`:             Topic for fault events.
+ * - `command_topic`:           Topic mode changes are received on. Empty to only publish.
+ * - `publish_interval`:        Time between comparisons of the snapshot.
+ * - `keep_alive`:              Keep alive of the connection in seconds. 0 disables pings.
+ * - `reconnect_delay`:         Time between attempts to reach the broker.
+ * - `local_id`:                The ID of the local elevator.
+ * - `coordinator_command_tx`:  Sends mode changes to the coordinator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread::{self, Builder};
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::MqttConfig;
+use crate::console::console::parse_mode_command;
+use crate::shared::{fleet_events, ControlCommand, FleetEvent, Snapshot};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const PINGREQ: [u8; 2] = [0xC0, 0x00];
+const RETAIN: u8 = 0x01;
+const CLEAN_SESSION: u8 = 0x02;
+const PROTOCOL_LEVEL: u8 = 4;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// A message for one of the telemetry topics
+#[derive(Debug, PartialEq)]
+pub enum Telemetry {
+    State(Value),
+    Order(Value),
+    Fault(Value),
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Mqtt {
+    broker_address: String,
+    client_id: String,
+    state_topic: String,
+    order_topic: String,
+    fault_topic: String,
+    command_topic: String,
+    publish_interval: Duration,
+    keep_alive: u16,
+    reconnect_delay: Duration,
+    local_id: String,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+}
+
+impl Mqtt {
+    pub fn new(
+        mqtt_config: &MqttConfig,
+        local_id: String,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    ) -> Mqtt {
+        let with_id = |template: &str| template.replace("{id}", &local_id);
+        Mqtt {
+            broker_address: mqtt_config.broker_address.clone(),
+            client_id: with_id(&mqtt_config.client_id),
+            state_topic: with_id(&mqtt_config.state_topic),
+            order_topic: with_id(&mqtt_config.order_topic),
+            fault_topic: with_id(&mqtt_config.fault_topic),
+            command_topic: with_id(&mqtt_config.command_topic),
+            publish_interval: Duration::from_millis(mqtt_config.publish_interval),
+            keep_alive: mqtt_config.keep_alive,
+            reconnect_delay: Duration::from_millis(mqtt_config.reconnect_delay),
+            local_id,
+            coordinator_command_tx,
+            coordinator_query_tx,
+        }
+    }
+
+    // The elevator keeps running without the broker
+    pub fn run(self) {
+        loop {
+            if let Err(e) = self.session() {
+                warn!("MQTT connection to {} failed: {:?}", self.broker_address, e);
+            }
+            thread::sleep(self.reconnect_delay);
+        }
+    }
+
+    fn session(&self) -> io::Result<()> {
+        let mut stream = TcpStream::connect(&self.broker_address)?;
+        stream.write_all(&connect_packet(&self.client_id, self.keep_alive))?;
+        let (header, body) = read_packet(&mut stream)?;
+        if header & 0xF0 != CONNACK || body.get(1) != Some(&0) {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "The broker refused the connection"));
+        }
+        info!("Connected to MQTT broker at {} as {}", self.broker_address, self.client_id);
+
+        if !self.command_topic.is_empty() {
+            stream.write_all(&subscribe_packet(1, &self.command_topic))?;
+        }
+
+        // Thread receiving mode changes until the connection is closed
+        let mut reader = stream.try_clone()?;
+        let (closed_tx, closed_rx) = cbc::bounded::<io::Error>(1);
+        let command_topic = self.command_topic.clone();
+        let coordinator_command_tx = self.coordinator_command_tx.clone();
+        let mqtt_rx_thread = Builder::new().name("mqtt_rx".into());
+        mqtt_rx_thread.spawn(move || loop {
+            match read_packet(&mut reader) {
+                Ok((header, body)) => {
+                    let Some((topic, payload)) = parse_publish(header, &body) else { continue };
+                    if topic != command_topic {
+                        continue;
+                    }
+                    match parse_mode_command(&payload) {
+                        Ok(command) => {
+                            info!("MQTT command: {:?}", command);
+                            let _ = coordinator_command_tx.send(command);
+                        }
+                        Err(e) => warn!("Ignored MQTT command {:?}: {}", payload, e),
+                    }
+                }
+                Err(e) => {
+                    let _ = closed_tx.send(e);
+                    return;
+                }
+            }
+        })?;
+
+        let result = self.publish(&mut stream, closed_rx);
+        let _ = stream.shutdown(Shutdown::Both);
+        result
+    }
+
+    fn publish(&self, stream: &mut TcpStream, closed_rx: cbc::Receiver<io::Error>) -> io::Result<()> {
+        let publish_ticker = cbc::tick(self.publish_interval);
+        let ping_ticker = match self.keep_alive {
+            0 => cbc::never(),
+            keep_alive => cbc::tick(Duration::from_secs(keep_alive as u64) / 2),
+        };
+        let mut previous: Option<Snapshot> = None;
+
+        loop {
+            cbc::select! {
+                recv(publish_ticker) -> _ => {
+                    if let Some(snapshot) = self.query() {
+                        for message in telemetry(&self.local_id, previous.as_ref(), &snapshot) {
+                            let packet = match message {
+                                Telemetry::State(payload) => publish_packet(&self.state_topic, &payload.to_string(), true),
+                                Telemetry::Order(payload) => publish_packet(&self.order_topic, &payload.to_string(), false),
+                                Telemetry::Fault(payload) => publish_packet(&self.fault_topic, &payload.to_string(), false),
+                            };
+                            stream.write_all(&packet)?;
+                        }
+                        previous = Some(snapshot);
+                    }
+                }
+                recv(ping_ticker) -> _ => {
+                    stream.write_all(&PINGREQ)?;
+                }
+                recv(closed_rx) -> error => {
+                    return Err(error.unwrap_or_else(|_| io::Error::from(io::ErrorKind::ConnectionAborted)));
+                }
+            }
+        }
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The fleet events as messages for the telemetry topics
+fn telemetry(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot) -> Vec<Telemetry> {
+    fleet_events(local_id, previous, snapshot)
+        .into_iter()
+        .map(|event| match event {
+            FleetEvent::State(state) => Telemetry::State(json!({ "id": local_id, "state": state })),
+            FleetEvent::Call(floor, call, placed) => {
+                let event = if placed { "placed" } else { "cleared" };
+                let call = match call {
+                    HALL_UP => "up",
+                    HALL_DOWN => "down",
+                    _ => "cab",
+                };
+                Telemetry::Order(json!({ "event": event, "call": call, "floor": floor }))
+            }
+            FleetEvent::Fault(floor, failed) => {
+                let event = if failed { "error" } else { "recovered" };
+                Telemetry::Fault(json!({ "event": event, "elevator": local_id, "floor": floor }))
+            }
+            FleetEvent::PeerLost(peer) => Telemetry::Fault(json!({ "event": "peerLost", "elevator": peer })),
+            FleetEvent::ObstructionAlarm(id, floor, active) => {
+                let event = if active { "obstructionAlarm" } else { "obstructionAlarmCleared" };
+                Telemetry::Fault(json!({ "event": event, "elevator": id, "floor": floor }))
+            }
+        })
+        .collect()
+}
+
+fn connect_packet(client_id: &str, keep_alive: u16) -> Vec<u8> {
+    let mut body = encode_string("MQTT");
+    body.push(PROTOCOL_LEVEL);
+    body.push(CLEAN_SESSION);
+    body.extend_from_slice(&keep_alive.to_be_bytes());
+    body.extend(encode_string(client_id));
+    packet(CONNECT, body)
+}
+
+fn publish_packet(topic: &str, payload: &str, retain: bool) -> Vec<u8> {
+    let mut body = encode_string(topic);
+    body.extend_from_slice(payload.as_bytes());
+    packet(if retain { PUBLISH | RETAIN } else { PUBLISH }, body)
+}
+
+fn subscribe_packet(packet_id: u16, topic: &str) -> Vec<u8> {
+    let mut body = packet_id.to_be_bytes().to_vec();
+    body.extend(encode_string(topic));
+    body.push(0);
+    packet(SUBSCRIBE, body)
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut packet = vec![header];
+    packet.extend(encode_remaining_length(body.len()));
+    packet.extend(body);
+    packet
+}
+
+fn encode_string(text: &str) -> Vec<u8> {
+    let mut encoded = (text.len() as u16).to_be_bytes().to_vec();
+    encoded.extend_from_slice(text.as_bytes());
+    encoded
+}
+
+// Seven bits per byte, least significant first, the top bit telling whether more bytes follow
+fn encode_remaining_length(mut length: usize) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        encoded.push(byte);
+        if length == 0 {
+            return encoded;
+        }
+    }
+}
+
+// Reads the fixed header and the body of the next packet
+fn read_packet(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut byte = [0; 1];
+    stream.read_exact(&mut byte)?;
+    let header = byte[0];
+
+    let mut length = 0;
+    for shift in (0..28).step_by(7) {
+        stream.read_exact(&mut byte)?;
+        length |= ((byte[0] & 0x7F) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body)?;
+            return Ok((header, body));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "Malformed remaining length"))
+}
+
+// The topic and payload of a PUBLISH packet. Packets with QoS above 0 carry a packet ID after the topic.
+fn parse_publish(header: u8, body: &[u8]) -> Option<(String, String)> {
+    if header & 0xF0 != PUBLISH {
+        return None;
+    }
+    let topic_length = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = std::str::from_utf8(body.get(2..2 + topic_length)?).ok()?;
+    let payload_start = if header & 0x06 == 0 { 2 + topic_length } else { 4 + topic_length };
+    let payload = std::str::from_utf8(body.get(payload_start..)?).ok()?;
+    Some((topic.to_string(), payload.trim().to_string()))
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::Telemetry;
+    use crate::shared::Snapshot;
+    use std::io::{self, Read};
+
+    pub fn test_telemetry(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot) -> Vec<Telemetry> {
+        super::telemetry(local_id, previous, snapshot)
+    }
+
+    pub fn test_encode_remaining_length(length: usize) -> Vec<u8> {
+        super::encode_remaining_length(length)
+    }
+
+    pub fn test_publish_packet(topic: &str, payload: &str, retain: bool) -> Vec<u8> {
+        super::publish_packet(topic, payload, retain)
+    }
+
+    pub fn test_read_packet(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+        super::read_packet(stream)
+    }
+
+    pub fn test_parse_publish(header: u8, body: &[u8]) -> Option<(String, String)> {
+        super::parse_publish(header, body)
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * Unit tests for the MQTT telemetry publisher
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * Packets are encoded and decoded in memory, without a broker.
+ *
+ * Tests:
+ *  - test_mqtt_encode_packets
+ *  - test_mqtt_read_publish
+ *  - test_mqtt_telemetry
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod mqtt_tests {
+    use crate::mqtt::mqtt::testing::{
+        test_encode_remaining_length, test_parse_publish, test_publish_packet, test_read_packet,
+        test_telemetry,
+    };
+    use crate::mqtt::mqtt::Telemetry;
+    use crate::shared::{Behaviour, NetworkStatistics, Snapshot, Uptime};
+    use crate::{ElevatorData, ElevatorState};
+    use driver_rust::elevio::elev::HALL_UP;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string(), "b".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    #[test]
+    fn test_mqtt_encode_packets() {
+        // Act
+        let publish = test_publish_packet("t", "on", true);
+
+        // Assert
+        assert_eq!(test_encode_remaining_length(0), vec![0x00]);
+        assert_eq!(test_encode_remaining_length(127), vec![0x7F]);
+        assert_eq!(test_encode_remaining_length(128), vec![0x80, 0x01]);
+        assert_eq!(test_encode_remaining_length(16_384), vec![0x80, 0x80, 0x01]);
+        // Retained PUBLISH, remaining length, topic length, topic and payload
+        assert_eq!(publish, vec![0x31, 0x05, 0x00, 0x01, b't', b'o', b'n']);
+    }
+
+    #[test]
+    fn test_mqtt_read_publish() {
+        // Arrange
+        let payload = "x".repeat(200);
+        let mut stream = Cursor::new(test_publish_packet("elevators/a/command", &payload, false));
+
+        // Act
+        let (header, body) = test_read_packet(&mut stream).unwrap();
+        let message = test_parse_publish(header, &body);
+
+        // Assert
+        // The remaining length takes two bytes, and the whole packet is consumed
+        assert_eq!(message, Some(("elevators/a/command".to_string(), payload)));
+        assert_eq!(stream.position() as usize, stream.get_ref().len());
+        assert!(test_read_packet(&mut stream).is_err());
+        assert_eq!(test_parse_publish(0x20, &[0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn test_mqtt_telemetry() {
+        // Arrange
+        let previous = snapshot();
+        let mut snapshot = snapshot();
+        snapshot.elevator_data.hall_requests[2][HALL_UP as usize] = true;
+        snapshot.elevator_data.states.get_mut("a").unwrap().cab_requests[3] = true;
+        snapshot.elevator_data.states.get_mut("a").unwrap().behaviour = Behaviour::Error;
+        snapshot.elevator_data.states.get_mut("b").unwrap().floor = 1;
+        snapshot.peers.retain(|peer| peer != "b");
+
+        // Act
+        let first = test_telemetry("a", None, &previous);
+        let unchanged = test_telemetry("a", Some(&previous), &previous);
+        let changed = test_telemetry("a", Some(&previous), &snapshot);
+
+        // Assert
+        // The first snapshot only publishes the state, and nothing is published without changes
+        assert_eq!(first, vec![Telemetry::State(json!({ "id": "a", "state": previous.elevator_data.states["a"] }))]);
+        assert!(unchanged.is_empty());
+        // Only the local elevator is reported, along with the hall calls of the fleet
+        assert_eq!(
+            changed,
+            vec![
+                Telemetry::State(json!({ "id": "a", "state": snapshot.elevator_data.states["a"] })),
+                Telemetry::Order(json!({ "event": "placed", "call": "up", "floor": 2 })),
+                Telemetry::Order(json!({ "event": "placed", "call": "cab", "floor": 3 })),
+                Telemetry::Fault(json!({ "event": "error", "elevator": "a", "floor": 0 })),
+                Telemetry::Fault(json!({ "event": "peerLost", "elevator": "b" })),
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+/**
+ * Encryption of the datagrams with a key shared by the fleet, so other machines on the shared network can neither
+ * read the state of the elevators nor spoof or alter it.
+ *
+ * Every datagram is sealed with ChaCha20-Poly1305 under `encryption_key`, with a random nonce of its own. The tag
+ * authenticates it, so a datagram forged, altered or sealed with another key fails to open, and is dropped. An
+ * encrypted datagram starts with a flag byte, which never starts a plaintext one, followed by the nonce and the
+ * ciphertext. The receiver tells the two apart by that flag, so encrypted and plaintext elevators interoperate
+ * while a fleet is moved to encryption: in `mixed` mode datagrams are sent encrypted and plaintext ones still
+ * accepted, and only in `required` mode are plaintext ones dropped. Encrypted datagrams are read with the key in
+ * `plaintext` mode too.
+ *
+ * Replayed datagrams are dropped. Every cipher, and its clones, seals a random sender tag and a counter raised for
+ * every datagram in front of the payload, where they are authenticated with it. The receiver keeps the highest
+ * counter opened of every sender, and a window of the `REPLAY_WINDOW` counters below it, and drops a datagram whose
+ * counter it has seen or which is below the window. Datagrams reordered within the window are still opened. Every
+ * clone keeps a record of its own, so each socket read with a clone is checked on its own. A restarted elevator
+ * seals with a new tag, and is not taken for a replay. Plaintext datagrams carry no counter, so their replays are
+ * only stopped by requiring encryption.
+ *
+ * The peer broadcasts and the mDNS announcements are sealed the same way, so no one without the key can announce a
+ * peer or replay the announcement of one that is gone.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::Encryption;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// Not valid in UTF-8, so no plaintext datagram starts with it
+const ENCRYPTED_FLAG: u8 = 0xff;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+const KEY_SIZE: usize = 32;
+const SENDER_SIZE: usize = 8;
+const COUNTER_SIZE: usize = 8;
+// The counters below the highest one opened of a sender that are still accepted once, if not seen
+const REPLAY_WINDOW: u64 = 64;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Default)]
+pub struct Cipher {
+    encryption: Encryption,
+    aead: Option<ChaCha20Poly1305>,
+    sender: u64,
+    counter: Arc<AtomicU64>,
+    opened: Mutex<HashMap<u64, ReplayWindow>>,
+}
+
+// The clones seal as the same sender, and keep a record of their own of the counters opened
+impl Clone for Cipher {
+    fn clone(&self) -> Cipher {
+        Cipher {
+            encryption: self.encryption,
+            aead: self.aead.clone(),
+            sender: self.sender,
+            counter: self.counter.clone(),
+            opened: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Cipher {
+    // The cipher of the mode with the key given in hexadecimal. Without a key, only plaintext is read and sent.
+    pub fn new(encryption: Encryption, key: &str) -> Result<Cipher, String> {
+        let aead = match key.is_empty() {
+            true => None,
+            false => Some(ChaCha20Poly1305::new(&parse_key(key)?)),
+        };
+        if aead.is_none() && encryption != Encryption::Plaintext {
+            return Err(format!("Encryption {:?} needs an encryption_key", encryption));
+        }
+        Ok(Cipher {
+            encryption,
+            aead,
+            sender: OsRng.next_u64(),
+            counter: Arc::new(AtomicU64::new(0)),
+            opened: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // The bytes sealing adds to a datagram
+    pub fn overhead(&self) -> usize {
+        match self.encrypts() {
+            true => 1 + NONCE_SIZE + SENDER_SIZE + COUNTER_SIZE + TAG_SIZE,
+            false => 0,
+        }
+    }
+
+    // The datagram to send, encrypted unless sent in plaintext
+    pub fn seal(&self, datagram: &[u8]) -> Vec<u8> {
+        let aead = match (self.encrypts(), self.aead.as_ref()) {
+            (true, Some(aead)) => aead,
+            _ => return datagram.to_vec(),
+        };
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut payload = Vec::with_capacity(SENDER_SIZE + COUNTER_SIZE + datagram.len());
+        payload.extend(self.sender.to_be_bytes());
+        payload.extend(counter.to_be_bytes());
+        payload.extend(datagram);
+
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut sealed = vec![ENCRYPTED_FLAG];
+        sealed.extend(nonce.as_slice());
+        sealed.extend(aead.encrypt(&nonce, payload.as_slice()).expect("Failed to encrypt a datagram"));
+        sealed
+    }
+
+    // The plaintext of a received datagram, or `None` if it is to be dropped: an encrypted one that does not open or
+    // is replayed, and a plaintext one where encryption is required
+    pub fn open(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        match packet.first() {
+            Some(&ENCRYPTED_FLAG) => {
+                let nonce = packet.get(1..1 + NONCE_SIZE)?;
+                let payload = self.aead.as_ref()?.decrypt(Nonce::from_slice(nonce), &packet[1 + NONCE_SIZE..]).ok()?;
+                let sender = u64::from_be_bytes(payload.get(..SENDER_SIZE)?.try_into().ok()?);
+                let counter = u64::from_be_bytes(payload.get(SENDER_SIZE..SENDER_SIZE + COUNTER_SIZE)?.try_into().ok()?);
+                let fresh = self.opened.lock().unwrap().entry(sender).or_default().accept(counter);
+                fresh.then(|| payload[SENDER_SIZE + COUNTER_SIZE..].to_vec())
+            }
+            _ => (self.encryption != Encryption::Required).then(|| packet.to_vec()),
+        }
+    }
+
+    // Whether datagrams are sent encrypted
+    pub fn encrypts(&self) -> bool {
+        self.encryption != Encryption::Plaintext && self.aead.is_some()
+    }
+}
+
+/***************************************/
+/*       Local data structures         */
+/***************************************/
+// The highest counter opened of a sender, and which of the `REPLAY_WINDOW` counters below it were opened
+#[derive(Default)]
+struct ReplayWindow {
+    highest: u64,
+    opened: u64,
+}
+
+impl ReplayWindow {
+    // Records the counter, returning false if it was opened before or is too old to tell
+    fn accept(&mut self, counter: u64) -> bool {
+        if counter > self.highest {
+            let shift = counter - self.highest;
+            self.opened = if shift >= REPLAY_WINDOW { 0 } else { self.opened << shift };
+            self.opened |= 1;
+            self.highest = counter;
+            return true;
+        }
+
+        let age = self.highest - counter;
+        if age >= REPLAY_WINDOW || self.opened & (1 << age) != 0 {
+            return false;
+        }
+        self.opened |= 1 << age;
+        true
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Whether a datagram is encrypted, by its flag
+pub fn is_encrypted(packet: &[u8]) -> bool {
+    packet.first() == Some(&ENCRYPTED_FLAG)
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn parse_key(key: &str) -> Result<Key, String> {
+    let key = key.trim();
+    let bytes = (0..key.len())
+        .step_by(2)
+        .map(|index| key.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok()))
+        .collect::<Option<Vec<u8>>>();
+    match bytes {
+        Some(bytes) if bytes.len() == KEY_SIZE => Ok(*Key::from_slice(&bytes)),
+        _ => Err(format!("The encryption_key must be {} hexadecimal digits", KEY_SIZE * 2)),
+    }
+}
@@ -0,0 +1,161 @@
+/**
+ * Sizing of the datagrams to the MTU, and fragmentation of the messages that do not fit in one.
+ *
+ * A datagram larger than the MTU of the path is fragmented by IP, and lost whole if any of its IP fragments is
+ * lost, which on a lossy WiFi makes large messages much likelier to be lost than small ones. No datagram is sent
+ * larger than the MTU, less the IP and UDP headers and room for a relay to wrap it. The MTU is configured, or
+ * detected as the smallest MTU of the network interfaces that are up, and taken as the 1500 bytes of Ethernet
+ * where it cannot be read.
+ *
+ * A message that does not fit in one datagram is split into `fragment` messages, each carrying a part of the
+ * serialized envelope, and addressed like it, so fragments pass through a relay like any message. The receiver
+ * reassembles the envelope once it has every fragment, and handles it as if it came whole. Fragments of a message
+ * that is not complete within `REASSEMBLY_TIMEOUT` are dropped, as the message is lost.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::network::{Envelope, GossipMessage};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const DEFAULT_MTU: usize = 1500;
+// The IPv4 and UDP headers
+const HEADERS: usize = 28;
+// The wrapping of a message forwarded by a relay
+const RELAY_HEADROOM: usize = 16;
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(2);
+// Fragments a message may be split into, so a peer cannot make the receiver hold an unbounded message
+const MAX_FRAGMENTS: u16 = 64;
+// The smallest datagram sent, so a fragment always carries some of the message
+const MIN_DATAGRAM: usize = 256;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// The largest datagram to send on a path with the configured MTU, or the detected one if it is 0
+pub fn datagram_limit(mtu: usize) -> usize {
+    let mtu = match mtu {
+        0 => detect_mtu().unwrap_or(DEFAULT_MTU),
+        mtu => mtu,
+    };
+    mtu.saturating_sub(HEADERS + RELAY_HEADROOM).max(MIN_DATAGRAM)
+}
+
+// The datagrams carrying an envelope, the serialized envelope itself if it fits in one
+pub fn fragment(envelope: &Envelope, limit: usize) -> Vec<String> {
+    let serialized = serde_json::to_string(envelope).unwrap();
+    if serialized.len() <= limit {
+        return vec![serialized];
+    }
+
+    // Every fragment takes as much of the rest as fits, sized with the largest count it can carry
+    let message = RandomState::new().build_hasher().finish();
+    let mut payloads = Vec::new();
+    let mut rest = serialized.as_str();
+    while !rest.is_empty() {
+        let fits = |end: usize| fragment_datagram(envelope, message, u16::MAX, u16::MAX, &rest[..end]).len() <= limit;
+        let mut end = largest(rest, fits);
+        // A limit below the size of an empty fragment still makes progress
+        if end == 0 {
+            end = rest.char_indices().nth(1).map_or(rest.len(), |(index, _)| index);
+        }
+        payloads.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let count = payloads.len() as u16;
+    payloads
+        .iter()
+        .enumerate()
+        .map(|(index, payload)| fragment_datagram(envelope, message, index as u16, count, payload))
+        .collect()
+}
+
+// Fragments of the messages being reassembled, by origin and message
+#[derive(Default)]
+pub struct Reassembly {
+    partial: HashMap<(String, u64), Partial>,
+}
+
+impl Reassembly {
+    // Adds a fragment, and returns the envelope once all of its fragments have come. Fragments that do not fit
+    // the message they claim to be of are dropped.
+    pub fn add(&mut self, origin: &str, message: u64, index: u16, count: u16, payload: String) -> Option<Envelope> {
+        let now = Instant::now();
+        self.partial.retain(|_, partial| now.duration_since(partial.started) < REASSEMBLY_TIMEOUT);
+        if count == 0 || count > MAX_FRAGMENTS || index >= count {
+            return None;
+        }
+
+        let key = (origin.to_string(), message);
+        let partial = self.partial.entry(key.clone()).or_insert_with(|| Partial {
+            started: now,
+            payloads: vec![None; count as usize],
+        });
+        if partial.payloads.len() != count as usize {
+            return None;
+        }
+        partial.payloads[index as usize] = Some(payload);
+        if partial.payloads.iter().any(Option::is_none) {
+            return None;
+        }
+
+        let serialized = self.partial.remove(&key)?.payloads.into_iter().flatten().collect::<String>();
+        serde_json::from_str::<Envelope>(&serialized).ok().filter(|envelope| envelope.origin == origin)
+    }
+}
+
+/***************************************/
+/*      Local data structures          */
+/***************************************/
+struct Partial {
+    started: Instant,
+    payloads: Vec<Option<String>>,
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn fragment_datagram(envelope: &Envelope, message: u64, index: u16, count: u16, payload: &str) -> String {
+    let fragment = Envelope {
+        origin: envelope.origin.clone(),
+        destination: envelope.destination.clone(),
+        message: GossipMessage::Fragment { message, index, count, payload: payload.to_string() },
+        sequence: None,
+        group: envelope.group.clone(),
+    };
+    serde_json::to_string(&fragment).unwrap()
+}
+
+// The end of the longest prefix of the text, on a character boundary, for which `fits` holds
+fn largest(text: &str, fits: impl Fn(usize) -> bool) -> usize {
+    let boundaries = text.char_indices().map(|(index, _)| index).skip(1).chain([text.len()]).collect::<Vec<usize>>();
+    let fitting = boundaries.partition_point(|end| fits(*end));
+    match fitting {
+        0 => 0,
+        fitting => boundaries[fitting - 1],
+    }
+}
+
+// The smallest MTU of the network interfaces that are up, other than the loopback
+fn detect_mtu() -> Option<usize> {
+    fs::read_dir("/sys/class/net")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name() != "lo")
+        .filter(|entry| fs::read_to_string(entry.path().join("operstate")).is_ok_and(|state| state.trim() == "up"))
+        .filter_map(|entry| fs::read_to_string(entry.path().join("mtu")).ok()?.trim().parse::<usize>().ok())
+        .min()
+}
@@ -0,0 +1,276 @@
+/**
+ * Discovery of the peers by mDNS, as the service `_elevator._udp`, in place of the broadcast beacons.
+ *
+ * Every `peer_interval` milliseconds each node multicasts an unsolicited mDNS response to 224.0.0.251:5353,
+ * announcing an instance of `_elevator._udp.local` named by its ID: the PTR of the service to the instance, the
+ * SRV with the message port, the TXT with the ID and the address, and the A with the IP. Multicast DNS is relayed
+ * between subnets by the mDNS gateways of the network, where the broadcasts are filtered, and the elevators show
+ * in any zeroconf browser. The announcements are read from the TXT records of the instances, and a peer not
+ * announced for `peer_timeout` milliseconds is lost, as with the beacons, so the coordinator gets the same updates.
+ *
+ * Only the records of `_elevator._udp` are read, and the other mDNS traffic on the network is skipped. No queries
+ * are answered, since every node announces itself unasked.
+ *
+ * With `encryption` on, the TXT record holds only the peer sealed with the key of the fleet, in hexadecimal, in
+ * place of the ID and address in plaintext, and only sealed peers are read. The packet is sealed afresh for every
+ * announcement, so a replayed one is dropped. The plaintext entries are only sent and read by nodes that do not
+ * encrypt, so while a fleet is moved to encryption the nodes still sending plaintext are not discovered by mDNS by
+ * the others. The instance and host names still hold the label of the ID.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use network_rust::udpnet::peers::PeerUpdate;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::Instant;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::network::encryption::Cipher;
+use crate::network::network::{split_peer, stopping};
+use crate::network::peers::{PeerSettings, PeerTable};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const SERVICE: &str = "_elevator._udp.local";
+const PACKET_SIZE: usize = 9000;
+// The DNS record types and class
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+const CACHE_FLUSH: u16 = 0x8000;
+const MAX_LABEL: usize = 63;
+// The hexadecimal digits of the sealed peer in every TXT entry
+const SEALED_CHUNK: usize = 240;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Announces the peer `<id>@<address>` while enabled, as `peers::tx`, until `tx_enable` is disconnected
+pub fn tx(settings: &PeerSettings, cipher: Cipher, peer: String, tx_enable: cbc::Receiver<bool>) -> io::Result<()> {
+    let socket = UdpSocket::from(socket()?);
+    socket.set_multicast_ttl_v4(255)?;
+    let ttl = settings.timeout.as_secs().max(1) as u32;
+    let ticker = cbc::tick(settings.interval);
+    let mut enabled = true;
+    loop {
+        cbc::select! {
+            recv(tx_enable) -> enable => {
+                match enable {
+                    Ok(enable) => enabled = enable,
+                    Err(_) => return Ok(()),
+                }
+            }
+            recv(ticker) -> _ => {
+                // An announcement that fails, as while the network is down, is made up for by the next
+                if enabled {
+                    let _ = socket.send_to(&announcement(&peer, ttl, &cipher), SocketAddrV4::new(MDNS_GROUP, MDNS_PORT));
+                }
+            }
+        }
+    }
+}
+
+// Receives the announcements of the service, and sends an update whenever a peer is found or lost, as `peers::rx`,
+// until `stop_rx` is disconnected
+pub fn rx(settings: &PeerSettings, cipher: Cipher, update: cbc::Sender<PeerUpdate>, stop_rx: cbc::Receiver<()>) -> io::Result<()> {
+    let socket = socket()?;
+    socket
+        .bind(&SocketAddr::from(([0, 0, 0, 0], MDNS_PORT)).into())
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to bind the mDNS port {}: {}", MDNS_PORT, e)))?;
+    let socket = UdpSocket::from(socket);
+    socket.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_read_timeout(Some(settings.interval))?;
+
+    let mut table = PeerTable::new(settings.timeout);
+    let mut buffer = [0; PACKET_SIZE];
+    while !stopping(&stop_rx) {
+        let peers = match socket.recv(&mut buffer) {
+            Ok(number_of_bytes) => announced_peers(&buffer[..number_of_bytes], &cipher),
+            Err(_) => Vec::new(),
+        };
+        let mut heard = peers.into_iter().map(Some).collect::<Vec<Option<String>>>();
+        if heard.is_empty() {
+            heard.push(None);
+        }
+        table.set_timeout(settings.current_timeout());
+        for peer in heard {
+            if let Some(peer_update) = table.update(peer, Instant::now()) {
+                update.send(peer_update).map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// The mDNS response announcing the peer `<id>@<address>` for `ttl` seconds, sealed with the cipher if it encrypts
+pub fn announcement(peer: &str, ttl: u32, cipher: &Cipher) -> Vec<u8> {
+    let (id, address) = split_peer(peer);
+    let address = address.parse::<SocketAddrV4>().ok();
+    let label = id.chars().filter(|c| *c != '.').take(MAX_LABEL).collect::<String>();
+    let instance = format!("{}.{}", label, SERVICE);
+    let host = format!("{}.local", label);
+
+    let mut records = vec![record(SERVICE, TYPE_PTR, CLASS_IN, ttl, &name(&instance))];
+    if let Some(address) = address {
+        let mut srv = vec![0, 0, 0, 0];
+        srv.extend(address.port().to_be_bytes());
+        srv.extend(name(&host));
+        records.push(record(&instance, TYPE_SRV, CLASS_IN | CACHE_FLUSH, ttl, &srv));
+        records.push(record(&host, TYPE_A, CLASS_IN | CACHE_FLUSH, ttl, &address.ip().octets()));
+    }
+    let entries = match cipher.encrypts() {
+        // The sealed peer is split over as many entries as it takes, as an entry holds up to 255 bytes
+        true => {
+            let sealed = cipher.seal(peer.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+            sealed.as_bytes().chunks(SEALED_CHUNK).map(|chunk| format!("sealed={}", String::from_utf8_lossy(chunk))).collect()
+        }
+        false => vec![format!("id={}", id), format!("peer={}", peer)],
+    };
+    let mut txt = Vec::new();
+    for entry in entries {
+        txt.push(entry.len().min(255) as u8);
+        txt.extend(&entry.as_bytes()[..entry.len().min(255)]);
+    }
+    records.push(record(&instance, TYPE_TXT, CLASS_IN | CACHE_FLUSH, ttl, &txt));
+
+    // A response, authoritative, with all the records as answers
+    let mut packet = vec![0, 0, 0x84, 0, 0, 0];
+    packet.extend((records.len() as u16).to_be_bytes());
+    packet.extend([0, 0, 0, 0]);
+    for record in records {
+        packet.extend(record);
+    }
+    packet
+}
+
+// The peers `<id>@<address>` announced in the TXT records of the service in an mDNS packet, which open with the
+// cipher. Only sealed peers are read with encryption on.
+pub fn announced_peers(packet: &[u8], cipher: &Cipher) -> Vec<String> {
+    read_records(packet)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(name, record_type, _)| *record_type == TYPE_TXT && name.to_ascii_lowercase().ends_with(&format!(".{}", SERVICE)))
+        .filter_map(|(_, _, data)| {
+            let entries = txt_entries(&data);
+            let sealed = entries.iter().filter_map(|entry| entry.strip_prefix("sealed=")).collect::<String>();
+            let peer = match sealed.is_empty() {
+                true if !cipher.encrypts() => entries.iter().find_map(|entry| entry.strip_prefix("peer="))?.as_bytes().to_vec(),
+                true => return None,
+                false => parse_hex(&sealed)?,
+            };
+            String::from_utf8(cipher.open(&peer)?).ok()
+        })
+        .collect()
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// A socket for mDNS, shared with the other responders on the computer
+fn socket() -> io::Result<Socket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    Ok(socket)
+}
+
+// A name in the wire format, without compression
+fn name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for label in name.split('.') {
+        encoded.push(label.len() as u8);
+        encoded.extend(label.as_bytes());
+    }
+    encoded.push(0);
+    encoded
+}
+
+fn record(owner: &str, record_type: u16, class: u16, ttl: u32, data: &[u8]) -> Vec<u8> {
+    let mut encoded = name(owner);
+    encoded.extend(record_type.to_be_bytes());
+    encoded.extend(class.to_be_bytes());
+    encoded.extend(ttl.to_be_bytes());
+    encoded.extend((data.len() as u16).to_be_bytes());
+    encoded.extend(data);
+    encoded
+}
+
+// The name, type and data of the records of every section of a packet, or `None` if it is malformed
+fn read_records(packet: &[u8]) -> Option<Vec<(String, u16, Vec<u8>)>> {
+    let count = |offset: usize| Some(u16::from_be_bytes(packet.get(offset..offset + 2)?.try_into().ok()?) as usize);
+    let questions = count(4)?;
+    let records = count(6)? + count(8)? + count(10)?;
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(packet, offset)?.1 + 4;
+    }
+    let mut read = Vec::new();
+    for _ in 0..records {
+        let (owner, end) = read_name(packet, offset)?;
+        let record_type = count(end)? as u16;
+        let length = count(end + 8)?;
+        let data = packet.get(end + 10..end + 10 + length)?.to_vec();
+        read.push((owner, record_type, data));
+        offset = end + 10 + length;
+    }
+    Some(read)
+}
+
+// A name at the offset, following the compression pointers, and the offset after it
+fn read_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Every pointer must point backwards, so a malformed packet cannot loop
+    let mut limit = offset;
+    loop {
+        let length = *packet.get(offset)? as usize;
+        match length {
+            0 => break,
+            length if length & 0xc0 == 0xc0 => {
+                let target = ((length & 0x3f) << 8) | *packet.get(offset + 1)? as usize;
+                if target >= limit {
+                    return None;
+                }
+                end.get_or_insert(offset + 2);
+                limit = target;
+                offset = target;
+            }
+            length => {
+                labels.push(String::from_utf8_lossy(packet.get(offset + 1..offset + 1 + length)?).to_string());
+                offset += 1 + length;
+            }
+        }
+    }
+    Some((labels.join("."), end.unwrap_or(offset + 1)))
+}
+
+// The bytes written in hexadecimal, or `None` if malformed
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| hex.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok()))
+        .collect()
+}
+
+// The strings of the data of a TXT record
+fn txt_entries(data: &[u8]) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while let Some(length) = data.get(offset).map(|length| *length as usize) {
+        let Some(entry) = data.get(offset + 1..offset + 1 + length) else { break };
+        entries.push(String::from_utf8_lossy(entry).to_string());
+        offset += 1 + length;
+    }
+    entries
+}
@@ -1,3 +1,14 @@
+pub mod encryption;
+pub mod fragment;
+pub mod mdns;
 pub mod network;
+pub mod network_tests;
+pub mod peers;
+pub mod quality;
+pub mod relay;
+pub mod relay_tests;
+pub mod retransmit;
+pub mod shaper;
 
-pub use network::Network;
+pub use network::{request_backup, BackupCommand, Digest, Envelope, GossipMessage, Network, NetworkCounters};
+pub use relay::{Relay, RelayMessage};
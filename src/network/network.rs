@@ -2,22 +2,121 @@
  * Facilitates network communications for the elevator system.
  *
  * This module sets up networking capabilities, allowing for the sending and receiving
- * of elevator data and peer updates over UDP with acknowledgements. It manages network interactions necessary
+ * of elevator data and peer updates over UDP. It manages network interactions necessary
  * for the distributed operation of elevator controllers. It communicates with the
  * coordinator thread. 
  *
+ * Elevator data is spread by gossip. Each node periodically sends a digest of its latest data
+ * to a random peer, which answers with its own data if it is ahead, or pulls the data if it is behind.
+ * Lost packets are made up for by the next round, so no acknowledgements are needed.
+ *
+ * Digests only tell which data is newer, so data that has diverged at the same version is never exchanged.
+ * Every `anti_entropy_interval` milliseconds each node therefore sends a hash of its data to a random peer.
+ * If the hashes differ, the two nodes exchange their data, and both coordinators merge it whatever its version.
+ *
+ * In consensus mode, the consensus messages of the coordinator are sent on the same socket,
+ * directly to the addressed peer. Lost consensus messages are retried by the consensus itself.
+ *
+ * Elevators are identified by an ID generated on first run and kept in `id_file`, so an elevator whose
+ * address changes stays the same elevator. The peer broadcast carries `<id>@<address>`, from which the
+ * current address of every peer is looked up. The coordinator only ever sees the IDs. If `msg_port` is
+ * taken, as by another group in the lab, the first free port of the `port_range` ports after it is used
+ * instead, and broadcast in the address. How often the
+ * broadcast is sent, and how long a peer may go unheard before it is lost, are configured. See `peers`.
+ *
+ * Two live nodes broadcasting the same ID, as with an ID file or config cloned to another machine, make the data
+ * of the fleet flap between them. A node that hears its own ID broadcast from another address logs it loudly, and
+ * the one at the higher address stands down, so both decide the same: it is quarantined, stops broadcasting its ID,
+ * sends nothing and drops what it receives, and the coordinator runs alone. It rejoins once the other is gone.
+ *
+ * The broadcast also carries the build of the node, as `<id>@<address>#<crate version>/<schema version>`. The version
+ * of every peer is sent to the coordinator when it is first heard and whenever it changes, and the coordinator logs
+ * peers running another build. With `version_policy = "refuse"`, a peer announcing another version of the messages,
+ * `SCHEMA_VERSION`, is left out of the peer updates and its messages are dropped, until it announces the same version.
+ * Peers announcing no version, as builds from before the version was announced and the elevators of a relay, are
+ * only warned about.
+ *
+ * Peers can also be configured statically, with their ID and address. Configured peers are gossiped to
+ * before they have been discovered, and their address is used until discovery finds them somewhere else.
+ * A configured peer that is lost is logged as a warning, since it is expected to be there.
+ *
+ * External systems, such as acceptance scripts, place and clear hall calls with a `hallCall` message
+ * addressed to the elevator. It is handled like the matching console command. A watchdog or operator
+ * halts a misbehaving elevator that is still reachable with a `halt` message, and resumes it with `resume`. An
+ * operator changes the settings of a running elevator with a `configUpdate` message, sealed with the key of
+ * `[reload]`, which is opened and applied by the `ConfigReloader`.
+ *
+ * Every message travels in an envelope naming its origin and destination by ID. Peers are told apart,
+ * and replied to, by the origin instead of the source address, so messages can pass through a relay.
+ *
+ * Several banks of elevators can share the ports of one network, each with its own `group_id`. The group is
+ * broadcast after the version, as `<id>@<address>#<version>#<group>`, and carried in every envelope. Peers of
+ * other groups are left out of the peer updates, and their messages dropped, so the coordinator never hears of
+ * them and the banks never merge. The default group, empty, is broadcast and sent as before.
+ *
+ * No datagram is larger than the MTU of the path, configured as `mtu` or detected, so none is fragmented by IP. A
+ * message too large for one datagram, such as the data of a large fleet or a backup, is sent in fragments, and
+ * reassembled by the receiver. See `fragment`.
+ *
+ * Messages to a peer are numbered, and the receiver asks with a `nack` for the ones it finds missing, which the
+ * sender sends again from a small buffer. See `retransmit`.
+ *
+ * The datagrams sent are kept within `max_packet_rate` per second, giving changes to the orders precedence over
+ * the periodic refreshes, so a burst of button presses does not flood the shared network. See `shaper`.
+ *
+ * The clocks of the lab machines may differ by seconds, which the times of the hall calls and the cab calls in the
+ * data, compared in the merges, would otherwise inherit. Every digest carries the wall clock of its sender, from which
+ * the offset of the clock of each peer is estimated, allowing half the round trip to it for the transit. The times
+ * in the data of a peer are moved to the local clock as it is received, so every node holds the times on its own
+ * clock, and the times compared are on the same clock, whichever node stamped them. See `quality`.
+ *
+ * The loss and round-trip time of the network are measured, and with `[adaptation]` enabled, sent to the coordinator
+ * every `interval` milliseconds. While the network is lossy, the digests of new data are held for more changes to
+ * send with them, and the peers may go unheard for longer. See `quality` and `AdaptationPolicy`.
+ *
+ * With `encryption` on, every datagram and peer broadcast is sealed with `encryption_key`, so it cannot be read,
+ * spoofed or replayed by other machines on the network. Encrypted datagrams are flagged, so encrypted and plaintext
+ * elevators interoperate while a fleet is moved to encryption. See `encryption`.
+ *
+ * Each node pushes a backup of its persistent state, a checkpoint of its storage, to the `peers` peers of `[backup]`
+ * following it in the order of the IDs, so the same peers keep hosting it. A peer keeps the latest backup of
+ * up to `peers` nodes in memory, only from the node it belongs to and only while that node is a current peer. A node
+ * that starts without its local state asks all the peers it knows for its backup, and is answered by those hosting
+ * one. A backup of the local node is only taken while it is asked for, and only from a current peer.
+ *
+ * The backups are identified by the ID their sender gives, and so only trusted as far as that ID. With `encryption`
+ * required, only the elevators holding the key of the fleet can send or read a backup. Without it, any machine on
+ * the network can claim the ID of a peer, and have its backup hosted or restored in its place.
+ *
+ * The network is terminated like the other modules. Its threads stop, the receiving ones within `STOP_POLL_INTERVAL`,
+ * and the request is acknowledged once they have all ended. Until then, a module the network sends to or receives
+ * from that is gone, as one terminated first, is left to main to act on.
+ *
  * # Network
  * Struct for initializing network communications.
  *
  * # Fields
- * - `id`: Unique identifier for the network node, based on the local IP and port.
+ * - `id`:       Unique identifier for the network node, persistent, or the local IP and port without an ID file.
+ * - `counters`: Datagrams sent, received, dropped and failed to send, and the links to the peers, shared with coordinator.
  *
  * # Constructor arguments
  * - `config`:                  Network configuration settings.
+ * - `backup_config`:           The number of peers hosting the backups of the local node.
+ * - `adaptation_config`:       How the network adapts to the loss it measures.
  * - `net_data_send_rx`:        Receiver for elevator data to be sent.
  * - `net_data_recv_tx`:        Sender for forwarding received elevator data to coordinator.
+ * - `net_sync_recv_tx`:        Sender for forwarding elevator data found to differ by anti-entropy to coordinator.
  * - `net_peer_update_tx`:      Sender for forwarding received peer updates to coordinator.
+ * - `net_peer_version_tx`:     Sender for forwarding the versions announced by the peers to coordinator, None if unannounced.
  * - `net_peer_tx_enable_rx`:   Receiver to enable/disable peer ID broadcasting.
+ * - `net_consensus_send_rx`:   Receiver for consensus messages to be sent, addressed by peer ID.
+ * - `net_consensus_recv_tx`:   Sender for forwarding received consensus messages to coordinator, with the sender's ID.
+ * - `net_command_tx`:          Sender for forwarding hall calls and halt commands from external systems to coordinator.
+ * - `net_backup_send_rx`:      Receiver for backups of the local node to push, and requests for them.
+ * - `net_backup_recv_tx`:      Sender for forwarding backups of the local node returned by peers.
+ * - `net_config_update_tx`:    Sender for forwarding the sealed configuration updates from operators to the reloader.
+ * - `net_quality_tx`:          Sender for forwarding the measured loss and round-trip time to coordinator.
+ * - `net_terminate_rx`:        Receiver for the request to terminate, acknowledged once all the threads have ended.
  *
  */
 
@@ -26,126 +125,716 @@
 /***************************************/
 use crossbeam_channel as cbc;
 use network_rust::udpnet;
+use network_rust::udpnet::peers::PeerUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::net::UdpSocket;
-use std::thread::{Builder, sleep};
-use std::time::{Duration, Instant};
-use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{Builder, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::net;
-use log::{info, error};
+use log::{info, warn, error};
 
 /***************************************/
 /*           Local modules             */
 /***************************************/
-use crate::config::NetworkConfig;
-use crate::shared::ElevatorData;
+use crate::config::{AdaptationConfig, BackupConfig, NetworkConfig};
+use crate::network::encryption::Cipher;
+use crate::network::fragment::{datagram_limit, fragment, Reassembly};
+use crate::network::peers::{self, PeerSettings};
+use crate::network::quality::LinkMonitor;
+use crate::network::retransmit::{Inbox, Outbox};
+use crate::shared::{AdaptationPolicy, Clock, ConsensusMessage, ControlCommand, ElevatorData, NetworkQuality, NetworkStatistics, Terminate};
+use crate::shared::{exit, ExitReason, PeerVersion, VersionPolicy};
+use crate::storage::Checkpoint;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const DATAGRAM_SIZE: usize = 4096;
+const BACKUP_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+const BACKUP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+// How long a receiving thread waits for a datagram before it checks whether it is asked to stop
+pub(super) const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// Messages exchanged between peers
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum GossipMessage {
+    Digest(Digest),
+    Pull,
+    State(ElevatorData),
+    Consensus(ConsensusMessage),
+    SyncDigest(u64),
+    Sync { data: ElevatorData, reply: bool },
+    HallCall { floor: u8, call: u8, place: bool },
+    Halt,
+    Resume,
+    // Settings to change, sealed with the key of `[reload]`, in hexadecimal. See `ConfigReloader`.
+    ConfigUpdate(String),
+    Backup(Box<Checkpoint>),
+    BackupRequest,
+    // A part of a serialized envelope too large for one datagram
+    Fragment { message: u64, index: u16, count: u16, payload: String },
+    // The numbers of the messages from the peer found missing, to send again
+    Nack(Vec<u64>),
+}
+
+// Backups of the local node to push to the peers hosting them, and requests for them on a cold start
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackupCommand {
+    Push(Box<Checkpoint>),
+    Request,
+}
+
+// A message with the IDs of the peer it comes from and the peer it is for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub origin: String,
+    pub destination: String,
+    pub message: GossipMessage,
+    // The number of the message in the sequence of messages from the origin to the destination, if numbered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+    // The group of the fleet the message belongs to, empty for the default group
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub group: String,
+}
+
+// Summary of the data a node holds, compared to decide what to exchange
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub version: u64,
+    pub elevators: Vec<String>,
+    // The wall clock of the sender as it was sent, in milliseconds since the Unix epoch. 0 from older elevators.
+    #[serde(default)]
+    pub sent: u64,
+}
+
+impl Digest {
+    pub fn of(data: &ElevatorData) -> Digest {
+        let mut elevators = data.states.keys().cloned().collect::<Vec<String>>();
+        elevators.sort();
+        Digest { version: data.clock.total(), elevators, sent: 0 }
+    }
+}
+
+// Addresses of the peers, by ID. Addresses found by discovery take precedence over the configured ones.
+struct PeerAddresses {
+    discovered: Mutex<HashMap<String, String>>,
+    configured: HashMap<String, String>,
+}
+
+impl PeerAddresses {
+    fn address_of(&self, id: &str) -> Option<String> {
+        self.discovered.lock().unwrap().get(id).or(self.configured.get(id)).cloned()
+    }
+
+    fn ids(&self) -> Vec<String> {
+        self.discovered.lock().unwrap().keys().chain(self.configured.keys()).cloned().collect()
+    }
+}
+
+// Counters of the datagrams handled by the network threads, whether the node is quarantined, and the quality of the
+// links to the peers
+#[derive(Default)]
+pub struct NetworkCounters {
+    quarantined: AtomicBool,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    messages_dropped: AtomicU64,
+    send_failures: AtomicU64,
+    messages_fragmented: AtomicU64,
+    messages_retransmitted: AtomicU64,
+    messages_shaped: AtomicU64,
+    link_monitor: Mutex<LinkMonitor>,
+}
+
+impl NetworkCounters {
+    pub fn statistics(&self) -> NetworkStatistics {
+        NetworkStatistics {
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            messages_dropped: self.messages_dropped.load(Ordering::Relaxed),
+            send_failures: self.send_failures.load(Ordering::Relaxed),
+            messages_fragmented: self.messages_fragmented.load(Ordering::Relaxed),
+            messages_retransmitted: self.messages_retransmitted.load(Ordering::Relaxed),
+            messages_shaped: self.messages_shaped.load(Ordering::Relaxed),
+            quarantined: self.quarantined.load(Ordering::Relaxed),
+            links: self.link_monitor.lock().unwrap().links(),
+        }
+    }
+}
 
 /***************************************/
 /*             Public API              */
 /***************************************/
 pub struct Network {
     pub id: String,
+    pub counters: Arc<NetworkCounters>,
 }
 
 impl Network {
     pub fn new(
         net_config: &NetworkConfig,
+        backup_config: &BackupConfig,
+        adaptation_config: &AdaptationConfig,
+        clock: &dyn Clock,
         net_data_send_rx: cbc::Receiver<ElevatorData>,
         net_data_recv_tx: cbc::Sender<ElevatorData>,
+        net_sync_recv_tx: cbc::Sender<ElevatorData>,
         net_peer_update_tx: cbc::Sender<udpnet::peers::PeerUpdate>,
+        net_peer_version_tx: cbc::Sender<(String, Option<PeerVersion>)>,
         net_peer_tx_enable_rx: cbc::Receiver<bool>,
+        net_consensus_send_rx: cbc::Receiver<(String, ConsensusMessage)>,
+        net_consensus_recv_tx: cbc::Sender<(String, ConsensusMessage)>,
+        net_command_tx: cbc::Sender<ControlCommand>,
+        net_backup_send_rx: cbc::Receiver<BackupCommand>,
+        net_backup_recv_tx: cbc::Sender<Checkpoint>,
+        net_config_update_tx: cbc::Sender<String>,
+        net_quality_tx: cbc::Sender<NetworkQuality>,
+        net_terminate_rx: cbc::Receiver<Terminate>,
     ) -> std::io::Result<Network> {
 
-        let msg_port = net_config.msg_port;
-        let peer_port = net_config.peer_port;
-        let ack_timeout = net_config.ack_timeout;
-        let max_retries = net_config.max_retries;
+        // Socket for gossip on the message port, or the first free port after it. Replies go back to the port of the
+        // peer, which is part of its ID.
+        let socket = match bind_message_port(net_config.msg_port, net_config.port_range) {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!("{}", error);
+                exit(ExitReason::PortInUse);
+            }
+        };
+        let msg_port = socket.local_addr()?.port();
+        let cipher = match Cipher::new(net_config.encryption, &net_config.encryption_key) {
+            Ok(cipher) => cipher,
+            Err(error) => {
+                error!("{}", error);
+                exit(ExitReason::ConfigInvalid);
+            }
+        };
+        let counters = Arc::new(NetworkCounters::default());
+        let peer_settings = PeerSettings::new(net_config);
+        let gossip_interval = Duration::from_millis(net_config.gossip_interval);
+        let anti_entropy_interval = Duration::from_millis(net_config.anti_entropy_interval);
+        let backup_peers = backup_config.peers;
+        let adaptation_policy = AdaptationPolicy::new(adaptation_config);
+        let adaptation_interval = Duration::from_millis(adaptation_config.interval);
+        let outbox = Arc::new(Outbox::new(datagram_limit(net_config.mtu), net_config.max_packet_rate, cipher.clone(), net_config.group_id.clone()));
+        info!("Sending datagrams of up to {} bytes", outbox.max_datagram());
+
+        // One ID file per port, so elevators run on the same computer get IDs of their own
+        let persistent_id = match net_config.id_file.is_empty() {
+            true => None,
+            false => Some(load_or_create_id(&format!("{}_{}", net_config.id_file, msg_port))?),
+        };
 
         let local_ip_result = find_local_ip(
             net_config.id_gen_address.clone(),
             net_config.max_attempts_id_generation,
             Duration::from_millis(net_config.delay_between_attempts_id_generation),
+            clock,
         );
 
-        let id = match local_ip_result {
+        let address = match local_ip_result {
             Some(ip) => format!("{}:{}", ip, msg_port.clone()),
             None => {
-                error!("Failed to generate ID, elevator is offline, running single elevator mode");
-                return Ok(Network { id: "Offline Elevator".to_string() });
+                error!("Failed to find local IP, elevator is offline, running single elevator mode");
+                return Ok(Network { id: persistent_id.unwrap_or("Offline Elevator".to_string()), counters });
             }
         };
 
-        info!("ID: {}", id);
-        let id_tx = id.clone();
+        let id = persistent_id.unwrap_or(address.clone());
+        info!("ID: {}, address: {}", id, address);
+        let id_tx = peer_announcement(&id, &address, Some(&PeerVersion::local()), &net_config.group_id);
+        let peer_addresses = Arc::new(PeerAddresses {
+            discovered: Mutex::new(HashMap::new()),
+            configured: net_config
+                .peers
+                .iter()
+                .filter(|peer| peer.id != id)
+                .map(|peer| (peer.id.clone(), peer.address.clone()))
+                .collect(),
+        });
 
-        // Thread for broadcasting peer ID
+        // The threads stop once the sender is dropped, and the receiving ones check for it between datagrams
+        let (stop_tx, stop_rx) = cbc::bounded::<()>(0);
+        socket.set_read_timeout(Some(STOP_POLL_INTERVAL))?;
+        let mut threads = Vec::new();
+
+        // Thread for broadcasting peer ID, while enabled and not quarantined. It stops with the peer_update thread.
+        let (peer_tx_enable_tx, peer_tx_enable_rx) = cbc::unbounded::<bool>();
+        let peer_tx_settings = peer_settings.clone();
+        let peer_tx_cipher = cipher.clone();
+        let peer_rx_cipher = cipher.clone();
+        let adaptation_peer_settings = peer_settings.clone();
         let peer_tx_thread = Builder::new().name("peer_tx".into());
-        peer_tx_thread
+        threads.push(peer_tx_thread
             .spawn(move || {
-                if udpnet::peers::tx(peer_port, id_tx, net_peer_tx_enable_rx).is_err() {
-                    error!("Failed to broadcast peer ID. Exiting...");
-                    process::exit(1);
+                if let Err(error) = peers::tx(&peer_tx_settings, peer_tx_cipher, id_tx, peer_tx_enable_rx) {
+                    error!("Failed to broadcast peer ID: {}. Exiting...", error);
+                    exit(ExitReason::Failure);
                 }
             })
-            .unwrap();
+            .unwrap());
 
-        // Thread for receiving and forwarding peer updates on port 'peer_port'
+        // Thread for receiving peer updates on port 'peer_port'
+        let (peer_update_tx, peer_update_rx) = cbc::unbounded::<PeerUpdate>();
+        let peer_rx_stop_rx = stop_rx.clone();
         let peer_rx_thread = Builder::new().name("peer_rx".into());
-        peer_rx_thread
+        threads.push(peer_rx_thread
             .spawn(move || {
-                if udpnet::peers::rx(peer_port, net_peer_update_tx).is_err() {
-                    error!("Failed to receive peer updates. Exiting...");
-                    process::exit(1);
+                if let Err(error) = peers::rx(&peer_settings, peer_rx_cipher, peer_update_tx, peer_rx_stop_rx) {
+                    error!("Failed to receive peer updates: {}. Exiting...", error);
+                    exit(ExitReason::PortInUse);
                 }
             })
-            .unwrap();
+            .unwrap());
 
+        // Thread recording the addresses and versions of the peers, and forwarding the peer updates by ID. It
+        // quarantines the node while another node broadcasts the same ID.
+        let refused_peers: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+        let update_refused_peers = refused_peers.clone();
+        let version_policy = net_config.version_policy;
+        let update_peer_addresses = peer_addresses.clone();
+        let update_counters = counters.clone();
+        let update_id = id.clone();
+        let update_address = address.clone();
+        let update_group = net_config.group_id.clone();
+        let update_stop_rx = stop_rx.clone();
+        let peer_update_thread = Builder::new().name("peer_update".into());
+        threads.push(peer_update_thread
+            .spawn(move || {
+                let mut net_peer_tx_enable_rx = net_peer_tx_enable_rx;
+                let mut enabled = true;
+                let mut broadcasting = true;
+                let mut versions: HashMap<String, Option<PeerVersion>> = HashMap::new();
+                let mut other_groups: HashSet<String> = HashSet::new();
+                loop {
+                    cbc::select! {
+                        recv(net_peer_tx_enable_rx) -> enable => {
+                            match enable {
+                                Ok(enable) => enabled = enable,
+                                Err(_) => net_peer_tx_enable_rx = cbc::never(),
+                            }
+                        }
+                        recv(peer_update_rx) -> update => {
+                            let update = match update {
+                                Ok(update) => update,
+                                Err(_) if stopping(&update_stop_rx) => break,
+                                Err(error) => {
+                                    error!("Error receiving peer update: {}", error);
+                                    exit(ExitReason::Failure);
+                                }
+                            };
 
-        // Thread for sending out data
+                            // The banks of other groups sharing the network are none of this fleet
+                            for peer in &update.peers {
+                                let group = peer_group(peer);
+                                if group != update_group && other_groups.insert(group.to_string()) {
+                                    info!("Ignoring the elevators of group {:?}, which share the network", group);
+                                }
+                            }
+                            let update = in_group(update, &update_group);
+
+                            let was_quarantined = update_counters.quarantined.load(Ordering::Relaxed);
+                            let quarantined = match duplicate_of(&update.peers, &update_id, &update_address) {
+                                Some(other) => {
+                                    if !was_quarantined {
+                                        error!("DUPLICATE ID: {} is also broadcast from {}, as by a cloned ID file or config", update_id, other);
+                                    }
+                                    stands_down(&update_address, &other)
+                                }
+                                None => false,
+                            };
+                            update_counters.quarantined.store(quarantined, Ordering::Relaxed);
+
+                            let mut discovered = update_peer_addresses.discovered.lock().unwrap();
+                            if quarantined {
+                                // The coordinator runs alone until the other node is gone
+                                if !was_quarantined {
+                                    error!("Quarantined: {} keeps the ID, this elevator stops broadcasting and runs alone", update_id);
+                                    let mut lost = discovered.keys().filter(|peer| **peer != update_id).cloned().collect::<Vec<String>>();
+                                    lost.sort();
+                                    discovered.clear();
+                                    let _ = net_peer_update_tx.send(PeerUpdate { peers: vec![update_id.clone()], new: None, lost });
+                                }
+                            } else {
+                                if was_quarantined {
+                                    info!("The other node with ID {} is gone, rejoining the fleet", update_id);
+                                }
+                                let mut refused = update_refused_peers.lock().unwrap();
+                                for (peer, version) in changed_versions(&mut versions, &update.peers, &update_id) {
+                                    let incompatible = version.as_ref().is_some_and(|version| !version.compatible());
+                                    if version_policy == VersionPolicy::Refuse && incompatible && refused.insert(peer.clone()) {
+                                        error!("Refusing peer {} until it announces version {} of the messages", peer, PeerVersion::local().schema_version);
+                                    }
+                                    if !incompatible && refused.remove(&peer) {
+                                        info!("Peer {} announces version {} of the messages, taking it back", peer, PeerVersion::local().schema_version);
+                                    }
+                                    let _ = net_peer_version_tx.send((peer, version));
+                                }
+                                refused.retain(|peer| versions.contains_key(peer));
+                                let update = PeerUpdate {
+                                    peers: update.peers.into_iter().filter(|peer| !refused.contains(&split_peer(peer).0)).collect(),
+                                    new: update.new.filter(|peer| !refused.contains(&split_peer(peer).0)),
+                                    lost: update.lost,
+                                };
+                                drop(refused);
+
+                                let update = translate_peer_update(&mut discovered, update);
+                                for peer in &update.lost {
+                                    if let Some(address) = update_peer_addresses.configured.get(peer) {
+                                        warn!("Configured peer {} at {} is unreachable", peer, address);
+                                    }
+                                }
+                                let _ = net_peer_update_tx.send(update);
+                            }
+                        }
+                        recv(update_stop_rx) -> _ => break,
+                    }
+                    let broadcast = enabled && !update_counters.quarantined.load(Ordering::Relaxed);
+                    if broadcast != broadcasting {
+                        broadcasting = broadcast;
+                        let _ = peer_tx_enable_tx.send(broadcast);
+                    }
+                }
+            })
+            .unwrap());
+
+        let gossip_socket = socket.try_clone()?;
+        let consensus_socket = socket.try_clone()?;
+        let latest_data: Arc<Mutex<Option<ElevatorData>>> = Arc::new(Mutex::new(None));
+        let gossip_latest_data = latest_data.clone();
+        let gossip_id = id.clone();
+        let gossip_peer_addresses = peer_addresses.clone();
+        let consensus_peer_addresses = peer_addresses.clone();
+        let gossip_outbox = outbox.clone();
+        let consensus_outbox = outbox.clone();
+        let gossip_counters = counters.clone();
+        let consensus_counters = counters.clone();
+        let rx_counters = counters.clone();
+        let consensus_id = id.clone();
+        let rx_id = id.clone();
+        // Set while a backup of the local node is asked for, until the first one comes back
+        let backup_requested = Arc::new(AtomicBool::new(false));
+        let gossip_backup_requested = backup_requested.clone();
+        let gossip_stop_rx = stop_rx.clone();
+        let consensus_stop_rx = stop_rx.clone();
+
+        // Thread keeping the latest local data, and periodically sending its digest and hash to random peers. It also
+        // adapts to the quality of the network.
         let data_tx_thread = Builder::new().name("data_tx".into());
-        data_tx_thread
+        threads.push(data_tx_thread
             .spawn(move || {
-                let max_retries = max_retries;
-                let ack_timeout = ack_timeout;
+                let (mut net_data_send_rx, mut net_backup_send_rx) = (net_data_send_rx, net_backup_send_rx);
+                let gossip_ticker = cbc::tick(gossip_interval);
+                let anti_entropy_ticker = if anti_entropy_interval.is_zero() {
+                    cbc::never()
+                } else {
+                    cbc::tick(anti_entropy_interval)
+                };
+                let adaptation_ticker = match adaptation_policy.enabled() && !adaptation_interval.is_zero() {
+                    true => cbc::tick(adaptation_interval),
+                    false => cbc::never(),
+                };
+                // The digest of new data is held for the coalescing, and then sent with all the changes since
+                let mut coalescing = Duration::ZERO;
+                let mut coalesced = cbc::never();
+                let mut digest_held = false;
                 loop {
-                    match net_data_send_rx.recv() {
-                        Ok(data) => {
-                            let peer_addresses = data.states.keys().cloned().collect::<Vec<String>>();
-                            send_ack(peer_addresses, data, max_retries, ack_timeout);
+                    cbc::select! {
+                        recv(net_data_send_rx) -> data => {
+                            match data {
+                                Ok(data) => {
+                                    *gossip_latest_data.lock().unwrap() = Some(data);
+                                    if coalescing.is_zero() {
+                                        send_digest(&gossip_socket, &gossip_id, &gossip_latest_data, &gossip_peer_addresses, &gossip_outbox, &gossip_counters);
+                                    } else if !digest_held {
+                                        coalesced = cbc::after(coalescing);
+                                        digest_held = true;
+                                    }
+                                }
+                                // The coordinator is gone, as while the modules terminate, and is seen to by main
+                                Err(_) => net_data_send_rx = cbc::never(),
+                            }
+                        }
+                        recv(coalesced) -> _ => {
+                            coalesced = cbc::never();
+                            digest_held = false;
+                            send_digest(&gossip_socket, &gossip_id, &gossip_latest_data, &gossip_peer_addresses, &gossip_outbox, &gossip_counters);
+                        }
+                        recv(gossip_ticker) -> _ => {
+                            send_digest(&gossip_socket, &gossip_id, &gossip_latest_data, &gossip_peer_addresses, &gossip_outbox, &gossip_counters);
                         }
-                        Err(error) => {
-                            error!("Error receiving data to send: {}", error);
+                        recv(adaptation_ticker) -> _ => {
+                            let quality = gossip_counters.link_monitor.lock().unwrap().sample(Instant::now());
+                            coalescing = adaptation_policy.coalescing(&quality);
+                            adaptation_peer_settings.widen_timeout(adaptation_policy.peer_timeout(&quality, adaptation_peer_settings.timeout));
+                            let _ = net_quality_tx.send(quality);
                         }
+                        recv(anti_entropy_ticker) -> _ => {
+                            send_sync_digest(&gossip_socket, &gossip_id, &gossip_latest_data, &gossip_peer_addresses, &gossip_outbox, &gossip_counters);
+                        }
+                        recv(net_backup_send_rx) -> command => {
+                            let (message, peers) = match command {
+                                Ok(BackupCommand::Push(checkpoint)) => {
+                                    let mut known = gossip_peer_addresses.ids();
+                                    if let Some(data) = gossip_latest_data.lock().unwrap().as_ref() {
+                                        known.extend(data.states.keys().cloned());
+                                    }
+                                    (GossipMessage::Backup(checkpoint), backup_hosts(known, &gossip_id, backup_peers))
+                                }
+                                Ok(BackupCommand::Request) => {
+                                    gossip_backup_requested.store(true, Ordering::Relaxed);
+                                    (GossipMessage::BackupRequest, gossip_peer_addresses.ids())
+                                }
+                                Err(_) => {
+                                    net_backup_send_rx = cbc::never();
+                                    continue;
+                                }
+                            };
+                            for peer in peers {
+                                if let Some(peer_address) = gossip_peer_addresses.address_of(&peer) {
+                                    send_message(&gossip_socket, &gossip_id, &peer, message.clone(), &peer_address, &gossip_outbox, &gossip_counters);
+                                }
+                            }
+                        }
+                        recv(gossip_stop_rx) -> _ => break,
                     }
                 }
+            })
+            .unwrap());
 
+        // Thread sending consensus messages to the peers they are addressed to
+        let consensus_tx_thread = Builder::new().name("consensus_tx".into());
+        threads.push(consensus_tx_thread
+            .spawn(move || {
+                let mut net_consensus_send_rx = net_consensus_send_rx;
+                loop {
+                    cbc::select! {
+                        recv(net_consensus_send_rx) -> message => {
+                            match message {
+                                Ok((peer, message)) => {
+                                    // Messages to peers whose address is not known yet are dropped, and retried by the consensus
+                                    if let Some(peer_address) = consensus_peer_addresses.address_of(&peer) {
+                                        send_message(&consensus_socket, &consensus_id, &peer, GossipMessage::Consensus(message), &peer_address, &consensus_outbox, &consensus_counters);
+                                    }
+                                }
+                                Err(_) => net_consensus_send_rx = cbc::never(),
+                            }
+                        }
+                        recv(consensus_stop_rx) -> _ => break,
+                    }
+                }
             })
-            .unwrap();
+            .unwrap());
 
 
-        // Thread for receiving data packets
+        // Thread answering digests and pulls, and forwarding received data to the coordinator
         let data_rx_thread = Builder::new().name("data_rx".into());
-        data_rx_thread.spawn(move || {
-            let socket = match UdpSocket::bind(format!("0.0.0.0:{}", msg_port)) {
-                Ok(socket) => socket,
-                Err(error) => {
-                    error!("Failed to bind UDP socket on port {}: {}", msg_port, error);
-                    process::exit(1);
+        threads.push(data_rx_thread.spawn(move || {
+            let mut buffer = [0; DATAGRAM_SIZE];
+            let mut hosted_backups: HashMap<String, Checkpoint> = HashMap::new();
+            let mut reassembly = Reassembly::default();
+            let mut inbox = Inbox::default();
+            while !stopping(&stop_rx) {
+                let (number_of_bytes, src_address) = match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(error) if matches!(error.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+                    Err(error) => {
+                        error!("Failed to receive a message: {}", error);
+                        continue;
+                    }
+                };
+
+                // Packets meant for an elevator that used to be at this address are dropped, as are packets that do not
+                // open with the key, and all packets while quarantined
+                rx_counters.messages_received.fetch_add(1, Ordering::Relaxed);
+                let packet = cipher.open(&buffer[..number_of_bytes]).filter(|_| !rx_counters.quarantined.load(Ordering::Relaxed));
+                let Some(packet) = packet else {
+                    rx_counters.messages_dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                };
+                let envelope = match parse_packet(&packet) {
+                    // A message sent in fragments is handled once all of them have come
+                    Some(Envelope { origin, destination, message: GossipMessage::Fragment { message, index, count, payload }, group, .. })
+                        if destination == rx_id && group == outbox.group() =>
+                    {
+                        match reassembly.add(&origin, message, index, count, payload) {
+                            Some(envelope) => Some(envelope),
+                            None => continue,
+                        }
+                    }
+                    envelope => envelope,
+                };
+                let (origin, message, sequence) = match envelope {
+                    Some(envelope) if envelope.destination == rx_id && envelope.group == outbox.group() => (envelope.origin, envelope.message, envelope.sequence),
+                    _ => {
+                        rx_counters.messages_dropped.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                };
+                if refused_peers.lock().unwrap().contains(&origin) {
+                    rx_counters.messages_dropped.fetch_add(1, Ordering::Relaxed);
+                    continue;
                 }
-            };
+                let address = peer_addresses.address_of(&origin).unwrap_or(src_address.to_string());
 
-            loop {
-                match recv_ack(&socket) {
-                    Some(data) => {
-                        net_data_recv_tx.send(data).unwrap();
+                // The messages found missing before this one are asked for again
+                let missing = sequence.map(|sequence| inbox.receive(&origin, sequence)).unwrap_or_default();
+                if sequence.is_some() {
+                    rx_counters.link_monitor.lock().unwrap().receive(&origin, missing.len());
+                }
+                if !missing.is_empty() {
+                    send_message(&socket, &rx_id, &origin, GossipMessage::Nack(missing), &address, &outbox, &rx_counters);
+                }
+
+                let reply = match message {
+                    GossipMessage::Digest(digest) => {
+                        if digest.sent > 0 {
+                            rx_counters.link_monitor.lock().unwrap().clock_received(&origin, digest.sent, wall_clock());
+                        }
+                        reply_to_digest(latest_data.lock().unwrap().as_ref(), &digest)
+                    }
+                    GossipMessage::Pull => latest_data.lock().unwrap().clone().map(GossipMessage::State),
+                    GossipMessage::State(data) => {
+                        let mut link_monitor = rx_counters.link_monitor.lock().unwrap();
+                        link_monitor.data_received(&origin, Instant::now());
+                        let data = on_local_clock(data, link_monitor.clock_offset(&origin));
+                        drop(link_monitor);
+                        let _ = net_data_recv_tx.send(data);
+                        None
+                    }
+                    GossipMessage::SyncDigest(hash) => reply_to_sync_digest(latest_data.lock().unwrap().as_ref(), hash),
+                    GossipMessage::Sync { data, reply } => {
+                        // The peer that found the difference gets the local data in return
+                        let response = match reply {
+                            true => latest_data.lock().unwrap().clone().map(|data| GossipMessage::Sync { data, reply: false }),
+                            false => None,
+                        };
+                        let clock_offset = rx_counters.link_monitor.lock().unwrap().clock_offset(&origin);
+                        let _ = net_sync_recv_tx.send(on_local_clock(data, clock_offset));
+                        response
+                    }
+                    GossipMessage::Consensus(message) => {
+                        let _ = net_consensus_recv_tx.send((origin.clone(), message));
+                        None
+                    }
+                    GossipMessage::HallCall { floor, call, place } => {
+                        info!("Hall call {:?} {} by {}", (floor, call), if place { "placed" } else { "cleared" }, origin);
+                        let _ = net_command_tx.send(ControlCommand::HallCall(floor, call, place));
+                        None
+                    }
+                    GossipMessage::Halt => {
+                        info!("Elevator halted by {}", origin);
+                        let _ = net_command_tx.send(ControlCommand::Halt(true));
+                        None
+                    }
+                    GossipMessage::Resume => {
+                        info!("Elevator resumed by {}", origin);
+                        let _ = net_command_tx.send(ControlCommand::Halt(false));
+                        None
+                    }
+                    GossipMessage::ConfigUpdate(sealed) => {
+                        info!("Configuration update from {}", origin);
+                        let _ = net_config_update_tx.send(sealed);
+                        None
+                    }
+                    GossipMessage::Backup(checkpoint) => {
+                        // A backup of the local node is the answer to a request, and a backup of a peer is only
+                        // kept from that peer. Both only come from current peers.
+                        let peers = peer_addresses.ids();
+                        if !peers.contains(&origin) {
+                            warn!("Dropped a backup sent by {}, which is not a peer", origin);
+                        } else if checkpoint.snapshot.id == rx_id {
+                            if backup_requested.swap(false, Ordering::Relaxed) {
+                                let _ = net_backup_recv_tx.send(*checkpoint);
+                            } else {
+                                warn!("Dropped a backup of this elevator sent by {} unasked", origin);
+                            }
+                        } else if checkpoint.snapshot.id != origin {
+                            warn!("Dropped a backup of {} sent by {}", checkpoint.snapshot.id, origin);
+                        } else if !host_backup(&mut hosted_backups, *checkpoint, &peers, backup_peers) {
+                            warn!("Dropped the backup of {}, already hosting {} backups", origin, backup_peers);
+                        }
+                        None
+                    }
+                    GossipMessage::BackupRequest => {
+                        let backup = hosted_backups.get(&origin).cloned();
+                        if backup.is_some() {
+                            info!("Returning the backup of {}", origin);
+                        }
+                        backup.map(|checkpoint| GossipMessage::Backup(Box::new(checkpoint)))
+                    }
+                    GossipMessage::Nack(sequences) => {
+                        for (sequence, message) in outbox.retransmit(&origin, &sequences) {
+                            let envelope = Envelope {
+                                origin: rx_id.clone(),
+                                destination: origin.clone(),
+                                message,
+                                sequence: Some(sequence),
+                                group: outbox.group().to_string(),
+                            };
+                            if send_envelope(&socket, &envelope, &address, &outbox, &rx_counters) {
+                                rx_counters.messages_retransmitted.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        None
                     }
-                    None => {
-                        error!("Failed to receive data");
+                    // Fragments are not sent in fragments
+                    GossipMessage::Fragment { .. } => {
+                        rx_counters.messages_dropped.fetch_add(1, Ordering::Relaxed);
+                        None
                     }
+                };
+
+                // Replies go to where the origin is reached, which is the relay for elevators behind one
+                if matches!(reply, Some(GossipMessage::Pull)) {
+                    rx_counters.link_monitor.lock().unwrap().pull_sent(&origin, Instant::now());
+                }
+                if let Some(reply) = reply {
+                    send_message(&socket, &rx_id, &origin, reply, &address, &outbox, &rx_counters);
                 }
             }
-        }).unwrap();
+        }).unwrap());
 
-        Ok(Network { id })
+        terminate_threads("network", net_terminate_rx, stop_tx, threads);
+        Ok(Network { id, counters })
+    }
+}
+
+// Asks the peers for the backup of the local node, again every second, and waits up to `timeout` on the clock for it
+pub fn request_backup(
+    net_backup_send_tx: &cbc::Sender<BackupCommand>,
+    net_backup_recv_rx: &cbc::Receiver<Checkpoint>,
+    timeout: Duration,
+    clock: &dyn Clock,
+) -> Option<Checkpoint> {
+    let deadline = clock.now() + timeout;
+    let mut next_request = clock.now();
+    loop {
+        match net_backup_recv_rx.try_recv() {
+            Ok(checkpoint) => return Some(checkpoint),
+            Err(cbc::TryRecvError::Disconnected) => return None,
+            Err(cbc::TryRecvError::Empty) => {}
+        }
+        let now = clock.now();
+        if now >= deadline {
+            return None;
+        }
+        if now >= next_request {
+            // Offline, there is no one to ask
+            net_backup_send_tx.send(BackupCommand::Request).ok()?;
+            next_request = now + BACKUP_REQUEST_INTERVAL;
+        }
+        clock.sleep(BACKUP_POLL_INTERVAL.min(deadline - now));
     }
 }
 
@@ -153,102 +842,408 @@ impl Network {
 /***************************************/
 /*           Local functions           */
 /***************************************/
-fn send_ack(peer_addresses: Vec<String>, data: ElevatorData, max_retries: u32, ack_timeout: u64) {
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
-        Ok(socket) => socket,
-        Err(error) => {
-            error!("Failed to bind UDP socket: {}", error);
-            process::exit(1);
+// Binds the socket for the messages on `port`, or on the first free port of the `range` ports after it
+fn bind_message_port(port: u16, range: u16) -> Result<UdpSocket, String> {
+    let last = port.saturating_add(range);
+    for candidate in port..=last {
+        match UdpSocket::bind(format!("0.0.0.0:{}", candidate)) {
+            Ok(socket) => {
+                if candidate != port {
+                    warn!("The message port {} is in use, using port {} instead", port, candidate);
+                }
+                return Ok(socket);
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::AddrInUse => continue,
+            Err(error) => return Err(format!("Failed to bind the message port {}: {}", candidate, error)),
         }
+    }
+    match range {
+        0 => Err(format!(
+            "The message port {} is in use, by another program or group. Set another msg_port, or a port_range to try the ports after it",
+            port
+        )),
+        _ => Err(format!("The message ports {} to {} are all in use, by other programs or groups. Set another msg_port", port, last)),
+    }
+}
+
+// Sends the digest of the latest local data to a random peer
+fn send_digest(
+    socket: &UdpSocket,
+    id: &str,
+    latest_data: &Mutex<Option<ElevatorData>>,
+    peer_addresses: &PeerAddresses,
+    outbox: &Outbox,
+    counters: &NetworkCounters,
+) {
+    let (digest, peer) = match latest_data.lock().unwrap().as_ref() {
+        Some(data) => (Digest { sent: wall_clock(), ..Digest::of(data) }, random_peer(data, id, &peer_addresses.configured)),
+        None => return,
     };
 
-    for peer_address in peer_addresses {
-        let mut retries = 0;
-        let serialized_data_string = serde_json::to_string(&data).unwrap();
-        let serialized_data = serialized_data_string.as_bytes();
-
-        // Try until max_retries or ACK received
-        while retries < max_retries {
-            
-            if socket.send_to(&serialized_data, &peer_address).is_ok() {
-                let start = Instant::now();
-                let mut ack_received = false;
-                socket.set_read_timeout(Some(Duration::from_millis(ack_timeout))).unwrap();
-
-                while start.elapsed() < Duration::from_millis(ack_timeout) {
-                    let mut buffer = [0; 1024];
-
-                    match socket.recv_from(&mut buffer) {
-                        Ok((number_of_bytes, src_addr)) => {
-                            if src_addr.to_string() == peer_address {
-
-                                // Verify if the received message is an ACK
-                                let msg = String::from_utf8_lossy(&buffer[..number_of_bytes]);
-                                let ack = msg.trim();
-                                if ack == "ACK" {
-                                    ack_received = true;
-                                    break;
-                                }
-                            }
-                        },
-                        Err(_) => continue, // Timeout
-                    }
-                }
+    let peer = match peer {
+        Some(peer) => peer,
+        None => return,
+    };
+    if let Some(peer_address) = peer_addresses.address_of(&peer) {
+        send_message(socket, id, &peer, GossipMessage::Digest(digest), &peer_address, outbox, counters);
+    }
+}
 
-                if ack_received {
-                    break;
-                }
-                info!("No ACK received, retrying...");
-                retries += 1;
-            } 
-            
-            else {
-                info!("Failed to send data to {}", peer_address);
-                retries += 1;
-            }
-        
-            if retries == max_retries {
-                info!("Failed to send data to {} after {} retries", peer_address, max_retries);
-            }
+// Sends the hash of the latest local data to a random peer
+fn send_sync_digest(
+    socket: &UdpSocket,
+    id: &str,
+    latest_data: &Mutex<Option<ElevatorData>>,
+    peer_addresses: &PeerAddresses,
+    outbox: &Outbox,
+    counters: &NetworkCounters,
+) {
+    let (hash, peer) = match latest_data.lock().unwrap().as_ref() {
+        Some(data) => (data_hash(data), random_peer(data, id, &peer_addresses.configured)),
+        None => return,
+    };
+
+    let peer = match peer {
+        Some(peer) => peer,
+        None => return,
+    };
+    if let Some(peer_address) = peer_addresses.address_of(&peer) {
+        send_message(socket, id, &peer, GossipMessage::SyncDigest(hash), &peer_address, outbox, counters);
+    }
+}
+
+// The peers hosting the backups of the local node, the `count` following it in the order of the IDs
+fn backup_hosts(mut peers: Vec<String>, id: &str, count: usize) -> Vec<String> {
+    peers.retain(|peer| peer != id);
+    peers.sort();
+    peers.dedup();
+    let following = peers.iter().position(|peer| peer.as_str() > id).unwrap_or(0);
+    peers.rotate_left(following);
+    peers.truncate(count);
+    peers
+}
+
+// Keeps the backup of the node it belongs to, in place of its last one. Backups of nodes that are no longer peers
+// make room for it, and it is refused if `capacity` backups of peers are already hosted.
+fn host_backup(hosted: &mut HashMap<String, Checkpoint>, checkpoint: Checkpoint, peers: &[String], capacity: usize) -> bool {
+    let id = checkpoint.snapshot.id.clone();
+    if !hosted.contains_key(&id) {
+        hosted.retain(|hosted_id, _| peers.contains(hosted_id));
+        if hosted.len() >= capacity {
+            return false;
         }
     }
+    hosted.insert(id, checkpoint);
+    true
 }
 
-fn recv_ack(socket: &UdpSocket) -> Option<ElevatorData> {
-    let mut buffer = [0; 1024];
-    match socket.recv_from(&mut buffer) {
-        Ok((number_of_bytes, src_address)) => {
-            let received_data = &buffer[..number_of_bytes];
-            let message = match std::str::from_utf8(received_data) {
-                Ok(message) => message,
-                Err(error) => {
-                    error!("Invalid UTF-8 sequence: {}", error);
-                    return None;
-                }
-            };
+// Picks among the known elevators and the configured peers, so configured peers get the data before they are known
+fn random_peer(data: &ElevatorData, id: &str, configured: &HashMap<String, String>) -> Option<String> {
+    let mut peers = data.states.keys().chain(configured.keys()).filter(|peer| *peer != id).cloned().collect::<Vec<String>>();
+    peers.sort();
+    peers.dedup();
+    if peers.is_empty() {
+        return None;
+    }
+    Some(peers[random_index(peers.len())].clone())
+}
 
-            let deserialized_message: Result<ElevatorData, _> = serde_json::from_str(message);
-            match deserialized_message {
-                Ok(data) => {
-                    if let Err(error) = socket.send_to(b"ACK", src_address) {
-                        error!("Failed to send ACK to {}: {}", src_address, error);
-                    }
-                    Some(data)
-                },
-                Err(error) => {
-                    error!("Failed to deserialize message: {}", error);
-                    None
-                }
-            }
-        },
+// Numbers a message and keeps it for retransmission, and sends it
+fn send_message(
+    socket: &UdpSocket,
+    id: &str,
+    peer: &str,
+    message: GossipMessage,
+    peer_address: &str,
+    outbox: &Outbox,
+    counters: &NetworkCounters,
+) {
+    let sequence = outbox.record(peer, &message);
+    let envelope = Envelope { origin: id.to_string(), destination: peer.to_string(), message, sequence, group: outbox.group().to_string() };
+    send_envelope(socket, &envelope, peer_address, outbox, counters);
+}
+
+// Sends an envelope in one datagram, or in fragments if it does not fit in the datagrams of the outbox, unless
+// they are over the rate budget or the node is quarantined. Returns whether it was sent.
+fn send_envelope(socket: &UdpSocket, envelope: &Envelope, peer_address: &str, outbox: &Outbox, counters: &NetworkCounters) -> bool {
+    if counters.quarantined.load(Ordering::Relaxed) {
+        return false;
+    }
+    let datagrams = fragment(envelope, outbox.max_datagram());
+    if !outbox.admit(&envelope.message, datagrams.len()) {
+        counters.messages_shaped.fetch_add(1, Ordering::Relaxed);
+        return false;
+    }
+    if datagrams.len() > 1 {
+        counters.messages_fragmented.fetch_add(1, Ordering::Relaxed);
+    }
+    for datagram in datagrams {
+        if let Err(error) = socket.send_to(&outbox.seal(datagram.as_bytes()), peer_address) {
+            info!("Failed to send data to {}: {}", peer_address, error);
+            counters.send_failures.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+    }
+    counters.messages_sent.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+// Pushes the local data to a peer that is behind, and pulls the data of a peer that is ahead.
+// A peer is also behind if it misses any of the local elevators, so the coordinators get to merge.
+fn reply_to_digest(latest_data: Option<&ElevatorData>, digest: &Digest) -> Option<GossipMessage> {
+    let data = match latest_data {
+        Some(data) => data,
+        None => return Some(GossipMessage::Pull),
+    };
+
+    let local_digest = Digest::of(data);
+    let peer_is_behind = local_digest.version > digest.version
+        || local_digest.elevators.iter().any(|id| !digest.elevators.contains(id));
+    let peer_is_ahead = digest.version > local_digest.version
+        || digest.elevators.iter().any(|id| !local_digest.elevators.contains(id));
+
+    if peer_is_behind {
+        Some(GossipMessage::State(data.clone()))
+    } else if peer_is_ahead {
+        Some(GossipMessage::Pull)
+    } else {
+        None
+    }
+}
+
+// Sends the local data to a peer whose data differs, and asks for its data in return
+fn reply_to_sync_digest(latest_data: Option<&ElevatorData>, hash: u64) -> Option<GossipMessage> {
+    let data = latest_data?;
+    if data_hash(data) == hash {
+        return None;
+    }
+    Some(GossipMessage::Sync { data: data.clone(), reply: true })
+}
+
+// Hash of the data that is the same on every node holding the same data. The JSON objects have sorted keys,
+// so the order of the states does not matter, and the hasher has fixed keys. The times are left out, as every
+// node holds them on its own clock.
+fn data_hash(data: &ElevatorData) -> u64 {
+    let mut data = data.clone();
+    data.hall_request_times.clear();
+    data.hall_completion_times.clear();
+    for state in data.states.values_mut() {
+        state.cab_completion_times.clear();
+    }
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_value(&data).unwrap().to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+// The data of a peer with its times moved from the clock of the peer to the local one, in whole seconds, given the
+// offset of the clock of the peer in milliseconds
+fn on_local_clock(mut data: ElevatorData, clock_offset: Option<f64>) -> ElevatorData {
+    let seconds = (clock_offset.unwrap_or(0.0) / 1000.0).round() as i64;
+    if seconds != 0 {
+        data.shift_times(-seconds);
+    }
+    data
+}
+
+// The wall clock, in milliseconds since the Unix epoch
+fn wall_clock() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0)
+}
+
+// Picks a peer without pulling in a random number generator. Every RandomState is seeded at random.
+fn random_index(len: usize) -> usize {
+    RandomState::new().build_hasher().finish() as usize % len
+}
+
+// Decodes a received datagram. Anything that is not a valid message is logged and dropped.
+pub(super) fn parse_packet(received_data: &[u8]) -> Option<Envelope> {
+    let message = match std::str::from_utf8(received_data) {
+        Ok(message) => message,
+        Err(error) => {
+            error!("Invalid UTF-8 sequence: {}", error);
+            return None;
+        }
+    };
+
+    match serde_json::from_str::<Envelope>(message) {
+        Ok(data) => Some(data),
         Err(error) => {
-            error!("Failed to receive a message: {}", error);
+            error!("Failed to deserialize message: {}", error);
             None
-        },
+        }
+    }
+}
+
+// Reports the peers by ID, and records the address each of them is reached at. An elevator that has moved
+// is seen at both its old and new address until the old one times out, but stays one peer, at the new address.
+// Broadcasts without an address come from elevators identified by their address.
+fn translate_peer_update(address_book: &mut HashMap<String, String>, update: PeerUpdate) -> PeerUpdate {
+    let peers = update.peers.iter().map(|peer| split_peer(peer)).collect::<Vec<(String, String)>>();
+
+    let mut lost = address_book
+        .keys()
+        .filter(|id| !peers.iter().any(|(peer, _)| peer == *id))
+        .cloned()
+        .collect::<Vec<String>>();
+    lost.sort();
+    for id in &lost {
+        address_book.remove(id);
+    }
+
+    let new = update.new.map(|peer| split_peer(&peer));
+    let new_id = new.as_ref().filter(|(id, _)| !address_book.contains_key(id)).map(|(id, _)| id.clone());
+
+    // Keep the recorded address while the peer is still seen there, and take the address of a new broadcast
+    for (id, address) in &peers {
+        let still_seen = address_book
+            .get(id)
+            .is_some_and(|recorded| peers.iter().any(|(peer, seen)| peer == id && seen == recorded));
+        if !still_seen {
+            address_book.insert(id.clone(), address.clone());
+        }
+    }
+    if let Some((id, address)) = new {
+        address_book.insert(id, address);
+    }
+
+    let mut ids = peers.into_iter().map(|(id, _)| id).collect::<Vec<String>>();
+    ids.sort();
+    ids.dedup();
+    PeerUpdate { peers: ids, new: new_id, lost }
+}
+
+// The version of a peer broadcast, None if it announces none or one that does not parse
+fn peer_version(peer: &str) -> Option<PeerVersion> {
+    let version = peer.split('#').nth(1)?;
+    let (crate_version, schema_version) = version.rsplit_once('/')?;
+    Some(PeerVersion { crate_version: crate_version.to_string(), schema_version: schema_version.parse().ok()? })
+}
+
+// The peers other than `id` whose version is new or changed since `known`, which is updated. The peers no longer
+// broadcast are forgotten.
+fn changed_versions(known: &mut HashMap<String, Option<PeerVersion>>, peers: &[String], id: &str) -> Vec<(String, Option<PeerVersion>)> {
+    let versions = peers
+        .iter()
+        .map(|peer| (split_peer(peer).0, peer_version(peer)))
+        .filter(|(peer, _)| peer != id)
+        .collect::<HashMap<String, Option<PeerVersion>>>();
+    known.retain(|peer, _| versions.contains_key(peer));
+
+    let mut changed = versions
+        .into_iter()
+        .filter(|(peer, version)| known.get(peer) != Some(version))
+        .collect::<Vec<(String, Option<PeerVersion>)>>();
+    changed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (peer, version) in &changed {
+        known.insert(peer.clone(), version.clone());
+    }
+    changed
+}
+
+// The address of another node broadcasting the local ID, if any
+fn duplicate_of(peers: &[String], id: &str, address: &str) -> Option<String> {
+    peers
+        .iter()
+        .map(|peer| split_peer(peer))
+        .filter(|(peer, peer_address)| peer == id && peer_address != address)
+        .map(|(_, peer_address)| peer_address)
+        .min()
+}
+
+// Of two nodes broadcasting the same ID, the one at the higher address stands down, so both come to the same decision
+fn stands_down(address: &str, other_address: &str) -> bool {
+    address > other_address
+}
+
+// Whether the threads are asked to stop, by the sender of `stop_rx` being dropped. No message is sent on it.
+pub(super) fn stopping(stop_rx: &cbc::Receiver<()>) -> bool {
+    stop_rx.try_recv() == Err(cbc::TryRecvError::Disconnected)
+}
+
+// Stops the threads once the module is asked to terminate, and acknowledges once they have all ended
+pub(super) fn terminate_threads(module: &str, terminate_rx: cbc::Receiver<Terminate>, stop_tx: cbc::Sender<()>, threads: Vec<JoinHandle<()>>) {
+    let terminate_thread = Builder::new().name(module.into());
+    terminate_thread
+        .spawn(move || {
+            let done = terminate_rx.recv().ok();
+            drop(stop_tx);
+            for thread in threads {
+                let _ = thread.join();
+            }
+            if let Some(done) = done {
+                let _ = done.send(());
+            }
+        })
+        .unwrap();
+}
+
+// The broadcast of a peer, with the version and the group only when there are any
+pub(super) fn peer_announcement(id: &str, address: &str, version: Option<&PeerVersion>, group: &str) -> String {
+    let announcement = format!("{}@{}", id, address);
+    let version = version.map(|version| version.to_string()).unwrap_or_default();
+    match (version.is_empty(), group.is_empty()) {
+        (true, true) => announcement,
+        (false, true) => format!("{}#{}", announcement, version),
+        (_, false) => format!("{}#{}#{}", announcement, version, group),
     }
 }
 
-fn find_local_ip(address: String, max_attempts: u32, delay_between_attempts: Duration) -> Option<std::net::IpAddr> {
+// The group of a peer broadcast, empty for the default group
+pub(super) fn peer_group(peer: &str) -> &str {
+    peer.splitn(3, '#').nth(2).unwrap_or_default()
+}
+
+// The peers of a raw peer update that are of the group
+fn in_group(update: PeerUpdate, group: &str) -> PeerUpdate {
+    PeerUpdate {
+        peers: update.peers.into_iter().filter(|peer| peer_group(peer) == group).collect(),
+        new: update.new.filter(|peer| peer_group(peer) == group),
+        lost: update.lost.into_iter().filter(|peer| peer_group(peer) == group).collect(),
+    }
+}
+
+// The ID and address of a peer broadcast, without its version and group
+pub(super) fn split_peer(peer: &str) -> (String, String) {
+    let peer = peer.split_once('#').map_or(peer, |(peer, _)| peer);
+    match peer.split_once('@') {
+        Some((id, address)) => (id.to_string(), address.to_string()),
+        None => (peer.to_string(), peer.to_string()),
+    }
+}
+
+// Reads the ID of the elevator, generating it on first run
+fn load_or_create_id(path: &str) -> std::io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) if !contents.trim().is_empty() => return Ok(contents.trim().to_string()),
+        Ok(_) => (),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+        Err(error) => return Err(error),
+    }
+
+    let id = generate_id();
+    std::fs::write(path, format!("{}\n", id))?;
+    info!("Generated elevator ID {} in {}", id, path);
+    Ok(id)
+}
+
+// Random version 4 UUID, without pulling in a UUID crate
+fn generate_id() -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&RandomState::new().build_hasher().finish().to_be_bytes());
+    bytes[8..].copy_from_slice(&RandomState::new().build_hasher().finish().to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+pub(super) fn find_local_ip(
+    address: String,
+    max_attempts: u32,
+    delay_between_attempts: Duration,
+    clock: &dyn Clock,
+) -> Option<std::net::IpAddr> {
     let mut attempts = 0;
     while attempts < max_attempts {
         match net::TcpStream::connect(address.clone()) {
@@ -258,10 +1253,90 @@ fn find_local_ip(address: String, max_attempts: u32, delay_between_attempts: Dur
             },
             Err(error) => {
                 error!("Attempt {} to generate ID failed: {}", attempts + 1, error);
-                sleep(delay_between_attempts);
+                clock.sleep(delay_between_attempts);
             },
         }
         attempts += 1;
     }
     None
 }
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::{Checkpoint, Digest, Envelope, GossipMessage, NetworkCounters, Outbox};
+    use crate::shared::{ElevatorData, PeerVersion};
+    use network_rust::udpnet::peers::PeerUpdate;
+    use std::collections::HashMap;
+    use std::net::UdpSocket;
+
+    pub fn test_send_message(socket: &UdpSocket, id: &str, peer: &str, message: GossipMessage, peer_address: &str, outbox: &Outbox, counters: &NetworkCounters) {
+        super::send_message(socket, id, peer, message, peer_address, outbox, counters)
+    }
+
+    pub fn test_bind_message_port(port: u16, range: u16) -> Result<UdpSocket, String> {
+        super::bind_message_port(port, range)
+    }
+
+    pub fn test_parse_packet(received_data: &[u8]) -> Option<Envelope> {
+        super::parse_packet(received_data)
+    }
+
+    pub fn test_reply_to_digest(latest_data: Option<&ElevatorData>, digest: &Digest) -> Option<GossipMessage> {
+        super::reply_to_digest(latest_data, digest)
+    }
+
+    pub fn test_reply_to_sync_digest(latest_data: Option<&ElevatorData>, hash: u64) -> Option<GossipMessage> {
+        super::reply_to_sync_digest(latest_data, hash)
+    }
+
+    pub fn test_data_hash(data: &ElevatorData) -> u64 {
+        super::data_hash(data)
+    }
+
+    pub fn test_on_local_clock(data: ElevatorData, clock_offset: Option<f64>) -> ElevatorData {
+        super::on_local_clock(data, clock_offset)
+    }
+
+    pub fn test_in_group(update: PeerUpdate, group: &str) -> PeerUpdate {
+        super::in_group(update, group)
+    }
+
+    pub fn test_peer_announcement(id: &str, address: &str, version: Option<&PeerVersion>, group: &str) -> String {
+        super::peer_announcement(id, address, version, group)
+    }
+
+    pub fn test_translate_peer_update(address_book: &mut HashMap<String, String>, update: PeerUpdate) -> PeerUpdate {
+        super::translate_peer_update(address_book, update)
+    }
+
+    pub fn test_load_or_create_id(path: &str) -> std::io::Result<String> {
+        super::load_or_create_id(path)
+    }
+
+    pub fn test_backup_hosts(peers: Vec<String>, id: &str, count: usize) -> Vec<String> {
+        super::backup_hosts(peers, id, count)
+    }
+
+    pub fn test_host_backup(hosted: &mut HashMap<String, Checkpoint>, checkpoint: Checkpoint, peers: &[String], capacity: usize) -> bool {
+        super::host_backup(hosted, checkpoint, peers, capacity)
+    }
+
+    pub fn test_duplicate_of(peers: &[String], id: &str, address: &str) -> Option<String> {
+        super::duplicate_of(peers, id, address)
+    }
+
+    pub fn test_changed_versions(known: &mut HashMap<String, Option<PeerVersion>>, peers: &[String], id: &str) -> Vec<(String, Option<PeerVersion>)> {
+        super::changed_versions(known, peers, id)
+    }
+
+    pub fn test_stands_down(address: &str, other_address: &str) -> bool {
+        super::stands_down(address, other_address)
+    }
+
+    pub fn test_random_peer(data: &ElevatorData, id: &str, configured: &HashMap<String, String>) -> Option<String> {
+        super::random_peer(data, id, configured)
+    }
+}
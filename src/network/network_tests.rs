@@ -0,0 +1,960 @@
+/*
+ * Fuzz tests for network deserialization
+ *
+ * Property based tests feeding arbitrary bytes, arbitrary strings and mutated
+ * gossip messages into the packet parsing path of the receiving thread.
+ * A stray packet on the shared lab network must never crash an elevator,
+ * so every property only asserts that parsing returns instead of panicking,
+ * and that whatever is accepted stays bounded by the size of the datagram.
+ *
+ * Tests:
+ *  - test_network_parse_arbitrary_bytes
+ *  - test_network_parse_arbitrary_strings
+ *  - test_network_parse_valid_packet_roundtrip
+ *  - test_network_parse_mutated_packet
+ *  - test_network_parse_truncated_packet
+ *  - test_network_parse_halt_command
+ *  - test_network_reply_to_digest
+ *  - test_network_reply_to_sync_digest
+ *  - test_network_peer_changes_address
+ *  - test_network_persistent_id
+ *  - test_network_gossip_to_configured_peer
+ *  - test_network_counters
+ *  - test_network_backup_hosts
+ *  - test_network_host_backup
+ *  - test_network_request_backup
+ *  - test_network_fragments
+ *  - test_network_retransmission
+ *  - test_network_rate_limit
+ *  - test_network_peer_timeout
+ *  - test_network_port_collision
+ *  - test_network_mdns_announcement
+ *  - test_network_link_quality
+ *  - test_network_encryption
+ *  - test_network_replays
+ *  - test_network_duplicate_id
+ *  - test_network_peer_versions
+ *  - test_network_groups
+ *  - test_network_clock_offsets
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod network_tests {
+    use crate::network::network::testing::{
+        test_backup_hosts, test_bind_message_port, test_host_backup, test_changed_versions, test_data_hash, test_duplicate_of, test_in_group, test_load_or_create_id, test_on_local_clock, test_parse_packet, test_peer_announcement, test_random_peer, test_reply_to_digest,
+        test_reply_to_sync_digest, test_send_message, test_stands_down, test_translate_peer_update,
+    };
+    use crate::network::encryption::Cipher;
+    use crate::network::fragment::{datagram_limit, fragment, Reassembly};
+    use crate::network::mdns::{announced_peers, announcement};
+    use crate::network::peers::PeerTable;
+    use crate::network::quality::LinkMonitor;
+    use crate::network::retransmit::{Inbox, Outbox};
+    use crate::network::shaper::{Priority, Shaper};
+    use crate::network::{request_backup, BackupCommand, Digest, Envelope, GossipMessage, NetworkCounters};
+    use crate::shared::{Behaviour, ConsensusMessage, Encryption, Direction, DoorState, ElevatorData, ElevatorState, NetworkStatistics, OperatingMode, PeerVersion, SimulatedClock, Snapshot, TrafficMode, Uptime, VectorClock, SCHEMA_VERSION};
+    use crate::elevator::statistics::Statistics;
+    use crate::storage::checkpoint::{Checkpoint, CHECKPOINT_VERSION};
+    use network_rust::udpnet::peers::PeerUpdate;
+    use proptest::prelude::*;
+    use std::collections::{BTreeMap, HashMap};
+    use std::net::UdpSocket;
+    use std::time::{Duration, Instant};
+
+    // Size of the receive buffer of the receiving thread
+    const DATAGRAM_SIZE: usize = 4096;
+
+    fn envelope(message: GossipMessage) -> Envelope {
+        Envelope { origin: "a".to_string(), destination: "b".to_string(), message, sequence: None, group: String::new() }
+    }
+
+    fn behaviour_strategy() -> impl Strategy<Value = Behaviour> {
+        prop_oneof![
+            Just(Behaviour::Idle),
+            Just(Behaviour::Moving),
+            Just(Behaviour::DoorOpen),
+            Just(Behaviour::Error),
+        ]
+    }
+
+    fn direction_strategy() -> impl Strategy<Value = Direction> {
+        prop_oneof![Just(Direction::Up), Just(Direction::Down), Just(Direction::Stop)]
+    }
+
+    fn operating_mode_strategy() -> impl Strategy<Value = OperatingMode> {
+        prop_oneof![
+            Just(OperatingMode::Normal),
+            any::<u8>().prop_map(OperatingMode::FireRecall),
+            Just(OperatingMode::FirePhase2),
+            Just(OperatingMode::Independent),
+        ]
+    }
+
+    fn traffic_mode_strategy() -> impl Strategy<Value = Option<TrafficMode>> {
+        prop_oneof![
+            Just(None),
+            Just(Some(TrafficMode::Normal)),
+            Just(Some(TrafficMode::UpPeak)),
+            Just(Some(TrafficMode::DownPeak)),
+        ]
+    }
+
+    fn door_strategy() -> impl Strategy<Value = DoorState> {
+        prop_oneof![Just(DoorState::Closed), Just(DoorState::Open), Just(DoorState::Blocked)]
+    }
+
+    fn state_strategy(n_floors: u8) -> impl Strategy<Value = ElevatorState> {
+        (
+            behaviour_strategy(),
+            0..n_floors,
+            direction_strategy(),
+            proptest::collection::vec(any::<bool>(), n_floors as usize),
+            operating_mode_strategy(),
+            proptest::collection::vec(0..n_floors, 0..2 * n_floors as usize),
+            any::<bool>(),
+            door_strategy(),
+            any::<bool>(),
+            0..4usize,
+            proptest::collection::vec(any::<u64>(), 0..n_floors as usize),
+            proptest::collection::vec(any::<u64>(), 0..=n_floors as usize),
+        )
+            .prop_map(|(behaviour, floor, direction, cab_requests, mode, stop_plan, obstruction_alarm, door, halted, capacity, arrival_times, cab_completion_times)| ElevatorState {
+                behaviour,
+                floor,
+                direction,
+                cab_requests,
+                mode,
+                stop_plan,
+                obstruction_alarm,
+                door,
+                halted,
+                capacity,
+                arrival_times,
+                cab_completion_times,
+                uptime: Uptime::default(),
+                dry_run: false,
+            })
+    }
+
+    prop_compose! {
+        fn elevator_data_strategy()(n_floors in 1u8..8)(
+            version in any::<u64>(),
+            hall_requests in proptest::collection::vec(proptest::collection::vec(any::<bool>(), 2), n_floors as usize),
+            states in proptest::collection::hash_map("[0-9.:]{1,21}", state_strategy(n_floors), 0..4),
+            traffic_mode in traffic_mode_strategy(),
+            fire_recall in any::<bool>(),
+            independent_service in proptest::collection::btree_set("[0-9.:]{1,21}", 0..4),
+            hall_cancellations in proptest::collection::vec(proptest::collection::vec(any::<u64>(), 2), n_floors as usize),
+            hall_acknowledgements in proptest::collection::vec(
+                proptest::collection::vec(proptest::collection::btree_set("[0-9.:]{1,21}", 0..3), 2),
+                n_floors as usize,
+            ),
+            hall_request_times in proptest::collection::vec(proptest::collection::vec(any::<u64>(), 2), n_floors as usize),
+            hall_completion_times in proptest::collection::vec(proptest::collection::vec(any::<u64>(), 2), n_floors as usize),
+        ) -> ElevatorData {
+            ElevatorData {
+                version,
+                clock: VectorClock::default(),
+                hall_requests,
+                states: states.into_iter().collect::<HashMap<String, ElevatorState>>(),
+                traffic_mode,
+                zoning_profile: None,
+                fire_recall,
+                independent_service,
+                hall_cancellations,
+                hall_orders: Vec::new(),
+                hall_acknowledgements,
+                hall_request_times,
+                hall_completion_times,
+                hall_assignment: None,
+                hall_owners: BTreeMap::new(),
+            }
+        }
+    }
+
+    // Whatever is accepted can never hold more elements than there were bytes on the wire
+    fn assert_bounded(message: &GossipMessage, n_bytes: usize) {
+        match message {
+            GossipMessage::State(data) | GossipMessage::Sync { data, .. } => {
+                assert!(data.hall_requests.len() <= n_bytes);
+                assert!(data.hall_requests.iter().all(|floor| floor.len() <= n_bytes));
+                assert!(data.states.len() <= n_bytes);
+                assert!(data.states.values().all(|state| state.cab_requests.len() <= n_bytes));
+            }
+            GossipMessage::Digest(digest) => assert!(digest.elevators.len() <= n_bytes),
+            GossipMessage::Consensus(ConsensusMessage::AppendEntries { entries, .. }) => assert!(entries.len() <= n_bytes),
+            GossipMessage::Pull | GossipMessage::Consensus(_) | GossipMessage::SyncDigest(_) | GossipMessage::HallCall { .. } => (),
+            GossipMessage::Backup(checkpoint) => {
+                assert!(checkpoint.cab_orders.len() <= n_bytes);
+                assert!(checkpoint.snapshot.elevator_data.states.len() <= n_bytes);
+            }
+            GossipMessage::Halt | GossipMessage::Resume | GossipMessage::BackupRequest => (),
+            GossipMessage::ConfigUpdate(sealed) => assert!(sealed.len() <= n_bytes),
+            GossipMessage::Fragment { payload, .. } => assert!(payload.len() <= n_bytes),
+            GossipMessage::Nack(sequences) => assert!(sequences.len() <= n_bytes),
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(512))]
+
+        #[test]
+        fn test_network_parse_arbitrary_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..DATAGRAM_SIZE)) {
+            if let Some(envelope) = test_parse_packet(&bytes) {
+                assert_bounded(&envelope.message, bytes.len());
+            }
+        }
+
+        #[test]
+        fn test_network_parse_arbitrary_strings(message in "\\PC{0,256}") {
+            if let Some(parsed) = test_parse_packet(message.as_bytes()) {
+                assert_bounded(&parsed.message, message.len());
+            }
+        }
+
+        #[test]
+        fn test_network_parse_valid_packet_roundtrip(data in elevator_data_strategy()) {
+            let message = envelope(GossipMessage::State(data));
+            let serialized = serde_json::to_string(&message).unwrap();
+            prop_assert_eq!(test_parse_packet(serialized.as_bytes()), Some(message));
+        }
+
+        #[test]
+        fn test_network_parse_mutated_packet(
+            data in elevator_data_strategy(),
+            mutations in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>(), 0u8..3), 1..16),
+        ) {
+            let mut bytes = serde_json::to_vec(&envelope(GossipMessage::State(data))).unwrap();
+
+            // Flip, insert or delete bytes at random positions
+            for (index, byte, kind) in mutations {
+                if bytes.is_empty() {
+                    break;
+                }
+                let position = index.index(bytes.len());
+                match kind {
+                    0 => bytes[position] = byte,
+                    1 => bytes.insert(position, byte),
+                    _ => { bytes.remove(position); }
+                }
+            }
+            bytes.truncate(DATAGRAM_SIZE);
+
+            if let Some(parsed) = test_parse_packet(&bytes) {
+                assert_bounded(&parsed.message, bytes.len());
+            }
+        }
+
+        #[test]
+        fn test_network_parse_truncated_packet(data in elevator_data_strategy(), cut in any::<prop::sample::Index>()) {
+            let bytes = serde_json::to_vec(&envelope(GossipMessage::State(data))).unwrap();
+            let truncated = &bytes[..cut.index(bytes.len())];
+
+            // A truncated JSON object can never be complete
+            prop_assert_eq!(test_parse_packet(truncated), None);
+        }
+    }
+
+    #[test]
+    fn test_network_parse_halt_command() {
+        // Arrange
+        let halt = br#"{"origin": "watchdog", "destination": "elevator", "message": "halt"}"#;
+        let resume = br#"{"origin": "watchdog", "destination": "elevator", "message": "resume"}"#;
+
+        // Act
+        let halt = test_parse_packet(halt).map(|envelope| envelope.message);
+        let resume = test_parse_packet(resume).map(|envelope| envelope.message);
+
+        // Assert
+        assert_eq!(halt, Some(GossipMessage::Halt));
+        assert_eq!(resume, Some(GossipMessage::Resume));
+    }
+
+    #[test]
+    fn test_network_reply_to_digest() {
+        // Arrange
+        let mut data = ElevatorData::new(4);
+        for _ in 0..5 {
+            data.clock.tick("a");
+        }
+        data.states.insert("a".to_string(), ElevatorState::new(4));
+        data.states.insert("b".to_string(), ElevatorState::new(4));
+
+        let digest = |version: u64, elevators: &[&str]| Digest {
+            version,
+            elevators: elevators.iter().map(|id| id.to_string()).collect(),
+            sent: 0,
+        };
+
+        // Act / Assert
+        // A peer that is behind, or misses an elevator, gets the local data
+        assert_eq!(test_reply_to_digest(Some(&data), &digest(4, &["a", "b"])), Some(GossipMessage::State(data.clone())));
+        assert_eq!(test_reply_to_digest(Some(&data), &digest(5, &["a"])), Some(GossipMessage::State(data.clone())));
+
+        // The data of a peer that is ahead, or knows of another elevator, is pulled
+        assert_eq!(test_reply_to_digest(Some(&data), &digest(6, &["a", "b"])), Some(GossipMessage::Pull));
+        assert_eq!(test_reply_to_digest(Some(&data), &digest(5, &["a", "b", "c"])), Some(GossipMessage::Pull));
+        assert_eq!(test_reply_to_digest(None, &digest(0, &[])), Some(GossipMessage::Pull));
+
+        // Peers in agreement exchange nothing
+        assert_eq!(test_reply_to_digest(Some(&data), &Digest::of(&data)), None);
+    }
+
+    #[test]
+    fn test_network_reply_to_sync_digest() {
+        // Arrange
+        let mut data = ElevatorData::new(4);
+        data.version = 5;
+        data.states.insert("a".to_string(), ElevatorState::new(4));
+        data.states.insert("b".to_string(), ElevatorState::new(4));
+
+        // The same data, with the states inserted in the other order
+        let mut same_data = ElevatorData::new(4);
+        same_data.version = 5;
+        same_data.states.insert("b".to_string(), ElevatorState::new(4));
+        same_data.states.insert("a".to_string(), ElevatorState::new(4));
+
+        // Diverged at the same version
+        let mut diverged_data = data.clone();
+        diverged_data.hall_requests[2][0] = true;
+
+        // Act / Assert
+        // Nodes holding the same data agree on the hash, and exchange nothing
+        assert_eq!(test_data_hash(&data), test_data_hash(&same_data));
+        assert_eq!(test_reply_to_sync_digest(Some(&data), test_data_hash(&same_data)), None);
+
+        // Data that has diverged is sent, asking for the data of the peer in return
+        assert_eq!(
+            test_reply_to_sync_digest(Some(&data), test_data_hash(&diverged_data)),
+            Some(GossipMessage::Sync { data: data.clone(), reply: true })
+        );
+        assert_eq!(test_reply_to_sync_digest(None, test_data_hash(&data)), None);
+    }
+
+    #[test]
+    fn test_network_peer_changes_address() {
+        // Arrange
+        let mut address_book = HashMap::new();
+        let update = |peers: &[&str], new: Option<&str>, lost: &[&str]| PeerUpdate {
+            peers: peers.iter().map(|peer| peer.to_string()).collect(),
+            new: new.map(|peer| peer.to_string()),
+            lost: lost.iter().map(|peer| peer.to_string()).collect(),
+        };
+
+        // Act
+        let joined = test_translate_peer_update(&mut address_book, update(&["a@10.0.0.1:1"], Some("a@10.0.0.1:1"), &[]));
+        let moved = test_translate_peer_update(
+            &mut address_book,
+            update(&["a@10.0.0.1:1", "a@10.0.0.2:1"], Some("a@10.0.0.2:1"), &[]),
+        );
+        let moved_address = address_book.get("a").cloned();
+        let old_address_lost = test_translate_peer_update(&mut address_book, update(&["a@10.0.0.2:1"], None, &["a@10.0.0.1:1"]));
+        let left = test_translate_peer_update(&mut address_book, update(&[], None, &["a@10.0.0.2:1"]));
+
+        // Assert
+        // The elevator is one peer at the address it was last found at, and is only lost once both addresses are
+        assert_eq!((joined.peers, joined.new, joined.lost), (vec!["a".to_string()], Some("a".to_string()), vec![]));
+        assert_eq!((moved.peers, moved.new, moved.lost), (vec!["a".to_string()], None, vec![]));
+        assert_eq!(moved_address, Some("10.0.0.2:1".to_string()));
+        assert_eq!((old_address_lost.peers, old_address_lost.new, old_address_lost.lost), (vec!["a".to_string()], None, vec![]));
+        assert_eq!((left.peers, left.new, left.lost), (vec![], None, vec!["a".to_string()]));
+        assert!(address_book.is_empty());
+    }
+
+    #[test]
+    fn test_network_persistent_id() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("elevator_id_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        // Act
+        let generated = test_load_or_create_id(path).unwrap();
+        let loaded = test_load_or_create_id(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Assert
+        // A version 4 UUID is generated on first run, and read back on the next
+        let groups = generated.split('-').map(|group| group.len()).collect::<Vec<usize>>();
+        assert_eq!(groups, vec![8, 4, 4, 4, 12]);
+        assert!(generated.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+        assert_eq!(generated.chars().nth(14), Some('4'));
+        assert_eq!(loaded, generated);
+    }
+
+    #[test]
+    fn test_network_gossip_to_configured_peer() {
+        // Arrange
+        let mut data = ElevatorData::new(4);
+        data.states.insert("a".to_string(), ElevatorState::new(4));
+        let configured = HashMap::from([
+            ("a".to_string(), "10.0.0.1:1".to_string()),
+            ("b".to_string(), "10.0.0.2:1".to_string()),
+        ]);
+
+        // Act
+        let without_configuration = test_random_peer(&data, "a", &HashMap::new());
+        let with_configuration = test_random_peer(&data, "a", &configured);
+
+        // Assert
+        // Alone the elevator has nobody to gossip to, unless a peer it has not met yet is configured
+        assert_eq!(without_configuration, None);
+        assert_eq!(with_configuration, Some("b".to_string()));
+    }
+
+    #[test]
+    fn test_network_counters() {
+        // Arrange
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer_address = peer.local_addr().unwrap().to_string();
+        let outbox = Outbox::new(1400, 0, Cipher::default(), String::new());
+        let counters = NetworkCounters::default();
+
+        // Act
+        test_send_message(&socket, "a", "b", GossipMessage::Pull, &peer_address, &outbox, &counters);
+        test_send_message(&socket, "a", "b", GossipMessage::Pull, "not an address", &outbox, &counters);
+
+        // Assert
+        // Sent datagrams arrive, and sends that fail are counted apart
+        let mut buffer = [0; DATAGRAM_SIZE];
+        let (number_of_bytes, _) = peer.recv_from(&mut buffer).unwrap();
+        assert_eq!(test_parse_packet(&buffer[..number_of_bytes]).map(|envelope| envelope.message), Some(GossipMessage::Pull));
+        assert_eq!(counters.statistics(), NetworkStatistics { messages_sent: 1, send_failures: 1, ..NetworkStatistics::default() });
+    }
+
+    #[test]
+    fn test_network_backup_hosts() {
+        // Arrange
+        let peers = ["d", "b", "a", "c", "b"].map(str::to_string).to_vec();
+        let mut elevator_data = ElevatorData::new(4);
+        for id in ["a", "b", "c"] {
+            elevator_data.states.insert(id.to_string(), ElevatorState::new(4));
+        }
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            created_at: "2024-03-01T12:00:00+01:00".to_string(),
+            n_floors: 4,
+            fsm: Some(ElevatorState::new(4)),
+            cab_orders: vec![false; 4],
+            statistics: Statistics::default(),
+            snapshot: Snapshot {
+                id: "a".to_string(),
+                peers: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                elevator_data,
+                confirmed_hall_requests: vec![vec![false; 2]; 4],
+                network: NetworkStatistics::default(),
+                wal_sequence: 0,
+                reassignments: 0,
+                uptime: Uptime::default(),
+            },
+        };
+        let envelope = Envelope {
+            origin: "a".to_string(),
+            destination: "b".to_string(),
+            message: GossipMessage::Backup(Box::new(checkpoint)),
+            sequence: None,
+            group: String::new(),
+        };
+
+        // Act
+        let hosts = [test_backup_hosts(peers.clone(), "b", 2), test_backup_hosts(peers.clone(), "d", 2), test_backup_hosts(peers, "a", 5)];
+        let packet = serde_json::to_string(&envelope).unwrap();
+
+        // Assert
+        // The peers following in the order of the IDs host the backups, wrapping around after the last
+        assert_eq!(hosts, [vec!["c", "d"], vec!["a", "b"], vec!["b", "c", "d"]]);
+        // The backup of a fleet of three fits in one datagram
+        assert!(packet.len() <= DATAGRAM_SIZE, "Backup of {} bytes", packet.len());
+        assert_eq!(test_parse_packet(packet.as_bytes()), Some(envelope));
+    }
+
+    #[test]
+    fn test_network_host_backup() {
+        // Arrange
+        let backup = |id: &str, wal_sequence: u64| Checkpoint {
+            version: CHECKPOINT_VERSION,
+            created_at: "2024-03-01T12:00:00+01:00".to_string(),
+            n_floors: 4,
+            fsm: None,
+            cab_orders: vec![false; 4],
+            statistics: Statistics::default(),
+            snapshot: Snapshot {
+                id: id.to_string(),
+                peers: Vec::new(),
+                elevator_data: ElevatorData::new(4),
+                confirmed_hall_requests: vec![vec![false; 2]; 4],
+                network: NetworkStatistics::default(),
+                wal_sequence,
+                reassignments: 0,
+                uptime: Uptime::default(),
+            },
+        };
+        let mut hosted = HashMap::new();
+        let peers = ["a", "b", "c"].map(str::to_string).to_vec();
+        let peers_after_b_left = ["a", "c"].map(str::to_string).to_vec();
+
+        // Act
+        let hosted_a = test_host_backup(&mut hosted, backup("a", 1), &peers, 2);
+        let hosted_b = test_host_backup(&mut hosted, backup("b", 1), &peers, 2);
+        let hosted_c = test_host_backup(&mut hosted, backup("c", 1), &peers, 2);
+        let updated_a = test_host_backup(&mut hosted, backup("a", 2), &peers, 2);
+        let hosted_c_after_b_left = test_host_backup(&mut hosted, backup("c", 2), &peers_after_b_left, 2);
+
+        // Assert
+        // No more backups than the capacity are hosted, but a hosted one is always replaced by a newer one
+        assert!(hosted_a && hosted_b && !hosted_c && updated_a);
+        // The backup of a node that left makes room for one of a peer
+        assert!(hosted_c_after_b_left);
+        assert_eq!(hosted.len(), 2);
+        assert_eq!(hosted["a"].snapshot.wal_sequence, 2);
+        assert_eq!(hosted["c"].snapshot.wal_sequence, 2);
+    }
+
+    #[test]
+    fn test_network_request_backup() {
+        // Arrange
+        let clock = std::sync::Arc::new(SimulatedClock::new(0.0));
+        let (net_backup_send_tx, net_backup_send_rx) = crossbeam_channel::unbounded::<BackupCommand>();
+        let (_net_backup_recv_tx, net_backup_recv_rx) = crossbeam_channel::unbounded::<Checkpoint>();
+        let stepper_clock = clock.clone();
+        let stepper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            stepper_clock.advance(Duration::from_secs(30));
+        });
+        let start = Instant::now();
+
+        // Act
+        let backup = request_backup(&net_backup_send_tx, &net_backup_recv_rx, Duration::from_secs(30), clock.as_ref());
+        stepper.join().unwrap();
+
+        // Assert
+        // The wait ends once the clock passes the timeout, long before it passes in real time
+        assert!(backup.is_none());
+        assert!(start.elapsed() < Duration::from_secs(10), "Waited for {:?}", start.elapsed());
+        assert!(matches!(net_backup_send_rx.try_recv(), Ok(BackupCommand::Request)));
+    }
+
+    #[test]
+    fn test_network_fragments() {
+        // Arrange
+        let mut data = ElevatorData::new(8);
+        for id in 0..8 {
+            data.states.insert(format!("elevator-{}", id), ElevatorState::new(8));
+        }
+        let message = envelope(GossipMessage::State(data));
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let outbox = Outbox::new(300, 0, Cipher::default(), String::new());
+        let counters = NetworkCounters::default();
+
+        // Act
+        let datagrams = fragment(&message, 300);
+        let fragments = datagrams.iter().map(|datagram| test_parse_packet(datagram.as_bytes()).unwrap()).collect::<Vec<Envelope>>();
+        let reassemble = |origin: &str, fragments: &[Envelope]| {
+            let mut reassembly = Reassembly::default();
+            let mut reassembled = None;
+            for fragment in fragments.iter().rev() {
+                if let GossipMessage::Fragment { message, index, count, payload } = fragment.message.clone() {
+                    reassembled = reassembly.add(origin, message, index, count, payload);
+                }
+            }
+            reassembled
+        };
+        test_send_message(&socket, "a", "b", message.message.clone(), &peer.local_addr().unwrap().to_string(), &outbox, &counters);
+
+        // Assert
+        // The message is split into datagrams within the limit, and reassembled in any order once all have come
+        assert!(datagrams.len() > 1 && datagrams.iter().all(|datagram| datagram.len() <= 300));
+        assert_eq!(reassemble("a", &fragments), Some(message.clone()));
+        assert_eq!(reassemble("a", &fragments[1..]), None);
+        // Fragments of a message from another origin are not taken as its own
+        assert_eq!(reassemble("c", &fragments), None);
+        assert_eq!(fragment(&envelope(GossipMessage::Pull), 300).len(), 1);
+        // The IP and UDP headers and the relay are left room for, down to a smallest datagram
+        assert_eq!((datagram_limit(1500), datagram_limit(100)), (1456, 256));
+        assert_eq!(counters.statistics().messages_fragmented, 1);
+    }
+
+    #[test]
+    fn test_network_retransmission() {
+        // Arrange
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let outbox = Outbox::new(1400, 0, Cipher::default(), String::new());
+        let counters = NetworkCounters::default();
+        let mut inbox = Inbox::default();
+
+        // Act
+        let sequences = [GossipMessage::Pull, GossipMessage::Halt, GossipMessage::Resume].map(|message| outbox.record("b", &message).unwrap());
+        let digest = outbox.record("b", &GossipMessage::SyncDigest(1));
+        let retransmitted = outbox.retransmit("b", &[sequences[1], sequences[2]]);
+        let elsewhere = outbox.retransmit("c", &[sequences[1]]);
+        for _ in 0..32 {
+            outbox.record("b", &GossipMessage::Pull);
+        }
+        let evicted = outbox.retransmit("b", &[sequences[0], sequences[2]]);
+        test_send_message(&socket, "a", "b", GossipMessage::Pull, &peer.local_addr().unwrap().to_string(), &outbox, &counters);
+        let mut buffer = [0; DATAGRAM_SIZE];
+        let (number_of_bytes, _) = peer.recv_from(&mut buffer).unwrap();
+        let sent = test_parse_packet(&buffer[..number_of_bytes]).unwrap();
+        let missing = [10, 11, 14, 12, 15, 500, 3, 5].map(|sequence| inbox.receive("a", sequence));
+
+        // Assert
+        // Messages are numbered in order, and kept until the buffer is full, except the digests
+        assert_eq!(sequences, [sequences[0], sequences[0] + 1, sequences[0] + 2]);
+        assert_eq!(digest, None);
+        assert_eq!(retransmitted, vec![(sequences[1], GossipMessage::Halt), (sequences[2], GossipMessage::Resume)]);
+        assert!(elsewhere.is_empty() && evicted.is_empty());
+        assert_eq!(sent.sequence, Some(sequences[0] + 35));
+        // The gaps are asked for once, and a jump past the buffer is a new sequence, as after a restart
+        let expected: [Vec<u64>; 8] = [vec![], vec![], vec![12, 13], vec![], vec![], vec![], vec![], vec![4]];
+        assert_eq!(missing, expected);
+    }
+
+    #[test]
+    fn test_network_rate_limit() {
+        // Arrange
+        let start = Instant::now();
+        let mut shaper = Shaper::new(10, start);
+        let mut unlimited = Shaper::new(0, start);
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let outbox = Outbox::new(1400, 1, Cipher::default(), String::new());
+        let counters = NetworkCounters::default();
+
+        // Act
+        let refreshes = (0..10).filter(|_| shaper.admit(Priority::Refresh, 1, start)).count();
+        let orders = (0..10).filter(|_| shaper.admit(Priority::Order, 1, start)).count();
+        let refilled = shaper.admit(Priority::Order, 3, start + Duration::from_millis(300));
+        let flood = (0..1000).filter(|_| unlimited.admit(Priority::Refresh, 1, start)).count();
+        for message in [GossipMessage::Pull, GossipMessage::Halt] {
+            test_send_message(&socket, "a", "b", message, &peer.local_addr().unwrap().to_string(), &outbox, &counters);
+        }
+
+        // Assert
+        // The refreshes leave half of the budget to the orders, which get the rest of it
+        assert_eq!((refreshes, orders), (5, 5));
+        assert!(refilled);
+        assert_eq!(flood, 1000);
+        assert_eq!((Priority::of(&GossipMessage::HallCall { floor: 1, call: 0, place: true }), Priority::of(&GossipMessage::SyncDigest(1))), (Priority::Order, Priority::Refresh));
+        let statistics = counters.statistics();
+        assert_eq!((statistics.messages_sent, statistics.messages_shaped), (1, 1));
+    }
+
+    #[test]
+    fn test_network_peer_timeout() {
+        // Arrange
+        let start = Instant::now();
+        let mut table = PeerTable::new(Duration::from_millis(2000));
+        let at = |milliseconds: u64| start + Duration::from_millis(milliseconds);
+
+        // Act
+        let found = table.update(Some("a".to_string()), at(0));
+        let second = table.update(Some("b".to_string()), at(100));
+        let heard_again = table.update(Some("a".to_string()), at(200));
+        // A burst of lost broadcasts shorter than the timeout
+        let silence = table.update(None, at(1900));
+        let lost = table.update(Some("a".to_string()), at(2200));
+
+        // Assert
+        assert_eq!(found.map(|update| (update.peers, update.new)), Some((vec!["a".to_string()], Some("a".to_string()))));
+        assert_eq!(second.map(|update| update.peers), Some(vec!["a".to_string(), "b".to_string()]));
+        assert!(heard_again.is_none() && silence.is_none());
+        let lost = lost.unwrap();
+        assert_eq!((lost.peers, lost.new, lost.lost), (vec!["a".to_string()], None, vec!["b".to_string()]));
+    }
+
+    #[test]
+    fn test_network_port_collision() {
+        // Arrange
+        let taken = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        // Taken here, unless another program has it already
+        let _next = UdpSocket::bind(format!("0.0.0.0:{}", port + 1));
+
+        // Act
+        let without_range = test_bind_message_port(port, 0);
+        let exhausted = test_bind_message_port(port, 1);
+        let fallback = test_bind_message_port(port, 50).unwrap().local_addr().unwrap().port();
+
+        // Assert
+        // The errors name the ports in use, and the first free port of the range is taken
+        assert!(without_range.unwrap_err().contains(&format!("port {} is in use", port)));
+        assert!(exhausted.unwrap_err().contains(&format!("ports {} to {}", port, port + 1)));
+        assert!(fallback > port + 1 && fallback <= port + 50);
+    }
+
+    #[test]
+    fn test_network_mdns_announcement() {
+        // Arrange
+        let packet = announcement("a1b2@10.0.0.1:19735", 2, &Cipher::default());
+        // A response of another responder, asking for the service, and answering with a name compressed against it
+        let mut compressed = vec![0, 0, 0x84, 0, 0, 1, 0, 1, 0, 0, 0, 0];
+        for label in ["_elevator", "_udp", "local"] {
+            compressed.push(label.len() as u8);
+            compressed.extend(label.as_bytes());
+        }
+        compressed.extend([0, 0, 12, 0, 1]);
+        compressed.extend([1, b'b', 0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 2, 0, 18, 17]);
+        compressed.extend(b"peer=b@1.2.3.4:56");
+        let mut looping = compressed.clone();
+        looping[41] = 38;
+
+        // Act
+        let peers = announced_peers(&packet, &Cipher::default());
+        let compressed_peers = announced_peers(&compressed, &Cipher::default());
+        let truncated = (0..packet.len()).flat_map(|end| announced_peers(&packet[..end], &Cipher::default())).count();
+
+        // Assert
+        assert_eq!(peers, vec!["a1b2@10.0.0.1:19735".to_string()]);
+        assert_eq!(compressed_peers, vec!["b@1.2.3.4:56".to_string()]);
+        // Malformed packets, such as a pointer that loops, are skipped without a panic
+        assert!(announced_peers(&looping, &Cipher::default()).is_empty());
+        assert_eq!(truncated, 0);
+    }
+
+    #[test]
+    fn test_network_link_quality() {
+        // Arrange
+        let start = Instant::now();
+        let at = |milliseconds: u64| start + Duration::from_millis(milliseconds);
+        let mut monitor = LinkMonitor::default();
+
+        // Act
+        let unmeasured = monitor.sample(at(0));
+        // Two of ten messages found missing, and a pull answered after 40 ms
+        for missing in [0, 0, 2, 0, 0, 0, 0, 0] {
+            monitor.receive("a", missing);
+        }
+        monitor.pull_sent("a", at(1000));
+        monitor.data_received("a", at(1040));
+        // Data that answers no pull is not a round trip
+        monitor.data_received("b", at(1050));
+        let lossy = monitor.sample(at(1100));
+        // A quiet sample keeps the loss, and a clean one halves it
+        let quiet = monitor.sample(at(2100));
+        for _ in 0..10 {
+            monitor.receive("b", 0);
+        }
+        let recovering = monitor.sample(at(3100));
+        let links = monitor.links();
+
+        // Assert
+        assert_eq!((unmeasured.loss, unmeasured.round_trip), (0.0, None));
+        assert!((lossy.loss - 0.2).abs() < 1e-9);
+        assert_eq!(lossy.round_trip, Some(Duration::from_millis(40)));
+        assert_eq!(quiet, lossy);
+        assert!((recovering.loss - 0.1).abs() < 1e-9);
+        // Every peer is measured on its own, and the loss of a peer counts since start
+        assert_eq!(links.keys().collect::<Vec<&String>>(), vec!["a", "b"]);
+        assert_eq!((links["a"].received, links["a"].missing, links["a"].round_trip), (8, 2, Some(40.0)));
+        assert!((links["a"].loss - 0.2).abs() < 1e-9);
+        assert_eq!((links["b"].received, links["b"].loss, links["b"].round_trip), (10, 0.0, None));
+    }
+
+    #[test]
+    fn test_network_encryption() {
+        // Arrange
+        let key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let other_key = "ff0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let plaintext = Cipher::new(Encryption::Plaintext, "").unwrap();
+        let plaintext_with_key = Cipher::new(Encryption::Plaintext, key).unwrap();
+        let mixed = Cipher::new(Encryption::Mixed, key).unwrap();
+        let required = Cipher::new(Encryption::Required, key).unwrap();
+        let other = Cipher::new(Encryption::Required, other_key).unwrap();
+        let datagram = serde_json::to_string(&envelope(GossipMessage::Pull)).unwrap();
+
+        // Act
+        let sealed = mixed.seal(datagram.as_bytes());
+        let mut altered = sealed.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        let unsealed = plaintext.seal(datagram.as_bytes());
+
+        // Assert
+        // Nothing of the message shows, and the nonce differs for every datagram
+        assert!(!String::from_utf8_lossy(&sealed).contains("pull"));
+        assert_eq!(sealed.len(), datagram.len() + mixed.overhead());
+        assert_ne!(mixed.seal(datagram.as_bytes()), sealed);
+        assert_eq!(unsealed, datagram.as_bytes());
+        // Opened with the key in every mode, and not without it, with another key or once altered
+        for cipher in [&plaintext_with_key, &mixed, &required] {
+            assert_eq!(cipher.open(&sealed).as_deref(), Some(datagram.as_bytes()));
+        }
+        assert_eq!(plaintext.open(&sealed), None);
+        assert_eq!(other.open(&sealed), None);
+        assert_eq!(required.open(&altered), None);
+        // Plaintext is accepted until encryption is required
+        assert_eq!(mixed.open(datagram.as_bytes()).as_deref(), Some(datagram.as_bytes()));
+        assert_eq!(required.open(datagram.as_bytes()), None);
+        // Encryption needs a valid key
+        assert!(Cipher::new(Encryption::Mixed, "").is_err());
+        assert!(Cipher::new(Encryption::Plaintext, "0011").is_err());
+    }
+
+    #[test]
+    fn test_network_replays() {
+        // Arrange
+        let key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let sender = Cipher::new(Encryption::Required, key).unwrap();
+        let receiver = Cipher::new(Encryption::Required, key).unwrap();
+        let mixed = Cipher::new(Encryption::Mixed, key).unwrap();
+        let restarted = Cipher::new(Encryption::Required, key).unwrap();
+        let sealed = (0..70).map(|index| sender.seal(format!("datagram {}", index).as_bytes())).collect::<Vec<Vec<u8>>>();
+        let peer = format!("{}@10.0.0.1:19735", "a1b2".repeat(30));
+        let announced = announcement(&peer, 2, &sender);
+
+        // Act
+        let first = receiver.open(&sealed[1]);
+        let replayed = receiver.open(&sealed[1]);
+        let reordered = receiver.open(&sealed[0]);
+        let last = receiver.open(&sealed[69]);
+        let below_window = receiver.open(&sealed[2]);
+        let within_window = receiver.open(&sealed[10]);
+        let by_clone = receiver.clone().open(&sealed[1]);
+        let after_restart = receiver.open(&restarted.seal(b"datagram 0"));
+
+        // Assert
+        // A datagram opens once, in any order within the window of its sender
+        assert_eq!(first.as_deref(), Some(b"datagram 1".as_slice()));
+        assert_eq!(replayed, None);
+        assert_eq!(reordered.as_deref(), Some(b"datagram 0".as_slice()));
+        assert_eq!(last.as_deref(), Some(b"datagram 69".as_slice()));
+        assert_eq!(below_window, None);
+        assert_eq!(within_window.as_deref(), Some(b"datagram 10".as_slice()));
+        // A clone checks the datagrams of its socket on its own, and a restarted sender is a new one
+        assert_eq!(by_clone.as_deref(), Some(b"datagram 1".as_slice()));
+        assert_eq!(after_restart.as_deref(), Some(b"datagram 0".as_slice()));
+        // The mDNS announcements are sealed too, however long the peer, and not read once replayed or in plaintext
+        assert_eq!(announced_peers(&announced, &receiver), vec![peer.clone()]);
+        assert!(announced_peers(&announced, &receiver).is_empty());
+        assert!(announced_peers(&announcement(&peer, 2, &Cipher::default()), &receiver).is_empty());
+        assert!(announced_peers(&announcement(&peer, 2, &Cipher::default()), &mixed).is_empty());
+        // A sealed announcement holds neither the ID nor the address in plaintext
+        let text = String::from_utf8_lossy(&announced);
+        assert!(!text.contains("id=") && !text.contains("peer=") && !text.contains("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_network_duplicate_id() {
+        // Arrange
+        let peers = |peers: &[&str]| peers.iter().map(|peer| peer.to_string()).collect::<Vec<String>>();
+        let local = "10.0.0.2:1";
+
+        // Act
+        let alone = test_duplicate_of(&peers(&["a@10.0.0.2:1", "b@10.0.0.3:1"]), "a", local);
+        let cloned = test_duplicate_of(&peers(&["a@10.0.0.2:1", "a@10.0.0.9:1", "a@10.0.0.1:1"]), "a", local);
+
+        // Assert
+        // Only the local ID broadcast from another address is a duplicate
+        assert_eq!(alone, None);
+        assert_eq!(cloned, Some("10.0.0.1:1".to_string()));
+        // Exactly one of the two stands down
+        assert!(test_stands_down(local, "10.0.0.1:1"));
+        assert!(!test_stands_down("10.0.0.1:1", local));
+    }
+
+    #[test]
+    fn test_network_peer_versions() {
+        // Arrange
+        let peers = |peers: &[&str]| peers.iter().map(|peer| peer.to_string()).collect::<Vec<String>>();
+        let local = format!("a@10.0.0.2:1#{}", PeerVersion::local());
+        let old = PeerVersion { crate_version: "0.1.0".to_string(), schema_version: 0 };
+        let mut known = HashMap::new();
+
+        // Act
+        let first = test_changed_versions(&mut known, &peers(&[&local, "b@10.0.0.3:1#0.1.0/0", "c@10.0.0.4:1"]), "a");
+        let same = test_changed_versions(&mut known, &peers(&[&local, "b@10.0.0.3:1#0.1.0/0", "c@10.0.0.4:1"]), "a");
+        let upgraded = test_changed_versions(&mut known, &peers(&[&local, &format!("b@10.0.0.3:1#{}", PeerVersion::local())]), "a");
+        let update = test_translate_peer_update(&mut HashMap::new(), PeerUpdate { peers: peers(&[&local]), new: Some(local.clone()), lost: vec![] });
+
+        // Assert
+        // Only new and changed versions are told, and a peer without one is None
+        assert_eq!(first, vec![("b".to_string(), Some(old.clone())), ("c".to_string(), None)]);
+        assert!(!old.compatible());
+        assert!(same.is_empty());
+        assert_eq!(upgraded, vec![("b".to_string(), Some(PeerVersion::local()))]);
+        assert_eq!(PeerVersion::local().schema_version, SCHEMA_VERSION);
+        assert!(!known.contains_key("c"));
+        // The version is not part of the ID or the address
+        assert_eq!(update.peers, vec!["a".to_string()]);
+        assert_eq!(announced_peers(&announcement(&local, 1, &Cipher::default()), &Cipher::default()), vec![local.clone()]);
+    }
+
+    #[test]
+    fn test_network_groups() {
+        // Arrange
+        let local = PeerVersion::local();
+        let default_group = test_peer_announcement("a", "10.0.0.2:1", Some(&local), "");
+        let north = test_peer_announcement("b", "10.0.0.3:1", Some(&local), "north");
+        let relayed = test_peer_announcement("c", "10.0.0.9:2", None, "north");
+        let update = || PeerUpdate { peers: vec![default_group.clone(), north.clone(), relayed.clone()], new: Some(north.clone()), lost: vec![default_group.clone()] };
+        let grouped = Envelope { group: "north".to_string(), ..envelope(GossipMessage::State(ElevatorData::new(4))) };
+
+        // Act
+        let in_north = test_in_group(update(), "north");
+        let in_default = test_in_group(update(), "");
+        let translated = test_translate_peer_update(&mut HashMap::new(), test_in_group(update(), "north"));
+        let versions = test_changed_versions(&mut HashMap::new(), &in_north.peers, "a");
+        let fragments = fragment(&grouped, 300).iter().map(|datagram| test_parse_packet(datagram.as_bytes()).unwrap()).collect::<Vec<Envelope>>();
+
+        // Assert
+        // The default group is announced and sent as before the groups
+        assert_eq!(default_group, format!("a@10.0.0.2:1#{}", local));
+        assert_eq!(relayed, "c@10.0.0.9:2##north");
+        assert!(!serde_json::to_string(&envelope(GossipMessage::Pull)).unwrap().contains("group"));
+        // Only the peers of the group are kept, by ID, with their versions
+        assert_eq!(in_north.peers, vec![north.clone(), relayed]);
+        assert_eq!((in_north.new, in_north.lost), (Some(north), vec![]));
+        assert_eq!((in_default.peers, in_default.new), (vec![default_group], None));
+        assert_eq!(translated.peers, vec!["b".to_string(), "c".to_string()]);
+        assert_eq!(versions, vec![("b".to_string(), Some(local)), ("c".to_string(), None)]);
+        // Every fragment of a message carries its group
+        assert!(fragments.len() > 1 && fragments.iter().all(|fragment| fragment.group == "north"));
+    }
+
+    #[test]
+    fn test_network_clock_offsets() {
+        // Arrange
+        let start = Instant::now();
+        let now = 1_700_000_000_000;
+        let mut monitor = LinkMonitor::default();
+        monitor.pull_sent("a", start);
+        monitor.data_received("a", start + Duration::from_millis(40));
+        let mut data = ElevatorData::new(4);
+        data.hall_request_times[1][0] = 1_700_000_000;
+        let mut state = ElevatorState::new(4);
+        state.cab_completion_times = vec![0, 1_700_000_100, 0, 0];
+        data.states.insert("a".to_string(), state);
+
+        // Act
+        // The clock of a is 3 s ahead, and its digest takes half the round trip to arrive
+        monitor.clock_received("a", now - 20 + 3000, now);
+        let first = monitor.clock_offset("a");
+        monitor.clock_received("a", now - 20 + 3800, now);
+        monitor.clock_received("b", now - 2000, now);
+        let shifted = test_on_local_clock(data.clone(), monitor.clock_offset("a"));
+        let within_a_second = test_on_local_clock(data.clone(), Some(400.0));
+        let unknown = test_on_local_clock(data.clone(), None);
+        let older = serde_json::from_str::<Digest>(r#"{"version":1,"elevators":[]}"#).unwrap();
+
+        // Assert
+        // The samples are smoothed, and a peer without a round trip yet is taken to have no transit
+        assert_eq!(first, Some(3000.0));
+        assert_eq!(monitor.clock_offset("a"), Some(3100.0));
+        assert_eq!(monitor.links()["b"].clock_offset, Some(-2000.0));
+        assert_eq!(monitor.clock_offset("c"), None);
+        // The times are moved to the local clock by whole seconds, and unset times stay unset
+        assert_eq!(shifted.hall_request_times[1], vec![1_699_999_997, 0]);
+        assert_eq!(shifted.hall_completion_times, data.hall_completion_times);
+        assert_eq!(shifted.states["a"].cab_completion_times, vec![0, 1_700_000_097, 0, 0]);
+        assert_eq!((within_a_second, unknown), (data.clone(), data.clone()));
+        // The same data on another clock has the same hash
+        assert_eq!(test_data_hash(&shifted), test_data_hash(&data));
+        assert_eq!(older.sent, 0);
+    }
+}
@@ -0,0 +1,214 @@
+/**
+ * Discovery of the peers by broadcast, in place of `udpnet::peers`, with the timings configured.
+ *
+ * Every node broadcasts its ID on `peer_port` every `peer_interval` milliseconds, to `peer_broadcast_address`, and
+ * takes a peer it has not heard from for `peer_timeout` milliseconds as lost. The updates are the `PeerUpdate`s of
+ * `udpnet::peers`, sent whenever a peer is found or lost. The constants of `udpnet::peers`, a broadcast every 15 ms
+ * and a timeout of 500 ms, are the defaults, and lose peers on a lossy WiFi that are not gone, which are then found
+ * again at once. Every such loss reassigns the hall calls, so a longer timeout is set where the network drops
+ * bursts of packets. The socket options are those of `udpnet`, so both can run side by side on the same port.
+ *
+ * With adaptation enabled, the timeout is widened while the network is lossy. See `AdaptationPolicy`.
+ *
+ * With `encryption` on, the broadcasts are sealed with the key of the fleet like the datagrams, and those that do
+ * not open or are replayed are dropped. See `Cipher`.
+ *
+ * With `peer_discovery` set to `mdns`, the IDs are announced by multicast DNS instead, with the same timings. See
+ * `mdns`.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use network_rust::udpnet::peers::PeerUpdate;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::NetworkConfig;
+use crate::network::encryption::Cipher;
+use crate::network::mdns;
+use crate::network::network::stopping;
+use crate::shared::PeerDiscovery;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(15);
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+const DEFAULT_BROADCAST_ADDRESS: &str = "255.255.255.255";
+const ID_SIZE: usize = 1024;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// The port and timings of the peer broadcasts. The timeout in milliseconds widened by the adaptation, if any, is
+// shared by the clones.
+#[derive(Debug, Clone)]
+pub struct PeerSettings {
+    pub discovery: PeerDiscovery,
+    pub port: u16,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub broadcast_address: String,
+    widened_timeout: Arc<AtomicU64>,
+}
+
+impl PeerSettings {
+    pub fn new(net_config: &NetworkConfig) -> PeerSettings {
+        let milliseconds = |value: u64, default: Duration| match value {
+            0 => default,
+            value => Duration::from_millis(value),
+        };
+        PeerSettings {
+            discovery: net_config.peer_discovery,
+            port: net_config.peer_port,
+            interval: milliseconds(net_config.peer_interval, DEFAULT_INTERVAL),
+            timeout: milliseconds(net_config.peer_timeout, DEFAULT_TIMEOUT),
+            broadcast_address: match net_config.peer_broadcast_address.is_empty() {
+                true => DEFAULT_BROADCAST_ADDRESS.to_string(),
+                false => net_config.peer_broadcast_address.clone(),
+            },
+            widened_timeout: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // The timeout in effect, the configured one unless widened
+    pub fn current_timeout(&self) -> Duration {
+        match self.widened_timeout.load(Ordering::Relaxed) {
+            0 => self.timeout,
+            milliseconds => Duration::from_millis(milliseconds),
+        }
+    }
+
+    pub fn widen_timeout(&self, timeout: Duration) {
+        self.widened_timeout.store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+// The peers heard from, and when they were last heard from
+pub struct PeerTable {
+    timeout: Duration,
+    last_heard: HashMap<String, Instant>,
+}
+
+impl PeerTable {
+    pub fn new(timeout: Duration) -> PeerTable {
+        PeerTable { timeout, last_heard: HashMap::new() }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    // Records the peer heard from, if any, and drops the peers not heard from within the timeout. Returns the update
+    // if a peer was found or lost.
+    pub fn update(&mut self, heard: Option<String>, now: Instant) -> Option<PeerUpdate> {
+        let new = heard.filter(|id| self.last_heard.insert(id.clone(), now).is_none());
+        let mut lost = self
+            .last_heard
+            .iter()
+            .filter(|(_, last_heard)| now.saturating_duration_since(**last_heard) > self.timeout)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<String>>();
+        if new.is_none() && lost.is_empty() {
+            return None;
+        }
+
+        for id in &lost {
+            self.last_heard.remove(id);
+        }
+        let mut peers = self.last_heard.keys().cloned().collect::<Vec<String>>();
+        peers.sort();
+        lost.sort();
+        Some(PeerUpdate { peers, new, lost })
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Broadcasts the ID while enabled, as `udpnet::peers::tx`, or announces it by mDNS. Ends once `tx_enable` is
+// disconnected.
+pub fn tx(settings: &PeerSettings, cipher: Cipher, id: String, tx_enable: cbc::Receiver<bool>) -> io::Result<()> {
+    match settings.discovery {
+        PeerDiscovery::Broadcast => broadcast_tx(settings, cipher, id, tx_enable),
+        PeerDiscovery::Mdns => mdns::tx(settings, cipher, id, tx_enable),
+    }
+}
+
+// Receives the IDs broadcast, as `udpnet::peers::rx`, or announced by mDNS, and sends an update whenever a peer is
+// found or lost. Ends once `stop_rx` is disconnected.
+pub fn rx(settings: &PeerSettings, cipher: Cipher, update: cbc::Sender<PeerUpdate>, stop_rx: cbc::Receiver<()>) -> io::Result<()> {
+    match settings.discovery {
+        PeerDiscovery::Broadcast => broadcast_rx(settings, cipher, update, stop_rx),
+        PeerDiscovery::Mdns => mdns::rx(settings, cipher, update, stop_rx),
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn broadcast_tx(settings: &PeerSettings, cipher: Cipher, id: String, tx_enable: cbc::Receiver<bool>) -> io::Result<()> {
+    let socket = UdpSocket::from(socket()?);
+    socket.connect(format!("{}:{}", settings.broadcast_address, settings.port))?;
+    let ticker = cbc::tick(settings.interval);
+    let mut enabled = true;
+    loop {
+        cbc::select! {
+            recv(tx_enable) -> enable => {
+                match enable {
+                    Ok(enable) => enabled = enable,
+                    Err(_) => return Ok(()),
+                }
+            }
+            recv(ticker) -> _ => {
+                // A broadcast that fails, as while the network is down, is made up for by the next
+                if enabled {
+                    let _ = socket.send(&cipher.seal(id.as_bytes()));
+                }
+            }
+        }
+    }
+}
+
+fn broadcast_rx(settings: &PeerSettings, cipher: Cipher, update: cbc::Sender<PeerUpdate>, stop_rx: cbc::Receiver<()>) -> io::Result<()> {
+    let socket = socket()?;
+    socket
+        .bind(&SocketAddr::from(([0, 0, 0, 0], settings.port)).into())
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to bind the peer port {}: {}", settings.port, e)))?;
+    let socket = UdpSocket::from(socket);
+    socket.set_read_timeout(Some(settings.interval))?;
+
+    let mut table = PeerTable::new(settings.timeout);
+    let mut buffer = [0; ID_SIZE];
+    while !stopping(&stop_rx) {
+        let heard = match socket.recv(&mut buffer) {
+            Ok(number_of_bytes) => cipher
+                .open(&buffer[..number_of_bytes])
+                .and_then(|id| String::from_utf8(id).ok()),
+            Err(_) => None,
+        };
+        table.set_timeout(settings.current_timeout());
+        if let Some(peer_update) = table.update(heard, Instant::now()) {
+            update.send(peer_update).map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+        }
+    }
+    Ok(())
+}
+
+// A socket for broadcasts, shared with the other nodes on the computer
+fn socket() -> io::Result<Socket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_broadcast(true)?;
+    socket.set_reuse_address(true)?;
+    Ok(socket)
+}
@@ -0,0 +1,119 @@
+/**
+ * Measurement of the loss and round-trip time of the network, fed back into the adaptation policy.
+ *
+ * The loss is estimated from the numbered messages of the peers: every message found missing by the receiver is
+ * counted as lost, against those received. The round-trip time is measured on the pulls, from sending a pull to
+ * the data it is answered with. Both are smoothed over the samples, so a single lost burst does not swing the
+ * policy, while a lasting change shows within a few samples.
+ *
+ * The same is kept for every peer on its own, as the `PeerLink` reported by the `links` command: the loss counted
+ * since start, and the round trip of the pulls to that peer, smoothed the same way.
+ *
+ * The offset of the clock of every peer is estimated from the wall clock its digests carry: the time it sent one,
+ * plus half the round trip to it, less the time it arrived. Without a round trip yet, the transit is taken as none.
+ * The samples are smoothed like the round trips, so the jitter of the transit averages out.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{NetworkQuality, PeerLink};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// The weight of a new sample of the loss, and of a new round trip, as for the round trips of TCP
+const LOSS_SMOOTHING: f64 = 0.5;
+const ROUND_TRIP_SMOOTHING: f64 = 0.125;
+const CLOCK_OFFSET_SMOOTHING: f64 = 0.125;
+// A pull not answered within this is taken as lost
+const PULL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Default)]
+pub struct LinkMonitor {
+    received: u64,
+    missing: u64,
+    loss: Option<f64>,
+    round_trip: Option<Duration>,
+    pulls: HashMap<String, Instant>,
+    peers: BTreeMap<String, PeerLink>,
+}
+
+impl LinkMonitor {
+    // Counts a numbered message received from a peer, and the messages found missing before it
+    pub fn receive(&mut self, peer: &str, missing: usize) {
+        self.received += 1;
+        self.missing += missing as u64;
+        let link = self.peers.entry(peer.to_string()).or_default();
+        link.received += 1;
+        link.missing += missing as u64;
+        link.loss = link.missing as f64 / (link.received + link.missing) as f64;
+    }
+
+    pub fn pull_sent(&mut self, peer: &str, now: Instant) {
+        self.pulls.insert(peer.to_string(), now);
+    }
+
+    // Measures the round trip of the pull the data of a peer answers, if any
+    pub fn data_received(&mut self, peer: &str, now: Instant) {
+        let Some(sent) = self.pulls.remove(peer) else { return };
+        let sample = now.saturating_duration_since(sent);
+        self.round_trip = Some(match self.round_trip {
+            Some(round_trip) => round_trip.mul_f64(1.0 - ROUND_TRIP_SMOOTHING) + sample.mul_f64(ROUND_TRIP_SMOOTHING),
+            None => sample,
+        });
+        let link = self.peers.entry(peer.to_string()).or_default();
+        let sample = sample.as_secs_f64() * 1000.0;
+        link.round_trip = Some(match link.round_trip {
+            Some(round_trip) => round_trip * (1.0 - ROUND_TRIP_SMOOTHING) + sample * ROUND_TRIP_SMOOTHING,
+            None => sample,
+        });
+    }
+
+    // Estimates the offset of the clock of a peer from a digest it sent at `sent`, received at `now`, both wall clocks
+    // in milliseconds since the Unix epoch
+    pub fn clock_received(&mut self, peer: &str, sent: u64, now: u64) {
+        let link = self.peers.entry(peer.to_string()).or_default();
+        let sample = sent as f64 + link.round_trip.unwrap_or(0.0) / 2.0 - now as f64;
+        link.clock_offset = Some(match link.clock_offset {
+            Some(offset) => offset * (1.0 - CLOCK_OFFSET_SMOOTHING) + sample * CLOCK_OFFSET_SMOOTHING,
+            None => sample,
+        });
+    }
+
+    // How far the clock of the peer is ahead of the local one, in milliseconds, if known
+    pub fn clock_offset(&self, peer: &str) -> Option<f64> {
+        self.peers.get(peer).and_then(|link| link.clock_offset)
+    }
+
+    // The links measured to every peer heard from since start
+    pub fn links(&self) -> BTreeMap<String, PeerLink> {
+        self.peers.clone()
+    }
+
+    // The quality measured so far, starting a new sample. A sample without messages leaves the loss as it was.
+    pub fn sample(&mut self, now: Instant) -> NetworkQuality {
+        let total = self.received + self.missing;
+        if total > 0 {
+            let loss = self.missing as f64 / total as f64;
+            self.loss = Some(match self.loss {
+                Some(smoothed) => smoothed * (1.0 - LOSS_SMOOTHING) + loss * LOSS_SMOOTHING,
+                None => loss,
+            });
+        }
+        self.received = 0;
+        self.missing = 0;
+        self.pulls.retain(|_, sent| now.saturating_duration_since(*sent) < PULL_TIMEOUT);
+
+        NetworkQuality { loss: self.loss.unwrap_or(0.0), round_trip: self.round_trip }
+    }
+}
@@ -0,0 +1,313 @@
+/**
+ * Relays the fleet between two network segments.
+ *
+ * Peer broadcasts do not cross routers, so elevators on another segment, for instance at the other end of a VPN,
+ * are never discovered. A relay on each segment makes up for this. Every `announce_interval` milliseconds each relay
+ * tells the other relay which elevators it hears on its own segment, and broadcasts the elevators of the other
+ * segment on its own segment, at the address of the relay. Messages sent to those elevators thus reach the relay,
+ * which forwards them to the other relay, which delivers them by their destination ID. Replies are addressed by
+ * the origin ID of the message, so they find their way back the same way.
+ *
+ * The elevators of the other segment are withdrawn when the other relay has not been heard from for a while.
+ *
+ * The relay is terminated like the modules. Its threads stop, and the broadcasts of the relayed elevators with them.
+ *
+ * The relay only relays the elevators and messages of its own `group_id`, and announces the elevators of the other
+ * segment in that group.
+ *
+ * With `encryption` on, the relay opens the datagrams and peer broadcasts of the elevators with the key of the fleet,
+ * and seals what it sends, to the other relay as well as to the elevators, and the broadcasts of the relayed elevators.
+ *
+ * # Relay
+ * Struct for initializing the relay.
+ *
+ * # Fields
+ * - `address`: The address the elevators of the other segment are announced at.
+ *
+ * # Constructor arguments
+ * - `net_config`:   Network configuration settings. The relay listens for peers on the port of the elevators.
+ * - `relay_config`: Relay configuration settings.
+ * - `relay_terminate_rx`: Receiver for the request to terminate, acknowledged once the threads have ended.
+ *
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use network_rust::udpnet::peers::PeerUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+use log::{info, warn, error};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::{NetworkConfig, RelayConfig};
+use crate::network::encryption::Cipher;
+use crate::network::network::{find_local_ip, parse_packet, peer_announcement, peer_group, split_peer, stopping, terminate_threads, STOP_POLL_INTERVAL};
+use crate::network::peers::{self, PeerSettings};
+use crate::network::Envelope;
+use crate::shared::{exit, Clock, ExitReason, Terminate};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const DATAGRAM_SIZE: usize = 4096;
+// Announcement rounds the other relay may miss before its elevators are withdrawn
+const MISSED_ANNOUNCEMENTS: u32 = 3;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// Messages exchanged between the two relays
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RelayMessage {
+    Peers(Vec<String>),
+    Forward(Box<Envelope>),
+}
+
+// Elevators of the other segment, and the broadcasts announcing them on this segment
+#[derive(Default)]
+struct RemotePeers {
+    announced: Vec<String>,
+    announcers: HashMap<String, cbc::Sender<bool>>,
+    last_heard: Option<Instant>,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Relay {
+    pub address: String,
+}
+
+impl Relay {
+    pub fn new(
+        net_config: &NetworkConfig,
+        relay_config: &RelayConfig,
+        clock: &dyn Clock,
+        relay_terminate_rx: cbc::Receiver<Terminate>,
+    ) -> std::io::Result<Relay> {
+        let peer_settings = PeerSettings::new(net_config);
+        let cipher = Cipher::new(net_config.encryption, &net_config.encryption_key)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+        let announce_cipher = cipher.clone();
+        let peer_rx_cipher = cipher.clone();
+        let announce_peer_settings = peer_settings.clone();
+        let relay_peer_settings = peer_settings.clone();
+        let remote_address = relay_config.remote_address.clone();
+        let announce_interval = Duration::from_millis(relay_config.announce_interval);
+        let group = net_config.group_id.clone();
+
+        let address = match find_local_ip(
+            net_config.id_gen_address.clone(),
+            net_config.max_attempts_id_generation,
+            Duration::from_millis(net_config.delay_between_attempts_id_generation),
+            clock,
+        ) {
+            Some(ip) => format!("{}:{}", ip, relay_config.port),
+            None => {
+                error!("Failed to find local IP, relay is offline");
+                return Ok(Relay { address: "Offline Relay".to_string() });
+            }
+        };
+        info!("Relaying at {} to {}", address, remote_address);
+
+        let socket = UdpSocket::bind(format!("0.0.0.0:{}", relay_config.port))?;
+        socket.set_read_timeout(Some(STOP_POLL_INTERVAL))?;
+        let announce_socket = socket.try_clone()?;
+        let local_peers: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
+        let remote_peers: Arc<Mutex<RemotePeers>> = Arc::new(Mutex::new(RemotePeers::default()));
+
+        // Thread receiving the peers of this segment on port 'peer_port'
+        let (peer_update_tx, peer_update_rx) = cbc::unbounded::<PeerUpdate>();
+        let (stop_tx, stop_rx) = cbc::bounded::<()>(0);
+        let peer_rx_stop_rx = stop_rx.clone();
+        let mut threads = Vec::new();
+        let relay_peer_rx_thread = Builder::new().name("relay_peer_rx".into());
+        threads.push(relay_peer_rx_thread
+            .spawn(move || {
+                if let Err(error) = peers::rx(&peer_settings, peer_rx_cipher, peer_update_tx, peer_rx_stop_rx) {
+                    error!("Failed to receive peer updates for the relay: {}. Exiting...", error);
+                    exit(ExitReason::PortInUse);
+                }
+            })
+            .unwrap());
+
+        // Thread keeping the elevators of this segment, and announcing them to the other relay
+        let announce_local_peers = local_peers.clone();
+        let announce_remote_peers = remote_peers.clone();
+        let announce_address = address.clone();
+        let announce_group = group.clone();
+        let announce_stop_rx = stop_rx.clone();
+        let relay_announce_thread = Builder::new().name("relay_announce".into());
+        threads.push(relay_announce_thread
+            .spawn(move || {
+                let announce_ticker = cbc::tick(announce_interval);
+                loop {
+                    cbc::select! {
+                        recv(peer_update_rx) -> update => {
+                            match update {
+                                Ok(update) => {
+                                    *announce_local_peers.lock().unwrap() = local_peers_of(&update, &announce_address, &announce_group);
+                                }
+                                Err(_) if stopping(&announce_stop_rx) => break,
+                                Err(error) => {
+                                    error!("Error receiving peer update for the relay: {}", error);
+                                    exit(ExitReason::Failure);
+                                }
+                            }
+                        }
+                        recv(announce_ticker) -> _ => {
+                            let mut ids = announce_local_peers.lock().unwrap().keys().cloned().collect::<Vec<String>>();
+                            ids.sort();
+                            send_relay_message(&announce_socket, &RelayMessage::Peers(ids), &remote_address, &announce_cipher);
+
+                            let mut remote = announce_remote_peers.lock().unwrap();
+                            let silent = remote.last_heard.is_some_and(|heard| heard.elapsed() > announce_interval * MISSED_ANNOUNCEMENTS);
+                            if silent && !remote.announced.is_empty() {
+                                warn!("Lost the relay at {}, withdrawing its elevators", remote_address);
+                                announce_remote(&mut remote, Vec::new(), &announce_peer_settings, &announce_cipher, &announce_address, &announce_group);
+                            }
+                        }
+                        recv(announce_stop_rx) -> _ => break,
+                    }
+                }
+
+                // The broadcasts of the relayed elevators stop with their announcers
+                announce_remote_peers.lock().unwrap().announcers.clear();
+            })
+            .unwrap());
+
+        // Thread forwarding messages between the elevators of this segment and the other relay
+        let remote_address = relay_config.remote_address.clone();
+        let relay_address = address.clone();
+        let relay_rx_thread = Builder::new().name("relay_rx".into());
+        threads.push(relay_rx_thread.spawn(move || {
+            let mut buffer = [0; DATAGRAM_SIZE];
+            while !stopping(&stop_rx) {
+                let number_of_bytes = match socket.recv_from(&mut buffer) {
+                    Ok((number_of_bytes, _)) => number_of_bytes,
+                    Err(error) if matches!(error.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+                    Err(error) => {
+                        error!("Failed to receive a message at the relay: {}", error);
+                        continue;
+                    }
+                };
+                let Some(received) = cipher.open(&buffer[..number_of_bytes]) else {
+                    continue;
+                };
+                let received = received.as_slice();
+
+                match serde_json::from_slice::<RelayMessage>(received) {
+                    // The elevators of the other segment, except any that are also heard here
+                    Ok(RelayMessage::Peers(ids)) => {
+                        let local_peers = local_peers.lock().unwrap();
+                        let ids = ids.into_iter().filter(|id| !local_peers.contains_key(id)).collect();
+                        let mut remote = remote_peers.lock().unwrap();
+                        remote.last_heard = Some(Instant::now());
+                        announce_remote(&mut remote, ids, &relay_peer_settings, &cipher, &relay_address, &group);
+                    }
+                    Ok(RelayMessage::Forward(envelope)) => {
+                        let peer_address = local_peers.lock().unwrap().get(&envelope.destination).cloned();
+                        if let Some(peer_address) = peer_address {
+                            let serialized_envelope = serde_json::to_string(&envelope).unwrap();
+                            if let Err(error) = socket.send_to(&cipher.seal(serialized_envelope.as_bytes()), &peer_address) {
+                                info!("Failed to deliver relayed message to {}: {}", peer_address, error);
+                            }
+                        }
+                    }
+                    Err(_) => {
+                        if let Some(envelope) = parse_packet(received).filter(|envelope| envelope.group == group) {
+                            if remote_peers.lock().unwrap().announced.contains(&envelope.destination) {
+                                send_relay_message(&socket, &RelayMessage::Forward(Box::new(envelope)), &remote_address, &cipher);
+                            }
+                        }
+                    }
+                }
+            }
+        }).unwrap());
+
+        terminate_threads("relay", relay_terminate_rx, stop_tx, threads);
+        Ok(Relay { address })
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The elevators of the group heard on this segment. Those at the relay address are the ones the relay announces
+// itself.
+fn local_peers_of(update: &PeerUpdate, relay_address: &str, group: &str) -> HashMap<String, String> {
+    update
+        .peers
+        .iter()
+        .filter(|peer| peer_group(peer) == group)
+        .map(|peer| split_peer(peer))
+        .filter(|(_, address)| address != relay_address)
+        .collect()
+}
+
+// Announces the given elevators of the other segment on this segment, and withdraws the rest.
+// The broadcast of an elevator is paused rather than stopped, and resumed if it returns.
+fn announce_remote(remote: &mut RemotePeers, ids: Vec<String>, peer_settings: &PeerSettings, cipher: &Cipher, relay_address: &str, group: &str) {
+    for id in remote.announced.iter().filter(|id| !ids.contains(id)) {
+        info!("Withdrawing relayed elevator {}", id);
+        if let Some(announcer) = remote.announcers.get(id) {
+            let _ = announcer.send(false);
+        }
+    }
+
+    for id in ids.iter().filter(|id| !remote.announced.contains(id)) {
+        info!("Announcing relayed elevator {}", id);
+        match remote.announcers.get(id) {
+            Some(announcer) => {
+                let _ = announcer.send(true);
+            }
+            None => {
+                let (announcer_tx, announcer_rx) = cbc::unbounded::<bool>();
+                let announcement = peer_announcement(id, relay_address, None, group);
+                let settings = peer_settings.clone();
+                let cipher = cipher.clone();
+                let relay_peer_tx_thread = Builder::new().name("relay_peer_tx".into());
+                relay_peer_tx_thread
+                    .spawn(move || {
+                        if peers::tx(&settings, cipher, announcement, announcer_rx).is_err() {
+                            error!("Failed to announce relayed elevator. Exiting...");
+                            exit(ExitReason::Failure);
+                        }
+                    })
+                    .unwrap();
+                remote.announcers.insert(id.clone(), announcer_tx);
+            }
+        }
+    }
+
+    remote.announced = ids;
+}
+
+fn send_relay_message(socket: &UdpSocket, message: &RelayMessage, remote_address: &str, cipher: &Cipher) {
+    let serialized_message = serde_json::to_string(message).unwrap();
+    if let Err(error) = socket.send_to(&cipher.seal(serialized_message.as_bytes()), remote_address) {
+        info!("Failed to send to the relay at {}: {}", remote_address, error);
+    }
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use network_rust::udpnet::peers::PeerUpdate;
+    use std::collections::HashMap;
+
+    pub fn test_local_peers_of(update: &PeerUpdate, relay_address: &str, group: &str) -> HashMap<String, String> {
+        super::local_peers_of(update, relay_address, group)
+    }
+}
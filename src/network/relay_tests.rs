@@ -0,0 +1,64 @@
+/*
+ * Unit tests for the relay between network segments
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_relay_ignores_own_announcements
+ *  - test_relay_tells_messages_apart
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod relay_tests {
+    use crate::network::relay::testing::test_local_peers_of;
+    use crate::network::{Envelope, GossipMessage, RelayMessage};
+    use network_rust::udpnet::peers::PeerUpdate;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_relay_ignores_own_announcements() {
+        // Arrange
+        let update = PeerUpdate {
+            peers: vec![
+                "a@10.0.0.1:19735".to_string(),
+                "b@10.0.0.9:19740".to_string(),
+                "10.0.0.3:19735".to_string(),
+                "c@10.0.0.4:19735#0.1.0/1#north".to_string(),
+            ],
+            new: None,
+            lost: Vec::new(),
+        };
+
+        // Act
+        let local_peers = test_local_peers_of(&update, "10.0.0.9:19740", "");
+
+        // Assert
+        // Elevators announced by the relay itself are not on this segment, and are never sent back, and elevators of
+        // other groups are not relayed
+        let expected = HashMap::from([
+            ("a".to_string(), "10.0.0.1:19735".to_string()),
+            ("10.0.0.3:19735".to_string(), "10.0.0.3:19735".to_string()),
+        ]);
+        assert_eq!(local_peers, expected);
+    }
+
+    #[test]
+    fn test_relay_tells_messages_apart() {
+        // Arrange
+        let envelope = Envelope { origin: "a".to_string(), destination: "b".to_string(), message: GossipMessage::Pull, sequence: None, group: String::new() };
+        let forward = RelayMessage::Forward(Box::new(envelope.clone()));
+
+        // Act
+        let from_elevator = serde_json::from_str::<RelayMessage>(&serde_json::to_string(&envelope).unwrap());
+        let from_relay = serde_json::from_str::<RelayMessage>(&serde_json::to_string(&forward).unwrap());
+
+        // Assert
+        // Messages of the elevators never pass for messages of the other relay, which arrive on the same socket
+        assert!(from_elevator.is_err());
+        assert_eq!(from_relay.unwrap(), forward);
+    }
+}
@@ -0,0 +1,149 @@
+/**
+ * Selective retransmission of lost messages, asked for by the receiver.
+ *
+ * Every message to a peer is numbered in a sequence of its own for that peer, and the last `BUFFERED_MESSAGES`
+ * messages sent to it are kept. The receiver follows the sequence of every peer, and on a message past the next
+ * one expected, asks the sender for the missing ones with a `nack` message. The sender sends them again from its
+ * buffer with their numbers, and no message waits for another to be acknowledged, so a loss delays only the lost
+ * message, by a round trip. A lost `nack`, or a message that has left the buffer, is made up for by the gossip.
+ *
+ * Digests and `nack` messages are not numbered, as a lost digest is replaced by the next round. The sequences start
+ * at a random number, so the receiver takes the sequence of a restarted peer for a new one instead of a gap, as it
+ * does after a gap longer than the buffer. Messages come in the order they arrive, retransmitted ones late, and
+ * are all handled, since a message that was only delayed may come twice.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::network::encryption::Cipher;
+use crate::network::shaper::{Priority, Shaper};
+use crate::network::GossipMessage;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// Messages kept per peer for retransmission, and the longest gap asked for
+const BUFFERED_MESSAGES: usize = 32;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// The messages sent to every peer, numbered and kept for retransmission, the size, rate and encryption of the
+// datagrams to send them in, and the group of the fleet they are sent in
+pub struct Outbox {
+    max_datagram: usize,
+    streams: Mutex<HashMap<String, Stream>>,
+    shaper: Mutex<Shaper>,
+    cipher: Cipher,
+    group: String,
+}
+
+impl Outbox {
+    pub fn new(max_datagram: usize, max_packet_rate: u32, cipher: Cipher, group: String) -> Outbox {
+        Outbox {
+            max_datagram,
+            streams: Mutex::new(HashMap::new()),
+            shaper: Mutex::new(Shaper::new(max_packet_rate, Instant::now())),
+            cipher,
+            group,
+        }
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    // The largest datagram before it is sealed
+    pub fn max_datagram(&self) -> usize {
+        self.max_datagram.saturating_sub(self.cipher.overhead())
+    }
+
+    pub fn seal(&self, datagram: &[u8]) -> Vec<u8> {
+        self.cipher.seal(datagram)
+    }
+
+    // Whether the datagrams of a message fit in the rate budget, which they are then taken out of
+    pub fn admit(&self, message: &GossipMessage, packets: usize) -> bool {
+        self.shaper.lock().unwrap().admit(Priority::of(message), packets, Instant::now())
+    }
+
+    // Numbers a message to a peer, and keeps it for retransmission. `None` for messages that are not numbered.
+    pub fn record(&self, peer: &str, message: &GossipMessage) -> Option<u64> {
+        if matches!(message, GossipMessage::Digest(_) | GossipMessage::SyncDigest(_) | GossipMessage::Nack(_)) {
+            return None;
+        }
+
+        let mut streams = self.streams.lock().unwrap();
+        let stream = streams.entry(peer.to_string()).or_insert_with(|| Stream {
+            // Room below the largest number, so the sequence never wraps
+            next: RandomState::new().build_hasher().finish() >> 1,
+            sent: VecDeque::new(),
+        });
+        let sequence = stream.next;
+        stream.next += 1;
+        stream.sent.push_back((sequence, message.clone()));
+        if stream.sent.len() > BUFFERED_MESSAGES {
+            stream.sent.pop_front();
+        }
+        Some(sequence)
+    }
+
+    // The messages asked for by a peer that are still kept, with their numbers
+    pub fn retransmit(&self, peer: &str, sequences: &[u64]) -> Vec<(u64, GossipMessage)> {
+        let streams = self.streams.lock().unwrap();
+        let Some(stream) = streams.get(peer) else { return Vec::new() };
+        stream.sent.iter().filter(|(sequence, _)| sequences.contains(sequence)).cloned().collect()
+    }
+}
+
+// The next number expected in the sequence of every peer
+#[derive(Default)]
+pub struct Inbox {
+    expected: HashMap<String, u64>,
+}
+
+impl Inbox {
+    // Follows the sequence of a peer with a message received from it, and returns the numbers found missing
+    pub fn receive(&mut self, origin: &str, sequence: u64) -> Vec<u64> {
+        let next = sequence.saturating_add(1);
+        let Some(expected) = self.expected.get_mut(origin) else {
+            self.expected.insert(origin.to_string(), next);
+            return Vec::new();
+        };
+
+        let window = BUFFERED_MESSAGES as u64;
+        if sequence >= *expected {
+            // A gap longer than the sender keeps is a new sequence, as after a restart of the peer
+            let missing = match sequence - *expected <= window {
+                true => (*expected..sequence).collect(),
+                false => Vec::new(),
+            };
+            *expected = next;
+            missing
+        } else {
+            // A retransmitted or delayed message, unless it is too old to be, from a restarted peer
+            if *expected - sequence > window {
+                *expected = next;
+            }
+            Vec::new()
+        }
+    }
+}
+
+/***************************************/
+/*      Local data structures          */
+/***************************************/
+struct Stream {
+    next: u64,
+    sent: VecDeque<(u64, GossipMessage)>,
+}
@@ -0,0 +1,83 @@
+/**
+ * Limit on the rate of the datagrams sent, shared by all the messages of the node.
+ *
+ * The datagrams are sent within a budget of `max_packet_rate` per second, as a token bucket holding one second of
+ * the budget, so short bursts pass and a steady flood does not. The messages carrying changes to the orders go
+ * first: the periodic refreshes, the digests, the anti-entropy and the backups, are only sent while more than half
+ * of the bucket is left, so the orders always find the other half. A message over the budget is dropped, like a
+ * lost message, and made up for by the gossip, or asked for again by the receiver if it was numbered.
+ *
+ * A budget of 0 leaves the rate unlimited.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::time::Instant;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::network::GossipMessage;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    // Changes to the orders and the state, and the consensus
+    Order,
+    // Periodic messages, repeated if lost
+    Refresh,
+}
+
+impl Priority {
+    pub fn of(message: &GossipMessage) -> Priority {
+        match message {
+            GossipMessage::Digest(_)
+            | GossipMessage::SyncDigest(_)
+            | GossipMessage::Sync { .. }
+            | GossipMessage::Backup(_)
+            | GossipMessage::BackupRequest => Priority::Refresh,
+            _ => Priority::Order,
+        }
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Shaper {
+    budget: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Shaper {
+    pub fn new(max_packet_rate: u32, now: Instant) -> Shaper {
+        let budget = max_packet_rate as f64;
+        Shaper { budget, tokens: budget, refilled: now }
+    }
+
+    // Takes `packets` datagrams of a message of the priority out of the budget, or returns false if they do not fit
+    pub fn admit(&mut self, priority: Priority, packets: usize, now: Instant) -> bool {
+        if self.budget == 0.0 {
+            return true;
+        }
+
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.budget).min(self.budget);
+        self.refilled = now;
+
+        let reserve = match priority {
+            Priority::Order => 0.0,
+            Priority::Refresh => self.budget / 2.0,
+        };
+        let packets = packets as f64;
+        if self.tokens - packets < reserve {
+            return false;
+        }
+        self.tokens -= packets;
+        true
+    }
+}
@@ -0,0 +1,4 @@
+pub mod report;
+pub mod report_tests;
+
+pub use report::Reporter;
@@ -0,0 +1,329 @@
+/**
+ * Periodic reports of the service of the local elevator, to put real numbers on its quality.
+ *
+ * Every `poll_interval` milliseconds the snapshot of the coordinator is compared with the previous one, and the
+ * service in between is counted. Every hour on the hour, or every day at midnight, local time, the counts of the
+ * period are written to `directory` as `report-<id>-<start>.json` and as text in `report-<id>-<start>.txt`, and
+ * counted afresh:
+ *
+ * - `ordersServed`: calls cleared, the hall calls of the fleet and the cab calls of the local elevator. A hall call
+ *   cancelled is counted too. Every elevator counts the hall calls, so the reports of the fleet are not to be added.
+ * - `floors`: per floor, the calls cleared, and the mean and the 95th percentile of the latency from when each was
+ *   pressed until it was served, in seconds. Calls pressed before the reporter started have no latency, and the
+ *   latencies are only as exact as the poll interval.
+ * - `doorCycles`: times the door of the local elevator opened.
+ * - `errors`: times the local elevator entered the error state.
+ * - `peersLost`: peers lost.
+ * - `reassignments`: hall calls the local coordinator moved from one elevator to another.
+ * - `retransmissions`: messages the network sent again, as asked for by the peers that found them missing.
+ *
+ * After every report the oldest reports of the elevator in `directory` beyond the last `retention` are removed, so
+ * the reports do not fill the disk. With a retention of 0 all of them are kept.
+ *
+ * The first period ends at the first boundary, so it is shorter than the rest. With `push`, the JSON report is also
+ * logged at info level with the target `report`, and so shipped to the log collector, if one is configured.
+ *
+ * # Fields
+ * - `period`:                  How long each report covers.
+ * - `directory`:               The directory the reports are written to.
+ * - `poll_interval`:           Time between comparisons of the snapshot.
+ * - `retention`:               The number of reports kept, or 0 for all.
+ * - `push`:                    Whether the reports are logged, for the log collector.
+ * - `local_id`:                The ID of the local elevator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, Timelike};
+use crossbeam_channel as cbc;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::ReportConfig;
+use crate::shared::{fleet_events, DoorState, FleetEvent, ReportPeriod, Snapshot};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Reporter {
+    period: ReportPeriod,
+    directory: String,
+    poll_interval: Duration,
+    retention: usize,
+    push: bool,
+    local_id: String,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+}
+
+impl Reporter {
+    pub fn new(report_config: &ReportConfig, local_id: String, coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>) -> Reporter {
+        Reporter {
+            period: report_config.period,
+            directory: report_config.directory.clone(),
+            poll_interval: match report_config.poll_interval {
+                0 => DEFAULT_POLL_INTERVAL,
+                milliseconds => Duration::from_millis(milliseconds),
+            },
+            retention: report_config.retention,
+            push: report_config.push,
+            local_id,
+            coordinator_query_tx,
+        }
+    }
+
+    pub fn run(self) {
+        info!("Writing {:?} service reports to '{}'", self.period, self.directory);
+        let poll_ticker = cbc::tick(self.poll_interval);
+        let mut statistics = ServiceStatistics::default();
+        let mut previous: Option<Snapshot> = None;
+        let mut start = Local::now();
+        let mut end = period_end(self.period, start);
+
+        for _ in poll_ticker.iter() {
+            if let Some(snapshot) = self.query() {
+                statistics.observe(&self.local_id, previous.as_ref(), &snapshot, Instant::now());
+                previous = Some(snapshot);
+            }
+
+            let now = Local::now();
+            if now < end {
+                continue;
+            }
+            let report = statistics.report(&self.local_id, start, end);
+            if let Err(e) = write_report(&report, &self.directory, self.retention) {
+                warn!("Failed to write the service report: {}", e);
+            }
+            if self.push {
+                info!(target: "report", "{}", serde_json::to_string(&report).unwrap_or_default());
+            }
+            start = end;
+            end = period_end(self.period, now);
+        }
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
+
+// The service counted between two reports. The calls still pending carry over to the next period.
+#[derive(Default)]
+pub struct ServiceStatistics {
+    pressed: HashMap<(u8, u8), Instant>,
+    served: BTreeMap<u8, u64>,
+    latencies: BTreeMap<u8, Vec<Duration>>,
+    orders_served: u64,
+    door_cycles: u64,
+    errors: u64,
+    peers_lost: u64,
+    reassignments: u64,
+    retransmissions: u64,
+}
+
+impl ServiceStatistics {
+    // Counts the service between two snapshots, seen at `now`
+    pub fn observe(&mut self, local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot, now: Instant) {
+        for event in fleet_events(local_id, previous, snapshot) {
+            match event {
+                FleetEvent::Call(floor, call, true) => {
+                    self.pressed.insert((floor, call), now);
+                }
+                FleetEvent::Call(floor, call, false) => {
+                    self.orders_served += 1;
+                    *self.served.entry(floor).or_default() += 1;
+                    if let Some(pressed) = self.pressed.remove(&(floor, call)) {
+                        self.latencies.entry(floor).or_default().push(now.saturating_duration_since(pressed));
+                    }
+                }
+                FleetEvent::Fault(_, true) => self.errors += 1,
+                FleetEvent::PeerLost(_) => self.peers_lost += 1,
+                _ => {}
+            }
+        }
+
+        let Some(previous) = previous else { return };
+        let door = |snapshot: &Snapshot| snapshot.elevator_data.states.get(local_id).map(|state| state.door);
+        if door(previous) == Some(DoorState::Closed) && door(snapshot).is_some_and(|door| door != DoorState::Closed) {
+            self.door_cycles += 1;
+        }
+        // A counter that went back has started over, as the elevator restarted
+        let increase = |count: u64, previous_count: u64| count.checked_sub(previous_count).unwrap_or(count);
+        self.reassignments += increase(snapshot.reassignments, previous.reassignments);
+        self.retransmissions += increase(snapshot.network.messages_retransmitted, previous.network.messages_retransmitted);
+    }
+
+    // The report of the period from `start` to `end`. The counts start over for the next period.
+    pub fn report(&mut self, elevator: &str, start: DateTime<Local>, end: DateTime<Local>) -> ServiceReport {
+        let latencies = std::mem::take(&mut self.latencies);
+        let floors = std::mem::take(&mut self.served)
+            .into_iter()
+            .map(|(floor, served)| {
+                let mut latencies = latencies.get(&floor).cloned().unwrap_or_default();
+                latencies.sort();
+                FloorReport {
+                    floor,
+                    served,
+                    mean_latency: (!latencies.is_empty())
+                        .then(|| latencies.iter().sum::<Duration>().as_secs_f64() / latencies.len() as f64),
+                    p95_latency: percentile(&latencies, 95).map(|latency| latency.as_secs_f64()),
+                }
+            })
+            .collect();
+
+        let report = ServiceReport {
+            elevator: elevator.to_string(),
+            start: start.to_rfc3339(),
+            end: end.to_rfc3339(),
+            orders_served: self.orders_served,
+            floors,
+            door_cycles: self.door_cycles,
+            errors: self.errors,
+            peers_lost: self.peers_lost,
+            reassignments: self.reassignments,
+            retransmissions: self.retransmissions,
+        };
+        *self = ServiceStatistics { pressed: std::mem::take(&mut self.pressed), ..Default::default() };
+        report
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ServiceReport {
+    pub elevator: String,
+    pub start: String,
+    pub end: String,
+    #[serde(rename = "ordersServed")]
+    pub orders_served: u64,
+    pub floors: Vec<FloorReport>,
+    #[serde(rename = "doorCycles")]
+    pub door_cycles: u64,
+    pub errors: u64,
+    #[serde(rename = "peersLost")]
+    pub peers_lost: u64,
+    pub reassignments: u64,
+    pub retransmissions: u64,
+}
+
+// The calls cleared at a floor, and their latencies in seconds. None without a latency measured.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FloorReport {
+    pub floor: u8,
+    pub served: u64,
+    #[serde(rename = "meanLatency")]
+    pub mean_latency: Option<f64>,
+    #[serde(rename = "p95Latency")]
+    pub p95_latency: Option<f64>,
+}
+
+impl std::fmt::Display for ServiceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let seconds = |latency: Option<f64>| latency.map_or("-".to_string(), |latency| format!("{:.1} s", latency));
+        writeln!(f, "Service report of elevator {}", self.elevator)?;
+        writeln!(f, "From {} to {}", self.start, self.end)?;
+        writeln!(f)?;
+        writeln!(f, "Orders served:     {}", self.orders_served)?;
+        writeln!(f, "Door cycles:       {}", self.door_cycles)?;
+        writeln!(f, "Errors:            {}", self.errors)?;
+        writeln!(f, "Peers lost:        {}", self.peers_lost)?;
+        writeln!(f, "Reassignments:     {}", self.reassignments)?;
+        writeln!(f, "Retransmissions:   {}", self.retransmissions)?;
+        writeln!(f)?;
+        writeln!(f, "Floor  Served  Mean latency  95% latency")?;
+        for floor in &self.floors {
+            writeln!(f, "{:<5}  {:<6}  {:<12}  {}", floor.floor, floor.served, seconds(floor.mean_latency), seconds(floor.p95_latency))?;
+        }
+        Ok(())
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// When the period running at `time` ends: on the next hour, or the next midnight. A boundary skipped by a change to
+// daylight saving time is taken an hour later.
+pub fn period_end(period: ReportPeriod, time: DateTime<Local>) -> DateTime<Local> {
+    let start: NaiveDateTime = match period {
+        ReportPeriod::Hourly => time.date_naive().and_hms_opt(time.hour(), 0, 0).unwrap_or_default(),
+        ReportPeriod::Daily => time.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default(),
+    };
+    let end = match period {
+        ReportPeriod::Hourly => start + TimeDelta::hours(1),
+        ReportPeriod::Daily => start + TimeDelta::days(1),
+    };
+    end.and_local_timezone(Local)
+        .earliest()
+        .or_else(|| (end + TimeDelta::hours(1)).and_local_timezone(Local).earliest())
+        .unwrap_or(time + TimeDelta::hours(1))
+}
+
+// Writes the report as JSON and as text, named by the elevator and the start of the period, and removes the oldest
+// reports of the elevator beyond the last `retention`, unless 0
+pub fn write_report(report: &ServiceReport, directory: &str, retention: usize) -> Result<(), String> {
+    let start = DateTime::parse_from_rfc3339(&report.start).map_err(|e| e.to_string())?;
+    let name = format!("report-{}-{}", report.elevator, start.format("%Y%m%d-%H%M"));
+    let directory = Path::new(directory);
+    fs::create_dir_all(directory).map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+
+    let json = serde_json::to_string_pretty(report).map_err(|e| e.to_string())?;
+    for (extension, contents) in [("json", json), ("txt", report.to_string())] {
+        let path = directory.join(format!("{}.{}", name, extension));
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    match retention {
+        0 => Ok(()),
+        retention => prune_reports(directory, &report.elevator, retention),
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Removes the reports of the elevator but the last `retention` of each format. The start of the period in the name
+// orders them, and the files of other elevators or of other names are left alone.
+fn prune_reports(directory: &Path, elevator: &str, retention: usize) -> Result<(), String> {
+    let prefix = format!("report-{}-", elevator);
+    let entries = fs::read_dir(directory).map_err(|e| format!("Failed to read {}: {}", directory.display(), e))?;
+    let names = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect::<Vec<String>>();
+
+    for extension in ["json", "txt"] {
+        let mut reports = names
+            .iter()
+            .filter(|name| {
+                name.strip_prefix(&prefix)
+                    .and_then(|name| name.strip_suffix(&format!(".{}", extension)))
+                    .is_some_and(|start| NaiveDateTime::parse_from_str(start, "%Y%m%d-%H%M").is_ok())
+            })
+            .collect::<Vec<&String>>();
+        reports.sort();
+        for name in reports.iter().rev().skip(retention) {
+            let path = directory.join(name);
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// The nearest-rank percentile of the sorted latencies
+fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted.get(rank.max(1) - 1).copied()
+}
@@ -0,0 +1,194 @@
+/*
+ * Unit tests for the service reports
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_report_latencies
+ *  - test_report_counts
+ *  - test_report_period_end
+ *  - test_report_written
+ *  - test_report_retention
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod report_tests {
+    use crate::report::report::{period_end, write_report, ServiceStatistics};
+    use crate::shared::{Behaviour, DoorState, NetworkStatistics, ReportPeriod, Snapshot, Uptime};
+    use crate::{ElevatorData, ElevatorState};
+    use chrono::{Local, TimeZone, Timelike};
+    use driver_rust::elevio::elev::{CAB, HALL_UP};
+    use std::time::{Duration, Instant};
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string(), "b".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    // Presses the call, and clears it `latency` later
+    fn serve(statistics: &mut ServiceStatistics, floor: usize, call: u8, start: Instant, latency: Duration) {
+        let idle = snapshot();
+        let mut pressed = snapshot();
+        match call {
+            CAB => pressed.elevator_data.states.get_mut("a").unwrap().cab_requests[floor] = true,
+            call => pressed.elevator_data.hall_requests[floor][call as usize] = true,
+        }
+        statistics.observe("a", Some(&idle), &pressed, start);
+        statistics.observe("a", Some(&pressed), &idle, start + latency);
+    }
+
+    #[test]
+    fn test_report_latencies() {
+        // Arrange
+        let mut statistics = ServiceStatistics::default();
+        let start = Instant::now();
+        let time = Local::now();
+
+        // Act
+        for seconds in 1..=20 {
+            serve(&mut statistics, 2, HALL_UP, start, Duration::from_secs(seconds));
+        }
+        serve(&mut statistics, 0, CAB, start, Duration::from_secs(4));
+        let report = statistics.report("a", time, time);
+        let next = statistics.report("a", time, time);
+
+        // Assert
+        // The 95th percentile is the 19th of the 20 latencies, by nearest rank
+        assert_eq!(report.orders_served, 21);
+        assert_eq!(report.floors.len(), 2);
+        assert_eq!((report.floors[0].floor, report.floors[0].served), (0, 1));
+        assert_eq!(report.floors[0].mean_latency, Some(4.0));
+        assert_eq!((report.floors[1].floor, report.floors[1].served), (2, 20));
+        assert_eq!(report.floors[1].mean_latency, Some(10.5));
+        assert_eq!(report.floors[1].p95_latency, Some(19.0));
+        assert_eq!(next.orders_served, 0);
+        assert!(next.floors.is_empty());
+    }
+
+    #[test]
+    fn test_report_counts() {
+        // Arrange
+        let mut statistics = ServiceStatistics::default();
+        let now = Instant::now();
+        let time = Local::now();
+        let closed = snapshot();
+        let mut open = snapshot();
+        open.elevator_data.states.get_mut("a").unwrap().door = DoorState::Open;
+        let mut failed = open.clone();
+        failed.elevator_data.states.get_mut("a").unwrap().behaviour = Behaviour::Error;
+        failed.peers = vec!["a".to_string()];
+        failed.reassignments = 3;
+        failed.network.messages_retransmitted = 5;
+        let mut restarted = closed.clone();
+        restarted.reassignments = 2;
+        restarted.network.messages_retransmitted = 1;
+
+        // Act
+        statistics.observe("a", None, &closed, now);
+        statistics.observe("a", Some(&closed), &open, now);
+        statistics.observe("a", Some(&open), &failed, now);
+        statistics.observe("a", Some(&failed), &restarted, now);
+        statistics.observe("a", Some(&restarted), &open, now);
+        let report = statistics.report("a", time, time);
+
+        // Assert
+        // A counter going back is taken as started over from 0
+        assert_eq!(report.door_cycles, 2);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.peers_lost, 1);
+        assert_eq!(report.reassignments, 5);
+        assert_eq!(report.retransmissions, 6);
+        assert_eq!(report.orders_served, 0);
+    }
+
+    #[test]
+    fn test_report_period_end() {
+        // Arrange
+        let time = Local.with_ymd_and_hms(2026, 3, 10, 14, 25, 7).unwrap();
+
+        // Act
+        let hourly = period_end(ReportPeriod::Hourly, time);
+        let daily = period_end(ReportPeriod::Daily, time);
+
+        // Assert
+        assert_eq!(hourly, Local.with_ymd_and_hms(2026, 3, 10, 15, 0, 0).unwrap());
+        assert_eq!(daily, Local.with_ymd_and_hms(2026, 3, 11, 0, 0, 0).unwrap());
+        assert_eq!(period_end(ReportPeriod::Hourly, hourly).hour(), 16);
+    }
+
+    #[test]
+    fn test_report_written() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("service_report_{}", std::process::id()));
+        let mut statistics = ServiceStatistics::default();
+        serve(&mut statistics, 1, HALL_UP, Instant::now(), Duration::from_millis(2500));
+        let start = Local.with_ymd_and_hms(2026, 3, 10, 14, 0, 0).unwrap();
+        let report = statistics.report("a", start, period_end(ReportPeriod::Hourly, start));
+
+        // Act
+        let written = write_report(&report, directory.to_str().unwrap(), 0);
+        let json = std::fs::read_to_string(directory.join("report-a-20260310-1400.json"));
+        let text = std::fs::read_to_string(directory.join("report-a-20260310-1400.txt"));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        // Assert
+        assert_eq!(written, Ok(()));
+        let json: serde_json::Value = serde_json::from_str(&json.unwrap()).unwrap();
+        assert_eq!(json["ordersServed"], 1);
+        assert_eq!(json["floors"][0]["meanLatency"], 2.5);
+        let text = text.unwrap();
+        assert!(text.contains("Orders served:     1"), "{}", text);
+        assert!(text.contains("2.5 s"), "{}", text);
+    }
+
+    #[test]
+    fn test_report_retention() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("service_report_retention_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        // A report of another elevator, and a file of another name, share the directory
+        std::fs::write(directory.join("report-b-20260310-0900.json"), "{}").unwrap();
+        std::fs::write(directory.join("report-a-notes.json"), "{}").unwrap();
+        let mut statistics = ServiceStatistics::default();
+        let starts = (10..14).map(|hour| Local.with_ymd_and_hms(2026, 3, 10, hour, 0, 0).unwrap()).collect::<Vec<_>>();
+
+        // Act
+        let written = starts
+            .iter()
+            .map(|start| write_report(&statistics.report("a", *start, period_end(ReportPeriod::Hourly, *start)), directory.to_str().unwrap(), 2))
+            .collect::<Result<Vec<()>, String>>();
+        let mut names = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<String>>();
+        names.sort();
+        let _ = std::fs::remove_dir_all(&directory);
+
+        // Assert
+        // Only the last two reports of the elevator are kept, in both formats
+        assert!(written.is_ok(), "{:?}", written);
+        assert_eq!(names, vec![
+            "report-a-20260310-1200.json",
+            "report-a-20260310-1200.txt",
+            "report-a-20260310-1300.json",
+            "report-a-20260310-1300.txt",
+            "report-a-notes.json",
+            "report-b-20260310-0900.json",
+        ]);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod rpc;
+pub mod rpc_tests;
+
+pub use rpc::Rpc;
@@ -0,0 +1,263 @@
+/**
+ * JSON-RPC control socket for scripting.
+ *
+ * Serves JSON-RPC 2.0 on a TCP port and/or a Unix socket, one request per line and one response per line, so test
+ * scripts can drive the elevator with nothing but a socket and a JSON library. Each connection is served on a
+ * thread of its own and may send any number of requests. Notifications, i.e. requests without an ID, are not
+ * answered. The methods mirror the debug console:
+ *
+ * - `state`:     The snapshot of the coordinator.
+ * - `peers`:     The IDs of the elevators this elevator is connected to.
+ * - `inject`:    Places a call from `{"floor": 2, "call": "up"}`, where the call is `up`, `down` or `cab`.
+ * - `set-mode`:  Changes a mode from a console command, e.g. `{"command": "traffic up-peak"}`.
+ *
+ * Hall calls are cleared with `"clear": true`. Cab calls are placed at this elevator.
+ *
+ * # Fields
+ * - `port`:                    TCP port the socket listens on. 0 disables TCP.
+ * - `socket_path`:             Path of the Unix socket. Empty disables the Unix socket.
+ * - `coordinator_command_tx`:  Sends calls and mode changes to the coordinator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::{info, error};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+use std::thread::Builder;
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::RpcConfig;
+use crate::console::console::parse_mode_command;
+use crate::shared::{ControlCommand, Snapshot};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+// Error codes of the JSON-RPC 2.0 specification, and one for the coordinator not answering
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const COORDINATOR_UNAVAILABLE: i64 = -32000;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct InjectParams {
+    floor: u8,
+    call: String,
+    #[serde(default)]
+    clear: bool,
+}
+
+#[derive(Deserialize)]
+struct SetModeParams {
+    command: String,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Clone)]
+pub struct Rpc {
+    port: u16,
+    socket_path: String,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+}
+
+impl Rpc {
+    pub fn new(
+        rpc_config: &RpcConfig,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    ) -> Rpc {
+        Rpc {
+            port: rpc_config.port,
+            socket_path: rpc_config.socket_path.clone(),
+            coordinator_command_tx,
+            coordinator_query_tx,
+        }
+    }
+
+    // The elevator keeps running without the control socket
+    pub fn run(self) {
+        if !self.socket_path.is_empty() {
+            // A socket left behind by an earlier run would fail the bind
+            let _ = std::fs::remove_file(&self.socket_path);
+            match UnixListener::bind(&self.socket_path) {
+                Ok(listener) => {
+                    info!("JSON-RPC listening on {}", self.socket_path);
+                    let rpc = self.clone();
+                    let rpc_unix_thread = Builder::new().name("rpc_unix".into());
+                    rpc_unix_thread
+                        .spawn(move || {
+                            for stream in listener.incoming() {
+                                match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                                    Ok((reader, writer)) => rpc.spawn_connection(reader, writer),
+                                    Err(e) => error!("ERROR - JSON-RPC connection {:?}", e),
+                                }
+                            }
+                        })
+                        .unwrap();
+                }
+                Err(e) => error!("Failed to start JSON-RPC on {}: {:?}", self.socket_path, e),
+            }
+        }
+
+        if self.port != 0 {
+            let listener = match TcpListener::bind(("0.0.0.0", self.port)) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Failed to start JSON-RPC on port {}: {:?}", self.port, e);
+                    return;
+                }
+            };
+            info!("JSON-RPC listening on port {}", self.port);
+
+            for stream in listener.incoming() {
+                match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                    Ok((reader, writer)) => self.spawn_connection(reader, writer),
+                    Err(e) => error!("ERROR - JSON-RPC connection {:?}", e),
+                }
+            }
+        }
+    }
+
+    fn spawn_connection<S: Read + Write + Send + 'static>(&self, reader: S, writer: S) {
+        let rpc = self.clone();
+        let rpc_connection_thread = Builder::new().name("rpc_connection".into());
+        let spawned = rpc_connection_thread.spawn(move || {
+            if let Err(e) = rpc.serve(BufReader::new(reader), writer) {
+                info!("JSON-RPC connection closed: {:?}", e);
+            }
+        });
+        if let Err(e) = spawned {
+            error!("Failed to serve JSON-RPC connection: {:?}", e);
+        }
+    }
+
+    fn serve(&self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                writer.write_all(format!("{}\n", response).as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Answers a request line. None for notifications.
+    fn handle(&self, line: &str) -> Option<Value> {
+        let request = match serde_json::from_str::<Value>(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        let request = match serde_json::from_value::<RpcRequest>(request) {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            Ok(_) => return Some(error_response(Value::Null, INVALID_REQUEST, "Only JSON-RPC 2.0 is served")),
+            Err(e) => return Some(error_response(Value::Null, INVALID_REQUEST, &e.to_string())),
+        };
+
+        let result = self.call(&request.method, request.params);
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, (i64, String)> {
+        match method {
+            "state" => self.query().map(|snapshot| json!(snapshot)),
+            "peers" => self.query().map(|snapshot| json!(snapshot.peers)),
+            "links" => self.query().map(|snapshot| json!(snapshot.network.links)),
+            "inject" => {
+                let inject = serde_json::from_value::<InjectParams>(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+                let command = match (inject.call.as_str(), inject.clear) {
+                    ("up", clear) => ControlCommand::HallCall(inject.floor, HALL_UP, !clear),
+                    ("down", clear) => ControlCommand::HallCall(inject.floor, HALL_DOWN, !clear),
+                    ("cab", false) => ControlCommand::CabCall(inject.floor),
+                    ("cab", true) => return Err((INVALID_PARAMS, "Cab calls are cleared by serving them".to_string())),
+                    _ => return Err((INVALID_PARAMS, "The call is either up, down or cab".to_string())),
+                };
+                self.command(command)
+            }
+            "set-mode" => {
+                let set_mode = serde_json::from_value::<SetModeParams>(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+                let command = parse_mode_command(&set_mode.command).map_err(|message| (INVALID_PARAMS, message))?;
+                self.command(command)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        }
+    }
+
+    // Commands are handled asynchronously, so they are only accepted
+    fn command(&self, command: ControlCommand) -> Result<Value, (i64, String)> {
+        match self.coordinator_command_tx.send(command) {
+            Ok(()) => Ok(json!("accepted")),
+            Err(_) => Err((COORDINATOR_UNAVAILABLE, "The coordinator is not running".to_string())),
+        }
+    }
+
+    fn query(&self) -> Result<Snapshot, (i64, String)> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx
+            .send(reply_tx)
+            .ok()
+            .and_then(|_| reply_rx.recv_timeout(QUERY_TIMEOUT).ok())
+            .ok_or((COORDINATOR_UNAVAILABLE, "The coordinator did not answer".to_string()))
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "error": { "code": code, "message": message }, "id": id })
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::Rpc;
+    use serde_json::Value;
+    use std::io;
+
+    impl Rpc {
+        pub fn test_handle(&self, line: &str) -> Option<Value> {
+            self.handle(line)
+        }
+
+        pub fn test_serve(&self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+            self.serve(input, output)
+        }
+    }
+}
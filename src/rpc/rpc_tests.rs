@@ -0,0 +1,146 @@
+/*
+ * Unit tests for the JSON-RPC control socket
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * Requests are handled directly, without a socket, against mock coordinator channels.
+ *
+ * Tests:
+ *  - test_rpc_errors
+ *  - test_rpc_inject_and_set_mode
+ *  - test_rpc_state_peers_and_links
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod rpc_tests {
+    use crate::config::RpcConfig;
+    use crate::rpc::Rpc;
+    use crate::shared::{ControlCommand, NetworkStatistics, PeerLink, Snapshot, TrafficMode, Uptime};
+    use crate::{ElevatorData, ElevatorState};
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+    use std::thread::Builder;
+
+    fn setup_rpc() -> (Rpc, Receiver<ControlCommand>, Receiver<Sender<Snapshot>>) {
+        let (coordinator_command_tx, coordinator_command_rx) = unbounded::<ControlCommand>();
+        let (coordinator_query_tx, coordinator_query_rx) = unbounded::<Sender<Snapshot>>();
+        let rpc_config = RpcConfig { enabled: true, port: 0, socket_path: String::new() };
+        let rpc = Rpc::new(&rpc_config, coordinator_command_tx, coordinator_query_tx);
+        (rpc, coordinator_command_rx, coordinator_query_rx)
+    }
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("elevator".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "elevator".to_string(),
+            peers: vec!["elevator".to_string(), "other".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics {
+                links: BTreeMap::from([("other".to_string(), PeerLink { received: 95, missing: 5, loss: 0.05, round_trip: Some(12.5), clock_offset: Some(-1500.0) })]),
+                ..NetworkStatistics::default()
+            },
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    fn error_code(response: Option<Value>) -> i64 {
+        response.unwrap()["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn test_rpc_errors() {
+        // Arrange
+        let (rpc, coordinator_command_rx, _coordinator_query_rx) = setup_rpc();
+
+        // Act
+        let malformed = rpc.test_handle("{\"jsonrpc\": \"2.0\", ");
+        let old_version = rpc.test_handle(r#"{"jsonrpc": "1.0", "method": "state", "id": 1}"#);
+        let unknown = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "shutdown", "id": 2}"#);
+        let bad_params = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "inject", "params": {"floor": 1, "call": "sideways"}, "id": 3}"#);
+        let bad_mode = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "set-mode", "params": {"command": "door open"}, "id": 4}"#);
+        let notification = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "shutdown"}"#);
+
+        // Assert
+        // Errors carry the codes of the specification, and notifications are never answered
+        assert_eq!(error_code(malformed), -32700);
+        assert_eq!(error_code(old_version), -32600);
+        assert_eq!(error_code(unknown.clone()), -32601);
+        assert_eq!(unknown.unwrap()["id"], json!(2));
+        assert_eq!(error_code(bad_params), -32602);
+        assert_eq!(error_code(bad_mode), -32602);
+        assert_eq!(notification, None);
+        assert!(coordinator_command_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_rpc_inject_and_set_mode() {
+        // Arrange
+        let (rpc, coordinator_command_rx, _coordinator_query_rx) = setup_rpc();
+        let input = [
+            r#"{"jsonrpc": "2.0", "method": "inject", "params": {"floor": 2, "call": "up"}, "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "inject", "params": {"floor": 1, "call": "down", "clear": true}, "id": 2}"#,
+            "",
+            r#"{"jsonrpc": "2.0", "method": "inject", "params": {"floor": 3, "call": "cab"}}"#,
+            r#"{"jsonrpc": "2.0", "method": "set-mode", "params": {"command": "traffic up-peak"}, "id": "mode"}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+
+        // Act
+        rpc.test_serve(input.as_bytes(), &mut output).unwrap();
+
+        // Assert
+        // Every request with an ID is answered on a line of its own, in order
+        let responses = String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str::<Value>(line).unwrap()).collect::<Vec<Value>>();
+        assert_eq!(
+            responses,
+            vec![
+                json!({ "jsonrpc": "2.0", "result": "accepted", "id": 1 }),
+                json!({ "jsonrpc": "2.0", "result": "accepted", "id": 2 }),
+                json!({ "jsonrpc": "2.0", "result": "accepted", "id": "mode" }),
+            ]
+        );
+        assert_eq!(
+            coordinator_command_rx.try_iter().collect::<Vec<ControlCommand>>(),
+            vec![
+                ControlCommand::HallCall(2, HALL_UP, true),
+                ControlCommand::HallCall(1, HALL_DOWN, false),
+                ControlCommand::CabCall(3),
+                ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rpc_state_peers_and_links() {
+        // Arrange
+        let (rpc, _coordinator_command_rx, coordinator_query_rx) = setup_rpc();
+        let coordinator_thread = Builder::new().name("coordinator".into());
+        coordinator_thread
+            .spawn(move || {
+                for reply_tx in coordinator_query_rx.iter() {
+                    reply_tx.send(snapshot()).unwrap();
+                }
+            })
+            .unwrap();
+
+        // Act
+        let state = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "state", "id": 1}"#).unwrap();
+        let peers = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "peers", "id": 2}"#).unwrap();
+        let links = rpc.test_handle(r#"{"jsonrpc": "2.0", "method": "links", "id": 3}"#).unwrap();
+
+        // Assert
+        assert_eq!(serde_json::from_value::<Snapshot>(state["result"].clone()).unwrap(), snapshot());
+        assert_eq!(peers["result"], json!(["elevator", "other"]));
+        assert_eq!(links["result"], json!({"other": {"received": 95, "missing": 5, "loss": 0.05, "roundTrip": 12.5, "clockOffset": -1500.0}}));
+    }
+}
@@ -0,0 +1,124 @@
+/**
+ * Access control for restricted floors and zoned elevators.
+ *
+ * Restricted floors can only be served by elevators on the allow-list. Zoned elevators only serve
+ * the floors of their zone, while elevators without a zone serve every floor. Cab calls to a floor
+ * an elevator may not serve are refused by it, and hall calls are only assigned to elevators
+ * allowed to serve their floor.
+ *
+ * The zones, and the floors the zoned elevators park at, can be switched at runtime between named zoning profiles,
+ * such as an express profile for the lunch rush. Without a profile the zones of `[access]` apply.
+ *
+ * # Fields
+ * - `restricted_floors`:       Floors requiring authorization.
+ * - `authorized_elevators`:    IDs of the elevators allowed to serve the restricted floors.
+ * - `zoning`:                  The zones and parking floors in force.
+ * - `default_zoning`:          The zones and parking floors of `[access]`, in force without a profile.
+ * - `profiles`:                The zones and parking floors of every zoning profile, by name.
+ * - `profile`:                 The zoning profile in force, None for the zones of `[access]`.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::collections::HashMap;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::{AccessConfig, ZoneConfig};
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessControl {
+    restricted_floors: Vec<u8>,
+    authorized_elevators: Vec<String>,
+    zoning: Zoning,
+    default_zoning: Zoning,
+    profiles: HashMap<String, Zoning>,
+    profile: Option<String>,
+}
+
+impl AccessControl {
+    pub fn new(access_config: &AccessConfig) -> AccessControl {
+        let default_zoning = Zoning::new(&access_config.zones);
+        AccessControl {
+            restricted_floors: access_config.restricted_floors.clone(),
+            authorized_elevators: access_config.authorized_elevators.clone(),
+            zoning: default_zoning.clone(),
+            default_zoning,
+            profiles: access_config.profiles
+                .iter()
+                .map(|profile| (profile.name.clone(), Zoning::new(&profile.zones)))
+                .collect(),
+            profile: None,
+        }
+    }
+
+    // Puts a zoning profile in force, or the zones of `[access]` for None. An unknown profile changes nothing.
+    pub fn set_profile(&mut self, profile: Option<&str>) -> Result<(), String> {
+        self.zoning = match profile {
+            Some(name) => self.profiles.get(name).cloned().ok_or(format!("Unknown zoning profile '{}'", name))?,
+            None => self.default_zoning.clone(),
+        };
+        self.profile = profile.map(str::to_string);
+        Ok(())
+    }
+
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    // The floor the zoning parks the elevator at when idle, if any
+    pub fn parking_floor(&self, id: &str) -> Option<u8> {
+        self.zoning.parking_floors.get(id).copied()
+    }
+
+    pub fn is_restricted(&self, floor: u8) -> bool {
+        self.restricted_floors.contains(&floor)
+    }
+
+    pub fn is_authorized(&self, id: &str) -> bool {
+        self.authorized_elevators.iter().any(|authorized| authorized == id)
+    }
+
+    // Returns true if the floor is in the zone of the elevator, or the elevator has no zone
+    pub fn in_zone(&self, id: &str, floor: u8) -> bool {
+        self.zoning.zones.get(id).is_none_or(|floors| floors.contains(&floor))
+    }
+
+    // Returns true if the elevator is allowed to stop at the floor
+    pub fn may_serve(&self, id: &str, floor: u8) -> bool {
+        (!self.is_restricted(floor) || self.is_authorized(id)) && self.in_zone(id, floor)
+    }
+
+    // Floors the elevator is not allowed to stop at
+    pub fn blocked_floors(&self, id: &str, n_floors: u8) -> Vec<u8> {
+        (0..n_floors).filter(|floor| !self.may_serve(id, *floor)).collect()
+    }
+}
+
+/***************************************/
+/*       Local data structures         */
+/***************************************/
+// The floors served by each zoned elevator, and the floors the elevators with one park at, by ID
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Zoning {
+    zones: HashMap<String, Vec<u8>>,
+    parking_floors: HashMap<String, u8>,
+}
+
+impl Zoning {
+    fn new(zones: &[ZoneConfig]) -> Zoning {
+        Zoning {
+            zones: zones.iter().map(|zone| (zone.elevator.clone(), zone.floors.clone())).collect(),
+            parking_floors: zones.iter().filter_map(|zone| Some((zone.elevator.clone(), zone.parking_floor?))).collect(),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+/**
+ * Adaptation of the network and the coordinator to the loss and round-trip time measured by the network.
+ *
+ * The loss is taken as a severity, from none without loss to full at `loss_threshold`. The data sent is coalesced
+ * for up to `max_coalescing` at full severity, so fewer messages compete for the lossy network, and a peer may go
+ * unheard for up to `max_peer_timeout`, so a burst of lost beacons does not lose a peer that is still there and
+ * reassign its calls. The peer timeout is also kept above a few round trips, as beacons queue behind the data on a
+ * slow network.
+ *
+ * From a loss of `loss_threshold` on the coordinator serves conservatively: the hall calls pressed at the local
+ * panel are served by the local elevator as well as by the one they are assigned to, in case the other never hears
+ * of them. It serves normally again once the loss is below half the threshold, so it does not flap.
+ *
+ * # Fields
+ * - `enabled`:             Whether to adapt at all. Disabled, nothing is widened and the coordinator never conserves.
+ * - `loss_threshold`:      The loss at which the widening is full and the coordinator turns conservative.
+ * - `max_coalescing`:      The longest the data sent is held for more changes to send with it.
+ * - `max_peer_timeout`:    The longest a peer may go unheard before it is lost.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::AdaptationConfig;
+use crate::shared::NetworkQuality;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// The round trips a peer may go unheard for however low the loss
+const ROUND_TRIPS_PER_TIMEOUT: u32 = 4;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdaptationPolicy {
+    enabled: bool,
+    loss_threshold: f64,
+    max_coalescing: Duration,
+    max_peer_timeout: Duration,
+}
+
+impl AdaptationPolicy {
+    pub fn new(adaptation_config: &AdaptationConfig) -> AdaptationPolicy {
+        AdaptationPolicy {
+            enabled: adaptation_config.enabled,
+            loss_threshold: adaptation_config.loss_threshold,
+            max_coalescing: Duration::from_millis(adaptation_config.max_coalescing),
+            max_peer_timeout: Duration::from_millis(adaptation_config.max_peer_timeout),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // How long to hold the data sent for more changes
+    pub fn coalescing(&self, quality: &NetworkQuality) -> Duration {
+        self.max_coalescing.mul_f64(self.severity(quality))
+    }
+
+    // How long a peer may go unheard, widened from the configured `timeout`
+    pub fn peer_timeout(&self, quality: &NetworkQuality, timeout: Duration) -> Duration {
+        if !self.enabled {
+            return timeout;
+        }
+        let widened = timeout + self.max_peer_timeout.saturating_sub(timeout).mul_f64(self.severity(quality));
+        let round_trips = quality.round_trip.unwrap_or_default() * ROUND_TRIPS_PER_TIMEOUT;
+        widened.max(round_trips.min(self.max_peer_timeout))
+    }
+
+    // Whether the coordinator serves conservatively, given whether it does now
+    pub fn conservative(&self, conservative: bool, quality: &NetworkQuality) -> bool {
+        if !self.enabled || self.loss_threshold <= 0.0 {
+            return false;
+        }
+        match conservative {
+            false => quality.loss >= self.loss_threshold,
+            true => quality.loss >= self.loss_threshold / 2.0,
+        }
+    }
+
+    // From 0 without loss to 1 at the threshold and beyond
+    fn severity(&self, quality: &NetworkQuality) -> f64 {
+        if !self.enabled || self.loss_threshold <= 0.0 {
+            return 0.0;
+        }
+        (quality.loss / self.loss_threshold).clamp(0.0, 1.0)
+    }
+}
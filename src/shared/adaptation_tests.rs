@@ -0,0 +1,77 @@
+/*
+ * Unit tests for the adaptation policy
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_adaptation_policy_widening
+ *  - test_adaptation_policy_conservative
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod adaptation_tests {
+    use crate::config::AdaptationConfig;
+    use crate::shared::{AdaptationPolicy, NetworkQuality};
+    use std::time::Duration;
+
+    fn policy(enabled: bool) -> AdaptationPolicy {
+        AdaptationPolicy::new(&AdaptationConfig {
+            enabled,
+            interval: 1000,
+            loss_threshold: 0.2,
+            max_coalescing: 200,
+            max_peer_timeout: 4000,
+        })
+    }
+
+    fn quality(loss: f64, round_trip: Option<u64>) -> NetworkQuality {
+        NetworkQuality { loss, round_trip: round_trip.map(Duration::from_millis) }
+    }
+
+    #[test]
+    fn test_adaptation_policy_widening() {
+        // Arrange
+        let timeout = Duration::from_millis(2000);
+
+        // Act
+        let calm = (policy(true).coalescing(&quality(0.0, None)), policy(true).peer_timeout(&quality(0.0, None), timeout));
+        let lossy = (policy(true).coalescing(&quality(0.1, None)), policy(true).peer_timeout(&quality(0.1, None), timeout));
+        let lost = (policy(true).coalescing(&quality(0.9, None)), policy(true).peer_timeout(&quality(0.9, None), timeout));
+        let slow = policy(true).peer_timeout(&quality(0.0, Some(800)), timeout);
+        let disabled = (policy(false).coalescing(&quality(0.9, None)), policy(false).peer_timeout(&quality(0.9, Some(800)), timeout));
+
+        // Assert
+        // The widening grows with the loss up to the threshold, and no further
+        assert_eq!(calm, (Duration::ZERO, timeout));
+        assert_eq!(lossy, (Duration::from_millis(100), Duration::from_millis(3000)));
+        assert_eq!(lost, (Duration::from_millis(200), Duration::from_millis(4000)));
+        // A peer may go unheard for a few round trips, within the maximum
+        assert_eq!(slow, Duration::from_millis(3200));
+        assert_eq!(disabled, (Duration::ZERO, timeout));
+    }
+
+    #[test]
+    fn test_adaptation_policy_conservative() {
+        // Arrange
+        let policy = policy(true);
+
+        // Act
+        let below = policy.conservative(false, &quality(0.15, None));
+        let entered = policy.conservative(false, &quality(0.2, None));
+        let kept = policy.conservative(true, &quality(0.15, None));
+        let left = policy.conservative(true, &quality(0.05, None));
+        let disabled = self::policy(false).conservative(false, &quality(0.9, None));
+
+        // Assert
+        // Entered at the threshold, and left below half of it
+        assert!(!below);
+        assert!(entered);
+        assert!(kept);
+        assert!(!left);
+        assert!(!disabled);
+    }
+}
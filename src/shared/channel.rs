@@ -0,0 +1,131 @@
+/**
+ * Channels named after the modules they connect, bounded by the configuration, for tracing which channel a message
+ * came from.
+ *
+ * Every channel between the modules is made by `ChannelFactory::named`, with the name its two ends share, such as
+ * `fsm_hall_requests` for `fsm_hall_requests_tx` and `fsm_hall_requests_rx`, and the `OverflowPolicy` that suits
+ * what it carries. A channel holds up to the capacity of the `[channels]` configuration, so a stuck module no longer
+ * grows the queues of the others without limit. Under `Block`, the sender waits for room, so nothing sent is lost.
+ * Under `DropOldest`, the oldest message waiting is dropped to make room, and under `CoalesceLatest` only the latest
+ * message waits, so a state a module reads late is the latest and not one long replaced. The configuration may
+ * override the capacity and the policy of a channel by its name.
+ *
+ * A channel that drops or is traced is passed on by a relay thread of its own, which keeps the messages waiting, in
+ * order, and hands them to the receiver one at a time. The receiver finds the channel disconnected once the senders
+ * are dropped and the messages waiting are received, as without it. A sender only finds the channel disconnected
+ * once the relay has failed to pass on a message. Built with the `trace-channels` feature, every channel is relayed,
+ * and every message sent on it is logged at trace level under the target `channel`, prefixed by the name. Without
+ * it, a channel that blocks is a plain bounded channel, and the name is not kept.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::{ChannelConfig, ChannelsConfig};
+use crate::shared::OverflowPolicy;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const DEFAULT_CAPACITY: usize = 1024;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Makes the channels between the modules, with the capacities and policies of the configuration
+pub struct ChannelFactory {
+    capacity: usize,
+    overrides: HashMap<String, ChannelConfig>,
+}
+
+impl ChannelFactory {
+    pub fn new(channels_config: &ChannelsConfig) -> ChannelFactory {
+        ChannelFactory {
+            capacity: match channels_config.capacity {
+                0 => DEFAULT_CAPACITY,
+                capacity => capacity,
+            },
+            overrides: channels_config.overrides.clone(),
+        }
+    }
+
+    // A channel of the configured capacity, handling overflow by `policy` unless the configuration overrides it
+    pub fn named<T: Debug + Send + 'static>(&self, name: &'static str, policy: OverflowPolicy) -> (cbc::Sender<T>, cbc::Receiver<T>) {
+        let (capacity, policy) = match self.overrides.get(name) {
+            Some(channel_config) => (
+                match channel_config.capacity {
+                    0 => self.capacity,
+                    capacity => capacity,
+                },
+                channel_config.policy.unwrap_or(policy),
+            ),
+            None => (self.capacity, policy),
+        };
+        if policy == OverflowPolicy::Block && !cfg!(feature = "trace-channels") {
+            return cbc::bounded(capacity);
+        }
+
+        let (sender, relay_rx) = cbc::bounded::<T>(capacity);
+        let (relay_tx, receiver) = cbc::bounded::<T>(0);
+        let relay_thread = std::thread::Builder::new().name(format!("channel_{}", name));
+        relay_thread.spawn(move || relay(name, policy, capacity, relay_rx, relay_tx)).unwrap();
+        (sender, receiver)
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Keeps up to `capacity` messages waiting, handling overflow by `policy`, until the receiver takes them. Ends once
+// the senders are dropped and the messages are passed on, or the receiver is dropped.
+fn relay<T: Debug>(name: &'static str, policy: OverflowPolicy, capacity: usize, relay_rx: cbc::Receiver<T>, relay_tx: cbc::Sender<T>) {
+    let mut waiting = VecDeque::<T>::new();
+    let mut connected = true;
+    let mut dropping = false;
+    while connected || !waiting.is_empty() {
+        let mut select = cbc::Select::new();
+        let receiving = connected && (policy != OverflowPolicy::Block || waiting.len() < capacity);
+        let recv_index = receiving.then(|| select.recv(&relay_rx));
+        if !waiting.is_empty() {
+            select.send(&relay_tx);
+        }
+        let operation = select.select();
+
+        if Some(operation.index()) != recv_index {
+            if operation.send(&relay_tx, waiting.pop_front().unwrap()).is_err() {
+                return;
+            }
+            dropping = dropping && !waiting.is_empty();
+            continue;
+        }
+        let message = match operation.recv(&relay_rx) {
+            Ok(message) => message,
+            Err(_) => {
+                connected = false;
+                continue;
+            }
+        };
+        #[cfg(feature = "trace-channels")]
+        log::trace!(target: "channel", "{}: {:?}", name, message);
+        match policy {
+            OverflowPolicy::Block => {}
+            OverflowPolicy::DropOldest if waiting.len() >= capacity => {
+                if !dropping {
+                    log::warn!("Channel {} is full, dropping its oldest messages", name);
+                    dropping = true;
+                }
+                waiting.pop_front();
+            }
+            OverflowPolicy::DropOldest => {}
+            OverflowPolicy::CoalesceLatest => waiting.clear(),
+        }
+        waiting.push_back(message);
+    }
+}
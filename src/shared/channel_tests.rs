@@ -0,0 +1,84 @@
+/*
+ * Unit tests for the named channels
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_named_channel_order_and_disconnection
+ *  - test_drop_oldest_keeps_the_latest_messages
+ *  - test_override_coalesces_to_the_latest_message
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod channel_tests {
+    use crate::config::{ChannelConfig, ChannelsConfig};
+    use crate::shared::{ChannelFactory, OverflowPolicy};
+    use std::collections::HashMap;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_named_channel_order_and_disconnection() {
+        // Arrange
+        let channels = ChannelFactory::new(&ChannelsConfig::default());
+        let (sender, receiver) = channels.named::<u8>("test", OverflowPolicy::Block);
+
+        // Act
+        for message in 0..10 {
+            sender.send(message).unwrap();
+        }
+        drop(sender);
+        let received = (0..10).map(|_| receiver.recv_timeout(Duration::from_secs(1)).unwrap()).collect::<Vec<u8>>();
+
+        // Assert
+        // Traced or not, the messages arrive in order, and the channel is disconnected once the sender is dropped
+        assert_eq!(received, (0..10).collect::<Vec<u8>>());
+        assert!(receiver.recv_timeout(Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_the_latest_messages() {
+        // Arrange
+        let channels = ChannelFactory::new(&ChannelsConfig { capacity: 3, overrides: HashMap::new() });
+        let (sender, receiver) = channels.named::<u8>("test", OverflowPolicy::DropOldest);
+
+        // Act
+        for message in 0..10 {
+            sender.send(message).unwrap();
+        }
+        sleep(Duration::from_millis(100));
+        drop(sender);
+        let received = receiver.iter().collect::<Vec<u8>>();
+
+        // Assert
+        // The sender never waits on the receiver, and only the latest messages up to the capacity are kept
+        assert_eq!(received, vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_override_coalesces_to_the_latest_message() {
+        // Arrange
+        let overrides = HashMap::from([(
+            "test".to_string(),
+            ChannelConfig { capacity: 0, policy: Some(OverflowPolicy::CoalesceLatest) },
+        )]);
+        let channels = ChannelFactory::new(&ChannelsConfig { capacity: 3, overrides });
+        let (sender, receiver) = channels.named::<u8>("test", OverflowPolicy::Block);
+
+        // Act
+        for message in 0..10 {
+            sender.send(message).unwrap();
+        }
+        sleep(Duration::from_millis(100));
+        drop(sender);
+        let received = receiver.iter().collect::<Vec<u8>>();
+
+        // Assert
+        // The policy of the configuration replaces the one the channel was made with
+        assert_eq!(received, vec![9]);
+    }
+}
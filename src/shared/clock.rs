@@ -0,0 +1,118 @@
+/**
+ * Time as seen by the timers of the elevator.
+ *
+ * The door, motor and obstruction timers, the hall call cancel window, the reconciliation window, the consensus
+ * timeouts and the retries of ID generation all read the time from a `Clock`. The real clock follows the wall
+ * clock. The simulated clock runs at a multiple of real time, and can be stepped by hand, so scenarios lasting
+ * tens of seconds run in a fraction of that, and tests control exactly when a timer expires. With a speed of 0
+ * the simulated clock only moves when stepped.
+ *
+ * The lockout and traffic schedules read the time of day from the clock as well. The real clock reads it from the
+ * local wall clock, while the simulated clock starts at a set time of day and moves with the simulated time, so
+ * a schedule window can be entered and left by stepping the clock.
+ *
+ * # Fields
+ * - `origin`:                  The instant the simulated clock started at.
+ * - `speed`:                   Simulated time passing per unit of real time.
+ * - `stepped`:                 The time the clock has been stepped forward by hand.
+ * - `start_time`:              The time of day the simulated clock started at.
+ * - `advanced`:                Wakes sleepers when the clock is stepped.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::{Local, NaiveTime};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+    fn time_of_day(&self) -> NaiveTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn time_of_day(&self) -> NaiveTime {
+        Local::now().time()
+    }
+}
+
+pub struct SimulatedClock {
+    origin: Instant,
+    speed: f64,
+    stepped: Mutex<Duration>,
+    advanced: Condvar,
+    start_time: NaiveTime,
+}
+
+impl SimulatedClock {
+    pub fn new(speed: f64) -> SimulatedClock {
+        SimulatedClock {
+            origin: Instant::now(),
+            speed: speed.max(0.0),
+            stepped: Mutex::new(Duration::ZERO),
+            advanced: Condvar::new(),
+            start_time: Local::now().time(),
+        }
+    }
+
+    // Sets the time of day the clock starts at
+    pub fn start_at(mut self, time: NaiveTime) -> SimulatedClock {
+        self.start_time = time;
+        self
+    }
+
+    // Steps the clock forward, waking the sleepers whose time has come
+    pub fn advance(&self, duration: Duration) {
+        *self.stepped.lock().unwrap() += duration;
+        self.advanced.notify_all();
+    }
+
+    fn elapsed(&self, stepped: Duration) -> Duration {
+        stepped + self.origin.elapsed().mul_f64(self.speed)
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        let stepped = self.stepped.lock().unwrap();
+        self.origin + self.elapsed(*stepped)
+    }
+
+    fn time_of_day(&self) -> NaiveTime {
+        let elapsed = self.elapsed(*self.stepped.lock().unwrap());
+        let elapsed = chrono::Duration::from_std(elapsed).unwrap_or(chrono::Duration::zero());
+        self.start_time.overflowing_add_signed(elapsed).0
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut stepped = self.stepped.lock().unwrap();
+        let wake_up = self.elapsed(*stepped) + duration;
+        loop {
+            let elapsed = self.elapsed(*stepped);
+            if elapsed >= wake_up {
+                return;
+            }
+            stepped = if self.speed > 0.0 {
+                self.advanced.wait_timeout(stepped, (wake_up - elapsed).div_f64(self.speed)).unwrap().0
+            } else {
+                self.advanced.wait(stepped).unwrap()
+            };
+        }
+    }
+}
@@ -0,0 +1,78 @@
+/*
+ * Unit tests for the clocks
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_simulated_clock_stepping
+ *  - test_simulated_clock_speed
+ *  - test_simulated_clock_time_of_day
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod clock_tests {
+    use chrono::NaiveTime;
+    use std::sync::Arc;
+    use std::thread::spawn;
+    use std::time::{Duration, Instant};
+    use crate::shared::{Clock, SimulatedClock};
+
+    #[test]
+    fn test_simulated_clock_stepping() {
+        // Arrange
+        let clock = Arc::new(SimulatedClock::new(0.0));
+        let start = clock.now();
+        let sleeper_clock = clock.clone();
+        let sleeper = spawn(move || {
+            sleeper_clock.sleep(Duration::from_secs(30));
+            sleeper_clock.now()
+        });
+
+        // Act
+        std::thread::sleep(Duration::from_millis(50));
+        let standing = clock.now();
+        clock.advance(Duration::from_secs(10));
+        clock.advance(Duration::from_secs(20));
+        let woken = sleeper.join().unwrap();
+
+        // Assert
+        // Without speed, time only passes when stepped
+        assert_eq!(standing, start);
+        assert_eq!(woken - start, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_simulated_clock_speed() {
+        // Arrange
+        let clock = SimulatedClock::new(1000.0);
+        let start = clock.now();
+        let real_start = Instant::now();
+
+        // Act
+        clock.sleep(Duration::from_secs(10));
+
+        // Assert
+        // Ten simulated seconds pass in about ten real milliseconds
+        assert!(clock.now() - start >= Duration::from_secs(10));
+        assert!(real_start.elapsed() < Duration::from_secs(2), "Slept for {:?}", real_start.elapsed());
+    }
+
+    #[test]
+    fn test_simulated_clock_time_of_day() {
+        // Arrange
+        let clock = SimulatedClock::new(0.0).start_at(NaiveTime::from_hms_opt(23, 59, 0).unwrap());
+
+        // Act
+        let start = clock.time_of_day();
+        clock.advance(Duration::from_secs(90));
+
+        // Assert
+        // The time of day follows the simulated time, wrapping past midnight
+        assert_eq!(start, NaiveTime::from_hms_opt(23, 59, 0).unwrap());
+        assert_eq!(clock.time_of_day(), NaiveTime::from_hms_opt(0, 0, 30).unwrap());
+    }
+}
@@ -0,0 +1,379 @@
+/**
+ * Replicated hall-order log with a simplified Raft consensus.
+ *
+ * An elected leader sequences the hall order operations (adding and clearing calls), and the
+ * followers replicate its log. An operation is committed once a majority of the known elevators
+ * holds it, and committed operations are applied in the same order on every elevator.
+ * Followers forward operations to the leader, and keep them until a leader is known.
+ *
+ * The state machine does no I/O. Every call returns the messages to send, addressed by peer ID,
+ * and time is passed in by the caller.
+ *
+ * Simplifications compared to Raft: the membership follows the peer list instead of being agreed
+ * on, nothing is persisted, and the log is never compacted.
+ *
+ * # Fields
+ * - `id`:                  ID of the local elevator.
+ * - `peers`:               IDs of the other elevators.
+ * - `role`:                Follower, candidate or leader.
+ * - `term`:                The current term.
+ * - `voted_for`:           The candidate voted for in the current term, if any.
+ * - `leader`:              The leader of the current term, if known.
+ * - `log`:                 The replicated log.
+ * - `commit_len`:          Number of committed log entries.
+ * - `applied_len`:         Number of committed log entries handed out for applying.
+ * - `votes`:               Votes received as a candidate.
+ * - `next_len`:            For each follower, the log length the next append starts at. Used as leader.
+ * - `match_len`:           For each follower, the log length known to be replicated. Used as leader.
+ * - `pending`:             Operations waiting for a leader to be known.
+ * - `election_timeout`:    Time without a leader before starting an election. Randomized up to twice this.
+ * - `heartbeat_interval`:  Time between appends from the leader.
+ * - `election_deadline`:   When to start the next election.
+ * - `heartbeat_deadline`:  When the leader sends the next appends.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// Keeps appends within a datagram. Followers that are further behind catch up over several heartbeats.
+const MAX_ENTRIES_PER_APPEND: usize = 8;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HallOperation {
+    Add(u8, u8),
+    Clear(u8, u8),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub term: u64,
+    pub operation: HallOperation,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConsensusMessage {
+    RequestVote { term: u64, log_len: usize, last_log_term: u64 },
+    Vote { term: u64, granted: bool },
+    AppendEntries { term: u64, prev_len: usize, prev_term: u64, entries: Vec<LogEntry>, leader_commit: usize },
+    AppendResponse { term: u64, success: bool, match_len: usize },
+    Propose(HallOperation),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone)]
+pub struct Consensus {
+    id: String,
+    peers: Vec<String>,
+    role: Role,
+    term: u64,
+    voted_for: Option<String>,
+    leader: Option<String>,
+    log: Vec<LogEntry>,
+    commit_len: usize,
+    applied_len: usize,
+    votes: HashSet<String>,
+    next_len: HashMap<String, usize>,
+    match_len: HashMap<String, usize>,
+    pending: Vec<HallOperation>,
+    election_timeout: Duration,
+    heartbeat_interval: Duration,
+    election_deadline: Instant,
+    heartbeat_deadline: Instant,
+}
+
+impl Consensus {
+    pub fn new(id: String, election_timeout: Duration, heartbeat_interval: Duration, now: Instant) -> Consensus {
+        let mut consensus = Consensus {
+            id,
+            peers: Vec::new(),
+            role: Role::Follower,
+            term: 0,
+            voted_for: None,
+            leader: None,
+            log: Vec::new(),
+            commit_len: 0,
+            applied_len: 0,
+            votes: HashSet::new(),
+            next_len: HashMap::new(),
+            match_len: HashMap::new(),
+            pending: Vec::new(),
+            election_timeout,
+            heartbeat_interval,
+            election_deadline: now,
+            heartbeat_deadline: now,
+        };
+        consensus.reset_election_deadline(now);
+        consensus
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn leader(&self) -> Option<&String> {
+        self.leader.as_ref()
+    }
+
+    // The other elevators taking part. The local elevator is left out if present.
+    pub fn set_peers(&mut self, peers: Vec<String>) {
+        self.peers = peers.into_iter().filter(|peer| *peer != self.id).collect();
+        for peer in self.peers.iter() {
+            self.next_len.entry(peer.clone()).or_insert(self.log.len());
+            self.match_len.entry(peer.clone()).or_insert(0);
+        }
+        self.advance_commit();
+    }
+
+    // Starts elections and sends heartbeats when due
+    pub fn tick(&mut self, now: Instant) -> Vec<(String, ConsensusMessage)> {
+        match self.role {
+            Role::Leader => {
+                if self.heartbeat_deadline <= now {
+                    return self.append_to_all(now);
+                }
+                Vec::new()
+            }
+            Role::Follower | Role::Candidate => {
+                if self.election_deadline <= now {
+                    return self.start_election(now);
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    // Appends the operation as leader, or forwards it to the leader
+    pub fn propose(&mut self, operation: HallOperation, now: Instant) -> Vec<(String, ConsensusMessage)> {
+        match (self.role, self.leader.clone()) {
+            (Role::Leader, _) => {
+                self.log.push(LogEntry { term: self.term, operation });
+                self.advance_commit();
+                self.append_to_all(now)
+            }
+            (_, Some(leader)) => vec![(leader, ConsensusMessage::Propose(operation))],
+            (_, None) => {
+                self.pending.push(operation);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn handle_message(&mut self, from: &str, message: ConsensusMessage, now: Instant) -> Vec<(String, ConsensusMessage)> {
+        // Any message from a later term makes this elevator a follower in that term
+        let message_term = match &message {
+            ConsensusMessage::RequestVote { term, .. }
+            | ConsensusMessage::Vote { term, .. }
+            | ConsensusMessage::AppendEntries { term, .. }
+            | ConsensusMessage::AppendResponse { term, .. } => Some(*term),
+            ConsensusMessage::Propose(_) => None,
+        };
+        if let Some(term) = message_term {
+            if term > self.term {
+                self.term = term;
+                self.role = Role::Follower;
+                self.voted_for = None;
+                self.leader = None;
+            }
+        }
+
+        match message {
+            ConsensusMessage::RequestVote { term, log_len, last_log_term } => {
+                let up_to_date = last_log_term > self.last_log_term()
+                    || (last_log_term == self.last_log_term() && log_len >= self.log.len());
+                let granted = term == self.term
+                    && up_to_date
+                    && self.voted_for.as_deref().is_none_or(|candidate| candidate == from);
+                if granted {
+                    self.voted_for = Some(from.to_string());
+                    self.reset_election_deadline(now);
+                }
+                vec![(from.to_string(), ConsensusMessage::Vote { term: self.term, granted })]
+            }
+
+            ConsensusMessage::Vote { term, granted } => {
+                if self.role != Role::Candidate || term != self.term || !granted {
+                    return Vec::new();
+                }
+                self.votes.insert(from.to_string());
+                if self.votes.len() >= self.majority() {
+                    return self.become_leader(now);
+                }
+                Vec::new()
+            }
+
+            ConsensusMessage::AppendEntries { term, prev_len, prev_term, entries, leader_commit } => {
+                if term < self.term {
+                    return vec![(from.to_string(), ConsensusMessage::AppendResponse { term: self.term, success: false, match_len: 0 })];
+                }
+                self.role = Role::Follower;
+                self.leader = Some(from.to_string());
+                self.reset_election_deadline(now);
+
+                let consistent = self.log.len() >= prev_len && (prev_len == 0 || self.log[prev_len - 1].term == prev_term);
+                let mut messages = Vec::new();
+                if consistent {
+                    // Entries conflicting with the leader are replaced by the leader's
+                    let match_len = prev_len + entries.len();
+                    for (offset, entry) in entries.into_iter().enumerate() {
+                        let index = prev_len + offset;
+                        if index < self.log.len() && self.log[index].term != entry.term {
+                            self.log.truncate(index);
+                        }
+                        if index >= self.log.len() {
+                            self.log.push(entry);
+                        }
+                    }
+                    self.commit_len = self.commit_len.max(leader_commit.min(match_len));
+                    messages.push((from.to_string(), ConsensusMessage::AppendResponse { term: self.term, success: true, match_len }));
+                } else {
+                    // The length of the local log tells the leader where to continue from
+                    let match_len = self.log.len();
+                    messages.push((from.to_string(), ConsensusMessage::AppendResponse { term: self.term, success: false, match_len }));
+                }
+
+                // Operations waiting for a leader can now be forwarded
+                for operation in self.pending.drain(..) {
+                    messages.push((from.to_string(), ConsensusMessage::Propose(operation)));
+                }
+                messages
+            }
+
+            ConsensusMessage::AppendResponse { term, success, match_len } => {
+                if self.role != Role::Leader || term != self.term {
+                    return Vec::new();
+                }
+                if success {
+                    let known_match_len = self.match_len.entry(from.to_string()).or_insert(0);
+                    *known_match_len = (*known_match_len).max(match_len);
+                    self.next_len.insert(from.to_string(), match_len);
+                    self.advance_commit();
+                } else {
+                    // Step back until the logs agree, and retry on the next heartbeat
+                    let next_len = self.next_len.entry(from.to_string()).or_insert(0);
+                    *next_len = next_len.saturating_sub(1).min(match_len);
+                }
+                Vec::new()
+            }
+
+            ConsensusMessage::Propose(operation) => self.propose(operation, now),
+        }
+    }
+
+    // Committed operations not handed out before, in log order
+    pub fn take_committed(&mut self) -> Vec<HallOperation> {
+        let operations = self.log[self.applied_len..self.commit_len]
+            .iter()
+            .map(|entry| entry.operation)
+            .collect();
+        self.applied_len = self.commit_len;
+        operations
+    }
+
+    // More than half of the elevators, counting the local one
+    fn majority(&self) -> usize {
+        let n_elevators = self.peers.len() + 1;
+        n_elevators / 2 + 1
+    }
+
+    fn last_log_term(&self) -> u64 {
+        self.log.last().map_or(0, |entry| entry.term)
+    }
+
+    fn reset_election_deadline(&mut self, now: Instant) {
+        // Randomized, so elevators rarely start elections at the same time
+        let jitter = RandomState::new().build_hasher().finish() % (self.election_timeout.as_millis() as u64 + 1);
+        self.election_deadline = now + self.election_timeout + Duration::from_millis(jitter);
+    }
+
+    fn start_election(&mut self, now: Instant) -> Vec<(String, ConsensusMessage)> {
+        self.term += 1;
+        self.role = Role::Candidate;
+        self.voted_for = Some(self.id.clone());
+        self.leader = None;
+        self.votes = HashSet::from([self.id.clone()]);
+        self.reset_election_deadline(now);
+
+        if self.votes.len() >= self.majority() {
+            return self.become_leader(now);
+        }
+
+        let request = ConsensusMessage::RequestVote {
+            term: self.term,
+            log_len: self.log.len(),
+            last_log_term: self.last_log_term(),
+        };
+        self.peers.iter().map(|peer| (peer.clone(), request.clone())).collect()
+    }
+
+    fn become_leader(&mut self, now: Instant) -> Vec<(String, ConsensusMessage)> {
+        self.role = Role::Leader;
+        self.leader = Some(self.id.clone());
+        for peer in self.peers.iter() {
+            self.next_len.insert(peer.clone(), self.log.len());
+            self.match_len.insert(peer.clone(), 0);
+        }
+
+        // Operations proposed while there was no leader are sequenced first
+        for operation in std::mem::take(&mut self.pending) {
+            self.log.push(LogEntry { term: self.term, operation });
+        }
+        self.advance_commit();
+        self.append_to_all(now)
+    }
+
+    fn append_to_all(&mut self, now: Instant) -> Vec<(String, ConsensusMessage)> {
+        self.heartbeat_deadline = now + self.heartbeat_interval;
+        self.peers
+            .iter()
+            .map(|peer| {
+                let prev_len = self.next_len.get(peer).copied().unwrap_or(self.log.len()).min(self.log.len());
+                let prev_term = if prev_len == 0 { 0 } else { self.log[prev_len - 1].term };
+                let message = ConsensusMessage::AppendEntries {
+                    term: self.term,
+                    prev_len,
+                    prev_term,
+                    entries: self.log[prev_len..].iter().take(MAX_ENTRIES_PER_APPEND).cloned().collect(),
+                    leader_commit: self.commit_len,
+                };
+                (peer.clone(), message)
+            })
+            .collect()
+    }
+
+    // Commits the longest log prefix held by a majority. Only entries of the current term are counted, as in Raft.
+    fn advance_commit(&mut self) {
+        if self.role != Role::Leader {
+            return;
+        }
+        for len in (self.commit_len + 1..=self.log.len()).rev() {
+            let replicas = 1 + self.peers.iter().filter(|peer| self.match_len.get(*peer).copied().unwrap_or(0) >= len).count();
+            if self.log[len - 1].term == self.term && replicas >= self.majority() {
+                self.commit_len = len;
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ * Unit tests for the hall-order consensus
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * Messages are passed between the state machines directly, without a network.
+ *
+ * Tests:
+ *  - test_consensus_single_elevator_commits
+ *  - test_consensus_replicates_to_followers
+ *  - test_consensus_follower_forwards_proposal
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod consensus_tests {
+    use crate::shared::consensus::Role;
+    use crate::shared::{Consensus, ConsensusMessage, HallOperation};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    const ELECTION_TIMEOUT: Duration = Duration::from_millis(100);
+    const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(20);
+
+    // Elevators "a", "b" and "c", all knowing of each other
+    fn setup_cluster(now: Instant) -> HashMap<String, Consensus> {
+        let ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        ids.iter()
+            .map(|id| {
+                let mut consensus = Consensus::new(id.clone(), ELECTION_TIMEOUT, HEARTBEAT_INTERVAL, now);
+                consensus.set_peers(ids.clone());
+                (id.clone(), consensus)
+            })
+            .collect()
+    }
+
+    // Delivers messages, and the replies they cause, until there are none left
+    fn deliver(cluster: &mut HashMap<String, Consensus>, from: &str, messages: Vec<(String, ConsensusMessage)>, now: Instant) {
+        let mut queue: Vec<(String, String, ConsensusMessage)> =
+            messages.into_iter().map(|(to, message)| (from.to_string(), to, message)).collect();
+        while !queue.is_empty() {
+            let (from, to, message) = queue.remove(0);
+            let replies = cluster.get_mut(&to).unwrap().handle_message(&from, message, now);
+            queue.extend(replies.into_iter().map(|(next, reply)| (to.clone(), next, reply)));
+        }
+    }
+
+    #[test]
+    fn test_consensus_single_elevator_commits() {
+        // Arrange
+        let start = Instant::now();
+        let mut consensus = Consensus::new("a".to_string(), ELECTION_TIMEOUT, HEARTBEAT_INTERVAL, start);
+
+        // Act
+        let before_election = consensus.propose(HallOperation::Add(1, 0), start);
+        let messages = consensus.tick(start + 2 * ELECTION_TIMEOUT);
+
+        // Assert
+        // The operation waits for a leader, and alone the elevator elects itself and commits it
+        assert!(before_election.is_empty());
+        assert!(messages.is_empty());
+        assert_eq!(consensus.role(), Role::Leader);
+        assert_eq!(consensus.take_committed(), vec![HallOperation::Add(1, 0)]);
+        assert!(consensus.take_committed().is_empty());
+    }
+
+    #[test]
+    fn test_consensus_replicates_to_followers() {
+        // Arrange
+        let start = Instant::now();
+        let mut cluster = setup_cluster(start);
+        let now = start + 2 * ELECTION_TIMEOUT;
+        let requests = cluster.get_mut("a").unwrap().tick(now);
+        deliver(&mut cluster, "a", requests, now);
+
+        // Act
+        let appends = cluster.get_mut("a").unwrap().propose(HallOperation::Add(2, 1), now);
+        deliver(&mut cluster, "a", appends, now);
+        let appends = cluster.get_mut("a").unwrap().propose(HallOperation::Clear(2, 1), now);
+        deliver(&mut cluster, "a", appends, now);
+        let heartbeat = now + HEARTBEAT_INTERVAL;
+        let appends = cluster.get_mut("a").unwrap().tick(heartbeat);
+        deliver(&mut cluster, "a", appends, heartbeat);
+
+        // Assert
+        // The followers learn the commit with the next append, and apply the same history
+        assert_eq!(cluster["a"].role(), Role::Leader);
+        for id in ["a", "b", "c"] {
+            let consensus = cluster.get_mut(id).unwrap();
+            assert_eq!(consensus.leader(), Some(&"a".to_string()));
+            assert_eq!(consensus.take_committed(), vec![HallOperation::Add(2, 1), HallOperation::Clear(2, 1)]);
+        }
+    }
+
+    #[test]
+    fn test_consensus_follower_forwards_proposal() {
+        // Arrange
+        let start = Instant::now();
+        let mut cluster = setup_cluster(start);
+        let now = start + 2 * ELECTION_TIMEOUT;
+        let requests = cluster.get_mut("b").unwrap().tick(now);
+        deliver(&mut cluster, "b", requests, now);
+
+        // Act
+        let forwarded = cluster.get_mut("c").unwrap().propose(HallOperation::Add(0, 0), now);
+        let forwarded_to = forwarded.first().map(|(to, _)| to.clone());
+        deliver(&mut cluster, "c", forwarded, now);
+
+        // Assert
+        // The proposal goes to the leader, which commits it once a follower holds it
+        assert_eq!(forwarded_to, Some("b".to_string()));
+        assert_eq!(cluster.get_mut("b").unwrap().take_committed(), vec![HallOperation::Add(0, 0)]);
+    }
+}
@@ -0,0 +1,92 @@
+/**
+ * The event loop of the modules.
+ *
+ * The driver, the FSM and the coordinator each wait on many channels at once, and each must end the same way: on a
+ * request to terminate, it cleans up and acknowledges, and on a channel disconnected, it fails with the name of the
+ * channel, unless asked to terminate, as the channel then belongs to a module that terminated first. `event_loop!`
+ * writes that loop once. Each iteration runs the `before` block, if any, and selects over the channels, the
+ * `recv` arms handling the messages and failing the module on disconnection, and the `tick` arms handling the
+ * tickers and timers, which never disconnect. Without a message within `idle`, if given, its block runs instead.
+ * The request to terminate is selected on by every loop, and a failure met while handling an event, as taken by
+ * `EventLoop::take_failure`, stops the module once the event is handled.
+ *
+ * The name of the channel in the error is the field or variable its receiver is held in, such as
+ * `hw_floor_sensor_rx`.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{ModuleError, Terminate};
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// A module run by `event_loop!`: how it is asked to terminate, how it cleans up, and what stops it between events
+pub trait EventLoop {
+    fn terminate_rx(&self) -> &cbc::Receiver<Terminate>;
+
+    // Cleans up, and acknowledges the termination if asked for it
+    fn terminate(&mut self, done: Option<Terminate>) -> Result<(), ModuleError>;
+
+    // The failure met while handling the last event, if any
+    fn take_failure(&mut self) -> Option<ModuleError> {
+        None
+    }
+
+    // A channel disconnected while the module is asked to terminate belongs to a module that terminated first
+    fn disconnected(&mut self, channel: &str) -> Result<(), ModuleError> {
+        match self.terminate_rx().try_recv() {
+            Ok(done) => self.terminate(Some(done)),
+            Err(_) => Err(ModuleError::Disconnected(channel.to_string())),
+        }
+    }
+
+    // What the module returns on the failure met while handling the last event, if any
+    fn stop_on_failure(&mut self) -> Option<Result<(), ModuleError>> {
+        match self.take_failure()? {
+            ModuleError::Disconnected(channel) => Some(self.disconnected(&channel)),
+            failure => Some(Err(failure)),
+        }
+    }
+}
+
+// Runs the module, an `EventLoop`, until terminated or stopped by a failure, returning from the enclosing function
+macro_rules! event_loop {
+    (
+        $module:expr;
+        $(before => $before:block)?
+        $(recv($rx:expr) -> $message:pat => $handle:block)*
+        $(tick($ticker:expr) => $tick:block)*
+        $(idle($timeout:expr) => $idle:block)?
+    ) => {
+        loop {
+            $($before)?
+            crossbeam_channel::select! {
+                $(
+                    recv($rx) -> message => match message {
+                        Ok($message) => $handle,
+                        Err(_) => return $module.disconnected(stringify!($rx).rsplit('.').next().unwrap_or_default()),
+                    },
+                )*
+                $(
+                    recv($ticker) -> _ => $tick,
+                )*
+                recv($module.terminate_rx()) -> done => return $module.terminate(done.ok()),
+                $(
+                    default($timeout) => $idle,
+                )?
+            }
+            if let Some(stopped) = $module.stop_on_failure() {
+                return stopped;
+            }
+        }
+    };
+}
+
+pub(crate) use event_loop;
@@ -0,0 +1,143 @@
+/*
+ * Unit tests for the event loop of the modules
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_event_loop_terminates
+ *  - test_event_loop_disconnected
+ *  - test_event_loop_stops_on_failure
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod event_loop_tests {
+    use crate::shared::{event_loop, EventLoop, ModuleError, Terminate};
+    use crossbeam_channel::{self as cbc, unbounded};
+    use std::time::Duration;
+
+    // A module adding up the numbers it receives, failing the assignment of a zero
+    struct Adder {
+        number_rx: cbc::Receiver<u8>,
+        terminate_rx: cbc::Receiver<Terminate>,
+        sum: u32,
+        idle: bool,
+        terminated: bool,
+        failure: Option<ModuleError>,
+    }
+
+    impl Adder {
+        fn run(&mut self) -> Result<(), ModuleError> {
+            event_loop! {
+                self;
+                recv(self.number_rx) -> number => {
+                    match number {
+                        0 => self.failure = Some(ModuleError::Assigner("zero".to_string())),
+                        number => self.sum += number as u32,
+                    }
+                }
+                idle(Duration::from_millis(10)) => {
+                    self.idle = true;
+                }
+            }
+        }
+    }
+
+    impl EventLoop for Adder {
+        fn terminate_rx(&self) -> &cbc::Receiver<Terminate> {
+            &self.terminate_rx
+        }
+
+        fn terminate(&mut self, done: Option<Terminate>) -> Result<(), ModuleError> {
+            self.terminated = true;
+            if let Some(done) = done {
+                let _ = done.send(());
+            }
+            Ok(())
+        }
+
+        fn take_failure(&mut self) -> Option<ModuleError> {
+            self.failure.take()
+        }
+    }
+
+    fn adder(number_rx: cbc::Receiver<u8>, terminate_rx: cbc::Receiver<Terminate>) -> Adder {
+        Adder { number_rx, terminate_rx, sum: 0, idle: false, terminated: false, failure: None }
+    }
+
+    #[test]
+    fn test_event_loop_terminates() {
+        // Arrange
+        let (number_tx, number_rx) = unbounded::<u8>();
+        let (terminate_tx, terminate_rx) = unbounded::<Terminate>();
+        let (done_tx, done_rx) = unbounded::<()>();
+        let adder_thread = std::thread::spawn(move || {
+            let mut adder = adder(number_rx, terminate_rx);
+            let result = adder.run();
+            (adder, result)
+        });
+
+        // Act
+        number_tx.send(1).unwrap();
+        number_tx.send(2).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        terminate_tx.send(done_tx).unwrap();
+        let (adder, result) = adder_thread.join().unwrap();
+
+        // Assert
+        // The messages are handled, the idle block runs while none arrive, and the termination is acknowledged
+        assert_eq!(result, Ok(()));
+        assert_eq!(adder.sum, 3);
+        assert!(adder.idle && adder.terminated);
+        assert!(done_rx.try_recv().is_ok());
+        drop(number_tx);
+    }
+
+    #[test]
+    fn test_event_loop_disconnected() {
+        // Arrange
+        let (number_tx, number_rx) = unbounded::<u8>();
+        let (terminate_tx, terminate_rx) = unbounded::<Terminate>();
+        let mut disconnected = adder(number_rx, terminate_rx);
+        let (number_tx_2, number_rx_2) = unbounded::<u8>();
+        let (terminate_tx_2, terminate_rx_2) = unbounded::<Terminate>();
+        let mut terminating = adder(number_rx_2, terminate_rx_2);
+
+        // Act
+        drop(number_tx);
+        let disconnected_result = disconnected.run();
+        drop(number_tx_2);
+        terminate_tx_2.send(unbounded().0).unwrap();
+        let terminating_result = terminating.run();
+
+        // Assert
+        // A disconnected channel fails the module by its name, unless the module is asked to terminate
+        assert_eq!(disconnected_result, Err(ModuleError::Disconnected("number_rx".to_string())));
+        assert!(!disconnected.terminated);
+        assert_eq!(terminating_result, Ok(()));
+        assert!(terminating.terminated);
+        drop(terminate_tx);
+    }
+
+    #[test]
+    fn test_event_loop_stops_on_failure() {
+        // Arrange
+        let (number_tx, number_rx) = unbounded::<u8>();
+        let (_terminate_tx, terminate_rx) = unbounded::<Terminate>();
+        let mut adder = adder(number_rx, terminate_rx);
+
+        // Act
+        number_tx.send(4).unwrap();
+        number_tx.send(0).unwrap();
+        number_tx.send(5).unwrap();
+        let result = adder.run();
+
+        // Assert
+        // The failure stops the module once the event that met it is handled
+        assert_eq!(result, Err(ModuleError::Assigner("zero".to_string())));
+        assert_eq!(adder.sum, 4);
+    }
+}
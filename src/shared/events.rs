@@ -0,0 +1,91 @@
+/**
+ * Events of the fleet, derived from consecutive snapshots of the coordinator.
+ *
+ * Telemetry publishers poll the snapshot of the coordinator and report what changed in between, from the point of
+ * view of the local elevator: its own state, cab calls and faults, the hall calls of the fleet, and lost peers.
+ * Obstruction alarms are reported for every elevator of the fleet, as they need someone on site.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{Behaviour, ElevatorState, Snapshot};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, PartialEq)]
+pub enum FleetEvent {
+    // The state of the local elevator changed
+    State(ElevatorState),
+    // A hall call of the fleet or a cab call of the local elevator was placed (true) or cleared (false)
+    Call(u8, u8, bool),
+    // The local elevator entered (true) or left (false) the error state, at the given floor
+    Fault(u8, bool),
+    // A peer was lost
+    PeerLost(String),
+    // An elevator of the fleet raised (true) or cleared (false) the obstruction alarm, at the given floor
+    ObstructionAlarm(String, u8, bool),
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// The changes between two snapshots. Without a previous snapshot, only the state is reported.
+pub fn fleet_events(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot) -> Vec<FleetEvent> {
+    let mut events = Vec::new();
+    let state = snapshot.elevator_data.states.get(local_id);
+    let previous_state = previous.and_then(|previous| previous.elevator_data.states.get(local_id));
+
+    if let Some(state) = state {
+        if previous.is_none() || previous_state != Some(state) {
+            events.push(FleetEvent::State(state.clone()));
+        }
+    }
+
+    let Some(previous) = previous else { return events };
+
+    let hall_calls = snapshot.elevator_data.hall_requests.iter().zip(&previous.elevator_data.hall_requests);
+    for (floor, (requested, was_requested)) in hall_calls.enumerate() {
+        for call in [HALL_UP, HALL_DOWN] {
+            if requested[call as usize] != was_requested[call as usize] {
+                events.push(FleetEvent::Call(floor as u8, call, requested[call as usize]));
+            }
+        }
+    }
+
+    if let (Some(state), Some(previous_state)) = (state, previous_state) {
+        let cab_calls = state.cab_requests.iter().zip(&previous_state.cab_requests);
+        for (floor, (requested, was_requested)) in cab_calls.enumerate() {
+            if requested != was_requested {
+                events.push(FleetEvent::Call(floor as u8, CAB, *requested));
+            }
+        }
+
+        let failed = state.behaviour == Behaviour::Error;
+        if failed != (previous_state.behaviour == Behaviour::Error) {
+            events.push(FleetEvent::Fault(state.floor, failed));
+        }
+    }
+
+    let mut ids = snapshot.elevator_data.states.keys().collect::<Vec<&String>>();
+    ids.sort();
+    for id in ids {
+        let state = &snapshot.elevator_data.states[id];
+        let had_alarm = previous.elevator_data.states.get(id).is_some_and(|state| state.obstruction_alarm);
+        if state.obstruction_alarm != had_alarm {
+            events.push(FleetEvent::ObstructionAlarm(id.clone(), state.floor, state.obstruction_alarm));
+        }
+    }
+
+    for lost in previous.peers.iter().filter(|peer| !snapshot.peers.contains(peer)) {
+        events.push(FleetEvent::PeerLost(lost.clone()));
+    }
+
+    events
+}
@@ -0,0 +1,70 @@
+/*
+ * Unit tests for the fleet events
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_fleet_events
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod events_tests {
+    use crate::shared::{fleet_events, Behaviour, FleetEvent, NetworkStatistics, Snapshot, Uptime};
+    use crate::{ElevatorData, ElevatorState};
+    use driver_rust::elevio::elev::{CAB, HALL_UP};
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string(), "b".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    #[test]
+    fn test_fleet_events() {
+        // Arrange
+        let previous = snapshot();
+        let mut snapshot = snapshot();
+        snapshot.elevator_data.hall_requests[2][HALL_UP as usize] = true;
+        snapshot.elevator_data.states.get_mut("a").unwrap().cab_requests[3] = true;
+        snapshot.elevator_data.states.get_mut("a").unwrap().behaviour = Behaviour::Error;
+        snapshot.elevator_data.states.get_mut("b").unwrap().floor = 1;
+        snapshot.elevator_data.states.get_mut("b").unwrap().obstruction_alarm = true;
+        snapshot.peers.retain(|peer| peer != "b");
+
+        // Act
+        let first = fleet_events("a", None, &previous);
+        let unchanged = fleet_events("a", Some(&previous), &previous);
+        let changed = fleet_events("a", Some(&previous), &snapshot);
+
+        // Assert
+        // The first snapshot only reports the state, and nothing is reported without changes
+        assert_eq!(first, vec![FleetEvent::State(previous.elevator_data.states["a"].clone())]);
+        assert!(unchanged.is_empty());
+        // Only the local elevator is reported, along with the hall calls and obstruction alarms of the fleet
+        assert_eq!(
+            changed,
+            vec![
+                FleetEvent::State(snapshot.elevator_data.states["a"].clone()),
+                FleetEvent::Call(2, HALL_UP, true),
+                FleetEvent::Call(3, CAB, true),
+                FleetEvent::Fault(0, true),
+                FleetEvent::ObstructionAlarm("b".to_string(), 1, true),
+                FleetEvent::PeerLost("b".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+/**
+ * The labels of the floors, as the building names them.
+ *
+ * Inside the program the floors are numbered densely from 0 at the lowest, as the hardware numbers its floor
+ * sensors, buttons and indicator lamps, and as the rest of the configuration gives them. The `floor_labels` of
+ * `[hardware]` name them in the logs, the console, the fleet view and the HTTP API instead, such as `B1` and `G`
+ * below `1`, or `14` above `12` in a building without a 13th floor. Without labels, a floor is named by its number.
+ *
+ * A label is given to every floor, and to one floor only, and holds no whitespace, so a label typed at the console
+ * names one floor.
+ *
+ * # Fields
+ * - `labels`:                  The label of every floor, from the lowest. Empty to name the floors by number.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FloorLabels {
+    labels: Vec<String>,
+}
+
+impl FloorLabels {
+    // The labels of the `n_floors` floors. Fails unless every floor has a label of its own.
+    pub fn new(labels: &[String], n_floors: u8) -> Result<FloorLabels, String> {
+        if labels.is_empty() {
+            return Ok(FloorLabels::default());
+        }
+        if labels.len() != n_floors as usize {
+            return Err(format!("There are {} floor_labels for {} floors", labels.len(), n_floors));
+        }
+        if let Some(label) = labels.iter().find(|label| label.is_empty() || label.contains(char::is_whitespace)) {
+            return Err(format!("The floor label '{}' is empty or holds whitespace", label));
+        }
+        if let Some((_, label)) = labels.iter().enumerate().find(|(floor, label)| labels[..*floor].contains(label)) {
+            return Err(format!("The floor label '{}' is given to more than one floor", label));
+        }
+        Ok(FloorLabels { labels: labels.to_vec() })
+    }
+
+    // The label of a floor, its number if it has none
+    pub fn label(&self, floor: u8) -> String {
+        self.labels.get(floor as usize).cloned().unwrap_or_else(|| floor.to_string())
+    }
+
+    // The floor of a label, or of a number where the floors have no labels
+    pub fn floor(&self, label: &str) -> Option<u8> {
+        match self.labels.is_empty() {
+            true => label.parse::<u8>().ok(),
+            false => self.labels.iter().position(|known| known == label).map(|floor| floor as u8),
+        }
+    }
+
+    // The labels of the `n_floors` floors, from the lowest
+    pub fn labels(&self, n_floors: u8) -> Vec<String> {
+        (0..n_floors).map(|floor| self.label(floor)).collect()
+    }
+
+    // A call named by the label of its floor, such as `G up`
+    pub fn call(&self, floor: u8, call: u8) -> String {
+        let button = match call {
+            HALL_UP => "up",
+            HALL_DOWN => "down",
+            _ => "cab",
+        };
+        format!("{} {}", self.label(floor), button)
+    }
+}
@@ -0,0 +1,61 @@
+/*
+ * Unit tests for the labels of the floors
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_floors_labels
+ *  - test_floors_invalid_labels
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod floors_tests {
+    use crate::shared::FloorLabels;
+    use driver_rust::elevio::elev::{CAB, HALL_UP};
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn test_floors_labels() {
+        // Arrange
+        let skipping = FloorLabels::new(&labels(&["B1", "G", "12", "14"]), 4).unwrap();
+        let numbered = FloorLabels::new(&[], 4).unwrap();
+
+        // Act / Assert
+        // The floors are named by their labels, and found by them
+        assert_eq!(skipping.label(0), "B1");
+        assert_eq!(skipping.label(3), "14");
+        assert_eq!(skipping.floor("G"), Some(1));
+        assert_eq!(skipping.floor("14"), Some(3));
+        assert_eq!(skipping.floor("13"), None);
+        assert_eq!(skipping.floor("2"), None);
+        assert_eq!(skipping.call(1, HALL_UP), "G up");
+        assert_eq!(skipping.call(0, CAB), "B1 cab");
+        // Without labels the floors are named by number, and a floor beyond the building by its number
+        assert_eq!(numbered.labels(3), vec!["0", "1", "2"]);
+        assert_eq!(numbered.floor("2"), Some(2));
+        assert_eq!(numbered.floor("G"), None);
+        assert_eq!(skipping.label(7), "7");
+    }
+
+    #[test]
+    fn test_floors_invalid_labels() {
+        // Act / Assert
+        assert_eq!(FloorLabels::new(&labels(&["G", "1"]), 4), Err("There are 2 floor_labels for 4 floors".to_string()));
+        assert_eq!(
+            FloorLabels::new(&labels(&["G", "1", "1", "2"]), 4),
+            Err("The floor label '1' is given to more than one floor".to_string())
+        );
+        assert_eq!(
+            FloorLabels::new(&labels(&["G", "", "1", "2"]), 4),
+            Err("The floor label '' is empty or holds whitespace".to_string())
+        );
+        assert!(FloorLabels::new(&labels(&["G", "1 A", "2", "3"]), 4).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+/**
+ * The state of a hall call, as a cyclic counter.
+ *
+ * Every hall call goes around `None → Unconfirmed → Confirmed → Done`, and on to `Unconfirmed` again when pressed
+ * once more. The counter counts the steps taken, and its state is the step it is at in the round. A merge keeps the
+ * counter that is further along, so a call served on one elevator is not brought back by a packet from before it was
+ * served, however late the packet arrives, and a call pressed again after being served is not dropped for the older
+ * service. The counter wraps around: of two counters, the one ahead is the one reached by going less than half way
+ * around from the other, so the elevators must not drift half a range of steps apart.
+ *
+ * The hall calls form a state-based CRDT: the join of two orders is the one further along, which is the same whatever
+ * order the joins are made in, and however often a packet is joined. Partitions that each serve and press calls
+ * converge once their orders have all been joined, in any order, without a served call coming back. Within a step the
+ * state is the same on every side, so nothing is decided by which side is local.
+ *
+ * # Fields
+ * - `counter`:                 The steps taken by the call. Its state is the step modulo the four states.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const N_STATES: u16 = 4;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderState {
+    // Never pressed
+    None,
+    // Pressed, and waiting for a quorum of the elevators to acknowledge it
+    Unconfirmed,
+    // Acknowledged, lit and assigned
+    Confirmed,
+    // Served or cancelled
+    Done,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct HallOrder {
+    counter: u16,
+}
+
+impl HallOrder {
+    pub fn state(&self) -> OrderState {
+        match self.counter % N_STATES {
+            0 => OrderState::None,
+            1 => OrderState::Unconfirmed,
+            2 => OrderState::Confirmed,
+            _ => OrderState::Done,
+        }
+    }
+
+    // Whether the call waits to be served
+    pub fn is_requested(&self) -> bool {
+        matches!(self.state(), OrderState::Unconfirmed | OrderState::Confirmed)
+    }
+
+    // Steps on to the state of the call as held locally: requested or not, and confirmed or not. Returns whether it
+    // stepped.
+    pub fn advance(&mut self, requested: bool, confirmed: bool) -> bool {
+        let target = match (requested, confirmed) {
+            (false, _) if self.is_requested() => OrderState::Done,
+            (false, _) => return false,
+            (true, true) => OrderState::Confirmed,
+            (true, false) if self.state() == OrderState::Confirmed => return false,
+            (true, false) => OrderState::Unconfirmed,
+        };
+        let steps = (target as u16 + N_STATES - self.state() as u16) % N_STATES;
+        self.counter = self.counter.wrapping_add(steps);
+        steps > 0
+    }
+
+    // Keeps the order further along, of this one and the other. Returns whether it took the other.
+    pub fn join(&mut self, other: &HallOrder) -> bool {
+        let behind = self.precedence(other) == Ordering::Less;
+        if behind {
+            *self = *other;
+        }
+        behind
+    }
+
+    // How far along the call is compared with the other. Greater means further along.
+    pub fn precedence(&self, other: &HallOrder) -> Ordering {
+        match self.counter.wrapping_sub(other.counter) {
+            0 => Ordering::Equal,
+            steps if steps < u16::MAX / 2 => Ordering::Greater,
+            _ => Ordering::Less,
+        }
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// The hall calls held, as the orders say
+pub fn requested_hall_calls(orders: &[Vec<HallOrder>]) -> Vec<Vec<bool>> {
+    orders.iter().map(|orders| orders.iter().map(HallOrder::is_requested).collect()).collect()
+}
+
+// Keeps the order of every call that is further along, of the local orders and the remote ones. Missing orders, as
+// from peers without them, are never pressed.
+pub fn join_hall_orders(local: &mut Vec<Vec<HallOrder>>, remote: &[Vec<HallOrder>], n_floors: u8) {
+    local.resize(n_floors as usize, vec![HallOrder::default(); 2]);
+    for (local, remote) in local.iter_mut().zip(remote) {
+        for (local, remote) in local.iter_mut().zip(remote) {
+            local.join(remote);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Unit tests for the states of the hall calls
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_hall_order_cycle
+ *  - test_hall_order_precedence
+ *  - test_hall_order_partitions_converge
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod hall_order_tests {
+    use crate::shared::{join_hall_orders, HallOrder, OrderState};
+    use std::cmp::Ordering;
+
+    // The order after the steps given as held locally: requested and confirmed
+    fn order(steps: &[(bool, bool)]) -> HallOrder {
+        let mut order = HallOrder::default();
+        for (requested, confirmed) in steps {
+            order.advance(*requested, *confirmed);
+        }
+        order
+    }
+
+    #[test]
+    fn test_hall_order_cycle() {
+        // Arrange
+        let mut order = HallOrder::default();
+
+        // Act / Assert
+        assert_eq!(order.state(), OrderState::None);
+        assert!(order.advance(true, false));
+        assert_eq!(order.state(), OrderState::Unconfirmed);
+        assert!(!order.advance(true, false));
+        assert!(order.advance(true, true));
+        assert_eq!(order.state(), OrderState::Confirmed);
+        // A confirmed call stays confirmed while held, though the acknowledgements are not all seen locally
+        assert!(!order.advance(true, false));
+        assert!(order.is_requested());
+        assert!(order.advance(false, false));
+        assert_eq!(order.state(), OrderState::Done);
+        assert!(!order.advance(false, false));
+        // Pressed again, the call starts the next round
+        let pressed_again = order;
+        assert!(order.advance(true, true));
+        assert_eq!(order.state(), OrderState::Confirmed);
+        assert_eq!(order.precedence(&pressed_again), Ordering::Greater);
+        // A call served before it was confirmed is done all the same
+        assert_eq!(self::order(&[(true, false), (false, false)]).state(), OrderState::Done);
+    }
+
+    #[test]
+    fn test_hall_order_precedence() {
+        // Arrange
+        let confirmed = order(&[(true, true)]);
+        let served = order(&[(true, true), (false, false)]);
+        let mut served_often = HallOrder::default();
+        for _ in 0..u16::MAX / 4 + 1 {
+            served_often.advance(true, false);
+            served_often.advance(false, false);
+        }
+        let mut wrapped = served_often;
+        wrapped.advance(true, false);
+        let mut local = vec![vec![confirmed, served]];
+
+        // Act
+        join_hall_orders(&mut local, &[vec![served, confirmed]], 2);
+
+        // Assert
+        // A stale packet holding the call before it was served is behind
+        assert_eq!(served.precedence(&confirmed), Ordering::Greater);
+        assert_eq!(confirmed.precedence(&served), Ordering::Less);
+        assert_eq!(served.precedence(&served), Ordering::Equal);
+        // Past the end of the range the counter wraps around, and is still ahead
+        assert_eq!(wrapped, order(&[(true, false)]));
+        assert_eq!(wrapped.precedence(&served_often), Ordering::Greater);
+        // Every call keeps the state further along, and missing floors are never pressed
+        assert_eq!(local, vec![vec![served, served], vec![HallOrder::default(); 2]]);
+    }
+
+    #[test]
+    fn test_hall_order_partitions_converge() {
+        // Arrange
+        // Three partitions hold a confirmed call apart: one serves it, one does not, and one serves it and has it
+        // pressed again
+        let held = order(&[(true, true)]);
+        let served = order(&[(true, true), (false, false)]);
+        let pressed_again = order(&[(true, true), (false, false), (true, false)]);
+        let partitions = [
+            vec![vec![served, held]],
+            vec![vec![held, held]],
+            vec![vec![pressed_again, served]],
+        ];
+        let schedules = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
+        // Act
+        let joined = schedules
+            .iter()
+            .map(|schedule| {
+                let mut joined = Vec::new();
+                for partition in schedule.iter().chain(schedule) {
+                    join_hall_orders(&mut joined, &partitions[*partition], 1);
+                }
+                joined
+            })
+            .collect::<Vec<Vec<Vec<HallOrder>>>>();
+        let mut local = served;
+
+        // Assert
+        // Joined in any order, and any number of times, the partitions agree, and no served call comes back
+        for joined in joined {
+            assert_eq!(joined, vec![vec![pressed_again, served]]);
+        }
+        assert!(!local.join(&held));
+        assert_eq!(local, served);
+    }
+}
@@ -0,0 +1,106 @@
+/**
+ * The health monitor, telling the status of the elevator as a whole to its panel.
+ *
+ * Every `POLL_INTERVAL` the coordinator is asked for its snapshot, and the status of the system judged from it: in
+ * error while the local elevator is in the error state or has its obstruction alarm raised, in maintenance while it
+ * is in independent service or halted, offline while it reaches no peer or its network stands down in quarantine,
+ * and connected otherwise. A coordinator that does not answer within `QUERY_TIMEOUT` is hung or gone, and is shown as
+ * an error too. The status is sent to the driver whenever it changes, which blinks it on the stop lamp. See
+ * `PatternManager`.
+ *
+ * The answers of the coordinator also drive the notifications to systemd: readiness once it first answers, and the
+ * watchdog keepalives while it keeps answering. See `SystemdNotifier`.
+ *
+ * # Fields
+ * - `local_id`:                The ID of the local elevator.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ * - `hw_status_tx`:            Sends the status of the system to the driver.
+ * - `systemd`:                 Notifies systemd of readiness and keeps its watchdog alive.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::info;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{Behaviour, OperatingMode, Snapshot, SystemStatus, SystemdNotifier};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct HealthMonitor {
+    local_id: String,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    hw_status_tx: cbc::Sender<SystemStatus>,
+    systemd: SystemdNotifier,
+}
+
+impl HealthMonitor {
+    pub fn new(
+        local_id: String,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+        hw_status_tx: cbc::Sender<SystemStatus>,
+        systemd: SystemdNotifier,
+    ) -> HealthMonitor {
+        HealthMonitor { local_id, coordinator_query_tx, hw_status_tx, systemd }
+    }
+
+    // Sends the status whenever it changes, until the driver is gone
+    pub fn run(mut self) {
+        let mut shown: Option<SystemStatus> = None;
+        for _ in cbc::tick(POLL_INTERVAL).iter() {
+            let status = match self.query() {
+                Some(snapshot) => {
+                    self.systemd.ready();
+                    self.systemd.keepalive(Instant::now());
+                    system_status(&self.local_id, &snapshot)
+                }
+                None => SystemStatus::Error,
+            };
+            if shown == Some(status) {
+                continue;
+            }
+
+            info!("System status: {:?}", status);
+            if self.hw_status_tx.send(status).is_err() {
+                return;
+            }
+            shown = Some(status);
+        }
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// The status of the system in the snapshot. Faults are shown over maintenance, and both over the network.
+pub fn system_status(local_id: &str, snapshot: &Snapshot) -> SystemStatus {
+    let state = snapshot.elevator_data.states.get(local_id);
+    if state.is_some_and(|state| state.behaviour == Behaviour::Error || state.obstruction_alarm) {
+        return SystemStatus::Error;
+    }
+    if state.is_some_and(|state| state.mode == OperatingMode::Independent || state.halted) {
+        return SystemStatus::Maintenance;
+    }
+    match snapshot.network.quarantined || snapshot.peers.iter().all(|peer| peer == local_id) {
+        true => SystemStatus::Offline,
+        false => SystemStatus::Connected,
+    }
+}
@@ -0,0 +1,92 @@
+/*
+ * Unit tests for the health monitor
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_health_system_status
+ *  - test_health_unanswered_query
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod health_tests {
+    use crate::shared::health::system_status;
+    use crate::shared::{Behaviour, HealthMonitor, NetworkStatistics, OperatingMode, Snapshot, SystemStatus, SystemdNotifier, Uptime};
+    use crate::{ElevatorData, ElevatorState};
+    use crossbeam_channel::{unbounded, Sender};
+    use std::time::Duration;
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string(), "b".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    #[test]
+    fn test_health_system_status() {
+        // Arrange
+        let connected = snapshot();
+        let mut alone = snapshot();
+        alone.peers = vec!["a".to_string()];
+        let mut quarantined = snapshot();
+        quarantined.network.quarantined = true;
+        let mut failed = alone.clone();
+        failed.elevator_data.states.get_mut("a").unwrap().behaviour = Behaviour::Error;
+        let mut alarm = snapshot();
+        alarm.elevator_data.states.get_mut("a").unwrap().obstruction_alarm = true;
+        let mut independent = alone.clone();
+        independent.elevator_data.states.get_mut("a").unwrap().mode = OperatingMode::Independent;
+        let mut halted = snapshot();
+        halted.elevator_data.states.get_mut("a").unwrap().halted = true;
+        let mut peer_failed = snapshot();
+        peer_failed.elevator_data.states.get_mut("b").unwrap().behaviour = Behaviour::Error;
+
+        // Act / Assert
+        assert_eq!(system_status("a", &connected), SystemStatus::Connected);
+        assert_eq!(system_status("a", &alone), SystemStatus::Offline);
+        assert_eq!(system_status("a", &quarantined), SystemStatus::Offline);
+        // Faults are shown over maintenance, and both over the network
+        assert_eq!(system_status("a", &failed), SystemStatus::Error);
+        assert_eq!(system_status("a", &alarm), SystemStatus::Error);
+        assert_eq!(system_status("a", &independent), SystemStatus::Maintenance);
+        assert_eq!(system_status("a", &halted), SystemStatus::Maintenance);
+        // Only the local elevator counts
+        assert_eq!(system_status("a", &peer_failed), SystemStatus::Connected);
+    }
+
+    #[test]
+    fn test_health_unanswered_query() {
+        // Arrange
+        let (coordinator_query_tx, coordinator_query_rx) = unbounded::<Sender<Snapshot>>();
+        let (hw_status_tx, hw_status_rx) = unbounded::<SystemStatus>();
+        let monitor = HealthMonitor::new("a".to_string(), coordinator_query_tx, hw_status_tx, SystemdNotifier::disabled());
+        std::thread::spawn(move || monitor.run());
+
+        // Act
+        let answered = coordinator_query_rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        answered.send(snapshot()).unwrap();
+        let connected = hw_status_rx.recv_timeout(Duration::from_secs(2));
+        let unanswered = coordinator_query_rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        let hung = hw_status_rx.recv_timeout(Duration::from_secs(3));
+        drop((unanswered, hw_status_rx));
+
+        // Assert
+        // A coordinator that does not answer is shown as an error
+        assert_eq!(connected, Ok(SystemStatus::Connected));
+        assert_eq!(hung, Ok(SystemStatus::Error));
+    }
+}
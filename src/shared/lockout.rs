@@ -0,0 +1,59 @@
+/**
+ * Time-based floor lockout schedule.
+ *
+ * Floors can be closed during daily time windows, e.g. floors 5-8 between 22:00 and 06:00.
+ * The schedule is evaluated against a time of day passed in by the caller, so it can be
+ * driven by any clock.
+ *
+ * # Fields
+ * - `windows`:     The configured lockout windows.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::NaiveTime;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::LockoutConfig;
+use crate::shared::schedule::TimeWindow;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockoutWindow {
+    pub floors: Vec<u8>,
+    pub window: TimeWindow,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LockoutSchedule {
+    windows: Vec<LockoutWindow>,
+}
+
+impl LockoutSchedule {
+    pub fn new(lockout_config: &[LockoutConfig]) -> LockoutSchedule {
+        let windows = lockout_config
+            .iter()
+            .map(|lockout| LockoutWindow {
+                floors: lockout.floors.clone(),
+                window: TimeWindow::parse(&lockout.start, &lockout.end),
+            })
+            .collect();
+
+        LockoutSchedule { windows }
+    }
+
+    // Returns true if the floor is closed at the given time of day
+    pub fn is_locked(&self, floor: u8, time: NaiveTime) -> bool {
+        self.windows
+            .iter()
+            .any(|lockout| lockout.floors.contains(&floor) && lockout.window.is_active(time))
+    }
+}
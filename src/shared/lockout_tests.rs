@@ -0,0 +1,71 @@
+/*
+ * Unit tests for the lockout schedule
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_lockout_window_same_day
+ *  - test_lockout_window_wraps_midnight
+ *  - test_lockout_schedule_floors
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod lockout_tests {
+    use crate::config::LockoutConfig;
+    use crate::shared::LockoutSchedule;
+    use chrono::NaiveTime;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn schedule(floors: Vec<u8>, start: &str, end: &str) -> LockoutSchedule {
+        LockoutSchedule::new(&[LockoutConfig {
+            floors,
+            start: start.to_string(),
+            end: end.to_string(),
+        }])
+    }
+
+    #[test]
+    fn test_lockout_window_same_day() {
+        // Arrange
+        let schedule = schedule(vec![1], "08:00", "16:30");
+
+        // Act / Assert
+        assert!(!schedule.is_locked(1, time(7, 59)));
+        assert!(schedule.is_locked(1, time(8, 0)));
+        assert!(schedule.is_locked(1, time(16, 29)));
+        assert!(!schedule.is_locked(1, time(16, 30)));
+    }
+
+    #[test]
+    fn test_lockout_window_wraps_midnight() {
+        // Arrange
+        let schedule = schedule(vec![2], "22:00", "06:00");
+
+        // Act / Assert
+        assert!(!schedule.is_locked(2, time(21, 59)));
+        assert!(schedule.is_locked(2, time(22, 0)));
+        assert!(schedule.is_locked(2, time(0, 0)));
+        assert!(schedule.is_locked(2, time(5, 59)));
+        assert!(!schedule.is_locked(2, time(6, 0)));
+        assert!(!schedule.is_locked(2, time(12, 0)));
+    }
+
+    #[test]
+    fn test_lockout_schedule_floors() {
+        // Arrange
+        let schedule = schedule(vec![1, 3], "00:00", "12:00");
+
+        // Act / Assert
+        assert!(schedule.is_locked(1, time(6, 0)));
+        assert!(!schedule.is_locked(2, time(6, 0)));
+        assert!(schedule.is_locked(3, time(6, 0)));
+        assert!(!LockoutSchedule::default().is_locked(1, time(6, 0)));
+    }
+}
@@ -1,6 +1,104 @@
+pub mod access;
+pub mod adaptation;
+pub mod adaptation_tests;
+pub mod channel;
+pub mod channel_tests;
+pub mod clock;
+pub mod clock_tests;
+pub mod consensus;
+pub mod consensus_tests;
+pub mod event_loop;
+pub mod event_loop_tests;
+pub mod events;
+pub mod events_tests;
+pub mod floors;
+pub mod floors_tests;
+pub mod hall_order;
+pub mod hall_order_tests;
+pub mod health;
+pub mod health_tests;
+pub mod lockout;
+pub mod lockout_tests;
+pub mod motion;
+pub mod motion_tests;
+pub mod reload;
+pub mod reload_tests;
+pub mod restart;
+pub mod restart_tests;
+pub mod repositioning;
+pub mod repositioning_tests;
+pub mod schedule;
+pub mod scheduling;
+pub mod scheduling_tests;
+pub mod shutdown;
+pub mod shutdown_tests;
+pub mod startup;
+pub mod startup_tests;
 pub mod structs;
+pub mod systemd;
+pub mod systemd_tests;
+pub mod traffic;
+pub mod traffic_tests;
+pub mod uptime;
+pub mod uptime_tests;
+pub mod vector_clock;
+pub mod vector_clock_tests;
+pub mod wiring;
 
+pub use access::AccessControl;
+pub use adaptation::AdaptationPolicy;
+pub use channel::ChannelFactory;
+pub use clock::{Clock, RealClock, SimulatedClock};
+pub use consensus::{Consensus, ConsensusMessage, HallOperation};
+pub use event_loop::EventLoop;
+pub use floors::FloorLabels;
+pub use hall_order::{join_hall_orders, requested_hall_calls, HallOrder, OrderState};
+pub(crate) use event_loop::event_loop;
+pub use health::HealthMonitor;
+pub use lockout::LockoutSchedule;
+pub use motion::MotionModel;
+pub use reload::{ConfigReloader, ConfigUpdate};
+pub use repositioning::RepositioningPolicy;
+pub use restart::{exit, exit_on_panic, supervise, Restarter, RestartDecision};
+pub use scheduling::schedule_thread;
+pub use shutdown::{terminate_modules, Terminate, Termination};
+pub use startup::{await_answer, await_ready};
+pub use structs::AssignmentModel;
 pub use structs::Behaviour;
+pub use structs::ControlCommand;
 pub use structs::Direction;
+pub use structs::DoorState;
+pub use events::{fleet_events, FleetEvent};
 pub use structs::ElevatorData;
 pub use structs::ElevatorState;
+pub use structs::ExitReason;
+pub use structs::Encryption;
+pub use structs::HallAssignment;
+pub use structs::HallCallConfirmation;
+pub use structs::HallClearing;
+pub use structs::HallRequestAssigner;
+pub use structs::ModuleError;
+pub use structs::NetworkQuality;
+pub use structs::NetworkStatistics;
+pub use structs::PeerLink;
+pub use structs::OperatingMode;
+pub use structs::OverflowPolicy;
+pub use structs::PeerDiscovery;
+pub use structs::PeerVersion;
+pub use structs::ReportPeriod;
+pub use structs::RestartPolicy;
+pub use structs::Snapshot;
+pub use structs::StorageBackend;
+pub use structs::StateExport;
+pub use structs::SystemStatus;
+pub use structs::Uptime;
+pub use structs::VersionPolicy;
+pub use structs::SCHEMA_VERSION;
+pub use structs::FsmTimings;
+pub use structs::FsmView;
+pub use structs::TrafficMode;
+pub use systemd::SystemdNotifier;
+pub use traffic::TrafficPolicy;
+pub use uptime::UptimeCounter;
+pub use vector_clock::{Causality, VectorClock};
+pub use wiring::attached;
@@ -0,0 +1,97 @@
+/**
+ * Travel-time model of the building.
+ *
+ * The car accelerates to its cruise speed in `acceleration_time`, and slows to a stop in as long. Trips too short
+ * to reach the cruise speed are spent speeding up and slowing down. The same model gives the FSM its arrival
+ * estimates and its motor timeout, so what is expected of the car is stated once.
+ *
+ * A model without a floor travel time is unset. It estimates nothing, and the configured motor timeout is used.
+ *
+ * # Fields
+ * - `acceleration_time`:   Time to reach the cruise speed from standstill, and to stop from it.
+ * - `floor_travel_time`:   Time to travel one floor at the cruise speed.
+ * - `door_cycle_time`:     Time the car stands at a stop, from the door opening to the car moving off.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::time::Duration;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::MotionConfig;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// The motor is given this many times the expected time to the next floor before it is taken as lost
+const MOTOR_TIMEOUT_MARGIN: u32 = 3;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MotionModel {
+    acceleration_time: Duration,
+    floor_travel_time: Duration,
+    door_cycle_time: Duration,
+}
+
+impl MotionModel {
+    pub fn new(motion_config: &MotionConfig) -> MotionModel {
+        MotionModel {
+            acceleration_time: Duration::from_millis(motion_config.acceleration_time),
+            floor_travel_time: Duration::from_millis(motion_config.floor_travel_time),
+            door_cycle_time: Duration::from_millis(motion_config.door_cycle_time),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        !self.floor_travel_time.is_zero()
+    }
+
+    // The time to travel between two floors, from standstill to standstill
+    pub fn travel_time(&self, from: u8, to: u8) -> Duration {
+        let floors = from.abs_diff(to) as f64;
+        if floors == 0.0 {
+            return Duration::ZERO;
+        }
+
+        // Speeding up and slowing down together cover the distance of one acceleration time at the cruise speed
+        let floor_time = self.floor_travel_time.as_secs_f64();
+        let acceleration_time = self.acceleration_time.as_secs_f64();
+        let seconds = if floors * floor_time >= acceleration_time {
+            floors * floor_time + acceleration_time
+        } else {
+            2.0 * (floors * floor_time * acceleration_time).sqrt()
+        };
+        Duration::from_secs_f64(seconds)
+    }
+
+    // The time from now until the car arrives at each stop, standing at every stop for a door cycle. A car with
+    // the door open first waits for it to close.
+    pub fn arrival_times(&self, floor: u8, door_time_left: Duration, stops: &[u8]) -> Vec<Duration> {
+        let mut time = door_time_left;
+        let mut floor = floor;
+        let mut arrival_times = Vec::with_capacity(stops.len());
+        for (index, stop) in stops.iter().enumerate() {
+            if index > 0 {
+                time += self.door_cycle_time;
+            }
+            time += self.travel_time(floor, *stop);
+            arrival_times.push(time);
+            floor = *stop;
+        }
+        arrival_times
+    }
+
+    // How long the motor may run without reaching the next floor, capped by the configured timeout
+    pub fn motor_timeout(&self, configured: Duration) -> Duration {
+        if !self.is_set() {
+            return configured;
+        }
+        configured.min(self.travel_time(0, 1) * MOTOR_TIMEOUT_MARGIN)
+    }
+}
@@ -0,0 +1,61 @@
+/*
+ * Unit tests for the travel-time model
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_motion_travel_time
+ *  - test_motion_arrival_times
+ *  - test_motion_motor_timeout
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod motion_tests {
+    use std::time::Duration;
+    use crate::config::MotionConfig;
+    use crate::shared::MotionModel;
+
+    fn model() -> MotionModel {
+        MotionModel::new(&MotionConfig { acceleration_time: 2000, floor_travel_time: 1000, door_cycle_time: 4000 })
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_motion_travel_time() {
+        // Act / Assert
+        assert_eq!(model().travel_time(2, 2), Duration::ZERO);
+        // Long trips reach the cruise speed, and lose one acceleration time to speeding up and slowing down
+        assert_eq!(model().travel_time(0, 3), ms(5000));
+        assert_eq!(model().travel_time(3, 1), ms(4000));
+        // A single floor is too short to reach the cruise speed
+        assert_eq!(model().travel_time(1, 2).as_millis(), 2828);
+    }
+
+    #[test]
+    fn test_motion_arrival_times() {
+        // Act
+        let moving_off = model().arrival_times(0, Duration::ZERO, &[2, 3]);
+        let door_open = model().arrival_times(1, ms(1500), &[1, 3]);
+
+        // Assert
+        // The car stands a door cycle at every stop before the next
+        assert_eq!(moving_off, vec![ms(4000), ms(4000 + 4000) + model().travel_time(2, 3)]);
+        assert_eq!(door_open, vec![ms(1500), ms(1500 + 4000 + 4000)]);
+        assert!(!MotionModel::default().is_set());
+    }
+
+    #[test]
+    fn test_motion_motor_timeout() {
+        // Act / Assert
+        assert_eq!(model().motor_timeout(ms(10_000)).as_millis(), 8485);
+        assert_eq!(model().motor_timeout(ms(5000)), ms(5000));
+        assert_eq!(MotionModel::default().motor_timeout(ms(10_000)), ms(10_000));
+    }
+}
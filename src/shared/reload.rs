@@ -0,0 +1,286 @@
+/**
+ * Changes to the settings of a running elevator, from `config.toml` or from an operator on the network.
+ *
+ * Every `interval` milliseconds of `[reload]` the modification time of `config.toml` is checked. Once the file has
+ * changed, the settings that may change while running are compared with those read before, and the ones that
+ * changed are applied. An operator may change the same settings from another machine with a `configUpdate`
+ * message, as sent by the `configure` command. The update is sealed with ChaCha20-Poly1305 under `key`, so only
+ * those holding the key can change the elevator, and carries the time it was issued. An update that does not open
+ * with the key, names a setting outside the list, was issued more than `MAX_AGE` ago or ahead, or is not newer than
+ * the last update applied is refused, so a captured update cannot be replayed. Without a key every update from the
+ * network is refused.
+ *
+ * The settings are:
+ * - `door_open_time`, `door_timeout` and `motor_timeout` of `[elevator]`, taken by the FSM from its next timer on.
+ * - `traffic_mode`, `mode` of `[traffic]` in the file, set as the operator override of the fleet.
+ * - `log_level`, `max_level` of `[logging]` in the file, the most verbose level logged.
+ *
+ * The other settings in the file take effect at the next start. Updates are applied in the order they come, so
+ * the last one wins, whether from the file or the network. The file is not rewritten by updates from the network.
+ *
+ * # Fields
+ * - `path`:                    The configuration file watched.
+ * - `interval`:                Time between checks of the file, if it is watched.
+ * - `cipher`:                  Opens the updates from the network, if a key is configured.
+ * - `config`:                  The configuration last read from the file.
+ * - `modified`:                When the file was last modified, as of the last check.
+ * - `last_issued`:             When the last update applied from the network was issued, in milliseconds.
+ * - `config_update_rx`:        Receives the sealed updates from the network.
+ * - `fsm_timings_tx`:          Sends the changed timings to the FSM.
+ * - `coordinator_command_tx`:  Sends the changed traffic mode to the coordinator.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::UdpSocket;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::{read_config, Config};
+use crate::logging::set_log_level;
+use crate::network::encryption::Cipher;
+use crate::network::{Envelope, GossipMessage};
+use crate::shared::{ControlCommand, Encryption, FsmTimings, TrafficMode};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// How far the time an update was issued may be from the clock of the elevator
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// The settings that may change while running. `None` leaves a setting as it is.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub door_open_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub door_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motor_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traffic_mode: Option<TrafficMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+}
+
+impl ConfigUpdate {
+    // The settings that differ between the two configurations, as they are in `new`
+    pub fn between(old: &Config, new: &Config) -> ConfigUpdate {
+        fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+            (old != new).then(|| new.clone())
+        }
+        ConfigUpdate {
+            door_open_time: changed(&old.elevator.door_open_time, &new.elevator.door_open_time),
+            door_timeout: changed(&old.elevator.door_timeout, &new.elevator.door_timeout),
+            motor_timeout: changed(&old.elevator.motor_timeout, &new.elevator.motor_timeout),
+            traffic_mode: changed(&old.traffic.mode, &new.traffic.mode),
+            log_level: changed(&old.logging.max_level, &new.logging.max_level),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ConfigUpdate::default()
+    }
+}
+
+// An update as sealed for the network, with the time it was issued in milliseconds since the Unix epoch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteUpdate {
+    pub issued: u64,
+    pub settings: ConfigUpdate,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct ConfigReloader {
+    path: String,
+    interval: Option<Duration>,
+    cipher: Option<Cipher>,
+    config: Config,
+    modified: Option<SystemTime>,
+    last_issued: u64,
+    config_update_rx: cbc::Receiver<String>,
+    fsm_timings_tx: cbc::Sender<FsmTimings>,
+    coordinator_command_tx: cbc::Sender<ControlCommand>,
+}
+
+impl ConfigReloader {
+    // Watches the file at `path`, as read into `config` at start-up
+    pub fn new(
+        config: &Config,
+        path: &str,
+        config_update_rx: cbc::Receiver<String>,
+        fsm_timings_tx: cbc::Sender<FsmTimings>,
+        coordinator_command_tx: cbc::Sender<ControlCommand>,
+    ) -> Result<ConfigReloader, String> {
+        let cipher = match config.reload.key.is_empty() {
+            true => None,
+            false => Some(reload_cipher(&config.reload.key)?),
+        };
+        Ok(ConfigReloader {
+            path: path.to_string(),
+            interval: (config.reload.interval > 0).then(|| Duration::from_millis(config.reload.interval)),
+            cipher,
+            config: config.clone(),
+            modified: modified(path),
+            last_issued: 0,
+            config_update_rx,
+            fsm_timings_tx,
+            coordinator_command_tx,
+        })
+    }
+
+    // Applies the changes until the network is gone
+    pub fn run(mut self) {
+        let check_ticker = self.interval.map_or(cbc::never(), cbc::tick);
+        loop {
+            cbc::select! {
+                recv(self.config_update_rx) -> sealed => {
+                    let Ok(sealed) = sealed else { return };
+                    match self.accept(&sealed, now_millis()) {
+                        Ok(update) => self.apply(&update, "the network"),
+                        Err(e) => warn!("Refused a configuration update: {}", e),
+                    }
+                }
+                recv(check_ticker) -> _ => self.check_file(),
+            }
+        }
+    }
+
+    // The settings of a sealed update received at `now`, if it is to be applied
+    pub fn accept(&mut self, sealed: &str, now: u64) -> Result<ConfigUpdate, String> {
+        let cipher = self.cipher.as_ref().ok_or("No reload key is configured")?;
+        let update = decode(sealed)
+            .and_then(|packet| cipher.open(&packet))
+            .ok_or("The update is not sealed with the reload key")?;
+        let update = serde_json::from_slice::<RemoteUpdate>(&update).map_err(|e| e.to_string())?;
+        if update.issued.abs_diff(now) > MAX_AGE.as_millis() as u64 {
+            return Err(format!("The update was issued {} ms from now", update.issued as i64 - now as i64));
+        }
+        if update.issued <= self.last_issued {
+            return Err("The update is not newer than the last one applied".to_string());
+        }
+        self.last_issued = update.issued;
+        Ok(update.settings)
+    }
+
+    // Sends the changed settings to the modules they belong to. An update with an invalid setting is applied as far
+    // as it is valid.
+    pub fn apply(&self, update: &ConfigUpdate, source: &str) {
+        if update.is_empty() {
+            return;
+        }
+        info!("Applying {} from {}", serde_json::to_string(update).unwrap_or_default(), source);
+
+        let timings = FsmTimings {
+            door_open_time: update.door_open_time,
+            door_timeout: update.door_timeout,
+            motor_timeout: update.motor_timeout,
+        };
+        if timings != FsmTimings::default() {
+            let _ = self.fsm_timings_tx.send(timings);
+        }
+        if let Some(traffic_mode) = update.traffic_mode {
+            let _ = self.coordinator_command_tx.send(ControlCommand::SetTrafficMode(Some(traffic_mode)));
+        }
+        if let Some(Err(e)) = update.log_level.as_deref().map(set_log_level) {
+            warn!("{}", e);
+        }
+    }
+
+    // Applies the changes to the file since it was last read. A file that does not parse is left until it does.
+    fn check_file(&mut self) {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        match read_config(&self.path) {
+            Ok(config) => {
+                let update = ConfigUpdate::between(&self.config, &config);
+                self.config = config;
+                self.apply(&update, &self.path);
+            }
+            Err(e) => warn!("{}. Keeping the running settings.", e),
+        }
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Seals the settings under the reload key, issued at `issued` milliseconds since the Unix epoch
+pub fn seal_update(key: &str, settings: &ConfigUpdate, issued: u64) -> Result<String, String> {
+    let cipher = reload_cipher(key)?;
+    let update = RemoteUpdate { issued, settings: settings.clone() };
+    let sealed = cipher.seal(&serde_json::to_vec(&update).map_err(|e| e.to_string())?);
+    Ok(sealed.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Sends the settings, a JSON object, to the elevator with the ID at the address, as the network of the config does
+pub fn send_config_update(config: &Config, elevator: &str, address: &str, settings: &str) -> Result<(), String> {
+    let settings = serde_json::from_str::<ConfigUpdate>(settings).map_err(|e| format!("Invalid settings: {}", e))?;
+    let envelope = Envelope {
+        origin: "operator".to_string(),
+        destination: elevator.to_string(),
+        message: GossipMessage::ConfigUpdate(seal_update(&config.reload.key, &settings, now_millis())?),
+        sequence: None,
+        group: config.network.group_id.clone(),
+    };
+    let network_cipher = Cipher::new(config.network.encryption, &config.network.encryption_key)?;
+    let datagram = network_cipher.seal(&serde_json::to_vec(&envelope).map_err(|e| e.to_string())?);
+
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+    socket.send_to(&datagram, address).map_err(|e| format!("Failed to send to {}: {}", address, e))?;
+    Ok(())
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn reload_cipher(key: &str) -> Result<Cipher, String> {
+    Cipher::new(Encryption::Required, key).map_err(|_| "The reload key must be 64 hexadecimal digits".to_string())
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or(0)
+}
+
+fn decode(hexadecimal: &str) -> Option<Vec<u8>> {
+    (0..hexadecimal.len())
+        .step_by(2)
+        .map(|index| hexadecimal.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok()))
+        .collect()
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::ConfigReloader;
+
+    impl ConfigReloader {
+        pub fn test_check_file(&mut self) {
+            self.check_file()
+        }
+    }
+}
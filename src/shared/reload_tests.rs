@@ -0,0 +1,143 @@
+/*
+ * Unit tests for the changes to the settings of a running elevator
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * The configuration file is written to a temporary directory.
+ *
+ * Tests:
+ *  - test_reload_file_changes
+ *  - test_reload_remote_update
+ *  - test_reload_refused_updates
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod reload_tests {
+    use crate::config::{read_config, Config};
+    use crate::shared::reload::seal_update;
+    use crate::shared::{ConfigReloader, ConfigUpdate, ControlCommand, FsmTimings, TrafficMode};
+    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use std::time::{Duration, SystemTime};
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn config_file(door_open_time: u64, traffic_mode: &str) -> String {
+        format!(
+            r#"
+            [network]
+            id_gen_address = "127.0.0.1:53"
+            msg_port = 19735
+            peer_port = 19738
+            gossip_interval = 50
+            max_attempts_id_generation = 1
+            delay_between_attempts_id_generation = 0
+
+            [elevator]
+            n_floors = 4
+            door_open_time = {}
+            door_timeout = 15000
+            motor_timeout = 10000
+
+            [hardware]
+            n_floors = 4
+            driver_address = "localhost"
+            driver_port = 15657
+            hw_thread_sleep_time = 10
+
+            [traffic]
+            mode = "{}"
+
+            [reload]
+            interval = 1000
+            key = "{}"
+            "#,
+            door_open_time, traffic_mode, KEY
+        )
+    }
+
+    fn setup_reloader(config: &Config, path: &str) -> (ConfigReloader, Sender<String>, Receiver<FsmTimings>, Receiver<ControlCommand>) {
+        let (config_update_tx, config_update_rx) = unbounded::<String>();
+        let (fsm_timings_tx, fsm_timings_rx) = unbounded::<FsmTimings>();
+        let (coordinator_command_tx, coordinator_command_rx) = unbounded::<ControlCommand>();
+        let reloader = ConfigReloader::new(config, path, config_update_rx, fsm_timings_tx, coordinator_command_tx).unwrap();
+        (reloader, config_update_tx, fsm_timings_rx, coordinator_command_rx)
+    }
+
+    #[test]
+    fn test_reload_file_changes() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("reload_test_{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, config_file(3000, "normal")).unwrap();
+        let config = read_config(path).unwrap();
+        let (mut reloader, _config_update_tx, fsm_timings_rx, coordinator_command_rx) = setup_reloader(&config, path);
+
+        // Act
+        reloader.test_check_file();
+        let unchanged = fsm_timings_rx.try_recv().ok();
+        std::fs::write(path, config_file(5000, "up-peak")).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        reloader.test_check_file();
+        std::fs::remove_file(path).unwrap();
+
+        // Assert
+        // Only the settings that changed are applied
+        assert_eq!(unchanged, None);
+        assert_eq!(fsm_timings_rx.try_recv().ok(), Some(FsmTimings { door_open_time: Some(5000), ..Default::default() }));
+        assert_eq!(coordinator_command_rx.try_recv().ok(), Some(ControlCommand::SetTrafficMode(Some(TrafficMode::UpPeak))));
+    }
+
+    #[test]
+    fn test_reload_remote_update() {
+        // Arrange
+        let config: Config = toml::from_str(&config_file(3000, "normal")).unwrap();
+        let (mut reloader, _config_update_tx, fsm_timings_rx, _coordinator_command_rx) = setup_reloader(&config, "");
+        let settings = ConfigUpdate { door_timeout: Some(20000), motor_timeout: Some(8000), ..Default::default() };
+        let sealed = seal_update(KEY, &settings, NOW).unwrap();
+
+        // Act
+        let accepted = reloader.accept(&sealed, NOW + 500);
+        reloader.apply(accepted.as_ref().unwrap(), "the network");
+
+        // Assert
+        assert_eq!(accepted, Ok(settings));
+        assert_eq!(
+            fsm_timings_rx.try_recv().ok(),
+            Some(FsmTimings { door_open_time: None, door_timeout: Some(20000), motor_timeout: Some(8000) })
+        );
+    }
+
+    #[test]
+    fn test_reload_refused_updates() {
+        // Arrange
+        let config: Config = toml::from_str(&config_file(3000, "normal")).unwrap();
+        let (mut reloader, _config_update_tx, _fsm_timings_rx, _coordinator_command_rx) = setup_reloader(&config, "");
+        let settings = ConfigUpdate { door_open_time: Some(1000), ..Default::default() };
+        let other_key = KEY.replace('0', "f");
+
+        // Act
+        let first = reloader.accept(&seal_update(KEY, &settings, NOW).unwrap(), NOW);
+        let replayed = reloader.accept(&seal_update(KEY, &settings, NOW).unwrap(), NOW);
+        let stale = reloader.accept(&seal_update(KEY, &settings, NOW - 120_000).unwrap(), NOW);
+        let forged = reloader.accept(&seal_update(&other_key, &settings, NOW + 1).unwrap(), NOW);
+        let plaintext = reloader.accept(&hex(br#"{"issued": 1700000000001, "settings": {"door_open_time": 1}}"#), NOW);
+        let unlisted = serde_json::from_str::<ConfigUpdate>(r#"{"n_floors": 9}"#);
+
+        // Assert
+        assert!(first.is_ok());
+        assert!(replayed.is_err());
+        assert!(stale.is_err());
+        assert!(forged.is_err());
+        assert!(plaintext.is_err());
+        assert!(unlisted.is_err());
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
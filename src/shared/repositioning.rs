@@ -0,0 +1,61 @@
+/**
+ * Repositioning of idle elevators.
+ *
+ * Idle elevators are spread across sectors of the building, so that some elevator is always close
+ * to each sector. Sectors are given as floors in order of priority, and each is taken by the closest
+ * idle elevator not already holding a sector. Elevators without a sector stay where they are.
+ *
+ * # Fields
+ * - `sectors`:             Floors idle elevators are parked at, in order of priority.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::collections::HashMap;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::RepositioningConfig;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepositioningPolicy {
+    sectors: Vec<u8>,
+}
+
+impl RepositioningPolicy {
+    pub fn new(repositioning_config: &RepositioningConfig) -> RepositioningPolicy {
+        RepositioningPolicy {
+            sectors: repositioning_config.sectors.clone(),
+        }
+    }
+
+    // Assigns sectors to the idle elevators, given by ID and floor. Ties go to the lowest ID,
+    // so every node arrives at the same assignment.
+    pub fn assign_sectors(&self, idle_elevators: &[(String, u8)]) -> HashMap<String, u8> {
+        let mut unassigned = idle_elevators.to_vec();
+        unassigned.sort();
+
+        let mut sectors = HashMap::new();
+        for sector in self.sectors.iter() {
+            let closest = unassigned
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, floor))| floor.abs_diff(*sector))
+                .map(|(index, _)| index);
+
+            match closest {
+                Some(index) => {
+                    let (id, _) = unassigned.remove(index);
+                    sectors.insert(id, *sector);
+                }
+                None => break,
+            }
+        }
+        sectors
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * Unit tests for the repositioning policy
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_repositioning_policy_assign_sectors
+ *  - test_repositioning_policy_more_sectors_than_elevators
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod repositioning_tests {
+    use crate::config::RepositioningConfig;
+    use crate::shared::RepositioningPolicy;
+
+    fn policy() -> RepositioningPolicy {
+        RepositioningPolicy::new(&RepositioningConfig { sectors: vec![3, 0] })
+    }
+
+    #[test]
+    fn test_repositioning_policy_assign_sectors() {
+        // Arrange
+        let idle_elevators = vec![
+            ("a".to_string(), 1),
+            ("b".to_string(), 2),
+            ("c".to_string(), 2),
+        ];
+
+        // Act
+        let sectors = policy().assign_sectors(&idle_elevators);
+
+        // Assert
+        // The top sector comes first and goes to the first of the closest elevators, the lobby to the closest remaining one
+        assert_eq!(sectors.get("b"), Some(&3));
+        assert_eq!(sectors.get("a"), Some(&0));
+        assert_eq!(sectors.get("c"), None);
+    }
+
+    #[test]
+    fn test_repositioning_policy_more_sectors_than_elevators() {
+        // Arrange
+        let idle_elevators = vec![("a".to_string(), 0)];
+
+        // Act
+        let sectors = policy().assign_sectors(&idle_elevators);
+        let no_sectors = policy().assign_sectors(&[]);
+
+        // Assert
+        assert_eq!(sectors.len(), 1);
+        assert_eq!(sectors.get("a"), Some(&3));
+        assert!(no_sectors.is_empty());
+    }
+}
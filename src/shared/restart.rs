@@ -0,0 +1,178 @@
+/**
+ * The exit codes of the elevator, and the supervisor restarting it by them.
+ *
+ * The program ends with the code of an `ExitReason`, so launch scripts tell a missing simulator from an invalid
+ * configuration, and a panicking thread ends the whole program as an internal panic, or as unreachable hardware if
+ * it is a thread of the driver, whose hardware calls panic when the connection to the elevator server breaks.
+ *
+ * With `--supervise`, the program runs the elevator as a child process and restarts it when it ends, by the restart
+ * policy of its exit reason: at once, after a delay doubling with every restart in a row, or not at all, as with an
+ * invalid configuration that would fail the same way again. A child killed by a signal, or ending with a code of no
+ * reason, is restarted with backoff. A clean exit ends the supervisor too.
+ *
+ * # Fields
+ * - `policies`:                The restart policy of every exit reason.
+ * - `initial_delay`:           The delay before the first restart with backoff.
+ * - `max_delay`:               The longest delay before a restart with backoff.
+ * - `stable_time`:             How long a run must last to end the restarts in a row.
+ * - `max_restarts`:            The restarts in a row before giving up. None restarts forever.
+ * - `restarts`:                The restarts in a row so far.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::RestartConfig;
+use crate::shared::{ExitReason, RestartPolicy};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+const DEFAULT_STABLE_TIME: Duration = Duration::from_secs(60);
+// The threads of the driver, which panic when the elevator server is lost
+const DRIVER_THREADS: [&str; 2] = ["elevator_driver", "elevator_poll"];
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// What the supervisor does after a run of the elevator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestartDecision {
+    Restart(Duration),
+    Stop,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Restarter {
+    policies: HashMap<ExitReason, RestartPolicy>,
+    initial_delay: Duration,
+    max_delay: Duration,
+    stable_time: Duration,
+    max_restarts: Option<u32>,
+    restarts: u32,
+}
+
+impl Restarter {
+    pub fn new(restart_config: &RestartConfig) -> Restarter {
+        Restarter {
+            policies: ExitReason::ALL
+                .into_iter()
+                .map(|reason| (reason, restart_config.policies.get(&reason).copied().unwrap_or(reason.default_restart_policy())))
+                .collect(),
+            initial_delay: milliseconds_or(restart_config.initial_delay, DEFAULT_INITIAL_DELAY),
+            max_delay: milliseconds_or(restart_config.max_delay, DEFAULT_MAX_DELAY),
+            stable_time: milliseconds_or(restart_config.stable_time, DEFAULT_STABLE_TIME),
+            max_restarts: (restart_config.max_restarts > 0).then_some(restart_config.max_restarts),
+            restarts: 0,
+        }
+    }
+
+    // The policy of an exit code, None for a child killed by a signal
+    pub fn policy(&self, code: Option<i32>) -> RestartPolicy {
+        match code.and_then(ExitReason::from_code) {
+            Some(reason) => self.policies[&reason],
+            None => RestartPolicy::Backoff,
+        }
+    }
+
+    // Decides on a run that ended with `code` after `run_time`
+    pub fn decide(&mut self, code: Option<i32>, run_time: Duration) -> RestartDecision {
+        if run_time >= self.stable_time {
+            self.restarts = 0;
+        }
+        if code == Some(ExitReason::Clean.code()) || self.max_restarts.is_some_and(|max_restarts| self.restarts >= max_restarts) {
+            return RestartDecision::Stop;
+        }
+        let delay = match self.policy(code) {
+            RestartPolicy::Immediate => Duration::ZERO,
+            RestartPolicy::Backoff => self.initial_delay.saturating_mul(1 << self.restarts.min(16)).min(self.max_delay),
+            RestartPolicy::GiveUp => return RestartDecision::Stop,
+        };
+        self.restarts += 1;
+        RestartDecision::Restart(delay)
+    }
+}
+
+/***************************************/
+/*           Public functions          */
+/***************************************/
+// Ends the program with the code of the reason
+pub fn exit(reason: ExitReason) -> ! {
+    std::process::exit(reason.code())
+}
+
+// Ends the program when a thread panics, as the module it ran is dead
+pub fn exit_on_panic() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        default_hook(panic_info);
+        let thread = thread::current().name().unwrap_or("unnamed").to_string();
+        let reason = match DRIVER_THREADS.contains(&thread.as_str()) {
+            true => ExitReason::HardwareUnreachable,
+            false => ExitReason::InternalPanic,
+        };
+        error!("The {} thread panicked, exiting with {}", thread, reason);
+        exit(reason)
+    }));
+}
+
+// Runs the program with `arguments` as a child process, restarting it by the policies until it is given up on. Ends
+// with the code of the last run.
+pub fn supervise(restart_config: &RestartConfig, arguments: Vec<String>) -> ! {
+    let program = match std::env::current_exe() {
+        Ok(program) => program,
+        Err(e) => {
+            error!("Failed to find the program to supervise: {}", e);
+            exit(ExitReason::Failure)
+        }
+    };
+    let mut restarter = Restarter::new(restart_config);
+    loop {
+        info!("Supervisor starting the elevator");
+        let started = Instant::now();
+        let code = match Command::new(&program).args(&arguments).status() {
+            Ok(status) => status.code(),
+            Err(e) => {
+                error!("Failed to start the elevator: {}", e);
+                exit(ExitReason::Failure)
+            }
+        };
+        let ended = match code.and_then(ExitReason::from_code) {
+            Some(reason) => reason.to_string(),
+            None => code.map_or("a signal".to_string(), |code| format!("code {}", code)),
+        };
+        match restarter.decide(code, started.elapsed()) {
+            RestartDecision::Restart(delay) => {
+                warn!("The elevator ended with {}, restarting it in {:?}", ended, delay);
+                thread::sleep(delay);
+            }
+            RestartDecision::Stop => {
+                info!("The elevator ended with {}, not restarting it", ended);
+                std::process::exit(code.unwrap_or(ExitReason::Failure.code()))
+            }
+        }
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn milliseconds_or(milliseconds: u64, default: Duration) -> Duration {
+    match milliseconds {
+        0 => default,
+        milliseconds => Duration::from_millis(milliseconds),
+    }
+}
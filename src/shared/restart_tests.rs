@@ -0,0 +1,85 @@
+/*
+ * Unit tests for the exit codes and the restart policies of the supervisor
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_restart_exit_codes
+ *  - test_restart_policies
+ *  - test_restart_backoff_and_give_up
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod restart_tests {
+    use crate::config::RestartConfig;
+    use crate::shared::{ExitReason, RestartDecision, RestartPolicy, Restarter};
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    const SHORT_RUN: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_restart_exit_codes() {
+        // Act
+        let codes = ExitReason::ALL.iter().map(ExitReason::code).collect::<HashSet<i32>>();
+
+        // Assert
+        // Every reason has a code of its own, told back by the supervisor
+        assert_eq!(codes.len(), ExitReason::ALL.len());
+        assert!(ExitReason::ALL.iter().all(|reason| ExitReason::from_code(reason.code()) == Some(*reason)));
+        assert_eq!(ExitReason::from_code(2), None);
+        assert_eq!(ExitReason::ConfigInvalid.to_string(), "config-invalid (78)");
+        assert_eq!(ExitReason::HardwareUnreachable.code(), 69);
+    }
+
+    #[test]
+    fn test_restart_policies() {
+        // Arrange
+        let restart_config: RestartConfig = toml::from_str("policies = { hardware-unreachable = \"give-up\", config-invalid = \"backoff\" }").unwrap();
+        let defaults = Restarter::new(&RestartConfig::default());
+        let configured = Restarter::new(&restart_config);
+
+        // Act / Assert
+        assert_eq!(defaults.policy(Some(ExitReason::InternalPanic.code())), RestartPolicy::Immediate);
+        assert_eq!(defaults.policy(Some(ExitReason::HardwareUnreachable.code())), RestartPolicy::Backoff);
+        assert_eq!(defaults.policy(Some(ExitReason::ConfigInvalid.code())), RestartPolicy::GiveUp);
+        // Killed by a signal, or ending with a code of no reason
+        assert_eq!(defaults.policy(None), RestartPolicy::Backoff);
+        assert_eq!(defaults.policy(Some(2)), RestartPolicy::Backoff);
+        // The configured policies override the defaults, and leave the others
+        assert_eq!(configured.policy(Some(ExitReason::HardwareUnreachable.code())), RestartPolicy::GiveUp);
+        assert_eq!(configured.policy(Some(ExitReason::ConfigInvalid.code())), RestartPolicy::Backoff);
+        assert_eq!(configured.policy(Some(ExitReason::Failure.code())), RestartPolicy::Immediate);
+    }
+
+    #[test]
+    fn test_restart_backoff_and_give_up() {
+        // Arrange
+        let restart_config = RestartConfig { initial_delay: 1000, max_delay: 3000, stable_time: 60000, max_restarts: 5, ..Default::default() };
+        let mut restarter = Restarter::new(&restart_config);
+        let unreachable = Some(ExitReason::HardwareUnreachable.code());
+
+        // Act
+        let backoff = (0..4).map(|_| restarter.decide(unreachable, SHORT_RUN)).collect::<Vec<RestartDecision>>();
+        let stable = restarter.decide(unreachable, Duration::from_secs(60));
+        let panicked = restarter.decide(Some(ExitReason::InternalPanic.code()), SHORT_RUN);
+        let exhausted = (0..4).map(|_| restarter.decide(None, SHORT_RUN)).last().unwrap();
+        let clean = Restarter::new(&restart_config).decide(Some(0), SHORT_RUN);
+        let invalid = Restarter::new(&restart_config).decide(Some(ExitReason::ConfigInvalid.code()), SHORT_RUN);
+
+        // Assert
+        // The delay doubles up to the longest, and starts over after a stable run
+        let seconds = |seconds| RestartDecision::Restart(Duration::from_secs(seconds));
+        assert_eq!(backoff, vec![seconds(1), seconds(2), seconds(3), seconds(3)]);
+        assert_eq!(stable, seconds(1));
+        assert_eq!(panicked, RestartDecision::Restart(Duration::ZERO));
+        // Given up after the restarts in a row, a clean exit and an invalid configuration
+        assert_eq!(exhausted, RestartDecision::Stop);
+        assert_eq!(clean, RestartDecision::Stop);
+        assert_eq!(invalid, RestartDecision::Stop);
+    }
+}
@@ -0,0 +1,39 @@
+/**
+ * Daily time windows used by the config schedules.
+ *
+ * A window is active from `start` (inclusive) to `end` (exclusive). Windows ending before they
+ * start wrap around midnight. Windows are evaluated against a time of day passed in by the
+ * caller, so they can be driven by any clock.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::NaiveTime;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    // Parses a window from "HH:MM" strings, panicking on invalid config like `load_config`
+    pub fn parse(start: &str, end: &str) -> TimeWindow {
+        TimeWindow {
+            start: NaiveTime::parse_from_str(start, "%H:%M").expect("Failed to parse schedule start time"),
+            end: NaiveTime::parse_from_str(end, "%H:%M").expect("Failed to parse schedule end time"),
+        }
+    }
+
+    pub fn is_active(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
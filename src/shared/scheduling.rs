@@ -0,0 +1,93 @@
+/**
+ * Scheduling of the threads that stop the car at the floors.
+ *
+ * The floor sensor is read by the driver thread and acted on by the FSM thread, which sends the motor the stop. Under
+ * load, as while the log is flushed, the hall calls are assigned or the network retries, both may wait for a core
+ * long enough for the car to overshoot the floor. Each of the two can therefore be given a real-time priority, under
+ * which it runs ahead of every thread of normal priority, and on Linux be pinned to cores, which may be kept free of
+ * the other threads by the operating system.
+ *
+ * Setting a real-time priority takes the privilege to, as `CAP_SYS_NICE` on Linux. A thread whose scheduling cannot
+ * be set keeps running as it was, so a missing privilege only loses the guarantee.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+#[cfg(target_os = "linux")]
+use std::mem;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::ThreadConfig;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const MAX_PRIORITY: u8 = 99;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Applies the priority and the cores to the calling thread, or names what could not be applied
+pub fn schedule_thread(thread_config: &ThreadConfig) -> Result<(), String> {
+    if thread_config.priority > MAX_PRIORITY {
+        return Err(format!("The priority {} is not within 0 to {}", thread_config.priority, MAX_PRIORITY));
+    }
+    if thread_config.priority > 0 {
+        set_priority(thread_config.priority)?;
+    }
+    if !thread_config.cores.is_empty() {
+        set_cores(&thread_config.cores)?;
+    }
+    Ok(())
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+#[cfg(unix)]
+fn set_priority(priority: u8) -> Result<(), String> {
+    // SAFETY: The parameters are initialized, and the thread is the calling one
+    let result = unsafe {
+        let parameters = libc::sched_param { sched_priority: priority as libc::c_int };
+        libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &parameters)
+    };
+    match result {
+        0 => Ok(()),
+        error => Err(format!("Failed to set the priority {}: {}", priority, std::io::Error::from_raw_os_error(error))),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_priority(_priority: u8) -> Result<(), String> {
+    Err("Priorities are only set on Unix".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn set_cores(cores: &[usize]) -> Result<(), String> {
+    let max_cores = 8 * mem::size_of::<libc::cpu_set_t>();
+    if let Some(core) = cores.iter().find(|core| **core >= max_cores) {
+        return Err(format!("The core {} is beyond the {} cores that can be set", core, max_cores));
+    }
+    // SAFETY: The set is initialized before the cores are added, and the cores are within it. A process ID of 0 is
+    // the calling thread.
+    let result = unsafe {
+        let mut set = mem::zeroed::<libc::cpu_set_t>();
+        libc::CPU_ZERO(&mut set);
+        for core in cores {
+            libc::CPU_SET(*core, &mut set);
+        }
+        libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(format!("Failed to run on the cores {:?}: {}", cores, std::io::Error::last_os_error())),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cores(_cores: &[usize]) -> Result<(), String> {
+    Err("Cores are only set on Linux".to_string())
+}
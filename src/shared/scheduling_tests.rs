@@ -0,0 +1,39 @@
+/*
+ * Unit tests for the scheduling of the threads
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_schedule_thread
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod scheduling_tests {
+    use crate::config::ThreadConfig;
+    use crate::shared::schedule_thread;
+
+    #[test]
+    fn test_schedule_thread() {
+        // Arrange
+        let unchanged = ThreadConfig::default();
+        let beyond_priorities = ThreadConfig { priority: 100, cores: Vec::new() };
+        let beyond_cores = ThreadConfig { priority: 0, cores: vec![0, usize::MAX] };
+
+        // Act
+        let results = std::thread::spawn(move || {
+            (schedule_thread(&unchanged), schedule_thread(&beyond_priorities), schedule_thread(&beyond_cores))
+        })
+        .join()
+        .unwrap();
+
+        // Assert
+        // Without a priority or cores the thread is left as it is, and settings out of range are refused
+        assert_eq!(results.0, Ok(()));
+        assert!(results.1.is_err());
+        assert!(results.2.is_err());
+    }
+}
@@ -0,0 +1,58 @@
+/**
+ * The shutdown protocol of the modules.
+ *
+ * Each module is terminated by sending it a `Terminate`, the sender it acknowledges on once it has cleaned up, as
+ * by stopping the motor or saving what it keeps. A module stops without an acknowledgement if its channel is gone,
+ * as when it already stopped on its own. The modules are asked to terminate all at once, so one that finds the
+ * channel of another disconnected has the request to terminate waiting, and cleans up as asked. Those that have not
+ * acknowledged by the deadline are taken as hung, and it is up to the caller to give up on them.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// The request to terminate, carrying the sender the module acknowledges on
+pub type Terminate = cbc::Sender<()>;
+
+// How a module answered the request to terminate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    Acknowledged,
+    Stopped,
+    Hung,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Asks every module to terminate, and waits up to `timeout` for them all to acknowledge. Returns how each answered,
+// in the order given.
+pub fn terminate_modules(modules: &[(&'static str, cbc::Sender<Terminate>)], timeout: Duration) -> Vec<(&'static str, Termination)> {
+    let deadline = Instant::now() + timeout;
+    let pending = modules
+        .iter()
+        .map(|(module, terminate_tx)| {
+            let (done_tx, done_rx) = cbc::bounded::<()>(1);
+            let sent = terminate_tx.send(done_tx).is_ok();
+            (*module, sent.then_some(done_rx))
+        })
+        .collect::<Vec<(&'static str, Option<cbc::Receiver<()>>)>>();
+
+    pending
+        .into_iter()
+        .map(|(module, done_rx)| {
+            let termination = match done_rx.map(|done_rx| done_rx.recv_deadline(deadline)) {
+                Some(Ok(())) => Termination::Acknowledged,
+                Some(Err(cbc::RecvTimeoutError::Timeout)) => Termination::Hung,
+                Some(Err(cbc::RecvTimeoutError::Disconnected)) | None => Termination::Stopped,
+            };
+            (module, termination)
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+/*
+ * Unit tests for the shutdown protocol
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_terminate_modules
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod shutdown_tests {
+    use crate::shared::{terminate_modules, Terminate, Termination};
+    use crossbeam_channel::unbounded;
+    use std::thread::spawn;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_terminate_modules() {
+        // Arrange
+        let (acknowledging_tx, acknowledging_rx) = unbounded::<Terminate>();
+        let (stopping_tx, stopping_rx) = unbounded::<Terminate>();
+        let (hanging_tx, hanging_rx) = unbounded::<Terminate>();
+        let (stopped_tx, stopped_rx) = unbounded::<Terminate>();
+        drop(stopped_rx);
+        spawn(move || acknowledging_rx.recv().unwrap().send(()).unwrap());
+        spawn(move || drop(stopping_rx.recv().unwrap()));
+        let hanging_thread = spawn(move || {
+            let done = hanging_rx.recv().unwrap();
+            std::thread::sleep(Duration::from_millis(500));
+            done
+        });
+        let modules = [
+            ("acknowledging", acknowledging_tx),
+            ("stopping", stopping_tx),
+            ("hanging", hanging_tx),
+            ("stopped", stopped_tx),
+        ];
+
+        // Act
+        let start = Instant::now();
+        let terminations = terminate_modules(&modules, Duration::from_millis(200));
+        let elapsed = start.elapsed();
+
+        // Assert
+        // The hung module is given up on at the deadline, and the others are told apart by their answer
+        assert_eq!(
+            terminations,
+            vec![
+                ("acknowledging", Termination::Acknowledged),
+                ("stopping", Termination::Stopped),
+                ("hanging", Termination::Hung),
+                ("stopped", Termination::Stopped),
+            ]
+        );
+        assert!(elapsed >= Duration::from_millis(200) && elapsed < Duration::from_millis(500));
+        drop(hanging_thread.join().unwrap());
+    }
+}
@@ -0,0 +1,39 @@
+/**
+ * The start-up protocol of the modules.
+ *
+ * The modules are started in the order they depend on each other: the driver, the FSM driving it, the network and
+ * the coordinator of them all. Each is waited for until it reports ready, so a module stuck starting, as a driver
+ * whose elevator server does not answer, is named in a clear error instead of leaving the others to run against dead
+ * channels. A module reports ready on a channel of its own, or by answering its first query, as one that answers is
+ * running its main loop. The sockets of the network are bound before the driver is started, as the ID of the
+ * elevator comes from them and names the instance lock guarding the hardware, so the network is ready once built.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use std::time::Duration;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Waits up to `timeout` for the module to report ready on `ready_rx`
+pub fn await_ready(module: &str, ready_rx: &cbc::Receiver<()>, timeout: Duration) -> Result<(), String> {
+    match ready_rx.recv_timeout(timeout) {
+        Ok(()) => Ok(()),
+        Err(cbc::RecvTimeoutError::Timeout) => Err(format!("The {} did not start within {:?}", module, timeout)),
+        Err(cbc::RecvTimeoutError::Disconnected) => Err(format!("The {} stopped while starting", module)),
+    }
+}
+
+// Waits up to `timeout` for the module to answer a query on `query_tx`, and returns the answer
+pub fn await_answer<T>(module: &str, query_tx: &cbc::Sender<cbc::Sender<T>>, timeout: Duration) -> Result<T, String> {
+    let (reply_tx, reply_rx) = cbc::bounded::<T>(1);
+    query_tx.send(reply_tx).map_err(|_| format!("The {} stopped while starting", module))?;
+    match reply_rx.recv_timeout(timeout) {
+        Ok(answer) => Ok(answer),
+        Err(cbc::RecvTimeoutError::Timeout) => Err(format!("The {} did not start within {:?}", module, timeout)),
+        Err(cbc::RecvTimeoutError::Disconnected) => Err(format!("The {} stopped while starting", module)),
+    }
+}
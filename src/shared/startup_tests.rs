@@ -0,0 +1,70 @@
+/*
+ * Unit tests for the start-up protocol of the modules
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_startup_await_ready
+ *  - test_startup_await_answer
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod startup_tests {
+    use crate::shared::{await_answer, await_ready};
+    use crossbeam_channel::{unbounded, Sender};
+    use std::thread::spawn;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn test_startup_await_ready() {
+        // Arrange
+        let (ready_tx, ready_rx) = unbounded::<()>();
+        let (stuck_tx, stuck_rx) = unbounded::<()>();
+        let (_, stopped_rx) = unbounded::<()>();
+        ready_tx.send(()).unwrap();
+
+        // Act
+        let ready = await_ready("driver", &ready_rx, TIMEOUT);
+        let stuck = await_ready("driver", &stuck_rx, TIMEOUT);
+        let stopped = await_ready("driver", &stopped_rx, TIMEOUT);
+        drop(stuck_tx);
+
+        // Assert
+        // The error names the module stuck starting
+        assert_eq!(ready, Ok(()));
+        assert_eq!(stuck, Err("The driver did not start within 50ms".to_string()));
+        assert_eq!(stopped, Err("The driver stopped while starting".to_string()));
+    }
+
+    #[test]
+    fn test_startup_await_answer() {
+        // Arrange
+        let (query_tx, query_rx) = unbounded::<Sender<u8>>();
+        let (hung_tx, hung_rx) = unbounded::<Sender<u8>>();
+        let (gone_tx, _) = unbounded::<Sender<u8>>();
+        let module = spawn(move || {
+            if let Ok(reply_tx) = query_rx.recv() {
+                let _ = reply_tx.send(3);
+            }
+        });
+
+        // Act
+        let answered = await_answer("FSM", &query_tx, Duration::from_secs(1));
+        let hung = await_answer("coordinator", &hung_tx, TIMEOUT);
+        let gone = await_answer("coordinator", &gone_tx, TIMEOUT);
+        module.join().unwrap();
+        drop(hung_rx);
+
+        // Assert
+        // A module that answers is running its main loop
+        assert_eq!(answered, Ok(3));
+        assert_eq!(hung, Err("The coordinator did not start within 50ms".to_string()));
+        assert_eq!(gone, Err("The coordinator stopped while starting".to_string()));
+    }
+}
@@ -4,7 +4,20 @@
 use driver_rust::elevio::elev::{DIRN_DOWN, DIRN_STOP, DIRN_UP};
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::{HallOrder, VectorClock};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+// The version of the messages between the elevators. Raise it with every change to `GossipMessage` or the structs it
+// carries that the previous build would misread or refuse.
+pub const SCHEMA_VERSION: u32 = 1;
 
 /***************************************/
 /*       Public data structures        */
@@ -21,6 +34,17 @@ pub enum Behaviour {
     Error,
 }
 
+// The door as the FSM drives it. The door is still closed while a stopped car levels out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DoorState {
+    #[default]
+    Closed,
+    Open,
+    // Held open by an obstruction
+    Blocked,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Direction {
@@ -29,6 +53,447 @@ pub enum Direction {
     Stop,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrafficMode {
+    #[default]
+    Normal,
+    UpPeak,
+    DownPeak,
+}
+
+// Which hall calls are cleared when a car stops at a floor
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum HallClearing {
+    // Only the call in the direction the car leaves in. Passengers going the other way wait for the car to turn.
+    #[default]
+    InDirection,
+    // Both calls, as everyone waiting boards whichever way the car is going
+    All,
+}
+
+// How many elevators must acknowledge a hall call before it is lit and assigned
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum HallCallConfirmation {
+    // The `hall_call_quorum` elevators, capped at those known
+    #[default]
+    Quorum,
+    // Every elevator known, so a lit call is held by every connected peer
+    AllPeers,
+}
+
+// How hall calls are assigned to the elevators
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum HallRequestAssigner {
+    // Runs the reference executable for every assignment
+    Process,
+    // Runs the reimplementation of the reference cost function in-process
+    #[default]
+    Native,
+    // Runs the WebAssembly module of `assigner_plugin`
+    Wasm,
+    // Gives every call to the closest elevator
+    NearestCar,
+    // Gives the calls to the elevators in turn
+    RoundRobin,
+}
+
+// Why the program ended, told to launch scripts and the supervisor by the exit code. The codes follow sysexits.h.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExitReason {
+    // Ended as asked
+    Clean,
+    // Any other failure, as a module stopping, hanging or not starting
+    Failure,
+    // The self-test of the hardware failed, and is required to pass
+    HardwareFault,
+    // The WebAssembly assigner plugin is missing, or the hall calls cannot be assigned
+    AssignerMissing,
+    // The elevator server could not be reached, or stopped answering
+    HardwareUnreachable,
+    // A thread panicked
+    InternalPanic,
+    // Another instance runs the same elevator
+    AlreadyRunning,
+    // The storage could not be opened or recovered
+    StorageFailed,
+    // A port of the network is taken
+    PortInUse,
+    // The configuration could not be read, or is invalid
+    ConfigInvalid,
+}
+
+// How the supervisor restarts the elevator after it ended for a reason
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    // Restarted at once
+    Immediate,
+    // Restarted after a delay doubling with every restart in a row
+    Backoff,
+    // Not restarted, as restarting would fail the same way
+    GiveUp,
+}
+
+// How the elevators find each other
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PeerDiscovery {
+    // Broadcasts of the ID on the peer port, within the subnet
+    #[default]
+    Broadcast,
+    // Announcements of the service `_elevator._udp` by multicast DNS
+    Mdns,
+}
+
+// Whether the datagrams are encrypted with the pre-shared key, and whether plaintext ones are still accepted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encryption {
+    // Sent in plaintext. Encrypted datagrams are still read with the key, if any.
+    #[default]
+    Plaintext,
+    // Sent encrypted, while plaintext datagrams are still accepted, for a fleet being moved to encryption
+    Mixed,
+    // Sent encrypted, and plaintext datagrams dropped
+    Required,
+}
+
+// What is done about peers announcing another version of the messages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionPolicy {
+    // Logged as an error, while the peer is still merged with
+    #[default]
+    Warn,
+    // Logged as an error, and the peer left out of the fleet until it runs the same version of the messages
+    Refuse,
+}
+
+// What a channel between the modules does with a message sent while it is full
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    // The sender waits until the receiver makes room
+    #[default]
+    Block,
+    // The oldest message waiting is dropped to make room
+    DropOldest,
+    // Only the latest message waits, for state that is replaced by the next
+    CoalesceLatest,
+}
+
+// The health of the elevator as a whole, shown on the panel to someone standing at it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SystemStatus {
+    // In service, and in touch with at least one peer
+    Connected,
+    // In service alone, as no peer is reached. Also until the status is first known.
+    #[default]
+    Offline,
+    // In the error state or with the obstruction alarm raised, or with a coordinator that does not answer
+    Error,
+    // Taken out of service by an operator, in independent service or halted
+    Maintenance,
+}
+
+// How long a service report covers, starting on the hour or at midnight, local time
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportPeriod {
+    #[default]
+    Hourly,
+    Daily,
+}
+
+// Where the hall calls are assigned
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssignmentModel {
+    // Every coordinator assigns all the calls, and serves its own share
+    #[default]
+    Independent,
+    // One coordinator assigns all the calls, and pushes the assignment to the others with the data
+    Push,
+}
+
+// Where the state of the elevator is kept across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageBackend {
+    // A file per concern, each written on its own
+    #[default]
+    Files,
+    // One SQLite database, written a transaction at a time. Needs the `sqlite` feature.
+    Sqlite,
+}
+
+// Service mode of a single elevator. Only elevators in normal service take hall calls.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperatingMode {
+    #[default]
+    Normal,
+    // Fire service phase 1: calls are cancelled and the car waits at the recall floor with the door open
+    FireRecall(u8),
+    // Fire service phase 2: the car only answers cab calls, and the door is operated by command
+    FirePhase2,
+    // Independent service: the car only answers its own cab calls
+    Independent,
+}
+
+// The view of the coordinator, answered to queries from the control interfaces
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub id: String,
+    pub peers: Vec<String>,
+    #[serde(rename = "elevatorData")]
+    pub elevator_data: ElevatorData,
+    #[serde(rename = "confirmedHallRequests")]
+    pub confirmed_hall_requests: Vec<Vec<bool>>,
+    #[serde(default)]
+    pub network: NetworkStatistics,
+    // The number of the last order mutation in the write-ahead log the snapshot includes
+    #[serde(rename = "walSequence", default)]
+    pub wal_sequence: u64,
+    // Hall calls the local coordinator moved from one elevator to another since start
+    #[serde(default)]
+    pub reassignments: u64,
+    #[serde(default)]
+    pub uptime: Uptime,
+}
+
+// The restarts and the uptime of an elevator, counted across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Uptime {
+    // Starts of the process after the first
+    pub restarts: u64,
+    // Restarts after a run that did not end cleanly, as when killed by a watchdog or stopped by a hung module
+    #[serde(rename = "watchdogResets")]
+    pub watchdog_resets: u64,
+    // Seconds run, over all the starts
+    pub seconds: u64,
+}
+
+// The build of an elevator, as announced to its peers
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerVersion {
+    #[serde(rename = "crateVersion")]
+    pub crate_version: String,
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: u32,
+}
+
+impl PeerVersion {
+    // The build of the local elevator
+    pub fn local() -> PeerVersion {
+        PeerVersion { crate_version: env!("CARGO_PKG_VERSION").to_string(), schema_version: SCHEMA_VERSION }
+    }
+
+    // Whether the messages of the peer are read as they were meant
+    pub fn compatible(&self) -> bool {
+        self.schema_version == SCHEMA_VERSION
+    }
+}
+
+// As announced, `<crate version>/<schema version>`
+impl fmt::Display for PeerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.crate_version, self.schema_version)
+    }
+}
+
+// Datagrams handled by the network since start
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct NetworkStatistics {
+    #[serde(rename = "messagesSent")]
+    pub messages_sent: u64,
+    #[serde(rename = "messagesReceived")]
+    pub messages_received: u64,
+    // Malformed, or addressed to another elevator
+    #[serde(rename = "messagesDropped")]
+    pub messages_dropped: u64,
+    #[serde(rename = "sendFailures")]
+    pub send_failures: u64,
+    // Sent in fragments, as too large for one datagram
+    #[serde(rename = "messagesFragmented", default)]
+    pub messages_fragmented: u64,
+    // Sent again, as asked for by the peer that found them missing
+    #[serde(rename = "messagesRetransmitted", default)]
+    pub messages_retransmitted: u64,
+    // Dropped, as over the rate budget
+    #[serde(rename = "messagesShaped", default)]
+    pub messages_shaped: u64,
+    // Stood down, as another node broadcasts the same ID
+    #[serde(default)]
+    pub quarantined: bool,
+    // The link measured to every peer heard from, by ID
+    #[serde(default)]
+    pub links: BTreeMap<String, PeerLink>,
+}
+
+// The link to one peer: the numbered messages received from it and found missing since start, the fraction of them
+// missing, the smoothed round trip of the pulls to it in milliseconds, unknown until one has been answered, and how
+// far its clock is ahead of the local one in milliseconds, unknown until it has sent a digest
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PeerLink {
+    pub received: u64,
+    pub missing: u64,
+    pub loss: f64,
+    #[serde(rename = "roundTrip")]
+    pub round_trip: Option<f64>,
+    #[serde(rename = "clockOffset", default)]
+    pub clock_offset: Option<f64>,
+}
+
+// The loss and round-trip time measured by the network, smoothed over the recent samples. The loss is the fraction
+// of the messages from the peers found missing, and the round trip is unknown until a pull has been answered.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkQuality {
+    pub loss: f64,
+    pub round_trip: Option<std::time::Duration>,
+}
+
+// A snapshot written out for verification scripts, with when it was taken and the state of the local car
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateExport {
+    #[serde(rename = "exportedAt")]
+    pub exported_at: String,
+    pub fsm: Option<ElevatorState>,
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+}
+
+// Timings of the FSM changed while running, in milliseconds. `None` keeps the timing as it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FsmTimings {
+    pub door_open_time: Option<u64>,
+    pub door_timeout: Option<u64>,
+    pub motor_timeout: Option<u64>,
+}
+
+// The internal view of the FSM, answered to inspection queries. The timers are the milliseconds left, or `None`
+// when not running.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FsmView {
+    pub state: ElevatorState,
+    #[serde(rename = "hallRequests")]
+    pub hall_requests: Vec<Vec<bool>>,
+    pub obstruction: bool,
+    #[serde(rename = "parkingFloor")]
+    pub parking_floor: Option<u8>,
+    #[serde(rename = "doorTimer")]
+    pub door_timer: Option<u64>,
+    #[serde(rename = "obstructionTimer")]
+    pub obstruction_timer: Option<u64>,
+    #[serde(rename = "motorTimer")]
+    pub motor_timer: Option<u64>,
+    #[serde(rename = "levellingTimer")]
+    pub levelling_timer: Option<u64>,
+}
+
+// Operator commands, entered on the debug console
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    // Overrides the scheduled traffic mode on the whole fleet. `None` returns to the schedule.
+    SetTrafficMode(Option<TrafficMode>),
+    // Starts or resets fire service phase 1 on the whole fleet
+    SetFireRecall(bool),
+    // Puts the local car in or out of fire service phase 2
+    SetFirePhase2(bool),
+    // Opens or closes the door of the local car in fire service phase 2
+    Door(bool),
+    // Puts an elevator in or out of independent service. `None` is the local elevator.
+    SetIndependentService(Option<String>, bool),
+    // Switches the whole fleet to a zoning profile, by name, or back to the zones of `[access]` if `None`
+    SetZoningProfile(Option<String>),
+    // Prints the state of every elevator in the fleet
+    ShowFleet,
+    // Prints the round trip and loss measured to every peer
+    ShowLinks,
+    // Prints the internal view of the local FSM, next to the hall calls the coordinator assigned it
+    ShowQueue,
+    // Places or clears a hall call as if its button were pressed. Clearing cancels the call.
+    HallCall(u8, u8, bool),
+    // Places a cab call at the local elevator as if its button were pressed
+    CabCall(u8),
+    // Writes the state of the elevator as JSON to a file, or to standard output if `None`
+    ExportState(Option<String>),
+    // Returns the local car to service after an obstruction alarm, once the door has been checked
+    ClearObstructionAlarm,
+    // Holds the local car once it has finished its current stop, or returns it to service
+    Halt(bool),
+}
+
+// Why a module stopped running, returned by its `run` for the supervisor to act on
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleError {
+    // A channel to another module was disconnected, as when that module stopped. Named by its field.
+    Disconnected(String),
+    // The hall calls could not be assigned
+    Assigner(String),
+}
+
+impl ExitReason {
+    pub const ALL: [ExitReason; 10] = [
+        ExitReason::Clean,
+        ExitReason::Failure,
+        ExitReason::HardwareFault,
+        ExitReason::AssignerMissing,
+        ExitReason::HardwareUnreachable,
+        ExitReason::InternalPanic,
+        ExitReason::AlreadyRunning,
+        ExitReason::StorageFailed,
+        ExitReason::PortInUse,
+        ExitReason::ConfigInvalid,
+    ];
+
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitReason::Clean => 0,
+            ExitReason::Failure => 1,
+            ExitReason::HardwareFault => 65,
+            ExitReason::AssignerMissing => 66,
+            ExitReason::HardwareUnreachable => 69,
+            ExitReason::InternalPanic => 70,
+            ExitReason::AlreadyRunning => 73,
+            ExitReason::StorageFailed => 74,
+            ExitReason::PortInUse => 75,
+            ExitReason::ConfigInvalid => 78,
+        }
+    }
+
+    // The reason of an exit code, None for a code of no reason
+    pub fn from_code(code: i32) -> Option<ExitReason> {
+        ExitReason::ALL.into_iter().find(|reason| reason.code() == code)
+    }
+
+    // The policy applied unless configured otherwise
+    pub fn default_restart_policy(&self) -> RestartPolicy {
+        match self {
+            ExitReason::Failure | ExitReason::InternalPanic => RestartPolicy::Immediate,
+            ExitReason::HardwareUnreachable | ExitReason::StorageFailed | ExitReason::PortInUse => RestartPolicy::Backoff,
+            ExitReason::Clean
+            | ExitReason::HardwareFault
+            | ExitReason::AssignerMissing
+            | ExitReason::AlreadyRunning
+            | ExitReason::ConfigInvalid => RestartPolicy::GiveUp,
+        }
+    }
+}
+
+impl std::fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = serde_json::to_value(self).ok().and_then(|name| name.as_str().map(str::to_string)).unwrap_or_default();
+        write!(f, "{} ({})", name, self.code())
+    }
+}
+
 impl Direction {
     pub fn to_u8(&self) -> u8 {
         match *self {
@@ -57,6 +522,37 @@ pub struct ElevatorState {
     pub direction: Direction,
     #[serde(rename = "cabRequests")]
     pub cab_requests: Vec<bool>,
+    #[serde(default)]
+    pub mode: OperatingMode,
+    // The floors the car will stop at, in order. Published by the FSM, and empty from older elevators.
+    #[serde(rename = "stopPlan", default)]
+    pub stop_plan: Vec<u8>,
+    // Raised after repeated obstruction timeouts. The car stays out of service until the alarm is cleared.
+    #[serde(rename = "obstructionAlarm", default)]
+    pub obstruction_alarm: bool,
+    // Closed from older elevators, which do not report their door
+    #[serde(default)]
+    pub door: DoorState,
+    // Held by a remote halt command. A halted car takes no hall calls.
+    #[serde(default)]
+    pub halted: bool,
+    // The most hall calls the car is assigned at once, from its configuration. 0 for no limit.
+    #[serde(default)]
+    pub capacity: usize,
+    // Milliseconds from when the state was published until the car arrives at each planned stop. Empty without
+    // a motion model, and from older elevators.
+    #[serde(rename = "arrivalTimes", default)]
+    pub arrival_times: Vec<u64>,
+    // When the cab call at each floor was last served, in seconds since the Unix epoch, or 0 if never. Empty from
+    // older elevators.
+    #[serde(rename = "cabCompletionTimes", default)]
+    pub cab_completion_times: Vec<u64>,
+    // The restarts and the uptime of the elevator, as of the last change of its state. Zero from older elevators.
+    #[serde(default)]
+    pub uptime: Uptime,
+    // Deciding in a dry run without acting. The peers assign it no hall calls. False from older elevators.
+    #[serde(rename = "dryRun", default)]
+    pub dry_run: bool,
 }
 
 
@@ -67,16 +563,64 @@ impl ElevatorState {
             floor: 0,
             direction: Direction::Stop,
             cab_requests: vec![false; n_floors as usize],
+            mode: OperatingMode::Normal,
+            stop_plan: Vec::new(),
+            obstruction_alarm: false,
+            door: DoorState::Closed,
+            halted: false,
+            capacity: 0,
+            arrival_times: Vec::new(),
+            cab_completion_times: Vec::new(),
+            uptime: Uptime::default(),
+            dry_run: false,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ElevatorData {
+    // The total of the clock, for the peers sending no clock. Their data is compared by it, and no other.
     pub version: u64,
+    // The updates seen from every elevator, telling concurrent data from outdated data
+    #[serde(default)]
+    pub clock: VectorClock,
     #[serde(rename = "hallRequests")]
     pub hall_requests: Vec<Vec<bool>>,
     pub states: HashMap<String, ElevatorState>,
+    #[serde(rename = "trafficMode", default)]
+    pub traffic_mode: Option<TrafficMode>,
+    // The zoning profile in force on the fleet, None for the zones of `[access]`
+    #[serde(rename = "zoningProfile", default)]
+    pub zoning_profile: Option<String>,
+    #[serde(rename = "fireRecall", default)]
+    pub fire_recall: bool,
+    #[serde(rename = "independentService", default)]
+    pub independent_service: BTreeSet<String>,
+    #[serde(rename = "hallCancellations", default)]
+    pub hall_cancellations: Vec<Vec<u64>>,
+    // The state of every hall call, as a counter of the steps it has taken. Packets of older peers have none.
+    #[serde(rename = "hallOrders", default)]
+    pub hall_orders: Vec<Vec<HallOrder>>,
+    #[serde(rename = "hallAcknowledgements", default)]
+    pub hall_acknowledgements: Vec<Vec<BTreeSet<String>>>,
+    #[serde(rename = "hallRequestTimes", default)]
+    pub hall_request_times: Vec<Vec<u64>>,
+    #[serde(rename = "hallCompletionTimes", default)]
+    pub hall_completion_times: Vec<Vec<u64>>,
+    #[serde(rename = "hallAssignment", default)]
+    pub hall_assignment: Option<HallAssignment>,
+    // The hall calls each elevator serves, as last given to its FSM, by ID. Elevators serving none are left out.
+    #[serde(rename = "hallOwners", default)]
+    pub hall_owners: BTreeMap<String, Vec<Vec<bool>>>,
+}
+
+// The hall calls of every elevator, as assigned by the assigning coordinator in the push model
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HallAssignment {
+    pub assigner: String,
+    // The version of the data the calls were assigned from, so a newer assignment replaces an older one
+    pub version: u64,
+    pub elevators: BTreeMap<String, Vec<Vec<bool>>>,
 }
 
 impl ElevatorData {
@@ -87,8 +631,59 @@ impl ElevatorData {
 
         ElevatorData {
             version: 0,
+            clock: VectorClock::default(),
             hall_requests,
             states: HashMap::new(),
+            traffic_mode: None,
+            zoning_profile: None,
+            fire_recall: false,
+            independent_service: BTreeSet::new(),
+            hall_cancellations: vec![vec![0; 2]; n_floors as usize],
+            hall_orders: vec![vec![HallOrder::default(); 2]; n_floors as usize],
+            hall_acknowledgements: vec![vec![BTreeSet::new(); 2]; n_floors as usize],
+            hall_request_times: vec![vec![0; 2]; n_floors as usize],
+            hall_completion_times: vec![vec![0; 2]; n_floors as usize],
+            hall_assignment: None,
+            hall_owners: BTreeMap::new(),
         }
     }
-}
\ No newline at end of file
+
+    // Records the hall calls an elevator serves
+    pub fn set_hall_owner(&mut self, id: &str, hall_requests: &[Vec<bool>]) {
+        match hall_requests.iter().flatten().any(|request| *request) {
+            true => self.hall_owners.insert(id.to_string(), hall_requests.to_vec()),
+            false => self.hall_owners.remove(id),
+        };
+    }
+
+    // Moves the times the calls were placed and served by the seconds, as from the clock of another node to the local
+    // one. Times never set, at 0, stay unset.
+    pub fn shift_times(&mut self, seconds: i64) {
+        let shift = |time: &mut u64| {
+            if *time > 0 {
+                *time = time.saturating_add_signed(seconds).max(1);
+            }
+        };
+        self.hall_request_times.iter_mut().chain(self.hall_completion_times.iter_mut()).flatten().for_each(shift);
+        self.states.values_mut().flat_map(|state| state.cab_completion_times.iter_mut()).for_each(shift);
+    }
+
+    // The elevators serving a hall call, in order of ID. More than one may serve it while it is reassigned, or
+    // while serving conservatively.
+    pub fn hall_call_owners(&self, floor: u8, call: u8) -> Vec<String> {
+        self.hall_owners
+            .iter()
+            .filter(|(_, owned)| owned.get(floor as usize).and_then(|calls| calls.get(call as usize)).copied().unwrap_or(false))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModuleError::Disconnected(channel) => write!(f, "The channel {} was disconnected", channel),
+            ModuleError::Assigner(e) => write!(f, "The hall calls could not be assigned: {}", e),
+        }
+    }
+}
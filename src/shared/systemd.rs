@@ -0,0 +1,144 @@
+/**
+ * Readiness and watchdog notifications to systemd, so a hung elevator process is restarted on the lab machines.
+ *
+ * Built with the `systemd` feature and started by a unit of `Type=notify`, the process finds the socket of systemd
+ * in `NOTIFY_SOCKET`, and the watchdog timeout set by `WatchdogSec=` in `WATCHDOG_USEC`. The health monitor drives
+ * the notifications: `READY=1` is sent once, when the coordinator first answers it, and `WATCHDOG=1` at half the
+ * watchdog timeout for as long as the coordinator keeps answering. A hung coordinator, or a dead health monitor,
+ * stops the keepalives, and systemd kills and restarts the process. On the panel the health monitor still shows the
+ * hung coordinator as an error until then. Without the feature, or when not started by systemd, nothing is sent.
+ *
+ * # Fields
+ * - `socket`:                  The socket notifications are sent on, and the address of systemd. None sends nothing.
+ * - `watchdog_interval`:       Time between the keepalives. None if the watchdog is off.
+ * - `ready`:                   Whether readiness is notified.
+ * - `last_keepalive`:          When the last keepalive was sent.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use log::{info, warn};
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct SystemdNotifier {
+    socket: Option<(UnixDatagram, SocketAddr)>,
+    watchdog_interval: Option<Duration>,
+    ready: bool,
+    last_keepalive: Option<Instant>,
+}
+
+impl SystemdNotifier {
+    // The notifier of the systemd unit the process runs in, doing nothing when not started by systemd
+    #[cfg(feature = "systemd")]
+    pub fn from_env() -> SystemdNotifier {
+        let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+            return SystemdNotifier::disabled();
+        };
+        // The watchdog is for this process only, not one started by it
+        let watchdog_pid = std::env::var("WATCHDOG_PID").ok();
+        let watchdog_usec = std::env::var("WATCHDOG_USEC")
+            .ok()
+            .filter(|_| watchdog_pid.is_none_or(|pid| pid == std::process::id().to_string()));
+        match SystemdNotifier::new(&path, watchdog_usec.as_deref()) {
+            Ok(notifier) => notifier,
+            Err(e) => {
+                warn!("{}. Nothing is notified to systemd.", e);
+                SystemdNotifier::disabled()
+            }
+        }
+    }
+
+    #[cfg(not(feature = "systemd"))]
+    pub fn from_env() -> SystemdNotifier {
+        if std::env::var_os("NOTIFY_SOCKET").is_some() {
+            warn!("Started by systemd, but the program is built without the systemd feature. Nothing is notified.");
+        }
+        SystemdNotifier::disabled()
+    }
+
+    pub fn disabled() -> SystemdNotifier {
+        SystemdNotifier { socket: None, watchdog_interval: None, ready: false, last_keepalive: None }
+    }
+
+    // Notifies the socket at `path`, abstract if it starts with '@', and keeps alive a watchdog timing out after
+    // `watchdog_usec` microseconds
+    pub fn new(path: &str, watchdog_usec: Option<&str>) -> Result<SystemdNotifier, String> {
+        let address = match path.strip_prefix('@') {
+            Some(name) => abstract_address(name)?,
+            None => SocketAddr::from_pathname(path).map_err(|e| format!("Invalid NOTIFY_SOCKET {}: {}", path, e))?,
+        };
+        let socket = UnixDatagram::unbound().map_err(|e| format!("Failed to open a socket to systemd: {}", e))?;
+        let watchdog_interval = watchdog_usec.and_then(watchdog_interval);
+        if let Some(interval) = watchdog_interval {
+            info!("Keeping the systemd watchdog alive every {:?}", interval);
+        }
+        Ok(SystemdNotifier { socket: Some((socket, address)), watchdog_interval, ready: false, last_keepalive: None })
+    }
+
+    // Notifies systemd that the elevator is up, once
+    pub fn ready(&mut self) {
+        if self.ready || self.socket.is_none() {
+            return;
+        }
+        info!("Notifying systemd that the elevator is ready");
+        self.notify("READY=1");
+        self.ready = true;
+    }
+
+    // Keeps the watchdog alive, if a keepalive is due at `now`
+    pub fn keepalive(&mut self, now: Instant) {
+        let Some(interval) = self.watchdog_interval else { return };
+        if self.last_keepalive.is_some_and(|last| now.saturating_duration_since(last) < interval) {
+            return;
+        }
+        self.notify("WATCHDOG=1");
+        self.last_keepalive = Some(now);
+    }
+
+    fn notify(&self, state: &str) {
+        if let Some((socket, address)) = &self.socket {
+            if let Err(e) = socket.send_to_addr(state.as_bytes(), address) {
+                warn!("Failed to notify systemd of {}: {}", state, e);
+            }
+        }
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Half the watchdog timeout, as systemd recommends. None if the watchdog is off or the timeout invalid.
+fn watchdog_interval(watchdog_usec: &str) -> Option<Duration> {
+    match watchdog_usec.trim().parse::<u64>() {
+        Ok(usec) if usec > 0 => Some(Duration::from_micros(usec / 2)),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_address(name: &str) -> Result<SocketAddr, String> {
+    use std::os::linux::net::SocketAddrExt;
+    SocketAddr::from_abstract_name(name).map_err(|e| format!("Invalid NOTIFY_SOCKET @{}: {}", name, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_address(name: &str) -> Result<SocketAddr, String> {
+    Err(format!("The abstract NOTIFY_SOCKET @{} is only supported on Linux", name))
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use std::time::Duration;
+
+    pub fn test_watchdog_interval(watchdog_usec: &str) -> Option<Duration> {
+        super::watchdog_interval(watchdog_usec)
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ * Unit tests for the notifications to systemd
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_systemd_ready_and_keepalives
+ *  - test_systemd_watchdog_interval
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod systemd_tests {
+    use crate::shared::systemd::testing::test_watchdog_interval;
+    use crate::shared::SystemdNotifier;
+    use std::os::unix::net::UnixDatagram;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_systemd_ready_and_keepalives() {
+        // Arrange
+        let path = std::env::temp_dir().join(format!("systemd_notify_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd.set_nonblocking(true).unwrap();
+        let mut notifier = SystemdNotifier::new(path.to_str().unwrap(), Some("2000000")).unwrap();
+        let mut without_watchdog = SystemdNotifier::new(path.to_str().unwrap(), None).unwrap();
+        let start = Instant::now();
+
+        // Act
+        notifier.ready();
+        notifier.ready();
+        notifier.keepalive(start);
+        notifier.keepalive(start + Duration::from_millis(500));
+        notifier.keepalive(start + Duration::from_secs(1));
+        without_watchdog.keepalive(start);
+        SystemdNotifier::disabled().ready();
+        let mut buffer = [0; 64];
+        let mut notified = Vec::new();
+        while let Ok(length) = systemd.recv(&mut buffer) {
+            notified.push(String::from_utf8_lossy(&buffer[..length]).to_string());
+        }
+        let _ = std::fs::remove_file(&path);
+
+        // Assert
+        // Readiness is notified once, and the keepalives at half the timeout of the watchdog
+        assert_eq!(notified, vec!["READY=1", "WATCHDOG=1", "WATCHDOG=1"]);
+    }
+
+    #[test]
+    fn test_systemd_watchdog_interval() {
+        // Act / Assert
+        assert_eq!(test_watchdog_interval("10000000"), Some(Duration::from_secs(5)));
+        assert_eq!(test_watchdog_interval(" 3000000\n"), Some(Duration::from_millis(1500)));
+        // A timeout of zero turns the watchdog off
+        assert_eq!(test_watchdog_interval("0"), None);
+        assert_eq!(test_watchdog_interval("ten"), None);
+    }
+}
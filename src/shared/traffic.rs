@@ -0,0 +1,80 @@
+/**
+ * Up-peak/down-peak traffic modes.
+ *
+ * In up-peak, idle elevators park at the lobby and up calls from the lobby are assigned with
+ * priority. In down-peak, idle elevators park at the down-peak floor (the top floor unless
+ * configured) and down calls, which carry passengers towards the lobby, get priority.
+ * The mode follows the configured schedule, falling back to the configured default mode.
+ *
+ * # Fields
+ * - `mode`:                The mode used outside the scheduled windows.
+ * - `lobby_floor`:         Parking floor in up-peak.
+ * - `down_peak_floor`:     Parking floor in down-peak.
+ * - `schedule`:            Daily windows with their traffic mode.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::NaiveTime;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::TrafficConfig;
+use crate::shared::schedule::TimeWindow;
+use crate::shared::TrafficMode;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrafficPolicy {
+    mode: TrafficMode,
+    lobby_floor: u8,
+    down_peak_floor: u8,
+    schedule: Vec<(TrafficMode, TimeWindow)>,
+}
+
+impl TrafficPolicy {
+    pub fn new(traffic_config: &TrafficConfig, n_floors: u8) -> TrafficPolicy {
+        TrafficPolicy {
+            mode: traffic_config.mode,
+            lobby_floor: traffic_config.lobby_floor,
+            down_peak_floor: traffic_config.down_peak_floor.unwrap_or(n_floors.saturating_sub(1)),
+            schedule: traffic_config
+                .schedule
+                .iter()
+                .map(|entry| (entry.mode, TimeWindow::parse(&entry.start, &entry.end)))
+                .collect(),
+        }
+    }
+
+    // The first scheduled window active at the given time of day decides the mode
+    pub fn scheduled_mode(&self, time: NaiveTime) -> TrafficMode {
+        self.schedule
+            .iter()
+            .find(|(_, window)| window.is_active(time))
+            .map(|(mode, _)| *mode)
+            .unwrap_or(self.mode)
+    }
+
+    // Floor idle elevators should return to, if any
+    pub fn parking_floor(&self, mode: TrafficMode) -> Option<u8> {
+        match mode {
+            TrafficMode::Normal => None,
+            TrafficMode::UpPeak => Some(self.lobby_floor),
+            TrafficMode::DownPeak => Some(self.down_peak_floor),
+        }
+    }
+
+    // Returns true if the hall call should be assigned ahead of the others
+    pub fn is_priority_call(&self, mode: TrafficMode, floor: u8, call: u8) -> bool {
+        match mode {
+            TrafficMode::Normal => false,
+            TrafficMode::UpPeak => floor == self.lobby_floor && call == HALL_UP,
+            TrafficMode::DownPeak => floor != self.lobby_floor && call == HALL_DOWN,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+/*
+ * Unit tests for the traffic policy
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_traffic_policy_scheduled_mode
+ *  - test_traffic_policy_parking_floor
+ *  - test_traffic_policy_priority_calls
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod traffic_tests {
+    use crate::config::{TrafficConfig, TrafficScheduleConfig};
+    use crate::shared::{TrafficMode, TrafficPolicy};
+    use chrono::NaiveTime;
+    use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn policy() -> TrafficPolicy {
+        TrafficPolicy::new(
+            &TrafficConfig {
+                mode: TrafficMode::Normal,
+                lobby_floor: 0,
+                down_peak_floor: None,
+                schedule: vec![
+                    TrafficScheduleConfig {
+                        mode: TrafficMode::UpPeak,
+                        start: "07:30".to_string(),
+                        end: "09:30".to_string(),
+                    },
+                    TrafficScheduleConfig {
+                        mode: TrafficMode::DownPeak,
+                        start: "15:30".to_string(),
+                        end: "17:00".to_string(),
+                    },
+                ],
+            },
+            4,
+        )
+    }
+
+    #[test]
+    fn test_traffic_policy_scheduled_mode() {
+        // Arrange
+        let policy = policy();
+
+        // Act / Assert
+        assert_eq!(policy.scheduled_mode(time(7, 0)), TrafficMode::Normal);
+        assert_eq!(policy.scheduled_mode(time(8, 0)), TrafficMode::UpPeak);
+        assert_eq!(policy.scheduled_mode(time(12, 0)), TrafficMode::Normal);
+        assert_eq!(policy.scheduled_mode(time(16, 0)), TrafficMode::DownPeak);
+        assert_eq!(TrafficPolicy::default().scheduled_mode(time(8, 0)), TrafficMode::Normal);
+    }
+
+    #[test]
+    fn test_traffic_policy_parking_floor() {
+        // Arrange
+        let policy = policy();
+
+        // Act / Assert
+        assert_eq!(policy.parking_floor(TrafficMode::Normal), None);
+        assert_eq!(policy.parking_floor(TrafficMode::UpPeak), Some(0));
+        assert_eq!(policy.parking_floor(TrafficMode::DownPeak), Some(3));
+    }
+
+    #[test]
+    fn test_traffic_policy_priority_calls() {
+        // Arrange
+        let policy = policy();
+
+        // Act / Assert
+        assert!(!policy.is_priority_call(TrafficMode::Normal, 0, HALL_UP));
+        assert!(policy.is_priority_call(TrafficMode::UpPeak, 0, HALL_UP));
+        assert!(!policy.is_priority_call(TrafficMode::UpPeak, 2, HALL_UP));
+        assert!(policy.is_priority_call(TrafficMode::DownPeak, 2, HALL_DOWN));
+        assert!(!policy.is_priority_call(TrafficMode::DownPeak, 0, HALL_DOWN));
+        assert!(!policy.is_priority_call(TrafficMode::DownPeak, 2, HALL_UP));
+    }
+}
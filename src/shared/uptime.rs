@@ -0,0 +1,100 @@
+/**
+ * The restarts and the uptime of the local elevator, counted across restarts of its process.
+ *
+ * The counters are loaded from the storage at start-up. A start after a saved run counts as a restart, and a start
+ * after a run still saved as running, that is one that was killed or hung instead of ending cleanly, counts as a
+ * watchdog reset too. While running, the counters are saved as running every `SAVE_INTERVAL`, so the uptime lost to
+ * a crash is at most that. A clean shutdown saves them as not running. Without a storage, the counters start from 0
+ * and are kept in memory only.
+ *
+ * # Fields
+ * - `storage`:                 The storage the counters are kept in, if any.
+ * - `counted`:                 The counters as of the start of the process.
+ * - `started`:                 When the process started.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crossbeam_channel as cbc;
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::shared::Uptime;
+use crate::storage::{Record, Storage};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct UptimeCounter {
+    storage: Option<Arc<dyn Storage>>,
+    counted: Uptime,
+    started: Instant,
+}
+
+impl Default for UptimeCounter {
+    fn default() -> UptimeCounter {
+        UptimeCounter { storage: None, counted: Uptime::default(), started: Instant::now() }
+    }
+}
+
+impl UptimeCounter {
+    // Counts the start of the process on the counters of the storage, and saves them as running
+    pub fn start(storage: Arc<dyn Storage>) -> UptimeCounter {
+        let mut counted = Uptime::default();
+        match storage.load_uptime() {
+            Ok(Some((uptime, running))) => {
+                counted = Uptime { restarts: uptime.restarts + 1, ..uptime };
+                if running {
+                    counted.watchdog_resets += 1;
+                    warn!("The last run did not end cleanly, counting a watchdog reset");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load the uptime counters, starting them over: {}", e),
+        }
+        info!("Started after {} restarts and {} watchdog resets", counted.restarts, counted.watchdog_resets);
+
+        let counter = UptimeCounter { storage: Some(storage), counted, started: Instant::now() };
+        counter.save();
+        counter
+    }
+
+    // The counters, with the uptime of the current run
+    pub fn uptime(&self) -> Uptime {
+        Uptime { seconds: self.counted.seconds + self.started.elapsed().as_secs(), ..self.counted }
+    }
+
+    // Saves the counters as running
+    pub fn save(&self) {
+        self.write(true);
+    }
+
+    // Saves the counters as not running, as the process ends cleanly
+    pub fn stop(&self) {
+        self.write(false);
+    }
+
+    // Saves the counters every `SAVE_INTERVAL`, for as long as the process runs
+    pub fn run(self: Arc<Self>) {
+        for _ in cbc::tick(SAVE_INTERVAL).iter() {
+            self.save();
+        }
+    }
+
+    fn write(&self, running: bool) {
+        let Some(storage) = &self.storage else { return };
+        if let Err(e) = storage.save(&[Record::Uptime(self.uptime(), running)]) {
+            warn!("Failed to save the uptime counters: {}", e);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * Unit tests for the uptime counter
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * The counters are kept in a temporary directory.
+ *
+ * Tests:
+ *  - test_uptime_restarts
+ *  - test_uptime_in_memory
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod uptime_tests {
+    use crate::shared::{Uptime, UptimeCounter};
+    use crate::storage::{FileStorage, LogRetention, Record, Storage};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_uptime_restarts() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("uptime_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage: Arc<dyn Storage> = Arc::new(FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default()));
+        storage.save(&[Record::Uptime(Uptime { restarts: 0, watchdog_resets: 0, seconds: 100 }, false)]).unwrap();
+
+        // Act
+        let clean = UptimeCounter::start(storage.clone()).uptime();
+        let killed = UptimeCounter::start(storage.clone());
+        let reset = killed.uptime();
+        killed.stop();
+        let stopped = storage.load_uptime();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // The first counter was never stopped, as if the process was killed
+        assert_eq!(clean, Uptime { restarts: 1, watchdog_resets: 0, seconds: 100 });
+        assert_eq!(reset, Uptime { restarts: 2, watchdog_resets: 1, seconds: 100 });
+        assert_eq!(stopped, Ok(Some((reset, false))));
+    }
+
+    #[test]
+    fn test_uptime_in_memory() {
+        // Arrange
+        let counter = UptimeCounter::default();
+
+        // Act
+        counter.save();
+        let uptime = counter.uptime();
+
+        // Assert
+        assert_eq!(uptime, Uptime::default());
+    }
+}
@@ -0,0 +1,103 @@
+/**
+ * Vector clocks versioning the elevator data.
+ *
+ * A single version counts the updates, but not whose they are. Two partitions that each update their data arrive at
+ * versions that compare, and the one counting more updates replaces the other, losing its updates. The vector clock
+ * counts the updates transmitted by every elevator instead. Data whose clock has seen every update of the other is
+ * ahead of it. Data whose clocks have each seen updates the other has not are concurrent, and are merged.
+ *
+ * The version of the data, the single count of updates sent to peers that send no clock and compared in the digests
+ * of anti-entropy, is the total of the clock. Only data from peers that send no clock is merged by version.
+ *
+ * Concurrent data is merged the same on every elevator. Where the merge must pick one side, it picks the side that
+ * goes first in the total order of `precedes`, whichever side is local.
+ *
+ * # Fields
+ * - `counters`:                The updates transmitted by every elevator, by ID. Elevators that transmitted none are left out.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+// How the updates seen by two clocks compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Causality {
+    Before,
+    Equal,
+    After,
+    Concurrent,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct VectorClock {
+    counters: BTreeMap<String, u64>,
+}
+
+impl VectorClock {
+    // Counts an update transmitted by the elevator
+    pub fn tick(&mut self, id: &str) {
+        *self.counters.entry(id.to_string()).or_default() += 1;
+    }
+
+    // The updates transmitted by the elevator
+    pub fn get(&self, id: &str) -> u64 {
+        self.counters.get(id).copied().unwrap_or(0)
+    }
+
+    // Counts the updates seen by either clock
+    pub fn join(&mut self, other: &VectorClock) {
+        for (id, counter) in other.counters.iter() {
+            let joined = self.counters.entry(id.clone()).or_default();
+            *joined = (*joined).max(*counter);
+        }
+    }
+
+    // Whether this clock has seen the updates of the other, and the other those of this one
+    pub fn compare(&self, other: &VectorClock) -> Causality {
+        let ids = self.counters.keys().chain(other.counters.keys());
+        let (mut behind, mut ahead) = (false, false);
+        for id in ids {
+            match self.get(id).cmp(&other.get(id)) {
+                Ordering::Less => behind = true,
+                Ordering::Greater => ahead = true,
+                Ordering::Equal => {}
+            }
+        }
+        match (behind, ahead) {
+            (false, false) => Causality::Equal,
+            (true, false) => Causality::Before,
+            (false, true) => Causality::After,
+            (true, true) => Causality::Concurrent,
+        }
+    }
+
+    // Whether the side of this clock goes first where a merge of concurrent data must pick one: the side that has seen
+    // the most updates, and then the one with the greater counters by ID. Every elevator orders two clocks the same.
+    pub fn precedes(&self, other: &VectorClock) -> bool {
+        self.total().cmp(&other.total()).then_with(|| self.counters.cmp(&other.counters)) == Ordering::Greater
+    }
+
+    // The updates transmitted by all the elevators
+    pub fn total(&self) -> u64 {
+        self.counters.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.counters.len()
+    }
+}
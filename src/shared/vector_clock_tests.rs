@@ -0,0 +1,67 @@
+/*
+ * Unit tests for the vector clocks versioning the elevator data
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ *
+ * Tests:
+ *  - test_vector_clock_causality
+ *  - test_vector_clock_precedence
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod vector_clock_tests {
+    use crate::shared::{Causality, VectorClock};
+
+    fn clock(ticks: &[&str]) -> VectorClock {
+        let mut clock = VectorClock::default();
+        for id in ticks {
+            clock.tick(id);
+        }
+        clock
+    }
+
+    #[test]
+    fn test_vector_clock_causality() {
+        // Arrange
+        let shared = clock(&["a", "b"]);
+        let mut partition_a = shared.clone();
+        partition_a.tick("a");
+        let mut partition_b = shared.clone();
+        partition_b.tick("b");
+        partition_b.tick("b");
+
+        // Act
+        let mut healed = partition_a.clone();
+        healed.join(&partition_b);
+
+        // Assert
+        assert_eq!(partition_a.compare(&shared), Causality::After);
+        assert_eq!(shared.compare(&partition_a), Causality::Before);
+        assert_eq!(shared.compare(&clock(&["b", "a"])), Causality::Equal);
+        // More updates in one partition do not make it ahead of the other
+        assert_eq!(partition_b.compare(&partition_a), Causality::Concurrent);
+        assert_eq!(healed.compare(&partition_a), Causality::After);
+        assert_eq!(healed.compare(&partition_b), Causality::After);
+        assert_eq!((healed.get("a"), healed.get("b"), healed.get("c")), (2, 3, 0));
+        // The clock is sent as the counters by ID
+        assert_eq!(serde_json::to_string(&healed).unwrap(), "{\"a\":2,\"b\":3}");
+    }
+
+    #[test]
+    fn test_vector_clock_precedence() {
+        // Arrange
+        let more = clock(&["a", "b", "b"]);
+        let fewer = clock(&["a", "a"]);
+        let tied = clock(&["a", "a", "b"]);
+
+        // Act / Assert
+        // Either side orders two concurrent clocks the same, by the updates seen and then by the counters
+        assert!(more.precedes(&fewer) && !fewer.precedes(&more));
+        assert!(tied.precedes(&more) && !more.precedes(&tied));
+        assert!(!tied.precedes(&tied));
+    }
+}
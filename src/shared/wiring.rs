@@ -0,0 +1,15 @@
+/**
+ * Validation of the channels attached to the builders of the modules.
+ *
+ * The coordinator, the FSM and the hardware driver are built with a builder, so each channel is attached by name
+ * instead of by its place in a long list of arguments. A builder only builds once every channel the module needs is
+ * attached, and otherwise names the one that is missing.
+ */
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// The channel attached to the builder of the module, or an error naming it if it was never attached
+pub fn attached<T>(channel: Option<T>, module: &str, name: &str) -> Result<T, String> {
+    channel.ok_or_else(|| format!("The {} has no {} attached", module, name))
+}
@@ -0,0 +1,116 @@
+/**
+ * Checkpoints of the state a node keeps across restarts, to roll a node back after an experiment.
+ *
+ * A checkpoint bundles the latest snapshot of the coordinator, with the orders recovered from the write-ahead log
+ * on top, the state of the local elevator, its cab orders and its statistics counters in one JSON document. It is
+ * taken from the storage, so the node may be running, but is only restored to a stopped node, as a running node
+ * would save over it.
+ *
+ * Restoring checks that the checkpoint is of the same version and the same number of floors before anything is
+ * saved. The snapshot, cab orders and counters are then saved together, and the write-ahead log is cut back, so
+ * the orders placed after the checkpoint are not replayed at start-up. The position of the elevator is not
+ * restored, as it is read from the hardware.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::elevator::statistics::Statistics;
+use crate::shared::{ElevatorState, Snapshot};
+use crate::storage::{recover_orders, Record, Storage};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub version: u32,
+    pub created_at: String,
+    pub n_floors: u8,
+    pub fsm: Option<ElevatorState>,
+    pub cab_orders: Vec<bool>,
+    pub statistics: Statistics,
+    pub snapshot: Snapshot,
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// Takes a checkpoint of the storage. Fails if no snapshot has been recorded yet.
+pub fn take_checkpoint(storage: &dyn Storage, n_floors: u8) -> Result<Checkpoint, String> {
+    let Some(mut snapshot) = storage.load_snapshot()? else {
+        return Err("No snapshot has been recorded to take a checkpoint of".to_string());
+    };
+
+    // The orders logged since the snapshot are taken in
+    let recovered_orders = recover_orders(storage, &snapshot.id, n_floors)?;
+    snapshot.elevator_data.hall_requests = recovered_orders.hall_requests;
+    if let Some(state) = snapshot.elevator_data.states.get_mut(&snapshot.id) {
+        state.cab_requests = recovered_orders.cab_requests;
+    }
+    snapshot.wal_sequence = recovered_orders.sequence;
+
+    Ok(Checkpoint {
+        version: CHECKPOINT_VERSION,
+        created_at: Local::now().to_rfc3339(),
+        n_floors,
+        fsm: snapshot.elevator_data.states.get(&snapshot.id).cloned(),
+        cab_orders: storage.load_cab_orders()?.unwrap_or_else(|| vec![false; n_floors as usize]),
+        statistics: storage.load_statistics()?.unwrap_or_default(),
+        snapshot,
+    })
+}
+
+// Restores a checkpoint to the storage, if it fits the node
+pub fn restore_checkpoint(storage: &dyn Storage, checkpoint: &Checkpoint, n_floors: u8) -> Result<(), String> {
+    validate(checkpoint, n_floors)?;
+
+    // The mutations logged so far are dropped with the restored snapshot
+    let mut snapshot = checkpoint.snapshot.clone();
+    let logged = storage.load_wal()?.last().map_or(0, |(sequence, _)| *sequence);
+    let checkpointed = storage.load_snapshot()?.map_or(0, |snapshot| snapshot.wal_sequence);
+    snapshot.wal_sequence = logged.max(checkpointed);
+
+    storage.save(&[
+        Record::CabOrders(checkpoint.cab_orders.clone()),
+        Record::Statistics(checkpoint.statistics.clone()),
+        Record::Snapshot(Box::new(snapshot)),
+    ])
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn validate(checkpoint: &Checkpoint, n_floors: u8) -> Result<(), String> {
+    if checkpoint.version != CHECKPOINT_VERSION {
+        return Err(format!(
+            "The checkpoint is of version {}, and this build restores version {}",
+            checkpoint.version, CHECKPOINT_VERSION
+        ));
+    }
+    if checkpoint.n_floors != n_floors {
+        return Err(format!("The checkpoint is of {} floors, and the node has {}", checkpoint.n_floors, n_floors));
+    }
+
+    let hall_floors = checkpoint.snapshot.elevator_data.hall_requests.len();
+    let cab_floors = checkpoint.cab_orders.len();
+    if hall_floors != n_floors as usize || cab_floors != n_floors as usize {
+        return Err(format!(
+            "The checkpoint holds hall calls for {} floors and cab calls for {}, and not {}",
+            hall_floors, cab_floors, n_floors
+        ));
+    }
+    Ok(())
+}
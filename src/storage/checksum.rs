@@ -0,0 +1,61 @@
+/**
+ * CRC-32 checksums of the files of the `FileStorage`.
+ *
+ * A file written whole ends with a footer line `# crc32 <checksum>` over the lines before it, which TOML reads
+ * as a comment. A line of a JSON lines log ends with ` # crc32 <checksum>` over the rest of the line. Contents
+ * without a footer, or with one that does not match, are corrupt, and are read as missing.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use crc32fast::hash;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const FOOTER: &str = "# crc32 ";
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// The contents of a file, with the footer line appended
+pub fn seal(contents: &str) -> String {
+    let mut sealed = contents.to_string();
+    if !sealed.is_empty() && !sealed.ends_with('\n') {
+        sealed.push('\n');
+    }
+    let checksum = hash(sealed.as_bytes());
+    format!("{}{}{:08x}\n", sealed, FOOTER, checksum)
+}
+
+// The contents of a sealed file, or `None` if it is corrupt
+pub fn unseal(sealed: &str) -> Option<&str> {
+    let footer = sealed.strip_suffix('\n').unwrap_or(sealed);
+    let start = footer.rfind(FOOTER).filter(|start| *start == 0 || footer[..*start].ends_with('\n'))?;
+    let contents = &footer[..start];
+    let checksum = u32::from_str_radix(&footer[start + FOOTER.len()..], 16).ok()?;
+    (hash(contents.as_bytes()) == checksum).then_some(contents)
+}
+
+// Whether the contents end with a footer line, whether it matches or not
+pub fn has_footer(contents: &str) -> bool {
+    contents.lines().next_back().is_some_and(|line| line.starts_with(FOOTER))
+}
+
+// A line of a log, with its checksum appended
+pub fn seal_line(line: &str) -> String {
+    format!("{} {}{:08x}", line, FOOTER, hash(line.as_bytes()))
+}
+
+// Whether the line of a log ends with a checksum, whether it matches or not
+pub fn has_line_checksum(line: &str) -> bool {
+    line.contains(&format!(" {}", FOOTER))
+}
+
+// The line of a log without its checksum, or `None` if it is corrupt
+pub fn unseal_line(sealed: &str) -> Option<&str> {
+    let (line, checksum) = sealed.rsplit_once(&format!(" {}", FOOTER))?;
+    let checksum = u32::from_str_radix(checksum, 16).ok()?;
+    (hash(line.as_bytes()) == checksum).then_some(line)
+}
@@ -0,0 +1,124 @@
+/**
+ * Versions of the format of the files of the `FileStorage`, and the migrations between them.
+ *
+ * A file written whole starts with a header line `# format <version>`, inside its checksum, and a log starts with
+ * the header line before its first entry. The formats so far are:
+ *
+ * - 1: The plain TOML, JSON and JSON lines, as written before the checksums.
+ * - 2: Files sealed with a checksum footer, and logs with a checksum at the end of every line.
+ * - 3: The format 2 with the version header.
+ *
+ * Files of an older format are read, and migrated to the current format by the migrations of every format since,
+ * when loaded. Files without a header are of format 2 if they are sealed, and of format 1 if they are not, so a
+ * file of format 1 is not taken as corrupt. A file of a newer format is refused, rather than read wrong by an older
+ * build. The migrations so far only change how the contents are framed, and not the contents themselves.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use serde_json::Value;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::storage::checksum::{has_footer, has_line_checksum, seal, seal_line, unseal, unseal_line};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+pub const FORMAT_VERSION: u32 = 3;
+const HEADER: &str = "# format ";
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+// The contents of a file written whole, in the current format
+pub fn encode(contents: &str) -> String {
+    seal(&format!("{}\n{}", header(), contents))
+}
+
+// The contents of a file written whole, migrated to the current format, and the format it was found in. `None` if
+// the file is corrupt.
+pub fn decode(file: &str) -> Result<Option<(u32, String)>, String> {
+    let (version, contents) = match unseal(file) {
+        Some(contents) => match split_header(contents) {
+            Some((version, contents)) => (version?, contents),
+            None => (2, contents),
+        },
+        None if !has_footer(file) => (1, file),
+        None => return Ok(None),
+    };
+    check(version)?;
+    Ok(Some((version, migrate(version, contents.to_string()))))
+}
+
+// The header line of a file or log in the current format
+pub fn header() -> String {
+    format!("{}{}", HEADER, FORMAT_VERSION)
+}
+
+// Whether a line is the header line of a file or log, of any format
+pub fn is_header(line: &str) -> bool {
+    line.starts_with(HEADER)
+}
+
+// The entries of a log, migrated to the current format, and the format the log was found in. The entries of the
+// lines found corrupt are `None`.
+pub fn decode_log(log: &str) -> Result<(u32, Vec<Option<String>>), String> {
+    let (version, lines) = match split_header(log) {
+        Some((version, lines)) => (version?, lines),
+        // A log of format 1 may have lines of format 2 appended to it by a later build
+        None if log.lines().all(has_line_checksum) => (2, log),
+        None => (1, log),
+    };
+    check(version)?;
+
+    let entries = lines
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| match version >= 2 || has_line_checksum(line) {
+            true => unseal_line(line).map(|entry| migrate(version, entry.to_string())),
+            // Without a checksum, a torn line of format 1 is only told from an intact one by parsing it
+            false => serde_json::from_str::<Value>(line).ok().map(|_| migrate(version, line.to_string())),
+        })
+        .collect();
+    Ok((version, entries))
+}
+
+// The entries of a log, as a log in the current format
+pub fn encode_log(entries: &[String]) -> String {
+    let mut log = header() + "\n";
+    for entry in entries {
+        log += &seal_line(entry);
+        log += "\n";
+    }
+    log
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The version of the header line and the lines after it, or `None` if there is no header line
+fn split_header(contents: &str) -> Option<(Result<u32, String>, &str)> {
+    let (first, rest) = contents.split_once('\n').unwrap_or((contents, ""));
+    let version = first.strip_prefix(HEADER)?;
+    let version = version.trim().parse::<u32>().map_err(|_| format!("The format version {} is not a number", version));
+    Some((version, rest))
+}
+
+fn check(version: u32) -> Result<(), String> {
+    match version > FORMAT_VERSION {
+        true => Err(format!("Written in format {}, newer than the format {} of this build", version, FORMAT_VERSION)),
+        false => Ok(()),
+    }
+}
+
+// Migrates the contents of a file, or an entry of a log, from the format it was found in to the current format
+fn migrate(version: u32, contents: String) -> String {
+    (version..FORMAT_VERSION).fold(contents, |contents, version| match version {
+        // The checksums of format 2 and the header of format 3 frame the contents, and leave them as they were
+        1 | 2 => contents,
+        _ => unreachable!("No migration from format {}", version),
+    })
+}
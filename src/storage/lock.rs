@@ -0,0 +1,78 @@
+/**
+ * Lock on the storage of an elevator, held by the running instance of it.
+ *
+ * An instance takes an advisory lock on `instance-<id>.lock` in the directory of its storage before it connects to
+ * the hardware, and holds it until it exits. A second instance of the same elevator, launched by mistake, finds the
+ * lock held and refuses to start, rather than fight the first over the hardware and the files. The lock file holds
+ * the process ID of the instance holding it, for the error. The operating system releases the lock when the process
+ * exits, also after a crash, so a stale lock file does not keep the elevator from starting.
+ *
+ * # Fields
+ * - `_file`:                   The lock file, locked for as long as it is open.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::StorageConfig;
+use crate::shared::StorageBackend;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    // Takes the lock of the elevator in the directory of the storage, or fails if another instance holds it
+    pub fn acquire(storage_config: &StorageConfig, id: &str) -> Result<InstanceLock, String> {
+        let directory = match storage_config.backend {
+            StorageBackend::Files => PathBuf::from(&storage_config.directory),
+            StorageBackend::Sqlite => Path::new(&storage_config.database).parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let path = directory.join(lock_file(id));
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open the lock file {}: {}", path.display(), e))?;
+
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => {
+                let holder = fs::read_to_string(&path).unwrap_or_default();
+                return Err(format!(
+                    "Another instance of elevator {} is running as process {}, and holds {}. Stop it before starting this one",
+                    id,
+                    holder.trim(),
+                    path.display()
+                ));
+            }
+            Err(TryLockError::Error(e)) => return Err(format!("Failed to lock {}: {}", path.display(), e)),
+        }
+
+        file.set_len(0)
+            .and_then(|_| writeln!(file, "{}", std::process::id()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(InstanceLock { _file: file })
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The name of the lock file, with the characters of the ID that do not belong in a file name replaced
+fn lock_file(id: &str) -> String {
+    let id = id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect::<String>();
+    format!("instance-{}.lock", id)
+}
@@ -0,0 +1,18 @@
+pub mod checkpoint;
+pub mod checksum;
+pub mod format;
+pub mod lock;
+pub mod recorder;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod storage;
+pub mod storage_tests;
+pub mod wal;
+
+pub use checkpoint::{restore_checkpoint, take_checkpoint, Checkpoint};
+pub use lock::InstanceLock;
+pub use recorder::Recorder;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+pub use storage::{open_storage, FileStorage, LogRetention, Record, Storage};
+pub use wal::{recover_orders, OrderMutation, RecoveredOrders, WriteAheadLog};
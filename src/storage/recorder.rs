@@ -0,0 +1,182 @@
+/**
+ * Recorder of the snapshots of the coordinator and the events of the fleet.
+ *
+ * Every `record_interval` milliseconds the snapshot of the coordinator is saved to the storage, together with the
+ * events of the fleet since the previous snapshot, so the event log and the snapshot it leads up to are saved in
+ * one go. The coordinator is looked at more often than that, and a snapshot saved as soon as there are events, so
+ * a call or a fault is on the disk within `CHANGE_INTERVAL`. Events are saved as JSON with the time they were
+ * seen:
+ *
+ * - `{"timestamp": "...", "event": "callPlaced", "call": "up", "floor": 2}`, and `callCleared`.
+ * - `{"timestamp": "...", "event": "error", "elevator": "<id>", "floor": 2}`, and `recovered`.
+ * - `{"timestamp": "...", "event": "obstructionAlarm", "elevator": "<id>", "floor": 2}`, and `obstructionAlarmCleared`.
+ * - `{"timestamp": "...", "event": "peerLost", "elevator": "<id>"}`.
+ *
+ * The state of the local elevator is in the snapshot, and is not logged as events. A save that fails is logged,
+ * and its events are lost.
+ *
+ * Every backup `interval` milliseconds after a snapshot is saved, a checkpoint of the storage is handed to the
+ * network as a backup, to be pushed to the peers hosting it.
+ *
+ * # Fields
+ * - `record_interval`:         Time between snapshots.
+ * - `backup_interval`:         Time between backups, or zero without backups.
+ * - `local_id`:                The ID of the local elevator.
+ * - `n_floors`:                The number of floors, which the backups are checked against when restored.
+ * - `storage`:                 The storage the records are saved to.
+ * - `coordinator_query_tx`:    Queries the coordinator for its snapshot.
+ * - `net_backup_send_tx`:      Hands the backups to the network.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::Local;
+use crossbeam_channel as cbc;
+use driver_rust::elevio::elev::{HALL_DOWN, HALL_UP};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::{BackupConfig, StorageConfig};
+use crate::network::BackupCommand;
+use crate::shared::{fleet_events, FleetEvent, Snapshot};
+use crate::storage::{take_checkpoint, Record, Storage};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+const CHANGE_INTERVAL: Duration = Duration::from_millis(100);
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct Recorder {
+    record_interval: Duration,
+    backup_interval: Duration,
+    local_id: String,
+    n_floors: u8,
+    storage: Arc<dyn Storage>,
+    coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+    net_backup_send_tx: cbc::Sender<BackupCommand>,
+}
+
+impl Recorder {
+    pub fn new(
+        storage_config: &StorageConfig,
+        backup_config: &BackupConfig,
+        local_id: String,
+        n_floors: u8,
+        storage: Arc<dyn Storage>,
+        coordinator_query_tx: cbc::Sender<cbc::Sender<Snapshot>>,
+        net_backup_send_tx: cbc::Sender<BackupCommand>,
+    ) -> Recorder {
+        Recorder {
+            record_interval: Duration::from_millis(storage_config.record_interval),
+            backup_interval: Duration::from_millis(backup_config.interval),
+            local_id,
+            n_floors,
+            storage,
+            coordinator_query_tx,
+            net_backup_send_tx,
+        }
+    }
+
+    pub fn run(self) {
+        info!("Recording snapshots every {} ms", self.record_interval.as_millis());
+        let change_ticker = cbc::tick(self.record_interval.min(CHANGE_INTERVAL));
+        let mut previous: Option<Snapshot> = None;
+        let mut recorded_at = Instant::now();
+        let mut backed_up_at: Option<Instant> = None;
+
+        for _ in change_ticker.iter() {
+            let Some(snapshot) = self.query() else { continue };
+            let timestamp = Local::now().to_rfc3339();
+            let events = events(&self.local_id, previous.as_ref(), &snapshot, &timestamp);
+            // Between the intervals a snapshot is only saved when the fleet changed
+            if previous.is_some() && events.is_empty() && recorded_at.elapsed() < self.record_interval {
+                continue;
+            }
+
+            let mut records: Vec<Record> = events.into_iter().map(Record::Event).collect();
+            records.push(Record::Snapshot(Box::new(snapshot.clone())));
+            let saved = self.storage.save(&records);
+            if let Err(e) = &saved {
+                warn!("Failed to record the snapshot: {}", e);
+            }
+            previous = Some(snapshot);
+            recorded_at = Instant::now();
+
+            let backup_due = backed_up_at.is_none_or(|at| at.elapsed() >= self.backup_interval);
+            if saved.is_ok() && !self.backup_interval.is_zero() && backup_due {
+                self.back_up();
+                backed_up_at = Some(Instant::now());
+            }
+        }
+    }
+
+    fn back_up(&self) {
+        match take_checkpoint(self.storage.as_ref(), self.n_floors) {
+            // Offline the network is not running, and the backup is dropped
+            Ok(checkpoint) => {
+                let _ = self.net_backup_send_tx.send(BackupCommand::Push(Box::new(checkpoint)));
+            }
+            Err(e) => warn!("Failed to take a backup: {}", e),
+        }
+    }
+
+    fn query(&self) -> Option<Snapshot> {
+        let (reply_tx, reply_rx) = cbc::bounded::<Snapshot>(1);
+        self.coordinator_query_tx.send(reply_tx).ok()?;
+        reply_rx.recv_timeout(QUERY_TIMEOUT).ok()
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// The fleet events between two snapshots as entries of the event log
+fn events(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot, timestamp: &str) -> Vec<Value> {
+    fleet_events(local_id, previous, snapshot)
+        .into_iter()
+        .filter_map(|event| match event {
+            FleetEvent::State(_) => None,
+            FleetEvent::Call(floor, call, placed) => {
+                let event = if placed { "callPlaced" } else { "callCleared" };
+                let call = match call {
+                    HALL_UP => "up",
+                    HALL_DOWN => "down",
+                    _ => "cab",
+                };
+                Some(json!({ "timestamp": timestamp, "event": event, "call": call, "floor": floor }))
+            }
+            FleetEvent::Fault(floor, failed) => {
+                let event = if failed { "error" } else { "recovered" };
+                Some(json!({ "timestamp": timestamp, "event": event, "elevator": local_id, "floor": floor }))
+            }
+            FleetEvent::PeerLost(peer) => Some(json!({ "timestamp": timestamp, "event": "peerLost", "elevator": peer })),
+            FleetEvent::ObstructionAlarm(id, floor, active) => {
+                let event = if active { "obstructionAlarm" } else { "obstructionAlarmCleared" };
+                Some(json!({ "timestamp": timestamp, "event": event, "elevator": id, "floor": floor }))
+            }
+        })
+        .collect()
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use crate::shared::Snapshot;
+    use serde_json::Value;
+
+    pub fn test_events(local_id: &str, previous: Option<&Snapshot>, snapshot: &Snapshot, timestamp: &str) -> Vec<Value> {
+        super::events(local_id, previous, snapshot, timestamp)
+    }
+}
@@ -0,0 +1,270 @@
+/**
+ * SQLite backend of the storage, built with the `sqlite` feature.
+ *
+ * Everything is kept in one database file. The records of a save are written in one transaction, so a crash in
+ * the middle of a save leaves the database as it was before it. The cab orders, the statistics, the uptime and the
+ * snapshot are single rows replaced on every save, and the events and mutations are rows appended in the order they were
+ * saved. A snapshot, its copy in the history and the dropping of the mutations it includes are one transaction.
+ *
+ * The version of the schema is kept in the `user_version` of the database. A database of an older schema is
+ * migrated when opened, by the migrations of every version since, and a database of a newer schema is refused. A
+ * database from before the schema was versioned is of version 0.
+ *
+ * The events are kept within the `LogRetention` as if the table were the live and rotated logs of the files: the
+ * oldest events are deleted in the transaction that saves new ones, beyond the maximum size of all the logs
+ * together, and once older than the maximum age.
+ *
+ * # Fields
+ * - `connection`:              The connection to the database, shared by the FSM and the recorder.
+ * - `snapshot_retention`:      The number of snapshots kept in the history.
+ * - `log_retention`:           The limits on the events.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::sync::Mutex;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::elevator::statistics::Statistics;
+use crate::shared::{Snapshot, Uptime};
+use crate::storage::{LogRetention, OrderMutation, Record, Storage};
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS cab_orders (id INTEGER PRIMARY KEY CHECK (id = 0), cab_calls TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS statistics (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        distance_travelled INTEGER NOT NULL,
+        door_cycles INTEGER NOT NULL,
+        orders_served INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS snapshot (id INTEGER PRIMARY KEY CHECK (id = 0), snapshot TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS events (id INTEGER PRIMARY KEY AUTOINCREMENT, event TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS wal (sequence INTEGER PRIMARY KEY, mutation TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS snapshots (id INTEGER PRIMARY KEY AUTOINCREMENT, snapshot TEXT NOT NULL);
+";
+
+const UPTIME_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS uptime (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        restarts INTEGER NOT NULL,
+        watchdog_resets INTEGER NOT NULL,
+        seconds INTEGER NOT NULL,
+        running INTEGER NOT NULL
+    );
+";
+
+// The statements migrating the schema from each version to the next, the first creating the tables
+const MIGRATIONS: [&str; 2] = [SCHEMA, UPTIME_TABLE];
+const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+    snapshot_retention: usize,
+    log_retention: LogRetention,
+}
+
+impl SqliteStorage {
+    // Opens the database, creating it and its tables if need be. `:memory:` opens a database in memory.
+    pub fn open(database: &str, snapshot_retention: usize, log_retention: LogRetention) -> Result<SqliteStorage, String> {
+        let mut connection = Connection::open(database).map_err(|e| format!("Failed to open {}: {}", database, e))?;
+        migrate(&mut connection).map_err(|e| format!("Failed to migrate {}: {}", database, e))?;
+        Ok(SqliteStorage { connection: Mutex::new(connection), snapshot_retention, log_retention })
+    }
+
+    // Replaces the latest snapshot, adds it to the history and drops the mutations it includes. Returns the number
+    // of mutations dropped.
+    fn save_snapshot(&self, connection: &Connection, snapshot: &Snapshot) -> rusqlite::Result<usize> {
+        let contents = serde_json::to_string(snapshot).unwrap();
+        connection.execute("INSERT OR REPLACE INTO snapshot (id, snapshot) VALUES (0, ?1)", params![contents])?;
+        if self.snapshot_retention > 0 {
+            connection.execute("INSERT INTO snapshots (snapshot) VALUES (?1)", params![contents])?;
+            connection.execute(
+                "DELETE FROM snapshots WHERE id <= (SELECT MAX(id) FROM snapshots) - ?1",
+                params![self.snapshot_retention as i64],
+            )?;
+        }
+        connection.execute("DELETE FROM wal WHERE sequence <= ?1", params![snapshot.wal_sequence as i64])
+    }
+
+    // Deletes the oldest events beyond the size of the live and rotated logs together, and the expired. Returns the
+    // number of events deleted.
+    fn prune_events(&self, connection: &Connection) -> rusqlite::Result<usize> {
+        let max_size = self.log_retention.max_size.saturating_mul(self.log_retention.rotations as u64 + 1);
+        let mut statement = connection.prepare("SELECT id, event FROM events ORDER BY id DESC")?;
+        let mut events = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        let mut size = 0;
+        let newest_pruned = events.find_map(|event| {
+            let (id, event) = event.ok()?;
+            size += event.len() as u64 + 1;
+            ((max_size > 0 && size > max_size) || self.log_retention.expired(&event)).then_some(id)
+        });
+        match newest_pruned {
+            Some(id) => connection.execute("DELETE FROM events WHERE id <= ?1", params![id]),
+            None => Ok(0),
+        }
+    }
+
+    // The JSON of the single row of a table, or `None` if nothing has been saved to it yet
+    fn load_json(&self, query: &str) -> Result<Option<String>, String> {
+        let connection = self.connection.lock().unwrap();
+        connection.query_row(query, [], |row| row.get(0)).optional().map_err(|e| e.to_string())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn save(&self, records: &[Record]) -> Result<(), String> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        for record in records {
+            let result = match record {
+                Record::CabOrders(cab_calls) => transaction.execute(
+                    "INSERT OR REPLACE INTO cab_orders (id, cab_calls) VALUES (0, ?1)",
+                    params![serde_json::to_string(cab_calls).unwrap()],
+                ),
+                Record::Statistics(statistics) => transaction.execute(
+                    "INSERT OR REPLACE INTO statistics (id, distance_travelled, door_cycles, orders_served) VALUES (0, ?1, ?2, ?3)",
+                    params![
+                        statistics.distance_travelled as i64,
+                        statistics.door_cycles as i64,
+                        statistics.orders_served as i64
+                    ],
+                ),
+                Record::Uptime(uptime, running) => transaction.execute(
+                    "INSERT OR REPLACE INTO uptime (id, restarts, watchdog_resets, seconds, running) VALUES (0, ?1, ?2, ?3, ?4)",
+                    params![uptime.restarts as i64, uptime.watchdog_resets as i64, uptime.seconds as i64, *running],
+                ),
+                Record::Snapshot(snapshot) => self.save_snapshot(&transaction, snapshot),
+                Record::Event(event) => transaction.execute("INSERT INTO events (event) VALUES (?1)", params![event.to_string()]),
+                Record::Mutation(sequence, mutation) => transaction.execute(
+                    "INSERT OR REPLACE INTO wal (sequence, mutation) VALUES (?1, ?2)",
+                    params![*sequence as i64, serde_json::to_string(mutation).unwrap()],
+                ),
+            };
+            // Dropping the transaction rolls back the records written so far
+            result.map_err(|e| e.to_string())?;
+        }
+        if records.iter().any(|record| matches!(record, Record::Event(_))) {
+            self.prune_events(&transaction).map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
+    fn load_cab_orders(&self) -> Result<Option<Vec<bool>>, String> {
+        let Some(cab_calls) = self.load_json("SELECT cab_calls FROM cab_orders")? else { return Ok(None) };
+        serde_json::from_str(&cab_calls).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn load_statistics(&self) -> Result<Option<Statistics>, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row("SELECT distance_travelled, door_cycles, orders_served FROM statistics", [], |row| {
+                Ok(Statistics {
+                    distance_travelled: row.get::<_, i64>(0)? as u64,
+                    door_cycles: row.get::<_, i64>(1)? as u64,
+                    orders_served: row.get::<_, i64>(2)? as u64,
+                })
+            })
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    fn load_uptime(&self) -> Result<Option<(Uptime, bool)>, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row("SELECT restarts, watchdog_resets, seconds, running FROM uptime", [], |row| {
+                let uptime = Uptime {
+                    restarts: row.get::<_, i64>(0)? as u64,
+                    watchdog_resets: row.get::<_, i64>(1)? as u64,
+                    seconds: row.get::<_, i64>(2)? as u64,
+                };
+                Ok((uptime, row.get::<_, bool>(3)?))
+            })
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    fn load_snapshot(&self) -> Result<Option<Snapshot>, String> {
+        let Some(snapshot) = self.load_json("SELECT snapshot FROM snapshot")? else { return Ok(None) };
+        serde_json::from_str(&snapshot).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn load_snapshots(&self) -> Result<Vec<Snapshot>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT snapshot FROM snapshots ORDER BY id").map_err(|e| e.to_string())?;
+        let snapshots = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+        snapshots
+            .map(|snapshot| {
+                let snapshot = snapshot.map_err(|e| e.to_string())?;
+                serde_json::from_str(&snapshot).map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    fn load_events(&self) -> Result<Vec<Value>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT event FROM events ORDER BY id").map_err(|e| e.to_string())?;
+        let events = statement.query_map([], |row| row.get::<_, String>(0)).map_err(|e| e.to_string())?;
+        events
+            .map(|event| {
+                let event = event.map_err(|e| e.to_string())?;
+                serde_json::from_str(&event).map_err(|e| e.to_string())
+            })
+            .collect()
+    }
+
+    fn load_wal(&self) -> Result<Vec<(u64, OrderMutation)>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT sequence, mutation FROM wal ORDER BY sequence").map_err(|e| e.to_string())?;
+        let entries = statement
+            .query_map([], |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, String>(1)?)))
+            .map_err(|e| e.to_string())?;
+        entries
+            .map(|entry| {
+                let (sequence, mutation) = entry.map_err(|e| e.to_string())?;
+                Ok((sequence, serde_json::from_str(&mutation).map_err(|e| e.to_string())?))
+            })
+            .collect()
+    }
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+// Migrates the schema to the current version in one transaction
+fn migrate(connection: &mut Connection) -> Result<(), String> {
+    let transaction = connection.transaction().map_err(|e| e.to_string())?;
+    let version: i64 = transaction.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(|e| e.to_string())?;
+    if version > SCHEMA_VERSION {
+        return Err(format!("The schema is of version {}, newer than the version {} of this build", version, SCHEMA_VERSION));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        transaction.execute_batch(migration).map_err(|e| e.to_string())?;
+    }
+    transaction.pragma_update(None, "user_version", SCHEMA_VERSION).map_err(|e| e.to_string())?;
+    transaction.commit().map_err(|e| e.to_string())
+}
+
+/***************************************/
+/*              Test API               */
+/***************************************/
+#[cfg(test)]
+pub mod testing {
+    use super::SqliteStorage;
+
+    impl SqliteStorage {
+        pub fn test_execute(&self, sql: &str) {
+            self.connection.lock().unwrap().execute_batch(sql).unwrap();
+        }
+    }
+}
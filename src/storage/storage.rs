@@ -0,0 +1,500 @@
+/**
+ * Storage of the state of the elevator across restarts.
+ *
+ * The FSM saves its cab orders and statistics counters, the recorder the snapshots of the coordinator and the
+ * events of the fleet, and the uptime counter the restarts and the uptime, through a `Storage`. Records that belong together, such as the cab orders and the counter
+ * of orders served when a cab call is served, are saved in one call. A backend that can write them in one
+ * transaction does, so a crash leaves either all of them or none.
+ *
+ * The `FileStorage` keeps a file per concern in `directory`. The cab orders and statistics are TOML, the latest
+ * snapshot JSON, and the event log and the write-ahead log JSON lines. Whole files are written to a temporary
+ * file and renamed over the old, so a crash leaves either the old file or the new, and mutations are flushed to
+ * the disk before the save returns. Every file, and every line of the logs, carries a checksum verified when it
+ * is loaded. A file or line found corrupt is logged, and taken as missing, rather than loaded wrong. The files are
+ * written one by one, so records saved together may be torn by a crash. The `SqliteStorage` keeps them all in one database, and is built with the `sqlite` feature.
+ *
+ * Besides the latest snapshot, the last `snapshot_retention` snapshots are kept as a history to look back on,
+ * numbered in `snapshots/` by the `FileStorage`.
+ *
+ * Every file and log is tagged with the version of its format, and files of an older format are migrated to the
+ * current format when loaded, and all of them when the storage is opened. See `format`.
+ *
+ * The event log is the only file that grows for as long as the node runs, and is kept within the `LogRetention`.
+ * The `FileStorage` rotates it to `events.1.jsonl`, shifting the older logs up to `events.2.jsonl` and on, when
+ * it has grown to the maximum size or its first event to the maximum age. The logs rotated beyond the number kept,
+ * or whose last event is older than the maximum age, are removed. The loaded events are those of the rotated logs
+ * and the live one, oldest first. The write-ahead log is cut back by every snapshot, and the other files are
+ * written whole, so they do not grow.
+ *
+ * # Fields
+ * - `directory`:               The directory the files are kept in.
+ * - `snapshot_retention`:      The number of snapshots kept in the history.
+ * - `log_retention`:           The limits on the event log.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use chrono::{DateTime, FixedOffset, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::config::StorageConfig;
+use crate::elevator::statistics::Statistics;
+use crate::shared::{Snapshot, StorageBackend, Uptime};
+use crate::storage::checksum::{seal_line, unseal_line};
+use crate::storage::format::{decode, decode_log, encode, encode_log, header, is_header, FORMAT_VERSION};
+use crate::storage::OrderMutation;
+#[cfg(feature = "sqlite")]
+use crate::storage::SqliteStorage;
+
+/***************************************/
+/*              Constants              */
+/***************************************/
+pub(crate) const CAB_ORDERS_FILE: &str = "cab_orders.toml";
+pub(crate) const STATISTICS_FILE: &str = "statistics.toml";
+pub(crate) const UPTIME_FILE: &str = "uptime.toml";
+pub(crate) const SNAPSHOT_FILE: &str = "snapshot.json";
+pub(crate) const EVENT_LOG_FILE: &str = "events.jsonl";
+pub(crate) const WAL_FILE: &str = "wal.jsonl";
+pub(crate) const SNAPSHOT_DIRECTORY: &str = "snapshots";
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    // The cab calls of the local elevator
+    CabOrders(Vec<bool>),
+    // The service counters of the local elevator
+    Statistics(Statistics),
+    // The restarts and the uptime, and whether the process is running. Saved as not running once it ends cleanly.
+    Uptime(Uptime, bool),
+    // The view of the coordinator. The latest is kept, and the last few in the history.
+    Snapshot(Box<Snapshot>),
+    // An event of the fleet, appended to the event log
+    Event(Value),
+    // An order mutation, appended to the write-ahead log with its number
+    Mutation(u64, OrderMutation),
+}
+
+// Limits on the event log. The log is rotated when it holds `max_size` bytes or its first event is `max_age`
+// seconds old, and the last `rotations` rotated logs are kept. A maximum of 0 is no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LogRetention {
+    pub max_size: u64,
+    pub max_age: u64,
+    pub rotations: usize,
+}
+
+impl LogRetention {
+    pub fn new(storage_config: &StorageConfig) -> LogRetention {
+        LogRetention {
+            max_size: storage_config.log_max_size,
+            max_age: storage_config.log_max_age,
+            rotations: storage_config.log_rotations,
+        }
+    }
+
+    // Whether an event of the log is older than the maximum age. Events without a timestamp never expire.
+    pub fn expired(&self, event: &str) -> bool {
+        let age = |time: DateTime<FixedOffset>| (Utc::now() - time.with_timezone(&Utc)).num_seconds();
+        self.max_age > 0 && event_time(event).is_some_and(|time| age(time) >= self.max_age as i64)
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub trait Storage: Send + Sync {
+    // Saves the records together. Events and mutations are appended to their logs, and the other records replace
+    // the last saved. A snapshot drops the mutations it includes from the write-ahead log.
+    fn save(&self, records: &[Record]) -> Result<(), String>;
+    fn load_cab_orders(&self) -> Result<Option<Vec<bool>>, String>;
+    fn load_statistics(&self) -> Result<Option<Statistics>, String>;
+    fn load_uptime(&self) -> Result<Option<(Uptime, bool)>, String>;
+    fn load_snapshot(&self) -> Result<Option<Snapshot>, String>;
+    // The snapshots in the history, oldest first
+    fn load_snapshots(&self) -> Result<Vec<Snapshot>, String>;
+    fn load_events(&self) -> Result<Vec<Value>, String>;
+    fn load_wal(&self) -> Result<Vec<(u64, OrderMutation)>, String>;
+}
+
+// Opens the backend of the configuration
+pub fn open_storage(storage_config: &StorageConfig) -> Result<Arc<dyn Storage>, String> {
+    match storage_config.backend {
+        StorageBackend::Files => {
+            let storage = FileStorage::new(&storage_config.directory, storage_config.snapshot_retention, LogRetention::new(storage_config));
+            storage.migrate()?;
+            Ok(Arc::new(storage))
+        }
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => Ok(Arc::new(SqliteStorage::open(
+            &storage_config.database,
+            storage_config.snapshot_retention,
+            LogRetention::new(storage_config),
+        )?)),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err("The sqlite storage backend needs the program built with the sqlite feature".to_string()),
+    }
+}
+
+pub struct FileStorage {
+    directory: PathBuf,
+    snapshot_retention: usize,
+    log_retention: LogRetention,
+}
+
+impl FileStorage {
+    pub fn new(directory: &str, snapshot_retention: usize, log_retention: LogRetention) -> FileStorage {
+        FileStorage { directory: PathBuf::from(directory), snapshot_retention, log_retention }
+    }
+
+    // Writes the file in full to a temporary file, and renames it over the old
+    fn write(&self, file: &str, contents: &str) -> Result<(), String> {
+        let path = self.directory.join(file);
+        let temporary = self.directory.join(format!("{}.tmp", file));
+        fs::File::create(&temporary)
+            .and_then(|mut temporary_file| {
+                temporary_file.write_all(contents.as_bytes())?;
+                temporary_file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temporary, &path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn append(&self, file: &str, line: &str, flush: bool) -> Result<(), String> {
+        let path = self.directory.join(file);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut log| {
+                // A new log starts with the header
+                if log.metadata()?.len() == 0 {
+                    writeln!(log, "{}", header())?;
+                }
+                writeln!(log, "{}", seal_line(line))?;
+                if flush {
+                    log.sync_data()?;
+                }
+                Ok(())
+            })
+            .map_err(|e| format!("Failed to append to {}: {}", path.display(), e))
+    }
+
+    // The contents of a file, or `None` if nothing has been saved to it yet
+    fn read(&self, file: &str) -> Result<Option<String>, String> {
+        let path = self.directory.join(file);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    // The record parsed from a file written whole, or `None` if nothing has been saved to it yet or it is corrupt. A
+    // file of an older format is migrated, and written back in the current format.
+    fn read_sealed<T>(&self, file: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+        let Some(sealed) = self.read(file)? else { return Ok(None) };
+        let path = self.directory.join(file);
+        let corrupt = || {
+            warn!("{} is corrupt, and is taken as missing", path.display());
+            Ok(None)
+        };
+        let Some((version, contents)) = decode(&sealed).map_err(|e| format!("Failed to read {}: {}", path.display(), e))? else {
+            return corrupt();
+        };
+        let record = match parse(&contents) {
+            Ok(record) => record,
+            // Without a checksum, a torn file of format 1 is only told from an intact one by parsing it
+            Err(_) if version == 1 => return corrupt(),
+            Err(e) => return Err(parse_error(file, e)),
+        };
+
+        if version < FORMAT_VERSION {
+            info!("Migrating {} from format {} to format {}", path.display(), version, FORMAT_VERSION);
+            self.write(file, &encode(&contents))?;
+        }
+        Ok(Some(record))
+    }
+
+    // The lines of a log, leaving out the corrupt. A log of an older format is migrated, and written back in the
+    // current format.
+    fn read_lines(&self, file: &str) -> Result<Vec<String>, String> {
+        let Some(contents) = self.read(file)? else { return Ok(Vec::new()) };
+        let path = self.directory.join(file);
+        let (version, entries) = decode_log(&contents).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut lines = Vec::new();
+        for (number, entry) in entries.into_iter().enumerate() {
+            match entry {
+                Some(line) => lines.push(line),
+                None => warn!("Entry {} of {} is corrupt, and is left out", number + 1, path.display()),
+            }
+        }
+        if version < FORMAT_VERSION {
+            info!("Migrating {} from format {} to format {}", path.display(), version, FORMAT_VERSION);
+            self.write(file, &encode_log(&lines))?;
+        }
+        Ok(lines)
+    }
+
+    // Migrates the files of an older format to the current format, by loading them
+    pub fn migrate(&self) -> Result<(), String> {
+        self.load_cab_orders()?;
+        self.load_statistics()?;
+        self.load_uptime()?;
+        self.load_snapshot()?;
+        self.load_snapshots()?;
+        self.load_events()?;
+        self.load_wal()?;
+        Ok(())
+    }
+
+    // Appends an event to the event log, rotating the log first if it has reached its limits
+    fn append_event(&self, event: &str) -> Result<(), String> {
+        let path = self.directory.join(EVENT_LOG_FILE);
+        let size = fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        let oversized = self.log_retention.max_size > 0 && size >= self.log_retention.max_size;
+        if oversized || self.first_event_expired(&path) {
+            self.rotate_event_log()?;
+        }
+        self.append(EVENT_LOG_FILE, event, false)
+    }
+
+    fn first_event_expired(&self, path: &Path) -> bool {
+        let Ok(log) = fs::File::open(path) else { return false };
+        let first = BufReader::new(log).lines().map_while(Result::ok).find(|line| !is_header(line));
+        first.and_then(|line| unseal_line(&line).map(|event| self.log_retention.expired(event))).unwrap_or(false)
+    }
+
+    // Shifts the rotated logs up, removing those beyond the number kept or expired, and rotates the live log
+    fn rotate_event_log(&self) -> Result<(), String> {
+        for number in self.rotated_event_logs()?.into_iter().rev() {
+            let path = self.directory.join(rotated_event_log(number));
+            let last_event = self.read_lines(&rotated_event_log(number))?.pop();
+            if number >= self.log_retention.rotations || last_event.is_some_and(|event| self.log_retention.expired(&event)) {
+                fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            } else {
+                let rotated = self.directory.join(rotated_event_log(number + 1));
+                fs::rename(&path, &rotated).map_err(|e| format!("Failed to rotate {}: {}", path.display(), e))?;
+            }
+        }
+
+        let path = self.directory.join(EVENT_LOG_FILE);
+        info!("Rotating the event log {}", path.display());
+        if self.log_retention.rotations == 0 {
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+        } else {
+            let rotated = self.directory.join(rotated_event_log(1));
+            fs::rename(&path, &rotated).map_err(|e| format!("Failed to rotate {}: {}", path.display(), e))
+        }
+    }
+
+    // The numbers of the rotated event logs, newest first
+    fn rotated_event_logs(&self) -> Result<Vec<usize>, String> {
+        let entries = fs::read_dir(&self.directory).map_err(|e| format!("Failed to read {}: {}", self.directory.display(), e))?;
+        let mut numbers = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                name.strip_prefix("events.")?.strip_suffix(".jsonl")?.parse::<usize>().ok()
+            })
+            .collect::<Vec<usize>>();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    // Adds a snapshot to the history, and removes the snapshots beyond the retention
+    fn save_history(&self, contents: &str) -> Result<(), String> {
+        if self.snapshot_retention == 0 {
+            return Ok(());
+        }
+        let directory = self.directory.join(SNAPSHOT_DIRECTORY);
+        fs::create_dir_all(&directory).map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+
+        let mut numbers = self.history()?;
+        let number = numbers.last().map_or(1, |number| number + 1);
+        self.write(&history_file(number), &encode(contents))?;
+        numbers.push(number);
+        for number in &numbers[..numbers.len().saturating_sub(self.snapshot_retention)] {
+            let path = self.directory.join(history_file(*number));
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    // The numbers of the snapshots in the history, oldest first
+    fn history(&self) -> Result<Vec<u64>, String> {
+        let directory = self.directory.join(SNAPSHOT_DIRECTORY);
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", directory.display(), e)),
+        };
+        let mut numbers = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                name.strip_prefix("snapshot-")?.strip_suffix(".json")?.parse::<u64>().ok()
+            })
+            .collect::<Vec<u64>>();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+}
+
+impl Storage for FileStorage {
+    fn save(&self, records: &[Record]) -> Result<(), String> {
+        for record in records {
+            match record {
+                Record::CabOrders(cab_calls) => {
+                    let cab_orders = CabOrders { cab_calls: cab_calls.clone() };
+                    self.write(CAB_ORDERS_FILE, &encode(&toml::to_string(&cab_orders).map_err(|e| e.to_string())?))?;
+                }
+                Record::Statistics(statistics) => {
+                    self.write(STATISTICS_FILE, &encode(&toml::to_string(statistics).map_err(|e| e.to_string())?))?;
+                }
+                Record::Uptime(uptime, running) => {
+                    let uptime = UptimeFile {
+                        restarts: uptime.restarts,
+                        watchdog_resets: uptime.watchdog_resets,
+                        seconds: uptime.seconds,
+                        running: *running,
+                    };
+                    self.write(UPTIME_FILE, &encode(&toml::to_string(&uptime).map_err(|e| e.to_string())?))?;
+                }
+                Record::Snapshot(snapshot) => {
+                    let contents = serde_json::to_string_pretty(snapshot).map_err(|e| e.to_string())?;
+                    self.write(SNAPSHOT_FILE, &encode(&contents))?;
+                    self.save_history(&contents)?;
+
+                    // The mutations after the snapshot are kept
+                    let wal = self.load_wal()?;
+                    if wal.iter().any(|(sequence, _)| *sequence <= snapshot.wal_sequence) {
+                        let kept = wal
+                            .into_iter()
+                            .filter(|(sequence, _)| *sequence > snapshot.wal_sequence)
+                            .map(|entry| wal_line(&entry))
+                            .collect::<Vec<String>>();
+                        self.write(WAL_FILE, &encode_log(&kept))?;
+                    }
+                }
+                Record::Event(event) => self.append_event(&event.to_string())?,
+                Record::Mutation(sequence, mutation) => {
+                    self.append(WAL_FILE, &wal_line(&(*sequence, mutation.clone())), true)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_cab_orders(&self) -> Result<Option<Vec<bool>>, String> {
+        let cab_orders = self.read_sealed(CAB_ORDERS_FILE, |contents| toml::from_str::<CabOrders>(contents).map_err(|e| e.to_string()))?;
+        Ok(cab_orders.map(|cab_orders| cab_orders.cab_calls))
+    }
+
+    fn load_statistics(&self) -> Result<Option<Statistics>, String> {
+        self.read_sealed(STATISTICS_FILE, |contents| toml::from_str(contents).map_err(|e| e.to_string()))
+    }
+
+    fn load_uptime(&self) -> Result<Option<(Uptime, bool)>, String> {
+        let uptime = self.read_sealed(UPTIME_FILE, |contents| toml::from_str::<UptimeFile>(contents).map_err(|e| e.to_string()))?;
+        Ok(uptime.map(|uptime| {
+            let counters = Uptime { restarts: uptime.restarts, watchdog_resets: uptime.watchdog_resets, seconds: uptime.seconds };
+            (counters, uptime.running)
+        }))
+    }
+
+    fn load_snapshot(&self) -> Result<Option<Snapshot>, String> {
+        self.read_sealed(SNAPSHOT_FILE, |contents| serde_json::from_str(contents).map_err(|e| e.to_string()))
+    }
+
+    fn load_snapshots(&self) -> Result<Vec<Snapshot>, String> {
+        let mut snapshots = Vec::new();
+        for number in self.history()? {
+            let file = history_file(number);
+            // A snapshot removed since the directory was read is skipped
+            let snapshot = self.read_sealed(&file, |contents| serde_json::from_str(contents).map_err(|e| e.to_string()))?;
+            snapshots.extend(snapshot);
+        }
+        Ok(snapshots)
+    }
+
+    fn load_events(&self) -> Result<Vec<Value>, String> {
+        let mut files = self.rotated_event_logs()?.into_iter().rev().map(rotated_event_log).collect::<Vec<String>>();
+        files.push(EVENT_LOG_FILE.to_string());
+
+        let mut events = Vec::new();
+        for file in files {
+            for line in self.read_lines(&file)? {
+                events.push(serde_json::from_str(&line).map_err(|e| parse_error(&file, e))?);
+            }
+        }
+        Ok(events)
+    }
+
+    fn load_wal(&self) -> Result<Vec<(u64, OrderMutation)>, String> {
+        self.read_lines(WAL_FILE)?
+            .iter()
+            .map(|line| {
+                let entry: WalEntry = serde_json::from_str(line).map_err(|e| parse_error(WAL_FILE, e))?;
+                Ok((entry.sequence, entry.mutation))
+            })
+            .collect()
+    }
+}
+
+/***************************************/
+/*      Local data structures          */
+/***************************************/
+#[derive(Deserialize, Serialize)]
+pub(crate) struct CabOrders {
+    cab_calls: Vec<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct UptimeFile {
+    restarts: u64,
+    watchdog_resets: u64,
+    seconds: u64,
+    running: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct WalEntry {
+    sequence: u64,
+    #[serde(flatten)]
+    mutation: OrderMutation,
+}
+
+/***************************************/
+/*           Local functions           */
+/***************************************/
+fn wal_line((sequence, mutation): &(u64, OrderMutation)) -> String {
+    serde_json::to_string(&WalEntry { sequence: *sequence, mutation: mutation.clone() }).unwrap()
+}
+
+fn history_file(number: u64) -> String {
+    format!("{}/snapshot-{:06}.json", SNAPSHOT_DIRECTORY, number)
+}
+
+fn rotated_event_log(number: usize) -> String {
+    format!("events.{}.jsonl", number)
+}
+
+// The time an event of the log was seen at
+fn event_time(event: &str) -> Option<DateTime<FixedOffset>> {
+    let event: Value = serde_json::from_str(event).ok()?;
+    DateTime::parse_from_rfc3339(event.get("timestamp")?.as_str()?).ok()
+}
+
+fn parse_error(file: &str, error: impl std::fmt::Display) -> String {
+    format!("Failed to parse {}: {}", file, error)
+}
@@ -0,0 +1,465 @@
+/*
+ * Unit tests for the storage and the recorder
+ *
+ * The unit tests follows the Arrange, Act, Assert pattern.
+ * The files backend is tested in a temporary directory, and the sqlite backend in memory.
+ *
+ * Tests:
+ *  - test_storage_files
+ *  - test_storage_sqlite
+ *  - test_storage_recorder_events
+ *  - test_storage_write_ahead_log
+ *  - test_storage_snapshot_retention
+ *  - test_storage_checkpoint
+ *  - test_storage_checksums
+ *  - test_storage_log_rotation
+ *  - test_storage_sqlite_log_retention
+ *  - test_storage_instance_lock
+ *  - test_storage_format_migration
+ *  - test_storage_sqlite_schema_migration
+ *
+ */
+
+/***************************************/
+/*             Unit tests              */
+/***************************************/
+#[cfg(test)]
+mod storage_tests {
+    use serde_json::json;
+    use crate::elevator::statistics::Statistics;
+    use crate::shared::{NetworkStatistics, Snapshot, Uptime};
+    use crate::storage::recorder::testing::test_events;
+    use crate::storage::{recover_orders, restore_checkpoint, take_checkpoint, FileStorage, InstanceLock, LogRetention, OrderMutation, Record, Storage, WriteAheadLog};
+    use driver_rust::elevio::elev::{CAB, HALL_UP};
+    use std::sync::Arc;
+    use crate::{ElevatorData, ElevatorState};
+
+    fn snapshot() -> Snapshot {
+        let mut elevator_data = ElevatorData::new(4);
+        elevator_data.states.insert("a".to_string(), ElevatorState::new(4));
+        elevator_data.states.insert("b".to_string(), ElevatorState::new(4));
+        Snapshot {
+            id: "a".to_string(),
+            peers: vec!["a".to_string(), "b".to_string()],
+            elevator_data,
+            confirmed_hall_requests: vec![vec![false; 2]; 4],
+            network: NetworkStatistics::default(),
+            wal_sequence: 0,
+            reassignments: 0,
+            uptime: Uptime::default(),
+        }
+    }
+
+    // Saves twice, and returns what is loaded before and after
+    fn round_trip(storage: &dyn Storage) -> [String; 2] {
+        let load = || {
+            format!(
+                "{:?} {:?} {:?} {:?} {:?}",
+                storage.load_cab_orders().unwrap(),
+                storage.load_statistics().unwrap(),
+                storage.load_uptime().unwrap(),
+                storage.load_snapshot().unwrap().map(|snapshot| snapshot.peers),
+                storage.load_events().unwrap()
+            )
+        };
+        let empty = load();
+
+        let statistics = Statistics { distance_travelled: 12, door_cycles: 3, orders_served: 2 };
+        let mut later = snapshot();
+        later.peers.pop();
+        storage
+            .save(&[
+                Record::CabOrders(vec![false, true, false, false]),
+                Record::Statistics(Statistics::default()),
+                Record::Uptime(Uptime::default(), true),
+                Record::Snapshot(Box::new(snapshot())),
+                Record::Event(json!({ "event": "peerLost", "elevator": "c" })),
+            ])
+            .unwrap();
+        storage
+            .save(&[
+                Record::CabOrders(vec![true, false, false, true]),
+                Record::Statistics(statistics),
+                Record::Uptime(Uptime { restarts: 2, watchdog_resets: 1, seconds: 90 }, false),
+                Record::Snapshot(Box::new(later)),
+                Record::Event(json!({ "event": "error", "elevator": "a", "floor": 1 })),
+            ])
+            .unwrap();
+
+        [empty, load()]
+    }
+
+    // Saves snapshots with one to four peers, and returns the peers of the snapshots in the history
+    fn history(storage: &dyn Storage) -> Vec<usize> {
+        for peers in 1..=4 {
+            let mut snapshot = snapshot();
+            snapshot.peers = vec!["a".to_string(); peers];
+            storage.save(&[Record::Snapshot(Box::new(snapshot))]).unwrap();
+        }
+        storage.load_snapshots().unwrap().iter().map(|snapshot| snapshot.peers.len()).collect()
+    }
+
+    // Saves an event from two hours ago and then `count` events from now, one by one, and returns the numbers of the
+    // events loaded. The old event is numbered 0.
+    fn log(storage: &dyn Storage, count: u64) -> Vec<u64> {
+        let old = (chrono::Local::now() - chrono::Duration::hours(2)).to_rfc3339();
+        storage.save(&[Record::Event(json!({ "timestamp": old, "event": "peerLost", "number": 0 }))]).unwrap();
+        for number in 1..=count {
+            let now = chrono::Local::now().to_rfc3339();
+            storage.save(&[Record::Event(json!({ "timestamp": now, "event": "peerLost", "number": number }))]).unwrap();
+        }
+        storage.load_events().unwrap().iter().map(|event| event["number"].as_u64().unwrap()).collect()
+    }
+
+    const SAVED: &str = concat!(
+        r#"Some([true, false, false, true]) "#,
+        r#"Some(Statistics { distance_travelled: 12, door_cycles: 3, orders_served: 2 }) "#,
+        r#"Some((Uptime { restarts: 2, watchdog_resets: 1, seconds: 90 }, false)) "#,
+        r#"Some(["a"]) "#,
+        r#"[Object {"elevator": String("c"), "event": String("peerLost")}, "#,
+        r#"Object {"elevator": String("a"), "event": String("error"), "floor": Number(1)}]"#
+    );
+
+    #[test]
+    fn test_storage_files() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("storage_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage = FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default());
+
+        // Act
+        let [empty, saved] = round_trip(&storage);
+        let reopened = FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default()).load_cab_orders();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        assert_eq!(empty, "None None None None []");
+        // The latest records replace those before them, and the events are appended
+        assert_eq!(saved, SAVED);
+        assert_eq!(reopened, Ok(Some(vec![true, false, false, true])));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_storage_sqlite() {
+        // Arrange
+        let storage = crate::storage::SqliteStorage::open(":memory:", 0, LogRetention::default()).unwrap();
+        let retained = history(&crate::storage::SqliteStorage::open(":memory:", 2, LogRetention::default()).unwrap());
+
+        // Act
+        let [empty, saved] = round_trip(&storage);
+        let whole = storage.save(&[Record::CabOrders(vec![false; 4]), Record::Event(json!({}))]);
+        storage.test_execute("DROP TABLE events");
+        let torn = storage.save(&[Record::CabOrders(vec![true; 4]), Record::Event(json!({}))]);
+
+        // Assert
+        assert_eq!(empty, "None None None None []");
+        assert_eq!(saved, SAVED);
+        assert_eq!(retained, vec![3, 4]);
+        assert!(whole.is_ok());
+        // A save failing part way leaves none of its records
+        assert!(torn.is_err());
+        assert_eq!(storage.load_cab_orders(), Ok(Some(vec![false; 4])));
+    }
+
+    #[test]
+    fn test_storage_recorder_events() {
+        // Arrange
+        let previous = snapshot();
+        let mut next = snapshot();
+        next.elevator_data.hall_requests[2][0] = true;
+        next.elevator_data.states.get_mut("a").unwrap().floor = 3;
+        next.peers.pop();
+
+        // Act
+        let first = test_events("a", None, &next, "t0");
+        let events = test_events("a", Some(&previous), &next, "t1");
+
+        // Assert
+        // The state of the local elevator is left to the snapshot
+        assert!(first.is_empty());
+        assert_eq!(
+            events,
+            vec![
+                json!({ "timestamp": "t1", "event": "callPlaced", "call": "up", "floor": 2 }),
+                json!({ "timestamp": "t1", "event": "peerLost", "elevator": "b" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_storage_write_ahead_log() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("wal_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage = Arc::new(FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default()));
+        let mut checkpoint = snapshot();
+        checkpoint.elevator_data.hall_requests[1][HALL_UP as usize] = true;
+        checkpoint.elevator_data.states.get_mut("a").unwrap().cab_requests[2] = true;
+        checkpoint.wal_sequence = 2;
+
+        // Act
+        let mut wal = WriteAheadLog::new(storage.clone(), recover_orders(storage.as_ref(), "a", 4).unwrap());
+        wal.append(OrderMutation::Add { floor: 1, call: HALL_UP });
+        wal.append(OrderMutation::Add { floor: 2, call: CAB });
+        storage.save(&[Record::Snapshot(Box::new(checkpoint))]).unwrap();
+        wal.append(OrderMutation::Add { floor: 0, call: HALL_UP });
+        wal.append(OrderMutation::Assign { hall_requests: vec![vec![true, false]; 4] });
+        wal.append(OrderMutation::Complete { floor: 2, call: CAB });
+        // A peer cleared the call at floor 0 and placed one at floor 3
+        wal.log_hall_requests(&[vec![false; 2], vec![true, false], vec![false; 2], vec![false, true]]);
+        let logged = storage.load_wal().unwrap().iter().map(|(sequence, _)| *sequence).collect::<Vec<u64>>();
+        let recovered = recover_orders(storage.as_ref(), "a", 4).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // The snapshot drops the mutations it includes
+        assert_eq!(logged, vec![3, 4, 5, 6, 7]);
+        // The orders of the snapshot, with the mutations after it replayed
+        assert_eq!(recovered.hall_requests, vec![vec![false; 2], vec![true, false], vec![false; 2], vec![false, true]]);
+        assert_eq!(recovered.cab_requests, vec![false; 4]);
+        assert_eq!((recovered.sequence, recovered.replayed), (7, 5));
+    }
+
+    #[test]
+    fn test_storage_snapshot_retention() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("snapshot_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage = FileStorage::new(directory.to_str().unwrap(), 2, LogRetention::default());
+
+        // Act
+        let retained = history(&storage);
+        let latest = storage.load_snapshot().unwrap().map(|snapshot| snapshot.peers.len());
+        let mut files = std::fs::read_dir(directory.join("snapshots"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<String>>();
+        files.sort();
+        let reopened = FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default()).load_snapshots();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // The last two snapshots are kept, and no temporary file is left behind
+        assert_eq!(retained, vec![3, 4]);
+        assert_eq!(latest, Some(4));
+        assert_eq!(files, vec!["snapshot-000003.json", "snapshot-000004.json"]);
+        assert_eq!(reopened.map(|snapshots| snapshots.len()), Ok(2));
+        assert!(!directory.join("snapshot.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_storage_checkpoint() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("checkpoint_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage = Arc::new(FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default()));
+        let statistics = Statistics { distance_travelled: 12, door_cycles: 3, orders_served: 2 };
+        storage
+            .save(&[
+                Record::CabOrders(vec![false, true, false, false]),
+                Record::Statistics(statistics.clone()),
+                Record::Snapshot(Box::new(snapshot())),
+            ])
+            .unwrap();
+        let mut wal = WriteAheadLog::new(storage.clone(), recover_orders(storage.as_ref(), "a", 4).unwrap());
+        wal.append(OrderMutation::Add { floor: 2, call: HALL_UP });
+
+        // Act
+        let checkpoint = take_checkpoint(storage.as_ref(), 4).unwrap();
+        // The experiment
+        wal.append(OrderMutation::Add { floor: 3, call: CAB });
+        storage.save(&[Record::CabOrders(vec![true; 4]), Record::Statistics(Statistics::default())]).unwrap();
+        let mut newer = checkpoint.clone();
+        newer.version += 1;
+        let refused = [restore_checkpoint(storage.as_ref(), &newer, 4), restore_checkpoint(storage.as_ref(), &checkpoint, 5)];
+        let restored = restore_checkpoint(storage.as_ref(), &checkpoint, 4);
+        let recovered = recover_orders(storage.as_ref(), "a", 4).unwrap();
+        let reloaded = (storage.load_cab_orders().unwrap(), storage.load_statistics().unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // The checkpoint holds the orders logged since the snapshot
+        assert!(checkpoint.snapshot.elevator_data.hall_requests[2][HALL_UP as usize]);
+        assert_eq!(checkpoint.cab_orders, vec![false, true, false, false]);
+        assert_eq!(checkpoint.statistics, statistics);
+        // A checkpoint of another version or building is refused
+        assert!(refused.iter().all(|result| result.is_err()));
+        assert_eq!(restored, Ok(()));
+        // The orders placed after the checkpoint are not replayed
+        assert_eq!(recovered.hall_requests, checkpoint.snapshot.elevator_data.hall_requests);
+        assert_eq!((recovered.cab_requests, recovered.replayed), (vec![false; 4], 0));
+        assert_eq!(reloaded, (Some(vec![false, true, false, false]), Some(statistics)));
+    }
+
+    #[test]
+    fn test_storage_checksums() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("checksum_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage = FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default());
+        storage
+            .save(&[
+                Record::CabOrders(vec![false, true, false, false]),
+                Record::Statistics(Statistics::default()),
+                Record::Snapshot(Box::new(snapshot())),
+                Record::Mutation(1, OrderMutation::Add { floor: 1, call: HALL_UP }),
+                Record::Mutation(2, OrderMutation::Add { floor: 2, call: HALL_UP }),
+                Record::Mutation(3, OrderMutation::Add { floor: 3, call: CAB }),
+            ])
+            .unwrap();
+        let intact = (storage.load_cab_orders(), storage.load_wal().map(|wal| wal.len()));
+        let corrupt = |file: &str, corrupt: &dyn Fn(String) -> String| {
+            let path = directory.join(file);
+            std::fs::write(&path, corrupt(std::fs::read_to_string(&path).unwrap())).unwrap();
+        };
+
+        // Act
+        corrupt("cab_orders.toml", &|contents| contents.replacen("true", "fals", 1));
+        corrupt("snapshot.json", &|contents| contents[..contents.len() / 2].to_string());
+        corrupt("wal.jsonl", &|contents| contents.replacen("\"floor\":2", "\"floor\":0", 1));
+        let loaded = (storage.load_cab_orders(), storage.load_statistics().map(|statistics| statistics.is_some()));
+        let snapshot = storage.load_snapshot();
+        let wal = storage.load_wal().unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        assert_eq!(intact, (Ok(Some(vec![false, true, false, false])), Ok(3)));
+        // Corrupt files are missing, and the intact files are loaded
+        assert_eq!(loaded, (Ok(None), Ok(true)));
+        assert_eq!(snapshot, Ok(None));
+        // A corrupt line of a log is left out
+        assert_eq!(wal.iter().map(|(sequence, _)| *sequence).collect::<Vec<u64>>(), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_storage_log_rotation() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("rotation_test_{}", std::process::id()));
+        let logs = ["by_size", "by_age", "pruned"].map(|log| directory.join(log));
+        for log in &logs {
+            std::fs::create_dir_all(log).unwrap();
+        }
+        let storage = |log: usize, max_size: u64, max_age: u64| {
+            FileStorage::new(logs[log].to_str().unwrap(), 0, LogRetention { max_size, max_age, rotations: 2 })
+        };
+
+        // Act
+        let by_size = log(&storage(0, 200, 0), 9);
+        let by_age = log(&storage(1, 0, 3600), 2);
+        let pruned = log(&storage(2, 1, 3600), 2);
+        let mut files = std::fs::read_dir(&logs[0])
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<String>>();
+        files.sort();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // Two rotated logs are kept besides the live one, and the oldest events are dropped with the rotations
+        assert_eq!(files, vec!["events.1.jsonl", "events.2.jsonl", "events.jsonl"]);
+        assert!(by_size.len() < 10 && by_size.len() > 3, "{:?} loaded", by_size);
+        assert_eq!(by_size, (10 - by_size.len() as u64..10).collect::<Vec<u64>>());
+        // The log starting with an old event is rotated, and the rotated log ending with it is removed
+        assert_eq!(by_age, vec![0, 1, 2]);
+        assert_eq!(pruned, vec![1, 2]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_storage_sqlite_log_retention() {
+        // Arrange
+        let retention = |max_size: u64, max_age: u64| LogRetention { max_size, max_age, rotations: 1 };
+        let by_size = crate::storage::SqliteStorage::open(":memory:", 0, retention(150, 0)).unwrap();
+        let by_age = crate::storage::SqliteStorage::open(":memory:", 0, retention(0, 3600)).unwrap();
+
+        // Act
+        let kept = [log(&by_size, 9), log(&by_age, 2)];
+
+        // Assert
+        // The events fitting in the size of a live and a rotated log are kept, and the expired are deleted
+        assert_eq!(kept, [vec![7, 8, 9], vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_storage_instance_lock() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("lock_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let storage_config = crate::config::StorageConfig { directory: directory.to_str().unwrap().to_string(), ..Default::default() };
+        let first = InstanceLock::acquire(&storage_config, "10.0.0.1:19735");
+
+        // Act
+        let second = InstanceLock::acquire(&storage_config, "10.0.0.1:19735").err();
+        let other = InstanceLock::acquire(&storage_config, "10.0.0.2:19735");
+        drop(first);
+        let restarted = InstanceLock::acquire(&storage_config, "10.0.0.1:19735");
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // A second instance of the elevator is refused with the process holding the lock, and not one of another
+        assert!(second.is_some_and(|e| e.contains(&format!("process {}", std::process::id()))));
+        assert!(other.is_ok());
+        // The lock is released with the instance
+        assert!(restarted.is_ok());
+    }
+
+    #[test]
+    fn test_storage_format_migration() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("format_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let write = |file: &str, contents: &str| std::fs::write(directory.join(file), contents).unwrap();
+        // Format 1, as left on the lab machines, with a torn last line of the event log
+        write("cab_orders.toml", "cab_calls = [false, true, false, true]\n");
+        write("events.jsonl", "{\"event\":\"peerLost\",\"elevator\":\"b\"}\n{\"event\":\"peer");
+        // Format 2
+        write("snapshot.json", &crate::storage::checksum::seal(&serde_json::to_string(&snapshot()).unwrap()));
+        let wal_line = r#"{"sequence":1,"mutation":"add","floor":2,"call":0}"#;
+        write("wal.jsonl", &(crate::storage::checksum::seal_line(wal_line) + "\n"));
+        let storage = FileStorage::new(directory.to_str().unwrap(), 0, LogRetention::default());
+
+        // Act
+        let migrated = storage.migrate();
+        let loaded = (storage.load_cab_orders(), storage.load_snapshot().map(|snapshot| snapshot.is_some()));
+        let logs = (storage.load_events().unwrap().len(), storage.load_wal().unwrap().len());
+        let headers = ["cab_orders.toml", "snapshot.json", "events.jsonl", "wal.jsonl"]
+            .map(|file| std::fs::read_to_string(directory.join(file)).unwrap().starts_with("# format 3\n"));
+        write("statistics.toml", &crate::storage::checksum::seal("# format 9\ndistance_travelled = 1\n"));
+        let newer = storage.load_statistics();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Assert
+        // The files of the older formats are loaded, and written back in the current format
+        assert_eq!(migrated, Ok(()));
+        assert_eq!(loaded, (Ok(Some(vec![false, true, false, true])), Ok(true)));
+        assert_eq!(logs, (1, 1));
+        assert_eq!(headers, [true; 4]);
+        // A file of a newer format is refused
+        assert!(newer.is_err_and(|e| e.contains("format 9")));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_storage_sqlite_schema_migration() {
+        // Arrange
+        let database = std::env::temp_dir().join(format!("schema_test_{}.db", std::process::id()));
+        let database = database.to_str().unwrap();
+        let open = || crate::storage::SqliteStorage::open(database, 2, LogRetention::default());
+        // A database from before the schema was versioned, without the history
+        let storage = open().unwrap();
+        storage.save(&[Record::CabOrders(vec![true; 4])]).unwrap();
+        storage.test_execute("DROP TABLE snapshots; PRAGMA user_version = 0");
+        drop(storage);
+
+        // Act
+        let migrated = open().map(|storage| (storage.load_cab_orders(), history(&storage)));
+        open().unwrap().test_execute("PRAGMA user_version = 9");
+        let newer = open().err();
+        std::fs::remove_file(database).unwrap();
+
+        // Assert
+        assert_eq!(migrated, Ok((Ok(Some(vec![true; 4])), vec![3, 4])));
+        assert!(newer.is_some_and(|e| e.contains("version 9")));
+    }
+}
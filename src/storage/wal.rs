@@ -0,0 +1,171 @@
+/**
+ * Write-ahead log of the order mutations of the coordinator.
+ *
+ * Every call placed, assigned, completed or cleared is appended to the log before it takes effect, that is before
+ * the call is lit, sent to the FSM or transmitted. Hall calls learned from peers or committed by the consensus are
+ * logged before they are lit. A call that cannot be logged is refused, so a lit call survives a crash of the
+ * process.
+ *
+ * Mutations are numbered in the order they are logged. A snapshot of the coordinator carries the number of the
+ * last mutation it includes, and saving it drops the mutations up to it. At start-up the orders of the last
+ * snapshot are taken, and the mutations logged after it replayed on top. Assignments are logged for the record,
+ * but not replayed, as the recovered calls are assigned afresh.
+ *
+ * # Fields
+ * - `storage`:                 The storage the log is kept in.
+ * - `logged`:                  The orders as logged, so changes from peers are logged once, and the last number.
+ */
+
+/***************************************/
+/*             Libraries               */
+/***************************************/
+use driver_rust::elevio::elev::{CAB, HALL_DOWN, HALL_UP};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/***************************************/
+/*           Local modules             */
+/***************************************/
+use crate::storage::{Record, Storage};
+
+/***************************************/
+/*       Public data structures        */
+/***************************************/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "mutation", rename_all = "camelCase")]
+pub enum OrderMutation {
+    // A hall call, or a cab call of the local elevator, was placed
+    Add { floor: u8, call: u8 },
+    // The hall calls assigned to the local elevator
+    Assign { hall_requests: Vec<Vec<bool>> },
+    // A call was served
+    Complete { floor: u8, call: u8 },
+    // A call was cancelled, or cleared by a peer
+    Clear { floor: u8, call: u8 },
+    // All hall calls and the local cab calls were cancelled
+    ClearAll,
+}
+
+// The orders of the last snapshot, with the mutations logged after it replayed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredOrders {
+    pub hall_requests: Vec<Vec<bool>>,
+    pub cab_requests: Vec<bool>,
+    pub sequence: u64,
+    pub replayed: usize,
+}
+
+impl RecoveredOrders {
+    pub fn new(n_floors: u8) -> RecoveredOrders {
+        RecoveredOrders {
+            hall_requests: vec![vec![false; 2]; n_floors as usize],
+            cab_requests: vec![false; n_floors as usize],
+            sequence: 0,
+            replayed: 0,
+        }
+    }
+
+    fn apply(&mut self, mutation: &OrderMutation) {
+        let (floor, call, request) = match *mutation {
+            OrderMutation::Add { floor, call } => (floor, call, true),
+            OrderMutation::Complete { floor, call } | OrderMutation::Clear { floor, call } => (floor, call, false),
+            OrderMutation::Assign { .. } => return,
+            OrderMutation::ClearAll => {
+                self.hall_requests.iter_mut().for_each(|requests| requests.fill(false));
+                self.cab_requests.fill(false);
+                return;
+            }
+        };
+
+        // Calls outside the building are left out, should the number of floors have changed
+        match call {
+            CAB => {
+                if let Some(cab_request) = self.cab_requests.get_mut(floor as usize) {
+                    *cab_request = request;
+                }
+            }
+            HALL_UP | HALL_DOWN => {
+                if let Some(requests) = self.hall_requests.get_mut(floor as usize) {
+                    requests[call as usize] = request;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/***************************************/
+/*             Public API              */
+/***************************************/
+pub struct WriteAheadLog {
+    storage: Arc<dyn Storage>,
+    logged: RecoveredOrders,
+}
+
+impl WriteAheadLog {
+    // Logs on from the recovered orders
+    pub fn new(storage: Arc<dyn Storage>, recovered_orders: RecoveredOrders) -> WriteAheadLog {
+        WriteAheadLog { storage, logged: recovered_orders }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.logged.sequence
+    }
+
+    // Appends a mutation to the log. Returns false if it could not be logged.
+    pub fn append(&mut self, mutation: OrderMutation) -> bool {
+        let sequence = self.logged.sequence + 1;
+        if let Err(e) = self.storage.save(&[Record::Mutation(sequence, mutation.clone())]) {
+            error!("Failed to log {:?}: {}", mutation, e);
+            return false;
+        }
+        self.logged.apply(&mutation);
+        self.logged.sequence = sequence;
+        true
+    }
+
+    // Logs the hall calls placed or cleared since they were last logged
+    pub fn log_hall_requests(&mut self, hall_requests: &[Vec<bool>]) {
+        self.logged.hall_requests.resize(hall_requests.len(), vec![false; 2]);
+        for (floor, requests) in hall_requests.iter().enumerate() {
+            for call in [HALL_UP, HALL_DOWN] {
+                let request = requests[call as usize];
+                if request == self.logged.hall_requests[floor][call as usize] {
+                    continue;
+                }
+                let floor = floor as u8;
+                self.append(match request {
+                    true => OrderMutation::Add { floor, call },
+                    false => OrderMutation::Clear { floor, call },
+                });
+            }
+        }
+    }
+}
+
+// Takes the orders of the last snapshot, and replays the mutations logged after it
+pub fn recover_orders(storage: &dyn Storage, local_id: &str, n_floors: u8) -> Result<RecoveredOrders, String> {
+    let mut recovered_orders = RecoveredOrders::new(n_floors);
+    if let Some(snapshot) = storage.load_snapshot()? {
+        recovered_orders.sequence = snapshot.wal_sequence;
+        for (floor, requests) in snapshot.elevator_data.hall_requests.iter().enumerate().take(n_floors as usize) {
+            recovered_orders.hall_requests[floor] = requests.clone();
+        }
+        if let Some(state) = snapshot.elevator_data.states.get(local_id) {
+            for (floor, cab_request) in state.cab_requests.iter().enumerate().take(n_floors as usize) {
+                recovered_orders.cab_requests[floor] = *cab_request;
+            }
+        }
+    }
+
+    for (sequence, mutation) in storage.load_wal()? {
+        if sequence <= recovered_orders.sequence {
+            continue;
+        }
+        recovered_orders.apply(&mutation);
+        recovered_orders.sequence = sequence;
+        recovered_orders.replayed += 1;
+    }
+    Ok(recovered_orders)
+}